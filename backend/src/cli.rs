@@ -0,0 +1,263 @@
+//! Maintenance subcommands, run instead of the server and worker: `backend <subcommand> ...`
+
+use crate::{
+    database::{Database, migrations::run_pending_migrations},
+    queries::{
+        check_results::{GraphGranularity, recompute_cached_check_results},
+        users::{create_user, get_user_by_username},
+    },
+};
+use anyhow::{Context, Result, anyhow, bail};
+use chrono::{DateTime, Utc};
+use uuid::Uuid;
+
+pub const USAGE: &str = "\
+Usage: backend [SUBCOMMAND]
+
+Without a subcommand, runs the server and the worker.
+
+Subcommands:
+  migrate [--baseline]
+      Applies the pending migrations. With --baseline, only records them as applied,
+      for keyspaces migrated by hand.
+  create-user <username> [password]
+      Creates a user. The password is read from stdin when omitted.
+  recompute-metrics <check_id> <from> <to> <hourly|daily>
+      Recomputes the cached aggregates of a check in [from, to), as RFC 3339 timestamps.";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Command {
+    Migrate {
+        baseline: bool,
+    },
+    CreateUser {
+        username: String,
+        /// Read from stdin when `None`, to keep it out of the shell history
+        password: Option<String>,
+    },
+    RecomputeMetrics {
+        check_id: Uuid,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+        granularity: GraphGranularity,
+    },
+}
+
+fn parse_timestamp(value: &str) -> Result<DateTime<Utc>> {
+    Ok(DateTime::parse_from_rfc3339(value)
+        .with_context(|| format!("invalid timestamp '{value}'"))?
+        .to_utc())
+}
+
+fn parse_granularity(value: &str) -> Result<GraphGranularity> {
+    match value.to_ascii_lowercase().as_str() {
+        "hourly" => Ok(GraphGranularity::Hourly),
+        "daily" => Ok(GraphGranularity::Daily),
+        _ => bail!("invalid granularity '{value}', expected hourly or daily"),
+    }
+}
+
+/// Parses the arguments following the program name.
+/// Returns `None` without a subcommand, when the server should run.
+pub fn parse_args<I, S>(args: I) -> Result<Option<Command>>
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<str>,
+{
+    let args: Vec<S> = args.into_iter().collect();
+    let args: Vec<&str> = args.iter().map(AsRef::as_ref).collect();
+
+    let command = match args.as_slice() {
+        [] => return Ok(None),
+        ["migrate"] => Command::Migrate { baseline: false },
+        ["migrate", "--baseline"] => Command::Migrate { baseline: true },
+        ["create-user", username] => Command::CreateUser {
+            username: username.to_string(),
+            password: None,
+        },
+        ["create-user", username, password] => Command::CreateUser {
+            username: username.to_string(),
+            password: Some(password.to_string()),
+        },
+        ["recompute-metrics", check_id, from, to, granularity] => Command::RecomputeMetrics {
+            check_id: check_id
+                .parse()
+                .with_context(|| format!("invalid check_id '{check_id}'"))?,
+            from: parse_timestamp(from)?,
+            to: parse_timestamp(to)?,
+            granularity: parse_granularity(granularity)?,
+        },
+        [subcommand, ..] => bail!("invalid arguments for '{subcommand}'"),
+    };
+
+    Ok(Some(command))
+}
+
+/// Runs a subcommand, reporting its outcome on stdout
+pub async fn run(command: Command, db: &Database) -> Result<()> {
+    match command {
+        Command::Migrate { baseline } => {
+            let migrations = run_pending_migrations(db, baseline).await?;
+            let action = if baseline { "Recorded" } else { "Applied" };
+            println!("{action} {} migrations", migrations.len());
+            for migration in migrations {
+                println!("  {migration}");
+            }
+        }
+        Command::CreateUser { username, password } => {
+            let password = match password {
+                Some(password) => password,
+                None => {
+                    let mut password = String::new();
+                    std::io::stdin().read_line(&mut password)?;
+                    password.trim_end_matches(['\r', '\n']).to_string()
+                }
+            };
+            if password.is_empty() {
+                bail!("the password must not be empty");
+            }
+            if get_user_by_username(db, &username).await?.is_some() {
+                return Err(anyhow!("user '{username}' already exists"));
+            }
+
+            let user_id = Uuid::new_v4();
+            create_user(db, user_id, &username, &password).await?;
+            println!("Created user '{username}' with id {user_id}");
+        }
+        Command::RecomputeMetrics {
+            check_id,
+            from,
+            to,
+            granularity,
+        } => {
+            let points =
+                recompute_cached_check_results(db, check_id, from, to, granularity).await?;
+            println!(
+                "Recomputed {} {granularity:?} points of check {check_id}",
+                points.len()
+            );
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        database::testing::create_test_database,
+        queries::{
+            check_results::RawResultsNotStored,
+            checks::{CheckData, create_check},
+            users::login_user,
+        },
+        regions::Region,
+    };
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_parse_args() {
+        assert_eq!(parse_args(Vec::<String>::new()).unwrap(), None);
+
+        assert_eq!(
+            parse_args(["migrate"]).unwrap(),
+            Some(Command::Migrate { baseline: false })
+        );
+        assert_eq!(
+            parse_args(["migrate", "--baseline"]).unwrap(),
+            Some(Command::Migrate { baseline: true })
+        );
+
+        assert_eq!(
+            parse_args(["create-user", "admin"]).unwrap(),
+            Some(Command::CreateUser {
+                username: "admin".to_string(),
+                password: None,
+            })
+        );
+        assert_eq!(
+            parse_args(["create-user", "admin", "hunter2"]).unwrap(),
+            Some(Command::CreateUser {
+                username: "admin".to_string(),
+                password: Some("hunter2".to_string()),
+            })
+        );
+
+        let check_id = Uuid::new_v4();
+        assert_eq!(
+            parse_args([
+                "recompute-metrics",
+                &check_id.to_string(),
+                "2026-10-01T00:00:00Z",
+                "2026-10-02T00:00:00+00:00",
+                "hourly",
+            ])
+            .unwrap(),
+            Some(Command::RecomputeMetrics {
+                check_id,
+                from: Utc.with_ymd_and_hms(2026, 10, 1, 0, 0, 0).unwrap(),
+                to: Utc.with_ymd_and_hms(2026, 10, 2, 0, 0, 0).unwrap(),
+                granularity: GraphGranularity::Hourly,
+            })
+        );
+
+        assert!(parse_args(["serve"]).is_err());
+        assert!(parse_args(["migrate", "--force"]).is_err());
+        assert!(parse_args(["create-user"]).is_err());
+        assert!(parse_args(["recompute-metrics", "not-a-uuid", "x", "y", "daily"]).is_err());
+        assert!(
+            parse_args([
+                "recompute-metrics",
+                &check_id.to_string(),
+                "2026-10-01T00:00:00Z",
+                "2026-10-02T00:00:00Z",
+                "weekly",
+            ])
+            .is_err()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_run() -> Result<()> {
+        let (db, _keyspace) = create_test_database(None).await?;
+
+        run(Command::Migrate { baseline: true }, &db).await?;
+        run(Command::Migrate { baseline: false }, &db).await?;
+
+        let create = Command::CreateUser {
+            username: "operator".to_string(),
+            password: Some("password123".to_string()),
+        };
+        run(create.clone(), &db).await?;
+        assert!(matches!(
+            login_user(&db, "operator", "password123").await?,
+            crate::queries::users::LoginResult::Ok(_)
+        ));
+        // Usernames are unique
+        assert!(run(create, &db).await.is_err());
+
+        let recompute = |check_id| Command::RecomputeMetrics {
+            check_id,
+            from: Utc.with_ymd_and_hms(2026, 10, 1, 0, 0, 0).unwrap(),
+            to: Utc.with_ymd_and_hms(2026, 10, 2, 0, 0, 0).unwrap(),
+            granularity: GraphGranularity::Hourly,
+        };
+        run(recompute(Uuid::new_v4()), &db).await?;
+
+        // The aggregates of checks not storing raw results are their only record
+        let check = create_check(
+            &db,
+            vec![Region::Fsn1],
+            CheckData {
+                store_raw_results: false,
+                ..CheckData::example()
+            },
+        )
+        .await?;
+        let error = run(recompute(check.check_id), &db).await.unwrap_err();
+        assert!(error.downcast_ref::<RawResultsNotStored>().is_some());
+
+        Ok(())
+    }
+}
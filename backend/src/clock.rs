@@ -0,0 +1,89 @@
+use chrono::{DateTime, Utc};
+use std::{sync::Arc, time::Instant};
+
+/// Source of the current time, injected where behaviour depends on it so tests can control it.
+pub trait Clock: Send + Sync {
+    /// Wall-clock time, for timestamps that are stored or compared with stored ones
+    fn now(&self) -> DateTime<Utc>;
+
+    /// Monotonic time, for scheduling
+    fn instant(&self) -> Instant;
+}
+
+pub type SharedClock = Arc<dyn Clock>;
+
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> DateTime<Utc> {
+        Utc::now()
+    }
+
+    fn instant(&self) -> Instant {
+        Instant::now()
+    }
+}
+
+impl SystemClock {
+    pub fn shared() -> SharedClock {
+        Arc::new(SystemClock)
+    }
+}
+
+/// Clock that only moves when advanced explicitly.
+#[cfg(test)]
+pub struct MockClock {
+    start: DateTime<Utc>,
+    start_instant: Instant,
+    elapsed: std::sync::Mutex<std::time::Duration>,
+}
+
+#[cfg(test)]
+impl MockClock {
+    pub fn new(start: DateTime<Utc>) -> Arc<Self> {
+        Arc::new(Self {
+            start,
+            start_instant: Instant::now(),
+            elapsed: Default::default(),
+        })
+    }
+
+    pub fn advance(&self, duration: std::time::Duration) {
+        *self.elapsed.lock().unwrap() += duration;
+    }
+
+    fn elapsed(&self) -> std::time::Duration {
+        *self.elapsed.lock().unwrap()
+    }
+}
+
+#[cfg(test)]
+impl Clock for MockClock {
+    fn now(&self) -> DateTime<Utc> {
+        self.start + self.elapsed()
+    }
+
+    fn instant(&self) -> Instant {
+        self.start_instant + self.elapsed()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_mock_clock_advances_both_times() {
+        let start = DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+        let clock = MockClock::new(start);
+        let instant = clock.instant();
+
+        assert_eq!(clock.now(), start);
+        assert_eq!(clock.instant(), instant);
+
+        clock.advance(Duration::from_secs(90));
+        assert_eq!(clock.now(), start + Duration::from_secs(90));
+        assert_eq!(clock.instant(), instant + Duration::from_secs(90));
+    }
+}
@@ -5,8 +5,10 @@ use anyhow::bail;
 use rand::rng;
 use rand_distr::num_traits::Pow;
 use rand_distr::{Beta, Distribution, weighted::WeightedIndex};
+use serde::Serialize;
 use std::collections::BTreeSet;
 use std::fmt::Display;
+use utoipa::ToSchema;
 use uuid::Uuid;
 
 pub type NodePosition = u32;
@@ -89,6 +91,38 @@ pub fn choose_new_node_position(
     Ok(final_position)
 }
 
+/// Position a node last held, along with the process that held it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StoredPosition {
+    pub position: NodePosition,
+    pub process_id: Uuid,
+}
+
+/// Returns the stored position if it can be reclaimed, i.e. it's on the ring and no other
+/// alive node holds it.
+///
+/// The previous process of the same replica may still look alive right after a restart,
+/// so it doesn't count as a collision.
+pub fn reusable_position(
+    stored: Option<StoredPosition>,
+    state: &BTreeSet<Heartbeat>,
+    ring_size: NodePosition,
+) -> Option<NodePosition> {
+    let stored = stored?;
+
+    let taken = state
+        .iter()
+        .any(|node| node.position == stored.position && node.node_id != stored.process_id);
+
+    (stored.position < ring_size && !taken).then_some(stored.position)
+}
+
+/// Returns the range of the ring `node_id` is responsible for, from its position to the
+/// position of its `replication_factor`-th successor.
+///
+/// Nodes sharing a position, e.g. after joining simultaneously, are ordered by `node_id`.
+/// A node whose successors all share its position owns nothing and gets `None`, like a node
+/// missing from the state: `start == end` would otherwise mean the whole ring.
 pub fn calculate_node_range(
     node_id: Uuid,
     replication_factor: u32,
@@ -105,8 +139,8 @@ pub fn calculate_node_range(
 
     let our_position = nodes[our_idx].position;
 
-    if nodes.len() == 1 {
-        // We're the only node - we cover the entire ring
+    if nodes.len() == 1 || replication_factor as usize >= nodes.len() {
+        // Every node holds a replica of everything - we cover the entire ring
         return Some(RingRange {
             start: our_position,
             end: our_position,
@@ -114,8 +148,13 @@ pub fn calculate_node_range(
     }
 
     // Find the k-th successor (wrapping around)
-    let end_idx = (our_idx + replication_factor as usize) % nodes.len();
-    let end_position = nodes[end_idx].position;
+    let successor_idx = our_idx + replication_factor as usize;
+    let end_position = nodes[successor_idx % nodes.len()].position;
+
+    // Successors at the same position without wrapping around leave us a zero-width range
+    if successor_idx < nodes.len() && end_position == our_position {
+        return None;
+    }
 
     Some(RingRange {
         start: our_position,
@@ -123,13 +162,93 @@ pub fn calculate_node_range(
     })
 }
 
+/// How a region's ring is covered by its alive nodes, given the replication factor.
+#[derive(Debug, Clone, PartialEq, Serialize, ToSchema)]
+pub struct RingCoverage {
+    pub alive_nodes: usize,
+    /// Nodes needed for every bucket to be held by `replication_factor` of them
+    pub min_nodes: usize,
+    /// Nodes holding each bucket
+    pub replicas: usize,
+    /// Whether every bucket is held by `min_nodes` nodes
+    pub guaranteed: bool,
+    /// Whether each node holds the whole ring, because there are no more nodes than replicas
+    pub whole_ring: bool,
+    /// Buckets held by at least one alive node, between 0 and 1
+    pub owned_fraction: f64,
+    /// Buckets held by `replication_factor` alive nodes, between 0 and 1
+    pub replicated_fraction: f64,
+}
+
+/// Computes the coverage of `region`'s ring of `ring_size` buckets by the nodes of
+/// `current_state`, matching the ranges of [`calculate_node_range`].
+///
+/// Nodes sharing a position don't lower the replicas: the ones owning nothing are made up for
+/// by their predecessors owning more.
+pub fn calculate_ring_coverage(
+    replication_factor: u32,
+    current_state: &BTreeSet<Heartbeat>,
+    region: Region,
+    ring_size: NodePosition,
+) -> RingCoverage {
+    let alive_nodes = current_state.iter().filter(|h| h.region == region).count();
+    // A lone node covers the whole ring whatever the replication factor
+    let min_nodes = (replication_factor as usize).max(1);
+    let whole_ring =
+        alive_nodes > 0 && (alive_nodes == 1 || replication_factor as usize >= alive_nodes);
+    let replicas = if whole_ring {
+        alive_nodes
+    } else {
+        alive_nodes.min(replication_factor as usize)
+    };
+
+    // Counted on the ring, as the ranges may not split it evenly
+    let mut holders = vec![0usize; ring_size as usize];
+    for node in current_state.iter().filter(|h| h.region == region) {
+        if let Some(range) =
+            calculate_node_range(node.node_id, replication_factor, current_state, region)
+        {
+            for bucket in range.iter(ring_size) {
+                holders[bucket as usize] += 1;
+            }
+        }
+    }
+    let fraction = |min_holders: usize| match ring_size {
+        0 => 0.0,
+        _ => holders.iter().filter(|&&h| h >= min_holders).count() as f64 / ring_size as f64,
+    };
+
+    RingCoverage {
+        alive_nodes,
+        min_nodes,
+        replicas,
+        guaranteed: replicas >= min_nodes,
+        whole_ring,
+        owned_fraction: fraction(1),
+        replicated_fraction: fraction(min_nodes),
+    }
+}
+
 impl RingRange {
+    /// Iterates over the positions of the range on a ring of `ring_size` positions.
+    ///
+    /// `start == end` yields every position once, starting at `start`. Positions past the end of
+    /// the ring, e.g. held by a node since the ring shrunk, wrap around rather than never being
+    /// reached.
     pub fn iter(&self, ring_size: NodePosition) -> RingRangeIterator {
+        let range = match ring_size {
+            0 => *self,
+            _ => RingRange {
+                start: self.start % ring_size,
+                end: self.end % ring_size,
+            },
+        };
+
         RingRangeIterator {
-            range: *self,
+            range,
             ring_size,
-            current: self.start,
-            done: false,
+            current: range.start,
+            done: ring_size == 0,
         }
     }
 
@@ -183,8 +302,72 @@ impl Iterator for RingRangeIterator {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use proptest::prelude::*;
     use uuid::uuid;
 
+    /// A ring size with a start and end position on it
+    fn ring_range() -> impl Strategy<Value = (NodePosition, RingRange)> {
+        (1..500 as NodePosition).prop_flat_map(|ring_size| {
+            (Just(ring_size), 0..ring_size, 0..ring_size)
+                .prop_map(|(ring_size, start, end)| (ring_size, RingRange { start, end }))
+        })
+    }
+
+    /// Nodes on a small ring, so that positions often collide
+    fn colliding_nodes() -> impl Strategy<Value = (NodePosition, Vec<NodePosition>)> {
+        (1..20 as NodePosition)
+            .prop_flat_map(|ring_size| (Just(ring_size), prop::collection::vec(0..ring_size, 1..8)))
+    }
+
+    proptest! {
+        #[test]
+        fn prop_each_bucket_assigned_replication_factor_times(
+            (ring_size, positions) in colliding_nodes(),
+            replication_factor in 1..5u32,
+        ) {
+            let state: BTreeSet<_> = positions
+                .iter()
+                .enumerate()
+                .map(|(i, &position)| Heartbeat {
+                    node_id: Uuid::from_u128(i as u128),
+                    position,
+                    ..Heartbeat::example()
+                })
+                .collect();
+
+            let ranges: Vec<_> = state
+                .iter()
+                .filter_map(|node| {
+                    calculate_node_range(node.node_id, replication_factor, &state, Region::Fsn1)
+                })
+                .collect();
+
+            let expected =
+                calculate_ring_coverage(replication_factor, &state, Region::Fsn1, ring_size).replicas;
+            for bucket in 0..ring_size {
+                let owners = ranges.iter().filter(|range| range.contains(bucket)).count();
+                prop_assert_eq!(owners, expected, "bucket {} of {:?}", bucket, ranges);
+            }
+        }
+
+        #[test]
+        fn prop_iter_matches_contains((ring_size, range) in ring_range()) {
+            let iterated: BTreeSet<_> = range.iter(ring_size).collect();
+            let contained: BTreeSet<_> = (0..ring_size).filter(|p| range.contains(*p)).collect();
+            prop_assert_eq!(iterated, contained);
+        }
+
+        #[test]
+        fn prop_range_and_complement_partition_ring((ring_size, range) in ring_range()) {
+            prop_assume!(range.start != range.end);
+            let complement = RingRange { start: range.end, end: range.start };
+
+            for position in 0..ring_size {
+                prop_assert_ne!(range.contains(position), complement.contains(position));
+            }
+        }
+    }
+
     #[test]
     fn test_into_iter() {
         const RING_SIZE: NodePosition = 10;
@@ -205,10 +388,20 @@ mod tests {
         let result: Vec<NodePosition> = range.iter(RING_SIZE).collect();
         assert_eq!(result, vec![0, 1, 2, 3, 4, 5, 6, 7, 8, 9]);
 
-        // TODO: fix (currently loops forever, end >= size edge case)
-        // let range = RingRange { start: 0, end: 3 };
-        // let result: Vec<NodePosition> = range.iter(3).collect();
-        // assert_eq!(result, vec![0, 1, 2]);
+        let range = RingRange { start: 7, end: 7 };
+        let result: Vec<NodePosition> = range.iter(RING_SIZE).collect();
+        assert_eq!(result, vec![7, 8, 9, 0, 1, 2, 3, 4, 5, 6]);
+
+        // Positions past the end of the ring wrap around
+        let range = RingRange { start: 0, end: 3 };
+        let result: Vec<NodePosition> = range.iter(3).collect();
+        assert_eq!(result, vec![0, 1, 2]);
+
+        let range = RingRange { start: 12, end: 12 };
+        let result: Vec<NodePosition> = range.iter(RING_SIZE).collect();
+        assert_eq!(result.len(), RING_SIZE as usize);
+
+        assert_eq!(range.iter(0).count(), 0);
     }
 
     #[test]
@@ -239,6 +432,38 @@ mod tests {
         assert!(range.contains(999));
     }
 
+    #[test]
+    fn test_reusable_position() {
+        let previous_process = Uuid::new_v4();
+        let stored = Some(StoredPosition {
+            position: 5,
+            process_id: previous_process,
+        });
+        let node = |position, node_id| Heartbeat {
+            node_id,
+            position,
+            ..Heartbeat::example()
+        };
+
+        // Nothing stored
+        assert_eq!(reusable_position(None, &BTreeSet::new(), 10), None);
+
+        // Free slot is reused
+        let state = BTreeSet::from([node(2, Uuid::new_v4()), node(7, Uuid::new_v4())]);
+        assert_eq!(reusable_position(stored, &state, 10), Some(5));
+
+        // Still held by our previous process
+        let state = BTreeSet::from([node(5, previous_process)]);
+        assert_eq!(reusable_position(stored, &state, 10), Some(5));
+
+        // Taken by another node
+        let state = BTreeSet::from([node(5, Uuid::new_v4())]);
+        assert_eq!(reusable_position(stored, &state, 10), None);
+
+        // Ring shrunk
+        assert_eq!(reusable_position(stored, &BTreeSet::new(), 5), None);
+    }
+
     #[test]
     fn test_no_nodes_present() {
         let state = BTreeSet::new();
@@ -326,6 +551,167 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_colliding_positions() {
+        let first = uuid!("00000000-0000-0000-0000-000000000001");
+        let second = uuid!("00000000-0000-0000-0000-000000000002");
+        let third = uuid!("00000000-0000-0000-0000-000000000003");
+
+        let state = BTreeSet::from([
+            Heartbeat {
+                node_id: second,
+                position: 100,
+                ..Heartbeat::example()
+            },
+            Heartbeat {
+                node_id: first,
+                position: 100,
+                ..Heartbeat::example()
+            },
+            Heartbeat {
+                node_id: third,
+                position: 200,
+                ..Heartbeat::example()
+            },
+        ]);
+
+        // Ties are broken by node id: the first node's range is zero-width,
+        // instead of wrongly covering the whole ring
+        assert_eq!(calculate_node_range(first, 1, &state, Region::Fsn1), None);
+        assert_eq!(
+            calculate_node_range(second, 1, &state, Region::Fsn1),
+            Some(RingRange {
+                start: 100,
+                end: 200
+            })
+        );
+        assert_eq!(
+            calculate_node_range(third, 1, &state, Region::Fsn1),
+            Some(RingRange {
+                start: 200,
+                end: 100
+            })
+        );
+
+        // With two replicas the first node shares the second one's arc
+        assert_eq!(
+            calculate_node_range(first, 2, &state, Region::Fsn1),
+            Some(RingRange {
+                start: 100,
+                end: 200
+            })
+        );
+
+        // All at the same position: only the last node covers the ring
+        let state = BTreeSet::from([
+            Heartbeat {
+                node_id: first,
+                position: 100,
+                ..Heartbeat::example()
+            },
+            Heartbeat {
+                node_id: second,
+                position: 100,
+                ..Heartbeat::example()
+            },
+        ]);
+        assert_eq!(calculate_node_range(first, 1, &state, Region::Fsn1), None);
+        assert_eq!(
+            calculate_node_range(second, 1, &state, Region::Fsn1),
+            Some(RingRange {
+                start: 100,
+                end: 100
+            })
+        );
+    }
+
+    #[test]
+    fn test_ring_coverage() {
+        let nodes = |count: u128| -> BTreeSet<_> {
+            (0..count)
+                .map(|i| Heartbeat {
+                    node_id: Uuid::from_u128(i),
+                    position: i as NodePosition * 100,
+                    ..Heartbeat::example()
+                })
+                .collect()
+        };
+        let coverage = |replication_factor, count| {
+            let state = nodes(count);
+            let coverage = calculate_ring_coverage(replication_factor, &state, Region::Fsn1, 500);
+            (
+                coverage.alive_nodes,
+                coverage.min_nodes,
+                coverage.replicas,
+                coverage.guaranteed,
+                coverage.whole_ring,
+            )
+        };
+
+        // Enough nodes: each holds its share of the ring
+        assert_eq!(coverage(1, 3), (3, 1, 1, true, false));
+        assert_eq!(coverage(2, 3), (3, 2, 2, true, false));
+        assert_eq!(coverage(2, 5), (5, 2, 2, true, false));
+
+        // As many nodes as replicas: each holds the whole ring
+        assert_eq!(coverage(3, 3), (3, 3, 3, true, true));
+
+        // Fewer nodes than replicas degrade to the whole ring, short of replicas
+        assert_eq!(coverage(3, 2), (2, 3, 2, false, true));
+        assert_eq!(coverage(3, 1), (1, 3, 1, false, true));
+
+        // A lone node always covers the ring
+        assert_eq!(coverage(0, 1), (1, 1, 1, true, true));
+        assert_eq!(coverage(0, 2), (2, 1, 0, false, false));
+
+        assert_eq!(coverage(2, 0), (0, 2, 0, false, false));
+
+        // Only nodes of the region count
+        let mut state = nodes(2);
+        state.insert(Heartbeat {
+            node_id: Uuid::from_u128(10),
+            region: Region::Hel1,
+            ..Heartbeat::example()
+        });
+        assert_eq!(
+            calculate_ring_coverage(2, &state, Region::Fsn1, 500).alive_nodes,
+            2
+        );
+    }
+
+    #[test]
+    fn test_bucket_coverage() {
+        let nodes = |count: u128| -> BTreeSet<_> {
+            (0..count)
+                .map(|i| Heartbeat {
+                    node_id: Uuid::from_u128(i),
+                    position: i as NodePosition * 100,
+                    ..Heartbeat::example()
+                })
+                .collect()
+        };
+        let coverage = |replication_factor, count| {
+            let coverage =
+                calculate_ring_coverage(replication_factor, &nodes(count), Region::Fsn1, 500);
+            (coverage.owned_fraction, coverage.replicated_fraction)
+        };
+
+        // Well populated: every bucket has its replicas
+        assert_eq!(coverage(2, 5), (1.0, 1.0));
+        assert_eq!(coverage(3, 3), (1.0, 1.0));
+
+        // Missing nodes: still owned, but short of replicas
+        assert_eq!(coverage(3, 2), (1.0, 0.0));
+
+        // No node at all
+        assert_eq!(coverage(2, 0), (0.0, 0.0));
+        // Nor in the region
+        assert_eq!(
+            calculate_ring_coverage(2, &nodes(5), Region::Hel1, 500).owned_fraction,
+            0.0
+        );
+    }
+
     #[test]
     fn test_poll_replication_factor() {
         let mut state = BTreeSet::new();
@@ -1,11 +1,13 @@
-use crate::collab::assignment::NodePosition;
+use crate::collab::assignment::{NodePosition, StoredPosition};
 use crate::database::Database;
 use crate::database::preparer::CachedPreparedStatement;
-use crate::eager_env::{PORT, SELF_IP};
+use crate::eager_env::{DATABASE_CONCURRENT_READS, HEARTBEAT_JITTER_PERCENT, PORT, SELF_IP};
 use crate::regions::Region;
 use anyhow::{Result, bail};
 use chrono::{DateTime, Utc};
+use futures::{StreamExt, future::BoxFuture, stream};
 use log::{error, info};
+use serde::{Deserialize, Serialize};
 use std::cmp::Ordering;
 use std::collections::hash_map::Entry;
 use std::collections::{BTreeSet, HashMap};
@@ -14,10 +16,27 @@ use std::sync::Arc;
 use std::time::{Duration, Instant};
 use strum::IntoEnumIterator;
 use tokio::sync::{Mutex, watch};
+use utoipa::ToSchema;
 use uuid::Uuid;
 
 const HEARTBEAT_FRESHNESS_MULTIPLE: u32 = 2;
 
+/// Delay of the first heartbeat, spread over `jitter_percent` of the interval by process so that
+/// nodes started together don't write in sync.
+///
+/// Capped below the interval: later heartbeats keep the interval, so they stay fresh.
+fn heartbeat_offset(process_id: Uuid, interval: Duration, jitter_percent: u32) -> Duration {
+    let window = interval * jitter_percent.min(100) / 100;
+    let offset_millis = process_id.as_u128() % window.as_millis().max(1);
+
+    Duration::from_millis(offset_millis as u64)
+}
+
+/// Ticks every `interval`, the first time after `offset`
+fn heartbeat_ticker(offset: Duration, interval: Duration) -> tokio::time::Interval {
+    tokio::time::interval_at(tokio::time::Instant::now() + offset, interval)
+}
+
 /// Returns the bucket's number (UTC minute)
 fn get_time_bucket_minutes(timestamp: DateTime<Utc>) -> i64 {
     timestamp.timestamp() / 60
@@ -83,6 +102,26 @@ async fn insert_worker_metadata(
     Ok(())
 }
 
+static GET_STORED_POSITION_QUERY: CachedPreparedStatement = CachedPreparedStatement::new(
+    "
+    SELECT position,
+           process_id
+    FROM worker_positions
+    WHERE region = ?
+      AND replica_id = ?
+    ",
+);
+
+static STORE_POSITION_QUERY: CachedPreparedStatement = CachedPreparedStatement::new(
+    "
+    INSERT INTO worker_positions (region,
+                                  replica_id,
+                                  position,
+                                  process_id)
+    VALUES (?, ?, ?, ?)
+    ",
+);
+
 static GET_ALIVE_WORKERS_QUERY: CachedPreparedStatement = CachedPreparedStatement::new(
     "
     SELECT process_id,
@@ -187,14 +226,367 @@ async fn fetch_alive_workers_within_interval(
     Ok(alive_workers)
 }
 
+static GET_HEARTBEAT_HISTORY_QUERY: CachedPreparedStatement = CachedPreparedStatement::new(
+    "
+    SELECT timestamp,
+           position,
+           address,
+           region,
+           process_id
+    FROM workers_heartbeats
+    WHERE region IN ?
+      AND time_bucket_minutes = ?
+      AND timestamp >= ?
+      AND timestamp < ?
+    ",
+);
+
+/// Heartbeat written by a node, as stored
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct HeartbeatRecord {
+    pub timestamp: DateTime<Utc>,
+    pub position: i32,
+    pub region: Region,
+    pub address: Option<String>,
+}
+
+/// Returns up to `limit` heartbeats of `process_id` in `[from, to)`, oldest first, along with
+/// whether more remain.
+///
+/// Heartbeats aren't indexed by process, so this reads the heartbeats of `region`, or of every
+/// region when the node's is unknown, minute by minute. Minutes are read concurrently but
+/// consumed in order, stopping once the page is full.
+pub async fn get_heartbeat_history(
+    session: &Database,
+    process_id: Uuid,
+    region: Option<Region>,
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+    limit: usize,
+) -> Result<(Vec<HeartbeatRecord>, bool)> {
+    let regions: Vec<_> = match region {
+        Some(region) => vec![region.to_identifier()],
+        None => Region::iter().map(|r| r.to_identifier()).collect(),
+    };
+    let regions = &regions;
+
+    let mut buckets = stream::iter(get_time_bucket_minutes(from)..=get_time_bucket_minutes(to))
+        .map(|bucket| async move {
+            GET_HEARTBEAT_HISTORY_QUERY
+                .execute_unpaged(session, (regions, bucket, from, to))
+                .await?
+                .into_rows_result()
+                .map_err(anyhow::Error::from)
+        })
+        .buffered(DATABASE_CONCURRENT_READS.get());
+
+    let mut heartbeats = Vec::new();
+    while let Some(rows) = buckets.next().await {
+        let rows = rows?;
+
+        let mut bucket_heartbeats = Vec::new();
+        for row in rows.rows::<(DateTime<Utc>, i32, Option<String>, String, Uuid)>()? {
+            let (timestamp, position, address, region, row_process_id) = row?;
+            if row_process_id != process_id {
+                continue;
+            }
+            bucket_heartbeats.push(HeartbeatRecord {
+                timestamp,
+                position,
+                region: Region::from_identifier(&region)?,
+                address,
+            });
+        }
+        bucket_heartbeats.sort_by_key(|h| h.timestamp);
+        heartbeats.extend(bucket_heartbeats);
+
+        if heartbeats.len() > limit {
+            heartbeats.truncate(limit);
+            return Ok((heartbeats, true));
+        }
+    }
+
+    Ok((heartbeats, false))
+}
+
+/// Storage of heartbeats and ring positions, abstracted so that the coordination logic can be
+/// tested without a cluster.
+pub trait HeartbeatStore: Send + Sync {
+    fn insert_heartbeat(
+        &self,
+        region: Region,
+        process_id: Uuid,
+        position: NodePosition,
+        timestamp: DateTime<Utc>,
+    ) -> BoxFuture<'_, Result<()>>;
+
+    fn insert_worker_metadata<'a>(
+        &'a self,
+        process_id: Uuid,
+        replica_id: Option<&'a str>,
+        git_sha: Option<&'a str>,
+    ) -> BoxFuture<'a, Result<()>>;
+
+    /// Latest heartbeat of each node in `regions` seen within `within_duration`
+    fn fetch_alive_workers<'a>(
+        &'a self,
+        regions: &'a [Region],
+        within_duration: Duration,
+    ) -> BoxFuture<'a, Result<AliveNodes>>;
+
+    fn get_stored_position<'a>(
+        &'a self,
+        region: Region,
+        replica_id: &'a str,
+    ) -> BoxFuture<'a, Result<Option<StoredPosition>>>;
+
+    fn store_position<'a>(
+        &'a self,
+        region: Region,
+        replica_id: &'a str,
+        position: NodePosition,
+        process_id: Uuid,
+    ) -> BoxFuture<'a, Result<()>>;
+}
+
+pub type SharedHeartbeatStore = Arc<dyn HeartbeatStore>;
+
+pub struct ScyllaHeartbeatStore {
+    session: Arc<Database>,
+}
+
+impl ScyllaHeartbeatStore {
+    pub async fn new(session: Arc<Database>) -> Result<Self> {
+        INSERT_HEARTBEAT_QUERY
+            .optimistically_prepare(&session)
+            .await?;
+        INSERT_WORKER_METADATA_QUERY
+            .optimistically_prepare(&session)
+            .await?;
+        GET_ALIVE_WORKERS_QUERY
+            .optimistically_prepare(&session)
+            .await?;
+        GET_STORED_POSITION_QUERY
+            .optimistically_prepare(&session)
+            .await?;
+        STORE_POSITION_QUERY
+            .optimistically_prepare(&session)
+            .await?;
+        GET_HEARTBEAT_HISTORY_QUERY
+            .optimistically_prepare(&session)
+            .await?;
+
+        Ok(Self { session })
+    }
+}
+
+impl HeartbeatStore for ScyllaHeartbeatStore {
+    fn insert_heartbeat(
+        &self,
+        region: Region,
+        process_id: Uuid,
+        position: NodePosition,
+        timestamp: DateTime<Utc>,
+    ) -> BoxFuture<'_, Result<()>> {
+        Box::pin(insert_heartbeat(
+            &self.session,
+            region,
+            process_id,
+            position,
+            timestamp,
+        ))
+    }
+
+    fn insert_worker_metadata<'a>(
+        &'a self,
+        process_id: Uuid,
+        replica_id: Option<&'a str>,
+        git_sha: Option<&'a str>,
+    ) -> BoxFuture<'a, Result<()>> {
+        Box::pin(insert_worker_metadata(
+            &self.session,
+            process_id,
+            replica_id,
+            git_sha,
+        ))
+    }
+
+    fn fetch_alive_workers<'a>(
+        &'a self,
+        regions: &'a [Region],
+        within_duration: Duration,
+    ) -> BoxFuture<'a, Result<AliveNodes>> {
+        Box::pin(fetch_alive_workers_within_interval(
+            &self.session,
+            regions,
+            within_duration,
+        ))
+    }
+
+    fn get_stored_position<'a>(
+        &'a self,
+        region: Region,
+        replica_id: &'a str,
+    ) -> BoxFuture<'a, Result<Option<StoredPosition>>> {
+        Box::pin(async move {
+            let row = GET_STORED_POSITION_QUERY
+                .execute_unpaged(&self.session, (region.to_identifier(), replica_id))
+                .await?
+                .into_rows_result()?
+                .maybe_first_row::<(i32, Uuid)>()?;
+
+            Ok(row.and_then(|(position, process_id)| {
+                Some(StoredPosition {
+                    position: position.try_into().ok()?,
+                    process_id,
+                })
+            }))
+        })
+    }
+
+    fn store_position<'a>(
+        &'a self,
+        region: Region,
+        replica_id: &'a str,
+        position: NodePosition,
+        process_id: Uuid,
+    ) -> BoxFuture<'a, Result<()>> {
+        Box::pin(async move {
+            STORE_POSITION_QUERY
+                .execute_unpaged(
+                    &self.session,
+                    (
+                        region.to_identifier(),
+                        replica_id,
+                        position as i32,
+                        process_id,
+                    ),
+                )
+                .await?;
+
+            Ok(())
+        })
+    }
+}
+
+/// Store kept in memory, for tests that don't need a cluster.
+#[cfg(test)]
+#[derive(Default)]
+pub struct InMemoryHeartbeatStore {
+    heartbeats: std::sync::Mutex<Vec<(DateTime<Utc>, Heartbeat)>>,
+    positions: std::sync::Mutex<HashMap<(Region, String), StoredPosition>>,
+}
+
+#[cfg(test)]
+impl InMemoryHeartbeatStore {
+    pub fn shared() -> Arc<Self> {
+        Arc::new(Self::default())
+    }
+}
+
+#[cfg(test)]
+impl HeartbeatStore for InMemoryHeartbeatStore {
+    fn insert_heartbeat(
+        &self,
+        region: Region,
+        process_id: Uuid,
+        position: NodePosition,
+        timestamp: DateTime<Utc>,
+    ) -> BoxFuture<'_, Result<()>> {
+        let heartbeat = Heartbeat {
+            node_id: process_id,
+            position,
+            socket_address: None,
+            region,
+        };
+        self.heartbeats.lock().unwrap().push((timestamp, heartbeat));
+
+        Box::pin(async { Ok(()) })
+    }
+
+    fn insert_worker_metadata<'a>(
+        &'a self,
+        _process_id: Uuid,
+        _replica_id: Option<&'a str>,
+        _git_sha: Option<&'a str>,
+    ) -> BoxFuture<'a, Result<()>> {
+        Box::pin(async { Ok(()) })
+    }
+
+    fn fetch_alive_workers<'a>(
+        &'a self,
+        regions: &'a [Region],
+        within_duration: Duration,
+    ) -> BoxFuture<'a, Result<AliveNodes>> {
+        let cutoff = Utc::now() - within_duration;
+        let mut latest_heartbeats: HashMap<Uuid, (DateTime<Utc>, Heartbeat)> = HashMap::new();
+
+        for (timestamp, heartbeat) in self.heartbeats.lock().unwrap().iter() {
+            if *timestamp < cutoff || !regions.contains(&heartbeat.region) {
+                continue;
+            }
+            let latest = latest_heartbeats
+                .entry(heartbeat.node_id)
+                .or_insert_with(|| (*timestamp, heartbeat.clone()));
+            if *timestamp > latest.0 {
+                *latest = (*timestamp, heartbeat.clone());
+            }
+        }
+
+        let alive_nodes = latest_heartbeats
+            .into_values()
+            .map(|(_, heartbeat)| heartbeat)
+            .collect();
+
+        Box::pin(async { Ok(alive_nodes) })
+    }
+
+    fn get_stored_position<'a>(
+        &'a self,
+        region: Region,
+        replica_id: &'a str,
+    ) -> BoxFuture<'a, Result<Option<StoredPosition>>> {
+        let stored = self
+            .positions
+            .lock()
+            .unwrap()
+            .get(&(region, replica_id.to_string()))
+            .copied();
+
+        Box::pin(async move { Ok(stored) })
+    }
+
+    fn store_position<'a>(
+        &'a self,
+        region: Region,
+        replica_id: &'a str,
+        position: NodePosition,
+        process_id: Uuid,
+    ) -> BoxFuture<'a, Result<()>> {
+        self.positions.lock().unwrap().insert(
+            (region, replica_id.to_string()),
+            StoredPosition {
+                position,
+                process_id,
+            },
+        );
+
+        Box::pin(async { Ok(()) })
+    }
+}
+
 pub struct HeartbeatManager {
     process_id: Uuid,
     region: Region,
     interval: Duration,
-    session: Arc<Database>,
+    /// Delay of the first heartbeat, see [`heartbeat_offset`]
+    offset: Duration,
+    store: SharedHeartbeatStore,
     /// Includes all regions.
     /// Comprised of `(last_fetched_at, alive_nodes)`.
     last_alive_nodes: Arc<Mutex<Option<(Instant, AliveNodes)>>>,
+    /// When this node last wrote its heartbeat successfully
+    last_heartbeat_at: Arc<std::sync::Mutex<Option<Instant>>>,
 }
 
 impl HeartbeatManager {
@@ -203,25 +595,32 @@ impl HeartbeatManager {
         region: Region,
         interval: Duration,
         session: Arc<Database>,
+        replica_id: Option<&str>,
     ) -> Result<Self> {
-        INSERT_HEARTBEAT_QUERY
-            .optimistically_prepare(&session)
-            .await?;
-        INSERT_WORKER_METADATA_QUERY
-            .optimistically_prepare(&session)
-            .await?;
-        GET_ALIVE_WORKERS_QUERY
-            .optimistically_prepare(&session)
-            .await?;
+        let store = ScyllaHeartbeatStore::new(session).await?;
+
+        Self::with_store(process_id, region, interval, Arc::new(store), replica_id).await
+    }
 
-        insert_worker_metadata(&session, process_id, None, None).await?;
+    pub async fn with_store(
+        process_id: Uuid,
+        region: Region,
+        interval: Duration,
+        store: SharedHeartbeatStore,
+        replica_id: Option<&str>,
+    ) -> Result<Self> {
+        store
+            .insert_worker_metadata(process_id, replica_id, None)
+            .await?;
 
         Ok(Self {
             process_id,
             region,
             interval,
-            session,
+            offset: heartbeat_offset(process_id, interval, *HEARTBEAT_JITTER_PERCENT),
+            store,
             last_alive_nodes: Default::default(),
+            last_heartbeat_at: Default::default(),
         })
     }
 
@@ -232,46 +631,44 @@ impl HeartbeatManager {
         let process_id = self.process_id;
         let region = self.region;
         let interval = self.interval;
+        let offset = self.offset;
 
         let initial_alive_nodes = self.get_alive_workers_all_regions().await?;
 
         let (sender, alive_nodes_receiver) = watch::channel(initial_alive_nodes);
 
-        let heartbeat_task_session = self.session.clone();
+        let heartbeat_task_store = self.store.clone();
+        let last_heartbeat_at = self.last_heartbeat_at.clone();
         let heartbeat_task = tokio::spawn(async move {
-            let mut ticker = tokio::time::interval(interval);
+            let mut ticker = heartbeat_ticker(offset, interval);
             loop {
                 ticker.tick().await;
 
                 let timestamp = Utc::now();
-                let result = insert_heartbeat(
-                    &heartbeat_task_session,
-                    region,
-                    process_id,
-                    position,
-                    timestamp,
-                )
-                .await;
+                let result = heartbeat_task_store
+                    .insert_heartbeat(region, process_id, position, timestamp)
+                    .await;
 
-                if let Err(e) = result {
-                    error!("failed to send heartbeat: {e}");
+                match result {
+                    Ok(()) => {
+                        *last_heartbeat_at.lock().expect("not poisoned") = Some(Instant::now());
+                    }
+                    Err(e) => error!("failed to send heartbeat: {e}"),
                 }
             }
         });
 
-        let state_task_session = self.session.clone();
+        let state_task_store = self.store.clone();
 
+        // Reads follow the same offset, so that they are spread out too
         let monitor_state_task = tokio::spawn(async move {
-            let mut ticker = tokio::time::interval(interval);
+            let mut ticker = heartbeat_ticker(offset, interval);
             loop {
                 ticker.tick().await;
 
-                let result = fetch_alive_workers_within_interval(
-                    &state_task_session,
-                    &[region],
-                    interval * HEARTBEAT_FRESHNESS_MULTIPLE,
-                )
-                .await;
+                let result = state_task_store
+                    .fetch_alive_workers(&[region], interval * HEARTBEAT_FRESHNESS_MULTIPLE)
+                    .await;
 
                 match result {
                     Ok(alive_nodes) => {
@@ -301,6 +698,20 @@ impl HeartbeatManager {
         Ok((alive_nodes_receiver, close_future))
     }
 
+    /// Time since this node last wrote its heartbeat, `None` before the first successful write.
+    pub fn last_heartbeat_age(&self) -> Option<Duration> {
+        self.last_heartbeat_at
+            .lock()
+            .expect("not poisoned")
+            .map(|at| at.elapsed())
+    }
+
+    /// Whether the last heartbeat is recent enough for other nodes to consider this one alive.
+    pub fn is_heartbeat_fresh(&self) -> bool {
+        self.last_heartbeat_age()
+            .is_some_and(|age| age <= self.interval * HEARTBEAT_FRESHNESS_MULTIPLE)
+    }
+
     pub async fn get_alive_workers_all_regions(&self) -> Result<AliveNodes> {
         // Hold the lock during the fetch so that only one thread fetches
         let mut lock = self.last_alive_nodes.lock().await;
@@ -318,20 +729,33 @@ impl HeartbeatManager {
         let all_regions: Vec<Region> = Region::iter().collect();
 
         // Double the interval
-        let alive_nodes = fetch_alive_workers_within_interval(
-            &self.session,
-            &all_regions,
-            self.interval * HEARTBEAT_FRESHNESS_MULTIPLE,
-        )
-        .await?;
+        let alive_nodes = self
+            .store
+            .fetch_alive_workers(&all_regions, self.interval * HEARTBEAT_FRESHNESS_MULTIPLE)
+            .await?;
 
         *lock = Some((Instant::now(), alive_nodes.clone()));
 
         Ok(alive_nodes)
     }
 
+    /// Returns the position last stored by `replica_id` in this region.
+    pub async fn get_stored_position(&self, replica_id: &str) -> Result<Option<StoredPosition>> {
+        self.store
+            .get_stored_position(self.region, replica_id)
+            .await
+    }
+
+    pub async fn store_position(&self, replica_id: &str, position: NodePosition) -> Result<()> {
+        self.store
+            .store_position(self.region, replica_id, position, self.process_id)
+            .await
+    }
+
     pub async fn get_alive_workers_same_region(&self) -> Result<AliveNodes> {
-        fetch_alive_workers_within_interval(&self.session, &[self.region], self.interval * 2).await
+        self.store
+            .fetch_alive_workers(&[self.region], self.interval * 2)
+            .await
     }
 
     #[cfg(test)]
@@ -385,6 +809,35 @@ mod tests {
     use super::*;
     use crate::database::testing::create_test_database;
 
+    #[tokio::test(start_paused = true)]
+    async fn test_heartbeat_offset_spreads_first_writes() {
+        let interval = Duration::from_millis(100);
+        let first_id = Uuid::from_u128(1234);
+        let second_id = Uuid::from_u128(5678);
+
+        let first_offset = heartbeat_offset(first_id, interval, 100);
+        let second_offset = heartbeat_offset(second_id, interval, 100);
+        assert_eq!(first_offset, Duration::from_millis(34));
+        assert_eq!(second_offset, Duration::from_millis(78));
+
+        let mut first = heartbeat_ticker(first_offset, interval);
+        let mut second = heartbeat_ticker(second_offset, interval);
+        let first_write = first.tick().await;
+        let second_write = second.tick().await;
+        assert_eq!(second_write - first_write, Duration::from_millis(44));
+
+        // Later writes keep the interval
+        assert_eq!(first.tick().await - first_write, interval);
+
+        // Offsets never reach the interval, and can be disabled
+        for process_id in [first_id, second_id, Uuid::new_v4()] {
+            assert!(heartbeat_offset(process_id, interval, 100) < interval);
+            assert!(heartbeat_offset(process_id, interval, 500) < interval);
+            assert!(heartbeat_offset(process_id, interval, 50) < interval / 2);
+            assert_eq!(heartbeat_offset(process_id, interval, 0), Duration::ZERO);
+        }
+    }
+
     #[test]
     fn test_get_time_bucket() {
         let timestamp1 = DateTime::parse_from_rfc3339("2024-01-15T12:30:45Z")
@@ -467,6 +920,7 @@ mod tests {
             Region::Fsn1,
             Duration::from_secs(300 / HEARTBEAT_FRESHNESS_MULTIPLE as u64),
             Arc::new(session),
+            None,
         )
         .await?;
 
@@ -4,6 +4,14 @@ use uuid::Uuid;
 
 #[derive(Debug, Serialize, Deserialize, ToSchema, Clone)]
 pub enum InterNodeMessage {
-    ServiceCheckMutation { check_id: Uuid },
-    ShuttingDown { process_id: Uuid },
+    ServiceCheckMutation {
+        check_id: Uuid,
+    },
+    ShuttingDown {
+        process_id: Uuid,
+    },
+    /// Cluster-wide switch to pause (or resume) probing, without touching the checks
+    SetProbingEnabled {
+        enabled: bool,
+    },
 }
@@ -1,36 +1,72 @@
 pub mod messages;
+pub mod recent;
+pub mod replay;
 
 use crate::{
+    clock::Clock,
     collab::{
         NodePosition,
         assignment::calculate_node_range,
-        heartbeat::{Heartbeat, HeartbeatManager},
+        heartbeat::{AliveNodes, HeartbeatManager},
         internode::messages::InterNodeMessage,
     },
     eager_env::{BACKEND_INTERNAL_PASSWORD, REPLICATION_FACTOR},
 };
 use anyhow::Result;
+use chrono::{DateTime, Utc};
+use futures::future::BoxFuture;
 use log::{error, warn};
 use reqwest::Client;
-use std::{collections::BTreeSet, net::SocketAddr};
+use serde::{Deserialize, Serialize};
+use std::net::SocketAddr;
+use utoipa::ToSchema;
+use uuid::Uuid;
 
 pub struct MessageWithFilters {
     pub message: InterNodeMessage,
     pub filter_bucket: Option<NodePosition>,
 }
 
-pub type BroadcastBody = Vec<InterNodeMessage>;
+/// Messages sent to `/internal`, stamped so that a captured request can't be replayed
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct BroadcastBody {
+    pub sent_at: DateTime<Utc>,
+    /// Unique per request
+    pub nonce: Uuid,
+    pub messages: Vec<InterNodeMessage>,
+}
 
-/// Broadcasts messages to the given alive nodes.
-/// Returns the number of hosts that received the messages successfully.
-pub async fn broadcast(
-    alive_nodes: &BTreeSet<Heartbeat>,
-    messages: Vec<MessageWithFilters>,
-    replication_factor: u32,
-) -> usize {
-    let client = Client::new();
+impl BroadcastBody {
+    pub fn new(messages: Vec<InterNodeMessage>, sent_at: DateTime<Utc>) -> Self {
+        Self {
+            sent_at,
+            nonce: Uuid::new_v4(),
+            messages,
+        }
+    }
+}
+
+/// Source of the nodes messages are broadcast to, so that broadcasting can be tested against a
+/// fixed set of nodes.
+pub trait AliveNodesSource: Send + Sync {
+    /// Alive nodes of every region
+    fn alive_nodes(&self) -> BoxFuture<'_, Result<AliveNodes>>;
+}
+
+impl AliveNodesSource for HeartbeatManager {
+    fn alive_nodes(&self) -> BoxFuture<'_, Result<AliveNodes>> {
+        Box::pin(self.get_alive_workers_all_regions())
+    }
+}
 
-    let tasks: Vec<_> = alive_nodes
+/// Messages each alive node should receive: those without `filter_bucket`, and those whose
+/// bucket is in the node's range. Nodes without address or without messages are skipped.
+fn route_messages(
+    alive_nodes: &AliveNodes,
+    messages: &[MessageWithFilters],
+    replication_factor: u32,
+) -> Vec<(SocketAddr, Vec<InterNodeMessage>)> {
+    alive_nodes
         .iter()
         .filter_map(|node| match node.socket_address {
             Some(socket_addr) => Some((node, socket_addr)),
@@ -40,7 +76,6 @@ pub async fn broadcast(
             }
         })
         .map(|(node, socket_addr)| {
-            let client = client.clone();
             let filtered_messages: Vec<_> = messages
                 .iter()
                 .filter(|m| {
@@ -61,18 +96,43 @@ pub async fn broadcast(
                 .map(|m| m.message.clone())
                 .collect();
 
-            let url = format!("http://{}/internal", socket_addr);
+            (socket_addr, filtered_messages)
+        })
+        .filter(|(_, filtered_messages)| !filtered_messages.is_empty())
+        .collect()
+}
 
-            async move {
-                if filtered_messages.is_empty() {
-                    return false;
-                }
+/// Broadcasts messages to the given alive nodes, see [`route_messages`].
+/// Returns the number of hosts that received the messages successfully.
+pub async fn broadcast(
+    alive_nodes: &AliveNodes,
+    messages: Vec<MessageWithFilters>,
+    replication_factor: u32,
+    clock: &dyn Clock,
+) -> usize {
+    send_routes(
+        route_messages(alive_nodes, &messages, replication_factor),
+        clock,
+    )
+    .await
+}
+
+/// Sends each node its messages, returning the number of nodes that received them successfully.
+/// Requests are stamped with `clock`, see [`BroadcastBody`].
+async fn send_routes(routes: Vec<(SocketAddr, Vec<InterNodeMessage>)>, clock: &dyn Clock) -> usize {
+    let client = Client::new();
 
-                let message: BroadcastBody = filtered_messages;
+    let tasks: Vec<_> = routes
+        .into_iter()
+        .map(|(socket_addr, filtered_messages)| {
+            let client = client.clone();
+            let url = format!("http://{}/internal", socket_addr);
+            let body = BroadcastBody::new(filtered_messages, clock.now());
 
+            async move {
                 let result = client
                     .post(&url)
-                    .json(&message)
+                    .json(&body)
                     .header(
                         "Authorization",
                         format!("Bearer {}", *BACKEND_INTERNAL_PASSWORD),
@@ -109,23 +169,150 @@ pub async fn broadcast(
 /// Returns the socket addresses of the (allegedly) currently alive nodes and the number of
 /// successful sends.
 pub async fn standard_broadcast(
-    heartbeat: &HeartbeatManager,
+    nodes: &impl AliveNodesSource,
+    clock: &dyn Clock,
     messages: Vec<MessageWithFilters>,
 ) -> Result<(Vec<SocketAddr>, usize)> {
-    let alive_nodes = heartbeat.get_alive_workers_all_regions().await?;
+    let alive_nodes = nodes.alive_nodes().await?;
     let alive_ips = alive_nodes
         .iter()
         .filter_map(|node| node.socket_address)
         .collect();
-    let success_count = broadcast(&alive_nodes, messages, *REPLICATION_FACTOR).await;
+    let success_count = broadcast(&alive_nodes, messages, *REPLICATION_FACTOR, clock).await;
     Ok((alive_ips, success_count))
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::{regions::Region, server::start_server_test};
-    use uuid::Uuid;
+    use crate::{
+        clock::SystemClock, collab::heartbeat::Heartbeat, regions::Region,
+        server::start_server_test,
+    };
+    use httpmock::prelude::*;
+
+    /// Fixed set of alive nodes
+    struct StubNodes(AliveNodes);
+
+    impl AliveNodesSource for StubNodes {
+        fn alive_nodes(&self) -> BoxFuture<'_, Result<AliveNodes>> {
+            Box::pin(async { Ok(self.0.clone()) })
+        }
+    }
+
+    fn node(
+        position: NodePosition,
+        region: Region,
+        socket_address: Option<SocketAddr>,
+    ) -> Heartbeat {
+        Heartbeat {
+            node_id: Uuid::new_v4(),
+            position,
+            socket_address,
+            region,
+        }
+    }
+
+    fn mutation(filter_bucket: Option<NodePosition>) -> MessageWithFilters {
+        MessageWithFilters {
+            message: InterNodeMessage::ServiceCheckMutation {
+                check_id: Uuid::new_v4(),
+            },
+            filter_bucket,
+        }
+    }
+
+    #[test]
+    fn test_route_messages_filters_by_bucket() {
+        let address = |i: u8| Some(SocketAddr::from(([10, 0, 0, i], 8801)));
+        let alive_nodes: AliveNodes = [
+            node(0, Region::Fsn1, address(1)),
+            node(5, Region::Fsn1, address(2)),
+            node(10, Region::Fsn1, address(3)),
+            node(15, Region::Fsn1, address(4)),
+            // Alone in its region, it holds the whole ring
+            node(3, Region::Hel1, address(5)),
+            node(12, Region::Fsn1, None),
+        ]
+        .into_iter()
+        .collect();
+
+        let filtered = mutation(Some(7));
+        let unfiltered = mutation(None);
+        let routes = route_messages(&alive_nodes, &[filtered, unfiltered], 2);
+        let messages_of = |i: u8| {
+            routes
+                .iter()
+                .find(|(socket_addr, _)| Some(*socket_addr) == address(i))
+                .map(|(_, messages)| messages.len())
+        };
+
+        // With 2 replicas, bucket 7 is held by the nodes at 0 (up to 12) and 5 (up to 15)
+        assert_eq!(messages_of(1), Some(2));
+        assert_eq!(messages_of(2), Some(2));
+        assert_eq!(messages_of(3), Some(1));
+        assert_eq!(messages_of(4), Some(1));
+        assert_eq!(messages_of(5), Some(2));
+        // The node without address is skipped
+        assert_eq!(routes.len(), 5);
+
+        // Nodes left without messages aren't contacted
+        let routes = route_messages(&alive_nodes, &[mutation(Some(7))], 2);
+        assert_eq!(routes.len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_standard_broadcast_filters_by_bucket() {
+        // One more node than replicas, so that one of them doesn't hold the bucket
+        let servers: Vec<_> = (0..=*REPLICATION_FACTOR)
+            .map(|_| MockServer::start())
+            .collect();
+        let mocks: Vec<_> = servers
+            .iter()
+            .map(|server| {
+                server.mock(|when, then| {
+                    when.method(POST).path("/internal");
+                    then.status(200);
+                })
+            })
+            .collect();
+        let alive_nodes: AliveNodes = servers
+            .iter()
+            .enumerate()
+            .map(|(i, server)| {
+                node(
+                    10 * i as NodePosition,
+                    Region::Fsn1,
+                    Some(*server.address()),
+                )
+            })
+            .collect();
+
+        let bucket = 5;
+        let holders: Vec<_> = alive_nodes
+            .iter()
+            .map(|node| {
+                calculate_node_range(node.node_id, *REPLICATION_FACTOR, &alive_nodes, node.region)
+                    .is_some_and(|range| range.contains(bucket))
+            })
+            .collect();
+        assert!(holders.contains(&false));
+
+        let (ips, success_count) = standard_broadcast(
+            &StubNodes(alive_nodes.clone()),
+            &SystemClock,
+            vec![mutation(Some(bucket))],
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(ips.len(), servers.len());
+        assert_eq!(success_count, holders.iter().filter(|&&held| held).count());
+        // Nodes are ordered by position, like the servers
+        for (mock, held) in mocks.iter().zip(holders) {
+            mock.assert_calls(held as usize);
+        }
+    }
 
     #[tokio::test]
     async fn test_standard_broadcast() {
@@ -163,9 +350,10 @@ mod tests {
             filter_bucket: None,
         }];
 
-        let (ips, success_count) = standard_broadcast(&state1.heartbeat_manager, messages)
-            .await
-            .unwrap();
+        let (ips, success_count) =
+            standard_broadcast(&*state1.heartbeat_manager, &*state1.clock, messages)
+                .await
+                .unwrap();
 
         assert_eq!(ips.len(), 2);
         assert_eq!(success_count, 2);
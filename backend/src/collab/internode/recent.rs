@@ -0,0 +1,179 @@
+use crate::{
+    clock::SharedClock,
+    collab::{
+        get_bucket_for_check,
+        heartbeat::AliveNodes,
+        internode::{MessageWithFilters, messages::InterNodeMessage, route_messages, send_routes},
+    },
+    eager_env::REPLICATION_FACTOR,
+};
+use chrono::{DateTime, Utc};
+use log::info;
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use tokio::sync::watch;
+use uuid::Uuid;
+
+/// Short-lived log of the check mutations broadcast by this node.
+///
+/// Nodes joining the cluster may have fetched their checks before the last mutations and missed
+/// their broadcast, so these are sent to them again rather than waiting for a full rescan.
+pub struct RecentMutations {
+    retention: Duration,
+    /// Check to the time of its last mutation
+    mutations: Mutex<HashMap<Uuid, DateTime<Utc>>>,
+}
+
+impl RecentMutations {
+    pub fn new(retention: Duration) -> Self {
+        Self {
+            retention,
+            mutations: Default::default(),
+        }
+    }
+
+    pub fn record(&self, check_id: Uuid, now: DateTime<Utc>) {
+        self.mutations.lock().unwrap().insert(check_id, now);
+    }
+
+    /// Checks mutated within the retention, forgetting the older ones
+    pub fn recent(&self, now: DateTime<Utc>) -> Vec<Uuid> {
+        let retention = chrono::Duration::from_std(self.retention).unwrap_or(chrono::Duration::MAX);
+        let mut mutations = self.mutations.lock().unwrap();
+        mutations.retain(|_, mutated_at| now - *mutated_at <= retention);
+        mutations.keys().copied().collect()
+    }
+}
+
+/// Sends the recent mutations to every node appearing in `alive_nodes`, only to the nodes whose
+/// range holds the mutated check, until the channel closes.
+pub async fn rebroadcast_task_body(
+    mut alive_nodes: watch::Receiver<AliveNodes>,
+    recent_mutations: Arc<RecentMutations>,
+    clock: SharedClock,
+) {
+    let node_ids =
+        |nodes: &AliveNodes| -> HashSet<Uuid> { nodes.iter().map(|node| node.node_id).collect() };
+    let mut known = node_ids(&alive_nodes.borrow_and_update());
+
+    while alive_nodes.changed().await.is_ok() {
+        let current = alive_nodes.borrow_and_update().clone();
+        let current_ids = node_ids(&current);
+        let new_addresses: HashSet<_> = current
+            .iter()
+            .filter(|node| !known.contains(&node.node_id))
+            .filter_map(|node| node.socket_address)
+            .collect();
+        known = current_ids;
+
+        if new_addresses.is_empty() {
+            continue;
+        }
+
+        let messages: Vec<_> = recent_mutations
+            .recent(clock.now())
+            .into_iter()
+            .map(|check_id| MessageWithFilters {
+                message: InterNodeMessage::ServiceCheckMutation { check_id },
+                filter_bucket: Some(get_bucket_for_check(check_id).1 as u32),
+            })
+            .collect();
+        if messages.is_empty() {
+            continue;
+        }
+
+        let routes: Vec<_> = route_messages(&current, &messages, *REPLICATION_FACTOR)
+            .into_iter()
+            .filter(|(socket_addr, _)| new_addresses.contains(socket_addr))
+            .collect();
+        let sent = routes.len();
+        let success_count = send_routes(routes, &*clock).await;
+        info!(
+            "Re-broadcast {} recent mutations to {success_count}/{sent} new nodes",
+            messages.len()
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{clock::SystemClock, collab::heartbeat::Heartbeat, regions::Region};
+    use httpmock::prelude::*;
+    use std::net::SocketAddr;
+    use tokio::time::{sleep, timeout};
+
+    #[test]
+    fn test_recent_mutations_expire() {
+        let recent = RecentMutations::new(Duration::from_secs(60));
+        let now = Utc::now();
+        let old = Uuid::new_v4();
+        let fresh = Uuid::new_v4();
+
+        recent.record(old, now - chrono::Duration::seconds(61));
+        recent.record(fresh, now - chrono::Duration::seconds(30));
+        assert_eq!(recent.recent(now), [fresh]);
+
+        // Mutating again refreshes it
+        recent.record(fresh, now);
+        assert_eq!(recent.recent(now + chrono::Duration::seconds(45)), [fresh]);
+        assert!(
+            recent
+                .recent(now + chrono::Duration::seconds(61))
+                .is_empty()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_new_node_receives_recent_mutations() {
+        let existing_server = MockServer::start();
+        let new_server = MockServer::start();
+        let existing_mock = existing_server.mock(|when, then| {
+            when.method(POST).path("/internal");
+            then.status(200);
+        });
+        let check_id = Uuid::new_v4();
+        let new_mock = new_server.mock(|when, then| {
+            when.method(POST)
+                .path("/internal")
+                .body_includes(check_id.to_string());
+            then.status(200);
+        });
+
+        let node = |server: &MockServer, region| Heartbeat {
+            node_id: Uuid::new_v4(),
+            position: 0,
+            socket_address: Some(SocketAddr::from(([127, 0, 0, 1], server.port()))),
+            region,
+        };
+        let existing = node(&existing_server, Region::Fsn1);
+        let (sender, receiver) = watch::channel(AliveNodes::from([existing.clone()]));
+
+        let clock = SystemClock::shared();
+        let recent_mutations = Arc::new(RecentMutations::new(Duration::from_secs(60)));
+        recent_mutations.record(check_id, clock.now());
+        let task = tokio::spawn(rebroadcast_task_body(receiver, recent_mutations, clock));
+        // Let the task record the nodes it starts with
+        tokio::task::yield_now().await;
+
+        // Alone in its region, the newcomer holds every bucket
+        sender.send_modify(|nodes| {
+            nodes.insert(node(&new_server, Region::Hel1));
+        });
+
+        timeout(Duration::from_secs(5), async {
+            while new_mock.calls() == 0 {
+                sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .expect("the new node should receive the mutation");
+        // Nodes already known got the original broadcast
+        existing_mock.assert_calls(0);
+
+        task.abort();
+    }
+}
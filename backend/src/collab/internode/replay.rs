@@ -0,0 +1,61 @@
+use chrono::{DateTime, Utc};
+use std::{collections::HashMap, sync::Mutex, time::Duration};
+use uuid::Uuid;
+
+/// Remembers the nonces of recently received internode messages, to reject replayed requests.
+///
+/// Nonces are only kept for the replay window: older messages are rejected by their timestamp.
+pub struct ReplayGuard {
+    window: Duration,
+    /// Nonce to the time it can be forgotten
+    seen: Mutex<HashMap<Uuid, DateTime<Utc>>>,
+}
+
+impl ReplayGuard {
+    pub fn new(window: Duration) -> Self {
+        Self {
+            window,
+            seen: Default::default(),
+        }
+    }
+
+    /// Records the nonce, returning false if the message is outside the window or was already seen.
+    pub fn accept(&self, sent_at: DateTime<Utc>, nonce: Uuid, now: DateTime<Utc>) -> bool {
+        let skew = (now - sent_at).abs().to_std().unwrap_or(Duration::MAX);
+        if skew > self.window {
+            return false;
+        }
+
+        let mut seen = self.seen.lock().unwrap();
+        seen.retain(|_, expires_at| *expires_at >= now);
+        // Kept until the message can no longer pass the timestamp check
+        let expires_at = sent_at + self.window;
+        seen.insert(nonce, expires_at).is_none()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_replay_guard() {
+        let guard = ReplayGuard::new(Duration::from_secs(60));
+        let now = Utc::now();
+        let nonce = Uuid::new_v4();
+
+        assert!(guard.accept(now, nonce, now));
+        assert!(!guard.accept(now, nonce, now + Duration::from_secs(1)));
+
+        // Outside the window, in either direction
+        assert!(!guard.accept(now - Duration::from_secs(61), Uuid::new_v4(), now));
+        assert!(!guard.accept(now + Duration::from_secs(61), Uuid::new_v4(), now));
+        assert!(guard.accept(now + Duration::from_secs(30), Uuid::new_v4(), now));
+
+        // Expired nonces are forgotten, the timestamp check rejects their messages
+        let later = now + Duration::from_secs(200);
+        assert!(guard.accept(later, Uuid::new_v4(), later));
+        assert!(!guard.seen.lock().unwrap().contains_key(&nonce));
+        assert!(!guard.accept(now, nonce, later));
+    }
+}
@@ -9,33 +9,137 @@
 //! Nodes are ordered by (position, node_id) to handle the rare case of position collisions.
 //! All state (heartbeats + ring positions) persists in Cassandra (eventually consistent).
 
-mod assignment;
+pub mod assignment;
 pub mod heartbeat;
 pub mod internode;
 pub mod range_manager;
 
 use crate::{
-    collab::{assignment::choose_new_node_position, heartbeat::HeartbeatManager},
+    collab::{
+        assignment::{choose_new_node_position, reusable_position},
+        heartbeat::HeartbeatManager,
+    },
     eager_env,
 };
 use anyhow::Result;
-pub use assignment::{NodePosition, RingRange};
+pub use assignment::{NodePosition, RingCoverage, RingRange, calculate_ring_coverage};
+use log::info;
 use uuid::Uuid;
 
+/// Picks the ring position of this node.
+///
+/// With a `replica_id` the position is persisted, and reclaimed on restart if still free.
 pub async fn decide_position(
     heartbeat: &HeartbeatManager,
     ring_size: NodePosition,
+    replica_id: Option<&str>,
 ) -> Result<NodePosition> {
     let state = heartbeat.get_alive_workers_same_region().await?;
 
-    let position = choose_new_node_position(&state, ring_size)?;
+    let stored = match replica_id {
+        Some(replica_id) => heartbeat.get_stored_position(replica_id).await?,
+        None => None,
+    };
+
+    let position = match reusable_position(stored, &state, ring_size) {
+        Some(position) => {
+            info!("reclaiming stored ring position {position}");
+            position
+        }
+        None => choose_new_node_position(&state, ring_size)?,
+    };
+
+    if let Some(replica_id) = replica_id {
+        heartbeat.store_position(replica_id, position).await?;
+    }
 
     Ok(position)
 }
 
 /// Returns `(bucket_version, bucket)` based on current env
 pub fn get_bucket_for_check(check_id: Uuid) -> (i16, i32) {
-    let bucket = (check_id.as_u128() % (*eager_env::CURRENT_BUCKETS_COUNT as u128)) as i32;
+    let bucket = bucket_for_check(check_id, *eager_env::CURRENT_BUCKETS_COUNT) as i32;
 
     (*eager_env::CURRENT_BUCKET_VERSION as i16, bucket)
 }
+
+/// Bucket layout checks are migrating from after `CURRENT_BUCKET_VERSION` was bumped. Checks not
+/// rewritten under the current version yet are still read from it.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct PreviousBuckets {
+    pub bucket_version: i16,
+    pub bucket_count: NodePosition,
+}
+
+impl PreviousBuckets {
+    /// `None` unless `PREVIOUS_BUCKET_VERSION` is set, i.e. outside of a migration window
+    pub fn from_env() -> Option<Self> {
+        let bucket_version = *eager_env::PREVIOUS_BUCKET_VERSION;
+        let bucket_count = match *eager_env::PREVIOUS_BUCKETS_COUNT {
+            0 => *eager_env::CURRENT_BUCKETS_COUNT,
+            count => count,
+        };
+
+        (bucket_version != 0).then_some(Self {
+            bucket_version: bucket_version as i16,
+            bucket_count,
+        })
+    }
+
+    /// Returns `(bucket_version, bucket)` of `check_id` in this layout
+    pub fn bucket_for_check(&self, check_id: Uuid) -> (i16, i32) {
+        (
+            self.bucket_version,
+            bucket_for_check(check_id, self.bucket_count) as i32,
+        )
+    }
+}
+
+/// Returns the bucket of `check_id` on a ring of `bucket_count` buckets
+pub fn bucket_for_check(check_id: Uuid, bucket_count: NodePosition) -> NodePosition {
+    (check_id.as_u128() % bucket_count as u128) as NodePosition
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{collab::heartbeat::InMemoryHeartbeatStore, regions::Region};
+    use proptest::prelude::*;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_decide_position_reuses_stored_free_position() -> Result<()> {
+        let heartbeat = HeartbeatManager::with_store(
+            Uuid::new_v4(),
+            Region::Fsn1,
+            Duration::from_secs(60),
+            InMemoryHeartbeatStore::shared(),
+            Some("replica-1"),
+        )
+        .await?;
+
+        heartbeat.store_position("replica-1", 7).await?;
+
+        let position = decide_position(&heartbeat, 20, Some("replica-1")).await?;
+        assert_eq!(position, 7);
+
+        Ok(())
+    }
+
+    proptest! {
+        #[test]
+        fn prop_bucket_within_ring(id: u128, bucket_count in 1..10_000 as NodePosition) {
+            let bucket = bucket_for_check(Uuid::from_u128(id), bucket_count);
+            prop_assert!(bucket < bucket_count);
+        }
+
+        #[test]
+        fn prop_bucket_is_stable(id: u128, bucket_count in 1..10_000 as NodePosition) {
+            let check_id = Uuid::from_u128(id);
+            prop_assert_eq!(
+                bucket_for_check(check_id, bucket_count),
+                bucket_for_check(check_id, bucket_count)
+            );
+        }
+    }
+}
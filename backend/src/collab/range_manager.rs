@@ -87,8 +87,12 @@ impl RangeManager {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::{collab::heartbeat::Heartbeat, regions::Region};
+    use crate::{
+        collab::heartbeat::{Heartbeat, HeartbeatManager, HeartbeatStore, InMemoryHeartbeatStore},
+        regions::Region,
+    };
     use anyhow::Result;
+    use chrono::Utc;
     use std::{collections::BTreeSet, time::Duration};
     use tokio::time::timeout;
 
@@ -153,4 +157,47 @@ mod tests {
 
         Ok(())
     }
+
+    #[tokio::test]
+    async fn test_range_manager_follows_heartbeats() -> Result<()> {
+        let store = InMemoryHeartbeatStore::shared();
+        let node_id = Uuid::new_v4();
+        let other_node_id = Uuid::new_v4();
+        let heartbeat = HeartbeatManager::with_store(
+            node_id,
+            Region::Fsn1,
+            Duration::from_millis(50),
+            store.clone(),
+            None,
+        )
+        .await?;
+
+        let (alive_nodes, stop_heartbeat) = heartbeat.start(10).await?;
+        let (close_fn, mut rx) = RangeManager::new(node_id, 1, Region::Fsn1)
+            .start(alive_nodes)
+            .await;
+
+        // Alone, the node covers the whole ring
+        let range = timeout(Duration::from_secs(5), rx.wait_for(Option::is_some))
+            .await?
+            .map(|range| *range)?;
+        assert_eq!(range, Some(RingRange { start: 10, end: 10 }));
+
+        // Another node joins
+        store
+            .insert_heartbeat(Region::Fsn1, other_node_id, 20, Utc::now())
+            .await?;
+        let range = timeout(
+            Duration::from_secs(5),
+            rx.wait_for(|range| *range != Some(RingRange { start: 10, end: 10 })),
+        )
+        .await?
+        .map(|range| *range)?;
+        assert_eq!(range, Some(RingRange { start: 10, end: 20 }));
+
+        close_fn();
+        stop_heartbeat.await;
+
+        Ok(())
+    }
 }
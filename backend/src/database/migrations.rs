@@ -0,0 +1,110 @@
+use anyhow::{Result, anyhow};
+use chrono::Utc;
+use include_dir::{Dir, include_dir};
+use scylla::client::session::Session;
+use std::collections::HashSet;
+
+use super::preparer::CachedPreparedStatement;
+
+static MIGRATIONS_DIR: Dir<'static> = include_dir!("$CARGO_MANIFEST_DIR/migrations");
+
+/// Returns all migrations, ordered by their timestamp prefix.
+pub fn get_migrations() -> Vec<(&'static str, &'static str)> {
+    let mut migrations: Vec<_> = MIGRATIONS_DIR
+        .files()
+        .map(|file| {
+            (
+                file.path().to_str().expect("valid utf8"),
+                file.contents_utf8().expect("valid utf8"),
+            )
+        })
+        .collect();
+
+    migrations.sort_by_key(|(path, _)| *path);
+    migrations
+}
+
+/// Splits SQL content into individual statements by semicolon delimiter.
+/// Returns an iterator of non-empty, trimmed statement strings.
+pub fn split_to_statements(content: &str) -> impl Iterator<Item = &str> {
+    content
+        .split(';')
+        .map(|s| s.trim())
+        .filter(|s| !s.is_empty())
+}
+
+async fn run_migration(session: &Session, file: &str, content: &str) -> Result<()> {
+    for statement in split_to_statements(content) {
+        session
+            .query_unpaged(statement, &[])
+            .await
+            .map_err(|e| anyhow!("Migration failed for file {}: {}", file, e))?;
+    }
+    Ok(())
+}
+
+/// Runs all migrations on the provided session, without recording them.
+/// Expects the session to have the keyspace already set.
+#[cfg(test)]
+pub async fn run_migrations(session: &Session) -> Result<()> {
+    for (file, content) in get_migrations() {
+        run_migration(session, file, content).await?;
+    }
+    Ok(())
+}
+
+/// Not a migration itself: it records which ones were applied.
+const CREATE_MIGRATIONS_TABLE_QUERY: &str = "
+    CREATE TABLE IF NOT EXISTS schema_migrations
+    (
+        migration  text,
+        applied_at timestamp,
+
+        PRIMARY KEY (migration)
+    )
+    ";
+
+static GET_APPLIED_MIGRATIONS_QUERY: CachedPreparedStatement =
+    CachedPreparedStatement::new("SELECT migration FROM schema_migrations");
+
+static RECORD_MIGRATION_QUERY: CachedPreparedStatement = CachedPreparedStatement::new(
+    "INSERT INTO schema_migrations (migration, applied_at) VALUES (?, ?)",
+);
+
+/// Runs the migrations not recorded in `schema_migrations` yet, in order, recording each one
+/// once applied. Returns the applied migrations.
+///
+/// With `baseline`, the migrations are only recorded: for keyspaces migrated by hand before.
+pub async fn run_pending_migrations(
+    session: &Session,
+    baseline: bool,
+) -> Result<Vec<&'static str>> {
+    session
+        .query_unpaged(CREATE_MIGRATIONS_TABLE_QUERY, &[])
+        .await?;
+
+    let applied = GET_APPLIED_MIGRATIONS_QUERY
+        .execute_unpaged(session, ())
+        .await?
+        .into_rows_result()?
+        .rows::<(String,)>()?
+        .map(|row| row.map(|(migration,)| migration))
+        .collect::<Result<HashSet<_>, _>>()?;
+
+    let mut newly_applied = Vec::new();
+    for (file, content) in get_migrations() {
+        if applied.contains(file) {
+            continue;
+        }
+
+        if !baseline {
+            run_migration(session, file, content).await?;
+        }
+        RECORD_MIGRATION_QUERY
+            .execute_unpaged(session, (file, Utc::now()))
+            .await?;
+        newly_applied.push(file);
+    }
+
+    Ok(newly_applied)
+}
@@ -1,4 +1,6 @@
+pub mod migrations;
 pub mod preparer;
+pub mod replication;
 #[cfg(test)]
 pub mod testing;
 
@@ -50,6 +52,14 @@ async fn connect_db_optional_ks(
     Ok(session)
 }
 
+/// Minimal round trip, to tell whether the database is reachable
+pub async fn ping(db: &Database) -> Result<()> {
+    db.query_unpaged("SELECT now() FROM system.local", &[])
+        .await?;
+
+    Ok(())
+}
+
 pub async fn connect_db(database_nodes_urls: &[&str], keyspace_name: &str) -> Result<Session> {
     connect_db_optional_ks(database_nodes_urls, Some(keyspace_name)).await
 }
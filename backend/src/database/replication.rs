@@ -0,0 +1,125 @@
+use crate::database::{Database, preparer::CachedPreparedStatement};
+use anyhow::{Context, Result, bail};
+use log::{info, warn};
+use std::collections::{BTreeSet, HashMap};
+
+static GET_KEYSPACE_REPLICATION_QUERY: CachedPreparedStatement = CachedPreparedStatement::new(
+    "
+    SELECT replication
+    FROM system_schema.keyspaces
+    WHERE keyspace_name = ?
+    ",
+);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ReplicationStrategy {
+    Simple,
+    NetworkTopology,
+    Other(String),
+}
+
+impl ReplicationStrategy {
+    /// Parses the `class` of a keyspace replication map, either short or fully qualified,
+    /// e.g. `org.apache.cassandra.locator.SimpleStrategy`
+    fn from_replication(replication: &HashMap<String, String>) -> Option<Self> {
+        let class = replication.get("class")?;
+        let name = class.rsplit('.').next().unwrap_or(class);
+
+        Some(match name {
+            "SimpleStrategy" => Self::Simple,
+            "NetworkTopologyStrategy" => Self::NetworkTopology,
+            _ => Self::Other(class.clone()),
+        })
+    }
+}
+
+/// Returns a warning when `strategy` can't place replicas per datacenter while the cluster
+/// spans `datacenters_count` of them.
+fn replication_warning(
+    keyspace: &str,
+    strategy: &ReplicationStrategy,
+    datacenters_count: usize,
+) -> Option<String> {
+    match strategy {
+        ReplicationStrategy::Simple if datacenters_count > 1 => Some(format!(
+            "keyspace {keyspace} uses SimpleStrategy across {datacenters_count} datacenters: \
+             replicas ignore datacenter boundaries, use NetworkTopologyStrategy instead"
+        )),
+        _ => None,
+    }
+}
+
+/// Verifies the replication of `keyspace` suits the cluster topology.
+///
+/// Misconfigurations are logged, or fail the startup when `strict` is set.
+pub async fn check_keyspace_replication(db: &Database, keyspace: &str, strict: bool) -> Result<()> {
+    let replication = GET_KEYSPACE_REPLICATION_QUERY
+        .execute_unpaged(db, (keyspace,))
+        .await?
+        .into_rows_result()?
+        .maybe_first_row::<(HashMap<String, String>,)>()?
+        .map(|(replication,)| replication)
+        .with_context(|| format!("keyspace {keyspace} not found"))?;
+
+    let strategy = ReplicationStrategy::from_replication(&replication)
+        .with_context(|| format!("keyspace {keyspace} has no replication class"))?;
+
+    let datacenters: BTreeSet<_> = db
+        .get_cluster_state()
+        .get_nodes_info()
+        .iter()
+        .filter_map(|node| node.datacenter.clone())
+        .collect();
+
+    match replication_warning(keyspace, &strategy, datacenters.len()) {
+        Some(warning) if strict => bail!(warning),
+        Some(warning) => warn!("{warning}"),
+        None => {
+            info!("keyspace {keyspace} replication: {strategy:?}, datacenters: {datacenters:?}")
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn replication(class: &str) -> HashMap<String, String> {
+        HashMap::from([
+            ("class".to_string(), class.to_string()),
+            ("replication_factor".to_string(), "3".to_string()),
+        ])
+    }
+
+    #[test]
+    fn test_parse_replication_strategy() {
+        assert_eq!(
+            ReplicationStrategy::from_replication(&replication(
+                "org.apache.cassandra.locator.SimpleStrategy"
+            )),
+            Some(ReplicationStrategy::Simple)
+        );
+        assert_eq!(
+            ReplicationStrategy::from_replication(&replication("NetworkTopologyStrategy")),
+            Some(ReplicationStrategy::NetworkTopology)
+        );
+        assert_eq!(
+            ReplicationStrategy::from_replication(&replication("LocalStrategy")),
+            Some(ReplicationStrategy::Other("LocalStrategy".to_string()))
+        );
+        assert_eq!(ReplicationStrategy::from_replication(&HashMap::new()), None);
+    }
+
+    #[test]
+    fn test_replication_warning() {
+        let simple = ReplicationStrategy::Simple;
+        let topology = ReplicationStrategy::NetworkTopology;
+
+        assert!(replication_warning("ks", &simple, 1).is_none());
+        assert!(replication_warning("ks", &simple, 3).is_some());
+        assert!(replication_warning("ks", &topology, 1).is_none());
+        assert!(replication_warning("ks", &topology, 3).is_none());
+    }
+}
@@ -1,48 +1,10 @@
 use crate::database::connect_db_optional_ks;
+use crate::database::migrations::{run_migrations, split_to_statements};
 use crate::database::parse_database_urls;
 use anyhow::Result;
-use include_dir::{Dir, include_dir};
 use rand::{Rng, rng};
 use scylla::client::session::Session;
 
-static MIGRATIONS_DIR: Dir<'static> = include_dir!("$CARGO_MANIFEST_DIR/migrations");
-
-pub fn get_migrations() -> Vec<(&'static str, &'static str)> {
-    MIGRATIONS_DIR
-        .files()
-        .map(|file| {
-            (
-                file.path().to_str().expect("valid utf8"),
-                file.contents_utf8().expect("valid utf8"),
-            )
-        })
-        .collect()
-}
-
-/// Splits SQL content into individual statements by semicolon delimiter.
-/// Returns an iterator of non-empty, trimmed statement strings.
-fn split_to_statements(content: &str) -> impl Iterator<Item = &str> {
-    content
-        .split(';')
-        .map(|s| s.trim())
-        .filter(|s| !s.is_empty())
-}
-
-/// Runs all migrations on the provided session.
-/// Expects the session to have the keyspace already set.
-pub async fn run_migrations(session: &Session) -> Result<()> {
-    let migration_files = get_migrations();
-    for (file, content) in migration_files {
-        for statement in split_to_statements(content) {
-            session
-                .query_unpaged(statement, &[])
-                .await
-                .map_err(|e| anyhow::anyhow!("Migration failed for file {}: {}", file, e))?;
-        }
-    }
-    Ok(())
-}
-
 // Test database setup utilities
 //
 // Returns a `Session` and the dedicated `keyspace`
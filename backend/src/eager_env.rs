@@ -1,24 +1,48 @@
 use std::env;
 use std::net::IpAddr;
+use std::num::NonZeroUsize;
+use std::str::FromStr;
 use std::sync::LazyLock;
+use url::Url;
+use uuid::Uuid;
 
 use crate::regions::Region;
+use crate::worker::{HostAllowlist, IpVersionPreference};
+
+/// Value used when an environment variable is missing: panics unless a default is given
+macro_rules! env_var_fallback {
+    ($env_name:expr $(,)?) => {
+        panic!("Missing required environment variable: {}", $env_name)
+    };
+    ($env_name:expr, $default:expr) => {
+        return $default
+    };
+}
+
+/// Parses the value of an environment variable, panicking with a descriptive message.
+///
+/// Concurrency limits are `NonZeroUsize`, so that `0` fails here rather than stalling
+/// `buffer_unordered` at runtime.
+fn parse_env_var<T: FromStr>(env_name: &str, val: &str) -> T {
+    val.parse::<T>().unwrap_or_else(|_| {
+        panic!(
+            "Failed to parse environment variable {} with value '{}' as {}",
+            env_name,
+            val,
+            std::any::type_name::<T>()
+        )
+    })
+}
 
 macro_rules! define_env_vars {
-    ($(($name:ident, $env_name:expr, $type:ty)),* $(,)?) => {
+    ($(($name:ident, $env_name:expr, $type:ty $(, default = $default:expr)?)),* $(,)?) => {
         $(
             pub static $name: LazyLock<$type> = LazyLock::new(|| {
-                let val = env::var($env_name).unwrap_or_else(|_| {
-                    panic!("Missing required environment variable: {}", $env_name)
-                });
-                val.parse::<$type>().unwrap_or_else(|_| {
-                    panic!(
-                        "Failed to parse environment variable {} with value '{}' as {}",
-                        $env_name,
-                        val,
-                        stringify!($type)
-                    )
-                })
+                let val = match env::var($env_name) {
+                    Ok(val) => val,
+                    Err(_) => env_var_fallback!($env_name, $($default)?),
+                };
+                parse_env_var::<$type>($env_name, &val)
             });
         )*
 
@@ -29,9 +53,54 @@ macro_rules! define_env_vars {
                 let _ = *$name;
             )*
         }
+
+        /// Every environment variable as `(name, resolved value)`, in definition order. Values
+        /// are only resolved when called.
+        static ENV_VARS: &[(&str, fn() -> String)] = &[
+            $(
+                ($env_name, || format!("{:?}", *$name)),
+            )*
+        ];
     };
 }
 
+/// Environment variables whose value is never logged
+const SECRET_ENV_VARS: &[&str] = &["BACKEND_INTERNAL_PASSWORD", "PROXY_PASSWORD_KEY"];
+
+fn redact(env_name: &str, value: fn() -> String) -> String {
+    if SECRET_ENV_VARS.contains(&env_name) {
+        "<redacted>".to_string()
+    } else {
+        value()
+    }
+}
+
+/// Resolved value of every environment variable as `(name, value)`, in definition order.
+/// Values of [`SECRET_ENV_VARS`] are redacted.
+pub fn effective_config() -> Vec<(&'static str, String)> {
+    ENV_VARS
+        .iter()
+        .map(|&(name, value)| (name, redact(name, value)))
+        .collect()
+}
+
+/// [`effective_config`] as a single line of space-separated `NAME=value` pairs
+fn format_effective_config(config: &[(&str, String)]) -> String {
+    config
+        .iter()
+        .map(|(name, value)| format!("{name}={value}"))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Logs the resolved configuration in a single line, to be called after [`check_env`]
+pub fn log_effective_config() {
+    log::info!(
+        "effective configuration: {}",
+        format_effective_config(&effective_config())
+    );
+}
+
 // Define all environment variables
 define_env_vars!(
     (PORT, "PORT", u16),
@@ -41,30 +110,397 @@ define_env_vars!(
         "BACKEND_INTERNAL_PASSWORD",
         String
     ),
+    (
+        INTERNODE_REPLAY_WINDOW_SECONDS,
+        "INTERNODE_REPLAY_WINDOW_SECONDS",
+        u64,
+        default = 60
+    ),
+    (
+        RECENT_MUTATIONS_RETENTION_SECONDS,
+        "RECENT_MUTATIONS_RETENTION_SECONDS",
+        u64,
+        default = 300
+    ),
     (DATABASE_NODE_URLS, "DATABASE_NODE_URLS", String),
+    (
+        DATABASE_NODE_URLS_READ,
+        "DATABASE_NODE_URLS_READ",
+        String,
+        default = String::new()
+    ),
     (DATABASE_KEYSPACE, "DATABASE_KEYSPACE", String),
     (
         DATABASE_CONCURRENT_REQUESTS,
         "DATABASE_CONCURRENT_REQUESTS",
-        usize
+        NonZeroUsize
+    ),
+    (
+        DATABASE_CONCURRENT_READS,
+        "DATABASE_CONCURRENT_READS",
+        NonZeroUsize,
+        default = *DATABASE_CONCURRENT_REQUESTS
+    ),
+    (
+        DATABASE_CONCURRENT_WRITES,
+        "DATABASE_CONCURRENT_WRITES",
+        NonZeroUsize,
+        default = *DATABASE_CONCURRENT_REQUESTS
+    ),
+    (
+        DATABASE_PARTITION_TIMEOUT_MILLIS,
+        "DATABASE_PARTITION_TIMEOUT_MILLIS",
+        u64,
+        default = 2000
     ),
     (DATABASE_CONNECTIONS, "DATABASE_CONNECTIONS", usize),
+    (
+        DATABASE_STRICT_REPLICATION,
+        "DATABASE_STRICT_REPLICATION",
+        bool,
+        default = false
+    ),
     (COOKIE_DOMAIN, "COOKIE_DOMAIN", String),
     (DEV_MODE, "DEV_MODE", bool),
     (SESSION_DURATION_DAYS, "SESSION_DURATION_DAYS", i64),
+    (
+        SESSION_EXPIRY_SKEW_SECONDS,
+        "SESSION_EXPIRY_SKEW_SECONDS",
+        i64,
+        default = 5
+    ),
     (FRONTEND_PUBLIC_URL, "FRONTEND_PUBLIC_URL", String),
     (
         HEARTBEAT_INTERVAL_SECONDS,
         "HEARTBEAT_INTERVAL_SECONDS",
         u64
     ),
+    (
+        HEARTBEAT_JITTER_PERCENT,
+        "HEARTBEAT_JITTER_PERCENT",
+        u32,
+        default = 50
+    ),
     (CURRENT_BUCKET_VERSION, "CURRENT_BUCKET_VERSION", u32),
     (CURRENT_BUCKETS_COUNT, "CURRENT_BUCKETS_COUNT", u32),
+    (
+        PREVIOUS_BUCKET_VERSION,
+        "PREVIOUS_BUCKET_VERSION",
+        u32,
+        default = 0
+    ),
+    (
+        PREVIOUS_BUCKETS_COUNT,
+        "PREVIOUS_BUCKETS_COUNT",
+        u32,
+        default = 0
+    ),
     (REPLICATION_FACTOR, "REPLICATION_FACTOR", u32),
     (
         MAX_CONCURRENT_HEALTH_CHECKS,
         "MAX_CONCURRENT_HEALTH_CHECKS",
         usize
     ),
+    (
+        MIN_CONCURRENT_HEALTH_CHECKS,
+        "MIN_CONCURRENT_HEALTH_CHECKS",
+        usize,
+        default = 10
+    ),
+    (
+        PROBE_PRIORITY_BY_FREQUENCY,
+        "PROBE_PRIORITY_BY_FREQUENCY",
+        bool,
+        default = true
+    ),
     (REGION, "REGION", Region),
+    (
+        METRICS_MAX_HOURLY_DAYS,
+        "METRICS_MAX_HOURLY_DAYS",
+        i64,
+        default = 14
+    ),
+    (
+        METRICS_MAX_DAILY_DAYS,
+        "METRICS_MAX_DAILY_DAYS",
+        i64,
+        default = 365
+    ),
+    (
+        METRICS_MAX_GRAPH_POINTS,
+        "METRICS_MAX_GRAPH_POINTS",
+        i64,
+        default = 500
+    ),
+    (REPLICA_ID, "REPLICA_ID", String, default = String::new()),
+    (
+        PROXY_REMOTE_DNS_ALLOWED_HOSTS,
+        "PROXY_REMOTE_DNS_ALLOWED_HOSTS",
+        HostAllowlist,
+        default = HostAllowlist::default()
+    ),
+    (
+        PROXY_PASSWORD_KEY,
+        "PROXY_PASSWORD_KEY",
+        String,
+        default = String::new()
+    ),
+    (
+        IP_VERSION_PREFERENCE,
+        "IP_VERSION_PREFERENCE",
+        IpVersionPreference,
+        default = IpVersionPreference::Any
+    ),
+    (
+        SCHEDULING_LAG_THRESHOLD_MILLIS,
+        "SCHEDULING_LAG_THRESHOLD_MILLIS",
+        u64,
+        default = 5_000
+    ),
+    (
+        SCHEDULING_LAG_WINDOW_SECONDS,
+        "SCHEDULING_LAG_WINDOW_SECONDS",
+        u64,
+        default = 60
+    ),
+    (
+        EGRESS_SELF_TEST_URL,
+        "EGRESS_SELF_TEST_URL",
+        String,
+        default = String::new()
+    ),
+    (
+        PROBE_BIND_ADDRESS,
+        "PROBE_BIND_ADDRESS",
+        String,
+        default = String::new()
+    ),
+    (
+        EGRESS_SELF_TEST_REQUIRED,
+        "EGRESS_SELF_TEST_REQUIRED",
+        bool,
+        default = false
+    ),
+    (
+        SELF_CHECK_URL,
+        "SELF_CHECK_URL",
+        String,
+        default = String::new()
+    ),
+    (
+        SELF_CHECK_FREQUENCY_SECONDS,
+        "SELF_CHECK_FREQUENCY_SECONDS",
+        i32,
+        default = 60
+    ),
+    (
+        SELF_CHECK_VIEWER_USER_ID,
+        "SELF_CHECK_VIEWER_USER_ID",
+        String,
+        default = String::new()
+    ),
+    (
+        SCHEDULING_STARVATION_FAILS_HEALTH,
+        "SCHEDULING_STARVATION_FAILS_HEALTH",
+        bool,
+        default = false
+    ),
+    (
+        CIRCUIT_BREAKER_FAILURE_THRESHOLD,
+        "CIRCUIT_BREAKER_FAILURE_THRESHOLD",
+        u32,
+        default = 0
+    ),
+    (
+        CIRCUIT_BREAKER_MAX_INTERVAL_SECONDS,
+        "CIRCUIT_BREAKER_MAX_INTERVAL_SECONDS",
+        u64,
+        default = 900
+    ),
+    (
+        FLAP_WINDOW_SECONDS,
+        "FLAP_WINDOW_SECONDS",
+        u64,
+        default = 600
+    ),
+    (
+        FLAP_MAX_STATE_CHANGES,
+        "FLAP_MAX_STATE_CHANGES",
+        u32,
+        default = 5
+    ),
+    (
+        MAX_PROBE_TIMEOUT_SECONDS,
+        "MAX_PROBE_TIMEOUT_SECONDS",
+        u64,
+        default = 30
+    ),
+    (
+        PERCENTILE_MIN_SAMPLES,
+        "PERCENTILE_MIN_SAMPLES",
+        u32,
+        default = 5
+    ),
+    (
+        PASSIVE_CHECK_GRACE_SECONDS,
+        "PASSIVE_CHECK_GRACE_SECONDS",
+        u64,
+        default = 60
+    ),
+    (
+        PING_FLUSH_INTERVAL_SECONDS,
+        "PING_FLUSH_INTERVAL_SECONDS",
+        u64,
+        default = 5
+    ),
+    (
+        STARTUP_GRACE_SECONDS,
+        "STARTUP_GRACE_SECONDS",
+        u64,
+        default = 0
+    ),
+    (
+        RESULT_SAMPLING_AFTER_HOURS,
+        "RESULT_SAMPLING_AFTER_HOURS",
+        u64,
+        default = 0
+    ),
+    (
+        RESULT_SAMPLING_INTERVAL_SECONDS,
+        "RESULT_SAMPLING_INTERVAL_SECONDS",
+        u64,
+        default = 60
+    ),
+    (
+        ALLOW_PRIVATE_TARGETS,
+        "ALLOW_PRIVATE_TARGETS",
+        bool,
+        default = false
+    ),
+    (
+        UNIQUE_CHECK_NAMES,
+        "UNIQUE_CHECK_NAMES",
+        bool,
+        default = false
+    ),
+    (
+        MAX_REGIONS_PER_CHECK,
+        "MAX_REGIONS_PER_CHECK",
+        usize,
+        default = usize::MAX
+    ),
 );
+
+/// Stable identifier of this deployment replica, surviving restarts. `None` when unset.
+pub fn replica_id() -> Option<&'static str> {
+    Some(REPLICA_ID.as_str()).filter(|id| !id.is_empty())
+}
+
+/// Known-good URL probed at startup to confirm outbound requests work. `None` when unset.
+pub fn egress_self_test_url() -> Option<Url> {
+    Some(EGRESS_SELF_TEST_URL.as_str())
+        .filter(|url| !url.is_empty())
+        .map(|url| {
+            url.parse()
+                .unwrap_or_else(|_| panic!("Invalid EGRESS_SELF_TEST_URL: '{url}'"))
+        })
+}
+
+/// AES-256 key proxy passwords are encrypted with before being stored. `None` when unset.
+pub fn proxy_password_key() -> Option<[u8; 32]> {
+    Some(PROXY_PASSWORD_KEY.as_str())
+        .filter(|key| !key.is_empty())
+        .map(|key| {
+            openssl::base64::decode_block(key)
+                .ok()
+                .and_then(|key| key.try_into().ok())
+                .unwrap_or_else(|| panic!("PROXY_PASSWORD_KEY must be 32 bytes encoded in base64"))
+        })
+}
+
+/// Target of the check monitoring this node, registered at startup. `None` when unset.
+pub fn self_check_url() -> Option<Url> {
+    Some(SELF_CHECK_URL.as_str())
+        .filter(|url| !url.is_empty())
+        .map(|url| {
+            url.parse()
+                .unwrap_or_else(|_| panic!("Invalid SELF_CHECK_URL: '{url}'"))
+        })
+}
+
+/// User shown the self-check of this node. `None` when unset.
+pub fn self_check_viewer() -> Option<Uuid> {
+    Some(SELF_CHECK_VIEWER_USER_ID.as_str())
+        .filter(|id| !id.is_empty())
+        .map(|id| {
+            id.parse()
+                .unwrap_or_else(|_| panic!("Invalid SELF_CHECK_VIEWER_USER_ID: '{id}'"))
+        })
+}
+
+/// Local address probes egress from, e.g. on multi-homed hosts. `None` when unset.
+pub fn probe_bind_address() -> Option<IpAddr> {
+    Some(PROBE_BIND_ADDRESS.as_str())
+        .filter(|address| !address.is_empty())
+        .map(|address| {
+            address
+                .parse()
+                .unwrap_or_else(|_| panic!("Invalid PROBE_BIND_ADDRESS: '{address}'"))
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use itertools::Itertools;
+
+    /// Parses `val` as the type of `_var`, without initializing it
+    fn parse_as<T: FromStr>(_var: &LazyLock<T>, val: &str) -> Result<T, T::Err> {
+        val.parse()
+    }
+
+    #[test]
+    fn test_database_concurrency_must_be_positive() {
+        assert!(parse_as(&DATABASE_CONCURRENT_REQUESTS, "0").is_err());
+        assert!(parse_as(&DATABASE_CONCURRENT_READS, "0").is_err());
+        assert!(parse_as(&DATABASE_CONCURRENT_WRITES, "0").is_err());
+        assert_eq!(
+            parse_as(&DATABASE_CONCURRENT_REQUESTS, "16").map(NonZeroUsize::get),
+            Ok(16)
+        );
+    }
+
+    #[test]
+    fn test_effective_config() {
+        assert_eq!(
+            ENV_VARS.len(),
+            ENV_VARS.iter().map(|(name, _)| name).unique().count()
+        );
+        // Only resolves the listed variable
+        let listed = |name: &str| {
+            ENV_VARS
+                .iter()
+                .find(|(n, _)| *n == name)
+                .map(|&(name, value)| redact(name, value))
+        };
+
+        assert_eq!(listed("PORT"), Some(PORT.to_string()));
+        let password = listed("BACKEND_INTERNAL_PASSWORD").unwrap();
+        assert_eq!(password, "<redacted>");
+
+        let line = format_effective_config(&[
+            ("PORT", listed("PORT").unwrap()),
+            ("BACKEND_INTERNAL_PASSWORD", password),
+        ]);
+        assert_eq!(
+            line,
+            format!("PORT={} BACKEND_INTERNAL_PASSWORD=<redacted>", *PORT)
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "DATABASE_CONCURRENT_REQUESTS with value '0'")]
+    fn test_parse_env_var_fails_fast_on_zero() {
+        parse_env_var::<NonZeroUsize>("DATABASE_CONCURRENT_REQUESTS", "0");
+    }
+}
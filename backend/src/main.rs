@@ -1,24 +1,38 @@
+mod cli;
+mod clock;
 mod collab;
 mod database;
 mod eager_env;
 mod queries;
 mod regions;
 mod server;
+mod single_flight;
 mod utils;
 mod worker;
 
 use crate::{
+    clock::{Clock, SystemClock},
     collab::{
-        decide_position,
+        PreviousBuckets, decide_position,
         heartbeat::HeartbeatManager,
-        internode::{MessageWithFilters, messages::InterNodeMessage, standard_broadcast},
+        internode::{
+            MessageWithFilters,
+            messages::InterNodeMessage,
+            recent::{RecentMutations, rebroadcast_task_body},
+            replay::ReplayGuard,
+            standard_broadcast,
+        },
         range_manager::RangeManager,
     },
-    database::{connect_db, parse_database_urls},
-    eager_env::check_env,
+    database::{connect_db, parse_database_urls, replication::check_keyspace_replication},
+    eager_env::{check_env, log_effective_config},
+    queries::{cluster::get_probing_enabled, pings::PingBatcher},
     regions::Region,
     server::{AppStateInner, start_server},
-    worker::Worker,
+    worker::{
+        EGRESS_SELF_TEST_RETRY_INTERVAL, SelfCheckConfig, Worker, egress_readiness,
+        register_self_check,
+    },
 };
 use anyhow::Result;
 use std::{
@@ -26,15 +40,17 @@ use std::{
     sync::Arc,
     time::Duration,
 };
-use tokio::sync::mpsc;
+use tokio::sync::{mpsc, watch};
 use uuid::Uuid;
 
 async fn communicate_shutdown(
     heartbeat: Arc<HeartbeatManager>,
+    clock: &dyn Clock,
     process_id: Uuid,
 ) -> Result<Vec<SocketAddr>> {
     let (ips, _) = standard_broadcast(
-        &heartbeat,
+        &*heartbeat,
+        clock,
         vec![MessageWithFilters {
             message: InterNodeMessage::ShuttingDown { process_id },
             filter_bucket: None,
@@ -49,7 +65,16 @@ async fn main() {
     env_logger::builder()
         .format_timestamp(Some(env_logger::TimestampPrecision::Millis))
         .init();
+    let command = match cli::parse_args(std::env::args().skip(1)) {
+        Ok(command) => command,
+        Err(e) => {
+            eprintln!("{e}\n\n{}", cli::USAGE);
+            std::process::exit(2);
+        }
+    };
+
     check_env();
+    log_effective_config();
 
     let process_id = Uuid::new_v4();
     let node_urls = parse_database_urls(&eager_env::DATABASE_NODE_URLS);
@@ -58,13 +83,39 @@ async fn main() {
     let database = connect_db(&node_urls, &eager_env::DATABASE_KEYSPACE)
         .await
         .expect("failed to connect to the database");
+
+    if let Some(command) = command {
+        if let Err(e) = cli::run(command, &database).await {
+            eprintln!("{e:?}");
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    check_keyspace_replication(
+        &database,
+        &eager_env::DATABASE_KEYSPACE,
+        *eager_env::DATABASE_STRICT_REPLICATION,
+    )
+    .await
+    .expect("keyspace replication check failed");
     let database = Arc::new(database);
 
+    let read_replica = match parse_database_urls(&eager_env::DATABASE_NODE_URLS_READ).as_slice() {
+        [] => None,
+        read_urls => Some(Arc::new(
+            connect_db(read_urls, &eager_env::DATABASE_KEYSPACE)
+                .await
+                .expect("failed to connect to the read replica"),
+        )),
+    };
+
     let heartbeat = HeartbeatManager::new(
         process_id,
         region,
         Duration::from_secs(*eager_env::HEARTBEAT_INTERVAL_SECONDS),
         database.clone(),
+        eager_env::replica_id(),
     )
     .await
     .expect("msg");
@@ -72,18 +123,24 @@ async fn main() {
 
     let range_manager = RangeManager::new(process_id, *eager_env::REPLICATION_FACTOR, region);
 
-    let position = decide_position(&heartbeat, *eager_env::CURRENT_BUCKETS_COUNT)
-        .await
-        .expect("msg");
+    let position = decide_position(
+        &heartbeat,
+        *eager_env::CURRENT_BUCKETS_COUNT,
+        eager_env::replica_id(),
+    )
+    .await
+    .expect("msg");
 
     let (task_updates_sender, task_updates_receiver) = mpsc::unbounded_channel();
 
-    let state = Arc::new(AppStateInner {
-        process_id,
-        database: database.clone(),
-        task_updates: task_updates_sender,
-        heartbeat_manager: heartbeat.clone(),
-    });
+    let probing_enabled = get_probing_enabled(&database)
+        .await
+        .expect("failed to read probing flag");
+    if !probing_enabled {
+        log::warn!("probing is paused cluster-wide");
+    }
+    let (probing_enabled_sender, probing_enabled_receiver) = watch::channel(probing_enabled);
+
     let listener =
         TcpListener::bind(format!("0.0.0.0:{}", *eager_env::PORT)).expect("Failed to bind PORT");
 
@@ -94,26 +151,100 @@ async fn main() {
 
     let (alive_nodes_receiver, stop_heartbeat) = heartbeat.start(position).await.unwrap();
 
+    let clock = SystemClock::shared();
+
+    let recent_mutations = Arc::new(RecentMutations::new(Duration::from_secs(
+        *eager_env::RECENT_MUTATIONS_RETENTION_SECONDS,
+    )));
+    let rebroadcast_task = tokio::spawn(rebroadcast_task_body(
+        alive_nodes_receiver.clone(),
+        recent_mutations.clone(),
+        clock.clone(),
+    ));
+
     let (stop_range_manager, range_updates) = range_manager.start(alive_nodes_receiver).await;
 
     let worker = Worker::new(
         database.clone(),
+        clock.clone(),
         region,
         *eager_env::CURRENT_BUCKET_VERSION as i16,
         *eager_env::CURRENT_BUCKETS_COUNT,
         range_updates,
         task_updates_receiver,
+        probing_enabled_receiver,
     )
     .await
     .expect("worker initialization failed");
 
+    let (ready, egress_self_test_task) = match eager_env::egress_self_test_url() {
+        Some(url) => {
+            let (ready, task) = egress_readiness(
+                url,
+                region,
+                *eager_env::DEV_MODE,
+                *eager_env::EGRESS_SELF_TEST_REQUIRED,
+                EGRESS_SELF_TEST_RETRY_INTERVAL,
+            );
+            (ready, Some(tokio::spawn(task)))
+        }
+        None => (watch::channel(true).1, None),
+    };
+
+    let ping_batcher = PingBatcher::from_env().map(Arc::new);
+    let ping_flush_task = ping_batcher
+        .clone()
+        .map(|batcher| tokio::spawn(batcher.flush_task_body(database.clone())));
+
+    let state = Arc::new(AppStateInner {
+        process_id,
+        database: database.clone(),
+        read_replica,
+        task_updates: task_updates_sender,
+        heartbeat_manager: heartbeat.clone(),
+        worker_status: worker.status(),
+        probing_enabled: probing_enabled_sender,
+        clock: clock.clone(),
+        ready,
+        private_targets_allowed: *eager_env::ALLOW_PRIVATE_TARGETS,
+        unique_check_names: *eager_env::UNIQUE_CHECK_NAMES,
+        max_regions_per_check: *eager_env::MAX_REGIONS_PER_CHECK,
+        replay_guard: ReplayGuard::new(Duration::from_secs(
+            *eager_env::INTERNODE_REPLAY_WINDOW_SECONDS,
+        )),
+        recent_mutations,
+        ip_version_preference: *eager_env::IP_VERSION_PREFERENCE,
+        ping_batcher: ping_batcher.clone(),
+        previous_buckets: PreviousBuckets::from_env(),
+    });
+
+    let worker = match SelfCheckConfig::from_env() {
+        Some(config) => {
+            let node = match eager_env::replica_id() {
+                Some(replica_id) => replica_id.to_string(),
+                None => format!("{}:{}", *eager_env::SELF_IP, *eager_env::PORT),
+            };
+            match register_self_check(&database, region, &node, &config).await {
+                Ok(check) => {
+                    log::info!("self-check {} registered", check.check_id);
+                    worker.with_self_check(check.check_id)
+                }
+                Err(e) => {
+                    log::error!("failed to register the self-check: {e:?}");
+                    worker
+                }
+            }
+        }
+        None => worker,
+    };
+
     let stop_worker = worker.start();
 
     start_server(state, listener)
         .await
         .expect("error while running server");
 
-    match communicate_shutdown(heartbeat.clone(), process_id).await {
+    match communicate_shutdown(heartbeat.clone(), &*clock, process_id).await {
         Err(e) => {
             log::error!("failed to communicate shutdown: {:?}", e);
         }
@@ -122,6 +253,16 @@ async fn main() {
         }
     }
 
+    rebroadcast_task.abort();
+    if let Some(task) = egress_self_test_task {
+        task.abort();
+    }
+    if let Some((task, batcher)) = ping_flush_task.zip(ping_batcher) {
+        task.abort();
+        if let Err(e) = batcher.flush(&database).await {
+            log::error!("failed to flush the pending pings: {:?}", e);
+        }
+    }
     stop_heartbeat.await;
     stop_range_manager();
     stop_worker.await;
@@ -0,0 +1,203 @@
+use crate::database::preparer::CachedPreparedStatement;
+use anyhow::Result;
+use scylla::client::session::Session;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+/// Sends the alerts of the checks tagged `tag: value` to `webhook_url`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct AlertRoute {
+    pub tag: String,
+    /// Any value of `tag` matches when unset
+    #[serde(default)]
+    pub value: Option<String>,
+    pub webhook_url: String,
+}
+
+impl AlertRoute {
+    fn matches(&self, tags: &HashMap<String, String>) -> bool {
+        tags.get(&self.tag)
+            .is_some_and(|value| self.value.as_ref().is_none_or(|expected| expected == value))
+    }
+}
+
+/// Where a user's alerts go, depending on the tags of the check
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct AlertRouting {
+    #[serde(default)]
+    pub routes: Vec<AlertRoute>,
+    /// Receives the alerts of the checks no route matches
+    #[serde(default)]
+    pub default_webhook_url: Option<String>,
+}
+
+impl AlertRouting {
+    /// Routes matching one of `tags`, in order
+    pub fn matching_routes(&self, tags: &HashMap<String, String>) -> Vec<&AlertRoute> {
+        self.routes
+            .iter()
+            .filter(|route| route.matches(tags))
+            .collect()
+    }
+
+    /// Webhooks notified for a check tagged `tags`: those of every matching route, in order and
+    /// without duplicates, or the default one if none matches.
+    pub fn destinations(&self, tags: &HashMap<String, String>) -> Vec<&str> {
+        let mut destinations = Vec::new();
+        for route in self.matching_routes(tags) {
+            if !destinations.contains(&route.webhook_url.as_str()) {
+                destinations.push(route.webhook_url.as_str());
+            }
+        }
+
+        if destinations.is_empty() {
+            destinations.extend(self.default_webhook_url.as_deref());
+        }
+        destinations
+    }
+}
+
+static GET_ALERT_ROUTING_QUERY: CachedPreparedStatement = CachedPreparedStatement::new(
+    "
+    SELECT routes,
+           default_webhook_url
+    FROM alert_routing
+    WHERE user_id = ?
+    ",
+);
+
+/// Alert routing of a user, empty if never set
+pub async fn get_alert_routing(session: &Session, user_id: Uuid) -> Result<AlertRouting> {
+    let row = GET_ALERT_ROUTING_QUERY
+        .execute_unpaged(session, (user_id,))
+        .await?
+        .into_rows_result()?
+        .maybe_first_row::<(Option<String>, Option<String>)>()?;
+
+    let Some((routes, default_webhook_url)) = row else {
+        return Ok(AlertRouting::default());
+    };
+
+    Ok(AlertRouting {
+        routes: routes
+            .map(|routes| serde_json::from_str(&routes))
+            .transpose()?
+            .unwrap_or_default(),
+        default_webhook_url,
+    })
+}
+
+static SET_ALERT_ROUTING_QUERY: CachedPreparedStatement = CachedPreparedStatement::new(
+    "
+    INSERT INTO alert_routing (user_id, routes, default_webhook_url)
+    VALUES (?, ?, ?)
+    ",
+);
+
+/// Replace the alert routing of a user
+pub async fn set_alert_routing(
+    session: &Session,
+    user_id: Uuid,
+    routing: &AlertRouting,
+) -> Result<()> {
+    SET_ALERT_ROUTING_QUERY
+        .execute_unpaged(
+            session,
+            (
+                user_id,
+                serde_json::to_string(&routing.routes)?,
+                &routing.default_webhook_url,
+            ),
+        )
+        .await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::testing::create_test_database;
+
+    fn routing() -> AlertRouting {
+        AlertRouting {
+            routes: vec![
+                AlertRoute {
+                    tag: "team".to_string(),
+                    value: Some("payments".to_string()),
+                    webhook_url: "https://hooks.example.com/payments".to_string(),
+                },
+                AlertRoute {
+                    tag: "critical".to_string(),
+                    value: None,
+                    webhook_url: "https://hooks.example.com/pager".to_string(),
+                },
+            ],
+            default_webhook_url: Some("https://hooks.example.com/all".to_string()),
+        }
+    }
+
+    #[test]
+    fn test_destinations() {
+        let routing = routing();
+        let tags = |pairs: &[(&str, &str)]| -> HashMap<String, String> {
+            pairs
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect()
+        };
+
+        assert_eq!(
+            routing.destinations(&tags(&[("team", "payments")])),
+            ["https://hooks.example.com/payments"]
+        );
+        assert_eq!(
+            routing.destinations(&tags(&[("team", "payments"), ("critical", "yes")])),
+            [
+                "https://hooks.example.com/payments",
+                "https://hooks.example.com/pager"
+            ]
+        );
+
+        // Untagged checks and unmatched values fall back to the default
+        assert_eq!(
+            routing.destinations(&HashMap::new()),
+            ["https://hooks.example.com/all"]
+        );
+        assert_eq!(
+            routing.destinations(&tags(&[("team", "search")])),
+            ["https://hooks.example.com/all"]
+        );
+
+        // Nowhere to send them without a default
+        let routing = AlertRouting {
+            default_webhook_url: None,
+            ..routing
+        };
+        assert!(routing.destinations(&HashMap::new()).is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_set_and_get_alert_routing() -> Result<()> {
+        let (session, _keyspace) = create_test_database(None).await?;
+        let user_id = Uuid::new_v4();
+
+        assert_eq!(
+            get_alert_routing(&session, user_id).await?,
+            AlertRouting::default()
+        );
+
+        set_alert_routing(&session, user_id, &routing()).await?;
+        assert_eq!(get_alert_routing(&session, user_id).await?, routing());
+
+        set_alert_routing(&session, user_id, &AlertRouting::default()).await?;
+        assert_eq!(
+            get_alert_routing(&session, user_id).await?,
+            AlertRouting::default()
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,191 @@
+use crate::database::preparer::CachedPreparedStatement;
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use scylla::{DeserializeRow, client::session::Session};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+/// Note left on a time range of a check, e.g. a deploy or a maintenance window
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema, DeserializeRow)]
+pub struct CheckAnnotation {
+    pub annotation_id: Uuid,
+    pub check_id: Uuid,
+    pub text: String,
+    /// Start of the annotated range, included
+    pub starts_at: DateTime<Utc>,
+    /// End of the annotated range, included. Equal to `starts_at` for a single instant
+    pub ends_at: DateTime<Utc>,
+    pub author_id: Uuid,
+    pub author_username: String,
+    pub created_at: DateTime<Utc>,
+}
+
+impl CheckAnnotation {
+    /// Whether the annotated range overlaps `[from, to)`
+    pub fn overlaps(&self, from: DateTime<Utc>, to: DateTime<Utc>) -> bool {
+        self.starts_at < to && self.ends_at >= from
+    }
+}
+
+static CREATE_ANNOTATION_QUERY: CachedPreparedStatement = CachedPreparedStatement::new(
+    "
+    INSERT INTO check_annotations (check_id,
+                                   starts_at,
+                                   annotation_id,
+                                   ends_at,
+                                   text,
+                                   author_id,
+                                   author_username,
+                                   created_at)
+    VALUES (?, ?, ?, ?, ?, ?, ?, ?)
+    ",
+);
+
+/// Create an annotation on a check
+pub async fn create_annotation(session: &Session, annotation: &CheckAnnotation) -> Result<()> {
+    CREATE_ANNOTATION_QUERY
+        .execute_unpaged(
+            session,
+            (
+                annotation.check_id,
+                annotation.starts_at,
+                annotation.annotation_id,
+                annotation.ends_at,
+                &annotation.text,
+                annotation.author_id,
+                &annotation.author_username,
+                annotation.created_at,
+            ),
+        )
+        .await?;
+
+    Ok(())
+}
+
+static LIST_ANNOTATIONS_QUERY: CachedPreparedStatement = CachedPreparedStatement::new(
+    "
+    SELECT annotation_id,
+           check_id,
+           text,
+           starts_at,
+           ends_at,
+           author_id,
+           author_username,
+           created_at
+    FROM check_annotations
+    WHERE check_id = ?
+      AND starts_at < ?
+    ",
+);
+
+/// List the annotations of a check overlapping `[from, to)`, ordered by start
+pub async fn list_annotations(
+    session: &Session,
+    check_id: Uuid,
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+) -> Result<Vec<CheckAnnotation>> {
+    let result = LIST_ANNOTATIONS_QUERY
+        .execute_unpaged(session, (check_id, to))
+        .await?
+        .into_rows_result()?;
+
+    // Ranges have no upper bound on their length, so the end can't be filtered by the query
+    let mut annotations = Vec::new();
+    for row in result.rows::<CheckAnnotation>()? {
+        let annotation = row?;
+        if annotation.overlaps(from, to) {
+            annotations.push(annotation);
+        }
+    }
+
+    Ok(annotations)
+}
+
+static DELETE_ANNOTATIONS_QUERY: CachedPreparedStatement =
+    CachedPreparedStatement::new("DELETE FROM check_annotations WHERE check_id = ?");
+
+/// Delete every annotation of a check
+pub async fn delete_annotations(session: &Session, check_id: Uuid) -> Result<()> {
+    DELETE_ANNOTATIONS_QUERY
+        .execute_unpaged(session, (check_id,))
+        .await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::testing::create_test_database;
+    use chrono::{Duration, DurationRound};
+
+    #[tokio::test]
+    async fn test_create_and_list_annotations() -> Result<()> {
+        let (session, _keyspace) = create_test_database(None).await?;
+
+        let check_id = Uuid::new_v4();
+        let now = Utc::now().duration_trunc(Duration::milliseconds(1))?;
+        let annotation = |starts_at, ends_at| CheckAnnotation {
+            annotation_id: Uuid::new_v4(),
+            check_id,
+            text: "Deploy".to_string(),
+            starts_at,
+            ends_at,
+            author_id: Uuid::new_v4(),
+            author_username: "alice".to_string(),
+            created_at: now,
+        };
+
+        let deploy = annotation(now - Duration::hours(5), now - Duration::hours(5));
+        let maintenance = annotation(now - Duration::hours(10), now - Duration::hours(2));
+        let incident = annotation(now - Duration::minutes(30), now);
+        for annotation in [&deploy, &maintenance, &incident] {
+            create_annotation(&session, annotation).await?;
+        }
+        // Annotations of other checks are never listed
+        create_annotation(
+            &session,
+            &CheckAnnotation {
+                check_id: Uuid::new_v4(),
+                ..deploy.clone()
+            },
+        )
+        .await?;
+
+        let all = list_annotations(&session, check_id, now - Duration::days(1), now).await?;
+        assert_eq!(all, [maintenance.clone(), deploy.clone(), incident.clone()]);
+
+        // Point annotations are only listed by ranges containing them
+        let listed = list_annotations(
+            &session,
+            check_id,
+            now - Duration::hours(4),
+            now + Duration::hours(1),
+        )
+        .await?;
+        assert_eq!(listed, [maintenance.clone(), incident.clone()]);
+
+        // Ranges ending where an annotation starts don't overlap it, ones starting where it ends do
+        let listed = list_annotations(
+            &session,
+            check_id,
+            now - Duration::hours(12),
+            now - Duration::hours(10),
+        )
+        .await?;
+        assert!(listed.is_empty());
+
+        let listed = list_annotations(
+            &session,
+            check_id,
+            now - Duration::hours(2),
+            now - Duration::hours(1),
+        )
+        .await?;
+        assert_eq!(listed, [maintenance]);
+
+        Ok(())
+    }
+}
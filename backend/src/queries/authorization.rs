@@ -1,5 +1,6 @@
 use crate::database::preparer::CachedPreparedStatement;
 use anyhow::Result;
+use chrono::Utc;
 use scylla::client::session::Session;
 use serde::{Deserialize, Serialize};
 use utoipa::ToSchema;
@@ -77,6 +78,24 @@ pub async fn grant_check_access(
     Ok(())
 }
 
+static REVOKE_CHECK_ACCESS_QUERY: CachedPreparedStatement = CachedPreparedStatement::new(
+    "
+    DELETE
+    FROM access_by_check
+    WHERE check_id = ?
+      AND user_id = ?
+    ",
+);
+
+/// Revoke all access of a user to a check
+pub async fn revoke_check_access(session: &Session, check_id: Uuid, user_id: Uuid) -> Result<()> {
+    REVOKE_CHECK_ACCESS_QUERY
+        .execute_unpaged(session, (check_id, user_id))
+        .await?;
+
+    Ok(())
+}
+
 static GET_USER_CHECKS_QUERY: CachedPreparedStatement = CachedPreparedStatement::new(
     "
     SELECT check_id,
@@ -105,6 +124,49 @@ pub async fn get_user_checks(session: &Session, user_id: Uuid) -> Result<Vec<(Uu
     Ok(checks)
 }
 
+static SET_CHECK_READ_TOKEN_QUERY: CachedPreparedStatement = CachedPreparedStatement::new(
+    "
+    INSERT INTO check_read_tokens (check_id,
+                                   read_token,
+                                   created_at)
+    VALUES (?, ?, ?)
+    ",
+);
+
+/// Creates a new read token for a check, replacing the previous one
+pub async fn rotate_check_read_token(session: &Session, check_id: Uuid) -> Result<Uuid> {
+    let read_token = Uuid::new_v4();
+
+    SET_CHECK_READ_TOKEN_QUERY
+        .execute_unpaged(session, (check_id, read_token, Utc::now()))
+        .await?;
+
+    Ok(read_token)
+}
+
+static GET_CHECK_READ_TOKEN_QUERY: CachedPreparedStatement = CachedPreparedStatement::new(
+    "
+    SELECT read_token
+    FROM check_read_tokens
+    WHERE check_id = ?
+    ",
+);
+
+/// Whether `read_token` grants unauthenticated read access to the check's public data
+pub async fn is_valid_check_read_token(
+    session: &Session,
+    check_id: Uuid,
+    read_token: Uuid,
+) -> Result<bool> {
+    let stored = GET_CHECK_READ_TOKEN_QUERY
+        .execute_unpaged(session, (check_id,))
+        .await?
+        .into_rows_result()?
+        .maybe_first_row::<(Uuid,)>()?;
+
+    Ok(stored.is_some_and(|(stored,)| stored == read_token))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -162,6 +224,13 @@ mod tests {
         assert!(access.can_edit);
         assert!(access.can_see);
 
+        revoke_check_access(&session, check_id, user_id).await?;
+        assert!(
+            get_user_access_to_check(&session, user_id, check_id)
+                .await?
+                .is_none()
+        );
+
         Ok(())
     }
 
@@ -201,4 +270,22 @@ mod tests {
 
         Ok(())
     }
+
+    #[tokio::test]
+    async fn test_check_read_token() -> Result<()> {
+        let (session, _keyspace) = create_test_database(None).await?;
+
+        let check_id = Uuid::new_v4();
+        assert!(!is_valid_check_read_token(&session, check_id, Uuid::new_v4()).await?);
+
+        let first = rotate_check_read_token(&session, check_id).await?;
+        assert!(is_valid_check_read_token(&session, check_id, first).await?);
+
+        // Rotating revokes the previous token
+        let second = rotate_check_read_token(&session, check_id).await?;
+        assert!(!is_valid_check_read_token(&session, check_id, first).await?);
+        assert!(is_valid_check_read_token(&session, check_id, second).await?);
+
+        Ok(())
+    }
 }
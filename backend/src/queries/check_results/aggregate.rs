@@ -0,0 +1,323 @@
+use super::queries::CheckResultRow;
+use super::{MetricsSummary, ResponseTimeUnit};
+use crate::eager_env;
+use chrono::{DateTime, Duration, Utc};
+use std::collections::{BTreeMap, HashMap};
+
+/// Growth between two buckets of [`PeriodAggregate::response_time_sketch`], percentiles read
+/// from the sketch are within 1% of the exact ones
+const SKETCH_GAMMA: f64 = 1.02;
+
+/// Bucket of the sketch holding `micros`, `0` for sub-microsecond times
+fn sketch_bucket(micros: i64) -> i32 {
+    if micros < 1 {
+        return 0;
+    }
+    ((micros as f64).ln() / SKETCH_GAMMA.ln()).floor() as i32 + 1
+}
+
+/// Middle of the bounds of `bucket`, see [`sketch_bucket`]
+fn sketch_value(bucket: i32) -> i64 {
+    if bucket == 0 {
+        return 0;
+    }
+    SKETCH_GAMMA.powf(f64::from(bucket) - 0.5) as i64
+}
+
+/// Mergeable summary of the results of a region within an hourly or daily point, for the checks
+/// not storing raw results.
+///
+/// Kept small whatever the number of results: counts, sums, bounds, and a logarithmic histogram
+/// of response times for the percentiles.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PeriodAggregate {
+    pub(super) successful_checks: u32,
+    pub(super) total_checks: u32,
+    pub(super) response_time_sum_micros: i64,
+    pub(super) min_response_time_micros: i64,
+    pub(super) max_response_time_micros: i64,
+    /// Number of response times by bucket, see [`sketch_bucket`]
+    pub(super) response_time_sketch: BTreeMap<i32, u32>,
+    pub(super) ttfb_sum_micros: i64,
+    pub(super) ttfb_samples: u32,
+    pub(super) response_size_sum_bytes: i64,
+    pub(super) response_size_samples: u32,
+    pub(super) max_response_size_bytes: Option<i64>,
+    pub(super) status_code_counts: HashMap<i32, u32>,
+    /// First and last results, the time-weighted uptime spans between them
+    pub(super) first_result_at: Option<DateTime<Utc>>,
+    pub(super) last_result_at: Option<DateTime<Utc>>,
+    pub(super) last_result_up: bool,
+    /// Time between the first and last results following a successful one
+    pub(super) up_duration: Duration,
+}
+
+impl PeriodAggregate {
+    pub fn record(&mut self, result: &CheckResultRow) {
+        let weight = result.sample_weight.max(1) as u32;
+        let mut single = PeriodAggregate {
+            successful_checks: u32::from(result.matches_expected) * weight,
+            total_checks: weight,
+            response_time_sum_micros: result.response_time_micros * i64::from(weight),
+            min_response_time_micros: result.response_time_micros,
+            max_response_time_micros: result.response_time_micros,
+            response_time_sketch: BTreeMap::from([(
+                sketch_bucket(result.response_time_micros),
+                weight,
+            )]),
+            first_result_at: Some(result.check_started_at),
+            last_result_at: Some(result.check_started_at),
+            last_result_up: result.matches_expected,
+            ..Default::default()
+        };
+        if let Some(ttfb) = result.ttfb_micros {
+            single.ttfb_sum_micros = ttfb * i64::from(weight);
+            single.ttfb_samples = weight;
+        }
+        if let Some(size) = result.response_size_bytes {
+            single.response_size_sum_bytes = size * i64::from(weight);
+            single.response_size_samples = weight;
+            single.max_response_size_bytes = Some(size);
+        }
+        if let Some(status_code) = result.status_code {
+            single.status_code_counts.insert(status_code, weight);
+        }
+
+        self.merge(single);
+    }
+
+    /// Adds the results summarized by `other`, e.g. those of another node probing the same check
+    /// during the same point.
+    ///
+    /// The time-weighted uptime of overlapping aggregates is approximate.
+    pub fn merge(&mut self, mut other: PeriodAggregate) {
+        if other.total_checks == 0 {
+            return;
+        }
+        if self.total_checks == 0 {
+            *self = other;
+            return;
+        }
+
+        // `self` is the earliest of the two from here on
+        if other.first_result_at < self.first_result_at {
+            std::mem::swap(self, &mut other);
+        }
+        if let (Some(last), Some(next_first)) = (self.last_result_at, other.first_result_at)
+            && self.last_result_up
+            && next_first > last
+        {
+            self.up_duration += next_first - last;
+        }
+        self.up_duration += other.up_duration;
+        if other.last_result_at >= self.last_result_at {
+            self.last_result_at = other.last_result_at;
+            self.last_result_up = other.last_result_up;
+        }
+        self.first_result_at = self.first_result_at.or(other.first_result_at);
+
+        self.successful_checks += other.successful_checks;
+        self.total_checks += other.total_checks;
+        self.response_time_sum_micros += other.response_time_sum_micros;
+        self.min_response_time_micros = self
+            .min_response_time_micros
+            .min(other.min_response_time_micros);
+        self.max_response_time_micros = self
+            .max_response_time_micros
+            .max(other.max_response_time_micros);
+        for (bucket, count) in other.response_time_sketch {
+            *self.response_time_sketch.entry(bucket).or_default() += count;
+        }
+        self.ttfb_sum_micros += other.ttfb_sum_micros;
+        self.ttfb_samples += other.ttfb_samples;
+        self.response_size_sum_bytes += other.response_size_sum_bytes;
+        self.response_size_samples += other.response_size_samples;
+        self.max_response_size_bytes = self
+            .max_response_size_bytes
+            .max(other.max_response_size_bytes);
+        for (status_code, count) in other.status_code_counts {
+            *self.status_code_counts.entry(status_code).or_default() += count;
+        }
+    }
+
+    /// Nearest-rank percentile of the response times, read from the sketch and kept within the
+    /// measured bounds
+    fn response_time_percentile(&self, percentile: u32) -> Option<i64> {
+        let samples: u32 = self.response_time_sketch.values().sum();
+        let rank = (u64::from(samples) * u64::from(percentile))
+            .div_ceil(100)
+            .max(1);
+
+        let mut seen = 0;
+        self.response_time_sketch
+            .iter()
+            .find(|(_, count)| {
+                seen += u64::from(**count);
+                seen >= rank
+            })
+            .map(|(bucket, _)| {
+                sketch_value(*bucket)
+                    .clamp(self.min_response_time_micros, self.max_response_time_micros)
+            })
+    }
+
+    pub fn metrics(&self) -> MetricsSummary {
+        let average = |sum: i64, samples: u32| (samples > 0).then(|| sum / i64::from(samples));
+
+        let count_weighted_uptime_percent = match self.total_checks {
+            0 => 0.0,
+            total => self.successful_checks as f32 / total as f32 * 100.0,
+        };
+        // As for raw results, see `calculate_uptime_percent`
+        let time_weighted_uptime_percent = match (self.first_result_at, self.last_result_at) {
+            (Some(first), Some(last)) if last > first => {
+                (self.up_duration.as_seconds_f64() / (last - first).as_seconds_f64() * 100.0) as f32
+            }
+            _ => count_weighted_uptime_percent,
+        };
+
+        let [p50, p95, p99] =
+            if self.total_checks > 0 && self.total_checks >= *eager_env::PERCENTILE_MIN_SAMPLES {
+                [50, 95, 99].map(|percentile| self.response_time_percentile(percentile))
+            } else {
+                [None; 3]
+            };
+
+        MetricsSummary {
+            has_data: self.total_checks > 0,
+            uptime_percent: time_weighted_uptime_percent,
+            time_weighted_uptime_percent,
+            count_weighted_uptime_percent,
+            total_checks: self.total_checks,
+            successful_checks: self.successful_checks,
+            failed_checks: self.total_checks - self.successful_checks,
+            response_time_unit: ResponseTimeUnit::Micros,
+            avg_response_time_micros: average(self.response_time_sum_micros, self.total_checks)
+                .unwrap_or(0),
+            min_response_time_micros: self.min_response_time_micros,
+            max_response_time_micros: self.max_response_time_micros,
+            p50_response_time_micros: p50,
+            p95_response_time_micros: p95,
+            p99_response_time_micros: p99,
+            avg_ttfb_micros: average(self.ttfb_sum_micros, self.ttfb_samples),
+            avg_response_size_bytes: average(
+                self.response_size_sum_bytes,
+                self.response_size_samples,
+            ),
+            max_response_size_bytes: self.max_response_size_bytes,
+            status_code_counts: self.status_code_counts.clone(),
+            time_in_state: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{queries::check_results::calculate_overall_metrics, regions::Region};
+
+    fn row(seconds: i64, response_time_micros: i64, matches_expected: bool) -> CheckResultRow {
+        CheckResultRow {
+            check_started_at: "2025-11-29T00:00:00Z".parse::<DateTime<Utc>>().unwrap()
+                + Duration::seconds(seconds),
+            response_time_micros,
+            status_code: Some(if matches_expected { 200 } else { 500 }),
+            matches_expected,
+            response_size_bytes: Some(100 + seconds),
+            ttfb_micros: Some(response_time_micros / 2),
+            region: Region::Fsn1,
+            sample_weight: 1,
+        }
+    }
+
+    #[test]
+    fn test_merged_aggregates_match_raw_metrics() {
+        let rows: Vec<_> = (0..200)
+            .map(|i| row(i * 30, 1000 + (i * 7919) % 5000, i % 13 != 0))
+            .collect();
+        let expected = calculate_overall_metrics(&rows);
+
+        // Split between two nodes, recorded out of order
+        let (first, second) = rows.split_at(120);
+        let mut aggregate = PeriodAggregate::default();
+        for result in second {
+            aggregate.record(result);
+        }
+        let mut other = PeriodAggregate::default();
+        for result in first {
+            other.record(result);
+        }
+        aggregate.merge(other);
+        let metrics = aggregate.metrics();
+
+        assert_eq!(metrics.total_checks, expected.total_checks);
+        assert_eq!(metrics.successful_checks, expected.successful_checks);
+        assert_eq!(metrics.status_code_counts, expected.status_code_counts);
+        assert_eq!(
+            metrics.avg_response_time_micros,
+            expected.avg_response_time_micros
+        );
+        assert_eq!(
+            metrics.min_response_time_micros,
+            expected.min_response_time_micros
+        );
+        assert_eq!(
+            metrics.max_response_time_micros,
+            expected.max_response_time_micros
+        );
+        assert_eq!(metrics.avg_ttfb_micros, expected.avg_ttfb_micros);
+        assert_eq!(
+            metrics.avg_response_size_bytes,
+            expected.avg_response_size_bytes
+        );
+        assert_eq!(
+            metrics.max_response_size_bytes,
+            expected.max_response_size_bytes
+        );
+        assert!(
+            (metrics.time_weighted_uptime_percent - expected.time_weighted_uptime_percent).abs()
+                < 0.001
+        );
+        assert!(
+            (metrics.count_weighted_uptime_percent - expected.count_weighted_uptime_percent).abs()
+                < 0.001
+        );
+
+        for (approximate, exact) in [
+            (
+                metrics.p50_response_time_micros,
+                expected.p50_response_time_micros,
+            ),
+            (
+                metrics.p95_response_time_micros,
+                expected.p95_response_time_micros,
+            ),
+            (
+                metrics.p99_response_time_micros,
+                expected.p99_response_time_micros,
+            ),
+        ] {
+            let (approximate, exact) = (approximate.unwrap(), exact.unwrap());
+            assert!(
+                (approximate - exact).abs() as f64 <= exact as f64 * 0.02,
+                "{approximate} too far from {exact}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_merging_empty_aggregates() {
+        let mut aggregate = PeriodAggregate::default();
+        aggregate.merge(PeriodAggregate::default());
+        assert!(!aggregate.metrics().has_data);
+
+        aggregate.record(&row(0, 1000, true));
+        let recorded = aggregate.clone();
+        aggregate.merge(PeriodAggregate::default());
+        assert_eq!(aggregate, recorded);
+
+        let metrics = aggregate.metrics();
+        assert_eq!(metrics.uptime_percent, 100.0);
+        assert_eq!(metrics.min_response_time_micros, 1000);
+    }
+}
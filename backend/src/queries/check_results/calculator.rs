@@ -1,15 +1,24 @@
-use super::MetricsSummary;
 use super::queries::CheckResultRow;
-use crate::regions::Region;
-use chrono::Duration;
+use super::{
+    BurnRates, MetricsSummary, OverallUptime, QuorumState, QuorumStatus, ResponseTimeUnit,
+    TimeInState,
+};
+use crate::{eager_env, regions::Region};
+use chrono::{DateTime, Duration, Utc};
 use statrs::statistics::{Data, OrderStatistics, Statistics};
 use std::borrow::Borrow;
 use std::collections::HashMap;
 
+/// Number of results `result` stands for, see [`CheckResultRow::sample_weight`]
+fn weight(result: &CheckResultRow) -> u32 {
+    result.sample_weight.max(1) as u32
+}
+
 /// Calculate time-weighted uptime percentage from check results.
 ///
 /// Each check's status applies to the time interval from that check until the next check.
-/// The bounds are defined by the first and last check timestamps.
+/// The bounds are defined by the first and last check timestamps. When they are equal, e.g. a
+/// single result or results sharing a timestamp, each result weighs the same instead.
 ///
 /// **Expects data sorted by `check_started_at` in ascending order.**
 fn calculate_uptime_percent<T>(sorted: &[T]) -> f32
@@ -30,11 +39,7 @@ where
 
             if total_duration == Duration::zero() {
                 // All checks at the same time, fall back to simple percentage
-                let successful = sorted
-                    .iter()
-                    .filter(|r| Borrow::<CheckResultRow>::borrow(*r).matches_expected)
-                    .count();
-                return (successful as f32 / sorted.len() as f32) * 100.0;
+                return calculate_count_weighted_uptime_percent(sorted);
             }
 
             // Calculate uptime by weighting each check by its time interval
@@ -47,12 +52,43 @@ where
                 })
                 .sum();
 
-            (uptime_duration.num_milliseconds() as f32 / total_duration.num_milliseconds() as f32)
-                * 100.0
+            // In seconds rather than whole milliseconds, which may round a short window to zero
+            (uptime_duration.as_seconds_f64() / total_duration.as_seconds_f64() * 100.0) as f32
         }
     }
 }
 
+/// Calculate the percentage of successful results, each weighing the same however they are spaced.
+fn calculate_count_weighted_uptime_percent<T>(results: &[T]) -> f32
+where
+    T: Borrow<CheckResultRow>,
+{
+    if results.is_empty() {
+        return 0.0;
+    }
+
+    let (successful, total) = results.iter().fold((0u64, 0u64), |(successful, total), r| {
+        let r = r.borrow();
+        let weight = u64::from(weight(r));
+        (
+            successful + u64::from(r.matches_expected) * weight,
+            total + weight,
+        )
+    });
+
+    (successful as f32 / total as f32) * 100.0
+}
+
+/// p50, p95 and p99 of `response_times`, all `None` with fewer than `min_samples` of them
+fn response_time_percentiles(response_times: Vec<f64>, min_samples: usize) -> [Option<i64>; 3] {
+    if response_times.is_empty() || response_times.len() < min_samples {
+        return [None; 3];
+    }
+
+    let mut data = Data::new(response_times);
+    [50, 95, 99].map(|percentile| Some(data.percentile(percentile) as i64))
+}
+
 /// Calculate metrics from a slice of results.
 ///
 /// **Expects data sorted by `check_started_at` in ascending order.**
@@ -69,24 +105,38 @@ where
 
     if sorted.is_empty() {
         return MetricsSummary {
+            has_data: false,
             uptime_percent: 0.0,
+            time_weighted_uptime_percent: 0.0,
+            count_weighted_uptime_percent: 0.0,
             total_checks: 0,
             successful_checks: 0,
             failed_checks: 0,
+            response_time_unit: ResponseTimeUnit::Micros,
             avg_response_time_micros: 0,
             min_response_time_micros: 0,
             max_response_time_micros: 0,
-            p50_response_time_micros: 0,
-            p95_response_time_micros: 0,
-            p99_response_time_micros: 0,
+            p50_response_time_micros: None,
+            p95_response_time_micros: None,
+            p99_response_time_micros: None,
+            avg_ttfb_micros: None,
+            avg_response_size_bytes: None,
+            max_response_size_bytes: None,
+            status_code_counts: HashMap::new(),
+            time_in_state: None,
         };
     }
 
-    let uptime_percent = calculate_uptime_percent(sorted);
+    let time_weighted_uptime_percent = calculate_uptime_percent(sorted);
+    let count_weighted_uptime_percent = calculate_count_weighted_uptime_percent(sorted);
 
+    // Sampled results stand for the ones compacted away
     let response_times: Vec<f64> = sorted
         .iter()
-        .map(|r| r.borrow().response_time_micros as f64)
+        .flat_map(|r| {
+            let r = r.borrow();
+            std::iter::repeat_n(r.response_time_micros as f64, weight(r) as usize)
+        })
         .collect();
 
     let avg_response_time_micros = Statistics::mean(&response_times) as i64;
@@ -97,28 +147,75 @@ where
         .cloned()
         .fold(f64::NEG_INFINITY, f64::max) as i64;
 
-    let mut data = Data::new(response_times);
-    let p50_response_time_micros = data.percentile(50) as i64;
-    let p95_response_time_micros = data.percentile(95) as i64;
-    let p99_response_time_micros = data.percentile(99) as i64;
+    let [
+        p50_response_time_micros,
+        p95_response_time_micros,
+        p99_response_time_micros,
+    ] = response_time_percentiles(response_times, *eager_env::PERCENTILE_MIN_SAMPLES as usize);
+
+    let weighted_average = |values: Vec<(i64, u32)>| {
+        let total_weight: i64 = values.iter().map(|(_, weight)| i64::from(*weight)).sum();
+        (total_weight > 0).then(|| {
+            values
+                .iter()
+                .map(|(value, weight)| value * i64::from(*weight))
+                .sum::<i64>()
+                / total_weight
+        })
+    };
+
+    let avg_ttfb_micros = weighted_average(
+        sorted
+            .iter()
+            .filter_map(|r| Some((r.borrow().ttfb_micros?, weight(r.borrow()))))
+            .collect(),
+    );
 
-    let successful_checks = sorted
+    let response_sizes: Vec<(i64, u32)> = sorted
         .iter()
-        .filter(|&r| r.borrow().matches_expected)
-        .count() as u32;
-    let failed_checks = sorted.len() as u32 - successful_checks;
+        .filter_map(|r| Some((r.borrow().response_size_bytes?, weight(r.borrow()))))
+        .collect();
+    let max_response_size_bytes = response_sizes.iter().map(|(size, _)| *size).max();
+    let avg_response_size_bytes = weighted_average(response_sizes);
+
+    let status_code_counts = sorted.iter().fold(HashMap::new(), |mut acc, r| {
+        let r = r.borrow();
+        if let Some(status_code) = r.status_code {
+            *acc.entry(status_code).or_default() += weight(r);
+        }
+        acc
+    });
+
+    let (successful_checks, total_checks) =
+        sorted.iter().fold((0u32, 0u32), |(successful, total), r| {
+            let r = r.borrow();
+            (
+                successful + u32::from(r.matches_expected) * weight(r),
+                total + weight(r),
+            )
+        });
+    let failed_checks = total_checks - successful_checks;
 
     MetricsSummary {
-        uptime_percent,
-        total_checks: sorted.len() as u32,
+        has_data: true,
+        uptime_percent: time_weighted_uptime_percent,
+        time_weighted_uptime_percent,
+        count_weighted_uptime_percent,
+        total_checks,
         successful_checks,
         failed_checks,
+        response_time_unit: ResponseTimeUnit::Micros,
         avg_response_time_micros,
         min_response_time_micros,
         max_response_time_micros,
         p50_response_time_micros,
         p95_response_time_micros,
         p99_response_time_micros,
+        avg_ttfb_micros,
+        avg_response_size_bytes,
+        max_response_size_bytes,
+        status_code_counts,
+        time_in_state: None,
     }
 }
 
@@ -164,9 +261,164 @@ pub fn calculate_by_region_metrics(sorted: &[CheckResultRow]) -> HashMap<Region,
         .collect()
 }
 
+/// Replaces the uptimes of the pooled `overall` metrics with the mean or the minimum of those of
+/// the `regions` with results, see [`OverallUptime`]. Left pooled when no region has results.
+pub fn derive_overall_uptime(
+    overall: &mut MetricsSummary,
+    regions: &[&MetricsSummary],
+    mode: OverallUptime,
+) {
+    let regions: Vec<_> = regions.iter().filter(|region| region.has_data).collect();
+    if regions.is_empty() {
+        return;
+    }
+
+    let combine = |uptime: fn(&MetricsSummary) -> f32| {
+        let uptimes = regions.iter().map(|region| uptime(region));
+        match mode {
+            OverallUptime::Pooled => None,
+            OverallUptime::RegionAverage => Some(uptimes.sum::<f32>() / regions.len() as f32),
+            OverallUptime::WorstRegion => uptimes.reduce(f32::min),
+        }
+    };
+
+    if let Some(uptime) = combine(|region| region.uptime_percent) {
+        overall.uptime_percent = uptime;
+    }
+    if let Some(uptime) = combine(|region| region.time_weighted_uptime_percent) {
+        overall.time_weighted_uptime_percent = uptime;
+    }
+    if let Some(uptime) = combine(|region| region.count_weighted_uptime_percent) {
+        overall.count_weighted_uptime_percent = uptime;
+    }
+}
+
+/// Time spent up, degraded and down: each result's state lasts until the next result.
+///
+/// Successful results slower than `degraded_threshold` are degraded, failed ones are down.
+///
+/// **Expects data sorted by `check_started_at` in ascending order.**
+pub fn calculate_time_in_state<T>(sorted: &[T], degraded_threshold: Option<Duration>) -> TimeInState
+where
+    T: Borrow<CheckResultRow>,
+{
+    let degraded_threshold_micros = degraded_threshold.and_then(|t| t.num_microseconds());
+
+    sorted
+        .windows(2)
+        .fold(TimeInState::default(), |mut time_in_state, w| {
+            let (current, next): (&CheckResultRow, &CheckResultRow) =
+                (w[0].borrow(), w[1].borrow());
+            let seconds = (next.check_started_at - current.check_started_at).num_seconds();
+
+            let state = if !current.matches_expected {
+                &mut time_in_state.down_seconds
+            } else if degraded_threshold_micros
+                .is_some_and(|threshold| current.response_time_micros > threshold)
+            {
+                &mut time_in_state.degraded_seconds
+            } else {
+                &mut time_in_state.up_seconds
+            };
+            *state += seconds;
+
+            time_in_state
+        })
+}
+
+/// Status across regions from the latest result of each, down when at least
+/// `required_agreeing_regions` of them are.
+///
+/// **Expects data sorted by `check_started_at` in ascending order.**
+pub fn calculate_quorum_status(
+    sorted: &[CheckResultRow],
+    required_agreeing_regions: u32,
+) -> QuorumStatus {
+    let latest_by_region = sorted.iter().fold(HashMap::new(), |mut acc, result| {
+        acc.insert(result.region, result.matches_expected);
+        acc
+    });
+
+    quorum_status(&latest_by_region, required_agreeing_regions)
+}
+
+/// Status across regions from whether the latest result of each is up, see
+/// [`calculate_quorum_status`]
+pub fn quorum_status(
+    latest_by_region: &HashMap<Region, bool>,
+    required_agreeing_regions: u32,
+) -> QuorumStatus {
+    let reporting_regions = latest_by_region.len() as u32;
+    let down_regions = latest_by_region.values().filter(|up| !**up).count() as u32;
+    let required_agreeing_regions = required_agreeing_regions.max(1);
+
+    let state = if reporting_regions == 0 {
+        QuorumState::Unknown
+    } else if down_regions >= required_agreeing_regions {
+        QuorumState::Down
+    } else {
+        QuorumState::Up
+    };
+
+    QuorumStatus {
+        state,
+        down_regions,
+        reporting_regions,
+        required_agreeing_regions,
+    }
+}
+
+/// Error-budget burn rate of `results` against an SLO target (e.g. `99.9`).
+///
+/// A rate of 1 consumes the budget exactly over the SLO period; `None` without results.
+fn calculate_burn_rate<'a>(
+    results: impl Iterator<Item = &'a CheckResultRow>,
+    slo_percent: f64,
+) -> Option<f64> {
+    let (total, failed) = results.fold((0u32, 0u32), |(total, failed), r| {
+        (
+            total + weight(r),
+            failed + u32::from(!r.matches_expected) * weight(r),
+        )
+    });
+
+    if total == 0 {
+        return None;
+    }
+
+    let error_rate = failed as f64 / total as f64;
+    let error_budget = 1.0 - slo_percent / 100.0;
+
+    Some(error_rate / error_budget)
+}
+
+/// Calculate burn rates over the short and long windows ending at `to`,
+/// as used by multi-window burn-rate alerting.
+pub fn calculate_burn_rates(
+    results: &[CheckResultRow],
+    to: DateTime<Utc>,
+    short_window: Duration,
+    long_window: Duration,
+    slo_percent: f64,
+) -> BurnRates {
+    let within = |window: Duration| {
+        results
+            .iter()
+            .filter(move |r| r.check_started_at >= to - window && r.check_started_at < to)
+    };
+
+    BurnRates {
+        short_burn_rate: calculate_burn_rate(within(short_window), slo_percent),
+        long_burn_rate: calculate_burn_rate(within(long_window), slo_percent),
+        partial: false,
+        errors: Vec::new(),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::queries::check_results::UptimeWeighting;
     use chrono::{DateTime, Utc};
 
     fn create_test_results(
@@ -180,8 +432,12 @@ mod tests {
             .map(|(i, (rt, success))| CheckResultRow {
                 check_started_at: start_time + chrono::Duration::hours(i as i64),
                 response_time_micros: rt,
+                status_code: None,
                 matches_expected: success,
+                response_size_bytes: None,
+                ttfb_micros: None,
                 region,
+                sample_weight: 1,
             })
             .collect()
     }
@@ -198,8 +454,8 @@ mod tests {
 
         assert_eq!(metrics.uptime_percent, 100.0);
         assert_eq!(metrics.avg_response_time_micros, 150000); // (100+150+200)/3 = 150
-        assert!(metrics.p95_response_time_micros > 0);
-        assert!(metrics.p99_response_time_micros >= metrics.p95_response_time_micros);
+        // Too few results for percentiles
+        assert_eq!(metrics.p95_response_time_micros, None);
     }
 
     #[test]
@@ -227,10 +483,23 @@ mod tests {
     fn test_calculate_overall_empty() {
         let metrics = calculate_overall_metrics(&[]);
 
+        assert!(!metrics.has_data);
         assert_eq!(metrics.uptime_percent, 0.0);
         assert_eq!(metrics.avg_response_time_micros, 0);
     }
 
+    #[test]
+    fn test_calculate_overall_all_failed() {
+        let start = "2025-11-29T10:00:00Z".parse::<DateTime<Utc>>().unwrap();
+        let results = create_test_results(vec![(0, false), (0, false)], Region::Fsn1, start);
+        let metrics = calculate_overall_metrics(&results);
+
+        // Same uptime as without results, but measured
+        assert!(metrics.has_data);
+        assert_eq!(metrics.uptime_percent, 0.0);
+        assert_eq!(metrics.failed_checks, 2);
+    }
+
     #[test]
     fn test_calculate_by_region() {
         let start = "2025-11-29T10:00:00Z".parse::<DateTime<Utc>>().unwrap();
@@ -281,6 +550,75 @@ mod tests {
         assert!(fsn1_metrics.avg_response_time_micros > 0);
     }
 
+    #[test]
+    fn test_overall_uptime_modes() {
+        let start = "2025-11-29T10:00:00Z".parse::<DateTime<Utc>>().unwrap();
+        // Fsn1 is up 2h out of 4h, Hel1 probes twice as often and is always up
+        let mut results = create_test_results(
+            vec![
+                (1000, true),
+                (1000, true),
+                (1000, false),
+                (1000, false),
+                (1000, true),
+            ],
+            Region::Fsn1,
+            start,
+        );
+        results.extend(
+            create_test_results(vec![(1000, true); 9], Region::Hel1, start)
+                .into_iter()
+                .enumerate()
+                .map(|(i, result)| CheckResultRow {
+                    check_started_at: start + chrono::Duration::minutes(30 * i as i64),
+                    ..result
+                }),
+        );
+        results.sort_by_key(|r| r.check_started_at);
+
+        let overall = calculate_overall_metrics(&results);
+        let by_region = calculate_by_region_metrics(&results);
+        let regions = [&by_region[&Region::Fsn1], &by_region[&Region::Hel1]];
+        assert!((regions[0].count_weighted_uptime_percent - 60.0).abs() < 0.01);
+        assert!((regions[1].count_weighted_uptime_percent - 100.0).abs() < 0.01);
+
+        let derive = |mode| {
+            let mut overall = overall.clone();
+            derive_overall_uptime(&mut overall, &regions, mode);
+            overall
+        };
+
+        // Pooled: 12 of the 14 results are successful, Hel1 weighing more
+        let pooled = derive(OverallUptime::Pooled);
+        assert!((pooled.count_weighted_uptime_percent - 85.71).abs() < 0.01);
+        assert_eq!(
+            pooled.time_weighted_uptime_percent,
+            overall.time_weighted_uptime_percent
+        );
+
+        let average = derive(OverallUptime::RegionAverage);
+        assert!((average.count_weighted_uptime_percent - 80.0).abs() < 0.01);
+        assert!((average.time_weighted_uptime_percent - 75.0).abs() < 0.01);
+        assert!((average.uptime_percent - 75.0).abs() < 0.01);
+
+        let worst = derive(OverallUptime::WorstRegion);
+        assert!((worst.count_weighted_uptime_percent - 60.0).abs() < 0.01);
+        assert!((worst.time_weighted_uptime_percent - 50.0).abs() < 0.01);
+
+        // Counts stay pooled
+        assert_eq!(worst.total_checks, 14);
+
+        // Regions without results are ignored
+        let empty = calculate_overall_metrics(&[]);
+        let mut overall_worst = overall.clone();
+        derive_overall_uptime(
+            &mut overall_worst,
+            &[regions[1], &empty],
+            OverallUptime::WorstRegion,
+        );
+        assert!((overall_worst.time_weighted_uptime_percent - 100.0).abs() < 0.01);
+    }
+
     #[test]
     fn test_percentile_calculation() {
         let start = "2025-11-29T10:00:00Z".parse::<DateTime<Utc>>().unwrap();
@@ -300,7 +638,294 @@ mod tests {
 
         // With sorted [100, 200, 300, 400, 500] microseconds
         assert_eq!(metrics.avg_response_time_micros, 300000);
-        assert!(metrics.p95_response_time_micros >= metrics.avg_response_time_micros);
-        assert!(metrics.p99_response_time_micros >= metrics.p95_response_time_micros);
+        let p95 = metrics.p95_response_time_micros.unwrap();
+        assert!(p95 >= metrics.avg_response_time_micros);
+        assert!(metrics.p99_response_time_micros.unwrap() >= p95);
+    }
+
+    #[test]
+    fn test_percentiles_need_min_samples() {
+        let start = "2025-11-29T10:00:00Z".parse::<DateTime<Utc>>().unwrap();
+        for count in 1..5 {
+            let results = create_test_results(vec![(100000, true); count], Region::Fsn1, start);
+            let metrics = calculate_overall_metrics(&results);
+
+            assert_eq!(metrics.avg_response_time_micros, 100000);
+            assert_eq!(metrics.min_response_time_micros, 100000);
+            assert_eq!(metrics.p50_response_time_micros, None);
+            assert_eq!(metrics.p95_response_time_micros, None);
+            assert_eq!(metrics.p99_response_time_micros, None);
+        }
+
+        let times = || vec![100000.0, 200000.0, 300000.0];
+        assert_eq!(response_time_percentiles(times(), 4), [None; 3]);
+        let [p50, p95, p99] = response_time_percentiles(times(), 3);
+        assert_eq!(p50, Some(200000));
+        assert!(p95 <= p99 && p99 <= Some(300000));
+        // 0 never suppresses, but there is nothing to compute without results
+        assert!(response_time_percentiles(times(), 0)[0].is_some());
+        assert_eq!(response_time_percentiles(Vec::new(), 0), [None; 3]);
+    }
+
+    #[test]
+    fn test_response_size_metrics() {
+        let start = "2025-11-29T10:00:00Z".parse::<DateTime<Utc>>().unwrap();
+        let mut results = create_test_results(
+            vec![(100000, true), (100000, true), (100000, false)],
+            Region::Fsn1,
+            start,
+        );
+        results[0].response_size_bytes = Some(1000);
+        results[1].response_size_bytes = Some(3000);
+
+        let metrics = calculate_overall_metrics(&results);
+
+        // Results without a recorded size are ignored
+        assert_eq!(metrics.avg_response_size_bytes, Some(2000));
+        assert_eq!(metrics.max_response_size_bytes, Some(3000));
+
+        let no_sizes = create_test_results(vec![(100000, true)], Region::Fsn1, start);
+        let metrics = calculate_overall_metrics(&no_sizes);
+        assert_eq!(metrics.avg_response_size_bytes, None);
+        assert_eq!(metrics.max_response_size_bytes, None);
+    }
+
+    #[test]
+    fn test_ttfb_metrics() {
+        let start = "2025-11-29T10:00:00Z".parse::<DateTime<Utc>>().unwrap();
+        let mut results = create_test_results(
+            vec![(100000, true), (300000, true), (50000, false)],
+            Region::Fsn1,
+            start,
+        );
+        results[0].ttfb_micros = Some(40000);
+        results[1].ttfb_micros = Some(80000);
+
+        // Results without a response are ignored
+        let mut metrics = calculate_overall_metrics(&results);
+        assert_eq!(metrics.avg_ttfb_micros, Some(60000));
+        assert_eq!(metrics.avg_response_time_micros, 150000);
+
+        metrics.convert_response_times(ResponseTimeUnit::Millis);
+        assert_eq!(metrics.avg_ttfb_micros, Some(60));
+
+        let metrics = calculate_overall_metrics(&results[2..]);
+        assert_eq!(metrics.avg_ttfb_micros, None);
+    }
+
+    #[test]
+    fn test_status_code_counts() {
+        let start = "2025-11-29T10:00:00Z".parse::<DateTime<Utc>>().unwrap();
+        let mut results = create_test_results(
+            vec![
+                (100000, true),
+                (100000, true),
+                (100000, false),
+                (100000, true),
+                (100000, false),
+                (100000, false),
+            ],
+            Region::Fsn1,
+            start,
+        );
+        let status_codes = [Some(200), Some(200), Some(502), Some(200), Some(503), None];
+        for (result, status_code) in results.iter_mut().zip(status_codes) {
+            result.status_code = status_code;
+        }
+
+        let metrics = calculate_overall_metrics(&results);
+
+        // The timed out result has no status code
+        assert_eq!(
+            metrics.status_code_counts,
+            HashMap::from([(200, 3), (502, 1), (503, 1)])
+        );
+        assert_eq!(metrics.total_checks, 6);
+
+        let empty = calculate_overall_metrics(&[]);
+        assert!(empty.status_code_counts.is_empty());
+    }
+
+    #[test]
+    fn test_equal_timestamps() {
+        let start = "2025-11-29T10:00:00Z".parse::<DateTime<Utc>>().unwrap();
+        let mut results = create_test_results(
+            vec![(1000, true), (1000, false), (1000, true), (1000, true)],
+            Region::Fsn1,
+            start,
+        );
+        for result in &mut results {
+            result.check_started_at = start;
+        }
+
+        // No duration to weigh by, each result counts the same
+        let metrics = calculate_overall_metrics(&results);
+        assert!(metrics.has_data);
+        assert_eq!(metrics.total_checks, 4);
+        assert_eq!(metrics.time_weighted_uptime_percent, 75.0);
+        assert_eq!(metrics.count_weighted_uptime_percent, 75.0);
+        assert_eq!(
+            calculate_time_in_state(&results, None),
+            TimeInState::default()
+        );
+
+        // Less than a millisecond apart
+        results[1].check_started_at = start + Duration::microseconds(200);
+        results[2].check_started_at = start + Duration::microseconds(400);
+        results[3].check_started_at = start + Duration::microseconds(400);
+        let metrics = calculate_overall_metrics(&results);
+        assert!((metrics.time_weighted_uptime_percent - 50.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_uptime_weightings_on_irregular_intervals() {
+        let start = "2025-11-29T10:00:00Z".parse::<DateTime<Utc>>().unwrap();
+        // The last failure is followed by a long gap, e.g. while its node was being taken over
+        let results: Vec<_> = [(0, true), (1, false), (2, false), (3, false), (60, true)]
+            .into_iter()
+            .map(|(minute, success)| CheckResultRow {
+                check_started_at: start + Duration::minutes(minute),
+                response_time_micros: 100000,
+                status_code: None,
+                matches_expected: success,
+                response_size_bytes: None,
+                ttfb_micros: None,
+                region: Region::Fsn1,
+                sample_weight: 1,
+            })
+            .collect();
+
+        let mut metrics = calculate_overall_metrics(&results);
+
+        // Only the first minute out of 60 was up
+        assert!((metrics.time_weighted_uptime_percent - 1.67).abs() < 0.01);
+        // 2 out of 5 results succeeded
+        assert_eq!(metrics.count_weighted_uptime_percent, 40.0);
+        assert_eq!(metrics.uptime_percent, metrics.time_weighted_uptime_percent);
+
+        metrics.select_uptime_weighting(UptimeWeighting::Count);
+        assert_eq!(metrics.uptime_percent, 40.0);
+        metrics.select_uptime_weighting(UptimeWeighting::Time);
+        assert!((metrics.uptime_percent - 1.67).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_time_in_state() {
+        let start = "2025-11-29T10:00:00Z".parse::<DateTime<Utc>>().unwrap();
+        // Fast, slow, timed out, slow, fast: one hour each until the last result
+        let results = create_test_results(
+            vec![
+                (100_000, true),
+                (900_000, true),
+                (10_000_000, false),
+                (600_000, true),
+                (100_000, true),
+            ],
+            Region::Fsn1,
+            start,
+        );
+
+        let time_in_state = calculate_time_in_state(&results, Some(Duration::milliseconds(500)));
+        assert_eq!(
+            time_in_state,
+            TimeInState {
+                up_seconds: 3600,
+                degraded_seconds: 2 * 3600,
+                down_seconds: 3600,
+            }
+        );
+
+        // Without a threshold, slow results are up
+        let time_in_state = calculate_time_in_state(&results, None);
+        assert_eq!(time_in_state.up_seconds, 3 * 3600);
+        assert_eq!(time_in_state.degraded_seconds, 0);
+        assert_eq!(time_in_state.down_seconds, 3600);
+
+        // A single result has no duration
+        assert_eq!(
+            calculate_time_in_state(&results[..1], None),
+            TimeInState::default()
+        );
+    }
+
+    #[test]
+    fn test_quorum_status() {
+        let start = "2025-11-29T10:00:00Z".parse::<DateTime<Utc>>().unwrap();
+        // Earlier results are superseded by the latest one of each region
+        let mut results =
+            create_test_results(vec![(1000, false), (1000, true)], Region::Fsn1, start);
+        results.extend(create_test_results(
+            vec![(1000, true), (1000, true)],
+            Region::Nbg1,
+            start,
+        ));
+        results.extend(create_test_results(
+            vec![(1000, true), (1000, false)],
+            Region::Hel1,
+            start,
+        ));
+        results.sort_by_key(|r| r.check_started_at);
+
+        // 1 of 3 down stays up with a quorum of 2
+        let status = calculate_quorum_status(&results, 2);
+        assert_eq!(status.state, QuorumState::Up);
+        assert_eq!(status.down_regions, 1);
+        assert_eq!(status.reporting_regions, 3);
+
+        // But is down when any region suffices
+        assert_eq!(
+            calculate_quorum_status(&results, 1).state,
+            QuorumState::Down
+        );
+
+        // 2 of 3 down flips to down
+        results.extend(create_test_results(
+            vec![(1000, false)],
+            Region::Nbg1,
+            start + Duration::hours(2),
+        ));
+        let status = calculate_quorum_status(&results, 2);
+        assert_eq!(status.state, QuorumState::Down);
+        assert_eq!(status.down_regions, 2);
+
+        assert_eq!(calculate_quorum_status(&[], 2).state, QuorumState::Unknown);
+    }
+
+    #[test]
+    fn test_burn_rates() {
+        let to = "2025-11-29T16:00:00Z".parse::<DateTime<Utc>>().unwrap();
+        let start = to - Duration::hours(6);
+
+        // One check per minute for 6 hours, the last 6 checks failing
+        let results: Vec<_> = (0..360)
+            .map(|i| CheckResultRow {
+                check_started_at: start + Duration::minutes(i),
+                response_time_micros: 1000,
+                status_code: Some(200),
+                matches_expected: i < 354,
+                response_size_bytes: None,
+                ttfb_micros: None,
+                region: Region::Fsn1,
+                sample_weight: 1,
+            })
+            .collect();
+
+        let rates =
+            calculate_burn_rates(&results, to, Duration::hours(1), Duration::hours(6), 99.0);
+
+        // 1h: 6/60 errors over a 1% budget, 6h: 6/360 errors over a 1% budget
+        assert!((rates.short_burn_rate.unwrap() - 10.0).abs() < 1e-9);
+        assert!((rates.long_burn_rate.unwrap() - 10.0 / 6.0).abs() < 1e-9);
+
+        // No failures in the short window once they're older than 1h
+        let rates = calculate_burn_rates(
+            &results,
+            to + Duration::hours(1),
+            Duration::hours(1),
+            Duration::hours(6),
+            99.0,
+        );
+        assert_eq!(rates.short_burn_rate, None);
+        assert!((rates.long_burn_rate.unwrap() - 6.0 / 300.0 / 0.01).abs() < 1e-9);
     }
 }
@@ -1,83 +1,506 @@
+mod aggregate;
 mod calculator;
 mod queries;
 
 use crate::regions::Region;
-use crate::{database::Database, eager_env};
+use crate::{
+    database::Database, eager_env, queries::checks::get_check_by_id, single_flight::SingleFlight,
+};
+pub use aggregate::PeriodAggregate;
 use anyhow::{Result, bail};
-use calculator::{calculate_by_region_metrics, calculate_overall_metrics};
-use chrono::{DateTime, NaiveDate, Timelike, Utc};
+pub(crate) use calculator::calculate_overall_metrics;
+use calculator::{
+    calculate_burn_rates, calculate_by_region_metrics, calculate_quorum_status,
+    calculate_time_in_state, derive_overall_uptime, quorum_status,
+};
+use chrono::{DateTime, DurationRound, NaiveDate, Timelike, Utc};
 use futures::{StreamExt, TryStreamExt};
-use queries::get_raw_check_results_range;
+pub use queries::{CheckResultRow, get_latest_check_result};
+use queries::{get_available_check_results_range, get_raw_check_results_range};
 use serde::{Deserialize, Serialize};
-use std::collections::{HashMap, HashSet};
+use std::{
+    collections::{BTreeSet, HashMap, HashSet},
+    fmt,
+    sync::LazyLock,
+};
+use strum::IntoEnumIterator;
 use utoipa::ToSchema;
 use uuid::Uuid;
 
 #[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct MetricsSummary {
+    /// `false` when the check has no results in the window, e.g. it never ran yet.
+    /// Uptimes and response times are then `0` rather than measured
+    pub has_data: bool,
+    /// Uptime with the requested weighting, time-weighted by default
     pub uptime_percent: f32,
+    /// Each result counts for the time until the next one
+    pub time_weighted_uptime_percent: f32,
+    /// Share of successful results, regardless of how they are spaced
+    pub count_weighted_uptime_percent: f32,
 
     pub total_checks: u32,
     pub successful_checks: u32,
     pub failed_checks: u32,
 
+    /// Unit of the `*_response_time_micros` fields, despite their name
+    #[serde(default)]
+    pub response_time_unit: ResponseTimeUnit,
     pub avg_response_time_micros: i64,
     pub min_response_time_micros: i64,
     pub max_response_time_micros: i64,
 
-    pub p50_response_time_micros: i64,
-    pub p95_response_time_micros: i64,
-    pub p99_response_time_micros: i64,
+    /// `None` when the window has fewer than `PERCENTILE_MIN_SAMPLES` results, too few for
+    /// percentiles to mean anything
+    pub p50_response_time_micros: Option<i64>,
+    pub p95_response_time_micros: Option<i64>,
+    pub p99_response_time_micros: Option<i64>,
+
+    /// Average time to the response headers, in `response_time_unit`. The `*_response_time_micros`
+    /// fields also include the download of the body. `None` when no result in the window
+    /// recorded it
+    #[serde(default)]
+    pub avg_ttfb_micros: Option<i64>,
+
+    /// `None` when no result in the window recorded a response size
+    pub avg_response_size_bytes: Option<i64>,
+    pub max_response_size_bytes: Option<i64>,
+
+    /// Number of results per HTTP status code. Results without a response, e.g. timeouts,
+    /// are not counted
+    #[serde(default)]
+    pub status_code_counts: HashMap<i32, u32>,
+
+    /// Only reported by the metrics summary of checks storing raw results, not by graph points
+    #[serde(default)]
+    pub time_in_state: Option<TimeInState>,
+}
+
+/// Time spent in each state, each result's state lasting until the next result
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct TimeInState {
+    pub up_seconds: i64,
+    /// Up, but slower than the check's `degraded_response_time_millis`
+    pub degraded_seconds: i64,
+    pub down_seconds: i64,
+}
+
+impl MetricsSummary {
+    /// Reports `uptime_percent` with the given weighting
+    pub fn select_uptime_weighting(&mut self, weighting: UptimeWeighting) {
+        self.uptime_percent = match weighting {
+            UptimeWeighting::Time => self.time_weighted_uptime_percent,
+            UptimeWeighting::Count => self.count_weighted_uptime_percent,
+        };
+    }
+
+    /// Expresses the response times in `unit`. Stored summaries are always in micros.
+    pub fn convert_response_times(&mut self, unit: ResponseTimeUnit) {
+        let factor = unit.micros() as f64 / self.response_time_unit.micros() as f64;
+        let convert = |value: &mut i64| *value = (*value as f64 / factor).round() as i64;
+
+        convert(&mut self.avg_response_time_micros);
+        convert(&mut self.min_response_time_micros);
+        convert(&mut self.max_response_time_micros);
+        for value in [
+            &mut self.p50_response_time_micros,
+            &mut self.p95_response_time_micros,
+            &mut self.p99_response_time_micros,
+            &mut self.avg_ttfb_micros,
+        ]
+        .into_iter()
+        .flatten()
+        {
+            convert(value);
+        }
+        self.response_time_unit = unit;
+    }
+
+    #[cfg(test)]
+    pub fn example() -> Self {
+        Self {
+            has_data: true,
+            uptime_percent: 99.0,
+            time_weighted_uptime_percent: 99.0,
+            count_weighted_uptime_percent: 99.0,
+            total_checks: 100,
+            successful_checks: 99,
+            failed_checks: 1,
+            response_time_unit: ResponseTimeUnit::Micros,
+            avg_response_time_micros: 105_000,
+            min_response_time_micros: 70_000,
+            max_response_time_micros: 250_000,
+            p50_response_time_micros: Some(100_400),
+            p95_response_time_micros: Some(200_500),
+            p99_response_time_micros: Some(240_000),
+            avg_ttfb_micros: Some(60_000),
+            avg_response_size_bytes: Some(2048),
+            max_response_size_bytes: Some(4096),
+            status_code_counts: HashMap::from([(200, 99), (502, 1)]),
+            time_in_state: None,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ResponseTimeUnit {
+    #[default]
+    Micros,
+    Millis,
+}
+
+impl ResponseTimeUnit {
+    fn micros(self) -> i64 {
+        match self {
+            ResponseTimeUnit::Micros => 1,
+            ResponseTimeUnit::Millis => 1_000,
+        }
+    }
+}
+
+/// How results are weighted when computing `uptime_percent`.
+///
+/// Time weighting misrepresents uptime when probes are unevenly spaced, e.g. after a node takeover.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Deserialize, ToSchema)]
+pub enum UptimeWeighting {
+    #[default]
+    Time,
+    Count,
+}
+
+/// How the uptimes of the `overall` metrics are derived.
+///
+/// Pooled uptimes weigh every result of every region alike, so they aren't a simple function of the
+/// regional ones: a region probing more often weighs more. The other modes only use the regions
+/// with results, and only apply to uptimes, not to counts and response times.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Deserialize, ToSchema)]
+pub enum OverallUptime {
+    /// From all the results of the requested regions together
+    #[default]
+    Pooled,
+    /// Mean of the regional uptimes
+    RegionAverage,
+    /// Lowest regional uptime
+    WorstRegion,
+}
+
+/// State of a check combined across regions
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum QuorumState {
+    Up,
+    Down,
+    /// No region reported in the window
+    Unknown,
+}
+
+/// Status of a check at the end of the window, from the latest result of each region.
+///
+/// The check is down only when at least `required_agreeing_regions` regions report it down.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct QuorumStatus {
+    pub state: QuorumState,
+    pub down_regions: u32,
+    pub reporting_regions: u32,
+    pub required_agreeing_regions: u32,
+}
+
+/// Metrics of a single region
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct RegionMetricsSummary {
+    pub region: Region,
+    #[serde(flatten)]
+    pub metrics: MetricsSummary,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct MetricsResponse {
     #[serde(flatten)]
     pub overall: MetricsSummary,
-    pub by_region: HashMap<Region, MetricsSummary>,
+    /// One entry per requested region, in the requested order. Regions without results in the
+    /// window are included, with `has_data` unset
+    pub by_region: Vec<RegionMetricsSummary>,
+    pub quorum: QuorumStatus,
+    /// Set when some results couldn't be read: metrics only cover the remaining ones
+    pub partial: bool,
+    pub errors: Vec<String>,
+    /// `metadata` of the check, for clients to correlate the metrics with their systems
+    #[serde(default)]
+    pub metadata: HashMap<String, String>,
+}
+
+impl MetricsResponse {
+    /// Derives the uptimes of `overall` from `by_region` according to `mode`
+    pub fn select_overall_uptime(&mut self, mode: OverallUptime) {
+        let regions: Vec<_> = self
+            .by_region
+            .iter()
+            .map(|region| &region.metrics)
+            .collect();
+        derive_overall_uptime(&mut self.overall, &regions, mode);
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct MetricsResponseDate {
     pub by_region: HashMap<Region, MetricsSummary>,
     pub date: DateTime<Utc>,
+    /// Set when some results of this date couldn't be read
+    pub partial: bool,
+    /// Why results of this date couldn't be read, when computed from raw results
+    #[serde(default)]
+    pub errors: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct BurnRates {
+    /// `None` when there are no results in the window
+    pub short_burn_rate: Option<f64>,
+    pub long_burn_rate: Option<f64>,
+    /// Set when some results couldn't be read: burn rates only cover the remaining ones
+    pub partial: bool,
+    pub errors: Vec<String>,
 }
 
-#[derive(Copy, Clone, Debug, Deserialize, ToSchema)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Deserialize, ToSchema)]
 pub enum GraphGranularity {
     Hourly,
     Daily,
 }
 
+impl GraphGranularity {
+    /// Longest window a graph of this granularity may span
+    pub fn max_window_days(self) -> i64 {
+        match self {
+            GraphGranularity::Hourly => *eager_env::METRICS_MAX_HOURLY_DAYS,
+            GraphGranularity::Daily => *eager_env::METRICS_MAX_DAILY_DAYS,
+        }
+    }
+
+    /// Length of a single data point
+    pub fn step(self) -> chrono::Duration {
+        match self {
+            GraphGranularity::Hourly => chrono::Duration::hours(1),
+            GraphGranularity::Daily => chrono::Duration::days(1),
+        }
+    }
+}
+
 /// Main function to get metrics for a check
+///
+/// An empty window, `from == to`, has no results: its metrics have `has_data` unset.
+/// Checks not storing raw results are served from their hourly aggregates, see
+/// [`get_aggregated_check_metrics`].
+///
+/// Successful results slower than `degraded_threshold` count as degraded in `time_in_state`,
+/// and `quorum` is down when at least `required_agreeing_regions` regions are.
 pub async fn get_check_metrics(
     db: &Database,
     check_id: Uuid,
     regions: &[Region],
     from: DateTime<Utc>,
     to: DateTime<Utc>,
+    degraded_threshold: Option<chrono::Duration>,
+    required_agreeing_regions: u32,
 ) -> Result<MetricsResponse> {
-    // TODO: Try to get pre-aggregated data
+    if let Some(check) = get_check_by_id(db, check_id).await?
+        && !check.data.store_raw_results
+    {
+        return get_aggregated_check_metrics(
+            db,
+            check_id,
+            regions,
+            from,
+            to,
+            required_agreeing_regions,
+        )
+        .await;
+    }
 
-    // Query raw data and aggregate
-    let mut raw_results = get_raw_check_results_range(db, check_id, regions, from, to).await?;
-    raw_results.sort_by_key(|r| r.check_started_at);
+    // Query raw data and aggregate, with whatever days could be read
+    let mut raw_results =
+        get_available_check_results_range(db, check_id, regions, from, to).await?;
+    raw_results.rows.sort_by_key(|r| r.check_started_at);
+
+    let mut overall = calculate_overall_metrics(&raw_results.rows);
+    overall.time_in_state = Some(calculate_time_in_state(
+        &raw_results.rows,
+        degraded_threshold,
+    ));
+
+    let mut computed = calculate_by_region_metrics(&raw_results.rows);
+    let mut seen = HashSet::new();
+    let by_region = regions
+        .iter()
+        .filter(|region| seen.insert(**region))
+        .map(|&region| {
+            let mut metrics = computed
+                .remove(&region)
+                .unwrap_or_else(|| calculate_overall_metrics(&[]));
+            let region_results: Vec<_> = raw_results
+                .rows
+                .iter()
+                .filter(|r| r.region == region)
+                .collect();
+            metrics.time_in_state =
+                Some(calculate_time_in_state(&region_results, degraded_threshold));
+            RegionMetricsSummary { region, metrics }
+        })
+        .collect();
 
-    let overall = calculate_overall_metrics(&raw_results);
-    let by_region = calculate_by_region_metrics(&raw_results);
+    let quorum = calculate_quorum_status(&raw_results.rows, required_agreeing_regions);
 
     // TODO: Cache the computed metrics back to the database
 
-    Ok(MetricsResponse { overall, by_region })
+    Ok(MetricsResponse {
+        overall,
+        by_region,
+        quorum,
+        partial: raw_results.is_partial(),
+        errors: raw_results.errors,
+        metadata: HashMap::new(),
+    })
+}
+
+/// Metrics of a check not storing raw results, merged from the cached aggregates of the hours
+/// overlapping `[from, to)`: those partially in the window count whole, and results of the
+/// current hour are only there once a node stopped probing the check.
+///
+/// Without raw results, `time_in_state` is left unset and `quorum` follows the latest result of
+/// each region's aggregates.
+async fn get_aggregated_check_metrics(
+    db: &Database,
+    check_id: Uuid,
+    regions: &[Region],
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+    required_agreeing_regions: u32,
+) -> Result<MetricsResponse> {
+    let aggregates = if from < to {
+        let first_hour = from
+            .duration_trunc(chrono::Duration::hours(1))
+            .expect("representable date");
+        queries::get_hourly_cached_aggregates(db, check_id, regions, first_hour, to).await?
+    } else {
+        Vec::new()
+    };
+
+    let mut overall = PeriodAggregate::default();
+    let mut computed: HashMap<Region, PeriodAggregate> = HashMap::new();
+    for (region, aggregate) in aggregates {
+        overall.merge(aggregate.clone());
+        computed.entry(region).or_default().merge(aggregate);
+    }
+
+    let latest_by_region = computed
+        .iter()
+        .filter(|(_, aggregate)| aggregate.total_checks > 0)
+        .map(|(&region, aggregate)| (region, aggregate.last_result_up))
+        .collect();
+
+    let mut seen = HashSet::new();
+    let by_region = regions
+        .iter()
+        .filter(|region| seen.insert(**region))
+        .map(|&region| RegionMetricsSummary {
+            region,
+            metrics: computed.remove(&region).unwrap_or_default().metrics(),
+        })
+        .collect();
+
+    Ok(MetricsResponse {
+        overall: overall.metrics(),
+        by_region,
+        quorum: quorum_status(&latest_by_region, required_agreeing_regions),
+        partial: false,
+        errors: Vec::new(),
+        metadata: HashMap::new(),
+    })
+}
+
+/// How far back [`get_recent_check_results`] looks for results
+const RECENT_RESULTS_WINDOW: chrono::Duration = chrono::Duration::days(1);
+
+/// Gets the `limit` most recent results of a check before `now`, newest first, merged across
+/// `regions`. Only results of the last day are considered.
+pub async fn get_recent_check_results(
+    db: &Database,
+    check_id: Uuid,
+    regions: &[Region],
+    now: DateTime<Utc>,
+    limit: usize,
+) -> Result<Vec<CheckResultRow>> {
+    let mut raw_results =
+        get_raw_check_results_range(db, check_id, regions, now - RECENT_RESULTS_WINDOW, now)
+            .await?;
+
+    raw_results
+        .rows
+        .sort_by_key(|r| std::cmp::Reverse(r.check_started_at));
+    raw_results.rows.truncate(limit);
+
+    Ok(raw_results.rows)
+}
+
+/// Gets the error-budget burn rates over the windows ending at `to`, from the results that
+/// could be read
+pub async fn get_check_burn_rates(
+    db: &Database,
+    check_id: Uuid,
+    regions: &[Region],
+    to: DateTime<Utc>,
+    short_window: chrono::Duration,
+    long_window: chrono::Duration,
+    slo_percent: f64,
+) -> Result<BurnRates> {
+    // Compute with whatever days could be read
+    let raw_results =
+        get_available_check_results_range(db, check_id, regions, to - long_window, to).await?;
+
+    let rates = calculate_burn_rates(
+        &raw_results.rows,
+        to,
+        short_window,
+        long_window,
+        slo_percent,
+    );
+
+    Ok(BurnRates {
+        partial: raw_results.is_partial(),
+        errors: raw_results.errors,
+        ..rates
+    })
 }
 
+type GraphKey = (
+    Uuid,
+    DateTime<Utc>,
+    DateTime<Utc>,
+    GraphGranularity,
+    Vec<Region>,
+);
+
+/// Identical graph requests in flight, so that dashboards opened at the same time
+/// compute (and cache) the missing dates once
+static GRAPH_FLIGHTS: LazyLock<SingleFlight<GraphKey, Vec<MetricsResponseDate>>> =
+    LazyLock::new(Default::default);
+
+/// Graphs computed from the database, by check
+#[cfg(test)]
+static GRAPH_COMPUTATIONS: LazyLock<std::sync::Mutex<HashMap<Uuid, usize>>> =
+    LazyLock::new(Default::default);
+
 /// Gets check results metrics for the time range `[from, to)`
 ///
 /// `from` and `to` must be aligned to the granularity.
 /// `to` must be a past date.
 /// Example: `Hourly`, `2017-01-01 01:00:00 UTC`
+///
+/// Results and cached aggregates are read from `read_db`, computed aggregates are cached in `db`.
 pub async fn get_check_metrics_graph(
     db: &Database,
+    read_db: &Database,
     check_id: Uuid,
     regions: &[Region],
     from: DateTime<Utc>,
@@ -91,9 +514,131 @@ pub async fn get_check_metrics_graph(
         bail!("'to' must be rounded");
     }
 
+    // Points starting after `now` can't have results yet
+    let to = clamp_graph_end(to, Utc::now(), granularity);
+    if from >= to {
+        return Ok(Vec::new());
+    }
+
+    let mut key_regions = regions.to_vec();
+    key_regions.sort();
+    key_regions.dedup();
+    let key = (check_id, from, to, granularity, key_regions);
+
+    GRAPH_FLIGHTS
+        .run(key, || {
+            compute_check_metrics_graph(db, read_db, check_id, regions, from, to, granularity)
+        })
+        .await
+}
+
+/// Error of [`recompute_cached_check_results`] for checks not storing raw results, whose
+/// aggregates are the only record
+#[derive(Debug)]
+pub struct RawResultsNotStored;
+
+impl fmt::Display for RawResultsNotStored {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("the check doesn't store raw results, its aggregates can't be recomputed")
+    }
+}
+
+/// Drops the cached aggregates of the time range `[from, to)` in every region and recomputes
+/// them from raw results, e.g. after a bug cached wrong values.
+///
+/// `from` and `to` must be aligned to the granularity.
+/// Returns the recomputed points; as usual, only completed points are cached again. Fails with
+/// [`RawResultsNotStored`] for checks not storing raw results, and keeps the points aggregated
+/// while a check didn't.
+pub async fn recompute_cached_check_results(
+    db: &Database,
+    check_id: Uuid,
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+    granularity: GraphGranularity,
+) -> Result<Vec<MetricsResponseDate>> {
+    if !is_rounded_to_granularity(from, granularity) {
+        bail!("'from' must be rounded");
+    }
+    if !is_rounded_to_granularity(to, granularity) {
+        bail!("'to' must be rounded");
+    }
+
+    if let Some(check) = get_check_by_id(db, check_id).await?
+        && !check.data.store_raw_results
+    {
+        bail!(RawResultsNotStored);
+    }
+
+    let to = clamp_graph_end(to, Utc::now(), granularity);
+    if from >= to {
+        return Ok(Vec::new());
+    }
+
+    let regions: Vec<_> = Region::iter().collect();
+
+    // There are no raw results to recompute them from
+    let aggregated =
+        queries::get_aggregated_dates(db, check_id, &regions, from, to, granularity).await?;
+    for (range_from, range_to) in ranges_around(from, to, granularity.step(), &aggregated) {
+        queries::delete_cached_check_results(
+            db,
+            check_id,
+            &regions,
+            range_from,
+            range_to,
+            granularity,
+        )
+        .await?;
+    }
+
+    compute_check_metrics_graph(db, db, check_id, &regions, from, to, granularity).await
+}
+
+/// Splits `[from, to)` into the ranges left between the points of `step` starting at `skipped`
+fn ranges_around(
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+    step: chrono::Duration,
+    skipped: &BTreeSet<DateTime<Utc>>,
+) -> Vec<(DateTime<Utc>, DateTime<Utc>)> {
+    let mut ranges = Vec::new();
+    let mut start = from;
+    for &point in skipped.range(from..to) {
+        if start < point {
+            ranges.push((start, point));
+        }
+        start = point + step;
+    }
+    if start < to {
+        ranges.push((start, to));
+    }
+
+    ranges
+}
+
+async fn compute_check_metrics_graph(
+    db: &Database,
+    read_db: &Database,
+    check_id: Uuid,
+    regions: &[Region],
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+    granularity: GraphGranularity,
+) -> Result<Vec<MetricsResponseDate>> {
+    #[cfg(test)]
+    {
+        *GRAPH_COMPUTATIONS
+            .lock()
+            .unwrap()
+            .entry(check_id)
+            .or_default() += 1;
+    }
+
     // Fetch cached results
     let cached_results =
-        queries::get_cached_check_results(db, check_id, regions, from, to, granularity).await?;
+        queries::get_cached_check_results(read_db, check_id, regions, from, to, granularity)
+            .await?;
 
     // Generate all expected dates based on granularity
     let expected_dates: Vec<DateTime<Utc>> = match granularity {
@@ -112,26 +657,31 @@ pub async fn get_check_metrics_graph(
         .copied()
         .collect();
 
+    // Aggregates flushed before their point ended
+    let mut partial_dates: HashSet<_> = cached_results
+        .iter()
+        .filter(|r| r.partial)
+        .map(|r| r.date)
+        .collect();
     let mut all_results = cached_results;
 
     // Calculate missing dates from raw data in parallel
     let futures = missing_dates.iter().map(|date| async move {
         let range_from = *date;
-        let range_to = match granularity {
-            GraphGranularity::Hourly => range_from + chrono::Duration::hours(1),
-            GraphGranularity::Daily => range_from + chrono::Duration::days(1),
-        };
+        let range_to = range_from + granularity.step();
 
-        // Query raw data for this period
+        // Query raw data for this period, with whatever could be read
         let mut raw_results =
-            get_raw_check_results_range(db, check_id, regions, range_from, range_to).await?;
-        raw_results.sort_by_key(|r| r.check_started_at);
+            get_available_check_results_range(read_db, check_id, regions, range_from, range_to)
+                .await?;
+        raw_results.rows.sort_by_key(|r| r.check_started_at);
+        let partial = raw_results.is_partial();
 
         // Calculate metrics
-        let by_region = calculate_by_region_metrics(&raw_results);
+        let by_region = calculate_by_region_metrics(&raw_results.rows);
 
-        // If the range is completed (to <= now), write to cache
-        if range_to <= Utc::now() {
+        // If the range is completed (to <= now) and fully read, write to cache
+        if range_to <= Utc::now() && !partial {
             queries::insert_cached_check_result(db, check_id, *date, &by_region, granularity)
                 .await?;
         }
@@ -143,34 +693,48 @@ pub async fn get_check_metrics_graph(
                 metrics_summary: metrics,
                 region,
                 date: *date,
+                partial,
             })
             .collect();
 
-        Ok::<_, anyhow::Error>(results)
+        Ok::<_, anyhow::Error>((results, *date, raw_results.errors))
     });
 
     let missing_results: Vec<_> = futures::stream::iter(futures)
-        .buffer_unordered(*eager_env::DATABASE_CONCURRENT_REQUESTS)
+        .buffer_unordered(eager_env::DATABASE_CONCURRENT_READS.get())
         .try_collect::<Vec<_>>()
-        .await?
-        .into_iter()
-        .flatten()
-        .collect();
+        .await?;
 
-    all_results.extend(missing_results);
+    let mut date_errors = HashMap::new();
+    for (results, date, errors) in missing_results {
+        all_results.extend(results);
+        if !errors.is_empty() {
+            partial_dates.insert(date);
+            date_errors.insert(date, errors);
+        }
+    }
 
     // Convert MetricsSummaryRegionDate to MetricsResponseDate
-    // Group by date and combine regions
+    // Group by date and combine regions, keeping the dates that couldn't be read at all
+    let unread_dates: HashMap<_, HashMap<_, _>> = date_errors
+        .keys()
+        .map(|&date| (date, HashMap::new()))
+        .collect();
     let mut final_results: Vec<_> = all_results
         .into_iter()
-        .fold(HashMap::new(), |mut acc, result| {
+        .fold(unread_dates, |mut acc, result| {
             acc.entry(result.date)
                 .or_insert_with(HashMap::new)
                 .insert(result.region, result.metrics_summary);
             acc
         })
         .into_iter()
-        .map(|(date, by_region)| MetricsResponseDate { by_region, date })
+        .map(|(date, by_region)| MetricsResponseDate {
+            by_region,
+            date,
+            partial: partial_dates.contains(&date),
+            errors: date_errors.remove(&date).unwrap_or_default(),
+        })
         .collect();
 
     // Sort by date
@@ -179,6 +743,68 @@ pub async fn get_check_metrics_graph(
     Ok(final_results)
 }
 
+/// Merges before giving up on a point other nodes keep merging into, see
+/// [`cache_aggregated_check_results`]
+const AGGREGATE_MERGE_ATTEMPTS: usize = 10;
+
+/// Merges `aggregate`, of `region` and the point of `granularity` starting at `date`, into the
+/// cached one, e.g. written by another node probing the check earlier in the point.
+/// The write is conditional on the cached one being unchanged, otherwise the merge is retried.
+///
+/// For checks not storing raw results, whose cache is the only record. `partial` while the point
+/// is still in progress, graphs flag it until a write after its end.
+pub async fn cache_aggregated_check_results(
+    db: &Database,
+    check_id: Uuid,
+    region: Region,
+    date: DateTime<Utc>,
+    granularity: GraphGranularity,
+    aggregate: PeriodAggregate,
+    partial: bool,
+) -> Result<()> {
+    for _ in 0..AGGREGATE_MERGE_ATTEMPTS {
+        let (mut merged, version) =
+            match queries::get_cached_aggregate(db, check_id, region, date, granularity).await? {
+                Some(cached) => (cached.aggregate, cached.version),
+                None => (PeriodAggregate::default(), None),
+            };
+        merged.merge(aggregate.clone());
+
+        let applied = queries::update_cached_aggregate(
+            db,
+            check_id,
+            region,
+            date,
+            granularity,
+            &merged,
+            partial,
+            version,
+        )
+        .await?;
+        if applied {
+            return Ok(());
+        }
+    }
+
+    bail!("the cached aggregate kept changing during {AGGREGATE_MERGE_ATTEMPTS} merge attempts")
+}
+
+/// Clamps an aligned `to` to the end of the point containing `now`,
+/// so that the in-progress point is kept but future ones are dropped
+fn clamp_graph_end(
+    to: DateTime<Utc>,
+    now: DateTime<Utc>,
+    granularity: GraphGranularity,
+) -> DateTime<Utc> {
+    let current_start = match granularity {
+        GraphGranularity::Hourly => now.duration_trunc(chrono::Duration::hours(1)),
+        GraphGranularity::Daily => now.duration_trunc(chrono::Duration::days(1)),
+    }
+    .expect("representable date");
+
+    to.min(current_start + granularity.step())
+}
+
 /// Check if a DateTime is rounded to the hour
 pub fn is_rounded_to_granularity(dt: DateTime<Utc>, graph_granularity: GraphGranularity) -> bool {
     dt.minute() == 0
@@ -244,23 +870,34 @@ mod tests {
             &[Region::Fsn1, Region::Nbg1, Region::Hel1],
             from,
             to,
+            None,
+            1,
         )
         .await?;
         assert_eq!(metrics.overall.uptime_percent, 100.0);
         assert!(metrics.overall.avg_response_time_micros > 0);
-        assert_eq!(metrics.by_region.len(), 3); // fsn1, hel1, nbg1
+        // In the requested order
+        assert_eq!(
+            metrics
+                .by_region
+                .iter()
+                .map(|r| r.region)
+                .collect::<Vec<_>>(),
+            [Region::Fsn1, Region::Nbg1, Region::Hel1]
+        );
 
         // Test: Each region has flattened metrics
-        for (_region, metrics) in metrics.by_region {
+        for RegionMetricsSummary { metrics, .. } in metrics.by_region {
             assert!(metrics.uptime_percent >= 0.0);
             assert!(metrics.avg_response_time_micros > 0);
         }
 
         // Test: Specific region filter
-        let metrics_fsn1 = get_check_metrics(&db, check_id, &[Region::Fsn1], from, to).await?;
+        let metrics_fsn1 =
+            get_check_metrics(&db, check_id, &[Region::Fsn1], from, to, None, 1).await?;
         assert_eq!(metrics_fsn1.by_region.len(), 1);
-        assert!(metrics_fsn1.by_region.contains_key(&Region::Fsn1));
-        assert_eq!(metrics_fsn1.by_region[&Region::Fsn1].uptime_percent, 100.0);
+        assert_eq!(metrics_fsn1.by_region[0].region, Region::Fsn1);
+        assert_eq!(metrics_fsn1.by_region[0].metrics.uptime_percent, 100.0);
 
         // Test: Mixed success/failure check (time-weighted uptime)
         let check_mixed = uuid!("bbbbbbbb-bbbb-bbbb-bbbb-bbbbbbbbbbbb");
@@ -270,6 +907,8 @@ mod tests {
             &[Region::Fsn1, Region::Nbg1, Region::Hel1],
             from,
             "2025-11-29T20:00:00Z".parse::<DateTime<Utc>>()?,
+            None,
+            1,
         )
         .await?;
         // Time-weighted: 7/9 intervals successful = 77.78%
@@ -278,13 +917,330 @@ mod tests {
 
         // Test: Empty result for non-existent check
         let nonexistent = uuid!("99999999-9999-9999-9999-999999999999");
-        let empty = get_check_metrics(&db, nonexistent, &[], from, to).await?;
+        let empty = get_check_metrics(&db, nonexistent, &[], from, to, None, 1).await?;
         assert_eq!(empty.overall.uptime_percent, 0.0);
         assert!(empty.by_region.is_empty());
 
+        // Test: Requested regions without results are reported empty rather than omitted
+        let empty = get_check_metrics(
+            &db,
+            nonexistent,
+            &[Region::Nbg1, Region::Fsn1, Region::Nbg1],
+            from,
+            to,
+            None,
+            1,
+        )
+        .await?;
+        assert_eq!(empty.by_region.len(), 2);
+        assert_eq!(empty.by_region[0].region, Region::Nbg1);
+        assert_eq!(empty.by_region[1].region, Region::Fsn1);
+        for RegionMetricsSummary { metrics, .. } in &empty.by_region {
+            assert!(!metrics.has_data);
+            assert_eq!(metrics.total_checks, 0);
+            assert_eq!(metrics.uptime_percent, 0.0);
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_get_aggregated_check_metrics() -> Result<()> {
+        let (db, _keyspace) = create_test_database(None).await?;
+
+        let check = crate::queries::checks::create_check(
+            &db,
+            vec![Region::Fsn1, Region::Nbg1],
+            crate::queries::checks::CheckData {
+                store_raw_results: false,
+                ..crate::queries::checks::CheckData::example()
+            },
+        )
+        .await?;
+        let hour = "2025-11-29T10:00:00Z".parse::<DateTime<Utc>>()?;
+
+        // Fsn1 up for two hours, Nbg1 down at the end of the first one
+        for (region, offset, up) in [
+            (Region::Fsn1, 0, true),
+            (Region::Fsn1, 1, true),
+            (Region::Nbg1, 0, false),
+        ] {
+            let mut aggregate = PeriodAggregate::default();
+            for i in 0..4 {
+                aggregate.record(&CheckResultRow {
+                    check_started_at: hour
+                        + chrono::Duration::hours(offset)
+                        + chrono::Duration::minutes(15 * i),
+                    response_time_micros: 1000,
+                    status_code: Some(200),
+                    matches_expected: up || i < 3,
+                    response_size_bytes: None,
+                    ttfb_micros: None,
+                    region,
+                    sample_weight: 1,
+                });
+            }
+            cache_aggregated_check_results(
+                &db,
+                check.check_id,
+                region,
+                hour + chrono::Duration::hours(offset),
+                GraphGranularity::Hourly,
+                aggregate,
+                false,
+            )
+            .await?;
+        }
+
+        // The first hour counts whole, the one starting at `to` not at all
+        let metrics = get_check_metrics(
+            &db,
+            check.check_id,
+            &[Region::Fsn1, Region::Nbg1, Region::Hel1],
+            hour + chrono::Duration::minutes(30),
+            hour + chrono::Duration::hours(1),
+            None,
+            1,
+        )
+        .await?;
+        assert!(metrics.overall.has_data);
+        assert_eq!(metrics.overall.total_checks, 8);
+        assert_eq!(metrics.overall.failed_checks, 1);
+        let totals: Vec<_> = metrics
+            .by_region
+            .iter()
+            .map(|r| (r.region, r.metrics.has_data, r.metrics.total_checks))
+            .collect();
+        assert_eq!(
+            totals,
+            [
+                (Region::Fsn1, true, 4),
+                (Region::Nbg1, true, 4),
+                (Region::Hel1, false, 0)
+            ]
+        );
+        assert_eq!(metrics.quorum.state, QuorumState::Down);
+        assert_eq!(metrics.quorum.reporting_regions, 2);
+        assert!(metrics.overall.time_in_state.is_none());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_get_check_metrics_graph_future_to() -> Result<()> {
+        let (db, _keyspace) = create_test_database(Some(FIXTURES)).await?;
+
+        let check_id = uuid!("aaaaaaaa-aaaa-aaaa-aaaa-aaaaaaaaaaaa");
+        let now = Utc::now();
+        let from = now.duration_trunc(chrono::Duration::hours(1))? - chrono::Duration::hours(2);
+        let to = from + chrono::Duration::hours(48);
+
+        let graph = get_check_metrics_graph(
+            &db,
+            &db,
+            check_id,
+            &[Region::Fsn1, Region::Nbg1, Region::Hel1],
+            from,
+            to,
+            GraphGranularity::Hourly,
+        )
+        .await?;
+
+        assert!(graph.iter().all(|point| point.date <= now));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_graphs_read_once() -> Result<()> {
+        let (db, _keyspace) = create_test_database(Some(FIXTURES)).await?;
+
+        // Nothing is cached for it, every computation reads the database
+        let check_id = Uuid::new_v4();
+        let to = "2025-11-29T14:00:00Z".parse::<DateTime<Utc>>()?;
+        let from = to - chrono::Duration::hours(5);
+        let graph = || {
+            get_check_metrics_graph(
+                &db,
+                &db,
+                check_id,
+                &[Region::Fsn1, Region::Nbg1],
+                from,
+                to,
+                GraphGranularity::Hourly,
+            )
+        };
+        let computations = || GRAPH_COMPUTATIONS.lock().unwrap()[&check_id];
+
+        let graphs = futures::future::try_join_all((0..8).map(|_| graph())).await?;
+        assert!(graphs.iter().all(|g| g.len() == graphs[0].len()));
+        assert_eq!(computations(), 1);
+
+        // Completed computations are not shared
+        graph().await?;
+        assert_eq!(computations(), 2);
+
         Ok(())
     }
 
+    #[tokio::test]
+    async fn test_recompute_cached_check_results() -> Result<()> {
+        let (db, _keyspace) = create_test_database(Some(FIXTURES)).await?;
+
+        let check_id = uuid!("aaaaaaaa-aaaa-aaaa-aaaa-aaaaaaaaaaaa");
+        let hour = "2025-11-29T10:00:00Z".parse::<DateTime<Utc>>()?;
+        let from = hour - chrono::Duration::hours(1);
+        let to = hour + chrono::Duration::hours(4);
+
+        // Fill the cache, then corrupt one of its rows
+        get_check_metrics_graph(
+            &db,
+            &db,
+            check_id,
+            &[Region::Fsn1],
+            from,
+            to,
+            GraphGranularity::Hourly,
+        )
+        .await?;
+        let mut corrupted = queries::get_hourly_cached_check_results(
+            &db,
+            check_id,
+            &[Region::Fsn1],
+            hour,
+            hour + chrono::Duration::hours(1),
+        )
+        .await?
+        .remove(0)
+        .metrics_summary;
+        corrupted.time_weighted_uptime_percent = 12.5;
+        corrupted.failed_checks = 7;
+        queries::insert_hourly_cached_check_result(&db, check_id, Region::Fsn1, hour, &corrupted)
+            .await?;
+
+        // Aggregated while the check didn't store raw results
+        let aggregated_hour = hour + chrono::Duration::hours(2);
+        let mut aggregate = PeriodAggregate::default();
+        for i in 0..5 {
+            aggregate.record(&CheckResultRow {
+                check_started_at: aggregated_hour + chrono::Duration::minutes(i),
+                response_time_micros: 1000,
+                status_code: Some(200),
+                matches_expected: i != 0,
+                response_size_bytes: None,
+                ttfb_micros: None,
+                region: Region::Nbg1,
+                sample_weight: 1,
+            });
+        }
+        cache_aggregated_check_results(
+            &db,
+            check_id,
+            Region::Nbg1,
+            aggregated_hour,
+            GraphGranularity::Hourly,
+            aggregate.clone(),
+            false,
+        )
+        .await?;
+
+        let recomputed =
+            recompute_cached_check_results(&db, check_id, from, to, GraphGranularity::Hourly)
+                .await?;
+        assert_eq!(recomputed.len(), 4);
+
+        let kept = queries::get_cached_aggregate(
+            &db,
+            check_id,
+            Region::Nbg1,
+            aggregated_hour,
+            GraphGranularity::Hourly,
+        )
+        .await?;
+        assert_eq!(kept.map(|cached| cached.aggregate), Some(aggregate));
+
+        let restored = queries::get_hourly_cached_check_results(
+            &db,
+            check_id,
+            &[Region::Fsn1],
+            hour,
+            hour + chrono::Duration::hours(1),
+        )
+        .await?;
+        assert_eq!(restored.len(), 1);
+        assert_eq!(
+            restored[0].metrics_summary.time_weighted_uptime_percent,
+            100.0
+        );
+        assert_eq!(restored[0].metrics_summary.successful_checks, 1);
+        assert_eq!(restored[0].metrics_summary.failed_checks, 0);
+
+        // Unaligned windows are rejected
+        assert!(
+            recompute_cached_check_results(
+                &db,
+                check_id,
+                hour + chrono::Duration::minutes(30),
+                to,
+                GraphGranularity::Hourly
+            )
+            .await
+            .is_err()
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_ranges_around() {
+        let at = |hour: u32| {
+            format!("2025-11-29T{hour:02}:00:00Z")
+                .parse::<DateTime<Utc>>()
+                .unwrap()
+        };
+        let step = chrono::Duration::hours(1);
+
+        assert_eq!(
+            ranges_around(at(0), at(6), step, &BTreeSet::new()),
+            [(at(0), at(6))]
+        );
+        assert_eq!(
+            ranges_around(
+                at(0),
+                at(6),
+                step,
+                &BTreeSet::from([at(0), at(2), at(3), at(9)])
+            ),
+            [(at(1), at(2)), (at(4), at(6))]
+        );
+        assert!(ranges_around(at(0), at(2), step, &BTreeSet::from([at(0), at(1)])).is_empty());
+    }
+
+    #[test]
+    fn test_clamp_graph_end() {
+        let now = "2025-11-29T10:30:00Z".parse::<DateTime<Utc>>().unwrap();
+        let past = "2025-11-29T09:00:00Z".parse::<DateTime<Utc>>().unwrap();
+        let future = "2025-12-05T00:00:00Z".parse::<DateTime<Utc>>().unwrap();
+
+        // Past ends are untouched
+        assert_eq!(clamp_graph_end(past, now, GraphGranularity::Hourly), past);
+
+        // Future ends stop after the in-progress point
+        assert_eq!(
+            clamp_graph_end(future, now, GraphGranularity::Hourly),
+            "2025-11-29T11:00:00Z".parse::<DateTime<Utc>>().unwrap()
+        );
+        assert_eq!(
+            clamp_graph_end(future, now, GraphGranularity::Daily),
+            "2025-11-30T00:00:00Z".parse::<DateTime<Utc>>().unwrap()
+        );
+
+        let hours =
+            get_hours_in_range(past, clamp_graph_end(future, now, GraphGranularity::Hourly));
+        assert_eq!(hours.len(), 2);
+        assert!(hours.iter().all(|hour| *hour <= now));
+    }
+
     #[test]
     fn test_is_rounded_to_gran() {
         // Rounded to hour
@@ -1,23 +1,61 @@
-use std::collections::HashMap;
+use std::collections::{BTreeSet, HashMap};
 
 use crate::database::preparer::CachedPreparedStatement;
 use crate::eager_env;
-use crate::queries::check_results::GraphGranularity;
+use crate::queries::check_results::{
+    GraphGranularity, ResponseTimeUnit, aggregate::PeriodAggregate,
+};
 use crate::regions::Region;
 use crate::{database::Database, queries::check_results::MetricsSummary};
-use anyhow::Result;
-use chrono::{DateTime, NaiveDate, Utc};
+use anyhow::{Result, bail};
+use chrono::{DateTime, Duration, NaiveDate, Utc};
 use futures::{StreamExt, TryStreamExt, stream};
+use log::{debug, warn};
+use scylla::{
+    DeserializeRow, SerializeRow,
+    serialize::value::SerializeValue,
+    statement::Consistency,
+    value::{CqlValue, Row},
+};
 use serde::{Deserialize, Serialize};
+use tokio::time::error::Elapsed;
 use utoipa::ToSchema;
 use uuid::Uuid;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct CheckResultRow {
     pub check_started_at: DateTime<Utc>,
     pub response_time_micros: i64,
+    pub status_code: Option<i32>,
     pub matches_expected: bool,
+    pub response_size_bytes: Option<i64>,
+    /// `None` for results without a response and those predating it
+    #[serde(default)]
+    pub ttfb_micros: Option<i64>,
     pub region: Region,
+    /// Number of results this one stands for, above 1 for successes kept by the compaction of
+    /// old results, see `SamplingPolicy`
+    #[serde(default = "default_sample_weight")]
+    pub sample_weight: i32,
+}
+
+fn default_sample_weight() -> i32 {
+    1
+}
+
+/// Raw results of the partitions that could be read.
+///
+/// `errors` describes the partitions that failed, whose results are missing from `rows`.
+#[derive(Debug, Default)]
+pub struct PartialCheckResults {
+    pub rows: Vec<CheckResultRow>,
+    pub errors: Vec<String>,
+}
+
+impl PartialCheckResults {
+    pub fn is_partial(&self) -> bool {
+        !self.errors.is_empty()
+    }
 }
 
 static GET_RAW_CHECK_RESULTS_QUERY_RANGE: CachedPreparedStatement = CachedPreparedStatement::new(
@@ -26,7 +64,10 @@ static GET_RAW_CHECK_RESULTS_QUERY_RANGE: CachedPreparedStatement = CachedPrepar
            check_started_at,
            response_time_micros,
            status_code,
-           matches_expected
+           matches_expected,
+           response_size_bytes,
+           ttfb_micros,
+           sample_weight
     FROM check_results
     WHERE service_check_id = ?
       AND region IN ?
@@ -37,8 +78,14 @@ static GET_RAW_CHECK_RESULTS_QUERY_RANGE: CachedPreparedStatement = CachedPrepar
 );
 
 /// Get all dates in the range [from, to)
+///
+/// These are exactly the days touched by the range, so none for an empty range.
 fn get_dates_in_range(from: DateTime<Utc>, to: DateTime<Utc>) -> Vec<NaiveDate> {
     let mut dates = Vec::new();
+    if from >= to {
+        return dates;
+    }
+
     let mut current = from.date_naive();
     let end = to.date_naive();
 
@@ -56,52 +103,242 @@ fn get_dates_in_range(from: DateTime<Utc>, to: DateTime<Utc>) -> Vec<NaiveDate>
 }
 
 /// Query raw check results for a given time range
+///
+/// Days that fail to be read are reported in [`PartialCheckResults::errors`] rather than failing
+/// the whole query, unless every day fails. Fails when `from` is after `to`.
 pub async fn get_raw_check_results_range(
     db: &Database,
     check_id: Uuid,
     regions: &[Region],
     from: DateTime<Utc>,
     to: DateTime<Utc>,
-) -> Result<Vec<CheckResultRow>> {
+) -> Result<PartialCheckResults> {
+    match query_check_results_partitions(db, check_id, regions, from, to).await? {
+        (_, Some(error)) => Err(error),
+        (partial, None) => Ok(partial),
+    }
+}
+
+/// Like [`get_raw_check_results_range`], but still succeeds when every day fails, with all of
+/// them reported in [`PartialCheckResults::errors`]
+pub async fn get_available_check_results_range(
+    db: &Database,
+    check_id: Uuid,
+    regions: &[Region],
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+) -> Result<PartialCheckResults> {
+    let (partial, _) = query_check_results_partitions(db, check_id, regions, from, to).await?;
+    Ok(partial)
+}
+
+async fn query_check_results_partitions(
+    db: &Database,
+    check_id: Uuid,
+    regions: &[Region],
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+) -> Result<(PartialCheckResults, Option<anyhow::Error>)> {
+    if from > to {
+        bail!("'from' ({from}) must not be after 'to' ({to})");
+    }
+
     let dates = get_dates_in_range(from, to);
     let regions_vec: Vec<_> = regions.iter().map(|r| r.to_identifier()).collect();
+    let regions_vec = &regions_vec;
 
-    let futures =
-        dates
-            .iter()
-            .map(|date| (date, &regions_vec))
-            .map(move |(&day, regions_vec)| async move {
-                let result = GET_RAW_CHECK_RESULTS_QUERY_RANGE
-                    .execute_unpaged(db, (check_id, &regions_vec, day, from, to))
-                    .await?
-                    .into_rows_result()?;
-
-                let rows = result.rows::<(String, DateTime<Utc>, i64, Option<i32>, bool)>()?;
-
-                rows.map(|row| {
-                    let (
-                        region_id,
-                        check_started_at,
-                        response_time_micros,
-                        _status_code,
-                        matches_expected,
-                    ) = row?;
-                    let region = Region::from_identifier(&region_id)?;
-                    Ok(CheckResultRow {
-                        check_started_at,
-                        response_time_micros,
-                        matches_expected,
-                        region,
-                    })
+    let queries = dates.into_iter().map(move |day| {
+        (day, move || async move {
+            let result = GET_RAW_CHECK_RESULTS_QUERY_RANGE
+                .execute_unpaged(db, (check_id, regions_vec, day, from, to))
+                .await?
+                .into_rows_result()?;
+
+            let rows = result.rows::<(
+                String,
+                DateTime<Utc>,
+                i64,
+                Option<i32>,
+                bool,
+                Option<i64>,
+                Option<i64>,
+                Option<i32>,
+            )>()?;
+
+            rows.map(|row| {
+                let (
+                    region_id,
+                    check_started_at,
+                    response_time_micros,
+                    status_code,
+                    matches_expected,
+                    response_size_bytes,
+                    ttfb_micros,
+                    sample_weight,
+                ) = row?;
+                let region = Region::from_identifier(&region_id)?;
+                Ok(CheckResultRow {
+                    check_started_at,
+                    response_time_micros,
+                    status_code,
+                    matches_expected,
+                    response_size_bytes,
+                    ttfb_micros,
+                    region,
+                    sample_weight: sample_weight.unwrap_or(1),
                 })
-                .collect::<Result<Vec<_>>>()
-            });
+            })
+            .collect::<Result<Vec<_>>>()
+        })
+    });
 
-    stream::iter(futures)
-        .buffer_unordered(*eager_env::DATABASE_CONCURRENT_REQUESTS)
-        .try_collect::<Vec<_>>()
-        .await
-        .map(|results| results.into_iter().flatten().collect())
+    let timeout = match *eager_env::DATABASE_PARTITION_TIMEOUT_MILLIS {
+        0 => None,
+        millis => Some(std::time::Duration::from_millis(millis)),
+    };
+
+    Ok(collect_partitions(queries, timeout).await)
+}
+
+static GET_LATEST_CHECK_RESULT_QUERY: CachedPreparedStatement = CachedPreparedStatement::new(
+    "
+    SELECT region,
+           check_started_at,
+           response_time_micros,
+           status_code,
+           matches_expected,
+           response_size_bytes,
+           ttfb_micros,
+           sample_weight
+    FROM check_results
+    WHERE service_check_id = ?
+      AND region = ?
+      AND day = ?
+    LIMIT 1
+    ",
+);
+
+/// Most recent result of a check in a region, from the day of `now` or the one before, so that
+/// results from just before midnight are still found
+pub async fn get_latest_check_result(
+    db: &Database,
+    check_id: Uuid,
+    region: Region,
+    now: DateTime<Utc>,
+) -> Result<Option<CheckResultRow>> {
+    let today = now.date_naive();
+    let days = [today, today.pred_opt().expect("representable date")];
+
+    for day in days {
+        let row = GET_LATEST_CHECK_RESULT_QUERY
+            .execute_unpaged(db, (check_id, region.to_identifier(), day))
+            .await?
+            .into_rows_result()?
+            .maybe_first_row::<(
+                String,
+                DateTime<Utc>,
+                i64,
+                Option<i32>,
+                bool,
+                Option<i64>,
+                Option<i64>,
+                Option<i32>,
+            )>()?;
+
+        if let Some((
+            region_id,
+            check_started_at,
+            response_time_micros,
+            status_code,
+            matches_expected,
+            response_size_bytes,
+            ttfb_micros,
+            sample_weight,
+        )) = row
+        {
+            return Ok(Some(CheckResultRow {
+                check_started_at,
+                response_time_micros,
+                status_code,
+                matches_expected,
+                response_size_bytes,
+                ttfb_micros,
+                region: Region::from_identifier(&region_id)?,
+                sample_weight: sample_weight.unwrap_or(1),
+            }));
+        }
+    }
+
+    Ok(None)
+}
+
+/// Runs a per-day sub-query, giving up on an attempt after `timeout` and retrying it once
+async fn fetch_partition<F>(
+    day: NaiveDate,
+    query: impl Fn() -> F,
+    timeout: Option<std::time::Duration>,
+) -> Result<Vec<CheckResultRow>>
+where
+    F: Future<Output = Result<Vec<CheckResultRow>>>,
+{
+    let attempt = || async {
+        match timeout {
+            Some(timeout) => tokio::time::timeout(timeout, query()).await?,
+            None => query().await,
+        }
+    };
+
+    match attempt().await {
+        Ok(rows) => Ok(rows),
+        Err(error) => {
+            debug!("Retrying check results for {day}: {error:?}");
+            attempt().await
+        }
+    }
+}
+
+/// Runs the per-day sub-queries, keeping the results of the successful ones.
+///
+/// Also returns the last error if there were sub-queries and all of them failed.
+async fn collect_partitions<F>(
+    partitions: impl Iterator<Item = (NaiveDate, impl Fn() -> F)>,
+    timeout: Option<std::time::Duration>,
+) -> (PartialCheckResults, Option<anyhow::Error>)
+where
+    F: Future<Output = Result<Vec<CheckResultRow>>>,
+{
+    let results: Vec<_> = stream::iter(
+        partitions
+            .map(|(day, query)| async move { (day, fetch_partition(day, query, timeout).await) }),
+    )
+    .buffer_unordered(eager_env::DATABASE_CONCURRENT_READS.get())
+    .collect()
+    .await;
+
+    let mut partial = PartialCheckResults::default();
+    let mut succeeded = false;
+    let mut last_error = None;
+
+    for (day, result) in results {
+        match result {
+            Ok(rows) => {
+                partial.rows.extend(rows);
+                succeeded = true;
+            }
+            Err(error) => {
+                warn!("Failed to fetch check results for {day}: {error:?}");
+                partial.errors.push(if error.is::<Elapsed>() {
+                    format!("Results for {day} timed out")
+                } else {
+                    format!("Results for {day} are unavailable")
+                });
+                last_error = Some(error);
+            }
+        }
+    }
+
+    partial.errors.sort();
+    (partial, last_error.filter(|_| !succeeded))
 }
 
 static GET_CACHED_HOURLY_CHECK_RESULTS_QUERY: CachedPreparedStatement =
@@ -117,7 +354,12 @@ static GET_CACHED_HOURLY_CHECK_RESULTS_QUERY: CachedPreparedStatement =
                p50_response_time_micros,
                p95_response_time_micros,
                p99_response_time_micros,
-               uptime_percent
+               uptime_percent,
+               avg_response_size_bytes,
+               max_response_size_bytes,
+               status_code_counts,
+               avg_ttfb_micros,
+               partial
         FROM check_results_hourly
         WHERE service_check_id = ?
           AND region IN ?
@@ -138,7 +380,12 @@ static GET_CACHED_DAILY_CHECK_RESULTS_QUERY: CachedPreparedStatement = CachedPre
            p50_response_time_micros,
            p95_response_time_micros,
            p99_response_time_micros,
-           uptime_percent
+           uptime_percent,
+           avg_response_size_bytes,
+           max_response_size_bytes,
+           status_code_counts,
+           avg_ttfb_micros,
+           partial
     FROM check_results_daily
     WHERE service_check_id = ?
       AND region IN ?
@@ -152,6 +399,32 @@ pub struct MetricsSummaryRegionDate {
     pub metrics_summary: MetricsSummary,
     pub date: DateTime<Utc>,
     pub region: Region,
+    /// Aggregated from part of the point's results only, e.g. by a node flushing on shutdown
+    pub partial: bool,
+}
+
+/// Cached rows computed before the column existed have no counts
+fn status_code_counts_from_column(column: Option<HashMap<i32, i32>>) -> HashMap<i32, u32> {
+    column
+        .unwrap_or_default()
+        .into_iter()
+        .map(|(status_code, count)| (status_code, count as u32))
+        .collect()
+}
+
+fn status_code_counts_to_column(counts: &HashMap<i32, u32>) -> HashMap<i32, i32> {
+    counts
+        .iter()
+        .map(|(&status_code, &count)| (status_code, count as i32))
+        .collect()
+}
+
+/// The cache only stores the time-weighted uptime, the count-weighted one follows from the counts
+fn count_weighted_uptime_percent(successful_checks: i32, failed_checks: i32) -> f32 {
+    match successful_checks + failed_checks {
+        0 => 0.0,
+        total => (successful_checks as f32 / total as f32) * 100.0,
+    }
 }
 
 /// Get cached check results for the time range `[from, to)`.
@@ -198,10 +471,15 @@ pub async fn get_hourly_cached_check_results(
         i64,
         i64,
         i64,
-        i64,
-        i64,
-        i64,
+        Option<i64>,
+        Option<i64>,
+        Option<i64>,
         f32,
+        Option<i64>,
+        Option<i64>,
+        Option<HashMap<i32, i32>>,
+        Option<i64>,
+        Option<bool>,
     )>()?;
 
     rows.map(|row| {
@@ -217,23 +495,41 @@ pub async fn get_hourly_cached_check_results(
             p95_response_time_micros,
             p99_response_time_micros,
             uptime_percent,
+            avg_response_size_bytes,
+            max_response_size_bytes,
+            status_code_counts,
+            avg_ttfb_micros,
+            partial,
         ) = row?;
         let region = Region::from_identifier(&region_id)?;
         Ok(MetricsSummaryRegionDate {
             metrics_summary: MetricsSummary {
+                has_data: successful_checks + failed_checks > 0,
                 uptime_percent,
+                time_weighted_uptime_percent: uptime_percent,
+                count_weighted_uptime_percent: count_weighted_uptime_percent(
+                    successful_checks,
+                    failed_checks,
+                ),
                 total_checks: (successful_checks + failed_checks) as u32,
                 successful_checks: successful_checks as u32,
                 failed_checks: failed_checks as u32,
+                response_time_unit: ResponseTimeUnit::Micros,
                 avg_response_time_micros,
                 min_response_time_micros,
                 max_response_time_micros,
                 p50_response_time_micros,
                 p95_response_time_micros,
                 p99_response_time_micros,
+                avg_response_size_bytes,
+                max_response_size_bytes,
+                avg_ttfb_micros,
+                status_code_counts: status_code_counts_from_column(status_code_counts),
+                time_in_state: None,
             },
             date: hour,
             region,
+            partial: partial.unwrap_or(false),
         })
     })
     .collect::<Result<Vec<_>>>()
@@ -265,10 +561,15 @@ pub async fn get_daily_cached_check_results(
         i64,
         i64,
         i64,
-        i64,
-        i64,
-        i64,
+        Option<i64>,
+        Option<i64>,
+        Option<i64>,
         f32,
+        Option<i64>,
+        Option<i64>,
+        Option<HashMap<i32, i32>>,
+        Option<i64>,
+        Option<bool>,
     )>()?;
 
     rows.map(|row| {
@@ -284,23 +585,41 @@ pub async fn get_daily_cached_check_results(
             p95_response_time_micros,
             p99_response_time_micros,
             uptime_percent,
+            avg_response_size_bytes,
+            max_response_size_bytes,
+            status_code_counts,
+            avg_ttfb_micros,
+            partial,
         ) = row?;
         let region = Region::from_identifier(&region_id)?;
         Ok(MetricsSummaryRegionDate {
             metrics_summary: MetricsSummary {
+                has_data: successful_checks + failed_checks > 0,
                 uptime_percent,
+                time_weighted_uptime_percent: uptime_percent,
+                count_weighted_uptime_percent: count_weighted_uptime_percent(
+                    successful_checks,
+                    failed_checks,
+                ),
                 total_checks: (successful_checks + failed_checks) as u32,
                 successful_checks: successful_checks as u32,
                 failed_checks: failed_checks as u32,
+                response_time_unit: ResponseTimeUnit::Micros,
                 avg_response_time_micros,
                 min_response_time_micros,
                 max_response_time_micros,
                 p50_response_time_micros,
                 p95_response_time_micros,
                 p99_response_time_micros,
+                avg_response_size_bytes,
+                max_response_size_bytes,
+                avg_ttfb_micros,
+                status_code_counts: status_code_counts_from_column(status_code_counts),
+                time_in_state: None,
             },
             date: day.and_hms_opt(0, 0, 0).unwrap().and_utc(),
             region,
+            partial: partial.unwrap_or(false),
         })
     })
     .collect::<Result<Vec<_>>>()
@@ -320,8 +639,23 @@ static INSERT_HOURLY_CACHED_CHECK_RESULTS: CachedPreparedStatement = CachedPrepa
                                       p95_response_time_micros,
                                       p99_response_time_micros,
                                       uptime_percent,
-                                      computed_at)
-    VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+                                      avg_response_size_bytes,
+                                      max_response_size_bytes,
+                                      status_code_counts,
+                                      avg_ttfb_micros,
+                                      computed_at,
+                                      response_time_sum_micros,
+                                      response_time_sketch,
+                                      ttfb_sum_micros,
+                                      ttfb_samples,
+                                      response_size_sum_bytes,
+                                      response_size_samples,
+                                      first_result_at,
+                                      last_result_at,
+                                      last_result_up,
+                                      up_micros,
+                                      partial)
+    VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
     ",
 );
 
@@ -339,11 +673,126 @@ static INSERT_DAILY_CACHED_CHECK_RESULTS: CachedPreparedStatement = CachedPrepar
                                      p95_response_time_micros,
                                      p99_response_time_micros,
                                      uptime_percent,
-                                     computed_at)
-    VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+                                     avg_response_size_bytes,
+                                     max_response_size_bytes,
+                                     status_code_counts,
+                                     avg_ttfb_micros,
+                                     computed_at,
+                                     response_time_sum_micros,
+                                     response_time_sketch,
+                                     ttfb_sum_micros,
+                                     ttfb_samples,
+                                     response_size_sum_bytes,
+                                     response_size_samples,
+                                     first_result_at,
+                                     last_result_at,
+                                     last_result_up,
+                                     up_micros,
+                                     partial)
+    VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
     ",
 );
 
+/// Values bound to [`INSERT_HOURLY_CACHED_CHECK_RESULTS`] and
+/// [`INSERT_DAILY_CACHED_CHECK_RESULTS`], `date` being the `hour` or the `day`
+#[derive(SerializeRow)]
+#[scylla(flavor = "enforce_order", skip_name_checks)]
+struct CachedCheckResultInsertRow<D: SerializeValue> {
+    service_check_id: Uuid,
+    region: &'static str,
+    date: D,
+    successful_checks: i32,
+    failed_checks: i32,
+    avg_response_time_micros: i64,
+    min_response_time_micros: i64,
+    max_response_time_micros: i64,
+    p50_response_time_micros: Option<i64>,
+    p95_response_time_micros: Option<i64>,
+    p99_response_time_micros: Option<i64>,
+    uptime_percent: f32,
+    avg_response_size_bytes: Option<i64>,
+    max_response_size_bytes: Option<i64>,
+    status_code_counts: HashMap<i32, i32>,
+    avg_ttfb_micros: Option<i64>,
+    computed_at: DateTime<Utc>,
+    response_time_sum_micros: Option<i64>,
+    response_time_sketch: Option<HashMap<i32, i32>>,
+    ttfb_sum_micros: Option<i64>,
+    ttfb_samples: Option<i32>,
+    response_size_sum_bytes: Option<i64>,
+    response_size_samples: Option<i32>,
+    first_result_at: Option<DateTime<Utc>>,
+    last_result_at: Option<DateTime<Utc>>,
+    last_result_up: Option<bool>,
+    up_micros: Option<i64>,
+    partial: bool,
+}
+
+impl<D: SerializeValue> CachedCheckResultInsertRow<D> {
+    fn new(check_id: Uuid, region: Region, date: D, metrics: &MetricsSummary) -> Self {
+        Self {
+            service_check_id: check_id,
+            region: region.to_identifier(),
+            date,
+            successful_checks: metrics.successful_checks as i32,
+            failed_checks: metrics.failed_checks as i32,
+            avg_response_time_micros: metrics.avg_response_time_micros,
+            min_response_time_micros: metrics.min_response_time_micros,
+            max_response_time_micros: metrics.max_response_time_micros,
+            p50_response_time_micros: metrics.p50_response_time_micros,
+            p95_response_time_micros: metrics.p95_response_time_micros,
+            p99_response_time_micros: metrics.p99_response_time_micros,
+            uptime_percent: metrics.time_weighted_uptime_percent,
+            avg_response_size_bytes: metrics.avg_response_size_bytes,
+            max_response_size_bytes: metrics.max_response_size_bytes,
+            status_code_counts: status_code_counts_to_column(&metrics.status_code_counts),
+            avg_ttfb_micros: metrics.avg_ttfb_micros,
+            computed_at: Utc::now(),
+            response_time_sum_micros: None,
+            response_time_sketch: None,
+            ttfb_sum_micros: None,
+            ttfb_samples: None,
+            response_size_sum_bytes: None,
+            response_size_samples: None,
+            first_result_at: None,
+            last_result_at: None,
+            last_result_up: None,
+            up_micros: None,
+            partial: false,
+        }
+    }
+
+    /// Also stores what later writes merge `aggregate` with
+    fn from_aggregate(
+        check_id: Uuid,
+        region: Region,
+        date: D,
+        aggregate: &PeriodAggregate,
+        partial: bool,
+    ) -> Self {
+        Self {
+            response_time_sum_micros: Some(aggregate.response_time_sum_micros),
+            response_time_sketch: Some(
+                aggregate
+                    .response_time_sketch
+                    .iter()
+                    .map(|(&bucket, &count)| (bucket, count as i32))
+                    .collect(),
+            ),
+            ttfb_sum_micros: Some(aggregate.ttfb_sum_micros),
+            ttfb_samples: Some(aggregate.ttfb_samples as i32),
+            response_size_sum_bytes: Some(aggregate.response_size_sum_bytes),
+            response_size_samples: Some(aggregate.response_size_samples as i32),
+            first_result_at: aggregate.first_result_at,
+            last_result_at: aggregate.last_result_at,
+            last_result_up: Some(aggregate.last_result_up),
+            up_micros: aggregate.up_duration.num_microseconds(),
+            partial,
+            ..Self::new(check_id, region, date, &aggregate.metrics())
+        }
+    }
+}
+
 pub async fn insert_hourly_cached_check_result(
     db: &Database,
     check_id: Uuid,
@@ -354,21 +803,7 @@ pub async fn insert_hourly_cached_check_result(
     INSERT_HOURLY_CACHED_CHECK_RESULTS
         .execute_unpaged(
             db,
-            (
-                check_id,
-                region.to_identifier(),
-                date,
-                metrics.successful_checks as i32,
-                metrics.failed_checks as i32,
-                metrics.avg_response_time_micros,
-                metrics.min_response_time_micros,
-                metrics.max_response_time_micros,
-                metrics.p50_response_time_micros,
-                metrics.p95_response_time_micros,
-                metrics.p99_response_time_micros,
-                metrics.uptime_percent,
-                Utc::now(),
-            ),
+            CachedCheckResultInsertRow::new(check_id, region, date, metrics),
         )
         .await?;
 
@@ -385,27 +820,580 @@ pub async fn insert_daily_cached_check_result(
     INSERT_DAILY_CACHED_CHECK_RESULTS
         .execute_unpaged(
             db,
-            (
-                check_id,
-                region.to_identifier(),
-                date.date_naive(),
-                metrics.successful_checks as i32,
-                metrics.failed_checks as i32,
-                metrics.avg_response_time_micros,
-                metrics.min_response_time_micros,
-                metrics.max_response_time_micros,
-                metrics.p50_response_time_micros,
-                metrics.p95_response_time_micros,
-                metrics.p99_response_time_micros,
-                metrics.uptime_percent,
-                Utc::now(),
-            ),
+            CachedCheckResultInsertRow::new(check_id, region, date.date_naive(), metrics),
         )
         .await?;
 
     Ok(())
 }
 
+static GET_HOURLY_CACHED_AGGREGATE_QUERY: CachedPreparedStatement = CachedPreparedStatement::new(
+    "
+    SELECT region,
+           successful_checks,
+           failed_checks,
+           avg_response_time_micros,
+           min_response_time_micros,
+           max_response_time_micros,
+           avg_response_size_bytes,
+           max_response_size_bytes,
+           status_code_counts,
+           avg_ttfb_micros,
+           response_time_sum_micros,
+           response_time_sketch,
+           ttfb_sum_micros,
+           ttfb_samples,
+           response_size_sum_bytes,
+           response_size_samples,
+           first_result_at,
+           last_result_at,
+           last_result_up,
+           up_micros,
+           version
+    FROM check_results_hourly
+    WHERE service_check_id = ?
+      AND region = ?
+      AND hour = ?
+    ",
+);
+
+static GET_DAILY_CACHED_AGGREGATE_QUERY: CachedPreparedStatement = CachedPreparedStatement::new(
+    "
+    SELECT region,
+           successful_checks,
+           failed_checks,
+           avg_response_time_micros,
+           min_response_time_micros,
+           max_response_time_micros,
+           avg_response_size_bytes,
+           max_response_size_bytes,
+           status_code_counts,
+           avg_ttfb_micros,
+           response_time_sum_micros,
+           response_time_sketch,
+           ttfb_sum_micros,
+           ttfb_samples,
+           response_size_sum_bytes,
+           response_size_samples,
+           first_result_at,
+           last_result_at,
+           last_result_up,
+           up_micros,
+           version
+    FROM check_results_daily
+    WHERE service_check_id = ?
+      AND region = ?
+      AND day = ?
+    ",
+);
+
+/// Row of [`GET_HOURLY_CACHED_AGGREGATE_QUERY`], [`GET_DAILY_CACHED_AGGREGATE_QUERY`] and
+/// [`GET_HOURLY_CACHED_AGGREGATES_QUERY`]
+#[derive(DeserializeRow)]
+struct CachedAggregateRow {
+    region: String,
+    successful_checks: i32,
+    failed_checks: i32,
+    avg_response_time_micros: i64,
+    min_response_time_micros: i64,
+    max_response_time_micros: i64,
+    avg_response_size_bytes: Option<i64>,
+    max_response_size_bytes: Option<i64>,
+    status_code_counts: Option<HashMap<i32, i32>>,
+    avg_ttfb_micros: Option<i64>,
+    response_time_sum_micros: Option<i64>,
+    response_time_sketch: Option<HashMap<i32, i32>>,
+    ttfb_sum_micros: Option<i64>,
+    ttfb_samples: Option<i32>,
+    response_size_sum_bytes: Option<i64>,
+    response_size_samples: Option<i32>,
+    first_result_at: Option<DateTime<Utc>>,
+    last_result_at: Option<DateTime<Utc>>,
+    last_result_up: Option<bool>,
+    up_micros: Option<i64>,
+    version: Option<i32>,
+}
+
+impl CachedAggregateRow {
+    /// Rows computed from raw results have no sums nor sketch, those follow from the averages
+    /// and the percentiles are left to the merged results
+    fn into_aggregate(self) -> PeriodAggregate {
+        let total_checks = (self.successful_checks + self.failed_checks) as u32;
+        let ttfb_samples = match self.ttfb_samples {
+            Some(samples) => samples as u32,
+            None if self.avg_ttfb_micros.is_some() => total_checks,
+            None => 0,
+        };
+        let response_size_samples = match self.response_size_samples {
+            Some(samples) => samples as u32,
+            None if self.avg_response_size_bytes.is_some() => total_checks,
+            None => 0,
+        };
+
+        PeriodAggregate {
+            successful_checks: self.successful_checks as u32,
+            total_checks,
+            response_time_sum_micros: self
+                .response_time_sum_micros
+                .unwrap_or(self.avg_response_time_micros * i64::from(total_checks)),
+            min_response_time_micros: self.min_response_time_micros,
+            max_response_time_micros: self.max_response_time_micros,
+            response_time_sketch: self
+                .response_time_sketch
+                .unwrap_or_default()
+                .into_iter()
+                .map(|(bucket, count)| (bucket, count as u32))
+                .collect(),
+            ttfb_sum_micros: self
+                .ttfb_sum_micros
+                .unwrap_or(self.avg_ttfb_micros.unwrap_or(0) * i64::from(ttfb_samples)),
+            ttfb_samples,
+            response_size_sum_bytes: self.response_size_sum_bytes.unwrap_or(
+                self.avg_response_size_bytes.unwrap_or(0) * i64::from(response_size_samples),
+            ),
+            response_size_samples,
+            max_response_size_bytes: self.max_response_size_bytes,
+            status_code_counts: status_code_counts_from_column(self.status_code_counts),
+            first_result_at: self.first_result_at,
+            last_result_at: self.last_result_at,
+            last_result_up: self.last_result_up.unwrap_or(false),
+            up_duration: Duration::microseconds(self.up_micros.unwrap_or(0)),
+        }
+    }
+}
+
+/// A cached aggregate along with the version [`update_cached_aggregate`] compares,
+/// `None` for rows computed from raw results
+#[derive(Debug, Clone, PartialEq)]
+pub struct VersionedAggregate {
+    pub aggregate: PeriodAggregate,
+    pub version: Option<i32>,
+}
+
+/// The cached aggregate of the point of `granularity` starting at `date`, if any.
+///
+/// Read at serial consistency, to see the writes of [`update_cached_aggregate`] still in progress.
+pub async fn get_cached_aggregate(
+    db: &Database,
+    check_id: Uuid,
+    region: Region,
+    date: DateTime<Utc>,
+    granularity: GraphGranularity,
+) -> Result<Option<VersionedAggregate>> {
+    let region = region.to_identifier();
+    let result = match granularity {
+        GraphGranularity::Hourly => {
+            let mut statement = GET_HOURLY_CACHED_AGGREGATE_QUERY
+                .get_prepared_statement(db)
+                .await?;
+            statement.set_consistency(Consistency::LocalSerial);
+            db.execute_unpaged(&statement, (check_id, region, date))
+                .await?
+        }
+        GraphGranularity::Daily => {
+            let mut statement = GET_DAILY_CACHED_AGGREGATE_QUERY
+                .get_prepared_statement(db)
+                .await?;
+            statement.set_consistency(Consistency::LocalSerial);
+            db.execute_unpaged(&statement, (check_id, region, date.date_naive()))
+                .await?
+        }
+    };
+
+    Ok(result
+        .into_rows_result()?
+        .maybe_first_row::<CachedAggregateRow>()?
+        .map(|row| VersionedAggregate {
+            version: row.version,
+            aggregate: row.into_aggregate(),
+        }))
+}
+
+static GET_HOURLY_CACHED_AGGREGATES_QUERY: CachedPreparedStatement = CachedPreparedStatement::new(
+    "
+    SELECT region,
+           successful_checks,
+           failed_checks,
+           avg_response_time_micros,
+           min_response_time_micros,
+           max_response_time_micros,
+           avg_response_size_bytes,
+           max_response_size_bytes,
+           status_code_counts,
+           avg_ttfb_micros,
+           response_time_sum_micros,
+           response_time_sketch,
+           ttfb_sum_micros,
+           ttfb_samples,
+           response_size_sum_bytes,
+           response_size_samples,
+           first_result_at,
+           last_result_at,
+           last_result_up,
+           up_micros,
+           version
+    FROM check_results_hourly
+    WHERE service_check_id = ?
+      AND region IN ?
+      AND hour >= ?
+      AND hour < ?
+    ",
+);
+
+/// The cached aggregates of the hours starting in `[from, to)`, along with their region
+pub async fn get_hourly_cached_aggregates(
+    db: &Database,
+    check_id: Uuid,
+    regions: &[Region],
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+) -> Result<Vec<(Region, PeriodAggregate)>> {
+    let regions_vec: Vec<_> = regions.iter().map(|r| r.to_identifier()).collect();
+
+    GET_HOURLY_CACHED_AGGREGATES_QUERY
+        .execute_unpaged(db, (check_id, &regions_vec, from, to))
+        .await?
+        .into_rows_result()?
+        .rows::<CachedAggregateRow>()?
+        .map(|row| {
+            let row = row?;
+            Ok((Region::from_identifier(&row.region)?, row.into_aggregate()))
+        })
+        .collect()
+}
+
+static UPDATE_HOURLY_CACHED_AGGREGATE: CachedPreparedStatement = CachedPreparedStatement::new(
+    "
+    UPDATE check_results_hourly
+    SET successful_checks = ?,
+        failed_checks = ?,
+        avg_response_time_micros = ?,
+        min_response_time_micros = ?,
+        max_response_time_micros = ?,
+        p50_response_time_micros = ?,
+        p95_response_time_micros = ?,
+        p99_response_time_micros = ?,
+        uptime_percent = ?,
+        avg_response_size_bytes = ?,
+        max_response_size_bytes = ?,
+        status_code_counts = ?,
+        avg_ttfb_micros = ?,
+        computed_at = ?,
+        response_time_sum_micros = ?,
+        response_time_sketch = ?,
+        ttfb_sum_micros = ?,
+        ttfb_samples = ?,
+        response_size_sum_bytes = ?,
+        response_size_samples = ?,
+        first_result_at = ?,
+        last_result_at = ?,
+        last_result_up = ?,
+        up_micros = ?,
+        partial = ?,
+        version = ?
+    WHERE service_check_id = ?
+      AND region = ?
+      AND hour = ?
+    IF version = ?
+    ",
+);
+
+static UPDATE_DAILY_CACHED_AGGREGATE: CachedPreparedStatement = CachedPreparedStatement::new(
+    "
+    UPDATE check_results_daily
+    SET successful_checks = ?,
+       failed_checks = ?,
+       avg_response_time_micros = ?,
+       min_response_time_micros = ?,
+       max_response_time_micros = ?,
+       p50_response_time_micros = ?,
+       p95_response_time_micros = ?,
+       p99_response_time_micros = ?,
+       uptime_percent = ?,
+       avg_response_size_bytes = ?,
+       max_response_size_bytes = ?,
+       status_code_counts = ?,
+       avg_ttfb_micros = ?,
+       computed_at = ?,
+       response_time_sum_micros = ?,
+       response_time_sketch = ?,
+       ttfb_sum_micros = ?,
+       ttfb_samples = ?,
+       response_size_sum_bytes = ?,
+       response_size_samples = ?,
+       first_result_at = ?,
+       last_result_at = ?,
+       last_result_up = ?,
+       up_micros = ?,
+       partial = ?,
+       version = ?
+    WHERE service_check_id = ?
+      AND region = ?
+      AND day = ?
+    IF version = ?
+    ",
+);
+
+/// Values bound to [`UPDATE_HOURLY_CACHED_AGGREGATE`] and [`UPDATE_DAILY_CACHED_AGGREGATE`]
+#[derive(SerializeRow)]
+#[scylla(flavor = "enforce_order", skip_name_checks)]
+struct CachedAggregateUpdateRow<D: SerializeValue> {
+    successful_checks: i32,
+    failed_checks: i32,
+    avg_response_time_micros: i64,
+    min_response_time_micros: i64,
+    max_response_time_micros: i64,
+    p50_response_time_micros: Option<i64>,
+    p95_response_time_micros: Option<i64>,
+    p99_response_time_micros: Option<i64>,
+    uptime_percent: f32,
+    avg_response_size_bytes: Option<i64>,
+    max_response_size_bytes: Option<i64>,
+    status_code_counts: HashMap<i32, i32>,
+    avg_ttfb_micros: Option<i64>,
+    computed_at: DateTime<Utc>,
+    response_time_sum_micros: Option<i64>,
+    response_time_sketch: Option<HashMap<i32, i32>>,
+    ttfb_sum_micros: Option<i64>,
+    ttfb_samples: Option<i32>,
+    response_size_sum_bytes: Option<i64>,
+    response_size_samples: Option<i32>,
+    first_result_at: Option<DateTime<Utc>>,
+    last_result_at: Option<DateTime<Utc>>,
+    last_result_up: Option<bool>,
+    up_micros: Option<i64>,
+    partial: bool,
+    version: i32,
+    service_check_id: Uuid,
+    region: &'static str,
+    date: D,
+    expected_version: Option<i32>,
+}
+
+impl<D: SerializeValue> CachedAggregateUpdateRow<D> {
+    fn new(row: CachedCheckResultInsertRow<D>, expected_version: Option<i32>) -> Self {
+        Self {
+            successful_checks: row.successful_checks,
+            failed_checks: row.failed_checks,
+            avg_response_time_micros: row.avg_response_time_micros,
+            min_response_time_micros: row.min_response_time_micros,
+            max_response_time_micros: row.max_response_time_micros,
+            p50_response_time_micros: row.p50_response_time_micros,
+            p95_response_time_micros: row.p95_response_time_micros,
+            p99_response_time_micros: row.p99_response_time_micros,
+            uptime_percent: row.uptime_percent,
+            avg_response_size_bytes: row.avg_response_size_bytes,
+            max_response_size_bytes: row.max_response_size_bytes,
+            status_code_counts: row.status_code_counts,
+            avg_ttfb_micros: row.avg_ttfb_micros,
+            computed_at: row.computed_at,
+            response_time_sum_micros: row.response_time_sum_micros,
+            response_time_sketch: row.response_time_sketch,
+            ttfb_sum_micros: row.ttfb_sum_micros,
+            ttfb_samples: row.ttfb_samples,
+            response_size_sum_bytes: row.response_size_sum_bytes,
+            response_size_samples: row.response_size_samples,
+            first_result_at: row.first_result_at,
+            last_result_at: row.last_result_at,
+            last_result_up: row.last_result_up,
+            up_micros: row.up_micros,
+            partial: row.partial,
+            version: expected_version.map_or(1, |version| version + 1),
+            service_check_id: row.service_check_id,
+            region: row.region,
+            date: row.date,
+            expected_version,
+        }
+    }
+}
+
+/// Caches `aggregate` as the point of `granularity` starting at `date`, `partial` while some of
+/// the point's results may still be missing from it.
+///
+/// Only applied if the cached version is still `expected_version`, read along with what
+/// `aggregate` was merged with: another node may have merged into the point meanwhile.
+/// Returns whether it was applied.
+#[allow(clippy::too_many_arguments)]
+pub async fn update_cached_aggregate(
+    db: &Database,
+    check_id: Uuid,
+    region: Region,
+    date: DateTime<Utc>,
+    granularity: GraphGranularity,
+    aggregate: &PeriodAggregate,
+    partial: bool,
+    expected_version: Option<i32>,
+) -> Result<bool> {
+    let result = match granularity {
+        GraphGranularity::Hourly => {
+            UPDATE_HOURLY_CACHED_AGGREGATE
+                .execute_unpaged(
+                    db,
+                    CachedAggregateUpdateRow::new(
+                        CachedCheckResultInsertRow::from_aggregate(
+                            check_id, region, date, aggregate, partial,
+                        ),
+                        expected_version,
+                    ),
+                )
+                .await?
+        }
+        GraphGranularity::Daily => {
+            UPDATE_DAILY_CACHED_AGGREGATE
+                .execute_unpaged(
+                    db,
+                    CachedAggregateUpdateRow::new(
+                        CachedCheckResultInsertRow::from_aggregate(
+                            check_id,
+                            region,
+                            date.date_naive(),
+                            aggregate,
+                            partial,
+                        ),
+                        expected_version,
+                    ),
+                )
+                .await?
+        }
+    };
+
+    // `[applied]` comes first, followed by the current version when not applied
+    let row = result.into_rows_result()?.first_row::<Row>()?;
+    match row.columns.first() {
+        Some(Some(CqlValue::Boolean(applied))) => Ok(*applied),
+        _ => bail!("conditional update without an [applied] column"),
+    }
+}
+
+static GET_HOURLY_MERGE_STATES_QUERY: CachedPreparedStatement = CachedPreparedStatement::new(
+    "
+    SELECT hour,
+           first_result_at,
+           response_time_sketch
+    FROM check_results_hourly
+    WHERE service_check_id = ?
+      AND region IN ?
+      AND hour >= ?
+      AND hour < ?
+    ",
+);
+
+static GET_DAILY_MERGE_STATES_QUERY: CachedPreparedStatement = CachedPreparedStatement::new(
+    "
+    SELECT day,
+           first_result_at,
+           response_time_sketch
+    FROM check_results_daily
+    WHERE service_check_id = ?
+      AND region IN ?
+      AND day >= ?
+      AND day < ?
+    ",
+);
+
+/// Starts of the points of `[from, to)` cached from aggregates in any of `regions`, i.e. whose
+/// rows carry what later writes merge with, see [`insert_cached_aggregate`].
+///
+/// Assumes `from` and `to` are already rounded to granularity.
+pub async fn get_aggregated_dates(
+    db: &Database,
+    check_id: Uuid,
+    regions: &[Region],
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+    granularity: GraphGranularity,
+) -> Result<BTreeSet<DateTime<Utc>>> {
+    let regions: Vec<_> = regions.iter().map(|r| r.to_identifier()).collect();
+
+    // Rows computed from raw results have neither
+    let points: Vec<(DateTime<Utc>, bool)> = match granularity {
+        GraphGranularity::Hourly => GET_HOURLY_MERGE_STATES_QUERY
+            .execute_unpaged(db, (check_id, &regions, from, to))
+            .await?
+            .into_rows_result()?
+            .rows::<(
+                DateTime<Utc>,
+                Option<DateTime<Utc>>,
+                Option<HashMap<i32, i32>>,
+            )>()?
+            .map(|row| row.map(|(hour, first, sketch)| (hour, first.is_some() || sketch.is_some())))
+            .collect::<Result<_, _>>()?,
+        GraphGranularity::Daily => GET_DAILY_MERGE_STATES_QUERY
+            .execute_unpaged(db, (check_id, &regions, from.date_naive(), to.date_naive()))
+            .await?
+            .into_rows_result()?
+            .rows::<(NaiveDate, Option<DateTime<Utc>>, Option<HashMap<i32, i32>>)>()?
+            .map(|row| {
+                row.map(|(day, first, sketch)| {
+                    let date = day.and_hms_opt(0, 0, 0).unwrap().and_utc();
+                    (date, first.is_some() || sketch.is_some())
+                })
+            })
+            .collect::<Result<_, _>>()?,
+    };
+
+    Ok(points
+        .into_iter()
+        .filter(|(_, aggregated)| *aggregated)
+        .map(|(date, _)| date)
+        .collect())
+}
+
+static DELETE_HOURLY_CACHED_CHECK_RESULTS: CachedPreparedStatement = CachedPreparedStatement::new(
+    "
+        DELETE FROM check_results_hourly
+        WHERE service_check_id = ?
+          AND region = ?
+          AND hour >= ?
+          AND hour < ?
+        ",
+);
+
+static DELETE_DAILY_CACHED_CHECK_RESULTS: CachedPreparedStatement = CachedPreparedStatement::new(
+    "
+    DELETE FROM check_results_daily
+    WHERE service_check_id = ?
+      AND region = ?
+      AND day >= ?
+      AND day < ?
+    ",
+);
+
+/// Deletes the cached results of the time range `[from, to)`.
+///
+/// Assumes `from` and `to` are already rounded to granularity.
+pub async fn delete_cached_check_results(
+    db: &Database,
+    check_id: Uuid,
+    regions: &[Region],
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+    granularity: GraphGranularity,
+) -> Result<()> {
+    // Range deletions must target a single partition
+    let futures = regions.iter().map(|region| async move {
+        let region = region.to_identifier();
+        match granularity {
+            GraphGranularity::Hourly => {
+                DELETE_HOURLY_CACHED_CHECK_RESULTS
+                    .execute_unpaged(db, (check_id, region, from, to))
+                    .await
+            }
+            GraphGranularity::Daily => {
+                DELETE_DAILY_CACHED_CHECK_RESULTS
+                    .execute_unpaged(db, (check_id, region, from.date_naive(), to.date_naive()))
+                    .await
+            }
+        }
+    });
+
+    stream::iter(futures)
+        .buffer_unordered(eager_env::DATABASE_CONCURRENT_WRITES.get())
+        .try_collect::<Vec<_>>()
+        .await?;
+    Ok(())
+}
+
 /// ``
 pub async fn insert_cached_check_result(
     db: &Database,
@@ -442,7 +1430,7 @@ pub async fn insert_cached_check_result(
         });
 
     stream::iter(futures)
-        .buffer_unordered(*eager_env::DATABASE_CONCURRENT_REQUESTS)
+        .buffer_unordered(eager_env::DATABASE_CONCURRENT_WRITES.get())
         .try_collect::<Vec<_>>()
         .await?;
     Ok(())
@@ -452,6 +1440,7 @@ pub async fn insert_cached_check_result(
 mod tests {
     use super::*;
     use crate::database::testing::create_test_database;
+    use proptest::prelude::*;
     use uuid::uuid;
 
     const FIXTURES: &str = include_str!("fixtures.cql");
@@ -473,11 +1462,13 @@ mod tests {
             to,
         )
         .await?;
-        assert_eq!(results.len(), 8); // 4 fsn1 + 2 hel1 + 2 nbg1
+        assert!(!results.is_partial());
+        assert_eq!(results.rows.len(), 8); // 4 fsn1 + 2 hel1 + 2 nbg1
 
         // Test: Query single region
-        let results_fsn1 =
-            get_raw_check_results_range(&db, check_id, &[Region::Fsn1], from, to).await?;
+        let results_fsn1 = get_raw_check_results_range(&db, check_id, &[Region::Fsn1], from, to)
+            .await?
+            .rows;
         assert_eq!(results_fsn1.len(), 4);
         assert!(results_fsn1.iter().all(|r| r.region == Region::Fsn1));
 
@@ -485,7 +1476,7 @@ mod tests {
         let nonexistent = uuid!("99999999-9999-9999-9999-999999999999");
         let empty =
             get_raw_check_results_range(&db, nonexistent, &[Region::Fsn1], from, to).await?;
-        assert!(empty.is_empty());
+        assert!(empty.rows.is_empty());
 
         // Test: Time range filtering works
         let narrow_from = "2025-11-29T10:00:00Z".parse::<DateTime<Utc>>()?;
@@ -493,11 +1484,124 @@ mod tests {
         let results_narrow =
             get_raw_check_results_range(&db, check_id, &[Region::Fsn1], narrow_from, narrow_to)
                 .await?;
-        assert_eq!(results_narrow.len(), 2); // 10:00 and 11:00
+        assert_eq!(results_narrow.rows.len(), 2); // 10:00 and 11:00
 
         Ok(())
     }
 
+    #[tokio::test]
+    async fn test_collect_partitions_keeps_successful_days() {
+        let row = |day: &str| CheckResultRow {
+            check_started_at: format!("{day}T10:00:00Z").parse().unwrap(),
+            response_time_micros: 1000,
+            status_code: Some(200),
+            matches_expected: true,
+            response_size_bytes: None,
+            ttfb_micros: None,
+            region: Region::Fsn1,
+            sample_weight: 1,
+        };
+        let day = |day: &str| day.parse::<NaiveDate>().unwrap();
+
+        let partitions = vec![
+            (day("2025-11-28"), Ok(vec![row("2025-11-28")])),
+            (day("2025-11-29"), Err("forced failure")),
+            (day("2025-11-30"), Ok(vec![row("2025-11-30")])),
+        ];
+        let (results, error) = collect_partitions(
+            partitions.into_iter().map(|(day, rows)| {
+                (day, move || {
+                    let rows = rows.clone();
+                    async move { rows.map_err(anyhow::Error::msg) }
+                })
+            }),
+            None,
+        )
+        .await;
+
+        assert!(error.is_none());
+        assert!(results.is_partial());
+        assert_eq!(results.rows.len(), 2);
+        assert_eq!(
+            results.errors,
+            vec!["Results for 2025-11-29 are unavailable"]
+        );
+
+        // Nothing to return when every day fails
+        let (results, error) = collect_partitions(
+            std::iter::once((day("2025-11-29"), || async {
+                Err(anyhow::anyhow!("forced failure"))
+            })),
+            None,
+        )
+        .await;
+        assert!(error.is_some());
+        assert_eq!(
+            results.errors,
+            vec!["Results for 2025-11-29 are unavailable"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_collect_partitions_retries_slow_days() {
+        let row = CheckResultRow {
+            check_started_at: "2025-11-29T10:00:00Z".parse().unwrap(),
+            response_time_micros: 1000,
+            status_code: Some(200),
+            matches_expected: true,
+            response_size_bytes: None,
+            ttfb_micros: None,
+            region: Region::Fsn1,
+            sample_weight: 1,
+        };
+        let timeout = Some(std::time::Duration::from_millis(50));
+
+        // Slow on the first attempt only
+        let attempts = std::sync::atomic::AtomicU32::new(0);
+        let (results, error) = collect_partitions(
+            std::iter::once(("2025-11-29".parse().unwrap(), || async {
+                if attempts.fetch_add(1, std::sync::atomic::Ordering::Relaxed) == 0 {
+                    tokio::time::sleep(std::time::Duration::from_secs(10)).await;
+                }
+                Ok(vec![row.clone()])
+            })),
+            timeout,
+        )
+        .await;
+        assert!(error.is_none());
+        assert!(!results.is_partial());
+        assert_eq!(results.rows.len(), 1);
+        assert_eq!(attempts.into_inner(), 2);
+
+        // Always slow, skipped after the retry
+        let started = std::time::Instant::now();
+        let (results, error) = collect_partitions(
+            [
+                ("2025-11-28".parse().unwrap(), false),
+                ("2025-11-29".parse().unwrap(), true),
+            ]
+            .into_iter()
+            .map(|(day, slow)| {
+                let row = row.clone();
+                (day, move || {
+                    let row = row.clone();
+                    async move {
+                        if slow {
+                            tokio::time::sleep(std::time::Duration::from_secs(10)).await;
+                        }
+                        Ok(vec![row])
+                    }
+                })
+            }),
+            timeout,
+        )
+        .await;
+        assert!(started.elapsed() < std::time::Duration::from_secs(5));
+        assert!(error.is_none());
+        assert_eq!(results.rows.len(), 1);
+        assert_eq!(results.errors, vec!["Results for 2025-11-29 timed out"]);
+    }
+
     #[test]
     fn test_get_dates_in_range() {
         // Single day
@@ -527,11 +1631,61 @@ mod tests {
         let dates = get_dates_in_range(from, to);
         assert_eq!(dates.len(), 2);
 
-        // Edge: same datetime
+        // Edge: same datetime, the empty range touches no day
         let from = "2025-11-29T10:00:00Z".parse::<DateTime<Utc>>().unwrap();
         let to = from;
         let dates = get_dates_in_range(from, to);
-        assert_eq!(dates.len(), 1);
+        assert!(dates.is_empty());
+    }
+
+    /// Timestamps within a few days of each other, often exactly at midnight
+    fn timestamp() -> impl Strategy<Value = DateTime<Utc>> {
+        let base = "2025-11-28T00:00:00Z".parse::<DateTime<Utc>>().unwrap();
+        let millis_per_day: i64 = 24 * 60 * 60 * 1000;
+        prop_oneof![
+            (0..5 * millis_per_day).prop_map(move |ms| base + chrono::Duration::milliseconds(ms)),
+            (0..5i64).prop_map(move |days| base + chrono::Duration::days(days)),
+        ]
+    }
+
+    proptest! {
+        #[test]
+        fn prop_dates_in_range_are_the_touched_days(from in timestamp(), to in timestamp()) {
+            let dates = get_dates_in_range(from, to);
+
+            // A day is touched iff [from, to) overlaps [midnight, next midnight)
+            let first = from.date_naive() - chrono::Duration::days(1);
+            let touched: Vec<_> = first
+                .iter_days()
+                .take(8)
+                .filter(|day| {
+                    let start = day.and_hms_opt(0, 0, 0).unwrap().and_utc();
+                    let end = start + chrono::Duration::days(1);
+                    from.max(start) < to.min(end)
+                })
+                .collect();
+
+            prop_assert_eq!(dates, touched);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_get_raw_check_results_rejects_reversed_range() -> Result<()> {
+        let (db, _keyspace) = create_test_database(Some(FIXTURES)).await?;
+        let check_id = uuid!("aaaaaaaa-aaaa-aaaa-aaaa-aaaaaaaaaaaa");
+        let from = "2025-11-29T14:00:00Z".parse::<DateTime<Utc>>()?;
+        let to = "2025-11-29T09:00:00Z".parse::<DateTime<Utc>>()?;
+
+        assert!(
+            get_raw_check_results_range(&db, check_id, &[Region::Fsn1], from, to)
+                .await
+                .is_err()
+        );
+        // Empty, but valid
+        let empty = get_raw_check_results_range(&db, check_id, &[Region::Fsn1], to, to).await?;
+        assert!(empty.rows.is_empty());
+
+        Ok(())
     }
 
     #[tokio::test]
@@ -568,16 +1722,25 @@ mod tests {
         // Insert new hourly metric
         let new_hourly_date = "2025-11-29T14:00:00Z".parse::<DateTime<Utc>>().unwrap();
         let new_metrics = MetricsSummary {
+            has_data: true,
             uptime_percent: 99.5,
+            time_weighted_uptime_percent: 99.5,
+            count_weighted_uptime_percent: 99.0,
             total_checks: 100,
             successful_checks: 99,
             failed_checks: 1,
+            response_time_unit: ResponseTimeUnit::Micros,
             avg_response_time_micros: 105000,
             min_response_time_micros: 70000,
             max_response_time_micros: 250000,
-            p50_response_time_micros: 100000,
-            p95_response_time_micros: 200000,
-            p99_response_time_micros: 240000,
+            p50_response_time_micros: Some(100000),
+            p95_response_time_micros: Some(200000),
+            p99_response_time_micros: Some(240000),
+            avg_ttfb_micros: Some(60000),
+            avg_response_size_bytes: Some(2048),
+            max_response_size_bytes: Some(4096),
+            status_code_counts: HashMap::from([(200, 99), (502, 1)]),
+            time_in_state: None,
         };
         insert_hourly_cached_check_result(
             &db,
@@ -596,6 +1759,11 @@ mod tests {
                 .await?;
         assert_eq!(verified.len(), 1);
         assert_eq!(verified[0].metrics_summary.successful_checks, 99);
+        assert_eq!(
+            verified[0].metrics_summary.status_code_counts,
+            new_metrics.status_code_counts
+        );
+        assert_eq!(verified[0].metrics_summary.avg_ttfb_micros, Some(60000));
 
         // Insert new daily metric
         let new_daily_date = "2025-11-30T00:00:00Z".parse::<DateTime<Utc>>().unwrap();
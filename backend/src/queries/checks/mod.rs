@@ -1,12 +1,23 @@
 use crate::database::Database;
 use crate::database::preparer::CachedPreparedStatement;
 use crate::regions::Region;
-use crate::{collab::get_bucket_for_check, worker::Method};
+use crate::{
+    collab::{PreviousBuckets, get_bucket_for_check},
+    eager_env,
+    queries::annotations::delete_annotations,
+    worker::{
+        Assertions, CheckKind, CheckStep, ConditionalRequest, ExpectedStatusCodes, GeoAssertion,
+        Method, MinTlsVersion, ProxyConfig, decrypt_password, encrypt_password,
+    },
+};
 use anyhow::Result;
 use chrono::{DateTime, Utc};
+use futures::{StreamExt, stream};
+use scylla::response::query_result::QueryRowsResult;
 use scylla::statement::batch::Batch;
+use scylla::{DeserializeRow, SerializeRow};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap, btree_map::Entry};
 use utoipa::ToSchema;
 use uuid::Uuid;
 
@@ -16,12 +27,145 @@ pub struct CheckData {
     pub url: String,
     pub http_method: Method,
     pub check_frequency_seconds: i32,
+    /// At most `MAX_PROBE_TIMEOUT_SECONDS`
     pub timeout_seconds: i32,
-    pub expected_status_code: i32,
+    /// Accepted status codes, a list or an inclusive range. `0` in a list accepts any response,
+    /// only connection errors and timeouts fail. The former `expected_status_code` is still
+    /// accepted, as a one-element list
+    #[serde(alias = "expected_status_code")]
+    pub expected_status_codes: ExpectedStatusCodes,
     pub request_headers: HashMap<String, String>,
+    /// Only allowed for `POST`, `PUT` and `PATCH` checks
     pub request_body: Option<String>,
     pub is_enabled: bool,
     pub created_at: DateTime<Utc>,
+    #[serde(default)]
+    pub kind: CheckKind,
+    /// Requests executed in order by `STEPS` checks
+    #[serde(default)]
+    pub steps: Vec<CheckStep>,
+    /// Reuse resolved addresses for this many seconds, at most an hour. No caching when unset
+    #[serde(default)]
+    pub dns_cache_ttl_seconds: Option<i32>,
+    #[serde(default)]
+    pub proxy: Option<ProxyConfig>,
+    /// Send validators with each probe and accept `304 Not Modified`
+    #[serde(default)]
+    pub conditional: Option<ConditionalRequest>,
+    /// Expected serving location per monitoring region
+    #[serde(default)]
+    pub geo_assertion: Option<GeoAssertion>,
+    /// Tried in order when `url` fails, the check is up if any of them succeeds.
+    /// Not used by `STEPS` checks
+    #[serde(default)]
+    pub fallback_urls: Vec<String>,
+    /// Regular expression the response body must match. Only the first 64 KiB are checked.
+    /// Not used by `HEAD` requests and `STEPS` checks
+    #[serde(default)]
+    pub body_regex: Option<String>,
+    /// Probes negotiating an older TLS version fail with `TLS_VERSION`
+    #[serde(default)]
+    pub min_tls_version: Option<MinTlsVersion>,
+    /// Advertises `gzip` and `br` and records the decompressed size along with the compressed one.
+    /// Off by default: bodies are never decompressed and only their size on the wire is recorded
+    #[serde(default)]
+    pub decompress_response: bool,
+    /// Lets the probes reach private, loopback and link-local addresses, blocked otherwise.
+    /// Only servers started with `ALLOW_PRIVATE_TARGETS` accept checks setting it
+    #[serde(default)]
+    pub allow_private_targets: bool,
+    /// Probes not connected within this are down with `CONNECT_TIMEOUT`, even if the `timeout`
+    /// isn't reached. Must be below the timeout
+    #[serde(default)]
+    pub connect_timeout_millis: Option<i32>,
+    /// Successful responses slower than this count as degraded rather than up in metrics.
+    /// Must be below the timeout, past which the check is down
+    #[serde(default)]
+    pub degraded_response_time_millis: Option<i32>,
+    /// Number of regions that must report down for the check to be down, `None` for any one.
+    /// Guards against regional network issues
+    #[serde(default)]
+    pub require_agreeing_regions: Option<i32>,
+    /// Conditions on the response combined with `all` or `any`, on top of the other expectations.
+    /// Not used by `STEPS` and `PASSIVE` checks
+    #[serde(default)]
+    pub assertions: Option<Assertions>,
+    /// Free-form labels, e.g. `team: payments`, matched by the owner's alert routes
+    #[serde(default)]
+    pub tags: HashMap<String, String>,
+    /// When unset, individual results are not kept: the worker only writes the hourly and daily
+    /// aggregates, so results and metrics summaries of the check are empty
+    #[serde(default = "default_store_raw_results")]
+    pub store_raw_results: bool,
+    /// Free-form values echoed verbatim in metrics responses, e.g. to correlate with other
+    /// systems. Never used by the probes
+    #[serde(default)]
+    pub metadata: HashMap<String, String>,
+    /// Extra attempts after a connection error or timeout, before the probe is recorded as down.
+    /// Only the final attempt is recorded. All attempts must fit within the check frequency.
+    /// Not used by `STEPS` checks
+    #[serde(default)]
+    pub retries: u8,
+    /// Pause before each retry, at most one minute
+    #[serde(default)]
+    pub retry_delay_ms: u32,
+    /// Set by the server on creation and never changed, `None` for checks predating it
+    #[serde(default)]
+    #[schema(read_only)]
+    pub created_by: Option<Uuid>,
+    #[serde(default)]
+    #[schema(read_only)]
+    pub created_by_username: Option<String>,
+    /// Node probing the check by itself rather than the owner of its bucket, only set for
+    /// self-checks
+    #[serde(default)]
+    #[schema(read_only)]
+    pub pinned_node: Option<String>,
+}
+
+pub(crate) fn default_store_raw_results() -> bool {
+    true
+}
+
+impl CheckData {
+    #[cfg(test)]
+    pub fn example() -> Self {
+        CheckData {
+            check_name: "Example Check".to_string(),
+            url: "https://example.com".to_string(),
+            http_method: Method::Get,
+            check_frequency_seconds: 60,
+            timeout_seconds: 10,
+            expected_status_codes: 200.into(),
+            request_headers: HashMap::new(),
+            request_body: None,
+            is_enabled: true,
+            created_at: Utc::now(),
+            kind: CheckKind::Http,
+            steps: vec![],
+            dns_cache_ttl_seconds: None,
+            proxy: None,
+            conditional: None,
+            geo_assertion: None,
+            fallback_urls: vec![],
+            body_regex: None,
+            min_tls_version: None,
+            decompress_response: false,
+            allow_private_targets: false,
+            connect_timeout_millis: None,
+            degraded_response_time_millis: None,
+            require_agreeing_regions: None,
+            assertions: None,
+            tags: HashMap::new(),
+            store_raw_results: true,
+            metadata: HashMap::new(),
+            retries: 0,
+            retry_delay_ms: 0,
+            created_by: None,
+            created_by_username: None,
+            pinned_node: None,
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
@@ -32,12 +176,234 @@ pub struct Check {
     pub data: CheckData,
 }
 
+/// Row of the `checks` table, without the partition key.
+#[derive(DeserializeRow)]
+struct CheckRow {
+    check_id: Uuid,
+    region: String,
+    check_name: String,
+    url: String,
+    http_method: String,
+    check_frequency_seconds: i32,
+    timeout_seconds: i32,
+    expected_status_code: i32,
+    expected_status_codes: Option<String>,
+    request_headers: HashMap<String, String>,
+    request_body: Option<String>,
+    is_enabled: bool,
+    created_at: DateTime<Utc>,
+    kind: Option<String>,
+    steps: Option<String>,
+    dns_cache_ttl_seconds: Option<i32>,
+    proxy_url: Option<String>,
+    proxy_username: Option<String>,
+    proxy_password: Option<String>,
+    proxy_remote_dns: Option<bool>,
+    conditional_etag: Option<String>,
+    conditional_modified_since: Option<DateTime<Utc>>,
+    geo_assertion: Option<String>,
+    created_by: Option<Uuid>,
+    created_by_username: Option<String>,
+    fallback_urls: Option<Vec<String>>,
+    body_regex: Option<String>,
+    min_tls_version: Option<String>,
+    decompress_response: Option<bool>,
+    allow_private_targets: Option<bool>,
+    connect_timeout_millis: Option<i32>,
+    degraded_response_time_millis: Option<i32>,
+    require_agreeing_regions: Option<i32>,
+    assertions: Option<String>,
+    tags: Option<HashMap<String, String>>,
+    store_raw_results: Option<bool>,
+    metadata: Option<HashMap<String, String>>,
+    retries: Option<i32>,
+    retry_delay_ms: Option<i32>,
+    pinned_node: Option<String>,
+}
+
+impl CheckRow {
+    fn into_data(self) -> Result<CheckData> {
+        let proxy_password = self.proxy_password.map(decrypt_password).transpose()?;
+
+        Ok(CheckData {
+            check_name: self.check_name,
+            url: self.url,
+            http_method: serde_plain::from_str(&self.http_method)?,
+            check_frequency_seconds: self.check_frequency_seconds,
+            timeout_seconds: self.timeout_seconds,
+            expected_status_codes: self
+                .expected_status_codes
+                .map(|c| serde_json::from_str(&c))
+                .transpose()?
+                .unwrap_or_else(|| self.expected_status_code.into()),
+            request_headers: self.request_headers,
+            request_body: self.request_body,
+            is_enabled: self.is_enabled,
+            created_at: self.created_at,
+            kind: self
+                .kind
+                .map(|k| serde_plain::from_str(&k))
+                .transpose()?
+                .unwrap_or_default(),
+            steps: self
+                .steps
+                .map(|s| serde_json::from_str(&s))
+                .transpose()?
+                .unwrap_or_default(),
+            dns_cache_ttl_seconds: self.dns_cache_ttl_seconds,
+            proxy: self.proxy_url.map(|url| ProxyConfig {
+                url,
+                username: self.proxy_username,
+                password: proxy_password,
+                remote_dns: self.proxy_remote_dns.unwrap_or_default(),
+            }),
+            conditional: ConditionalRequest::from_columns(
+                self.conditional_etag,
+                self.conditional_modified_since,
+            ),
+            geo_assertion: self
+                .geo_assertion
+                .map(|g| serde_json::from_str(&g))
+                .transpose()?,
+            fallback_urls: self.fallback_urls.unwrap_or_default(),
+            body_regex: self.body_regex,
+            min_tls_version: self.min_tls_version.map(|v| v.parse()).transpose()?,
+            decompress_response: self.decompress_response.unwrap_or_default(),
+            allow_private_targets: self.allow_private_targets.unwrap_or_default(),
+            connect_timeout_millis: self.connect_timeout_millis,
+            degraded_response_time_millis: self.degraded_response_time_millis,
+            require_agreeing_regions: self.require_agreeing_regions,
+            assertions: self
+                .assertions
+                .map(|a| serde_json::from_str(&a))
+                .transpose()?,
+            tags: self.tags.unwrap_or_default(),
+            store_raw_results: self.store_raw_results.unwrap_or(true),
+            metadata: self.metadata.unwrap_or_default(),
+            retries: self.retries.unwrap_or_default().try_into()?,
+            retry_delay_ms: self.retry_delay_ms.unwrap_or_default().try_into()?,
+            created_by: self.created_by,
+            created_by_username: self.created_by_username,
+            pinned_node: self.pinned_node,
+        })
+    }
+}
+
+/// Values bound to [`CREATE_CHECK_QUERY`], one per region.
+#[derive(SerializeRow)]
+struct CheckInsertRow<'a> {
+    check_id: Uuid,
+    region: &'static str,
+    bucket_version: i16,
+    bucket: i32,
+    check_name: &'a str,
+    url: &'a str,
+    http_method: String,
+    check_frequency_seconds: i32,
+    timeout_seconds: i32,
+    expected_status_code: i32,
+    request_headers: &'a HashMap<String, String>,
+    request_body: Option<&'a str>,
+    is_enabled: bool,
+    created_at: DateTime<Utc>,
+    kind: String,
+    steps: String,
+    dns_cache_ttl_seconds: Option<i32>,
+    proxy_url: Option<&'a str>,
+    proxy_username: Option<&'a str>,
+    proxy_password: Option<String>,
+    proxy_remote_dns: Option<bool>,
+    conditional_etag: Option<&'a str>,
+    conditional_modified_since: Option<DateTime<Utc>>,
+    geo_assertion: Option<String>,
+    created_by: Option<Uuid>,
+    created_by_username: Option<&'a str>,
+    fallback_urls: &'a Vec<String>,
+    body_regex: Option<&'a str>,
+    min_tls_version: Option<&'static str>,
+    decompress_response: bool,
+    allow_private_targets: bool,
+    connect_timeout_millis: Option<i32>,
+    degraded_response_time_millis: Option<i32>,
+    require_agreeing_regions: Option<i32>,
+    assertions: Option<String>,
+    tags: &'a HashMap<String, String>,
+    store_raw_results: bool,
+    metadata: &'a HashMap<String, String>,
+    expected_status_codes: String,
+    retries: i32,
+    retry_delay_ms: i32,
+    pinned_node: Option<&'a str>,
+}
+
+impl<'a> CheckInsertRow<'a> {
+    fn new(check_id: Uuid, region: Region, data: &'a CheckData) -> Result<Self> {
+        let (bucket_version, bucket) = get_bucket_for_check(check_id);
+
+        Ok(Self {
+            check_id,
+            region: region.to_identifier(),
+            bucket_version,
+            bucket,
+            check_name: &data.check_name,
+            url: &data.url,
+            http_method: serde_plain::to_string(&data.http_method)?,
+            check_frequency_seconds: data.check_frequency_seconds,
+            timeout_seconds: data.timeout_seconds,
+            expected_status_code: data.expected_status_codes.legacy_code(),
+            request_headers: &data.request_headers,
+            request_body: data.request_body.as_deref(),
+            is_enabled: data.is_enabled,
+            created_at: data.created_at,
+            kind: serde_plain::to_string(&data.kind)?,
+            steps: serde_json::to_string(&data.steps)?,
+            dns_cache_ttl_seconds: data.dns_cache_ttl_seconds,
+            proxy_url: data.proxy.as_ref().map(|p| p.url.as_str()),
+            proxy_username: data.proxy.as_ref().and_then(|p| p.username.as_deref()),
+            proxy_password: data
+                .proxy
+                .as_ref()
+                .and_then(|p| p.password.as_deref())
+                .map(encrypt_password)
+                .transpose()?,
+            proxy_remote_dns: data.proxy.as_ref().map(|p| p.remote_dns),
+            conditional_etag: data.conditional.as_ref().and_then(|c| c.etag.as_deref()),
+            conditional_modified_since: data.conditional.as_ref().and_then(|c| c.modified_since),
+            geo_assertion: data
+                .geo_assertion
+                .as_ref()
+                .map(serde_json::to_string)
+                .transpose()?,
+            created_by: data.created_by,
+            created_by_username: data.created_by_username.as_deref(),
+            fallback_urls: &data.fallback_urls,
+            body_regex: data.body_regex.as_deref(),
+            min_tls_version: data.min_tls_version.map(MinTlsVersion::as_str),
+            decompress_response: data.decompress_response,
+            allow_private_targets: data.allow_private_targets,
+            connect_timeout_millis: data.connect_timeout_millis,
+            degraded_response_time_millis: data.degraded_response_time_millis,
+            require_agreeing_regions: data.require_agreeing_regions,
+            assertions: data
+                .assertions
+                .as_ref()
+                .map(serde_json::to_string)
+                .transpose()?,
+            tags: &data.tags,
+            store_raw_results: data.store_raw_results,
+            metadata: &data.metadata,
+            expected_status_codes: serde_json::to_string(&data.expected_status_codes)?,
+            retries: data.retries.into(),
+            retry_delay_ms: data.retry_delay_ms.try_into()?,
+            pinned_node: data.pinned_node.as_deref(),
+        })
+    }
+}
+
 static GET_CHECK_BY_ID_QUERY: CachedPreparedStatement = CachedPreparedStatement::new(
     "
     SELECT check_id,
            region,
-           bucket_version,
-           bucket,
            check_name,
            url,
            http_method,
@@ -47,7 +413,35 @@ static GET_CHECK_BY_ID_QUERY: CachedPreparedStatement = CachedPreparedStatement:
            request_headers,
            request_body,
            is_enabled,
-           created_at
+           created_at,
+           kind,
+           steps,
+           dns_cache_ttl_seconds,
+           proxy_url,
+           proxy_username,
+           proxy_password,
+           proxy_remote_dns,
+           conditional_etag,
+           conditional_modified_since,
+           geo_assertion,
+           created_by,
+           created_by_username,
+           fallback_urls,
+           body_regex,
+           min_tls_version,
+           decompress_response,
+           allow_private_targets,
+           connect_timeout_millis,
+           degraded_response_time_millis,
+           require_agreeing_regions,
+           assertions,
+           tags,
+           store_raw_results,
+           metadata,
+           expected_status_codes,
+           retries,
+           retry_delay_ms,
+           pinned_node
     FROM checks
     WHERE region IN ?
       AND bucket_version = ?
@@ -56,8 +450,33 @@ static GET_CHECK_BY_ID_QUERY: CachedPreparedStatement = CachedPreparedStatement:
     ",
 );
 
+/// Reads a check, falling back to its copy under the previous bucket version during a migration.
 pub async fn get_check_by_id(session: &Database, check_id: Uuid) -> Result<Option<Check>> {
-    let (bucket_version, bucket) = get_bucket_for_check(check_id);
+    find_check(session, check_id, PreviousBuckets::from_env()).await
+}
+
+async fn find_check(
+    session: &Database,
+    check_id: Uuid,
+    previous_buckets: Option<PreviousBuckets>,
+) -> Result<Option<Check>> {
+    let locations = std::iter::once(get_bucket_for_check(check_id))
+        .chain(previous_buckets.map(|previous| previous.bucket_for_check(check_id)));
+
+    for location in locations {
+        if let Some(check) = get_check_in_bucket(session, check_id, location).await? {
+            return Ok(Some(check));
+        }
+    }
+
+    Ok(None)
+}
+
+async fn get_check_in_bucket(
+    session: &Database,
+    check_id: Uuid,
+    (bucket_version, bucket): (i16, i32),
+) -> Result<Option<Check>> {
     let all_regions = Region::get_all_region_identifiers();
 
     let result = GET_CHECK_BY_ID_QUERY
@@ -65,103 +484,321 @@ pub async fn get_check_by_id(session: &Database, check_id: Uuid) -> Result<Optio
         .await?
         .into_rows_result()?;
 
-    let rows = result.rows::<(
-        Uuid,
-        String,
-        i16,
-        i32,
-        String,
-        String,
-        String,
-        i32,
-        i32,
-        i32,
-        HashMap<String, String>,
-        Option<String>,
-        bool,
-        DateTime<Utc>,
-    )>()?;
+    let rows = result.rows::<CheckRow>()?;
 
     let mut regions_found = Vec::new();
     let mut check_data = None;
 
     for row in rows {
-        let (
-            _check_id,
-            region,
-            _bucket_version,
-            _bucket,
-            check_name,
-            url,
-            http_method_str,
-            check_frequency_seconds,
-            timeout_seconds,
-            expected_status_code,
-            request_headers,
-            request_body,
-            is_enabled,
-            created_at,
-        ) = row?;
-
-        if let Ok(region_enum) = Region::from_identifier(&region) {
+        let row = row?;
+
+        if let Ok(region_enum) = Region::from_identifier(&row.region) {
             regions_found.push(region_enum);
         }
 
         if check_data.is_none() {
-            let http_method = serde_plain::from_str(&http_method_str)?;
-            check_data = Some((
-                check_name,
-                url,
-                http_method,
-                check_frequency_seconds,
-                timeout_seconds,
-                expected_status_code,
-                request_headers,
-                request_body,
-                is_enabled,
-                created_at,
-            ));
+            check_data = Some(row.into_data()?);
         }
     }
 
-    match check_data {
-        Some((
-            check_name,
-            url,
-            http_method,
-            check_frequency_seconds,
-            timeout_seconds,
-            expected_status_code,
-            request_headers,
-            request_body,
-            is_enabled,
-            created_at,
-        )) => Ok(Some(Check {
-            check_id,
-            regions: regions_found,
-            data: CheckData {
-                check_name,
-                url,
-                http_method,
-                check_frequency_seconds,
-                timeout_seconds,
-                expected_status_code,
-                request_headers,
-                request_body,
-                is_enabled,
-                created_at,
-            },
-        })),
-        None => Ok(None),
+    Ok(check_data.map(|data| Check {
+        check_id,
+        regions: regions_found,
+        data,
+    }))
+}
+
+static LIST_BUCKET_CHECKS_QUERY: CachedPreparedStatement = CachedPreparedStatement::new(
+    "
+    SELECT check_id,
+           region,
+           check_name,
+           url,
+           http_method,
+           check_frequency_seconds,
+           timeout_seconds,
+           expected_status_code,
+           request_headers,
+           request_body,
+           is_enabled,
+           created_at,
+           kind,
+           steps,
+           dns_cache_ttl_seconds,
+           proxy_url,
+           proxy_username,
+           proxy_password,
+           proxy_remote_dns,
+           conditional_etag,
+           conditional_modified_since,
+           geo_assertion,
+           created_by,
+           created_by_username,
+           fallback_urls,
+           body_regex,
+           min_tls_version,
+           decompress_response,
+           allow_private_targets,
+           connect_timeout_millis,
+           degraded_response_time_millis,
+           require_agreeing_regions,
+           assertions,
+           tags,
+           store_raw_results,
+           metadata,
+           expected_status_codes,
+           retries,
+           retry_delay_ms,
+           pinned_node
+    FROM checks
+    WHERE region IN ?
+      AND bucket_version = ?
+      AND bucket = ?
+    ",
+);
+
+static GET_CHECKS_BY_IDS_QUERY: CachedPreparedStatement = CachedPreparedStatement::new(
+    "
+    SELECT check_id,
+           region,
+           check_name,
+           url,
+           http_method,
+           check_frequency_seconds,
+           timeout_seconds,
+           expected_status_code,
+           request_headers,
+           request_body,
+           is_enabled,
+           created_at,
+           kind,
+           steps,
+           dns_cache_ttl_seconds,
+           proxy_url,
+           proxy_username,
+           proxy_password,
+           proxy_remote_dns,
+           conditional_etag,
+           conditional_modified_since,
+           geo_assertion,
+           created_by,
+           created_by_username,
+           fallback_urls,
+           body_regex,
+           min_tls_version,
+           decompress_response,
+           allow_private_targets,
+           connect_timeout_millis,
+           degraded_response_time_millis,
+           require_agreeing_regions,
+           assertions,
+           tags,
+           store_raw_results,
+           metadata,
+           expected_status_codes,
+           retries,
+           retry_delay_ms,
+           pinned_node
+    FROM checks
+    WHERE region IN ?
+      AND bucket_version = ?
+      AND bucket = ?
+      AND check_id IN ?
+    ",
+);
+
+/// Adds the rows to `checks`: a check has one row per region, in different partitions.
+fn merge_check_rows(checks: &mut BTreeMap<Uuid, Check>, result: QueryRowsResult) -> Result<()> {
+    for row in result.rows::<CheckRow>()? {
+        let row = row?;
+        let region = Region::from_identifier(&row.region).ok();
+
+        let check = match checks.entry(row.check_id) {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => entry.insert(Check {
+                check_id: row.check_id,
+                regions: Vec::new(),
+                data: row.into_data()?,
+            }),
+        };
+        check.regions.extend(region);
+    }
+
+    Ok(())
+}
+
+/// Groups check ids by `(bucket_version, bucket)`, i.e. by the partitions holding them.
+fn group_by_bucket(
+    check_ids: impl IntoIterator<Item = Uuid>,
+    bucket_for_check: impl Fn(Uuid) -> (i16, i32),
+) -> BTreeMap<(i16, i32), Vec<Uuid>> {
+    check_ids
+        .into_iter()
+        .fold(BTreeMap::new(), |mut buckets, check_id| {
+            buckets
+                .entry(bucket_for_check(check_id))
+                .or_default()
+                .push(check_id);
+            buckets
+        })
+}
+
+/// Reads many checks at once, with one query per bucket rather than one per check.
+/// Checks that don't exist are missing from the result.
+///
+/// During a migration, checks missing under the current bucket version are read from the
+/// previous one.
+pub async fn get_checks_by_ids(
+    session: &Database,
+    check_ids: impl IntoIterator<Item = Uuid>,
+) -> Result<BTreeMap<Uuid, Check>> {
+    find_checks(session, check_ids, PreviousBuckets::from_env()).await
+}
+
+async fn find_checks(
+    session: &Database,
+    check_ids: impl IntoIterator<Item = Uuid>,
+    previous_buckets: Option<PreviousBuckets>,
+) -> Result<BTreeMap<Uuid, Check>> {
+    let check_ids: Vec<_> = check_ids.into_iter().collect();
+    let mut checks = get_checks_in_buckets(
+        session,
+        group_by_bucket(check_ids.iter().copied(), get_bucket_for_check),
+    )
+    .await?;
+
+    if let Some(previous) = previous_buckets {
+        let missing = check_ids
+            .into_iter()
+            .filter(|check_id| !checks.contains_key(check_id));
+        let migrating = get_checks_in_buckets(
+            session,
+            group_by_bucket(missing, |check_id| previous.bucket_for_check(check_id)),
+        )
+        .await?;
+        checks.extend(migrating);
+    }
+
+    Ok(checks)
+}
+
+async fn get_checks_in_buckets(
+    session: &Database,
+    buckets: BTreeMap<(i16, i32), Vec<Uuid>>,
+) -> Result<BTreeMap<Uuid, Check>> {
+    let all_regions = Region::get_all_region_identifiers();
+
+    let results = stream::iter(buckets)
+        .map(|((bucket_version, bucket), bucket_check_ids)| {
+            let all_regions = &all_regions;
+            async move {
+                GET_CHECKS_BY_IDS_QUERY
+                    .execute_unpaged(
+                        session,
+                        (all_regions, bucket_version, bucket, bucket_check_ids),
+                    )
+                    .await?
+                    .into_rows_result()
+                    .map_err(anyhow::Error::from)
+            }
+        })
+        .buffer_unordered(eager_env::DATABASE_CONCURRENT_READS.get())
+        .collect::<Vec<_>>()
+        .await;
+
+    let mut checks = BTreeMap::new();
+    for result in results {
+        merge_check_rows(&mut checks, result?)?;
+    }
+
+    Ok(checks)
+}
+
+/// Page of [`list_all_checks`], `next_bucket` is `None` once every bucket was read.
+pub struct ChecksPage {
+    pub checks: Vec<Check>,
+    pub next_bucket: Option<i32>,
+}
+
+/// Number of buckets [`list_all_checks`] walks through: those of the current bucket version,
+/// followed during a migration by those of `previous_buckets`.
+pub fn listed_buckets_count(previous_buckets: Option<PreviousBuckets>) -> i32 {
+    let previous_count = previous_buckets.map_or(0, |previous| previous.bucket_count);
+    (*eager_env::CURRENT_BUCKETS_COUNT + previous_count) as i32
+}
+
+/// Lists every check, reading whole buckets from `start_bucket` until at least `min_checks` are
+/// collected. Checks are sorted by id within a page.
+///
+/// During a migration, the buckets of `previous_buckets` follow the current ones, without the
+/// checks already rewritten under the current version. See [`listed_buckets_count`].
+///
+/// Each bucket is one query over the partitions of every region, so walking all pages scans the
+/// entire `checks` table. Meant for admin tooling, not for request paths.
+pub async fn list_all_checks(
+    session: &Database,
+    start_bucket: i32,
+    min_checks: usize,
+    previous_buckets: Option<PreviousBuckets>,
+) -> Result<ChecksPage> {
+    let current_version = *eager_env::CURRENT_BUCKET_VERSION as i16;
+    let current_count = *eager_env::CURRENT_BUCKETS_COUNT as i32;
+    let bucket_count = listed_buckets_count(previous_buckets);
+    let all_regions = Region::get_all_region_identifiers();
+
+    let mut checks = Vec::new();
+    let mut bucket = start_bucket;
+
+    while bucket < bucket_count && checks.len() < min_checks.max(1) {
+        let (bucket_version, partition) = match previous_buckets {
+            Some(previous) if bucket >= current_count => {
+                (previous.bucket_version, bucket - current_count)
+            }
+            _ => (current_version, bucket),
+        };
+
+        let result = LIST_BUCKET_CHECKS_QUERY
+            .execute_unpaged(session, (&all_regions, bucket_version, partition))
+            .await?
+            .into_rows_result()?;
+
+        let mut bucket_checks = BTreeMap::new();
+        merge_check_rows(&mut bucket_checks, result)?;
+
+        if bucket_version != current_version {
+            // Listed from their current bucket already
+            let rewritten = get_checks_in_buckets(
+                session,
+                group_by_bucket(bucket_checks.keys().copied(), get_bucket_for_check),
+            )
+            .await?;
+            bucket_checks.retain(|check_id, _| !rewritten.contains_key(check_id));
+        }
+
+        checks.extend(bucket_checks.into_values());
+        bucket += 1;
     }
+
+    Ok(ChecksPage {
+        checks,
+        next_bucket: (bucket < bucket_count).then_some(bucket),
+    })
 }
 
 static CREATE_CHECK_QUERY: CachedPreparedStatement = CachedPreparedStatement::new(
     "
     INSERT INTO checks (check_id, region, bucket_version, bucket, check_name, url,
                         http_method, check_frequency_seconds, timeout_seconds, expected_status_code,
-                        request_headers, request_body, is_enabled, created_at)
-    VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+                        request_headers, request_body, is_enabled, created_at, kind, steps,
+                        dns_cache_ttl_seconds, proxy_url, proxy_username, proxy_password,
+                        proxy_remote_dns, conditional_etag, conditional_modified_since,
+                        geo_assertion, created_by, created_by_username, fallback_urls,
+                        body_regex, min_tls_version, decompress_response, allow_private_targets, connect_timeout_millis,
+                        degraded_response_time_millis, require_agreeing_regions, assertions, tags,
+                        store_raw_results, metadata, expected_status_codes, retries, retry_delay_ms,
+                        pinned_node)
+    VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?,
+            ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
     ",
 );
 
@@ -171,33 +808,15 @@ pub async fn create_check(db: &Database, regions: Vec<Region>, data: CheckData)
     }
 
     let check_id = Uuid::new_v4();
-    let (bucket_version, bucket) = get_bucket_for_check(check_id);
 
     // Use batched writes for multiple regions
     let mut batch = Batch::default();
     let mut batch_values = Vec::new();
     let query = CREATE_CHECK_QUERY.get_prepared_statement(db).await?;
 
-    let http_method_str = serde_plain::to_string(&data.http_method)?;
-
     for region in &regions {
         batch.append_statement(query.clone());
-        batch_values.push((
-            check_id,
-            region.to_identifier(),
-            bucket_version,
-            bucket,
-            data.check_name.clone(),
-            data.url.clone(),
-            http_method_str.clone(),
-            data.check_frequency_seconds,
-            data.timeout_seconds,
-            data.expected_status_code,
-            data.request_headers.clone(),
-            data.request_body.clone(),
-            data.is_enabled,
-            data.created_at,
-        ));
+        batch_values.push(CheckInsertRow::new(check_id, *region, &data)?);
     }
 
     db.batch(&batch, batch_values).await?;
@@ -233,47 +852,13 @@ pub async fn update_check(session: &Database, check: Check) -> Result<()> {
     session.batch(&delete_batch, delete_values).await?;
 
     // Then insert into the specified regions
-    let insert_query = "
-        INSERT INTO checks (check_id,
-                                    region,
-                                    bucket_version,
-                                    bucket,
-                                    check_name,
-                                    url,
-                                    http_method,
-                                    check_frequency_seconds,
-                                    timeout_seconds,
-                                    expected_status_code,
-                                    request_headers,
-                                    request_body,
-                                    is_enabled,
-                                    created_at)
-        VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
-    ";
-
     let mut insert_batch = Batch::default();
     let mut insert_values = Vec::new();
-
-    let http_method_str = serde_plain::to_string(&check.data.http_method)?;
+    let insert_query = CREATE_CHECK_QUERY.get_prepared_statement(session).await?;
 
     for region in &check.regions {
-        insert_batch.append_statement(insert_query);
-        insert_values.push((
-            check.check_id,
-            region.to_identifier(),
-            bucket_version,
-            bucket,
-            &check.data.check_name,
-            &check.data.url,
-            &http_method_str,
-            check.data.check_frequency_seconds,
-            check.data.timeout_seconds,
-            check.data.expected_status_code,
-            &check.data.request_headers,
-            &check.data.request_body,
-            check.data.is_enabled,
-            check.data.created_at,
-        ));
+        insert_batch.append_statement(insert_query.clone());
+        insert_values.push(CheckInsertRow::new(check.check_id, *region, &check.data)?);
     }
 
     session.batch(&insert_batch, insert_values).await?;
@@ -292,13 +877,20 @@ static DELETE_CHECK_QUERY: CachedPreparedStatement = CachedPreparedStatement::ne
     ",
 );
 
+/// Deletes the check in every region, including its copy under the previous bucket version during
+/// a migration, which workers would otherwise keep fetching.
 pub async fn delete_check(session: &Database, check_id: Uuid) -> Result<()> {
-    let (bucket_version, bucket) = get_bucket_for_check(check_id);
     let all_regions = Region::get_all_region_identifiers();
+    let locations = std::iter::once(get_bucket_for_check(check_id))
+        .chain(PreviousBuckets::from_env().map(|previous| previous.bucket_for_check(check_id)));
 
-    DELETE_CHECK_QUERY
-        .execute_unpaged(session, (all_regions, bucket_version, bucket, check_id))
-        .await?;
+    for (bucket_version, bucket) in locations {
+        DELETE_CHECK_QUERY
+            .execute_unpaged(session, (&all_regions, bucket_version, bucket, check_id))
+            .await?;
+    }
+
+    delete_annotations(session, check_id).await?;
 
     Ok(())
 }
@@ -307,6 +899,7 @@ pub async fn delete_check(session: &Database, check_id: Uuid) -> Result<()> {
 mod tests {
     use super::*;
     use crate::database::testing::create_test_database;
+    use crate::queries::annotations::{CheckAnnotation, create_annotation, list_annotations};
 
     #[tokio::test]
     async fn test_checks_basic() -> Result<()> {
@@ -320,11 +913,34 @@ mod tests {
             http_method: Method::Get,
             check_frequency_seconds: 60,
             timeout_seconds: 10,
-            expected_status_code: 200,
+            expected_status_codes: 200.into(),
             request_headers: HashMap::new(),
             request_body: None,
             is_enabled: true,
             created_at: Utc::now(),
+            kind: CheckKind::Http,
+            steps: vec![],
+            dns_cache_ttl_seconds: None,
+            proxy: None,
+            conditional: None,
+            geo_assertion: None,
+            fallback_urls: vec![],
+            body_regex: None,
+            min_tls_version: None,
+            decompress_response: false,
+            allow_private_targets: false,
+            connect_timeout_millis: None,
+            degraded_response_time_millis: None,
+            require_agreeing_regions: None,
+            assertions: None,
+            tags: HashMap::new(),
+            store_raw_results: true,
+            metadata: HashMap::new(),
+            retries: 0,
+            retry_delay_ms: 0,
+            created_by: None,
+            created_by_username: None,
+            pinned_node: None,
         };
 
         let check = create_check(&session, regions.clone(), data).await?;
@@ -357,11 +973,179 @@ mod tests {
 
         assert_eq!(actual_regions, expected_regions);
 
-        // Test delete
+        // Test delete, along with the annotations
+        let annotation = CheckAnnotation {
+            annotation_id: Uuid::new_v4(),
+            check_id,
+            text: "Deploy".to_string(),
+            starts_at: Utc::now(),
+            ends_at: Utc::now(),
+            author_id: Uuid::new_v4(),
+            author_username: "alice".to_string(),
+            created_at: Utc::now(),
+        };
+        create_annotation(&session, &annotation).await?;
         delete_check(&session, check_id).await?;
         let deleted = get_check_by_id(&session, check_id).await?;
         assert!(deleted.is_none());
+        let far = chrono::Duration::days(1);
+        assert!(
+            list_annotations(&session, check_id, Utc::now() - far, Utc::now() + far)
+                .await?
+                .is_empty()
+        );
 
         Ok(())
     }
+
+    #[tokio::test]
+    async fn test_list_all_checks() -> Result<()> {
+        let (session, _keyspace) = create_test_database(None).await?;
+
+        let fixtures = [
+            vec![Region::Fsn1],
+            vec![Region::Hel1, Region::Nbg1],
+            vec![Region::Fsn1, Region::Hel1, Region::Nbg1],
+        ];
+        let mut expected = BTreeMap::new();
+        for regions in fixtures {
+            let check = create_check(&session, regions.clone(), CheckData::example()).await?;
+            expected.insert(check.check_id, regions);
+        }
+
+        // Walk every page, at least one check at a time
+        let mut listed = BTreeMap::new();
+        let mut cursor = Some(0);
+        while let Some(bucket) = cursor {
+            let page = list_all_checks(&session, bucket, 1, None).await?;
+            for mut check in page.checks {
+                check.regions.sort();
+                assert!(listed.insert(check.check_id, check.regions).is_none());
+            }
+            cursor = page.next_bucket;
+        }
+
+        assert_eq!(listed, expected);
+
+        // A single large page holds everything
+        let page = list_all_checks(&session, 0, 100, None).await?;
+        assert_eq!(page.checks.len(), 3);
+        assert_eq!(page.next_bucket, None);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_get_checks_by_ids() -> Result<()> {
+        let (session, _keyspace) = create_test_database(None).await?;
+
+        let mut expected = BTreeMap::new();
+        for regions in [vec![Region::Fsn1], vec![Region::Hel1, Region::Nbg1]] {
+            let check = create_check(&session, regions.clone(), CheckData::example()).await?;
+            expected.insert(check.check_id, regions);
+        }
+
+        let missing = Uuid::new_v4();
+        let checks = get_checks_by_ids(&session, expected.keys().copied().chain([missing])).await?;
+
+        let listed: BTreeMap<_, _> = checks
+            .into_values()
+            .map(|mut check| {
+                check.regions.sort();
+                (check.check_id, check.regions)
+            })
+            .collect();
+        assert_eq!(listed, expected);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_checks_are_read_under_previous_bucket_version() -> Result<()> {
+        let (session, _keyspace) = create_test_database(None).await?;
+        let current_version = *eager_env::CURRENT_BUCKET_VERSION as i16;
+        let previous = PreviousBuckets {
+            bucket_version: current_version + 1,
+            bucket_count: 7,
+        };
+
+        // Only stored under the previous version, i.e. not rewritten since the migration started
+        let migrating = Uuid::new_v4();
+        let (bucket_version, bucket) = previous.bucket_for_check(migrating);
+        session
+            .query_unpaged(
+                "INSERT INTO checks (check_id, region, bucket_version, bucket, check_name, url, http_method, check_frequency_seconds, timeout_seconds, expected_status_code, request_headers, is_enabled, created_at) VALUES (?, 'hel1', ?, ?, 'previous', 'https://example.com', 'GET', 60, 10, 200, {}, true, toTimestamp(now()))",
+                (migrating, bucket_version, bucket),
+            )
+            .await?;
+
+        // Stored under both, the current copy wins
+        let rewritten = create_check(&session, vec![Region::Fsn1], CheckData::example()).await?;
+        let (bucket_version, bucket) = previous.bucket_for_check(rewritten.check_id);
+        session
+            .query_unpaged(
+                "INSERT INTO checks (check_id, region, bucket_version, bucket, check_name, url, http_method, check_frequency_seconds, timeout_seconds, expected_status_code, request_headers, is_enabled, created_at) VALUES (?, 'hel1', ?, ?, 'previous', 'https://example.com', 'GET', 60, 10, 200, {}, true, toTimestamp(now()))",
+                (rewritten.check_id, bucket_version, bucket),
+            )
+            .await?;
+
+        // Outside of a migration, only the current version is read
+        assert!(find_check(&session, migrating, None).await?.is_none());
+
+        let check = find_check(&session, migrating, Some(previous))
+            .await?
+            .unwrap();
+        assert_eq!(check.data.check_name, "previous");
+        assert_eq!(check.regions, [Region::Hel1]);
+
+        let checks = find_checks(&session, [migrating, rewritten.check_id], Some(previous)).await?;
+        assert_eq!(checks[&migrating].data.check_name, "previous");
+        assert_eq!(
+            checks[&rewritten.check_id].data.check_name,
+            rewritten.data.check_name
+        );
+        assert_eq!(checks[&rewritten.check_id].regions, [Region::Fsn1]);
+
+        // Each check is listed once, from its current copy if any
+        let mut listed = BTreeMap::new();
+        let mut cursor = Some(0);
+        while let Some(bucket) = cursor {
+            let page = list_all_checks(&session, bucket, 1, Some(previous)).await?;
+            for check in page.checks {
+                assert!(listed.insert(check.check_id, check).is_none());
+            }
+            cursor = page.next_bucket;
+        }
+        assert_eq!(listed.len(), 2);
+        assert_eq!(listed[&migrating].data.check_name, "previous");
+        assert_eq!(listed[&rewritten.check_id].regions, [Region::Fsn1]);
+
+        let page = list_all_checks(&session, 0, 100, None).await?;
+        assert_eq!(page.checks.len(), 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_group_by_bucket_bounds_queries() {
+        let check_ids: Vec<_> = (0..1_000).map(|_| Uuid::new_v4()).collect();
+        let buckets = group_by_bucket(check_ids.iter().copied(), get_bucket_for_check);
+
+        // One query per bucket, however many checks there are
+        assert!(buckets.len() <= *eager_env::CURRENT_BUCKETS_COUNT as usize);
+
+        let mut grouped: Vec<_> = buckets.values().flatten().copied().collect();
+        grouped.sort();
+        let mut check_ids = check_ids;
+        check_ids.sort();
+        assert_eq!(grouped, check_ids);
+
+        for (&bucket, bucket_check_ids) in &buckets {
+            assert!(
+                bucket_check_ids
+                    .iter()
+                    .all(|&check_id| get_bucket_for_check(check_id) == bucket)
+            );
+        }
+    }
 }
@@ -0,0 +1,65 @@
+use crate::database::{Database, preparer::CachedPreparedStatement};
+use anyhow::Result;
+
+const PROBING_ENABLED_FLAG: &str = "probing_enabled";
+
+static GET_FLAG_QUERY: CachedPreparedStatement = CachedPreparedStatement::new(
+    "
+    SELECT enabled
+    FROM cluster_flags
+    WHERE flag = ?
+    ",
+);
+
+static SET_FLAG_QUERY: CachedPreparedStatement = CachedPreparedStatement::new(
+    "
+    INSERT INTO cluster_flags (flag,
+                               enabled)
+    VALUES (?, ?)
+    ",
+);
+
+/// Whether workers should dispatch probes. Enabled unless explicitly paused.
+pub async fn get_probing_enabled(db: &Database) -> Result<bool> {
+    let result = GET_FLAG_QUERY
+        .execute_unpaged(db, (PROBING_ENABLED_FLAG,))
+        .await?
+        .into_rows_result()?;
+
+    let enabled = result
+        .maybe_first_row::<(Option<bool>,)>()?
+        .and_then(|(enabled,)| enabled)
+        .unwrap_or(true);
+
+    Ok(enabled)
+}
+
+pub async fn set_probing_enabled(db: &Database, enabled: bool) -> Result<()> {
+    SET_FLAG_QUERY
+        .execute_unpaged(db, (PROBING_ENABLED_FLAG, enabled))
+        .await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::testing::create_test_database;
+
+    #[tokio::test]
+    async fn test_probing_enabled_flag() -> Result<()> {
+        let (db, _keyspace) = create_test_database(None).await?;
+
+        // Enabled by default
+        assert!(get_probing_enabled(&db).await?);
+
+        set_probing_enabled(&db, false).await?;
+        assert!(!get_probing_enabled(&db).await?);
+
+        set_probing_enabled(&db, true).await?;
+        assert!(get_probing_enabled(&db).await?);
+
+        Ok(())
+    }
+}
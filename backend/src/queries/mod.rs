@@ -1,5 +1,9 @@
+pub mod alert_routing;
+pub mod annotations;
 pub mod authorization;
 pub mod check_results;
 pub mod checks;
+pub mod cluster;
+pub mod pings;
 pub mod sessions;
 pub mod users;
@@ -0,0 +1,244 @@
+use crate::{
+    database::{Database, preparer::CachedPreparedStatement},
+    eager_env,
+};
+use anyhow::{Result, anyhow};
+use chrono::{DateTime, Utc};
+use futures::{StreamExt, stream};
+use log::error;
+use scylla::client::session::Session;
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use tokio::time::MissedTickBehavior;
+use uuid::Uuid;
+
+static SET_CHECK_PING_TOKEN_QUERY: CachedPreparedStatement = CachedPreparedStatement::new(
+    "
+    UPDATE check_pings
+    SET ping_token = ?
+    WHERE check_id = ?
+    ",
+);
+
+/// Creates a new ping token for a passive check, replacing the previous one
+pub async fn rotate_check_ping_token(session: &Session, check_id: Uuid) -> Result<Uuid> {
+    let ping_token = Uuid::new_v4();
+
+    SET_CHECK_PING_TOKEN_QUERY
+        .execute_unpaged(session, (ping_token, check_id))
+        .await?;
+
+    Ok(ping_token)
+}
+
+static GET_CHECK_PING_QUERY: CachedPreparedStatement = CachedPreparedStatement::new(
+    "
+    SELECT ping_token, last_ping_at
+    FROM check_pings
+    WHERE check_id = ?
+    ",
+);
+
+static SET_CHECK_LAST_PING_QUERY: CachedPreparedStatement = CachedPreparedStatement::new(
+    "
+    UPDATE check_pings
+    SET last_ping_at = ?
+    WHERE check_id = ?
+    ",
+);
+
+/// Whether `ping_token` is the current ping token of a passive check
+pub async fn check_ping_token(session: &Session, check_id: Uuid, ping_token: Uuid) -> Result<bool> {
+    let stored = GET_CHECK_PING_QUERY
+        .execute_unpaged(session, (check_id,))
+        .await?
+        .into_rows_result()?
+        .maybe_first_row::<(Option<Uuid>, Option<DateTime<Utc>>)>()?;
+
+    Ok(stored.is_some_and(|(stored, _)| stored == Some(ping_token)))
+}
+
+/// Sets the time of the last ping of a passive check, without checking any token
+pub async fn set_last_check_ping(
+    session: &Session,
+    check_id: Uuid,
+    at: DateTime<Utc>,
+) -> Result<()> {
+    SET_CHECK_LAST_PING_QUERY
+        .execute_unpaged(session, (at, check_id))
+        .await?;
+    Ok(())
+}
+
+/// Coalesces the pings of passive checks so that each check is written at most once per
+/// `interval`, however often it is pinged.
+///
+/// Only the latest ping of a check matters, so bursts only grow the pending map by the number of
+/// distinct checks, never by the number of pings.
+pub struct PingBatcher {
+    interval: Duration,
+    /// Check to the time of its latest ping not written yet
+    pending: Mutex<HashMap<Uuid, DateTime<Utc>>>,
+}
+
+impl PingBatcher {
+    pub fn new(interval: Duration) -> Self {
+        Self {
+            interval,
+            pending: Default::default(),
+        }
+    }
+
+    /// `None` when pings are written right away
+    pub fn from_env() -> Option<Self> {
+        match *eager_env::PING_FLUSH_INTERVAL_SECONDS {
+            0 => None,
+            seconds => Some(Self::new(Duration::from_secs(seconds))),
+        }
+    }
+
+    /// Queues a ping of `check_id` at `at`, replacing any earlier pending one
+    pub fn record(&self, check_id: Uuid, at: DateTime<Utc>) {
+        self.pending
+            .lock()
+            .unwrap()
+            .entry(check_id)
+            .and_modify(|pending| *pending = (*pending).max(at))
+            .or_insert(at);
+    }
+
+    fn take_pending(&self) -> Vec<(Uuid, DateTime<Utc>)> {
+        std::mem::take(&mut *self.pending.lock().unwrap())
+            .into_iter()
+            .collect()
+    }
+
+    /// Writes the pending pings, with at most `DATABASE_CONCURRENT_WRITES` writes in flight.
+    /// Failed writes are queued again unless a later ping arrived meanwhile.
+    ///
+    /// Returns the number of writes.
+    pub async fn flush(&self, session: &Session) -> Result<usize> {
+        let pending = self.take_pending();
+        let writes = pending.len();
+
+        let failed: Vec<_> = stream::iter(pending)
+            .map(|(check_id, at)| async move {
+                set_last_check_ping(session, check_id, at)
+                    .await
+                    .err()
+                    .map(|e| (check_id, at, e))
+            })
+            .buffer_unordered(eager_env::DATABASE_CONCURRENT_WRITES.get())
+            .filter_map(|failed| async move { failed })
+            .collect()
+            .await;
+
+        let Some((_, _, first_error)) = failed.first() else {
+            return Ok(writes);
+        };
+        let error = anyhow!(
+            "failed to write {} of {writes} pings: {first_error}",
+            failed.len()
+        );
+        for (check_id, at, _) in failed {
+            self.record(check_id, at);
+        }
+        Err(error)
+    }
+
+    /// Flushes the pending pings every `interval`, forever
+    pub async fn flush_task_body(self: Arc<Self>, db: Arc<Database>) {
+        let mut interval = tokio::time::interval(self.interval);
+        interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        loop {
+            interval.tick().await;
+            if let Err(e) = self.flush(&db).await {
+                error!("{e:?}");
+            }
+        }
+    }
+}
+
+/// Time of the last ping of a passive check, `None` if it was never pinged
+pub async fn get_last_check_ping(
+    session: &Session,
+    check_id: Uuid,
+) -> Result<Option<DateTime<Utc>>> {
+    let stored = GET_CHECK_PING_QUERY
+        .execute_unpaged(session, (check_id,))
+        .await?
+        .into_rows_result()?
+        .maybe_first_row::<(Option<Uuid>, Option<DateTime<Utc>>)>()?;
+
+    Ok(stored.and_then(|(_, last_ping_at)| last_ping_at))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::testing::create_test_database;
+    use chrono::DurationRound;
+
+    #[test]
+    fn test_ping_batcher_keeps_latest_ping() {
+        let batcher = PingBatcher::new(Duration::from_secs(5));
+        let now = Utc::now();
+        let first = Uuid::new_v4();
+        let second = Uuid::new_v4();
+
+        for i in 0..1000 {
+            batcher.record(first, now + chrono::Duration::milliseconds(i));
+        }
+        // Out of order pings don't move it back
+        batcher.record(first, now);
+        batcher.record(second, now);
+
+        let mut pending = batcher.take_pending();
+        pending.sort_by_key(|(_, at)| *at);
+        assert_eq!(
+            pending,
+            [
+                (second, now),
+                (first, now + chrono::Duration::milliseconds(999))
+            ]
+        );
+        assert!(batcher.take_pending().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_check_pings() -> Result<()> {
+        let (session, _keyspace) = create_test_database(None).await?;
+        let check_id = Uuid::new_v4();
+        // Timestamps are stored with millisecond precision
+        let at = Utc::now().duration_trunc(chrono::Duration::milliseconds(1))?;
+
+        assert!(!check_ping_token(&session, check_id, Uuid::new_v4()).await?);
+        assert_eq!(get_last_check_ping(&session, check_id).await?, None);
+
+        let first = rotate_check_ping_token(&session, check_id).await?;
+        assert_eq!(get_last_check_ping(&session, check_id).await?, None);
+        assert!(check_ping_token(&session, check_id, first).await?);
+        set_last_check_ping(&session, check_id, at).await?;
+        assert_eq!(get_last_check_ping(&session, check_id).await?, Some(at));
+
+        // Rotating revokes the previous token but keeps the last ping
+        let second = rotate_check_ping_token(&session, check_id).await?;
+        assert!(!check_ping_token(&session, check_id, first).await?);
+        assert!(check_ping_token(&session, check_id, second).await?);
+        assert_eq!(get_last_check_ping(&session, check_id).await?, Some(at));
+
+        // Pending pings are written by the flush, the latest only
+        let batcher = PingBatcher::new(Duration::from_secs(5));
+        let later = at + chrono::Duration::seconds(30);
+        batcher.record(check_id, later - chrono::Duration::seconds(1));
+        batcher.record(check_id, later);
+        assert_eq!(get_last_check_ping(&session, check_id).await?, Some(at));
+        assert_eq!(batcher.flush(&session).await?, 1);
+        assert_eq!(get_last_check_ping(&session, check_id).await?, Some(later));
+
+        Ok(())
+    }
+}
@@ -1,9 +1,10 @@
-use crate::{database::preparer::CachedPreparedStatement, eager_env};
+use crate::{clock::Clock, database::preparer::CachedPreparedStatement, eager_env};
 use anyhow::Result;
 use chrono::{DateTime, Duration, Utc};
 use scylla::client::session::Session;
 use uuid::Uuid;
 
+#[allow(dead_code)]
 pub struct UserSession {
     pub session_id: Uuid,
     pub user_id: Uuid,
@@ -12,6 +13,21 @@ pub struct UserSession {
     logged_out: bool,
 }
 
+impl UserSession {
+    /// A session is valid until it expires or is logged out. Sessions without expiry are invalid.
+    ///
+    /// Expiry is evaluated at `now - skew_tolerance`, so that a node whose clock runs slightly
+    /// ahead doesn't reject a session that is still valid elsewhere.
+    fn is_valid_at(&self, now: DateTime<Utc>, skew_tolerance: Duration) -> bool {
+        let is_expired = match self.expires_at {
+            Some(expires_at) => expires_at <= now - skew_tolerance,
+            None => true,
+        };
+
+        !is_expired && !self.logged_out
+    }
+}
+
 static CREATE_SESSION_QUERY: CachedPreparedStatement = CachedPreparedStatement::new(
     "
     INSERT INTO sessions (session_id,
@@ -24,10 +40,11 @@ static CREATE_SESSION_QUERY: CachedPreparedStatement = CachedPreparedStatement::
 
 pub async fn create_session(
     db_session: &Session,
+    clock: &dyn Clock,
     user_id: Uuid,
     session_id: Uuid,
 ) -> Result<UserSession> {
-    let now = Utc::now();
+    let now = clock.now();
     let expires_at = now + Duration::days(*eager_env::SESSION_DURATION_DAYS);
 
     CREATE_SESSION_QUERY
@@ -85,24 +102,15 @@ async fn get_session(db_session: &Session, session_id: Uuid) -> Result<Option<Us
 
 pub async fn get_valid_session_user_id(
     db_session: &Session,
+    clock: &dyn Clock,
     session_id: Uuid,
 ) -> Result<Option<Uuid>> {
     let maybe_user_session = get_session(db_session, session_id).await?;
+    let skew_tolerance = Duration::seconds(*eager_env::SESSION_EXPIRY_SKEW_SECONDS);
 
-    if let Some(user_session) = maybe_user_session {
-        let is_expired = match user_session.expires_at {
-            Some(expires_at) => expires_at <= Utc::now(),
-            None => true,
-        };
-
-        if !is_expired && !user_session.logged_out {
-            Ok(Some(user_session.user_id))
-        } else {
-            Ok(None)
-        }
-    } else {
-        Ok(None)
-    }
+    Ok(maybe_user_session
+        .filter(|user_session| user_session.is_valid_at(clock.now(), skew_tolerance))
+        .map(|user_session| user_session.user_id))
 }
 
 static LOG_OUT_SESSION_QUERY: CachedPreparedStatement =
@@ -118,7 +126,7 @@ pub async fn log_out_session(db_session: &Session, session_id: Uuid) -> Result<(
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::database::testing::create_test_database;
+    use crate::{clock::MockClock, database::testing::create_test_database};
     use uuid::uuid;
 
     const FIXTURES: &str = include_str!("fixtures.cql");
@@ -126,11 +134,12 @@ mod tests {
     #[tokio::test]
     async fn test_session_operations() -> Result<()> {
         let (db_session, _keyspace) = create_test_database(Some(FIXTURES)).await?;
+        let clock = &*MockClock::new(Utc::now());
         let user_id = uuid!("11111111-1111-1111-1111-111111111111");
         let session_id = Uuid::new_v4();
 
         // Test: Create and retrieve session
-        let created = create_session(&db_session, user_id, session_id).await?;
+        let created = create_session(&db_session, clock, user_id, session_id).await?;
         assert_eq!(created.session_id, session_id);
         assert_eq!(created.user_id, user_id);
         assert!(!created.logged_out);
@@ -142,22 +151,22 @@ mod tests {
 
         // Test: Valid session returns user_id
         assert_eq!(
-            get_valid_session_user_id(&db_session, session_id).await?,
+            get_valid_session_user_id(&db_session, clock, session_id).await?,
             Some(user_id)
         );
 
         // Test: Logout invalidates session
         log_out_session(&db_session, session_id).await?;
         assert_eq!(
-            get_valid_session_user_id(&db_session, session_id).await?,
+            get_valid_session_user_id(&db_session, clock, session_id).await?,
             None
         );
 
         // Test: Multiple sessions per user
         let session_id2 = Uuid::new_v4();
-        create_session(&db_session, user_id, session_id2).await?;
+        create_session(&db_session, clock, user_id, session_id2).await?;
         assert!(
-            get_valid_session_user_id(&db_session, session_id2)
+            get_valid_session_user_id(&db_session, clock, session_id2)
                 .await?
                 .is_some()
         );
@@ -166,7 +175,7 @@ mod tests {
         let nonexistent = uuid!("99999999-9999-9999-9999-999999999999");
         assert!(get_session(&db_session, nonexistent).await?.is_none());
         assert!(
-            get_valid_session_user_id(&db_session, nonexistent)
+            get_valid_session_user_id(&db_session, clock, nonexistent)
                 .await?
                 .is_none()
         );
@@ -174,7 +183,7 @@ mod tests {
         // Test: Valid session from fixtures
         let valid_id = uuid!("aaaaaaaa-aaaa-aaaa-aaaa-aaaaaaaaaaaa");
         assert!(
-            get_valid_session_user_id(&db_session, valid_id)
+            get_valid_session_user_id(&db_session, clock, valid_id)
                 .await?
                 .is_some()
         );
@@ -182,7 +191,7 @@ mod tests {
         // Test: Expired session from fixtures
         let expired_id = uuid!("bbbbbbbb-bbbb-bbbb-bbbb-bbbbbbbbbbbb");
         assert!(
-            get_valid_session_user_id(&db_session, expired_id)
+            get_valid_session_user_id(&db_session, clock, expired_id)
                 .await?
                 .is_none()
         );
@@ -192,11 +201,111 @@ mod tests {
         let session = get_session(&db_session, null_expiry_id).await?.unwrap();
         assert_eq!(session.expires_at, None);
         assert!(
-            get_valid_session_user_id(&db_session, null_expiry_id)
+            get_valid_session_user_id(&db_session, clock, null_expiry_id)
                 .await?
                 .is_none()
         );
 
         Ok(())
     }
+
+    #[tokio::test]
+    async fn test_session_expiry() -> Result<()> {
+        let (db_session, _keyspace) = create_test_database(Some(FIXTURES)).await?;
+        let clock = MockClock::new(Utc::now());
+        let user_id = uuid!("11111111-1111-1111-1111-111111111111");
+        let session_id = Uuid::new_v4();
+
+        let created = create_session(&db_session, &*clock, user_id, session_id).await?;
+        let lifetime = (created.expires_at.unwrap() - clock.now()).to_std()?;
+
+        clock.advance(lifetime - std::time::Duration::from_secs(1));
+        assert_eq!(
+            get_valid_session_user_id(&db_session, &*clock, session_id).await?,
+            Some(user_id)
+        );
+
+        // Still accepted within the skew tolerance
+        let skew_tolerance = (*eager_env::SESSION_EXPIRY_SKEW_SECONDS).try_into()?;
+        clock.advance(std::time::Duration::from_secs(skew_tolerance));
+        assert_eq!(
+            get_valid_session_user_id(&db_session, &*clock, session_id).await?,
+            Some(user_id)
+        );
+
+        clock.advance(std::time::Duration::from_secs(1));
+        assert_eq!(
+            get_valid_session_user_id(&db_session, &*clock, session_id).await?,
+            None
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_is_valid_at() {
+        let clock = MockClock::new(Utc::now());
+        let session = UserSession {
+            session_id: Uuid::new_v4(),
+            user_id: Uuid::new_v4(),
+            created_at: Some(clock.now()),
+            expires_at: Some(clock.now() + Duration::hours(1)),
+            logged_out: false,
+        };
+        assert!(session.is_valid_at(clock.now(), Duration::zero()));
+
+        clock.advance(std::time::Duration::from_secs(3599));
+        assert!(session.is_valid_at(clock.now(), Duration::zero()));
+
+        // Expiry is exclusive
+        clock.advance(std::time::Duration::from_secs(1));
+        assert!(!session.is_valid_at(clock.now(), Duration::zero()));
+
+        let logged_out = UserSession {
+            logged_out: true,
+            expires_at: Some(clock.now() + Duration::hours(1)),
+            ..session
+        };
+        assert!(!logged_out.is_valid_at(clock.now(), Duration::zero()));
+
+        let no_expiry = UserSession {
+            expires_at: None,
+            logged_out: false,
+            ..logged_out
+        };
+        assert!(!no_expiry.is_valid_at(clock.now(), Duration::zero()));
+    }
+
+    #[test]
+    fn test_is_valid_at_with_skew_tolerance() {
+        let now = Utc::now();
+        let tolerance = Duration::seconds(5);
+        let session = UserSession {
+            session_id: Uuid::new_v4(),
+            user_id: Uuid::new_v4(),
+            created_at: Some(now - Duration::hours(1)),
+            expires_at: Some(now - Duration::seconds(3)),
+            logged_out: false,
+        };
+        // Expired 3 seconds ago, within the tolerance
+        assert!(!session.is_valid_at(now, Duration::zero()));
+        assert!(session.is_valid_at(now, tolerance));
+
+        // Beyond the tolerance
+        assert!(!session.is_valid_at(now + Duration::seconds(2), tolerance));
+
+        // Logged-out and null-expiry sessions are unaffected
+        let logged_out = UserSession {
+            logged_out: true,
+            expires_at: Some(now + Duration::hours(1)),
+            ..session
+        };
+        assert!(!logged_out.is_valid_at(now, tolerance));
+        let no_expiry = UserSession {
+            expires_at: None,
+            logged_out: false,
+            ..logged_out
+        };
+        assert!(!no_expiry.is_valid_at(now, tolerance));
+    }
 }
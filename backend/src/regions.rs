@@ -24,6 +24,10 @@ pub enum Region {
     Nbg1, // Nuremberg, Germany
 }
 
+/// Identifiers regions were stored under before being renamed, so that historical rows still
+/// parse. When changing a `to_identifier`, add the old identifier here instead of rewriting data.
+const REGION_ALIASES: &[(&str, Region)] = &[];
+
 impl FromStr for Region {
     type Err = anyhow::Error;
 
@@ -41,16 +45,88 @@ impl Region {
         }
     }
 
+    /// Parses a current identifier, or a legacy one from `REGION_ALIASES`
     pub fn from_identifier(identifier: &str) -> anyhow::Result<Self> {
-        match identifier {
-            "fsn1" => Ok(Region::Fsn1),
-            "hel1" => Ok(Region::Hel1),
-            "nbg1" => Ok(Region::Nbg1),
-            _ => Err(anyhow!("unknown region identifier: {identifier}")),
-        }
+        Self::from_identifier_with_aliases(identifier, REGION_ALIASES)
+    }
+
+    fn from_identifier_with_aliases(
+        identifier: &str,
+        aliases: &[(&str, Region)],
+    ) -> anyhow::Result<Self> {
+        Region::iter()
+            .find(|region| region.to_identifier() == identifier)
+            .or_else(|| {
+                aliases
+                    .iter()
+                    .find(|(alias, _)| *alias == identifier)
+                    .map(|(_, region)| *region)
+            })
+            .ok_or_else(|| anyhow!("unknown region identifier: {identifier}"))
     }
 
     pub fn get_all_region_identifiers() -> Vec<&'static str> {
         Region::iter().map(|r| r.to_identifier()).collect()
     }
+
+    /// Human-friendly name of the region's location
+    pub fn display_name(self) -> &'static str {
+        match self {
+            Region::Fsn1 => "Falkenstein, Germany",
+            Region::Hel1 => "Helsinki, Finland",
+            Region::Nbg1 => "Nuremberg, Germany",
+        }
+    }
+
+    /// Approximate `(latitude, longitude)` of the region's datacenter, in degrees
+    pub fn coordinates(self) -> (f64, f64) {
+        match self {
+            Region::Fsn1 => (50.4777, 12.3649),
+            Region::Hel1 => (60.1699, 24.9384),
+            Region::Nbg1 => (49.4521, 11.0767),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_region_metadata() {
+        for region in Region::iter() {
+            assert_eq!(
+                Region::from_identifier(region.to_identifier()).ok(),
+                Some(region)
+            );
+            assert!(!region.display_name().is_empty());
+
+            let (latitude, longitude) = region.coordinates();
+            assert!((-90.0..=90.0).contains(&latitude));
+            assert!((-180.0..=180.0).contains(&longitude));
+        }
+    }
+
+    #[test]
+    fn test_legacy_region_identifiers() {
+        let aliases = [("falkenstein", Region::Fsn1), ("hel1", Region::Nbg1)];
+
+        assert_eq!(
+            Region::from_identifier_with_aliases("falkenstein", &aliases).ok(),
+            Some(Region::Fsn1)
+        );
+        // Current identifiers take precedence over aliases
+        assert_eq!(
+            Region::from_identifier_with_aliases("hel1", &aliases).ok(),
+            Some(Region::Hel1)
+        );
+        assert!(Region::from_identifier_with_aliases("ash1", &aliases).is_err());
+
+        for (alias, _) in REGION_ALIASES {
+            assert!(
+                !Region::get_all_region_identifiers().contains(alias),
+                "alias {alias} shadows a current identifier"
+            );
+        }
+    }
 }
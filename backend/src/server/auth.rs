@@ -22,6 +22,7 @@ pub enum AuthenticatedUser {
     /// `user_id` from cookie session
     User(UserSession),
     /// `api_key_id` from Authorization header
+    #[allow(dead_code)]
     Api(Uuid),
 }
 
@@ -58,7 +59,8 @@ impl FromRequest for AuthenticatedUser {
                 };
 
                 let maybe_user_id =
-                    get_valid_session_user_id(&app_state.database, session_id).await;
+                    get_valid_session_user_id(&app_state.database, &*app_state.clock, session_id)
+                        .await;
 
                 match maybe_user_id {
                     Ok(Some(user_id)) => {
@@ -0,0 +1,88 @@
+use crate::{
+    queries::{
+        alert_routing::get_alert_routing, authorization::get_user_access_to_check,
+        checks::get_check_by_id,
+    },
+    server::{AppState, auth::AuthenticatedUser},
+};
+use actix_web::{
+    Error,
+    error::{ErrorForbidden, ErrorInternalServerError, ErrorNotFound},
+    get,
+    web::{Data, Json, Path},
+};
+use serde::Serialize;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct AlertDestinations {
+    /// Webhooks notified of the check's alerts, empty when none is configured
+    pub webhook_urls: Vec<String>,
+    /// Whether no route matches the tags of the check, leaving only the default webhook
+    pub is_default: bool,
+}
+
+#[utoipa::path(
+    summary = "Get check alert destinations",
+    description = "Resolves the alert routing of the current user against the tags of a check",
+    params(
+        ("check_id" = Uuid, Path, description = "Check ID")
+    ),
+    responses(
+        (status = 200, description = "Destinations resolved successfully", body = AlertDestinations),
+        (status = 403, description = "Forbidden - no access to check"),
+        (status = 404, description = "Check not found"),
+        (status = 500, description = "Internal server error")
+    ),
+    security(
+        ("cookie_auth" = []),
+        ("bearer_auth" = [])
+    ),
+    tags = ["checks"],
+    operation_id = "getCheckAlertDestinations"
+)]
+#[get("/{check_id}/alert-destinations")]
+pub async fn get_alert_destinations_endpoint(
+    check_id: Path<Uuid>,
+    app_state: Data<AppState>,
+    auth: AuthenticatedUser,
+) -> Result<Json<AlertDestinations>, Error> {
+    let check_id = check_id.into_inner();
+    let user_id = match auth {
+        AuthenticatedUser::User(session) => session.user_id,
+        AuthenticatedUser::Api(_) => {
+            // TODO: Check API key permissions
+            todo!("API key alert destinations not yet implemented")
+        }
+    };
+
+    let access = get_user_access_to_check(&app_state.database, user_id, check_id)
+        .await
+        .map_err(ErrorInternalServerError)?
+        .ok_or_else(|| ErrorForbidden("No access to this check"))?;
+
+    if !access.can_see {
+        return Err(ErrorForbidden("No permission to view this check"));
+    }
+
+    let check = get_check_by_id(&app_state.database, check_id)
+        .await
+        .map_err(ErrorInternalServerError)?
+        .ok_or_else(|| ErrorNotFound("Check not found"))?;
+    let routing = get_alert_routing(&app_state.database, user_id)
+        .await
+        .map_err(ErrorInternalServerError)?;
+
+    let webhook_urls: Vec<_> = routing
+        .destinations(&check.data.tags)
+        .into_iter()
+        .map(str::to_string)
+        .collect();
+    let is_default = routing.matching_routes(&check.data.tags).is_empty();
+
+    Ok(Json(AlertDestinations {
+        webhook_urls,
+        is_default,
+    }))
+}
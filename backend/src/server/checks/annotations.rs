@@ -0,0 +1,184 @@
+use crate::{
+    queries::{
+        annotations::{CheckAnnotation, create_annotation, list_annotations},
+        authorization::get_user_access_to_check,
+        users::get_user_by_id,
+    },
+    server::{AppState, auth::AuthenticatedUser},
+};
+use actix_web::{
+    Error,
+    error::{ErrorBadRequest, ErrorForbidden, ErrorInternalServerError, ErrorNotFound},
+    get, post,
+    web::{Data, Json, Path, Query},
+};
+use chrono::{DateTime, DurationRound, TimeDelta, Utc};
+use serde::Deserialize;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+/// Longest annotation text, in characters
+const MAX_ANNOTATION_CHARS: usize = 1000;
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct CreateAnnotationRequest {
+    pub text: String,
+    /// Start of the annotated range, included
+    pub starts_at: DateTime<Utc>,
+    /// End of the annotated range, included. Defaults to `starts_at`, annotating a single instant
+    pub ends_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct AnnotationsQuery {
+    /// Start timestamp (ISO 8601)
+    pub from: DateTime<Utc>,
+    /// End timestamp (ISO 8601, exclusive)
+    pub to: DateTime<Utc>,
+}
+
+#[utoipa::path(
+    summary = "Annotate a check",
+    description = "Leave a note on a time range of a check, e.g. a deploy or a maintenance window. Annotations are returned alongside the metrics graph",
+    params(
+        ("check_id" = Uuid, Path, description = "Check ID")
+    ),
+    request_body = CreateAnnotationRequest,
+    responses(
+        (status = 200, description = "Annotation created successfully", body = CheckAnnotation),
+        (status = 400, description = "Invalid annotation"),
+        (status = 403, description = "Forbidden - no permission to edit check"),
+        (status = 500, description = "Internal server error")
+    ),
+    security(
+        ("cookie_auth" = []),
+        ("bearer_auth" = [])
+    ),
+    tags = ["checks"],
+    operation_id = "createCheckAnnotation"
+)]
+#[post("/{check_id}/annotations")]
+pub async fn create_annotation_endpoint(
+    check_id: Path<Uuid>,
+    body: Json<CreateAnnotationRequest>,
+    app_state: Data<AppState>,
+    auth: AuthenticatedUser,
+) -> Result<Json<CheckAnnotation>, Error> {
+    let check_id = check_id.into_inner();
+    let user_id = match auth {
+        AuthenticatedUser::User(session) => session.user_id,
+        AuthenticatedUser::Api(_) => {
+            // TODO: Check API key permissions
+            todo!("API key check annotations not yet implemented")
+        }
+    };
+
+    let text = body.text.trim();
+    if text.is_empty() {
+        return Err(ErrorBadRequest("Annotation text cannot be empty"));
+    }
+    if text.chars().count() > MAX_ANNOTATION_CHARS {
+        return Err(ErrorBadRequest(format!(
+            "Annotation text cannot exceed {MAX_ANNOTATION_CHARS} characters"
+        )));
+    }
+
+    // Stored with millisecond precision
+    let truncate = |time: DateTime<Utc>| {
+        time.duration_trunc(TimeDelta::milliseconds(1))
+            .map_err(ErrorBadRequest)
+    };
+    let starts_at = truncate(body.starts_at)?;
+    let ends_at = truncate(body.ends_at.unwrap_or(body.starts_at))?;
+    if ends_at < starts_at {
+        return Err(ErrorBadRequest("'ends_at' cannot be before 'starts_at'"));
+    }
+
+    let access = get_user_access_to_check(&app_state.database, user_id, check_id)
+        .await
+        .map_err(ErrorInternalServerError)?
+        .ok_or_else(|| ErrorForbidden("No access to this check"))?;
+
+    if !access.can_edit {
+        return Err(ErrorForbidden("No permission to edit this check"));
+    }
+
+    let user = get_user_by_id(&app_state.database, user_id)
+        .await
+        .map_err(ErrorInternalServerError)?
+        .ok_or_else(|| ErrorNotFound("User not found"))?;
+
+    let annotation = CheckAnnotation {
+        annotation_id: Uuid::new_v4(),
+        check_id,
+        text: text.to_string(),
+        starts_at,
+        ends_at,
+        author_id: user_id,
+        author_username: user.username,
+        created_at: truncate(Utc::now())?,
+    };
+
+    create_annotation(&app_state.database, &annotation)
+        .await
+        .map_err(ErrorInternalServerError)?;
+
+    Ok(Json(annotation))
+}
+
+#[utoipa::path(
+    summary = "List check annotations",
+    description = "List the annotations of a check overlapping a time range, ordered by start",
+    params(
+        ("check_id" = Uuid, Path, description = "Check ID"),
+        ("from" = DateTime<Utc>, Query, description = "Start timestamp (ISO 8601)"),
+        ("to" = DateTime<Utc>, Query, description = "End timestamp (ISO 8601, exclusive)"),
+    ),
+    responses(
+        (status = 200, description = "Annotations retrieved successfully", body = Vec<CheckAnnotation>),
+        (status = 400, description = "Invalid query parameters"),
+        (status = 403, description = "Forbidden - no access to check"),
+        (status = 500, description = "Internal server error")
+    ),
+    security(
+        ("cookie_auth" = []),
+        ("bearer_auth" = [])
+    ),
+    tags = ["checks"],
+    operation_id = "listCheckAnnotations"
+)]
+#[get("/{check_id}/annotations")]
+pub async fn list_annotations_endpoint(
+    check_id: Path<Uuid>,
+    query: Query<AnnotationsQuery>,
+    app_state: Data<AppState>,
+    auth: AuthenticatedUser,
+) -> Result<Json<Vec<CheckAnnotation>>, Error> {
+    let check_id = check_id.into_inner();
+    let user_id = match auth {
+        AuthenticatedUser::User(session) => session.user_id,
+        AuthenticatedUser::Api(_) => {
+            // TODO: Check API key permissions
+            todo!("API key check annotations not yet implemented")
+        }
+    };
+
+    if query.from >= query.to {
+        return Err(ErrorBadRequest("'from' must be before 'to'"));
+    }
+
+    let access = get_user_access_to_check(&app_state.database, user_id, check_id)
+        .await
+        .map_err(ErrorInternalServerError)?
+        .ok_or_else(|| ErrorForbidden("No access to this check"))?;
+
+    if !access.can_see {
+        return Err(ErrorForbidden("No permission to view this check"));
+    }
+
+    let annotations = list_annotations(&app_state.database, check_id, query.from, query.to)
+        .await
+        .map_err(ErrorInternalServerError)?;
+
+    Ok(Json(annotations))
+}
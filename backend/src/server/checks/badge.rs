@@ -0,0 +1,204 @@
+use crate::{
+    queries::{
+        authorization::{
+            get_user_access_to_check, is_valid_check_read_token, rotate_check_read_token,
+        },
+        check_results::get_check_metrics,
+    },
+    regions::Region,
+    server::{AppState, auth::AuthenticatedUser},
+};
+use actix_web::{
+    Error, HttpResponse,
+    error::{ErrorForbidden, ErrorInternalServerError},
+    get,
+    http::header::{CacheControl, CacheDirective},
+    post,
+    web::{Data, Json, Path, Query},
+};
+use chrono::{Duration, Utc};
+use serde::{Deserialize, Serialize};
+use strum::IntoEnumIterator;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+const BADGE_LABEL: &str = "uptime 24h";
+/// Rough width of a badge character in the 11px Verdana font
+const BADGE_CHAR_WIDTH: usize = 7;
+const BADGE_PADDING: usize = 10;
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct ReadTokenResponse {
+    pub read_token: Uuid,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct BadgeQuery {
+    /// Read token of the check
+    pub token: Uuid,
+}
+
+#[utoipa::path(
+    summary = "Create a check read token",
+    description = "Creates a token granting unauthenticated access to the check's public data, such as its uptime badge. Any previous token is revoked.",
+    params(
+        ("check_id" = Uuid, Path, description = "Check ID"),
+    ),
+    responses(
+        (status = 200, description = "Read token created", body = ReadTokenResponse),
+        (status = 403, description = "Forbidden - no permission to edit check"),
+        (status = 500, description = "Internal server error")
+    ),
+    security(
+        ("cookie_auth" = []),
+        ("bearer_auth" = [])
+    ),
+    tags = ["checks"],
+    operation_id = "rotateCheckReadToken"
+)]
+#[post("/{check_id}/read-token")]
+pub async fn rotate_read_token_endpoint(
+    check_id: Path<Uuid>,
+    app_state: Data<AppState>,
+    auth: AuthenticatedUser,
+) -> Result<Json<ReadTokenResponse>, Error> {
+    let check_id = check_id.into_inner();
+    let user_id = match auth {
+        AuthenticatedUser::User(session) => session.user_id,
+        AuthenticatedUser::Api(_) => {
+            // TODO: Check API key permissions
+            todo!("API key access not yet implemented")
+        }
+    };
+
+    let access = get_user_access_to_check(&app_state.database, user_id, check_id)
+        .await
+        .map_err(ErrorInternalServerError)?
+        .ok_or_else(|| ErrorForbidden("No access to this check"))?;
+
+    if !access.can_edit {
+        return Err(ErrorForbidden("No permission to edit this check"));
+    }
+
+    let read_token = rotate_check_read_token(&app_state.database, check_id)
+        .await
+        .map_err(ErrorInternalServerError)?;
+
+    Ok(Json(ReadTokenResponse { read_token }))
+}
+
+#[utoipa::path(
+    summary = "Get check uptime badge",
+    description = "SVG badge with the check's uptime over the last 24 hours, authorized by the check's read token",
+    params(
+        ("check_id" = Uuid, Path, description = "Check ID"),
+        ("token" = Uuid, Query, description = "Read token of the check"),
+    ),
+    responses(
+        (status = 200, description = "SVG badge", content_type = "image/svg+xml", body = String),
+        (status = 403, description = "Forbidden - invalid read token"),
+        (status = 500, description = "Internal server error")
+    ),
+    tags = ["checks"],
+    operation_id = "getCheckUptimeBadge"
+)]
+#[get("/{check_id}/uptime-badge")]
+pub async fn get_uptime_badge_endpoint(
+    check_id: Path<Uuid>,
+    query: Query<BadgeQuery>,
+    app_state: Data<AppState>,
+) -> Result<HttpResponse, Error> {
+    let check_id = check_id.into_inner();
+
+    let valid = is_valid_check_read_token(&app_state.database, check_id, query.token)
+        .await
+        .map_err(ErrorInternalServerError)?;
+
+    if !valid {
+        return Err(ErrorForbidden("Invalid read token"));
+    }
+
+    let to = Utc::now();
+    let regions: Vec<Region> = Region::iter().collect();
+    let metrics = get_check_metrics(
+        app_state.metrics_database(),
+        check_id,
+        &regions,
+        to - Duration::hours(24),
+        to,
+        None,
+        1,
+    )
+    .await
+    .map_err(ErrorInternalServerError)?;
+
+    let uptime = metrics
+        .overall
+        .has_data
+        .then_some(metrics.overall.uptime_percent);
+
+    Ok(HttpResponse::Ok()
+        .content_type("image/svg+xml")
+        .insert_header(CacheControl(vec![CacheDirective::MaxAge(300)]))
+        .body(render_uptime_badge(uptime)))
+}
+
+/// Badge color for an uptime percentage, grey without data
+fn badge_color(uptime_percent: Option<f32>) -> &'static str {
+    match uptime_percent {
+        None => "#9f9f9f",
+        Some(p) if p >= 99.0 => "#4c1",
+        Some(p) if p >= 95.0 => "#dfb317",
+        Some(_) => "#e05d44",
+    }
+}
+
+/// Formats a percentage with at most two decimals, e.g. `99.5%` or `100%`
+fn format_percent(percent: f32) -> String {
+    let formatted = format!("{percent:.2}");
+    let trimmed = formatted.trim_end_matches('0').trim_end_matches('.');
+    format!("{trimmed}%")
+}
+
+/// Renders a flat shields.io-style badge
+fn render_uptime_badge(uptime_percent: Option<f32>) -> String {
+    let message = uptime_percent
+        .map(format_percent)
+        .unwrap_or_else(|| "no data".to_string());
+    let color = badge_color(uptime_percent);
+
+    let label_width = BADGE_LABEL.len() * BADGE_CHAR_WIDTH + BADGE_PADDING;
+    let message_width = message.len() * BADGE_CHAR_WIDTH + BADGE_PADDING;
+    let width = label_width + message_width;
+    let label_x = label_width / 2;
+    let message_x = label_width + message_width / 2;
+
+    format!(
+        r##"<svg xmlns="http://www.w3.org/2000/svg" width="{width}" height="20" role="img" aria-label="{BADGE_LABEL}: {message}"><title>{BADGE_LABEL}: {message}</title><rect width="{label_width}" height="20" fill="#555"/><rect x="{label_width}" width="{message_width}" height="20" fill="{color}"/><g fill="#fff" text-anchor="middle" font-family="Verdana,Geneva,DejaVu Sans,sans-serif" font-size="11"><text x="{label_x}" y="14">{BADGE_LABEL}</text><text x="{message_x}" y="14">{message}</text></g></svg>"##
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_uptime_badge() {
+        let svg = render_uptime_badge(Some(99.5));
+        assert!(svg.starts_with("<svg"));
+        assert!(svg.contains(">99.5%<"));
+        assert!(svg.contains("#4c1"));
+
+        assert!(render_uptime_badge(Some(100.0)).contains(">100%<"));
+        assert!(render_uptime_badge(None).contains(">no data<"));
+    }
+
+    #[test]
+    fn test_badge_color_thresholds() {
+        assert_eq!(badge_color(Some(99.0)), "#4c1");
+        assert_eq!(badge_color(Some(98.9)), "#dfb317");
+        assert_eq!(badge_color(Some(95.0)), "#dfb317");
+        assert_eq!(badge_color(Some(94.9)), "#e05d44");
+        assert_eq!(badge_color(None), "#9f9f9f");
+    }
+}
@@ -1,12 +1,26 @@
+use crate::clock::MockClock;
 use crate::collab::get_bucket_for_check;
-use crate::queries::checks::{Check, CheckData};
+use crate::collab::heartbeat::Heartbeat;
+use crate::database::{Database, testing::create_test_database};
+use crate::eager_env;
+use crate::queries::annotations::CheckAnnotation;
+use crate::queries::authorization::{CheckAccess, grant_check_access};
+use crate::queries::checks::{Check, CheckData, get_check_by_id};
+use crate::queries::pings::{PingBatcher, get_last_check_ping, rotate_check_ping_token};
+use crate::queries::sessions::create_session;
+use crate::queries::users::create_user;
 use crate::regions::Region;
-use crate::server::checks::CheckWithAccess;
-use crate::server::start_server_test;
-use crate::worker::Method;
-use chrono::Utc;
+use crate::server::checks::metrics::MetricsGraph;
+use crate::server::checks::{CheckWithAccess, CreateCheckRequest, CreatedCheck};
+use crate::server::{start_server_test, start_server_test_with};
+use crate::worker::{
+    CheckKind, CheckStep, ExpectedStatusCodes, IpVersionPreference, Method, ProxyConfig,
+};
+use chrono::{DateTime, DurationRound, Utc};
 use reqwest::StatusCode;
 use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
 use uuid::{Uuid, uuid};
 
 const FIXTURES_TEMPLATE: &str = include_str!("fixtures.cql");
@@ -53,11 +67,34 @@ async fn test_check_endpoints() {
         http_method: Method::Get,
         check_frequency_seconds: 60,
         timeout_seconds: 10,
-        expected_status_code: 200,
+        expected_status_codes: 200.into(),
         request_headers: HashMap::new(),
         request_body: None,
         is_enabled: true,
         created_at: Utc::now(),
+        kind: CheckKind::Http,
+        steps: vec![],
+        dns_cache_ttl_seconds: None,
+        proxy: None,
+        conditional: None,
+        geo_assertion: None,
+        fallback_urls: vec![],
+        body_regex: None,
+        min_tls_version: None,
+        decompress_response: false,
+        allow_private_targets: false,
+        connect_timeout_millis: None,
+        degraded_response_time_millis: None,
+        require_agreeing_regions: None,
+        assertions: None,
+        tags: HashMap::new(),
+        store_raw_results: true,
+        metadata: HashMap::new(),
+        retries: 0,
+        retry_delay_ms: 0,
+        created_by: None,
+        created_by_username: None,
+        pinned_node: None,
     };
 
     let test_check = Check {
@@ -145,11 +182,34 @@ async fn test_check_endpoints() {
         http_method: Method::Post,
         check_frequency_seconds: 60,
         timeout_seconds: 10,
-        expected_status_code: 200,
+        expected_status_codes: 200.into(),
         request_headers: HashMap::new(),
         request_body: Some(r#"{"test": "data"}"#.to_string()),
         is_enabled: true,
         created_at: Utc::now(),
+        kind: CheckKind::Http,
+        steps: vec![],
+        dns_cache_ttl_seconds: None,
+        proxy: None,
+        conditional: None,
+        geo_assertion: None,
+        fallback_urls: vec![],
+        body_regex: None,
+        min_tls_version: None,
+        decompress_response: false,
+        allow_private_targets: false,
+        connect_timeout_millis: None,
+        degraded_response_time_millis: None,
+        require_agreeing_regions: None,
+        assertions: None,
+        tags: HashMap::new(),
+        store_raw_results: true,
+        metadata: HashMap::new(),
+        retries: 0,
+        retry_delay_ms: 0,
+        created_by: None,
+        created_by_username: None,
+        pinned_node: None,
     };
 
     let new_check = Check {
@@ -161,7 +221,10 @@ async fn test_check_endpoints() {
     let response = client
         .post(format!("{}/checks/", base_url))
         .header("Cookie", &session_cookie)
-        .json(&new_check)
+        .json(&CreateCheckRequest {
+            check: new_check,
+            acknowledge_side_effects: true,
+        })
         .send()
         .await
         .unwrap();
@@ -192,11 +255,34 @@ async fn test_check_endpoints() {
         http_method: Method::Get,
         check_frequency_seconds: 120,
         timeout_seconds: 15,
-        expected_status_code: 201,
+        expected_status_codes: 201.into(),
         request_headers: HashMap::new(),
         request_body: None,
         is_enabled: true,
         created_at: Utc::now(),
+        kind: CheckKind::Http,
+        steps: vec![],
+        dns_cache_ttl_seconds: None,
+        proxy: None,
+        conditional: None,
+        geo_assertion: None,
+        fallback_urls: vec![],
+        body_regex: None,
+        min_tls_version: None,
+        decompress_response: false,
+        allow_private_targets: false,
+        connect_timeout_millis: None,
+        degraded_response_time_millis: None,
+        require_agreeing_regions: None,
+        assertions: None,
+        tags: HashMap::new(),
+        store_raw_results: true,
+        metadata: HashMap::new(),
+        retries: 0,
+        retry_delay_ms: 0,
+        created_by: None,
+        created_by_username: None,
+        pinned_node: None,
     };
 
     let updated_check = Check {
@@ -259,3 +345,1956 @@ async fn test_check_endpoints() {
     let checks: Vec<CheckWithAccess> = response.json().await.unwrap();
     assert!(!checks.iter().any(|c| c.check.check_id == new_check_id));
 }
+
+#[tokio::test]
+async fn test_uptime_badge_endpoint() {
+    let fixtures = get_fixtures();
+    let (port, state) = start_server_test(Some(&fixtures)).await;
+    let client = reqwest::Client::new();
+    let base_url = format!("http://localhost:{}", port);
+    let check_id = uuid!("44444444-4444-4444-4444-444444444444");
+    let session_cookie = format!(
+        "session_id={}",
+        uuid!("55555555-5555-5555-5555-555555555555")
+    );
+
+    // A single successful result in the last 24h
+    let started_at = Utc::now() - chrono::Duration::hours(1);
+    state
+        .database
+        .query_unpaged(
+            "INSERT INTO check_results (result_id, service_check_id, region, day, check_started_at,
+                                        response_time_micros, status_code, matches_expected,
+                                        response_body_fetched)
+             VALUES (?, ?, 'hel1', ?, ?, 1000, 200, true, false)",
+            (
+                Uuid::new_v4(),
+                check_id,
+                started_at.date_naive(),
+                started_at,
+            ),
+        )
+        .await
+        .unwrap();
+
+    // Invalid token
+    let response = client
+        .get(format!(
+            "{base_url}/checks/{check_id}/uptime-badge?token={}",
+            Uuid::new_v4()
+        ))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+    let response = client
+        .post(format!("{base_url}/checks/{check_id}/read-token"))
+        .header("Cookie", &session_cookie)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let read_token = response.json::<serde_json::Value>().await.unwrap()["read_token"]
+        .as_str()
+        .unwrap()
+        .to_string();
+
+    // Unauthenticated, with the read token
+    let response = client
+        .get(format!(
+            "{base_url}/checks/{check_id}/uptime-badge?token={read_token}"
+        ))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        response.headers().get("content-type").unwrap(),
+        "image/svg+xml"
+    );
+    let svg = response.text().await.unwrap();
+    assert!(svg.contains("100%"));
+}
+
+#[tokio::test]
+async fn test_check_created_by() {
+    let fixtures = get_fixtures();
+    let (port, state) = start_server_test(Some(&fixtures)).await;
+    let client = reqwest::Client::new();
+    let base_url = format!("http://localhost:{}", port);
+    let creator_id = uuid!("33333333-3333-3333-3333-333333333333");
+    let creator_cookie = format!(
+        "session_id={}",
+        uuid!("55555555-5555-5555-5555-555555555555")
+    );
+
+    let check = Check {
+        check_id: Uuid::new_v4(),
+        regions: vec![Region::Fsn1],
+        data: CheckData {
+            // Ignored, the server records the authenticated user
+            created_by: Some(Uuid::new_v4()),
+            ..CheckData::example()
+        },
+    };
+
+    let response = client
+        .post(format!("{base_url}/checks/"))
+        .header("Cookie", &creator_cookie)
+        .json(&check)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let created: Check = response.json().await.unwrap();
+    assert_eq!(created.data.created_by, Some(creator_id));
+    assert_eq!(
+        created.data.created_by_username.as_deref(),
+        Some("testuser")
+    );
+
+    // Share the check with another editor
+    let editor_id = Uuid::new_v4();
+    let editor_session = Uuid::new_v4();
+    create_user(&state.database, editor_id, "editor", "password123")
+        .await
+        .unwrap();
+    create_session(&state.database, &*state.clock, editor_id, editor_session)
+        .await
+        .unwrap();
+    grant_check_access(
+        &state.database,
+        created.check_id,
+        editor_id,
+        "editor",
+        CheckAccess {
+            can_edit: true,
+            can_see: true,
+        },
+    )
+    .await
+    .unwrap();
+
+    let mut edited = created.clone();
+    edited.data.check_name = "Edited by editor".to_string();
+    edited.data.created_by = Some(editor_id);
+    edited.data.created_by_username = Some("editor".to_string());
+
+    let response = client
+        .patch(format!("{base_url}/checks/{}", created.check_id))
+        .header("Cookie", format!("session_id={editor_session}"))
+        .json(&edited)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let response = client
+        .get(format!("{base_url}/checks/{}", created.check_id))
+        .header("Cookie", &creator_cookie)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let fetched: CheckWithAccess = response.json().await.unwrap();
+    assert_eq!(fetched.check.data.check_name, "Edited by editor");
+    assert_eq!(fetched.check.data.created_by, Some(creator_id));
+    assert_eq!(
+        fetched.check.data.created_by_username.as_deref(),
+        Some("testuser")
+    );
+}
+
+#[tokio::test]
+async fn test_check_body_regex_validation() {
+    let fixtures = get_fixtures();
+    let (port, _) = start_server_test(Some(&fixtures)).await;
+    let client = reqwest::Client::new();
+    let base_url = format!("http://localhost:{}", port);
+    let session_cookie = format!(
+        "session_id={}",
+        uuid!("55555555-5555-5555-5555-555555555555")
+    );
+
+    let mut check = Check {
+        check_id: Uuid::new_v4(),
+        regions: vec![Region::Fsn1],
+        data: CheckData {
+            body_regex: Some("(unclosed".to_string()),
+            ..CheckData::example()
+        },
+    };
+
+    let response = client
+        .post(format!("{base_url}/checks/"))
+        .header("Cookie", &session_cookie)
+        .json(&check)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    check.data.body_regex = Some(r"v\d+\.\d+".to_string());
+    let response = client
+        .post(format!("{base_url}/checks/"))
+        .header("Cookie", &session_cookie)
+        .json(&check)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let created: Check = response.json().await.unwrap();
+    assert_eq!(created.data.body_regex, check.data.body_regex);
+}
+
+#[tokio::test]
+async fn test_check_fallback_urls_validation() {
+    let fixtures = get_fixtures();
+    let (port, _) = start_server_test(Some(&fixtures)).await;
+    let client = reqwest::Client::new();
+    let base_url = format!("http://localhost:{}", port);
+    let session_cookie = format!(
+        "session_id={}",
+        uuid!("55555555-5555-5555-5555-555555555555")
+    );
+
+    let mut check = Check {
+        check_id: Uuid::new_v4(),
+        regions: vec![Region::Fsn1],
+        data: CheckData::example(),
+    };
+
+    for fallback_url in [
+        "not a url",
+        "ftp://example.com/",
+        "http://127.0.0.1/health",
+        "http://localhost:8080/",
+        "http://[::1]/",
+        "https://10.0.0.1/",
+    ] {
+        check.data.fallback_urls =
+            vec!["https://example.org/".to_string(), fallback_url.to_string()];
+        let response = client
+            .post(format!("{base_url}/checks/"))
+            .header("Cookie", &session_cookie)
+            .json(&check)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST, "{fallback_url}");
+    }
+
+    check.data.fallback_urls = vec!["https://example.org/health".to_string()];
+    let response = client
+        .post(format!("{base_url}/checks/"))
+        .header("Cookie", &session_cookie)
+        .json(&check)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let created: Check = response.json().await.unwrap();
+    assert_eq!(created.data.fallback_urls, check.data.fallback_urls);
+
+    // Updates are validated too
+    let response = client
+        .patch(format!("{base_url}/checks/{}", created.check_id))
+        .header("Cookie", &session_cookie)
+        .json(&serde_json::json!({ "fallback_urls": ["http://192.168.1.1/"] }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn test_check_timeout_validation() {
+    let fixtures = get_fixtures();
+    let (port, _) = start_server_test(Some(&fixtures)).await;
+    let client = reqwest::Client::new();
+    let base_url = format!("http://localhost:{}", port);
+    let session_cookie = format!(
+        "session_id={}",
+        uuid!("55555555-5555-5555-5555-555555555555")
+    );
+    let max_timeout = *eager_env::MAX_PROBE_TIMEOUT_SECONDS as i32;
+
+    let mut check = Check {
+        check_id: Uuid::new_v4(),
+        regions: vec![Region::Fsn1],
+        data: CheckData::example(),
+    };
+
+    for timeout_seconds in [0, max_timeout + 1, i32::MAX] {
+        check.data.timeout_seconds = timeout_seconds;
+        let response = client
+            .post(format!("{base_url}/checks/"))
+            .header("Cookie", &session_cookie)
+            .json(&check)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(
+            response.status(),
+            StatusCode::BAD_REQUEST,
+            "{timeout_seconds}"
+        );
+    }
+
+    check.data.timeout_seconds = max_timeout;
+    let response = client
+        .post(format!("{base_url}/checks/"))
+        .header("Cookie", &session_cookie)
+        .json(&check)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let created: Check = response.json().await.unwrap();
+
+    // Updates are validated too
+    let response = client
+        .patch(format!("{base_url}/checks/{}", created.check_id))
+        .header("Cookie", &session_cookie)
+        .json(&serde_json::json!({ "timeout_seconds": max_timeout + 1 }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    // The connect timeout must be below the total one
+    let response = client
+        .patch(format!("{base_url}/checks/{}", created.check_id))
+        .header("Cookie", &session_cookie)
+        .json(&serde_json::json!({ "connect_timeout_millis": created.data.timeout_seconds * 1000 }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    // Retries must fit within the 60s frequency, 6 attempts of 10s and 5 delays of 1s don't
+    let response = client
+        .patch(format!("{base_url}/checks/{}", created.check_id))
+        .header("Cookie", &session_cookie)
+        .json(&serde_json::json!({ "timeout_seconds": 10, "retries": 5, "retry_delay_ms": 1000 }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    let response = client
+        .patch(format!("{base_url}/checks/{}", created.check_id))
+        .header("Cookie", &session_cookie)
+        .json(&serde_json::json!({ "timeout_seconds": 10, "retries": 2, "retry_delay_ms": 1000 }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let updated: Check = response.json().await.unwrap();
+    assert_eq!(updated.data.retries, 2);
+    assert_eq!(updated.data.retry_delay_ms, 1000);
+
+    // The delay is bounded even without retries, it would not fit in the database otherwise
+    let response = client
+        .patch(format!("{base_url}/checks/{}", created.check_id))
+        .header("Cookie", &session_cookie)
+        .json(&serde_json::json!({ "retries": 0, "retry_delay_ms": u32::MAX }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    for dns_cache_ttl_seconds in [-1, 0, 3601] {
+        let response = client
+            .patch(format!("{base_url}/checks/{}", created.check_id))
+            .header("Cookie", &session_cookie)
+            .json(&serde_json::json!({ "dns_cache_ttl_seconds": dns_cache_ttl_seconds }))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(
+            response.status(),
+            StatusCode::BAD_REQUEST,
+            "{dns_cache_ttl_seconds}"
+        );
+    }
+}
+
+#[tokio::test]
+async fn test_check_expected_status_codes() {
+    let fixtures = get_fixtures();
+    let (port, _) = start_server_test(Some(&fixtures)).await;
+    let client = reqwest::Client::new();
+    let base_url = format!("http://localhost:{}", port);
+    let session_cookie = format!(
+        "session_id={}",
+        uuid!("55555555-5555-5555-5555-555555555555")
+    );
+
+    // The former single code is still accepted
+    let mut check = serde_json::to_value(Check {
+        check_id: Uuid::new_v4(),
+        regions: vec![Region::Fsn1],
+        data: CheckData::example(),
+    })
+    .unwrap();
+    let fields = check.as_object_mut().unwrap();
+    fields.remove("expected_status_codes");
+    fields.insert("expected_status_code".to_string(), 204.into());
+    let response = client
+        .post(format!("{base_url}/checks/"))
+        .header("Cookie", &session_cookie)
+        .json(&check)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let created: Check = response.json().await.unwrap();
+    assert_eq!(created.data.expected_status_codes, 204.into());
+
+    let update = async |patch: serde_json::Value| {
+        client
+            .patch(format!("{base_url}/checks/{}", created.check_id))
+            .header("Cookie", &session_cookie)
+            .json(&patch)
+            .send()
+            .await
+            .unwrap()
+    };
+
+    let response =
+        update(serde_json::json!({ "expected_status_codes": { "min": 200, "max": 299 } })).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let updated: Check = response.json().await.unwrap();
+    assert_eq!(
+        updated.data.expected_status_codes,
+        ExpectedStatusCodes::Range { min: 200, max: 299 }
+    );
+
+    // Replacing the codes under the former name, `0` accepting any response
+    let response = update(serde_json::json!({ "expected_status_code": 0 })).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let updated: Check = response.json().await.unwrap();
+    assert_eq!(updated.data.expected_status_codes, 0.into());
+
+    for codes in [
+        serde_json::json!([200, 600]),
+        serde_json::json!(-1),
+        serde_json::json!({ "min": 0, "max": 299 }),
+    ] {
+        let response = update(serde_json::json!({ "expected_status_codes": codes })).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST, "{codes}");
+    }
+}
+
+#[tokio::test]
+async fn test_check_request_body_validation() {
+    let fixtures = get_fixtures();
+    let (port, _) = start_server_test(Some(&fixtures)).await;
+    let client = reqwest::Client::new();
+    let base_url = format!("http://localhost:{}", port);
+    let session_cookie = format!(
+        "session_id={}",
+        uuid!("55555555-5555-5555-5555-555555555555")
+    );
+
+    let mut check = Check {
+        check_id: Uuid::new_v4(),
+        regions: vec![Region::Fsn1],
+        data: CheckData::example(),
+    };
+    check.data.http_method = Method::Get;
+    check.data.request_body = Some("payload".to_string());
+
+    let response = client
+        .post(format!("{base_url}/checks/"))
+        .header("Cookie", &session_cookie)
+        .json(&check)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    // An empty body is never sent, so it's accepted
+    check.data.request_body = Some(String::new());
+    let response = client
+        .post(format!("{base_url}/checks/"))
+        .header("Cookie", &session_cookie)
+        .json(&check)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let created: Check = response.json().await.unwrap();
+
+    let response = client
+        .patch(format!("{base_url}/checks/{}", created.check_id))
+        .header("Cookie", &session_cookie)
+        .json(&serde_json::json!({ "http_method": "HEAD", "request_body": "payload" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    let response = client
+        .patch(format!("{base_url}/checks/{}", created.check_id))
+        .header("Cookie", &session_cookie)
+        .json(&serde_json::json!({ "http_method": "POST", "request_body": "payload" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+}
+
+#[tokio::test]
+async fn test_unique_check_names() {
+    let fixtures = get_fixtures();
+    let (port, _) = start_server_test_with(Some(&fixtures), |state| {
+        state.unique_check_names = true;
+    })
+    .await;
+    let client = reqwest::Client::new();
+    let base_url = format!("http://localhost:{port}");
+    let session_cookie = format!(
+        "session_id={}",
+        uuid!("55555555-5555-5555-5555-555555555555")
+    );
+
+    let mut check = Check {
+        check_id: Uuid::new_v4(),
+        regions: vec![Region::Fsn1],
+        data: CheckData {
+            check_name: "API prod".to_string(),
+            ..CheckData::example()
+        },
+    };
+
+    let response = client
+        .post(format!("{base_url}/checks/"))
+        .header("Cookie", &session_cookie)
+        .json(&check)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let created: Check = response.json().await.unwrap();
+
+    // Names are compared ignoring case
+    check.data.check_name = "api PROD".to_string();
+    let response = client
+        .post(format!("{base_url}/checks/"))
+        .header("Cookie", &session_cookie)
+        .json(&check)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::CONFLICT);
+
+    // A check keeps its own name when updated
+    let response = client
+        .patch(format!("{base_url}/checks/{}", created.check_id))
+        .header("Cookie", &session_cookie)
+        .json(&created)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    check.data.check_name = "API staging".to_string();
+    let response = client
+        .post(format!("{base_url}/checks/"))
+        .header("Cookie", &session_cookie)
+        .json(&check)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+}
+
+#[tokio::test]
+async fn test_check_annotations() {
+    let fixtures = get_fixtures();
+    let (port, state) = start_server_test(Some(&fixtures)).await;
+    let client = reqwest::Client::new();
+    let base_url = format!("http://localhost:{}", port);
+    let check_id = uuid!("44444444-4444-4444-4444-444444444444");
+    let annotations_url = format!("{base_url}/checks/{check_id}/annotations");
+    let editor_cookie = format!(
+        "session_id={}",
+        uuid!("55555555-5555-5555-5555-555555555555")
+    );
+
+    let response = client
+        .post(&annotations_url)
+        .header("Cookie", &editor_cookie)
+        .json(&serde_json::json!({
+            "text": "Deploy v2.14",
+            "starts_at": "2025-11-29T10:15:00Z",
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let deploy: CheckAnnotation = response.json().await.unwrap();
+    assert_eq!(deploy.check_id, check_id);
+    assert_eq!(deploy.ends_at, deploy.starts_at);
+    assert_eq!(deploy.author_username, "testuser");
+
+    let response = client
+        .post(&annotations_url)
+        .header("Cookie", &editor_cookie)
+        .json(&serde_json::json!({
+            "text": "  Maintenance  ",
+            "starts_at": "2025-11-29T12:00:00Z",
+            "ends_at": "2025-11-29T14:00:00Z",
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let maintenance: CheckAnnotation = response.json().await.unwrap();
+    assert_eq!(maintenance.text, "Maintenance");
+
+    // Empty texts and reversed ranges are rejected
+    for body in [
+        serde_json::json!({ "text": " ", "starts_at": "2025-11-29T10:00:00Z" }),
+        serde_json::json!({
+            "text": "Reversed",
+            "starts_at": "2025-11-29T10:00:00Z",
+            "ends_at": "2025-11-29T09:00:00Z",
+        }),
+    ] {
+        let response = client
+            .post(&annotations_url)
+            .header("Cookie", &editor_cookie)
+            .json(&body)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    // Viewers can read annotations but not write them
+    let viewer_id = Uuid::new_v4();
+    let viewer_session = Uuid::new_v4();
+    create_user(&state.database, viewer_id, "viewer", "password123")
+        .await
+        .unwrap();
+    create_session(&state.database, &*state.clock, viewer_id, viewer_session)
+        .await
+        .unwrap();
+    grant_check_access(
+        &state.database,
+        check_id,
+        viewer_id,
+        "viewer",
+        CheckAccess {
+            can_edit: false,
+            can_see: true,
+        },
+    )
+    .await
+    .unwrap();
+    let viewer_cookie = format!("session_id={viewer_session}");
+
+    let response = client
+        .post(&annotations_url)
+        .header("Cookie", &viewer_cookie)
+        .json(&serde_json::json!({
+            "text": "Not allowed",
+            "starts_at": "2025-11-29T10:00:00Z",
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+    let list = |from: &str, to: &str| {
+        client
+            .get(&annotations_url)
+            .header("Cookie", &viewer_cookie)
+            .query(&[("from", from), ("to", to)])
+            .send()
+    };
+
+    // Graphs return them separately from their points, even when there are none
+    let response = client
+        .get(format!("{base_url}/checks/{check_id}/metrics/graph"))
+        .header("Cookie", &viewer_cookie)
+        .query(&[
+            ("from", "2025-11-29T00:00:00Z"),
+            ("to", "2025-11-30T00:00:00Z"),
+            ("granularity", "Hourly"),
+        ])
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let graph: MetricsGraph = response.json().await.unwrap();
+    assert!(graph.dates.is_empty());
+    assert_eq!(graph.annotations, [deploy.clone(), maintenance.clone()]);
+
+    let response = list("2025-11-29T00:00:00Z", "2025-11-30T00:00:00Z")
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let listed: Vec<CheckAnnotation> = response.json().await.unwrap();
+    assert_eq!(listed, [deploy.clone(), maintenance.clone()]);
+
+    // Only annotations overlapping the range are listed
+    let listed: Vec<CheckAnnotation> = list("2025-11-29T13:00:00Z", "2025-11-29T15:00:00Z")
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(listed, [maintenance]);
+
+    let listed: Vec<CheckAnnotation> = list("2025-11-29T10:00:00Z", "2025-11-29T10:15:00Z")
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert!(listed.is_empty());
+
+    // Users without access can't read them
+    let stranger_id = Uuid::new_v4();
+    let stranger_session = Uuid::new_v4();
+    create_user(&state.database, stranger_id, "stranger", "password123")
+        .await
+        .unwrap();
+    create_session(
+        &state.database,
+        &*state.clock,
+        stranger_id,
+        stranger_session,
+    )
+    .await
+    .unwrap();
+    let response = client
+        .get(&annotations_url)
+        .header("Cookie", format!("session_id={stranger_session}"))
+        .query(&[
+            ("from", "2025-11-29T00:00:00Z"),
+            ("to", "2025-11-30T00:00:00Z"),
+        ])
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+}
+
+#[test]
+fn test_merge_patch() {
+    let mut target = serde_json::json!({
+        "check_name": "API",
+        "timeout_seconds": 10,
+        "body_regex": "ok",
+        "request_headers": { "Accept": "text/plain", "X-Token": "secret" },
+    });
+
+    super::merge_patch(
+        &mut target,
+        serde_json::json!({
+            "timeout_seconds": 30,
+            "body_regex": null,
+            "request_headers": { "Accept": "application/json", "X-Token": null },
+        }),
+    );
+
+    assert_eq!(
+        target,
+        serde_json::json!({
+            "check_name": "API",
+            "timeout_seconds": 30,
+            "request_headers": { "Accept": "application/json" },
+        })
+    );
+}
+
+#[tokio::test]
+async fn test_partial_check_update() {
+    let fixtures = get_fixtures();
+    let (port, _) = start_server_test(Some(&fixtures)).await;
+    let client = reqwest::Client::new();
+    let base_url = format!("http://localhost:{}", port);
+    let session_cookie = format!(
+        "session_id={}",
+        uuid!("55555555-5555-5555-5555-555555555555")
+    );
+
+    let check = Check {
+        check_id: Uuid::new_v4(),
+        regions: vec![Region::Fsn1, Region::Hel1],
+        data: CheckData {
+            check_name: "Partially updated".to_string(),
+            request_headers: HashMap::from([("Accept".to_string(), "text/plain".to_string())]),
+            body_regex: Some("ok".to_string()),
+            ..CheckData::example()
+        },
+    };
+    let response = client
+        .post(format!("{base_url}/checks/"))
+        .header("Cookie", &session_cookie)
+        .json(&check)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let created: Check = response.json().await.unwrap();
+    let check_url = format!("{base_url}/checks/{}", created.check_id);
+
+    let get_check = || async {
+        let response = client
+            .get(&check_url)
+            .header("Cookie", &session_cookie)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        response.json::<CheckWithAccess>().await.unwrap().check
+    };
+    let before = get_check().await;
+
+    let response = client
+        .patch(&check_url)
+        .header("Cookie", &session_cookie)
+        .json(&serde_json::json!({ "check_frequency_seconds": 300 }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let patched: Check = response.json().await.unwrap();
+    assert_eq!(patched.data.check_frequency_seconds, 300);
+
+    // Every other field is preserved
+    let after = get_check().await;
+    let mut expected = serde_json::to_value(&before).unwrap();
+    expected["check_frequency_seconds"] = 300.into();
+    assert_eq!(serde_json::to_value(&after).unwrap(), expected);
+    assert_eq!(after.data.check_name, "Partially updated");
+    assert_eq!(after.data.body_regex.as_deref(), Some("ok"));
+    assert_eq!(after.regions.len(), 2);
+
+    // `null` clears optional fields
+    let response = client
+        .patch(&check_url)
+        .header("Cookie", &session_cookie)
+        .json(&serde_json::json!({ "body_regex": null }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let after = get_check().await;
+    assert_eq!(after.data.body_regex, None);
+    assert_eq!(after.data.check_frequency_seconds, 300);
+
+    // Required fields can't be cleared, and patched values are validated
+    for patch in [
+        serde_json::json!({ "check_name": null }),
+        serde_json::json!({ "check_frequency_seconds": "often" }),
+        serde_json::json!({ "body_regex": "(unclosed" }),
+    ] {
+        let response = client
+            .patch(&check_url)
+            .header("Cookie", &session_cookie)
+            .json(&patch)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST, "{patch}");
+    }
+    assert_eq!(get_check().await.data.check_frequency_seconds, 300);
+}
+
+#[tokio::test]
+async fn test_update_proxy_password() {
+    let fixtures = get_fixtures();
+    let (port, state) = start_server_test(Some(&fixtures)).await;
+    let client = reqwest::Client::new();
+    let base_url = format!("http://localhost:{}", port);
+    let session_cookie = format!(
+        "session_id={}",
+        uuid!("55555555-5555-5555-5555-555555555555")
+    );
+
+    let check = Check {
+        check_id: Uuid::new_v4(),
+        regions: vec![Region::Hel1],
+        data: CheckData {
+            check_name: "Behind a proxy".to_string(),
+            proxy: Some(ProxyConfig {
+                url: "http://proxy.example.com:3128".to_string(),
+                username: Some("user".to_string()),
+                password: Some("hunter2".to_string()),
+                remote_dns: false,
+            }),
+            ..CheckData::example()
+        },
+    };
+    let response = client
+        .post(format!("{base_url}/checks/"))
+        .header("Cookie", &session_cookie)
+        .json(&check)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let created: Check = response.json().await.unwrap();
+    let check_url = format!("{base_url}/checks/{}", created.check_id);
+
+    let stored_password = || async {
+        get_check_by_id(&state.database, created.check_id)
+            .await
+            .unwrap()
+            .unwrap()
+            .data
+            .proxy
+            .unwrap()
+            .password
+    };
+
+    // Omitting the password keeps the stored one
+    for patch in [
+        serde_json::json!({ "check_frequency_seconds": 300 }),
+        serde_json::json!({ "proxy": { "remote_dns": false } }),
+    ] {
+        let response = client
+            .patch(&check_url)
+            .header("Cookie", &session_cookie)
+            .json(&patch)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK, "{patch}");
+        assert_eq!(
+            stored_password().await.as_deref(),
+            Some("hunter2"),
+            "{patch}"
+        );
+    }
+
+    // An explicit `null` clears it
+    let response = client
+        .patch(&check_url)
+        .header("Cookie", &session_cookie)
+        .json(&serde_json::json!({ "proxy": { "password": null } }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(stored_password().await, None);
+}
+
+#[tokio::test]
+async fn test_passive_check_ping() {
+    let fixtures = get_fixtures();
+    let (port, state) = start_server_test(Some(&fixtures)).await;
+    let client = reqwest::Client::new();
+    let base_url = format!("http://localhost:{}", port);
+    let session_cookie = format!(
+        "session_id={}",
+        uuid!("55555555-5555-5555-5555-555555555555")
+    );
+
+    // Only passive checks are pinged
+    let response = client
+        .post(format!(
+            "{base_url}/checks/44444444-4444-4444-4444-444444444444/ping-token"
+        ))
+        .header("Cookie", &session_cookie)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    let check = Check {
+        check_id: Uuid::new_v4(),
+        regions: vec![Region::Hel1],
+        data: CheckData {
+            check_name: "Nightly backup".to_string(),
+            kind: CheckKind::Passive,
+            ..CheckData::example()
+        },
+    };
+    let response = client
+        .post(format!("{base_url}/checks/"))
+        .header("Cookie", &session_cookie)
+        .json(&check)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let check_id = response.json::<Check>().await.unwrap().check_id;
+
+    let response = client
+        .post(format!("{base_url}/checks/{check_id}/ping-token"))
+        .header("Cookie", &session_cookie)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let ping_token = response.json::<serde_json::Value>().await.unwrap()["ping_token"]
+        .as_str()
+        .unwrap()
+        .to_string();
+
+    // Invalid token
+    let response = client
+        .post(format!(
+            "{base_url}/checks/{check_id}/ping?token={}",
+            Uuid::new_v4()
+        ))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    assert_eq!(
+        get_last_check_ping(&state.database, check_id)
+            .await
+            .unwrap(),
+        None
+    );
+
+    // Unauthenticated, with the ping token
+    let response = client
+        .post(format!(
+            "{base_url}/checks/{check_id}/ping?token={ping_token}"
+        ))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::NO_CONTENT);
+    assert!(
+        get_last_check_ping(&state.database, check_id)
+            .await
+            .unwrap()
+            .is_some()
+    );
+}
+
+#[tokio::test]
+async fn test_passive_check_ping_burst_is_coalesced() {
+    let fixtures = get_fixtures();
+    // Never flushed on its own, the test does it
+    let batcher = Arc::new(PingBatcher::new(Duration::from_secs(3600)));
+    let batcher_clone = batcher.clone();
+    let (port, state) = start_server_test_with(Some(&fixtures), move |state| {
+        state.ping_batcher = Some(batcher_clone);
+    })
+    .await;
+    let client = reqwest::Client::new();
+    let base_url = format!("http://localhost:{}", port);
+
+    let check_id = Uuid::new_v4();
+    let ping_token = rotate_check_ping_token(&state.database, check_id)
+        .await
+        .unwrap();
+
+    let statuses = futures::future::join_all((0..200).map(|_| {
+        client
+            .post(format!(
+                "{base_url}/checks/{check_id}/ping?token={ping_token}"
+            ))
+            .send()
+    }))
+    .await;
+    for response in statuses {
+        assert_eq!(response.unwrap().status(), StatusCode::NO_CONTENT);
+    }
+
+    // Nothing written until the flush
+    assert_eq!(
+        get_last_check_ping(&state.database, check_id)
+            .await
+            .unwrap(),
+        None
+    );
+    assert_eq!(batcher.flush(&state.database).await.unwrap(), 1);
+    assert!(
+        get_last_check_ping(&state.database, check_id)
+            .await
+            .unwrap()
+            .is_some()
+    );
+    assert_eq!(batcher.flush(&state.database).await.unwrap(), 0);
+}
+
+#[tokio::test]
+async fn test_create_check_warns_about_unmonitored_regions() {
+    let fixtures = get_fixtures();
+    let (port, state) = start_server_test(Some(&fixtures)).await;
+    let client = reqwest::Client::new();
+    let base_url = format!("http://localhost:{}", port);
+    let session_cookie = format!(
+        "session_id={}",
+        uuid!("55555555-5555-5555-5555-555555555555")
+    );
+
+    // Only Fsn1 has an alive worker
+    state
+        .heartbeat_manager
+        .register_nodes(&[Heartbeat {
+            region: Region::Fsn1,
+            ..Heartbeat::example()
+        }])
+        .await;
+
+    let create = |regions: Vec<Region>| {
+        let check = Check {
+            check_id: Uuid::new_v4(),
+            regions,
+            data: CheckData::example(),
+        };
+        client
+            .post(format!("{base_url}/checks/"))
+            .header("Cookie", &session_cookie)
+            .json(&check)
+            .send()
+    };
+
+    let response = create(vec![Region::Fsn1, Region::Hel1]).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let created: CreatedCheck = response.json().await.unwrap();
+    assert_eq!(created.check.regions.len(), 2);
+    assert_eq!(created.warnings.len(), 1);
+    assert!(created.warnings[0].contains("hel1"));
+
+    let response = create(vec![Region::Fsn1]).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let created: CreatedCheck = response.json().await.unwrap();
+    assert!(created.warnings.is_empty());
+}
+
+#[tokio::test]
+async fn test_transfer_check() {
+    let fixtures = get_fixtures();
+    let (port, state) = start_server_test(Some(&fixtures)).await;
+    let client = reqwest::Client::new();
+    let base_url = format!("http://localhost:{port}");
+    let owner_cookie = format!(
+        "session_id={}",
+        uuid!("55555555-5555-5555-5555-555555555555")
+    );
+
+    let check = Check {
+        check_id: Uuid::new_v4(),
+        regions: vec![Region::Fsn1],
+        data: CheckData::example(),
+    };
+    let response = client
+        .post(format!("{base_url}/checks/"))
+        .header("Cookie", &owner_cookie)
+        .json(&check)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let created: Check = response.json().await.unwrap();
+
+    let target_id = Uuid::new_v4();
+    let target_session = Uuid::new_v4();
+    create_user(&state.database, target_id, "teammate", "password123")
+        .await
+        .unwrap();
+    create_session(&state.database, &*state.clock, target_id, target_session)
+        .await
+        .unwrap();
+    let target_cookie = format!("session_id={target_session}");
+
+    // Only editors can transfer
+    let response = client
+        .post(format!("{base_url}/checks/{}/transfer", created.check_id))
+        .header("Cookie", &target_cookie)
+        .json(&serde_json::json!({ "username": "teammate" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+    let response = client
+        .post(format!("{base_url}/checks/{}/transfer", created.check_id))
+        .header("Cookie", &owner_cookie)
+        .json(&serde_json::json!({ "username": "nobody" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+    let response = client
+        .post(format!("{base_url}/checks/{}/transfer", created.check_id))
+        .header("Cookie", &owner_cookie)
+        .json(&serde_json::json!({ "username": "teammate", "revoke_previous_owner": true }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let transferred: Check = response.json().await.unwrap();
+    assert_eq!(transferred.data.created_by, Some(target_id));
+    assert_eq!(
+        transferred.data.created_by_username.as_deref(),
+        Some("teammate")
+    );
+
+    let response = client
+        .get(format!("{base_url}/checks/"))
+        .header("Cookie", &target_cookie)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let checks: Vec<CheckWithAccess> = response.json().await.unwrap();
+    let owned = checks
+        .iter()
+        .find(|c| c.check.check_id == created.check_id)
+        .expect("transferred check listed for the new owner");
+    assert!(owned.access.can_edit);
+    assert_eq!(owned.check.data.created_by, Some(target_id));
+
+    // The previous owner's access was revoked
+    let response = client
+        .get(format!("{base_url}/checks/{}", created.check_id))
+        .header("Cookie", &owner_cookie)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+async fn test_create_check_warns_about_unreachable_ip_versions() {
+    let fixtures = get_fixtures();
+    let (port, state) = start_server_test_with(Some(&fixtures), |state| {
+        state.ip_version_preference = IpVersionPreference::V4;
+    })
+    .await;
+    let client = reqwest::Client::new();
+    let base_url = format!("http://localhost:{port}");
+    let session_cookie = format!(
+        "session_id={}",
+        uuid!("55555555-5555-5555-5555-555555555555")
+    );
+
+    // No warnings about regions
+    state
+        .heartbeat_manager
+        .register_nodes(&[Heartbeat {
+            region: Region::Fsn1,
+            ..Heartbeat::example()
+        }])
+        .await;
+
+    let create = |url: &str| {
+        let check = Check {
+            check_id: Uuid::new_v4(),
+            regions: vec![Region::Fsn1],
+            data: CheckData {
+                url: url.to_string(),
+                ..CheckData::example()
+            },
+        };
+        client
+            .post(format!("{base_url}/checks/"))
+            .header("Cookie", &session_cookie)
+            .json(&check)
+            .send()
+    };
+
+    // IPv6-only host
+    let response = create("https://[::1]/").await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let created: CreatedCheck = response.json().await.unwrap();
+    assert_eq!(created.warnings.len(), 1);
+    assert!(created.warnings[0].contains("IPv6"));
+
+    let response = create("https://127.0.0.1/").await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let created: CreatedCheck = response.json().await.unwrap();
+    assert!(created.warnings.is_empty());
+}
+
+#[tokio::test]
+async fn test_max_regions_per_check() {
+    let fixtures = get_fixtures();
+    let (port, _) = start_server_test_with(Some(&fixtures), |state| {
+        state.max_regions_per_check = 2;
+    })
+    .await;
+    let client = reqwest::Client::new();
+    let base_url = format!("http://localhost:{port}");
+    let session_cookie = format!(
+        "session_id={}",
+        uuid!("55555555-5555-5555-5555-555555555555")
+    );
+
+    let mut check = Check {
+        check_id: Uuid::new_v4(),
+        regions: vec![Region::Fsn1, Region::Hel1, Region::Nbg1],
+        data: CheckData::example(),
+    };
+    let response = client
+        .post(format!("{base_url}/checks/"))
+        .header("Cookie", &session_cookie)
+        .json(&check)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    // Duplicates count once
+    check.regions = vec![Region::Fsn1, Region::Hel1, Region::Fsn1];
+    let response = client
+        .post(format!("{base_url}/checks/"))
+        .header("Cookie", &session_cookie)
+        .json(&check)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let created: Check = response.json().await.unwrap();
+
+    let response = client
+        .patch(format!("{base_url}/checks/{}", created.check_id))
+        .header("Cookie", &session_cookie)
+        .json(&serde_json::json!({ "regions": ["Fsn1", "Hel1", "Nbg1"] }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn test_get_check_with_recent_results() {
+    let fixtures = get_fixtures();
+    let now = "2025-11-29T12:00:00Z".parse::<DateTime<Utc>>().unwrap();
+    let (port, state) = start_server_test_with(Some(&fixtures), |state| {
+        state.clock = MockClock::new(now);
+    })
+    .await;
+    let client = reqwest::Client::new();
+    let base_url = format!("http://localhost:{port}");
+    let session_cookie = format!(
+        "session_id={}",
+        uuid!("55555555-5555-5555-5555-555555555555")
+    );
+    let check_id = uuid!("44444444-4444-4444-4444-444444444444");
+
+    // The last one is after the server's now
+    for minutes_ago in (-1..=105).filter(|&minutes_ago| minutes_ago != 0) {
+        let started_at = now - chrono::Duration::minutes(minutes_ago);
+        state
+            .database
+            .query_unpaged(
+                "INSERT INTO check_results (result_id, service_check_id, region, day, check_started_at, response_time_micros, status_code, matches_expected, response_body_fetched) VALUES (?, ?, 'hel1', ?, ?, 1000, 200, true, false)",
+                (Uuid::new_v4(), check_id, started_at.date_naive(), started_at),
+            )
+            .await
+            .unwrap();
+    }
+
+    // Not requested, not included
+    let response = client
+        .get(format!("{base_url}/checks/{check_id}"))
+        .header("Cookie", &session_cookie)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let check: CheckWithAccess = response.json().await.unwrap();
+    assert!(check.recent_results.is_none());
+
+    let response = client
+        .get(format!("{base_url}/checks/{check_id}?recent=3"))
+        .header("Cookie", &session_cookie)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let check: CheckWithAccess = response.json().await.unwrap();
+    let recent = check.recent_results.unwrap();
+    assert_eq!(recent.len(), 3);
+    assert!(
+        recent
+            .windows(2)
+            .all(|pair| pair[0].check_started_at >= pair[1].check_started_at)
+    );
+    assert_eq!(
+        recent[0].check_started_at,
+        now - chrono::Duration::minutes(1)
+    );
+
+    // Capped
+    let response = client
+        .get(format!("{base_url}/checks/{check_id}?recent=1000"))
+        .header("Cookie", &session_cookie)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let check: CheckWithAccess = response.json().await.unwrap();
+    assert_eq!(check.recent_results.unwrap().len(), 100);
+}
+
+#[tokio::test]
+async fn test_create_check_requires_side_effects_acknowledgment() {
+    let fixtures = get_fixtures();
+    let (port, _) = start_server_test(Some(&fixtures)).await;
+    let client = reqwest::Client::new();
+    let base_url = format!("http://localhost:{port}");
+    let session_cookie = format!(
+        "session_id={}",
+        uuid!("55555555-5555-5555-5555-555555555555")
+    );
+
+    let mut check = Check {
+        check_id: Uuid::new_v4(),
+        regions: vec![Region::Fsn1],
+        data: CheckData::example(),
+    };
+
+    for method in [Method::Post, Method::Put, Method::Patch, Method::Delete] {
+        check.data.http_method = method;
+        let response = client
+            .post(format!("{base_url}/checks/"))
+            .header("Cookie", &session_cookie)
+            .json(&check)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST, "{method:?}");
+    }
+    check.data.http_method = Method::Delete;
+
+    let response = client
+        .post(format!("{base_url}/checks/"))
+        .header("Cookie", &session_cookie)
+        .json(&CreateCheckRequest {
+            check,
+            acknowledge_side_effects: true,
+        })
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let created: Check = response.json().await.unwrap();
+    assert_eq!(created.data.http_method, Method::Delete);
+}
+
+#[tokio::test]
+async fn test_steps_check_requires_side_effects_acknowledgment() {
+    let fixtures = get_fixtures();
+    let (port, _) = start_server_test(Some(&fixtures)).await;
+    let client = reqwest::Client::new();
+    let base_url = format!("http://localhost:{port}");
+    let session_cookie = format!(
+        "session_id={}",
+        uuid!("55555555-5555-5555-5555-555555555555")
+    );
+
+    let step = |http_method: Method| CheckStep {
+        url: "https://example.com/items/1".to_string(),
+        http_method,
+        request_headers: HashMap::new(),
+        request_body: None,
+        expected_status_code: 200,
+        extract: vec![],
+    };
+    let check = Check {
+        check_id: Uuid::new_v4(),
+        regions: vec![Region::Fsn1],
+        data: CheckData {
+            kind: CheckKind::Steps,
+            steps: vec![step(Method::Get), step(Method::Delete)],
+            ..CheckData::example()
+        },
+    };
+
+    let create = async |acknowledge_side_effects: bool| {
+        client
+            .post(format!("{base_url}/checks/"))
+            .header("Cookie", &session_cookie)
+            .json(&CreateCheckRequest {
+                check: check.clone(),
+                acknowledge_side_effects,
+            })
+            .send()
+            .await
+            .unwrap()
+            .status()
+    };
+    assert_eq!(create(false).await, StatusCode::BAD_REQUEST);
+    assert_eq!(create(true).await, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn test_steps_check_validation() {
+    let fixtures = get_fixtures();
+    let (port, _) = start_server_test(Some(&fixtures)).await;
+    let client = reqwest::Client::new();
+    let base_url = format!("http://localhost:{port}");
+    let session_cookie = format!(
+        "session_id={}",
+        uuid!("55555555-5555-5555-5555-555555555555")
+    );
+
+    let step = |url: &str| CheckStep {
+        url: url.to_string(),
+        http_method: Method::Get,
+        request_headers: HashMap::new(),
+        request_body: None,
+        expected_status_code: 200,
+        extract: vec![],
+    };
+    let create = async |steps: Vec<CheckStep>| {
+        let check = Check {
+            check_id: Uuid::new_v4(),
+            regions: vec![Region::Fsn1],
+            data: CheckData {
+                kind: CheckKind::Steps,
+                steps,
+                ..CheckData::example()
+            },
+        };
+        client
+            .post(format!("{base_url}/checks/"))
+            .header("Cookie", &session_cookie)
+            .json(&check)
+            .send()
+            .await
+            .unwrap()
+            .status()
+    };
+
+    assert_eq!(create(vec![]).await, StatusCode::BAD_REQUEST);
+    for url in ["not a url", "ftp://example.com/", "http://10.0.0.1/admin"] {
+        assert_eq!(
+            create(vec![step("https://example.com/"), step(url)]).await,
+            StatusCode::BAD_REQUEST,
+            "{url}"
+        );
+    }
+    // Filled in by the probe
+    assert_eq!(
+        create(vec![step("https://example.com/"), step("{{next_url}}")]).await,
+        StatusCode::OK
+    );
+}
+
+#[tokio::test]
+async fn test_update_check_requires_side_effects_acknowledgment() {
+    let fixtures = get_fixtures();
+    let (port, _) = start_server_test(Some(&fixtures)).await;
+    let client = reqwest::Client::new();
+    let base_url = format!("http://localhost:{port}");
+    let session_cookie = format!(
+        "session_id={}",
+        uuid!("55555555-5555-5555-5555-555555555555")
+    );
+
+    let check = Check {
+        check_id: Uuid::new_v4(),
+        regions: vec![Region::Fsn1],
+        data: CheckData::example(),
+    };
+    let response = client
+        .post(format!("{base_url}/checks/"))
+        .header("Cookie", &session_cookie)
+        .json(&check)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let created: Check = response.json().await.unwrap();
+
+    let update = async |patch: serde_json::Value| {
+        client
+            .patch(format!("{base_url}/checks/{}", created.check_id))
+            .header("Cookie", &session_cookie)
+            .json(&patch)
+            .send()
+            .await
+            .unwrap()
+            .status()
+    };
+
+    assert_eq!(
+        update(serde_json::json!({ "http_method": "DELETE" })).await,
+        StatusCode::BAD_REQUEST
+    );
+    assert_eq!(
+        update(serde_json::json!({ "http_method": "DELETE", "acknowledge_side_effects": true }))
+            .await,
+        StatusCode::OK
+    );
+    // Already acknowledged
+    assert_eq!(
+        update(serde_json::json!({ "check_name": "Renamed" })).await,
+        StatusCode::OK
+    );
+}
+
+#[tokio::test]
+async fn test_private_targets_require_server_permission() {
+    let fixtures = get_fixtures();
+    let session_cookie = format!(
+        "session_id={}",
+        uuid!("55555555-5555-5555-5555-555555555555")
+    );
+    let check = Check {
+        check_id: Uuid::new_v4(),
+        regions: vec![Region::Fsn1],
+        data: CheckData {
+            url: "http://10.0.0.1/health".to_string(),
+            allow_private_targets: true,
+            ..CheckData::example()
+        },
+    };
+
+    let create = async |allowed: bool| {
+        let (port, _) = start_server_test_with(Some(&fixtures), |state| {
+            state.private_targets_allowed = allowed;
+        })
+        .await;
+        let base_url = format!("http://localhost:{port}");
+        let client = reqwest::Client::new();
+        let response = client
+            .post(format!("{base_url}/checks/"))
+            .header("Cookie", &session_cookie)
+            .json(&check)
+            .send()
+            .await
+            .unwrap();
+        (base_url, response)
+    };
+
+    let (base_url, response) = create(false).await;
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+    // Nor can an existing check be switched to it
+    let client = reqwest::Client::new();
+    let response = client
+        .patch(format!(
+            "{base_url}/checks/{}",
+            uuid!("44444444-4444-4444-4444-444444444444")
+        ))
+        .header("Cookie", &session_cookie)
+        .json(&serde_json::json!({"allow_private_targets": true}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+    let (_, response) = create(true).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let created: Check = response.json().await.unwrap();
+    assert!(created.data.allow_private_targets);
+}
+
+#[tokio::test]
+async fn test_alert_destinations_follow_tags() {
+    let fixtures = get_fixtures();
+    let (port, _) = start_server_test(Some(&fixtures)).await;
+    let client = reqwest::Client::new();
+    let base_url = format!("http://localhost:{port}");
+    let session_cookie = format!(
+        "session_id={}",
+        uuid!("55555555-5555-5555-5555-555555555555")
+    );
+
+    let response = client
+        .put(format!("{base_url}/users/me/alert-routing"))
+        .header("Cookie", &session_cookie)
+        .json(&serde_json::json!({
+            "routes": [{"tag": "team", "value": "payments", "webhook_url": "https://hooks.example.com/payments"}],
+            "default_webhook_url": "https://hooks.example.com/all",
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let check = Check {
+        check_id: Uuid::new_v4(),
+        regions: vec![Region::Hel1],
+        data: CheckData {
+            tags: HashMap::from([("team".to_string(), "payments".to_string())]),
+            ..CheckData::example()
+        },
+    };
+    let response = client
+        .post(format!("{base_url}/checks/"))
+        .header("Cookie", &session_cookie)
+        .json(&check)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let tagged: Check = response.json().await.unwrap();
+    assert_eq!(tagged.data.tags, check.data.tags);
+
+    let destinations = async |check_id: Uuid| -> serde_json::Value {
+        client
+            .get(format!("{base_url}/checks/{check_id}/alert-destinations"))
+            .header("Cookie", &session_cookie)
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap()
+    };
+
+    assert_eq!(
+        destinations(tagged.check_id).await,
+        serde_json::json!({"webhook_urls": ["https://hooks.example.com/payments"], "is_default": false})
+    );
+    // The fixture check has no tags
+    assert_eq!(
+        destinations(uuid!("44444444-4444-4444-4444-444444444444")).await,
+        serde_json::json!({"webhook_urls": ["https://hooks.example.com/all"], "is_default": true})
+    );
+
+    // Routes need a tag and a web URL
+    let response = client
+        .put(format!("{base_url}/users/me/alert-routing"))
+        .header("Cookie", &session_cookie)
+        .json(&serde_json::json!({
+            "routes": [{"tag": "team", "webhook_url": "ftp://hooks.example.com"}],
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn test_metrics_use_read_replica() {
+    let fixtures = get_fixtures();
+    let (replica, _) = create_test_database(Some(&fixtures)).await.unwrap();
+    let replica = Arc::new(replica);
+    let check_id = uuid!("44444444-4444-4444-4444-444444444444");
+
+    // The result only exists on the replica
+    let started_at = Utc::now() - chrono::Duration::minutes(5);
+    replica
+        .query_unpaged(
+            "INSERT INTO check_results (result_id, service_check_id, region, day, check_started_at, response_time_micros, status_code, matches_expected, response_body_fetched) VALUES (?, ?, 'hel1', ?, ?, 1000, 200, true, false)",
+            (Uuid::new_v4(), check_id, started_at.date_naive(), started_at),
+        )
+        .await
+        .unwrap();
+
+    let has_data = async |read_replica: Option<Arc<Database>>| {
+        let (port, _) = start_server_test_with(Some(&fixtures), |state| {
+            state.read_replica = read_replica;
+        })
+        .await;
+        let to = Utc::now();
+        let response = reqwest::Client::new()
+            .get(format!("http://localhost:{port}/checks/{check_id}/metrics"))
+            .query(&[
+                ("from", (to - chrono::Duration::hours(1)).to_rfc3339()),
+                ("to", to.to_rfc3339()),
+            ])
+            .header(
+                "Cookie",
+                format!(
+                    "session_id={}",
+                    uuid!("55555555-5555-5555-5555-555555555555")
+                ),
+            )
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let metrics: serde_json::Value = response.json().await.unwrap();
+        metrics["has_data"].as_bool().unwrap()
+    };
+
+    assert!(has_data(Some(replica.clone())).await);
+    // Falls back to the primary, which has no results
+    assert!(!has_data(None).await);
+}
+
+#[tokio::test]
+async fn test_check_metadata_is_echoed_in_metrics() {
+    let fixtures = get_fixtures();
+    let (port, _) = start_server_test(Some(&fixtures)).await;
+    let client = reqwest::Client::new();
+    let base_url = format!("http://localhost:{port}");
+    let session_cookie = format!(
+        "session_id={}",
+        uuid!("55555555-5555-5555-5555-555555555555")
+    );
+
+    let metadata = HashMap::from([
+        ("service_id".to_string(), "svc-42".to_string()),
+        ("owner".to_string(), "payments@example.com".to_string()),
+    ]);
+    let check = Check {
+        check_id: Uuid::new_v4(),
+        regions: vec![Region::Hel1],
+        data: CheckData {
+            metadata: metadata.clone(),
+            ..CheckData::example()
+        },
+    };
+    let response = client
+        .post(format!("{base_url}/checks/"))
+        .header("Cookie", &session_cookie)
+        .json(&check)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let created: Check = response.json().await.unwrap();
+    assert_eq!(created.data.metadata, metadata);
+
+    let now = Utc::now();
+    let response = client
+        .get(format!("{base_url}/checks/{}/metrics", created.check_id))
+        .query(&[
+            ("from", (now - chrono::Duration::hours(1)).to_rfc3339()),
+            ("to", now.to_rfc3339()),
+        ])
+        .header("Cookie", &session_cookie)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let metrics: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(metrics["metadata"], serde_json::json!(metadata));
+
+    // Bounded
+    let check = Check {
+        check_id: Uuid::new_v4(),
+        regions: vec![Region::Hel1],
+        data: CheckData {
+            metadata: HashMap::from([("blob".to_string(), "x".repeat(5000))]),
+            ..CheckData::example()
+        },
+    };
+    let response = client
+        .post(format!("{base_url}/checks/"))
+        .header("Cookie", &session_cookie)
+        .json(&check)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn test_empty_metrics_window() {
+    let fixtures = get_fixtures();
+    let (port, _) = start_server_test(Some(&fixtures)).await;
+    let client = reqwest::Client::new();
+    let base_url = format!("http://localhost:{port}");
+    let check_id = uuid!("44444444-4444-4444-4444-444444444444");
+    let session_cookie = format!(
+        "session_id={}",
+        uuid!("55555555-5555-5555-5555-555555555555")
+    );
+
+    let get = async |path: &str, from: DateTime<Utc>, to: DateTime<Utc>, extra: &[(&str, &str)]| {
+        client
+            .get(format!("{base_url}/checks/{check_id}/{path}"))
+            .query(&[("from", from.to_rfc3339()), ("to", to.to_rfc3339())])
+            .query(extra)
+            .header("Cookie", &session_cookie)
+            .send()
+            .await
+            .unwrap()
+    };
+    let hour = Utc::now()
+        .duration_trunc(chrono::Duration::hours(1))
+        .unwrap();
+
+    // Empty window, no results
+    let response = get("metrics", hour, hour, &[]).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let metrics: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(metrics["has_data"], false);
+    assert_eq!(metrics["uptime_percent"], 0.0);
+    assert_eq!(metrics["time_in_state"]["up_seconds"], 0);
+
+    // No points
+    let response = get("metrics/graph", hour, hour, &[("granularity", "Hourly")]).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let graph: serde_json::Value = response.json().await.unwrap();
+    assert!(graph["dates"].as_array().unwrap().is_empty());
+
+    // Reversed windows are still rejected
+    let earlier = hour - chrono::Duration::hours(1);
+    let response = get("metrics", hour, earlier, &[]).await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    let response = get("metrics/graph", hour, earlier, &[("granularity", "Hourly")]).await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn test_metrics_report_unreadable_results() {
+    let fixtures = get_fixtures();
+    let (port, state) = start_server_test(Some(&fixtures)).await;
+    let client = reqwest::Client::new();
+    let base_url = format!("http://localhost:{port}");
+    let check_id = uuid!("44444444-4444-4444-4444-444444444444");
+    let session_cookie = format!(
+        "session_id={}",
+        uuid!("55555555-5555-5555-5555-555555555555")
+    );
+
+    // Every read of raw results now fails
+    state
+        .database
+        .query_unpaged("ALTER TABLE check_results DROP ttfb_micros", &[])
+        .await
+        .unwrap();
+
+    let get = async |path: &str, from: DateTime<Utc>, to: DateTime<Utc>, extra: &[(&str, &str)]| {
+        client
+            .get(format!("{base_url}/checks/{check_id}/{path}"))
+            .query(&[("from", from.to_rfc3339()), ("to", to.to_rfc3339())])
+            .query(extra)
+            .header("Cookie", &session_cookie)
+            .send()
+            .await
+            .unwrap()
+    };
+    let hour = Utc::now()
+        .duration_trunc(chrono::Duration::hours(1))
+        .unwrap();
+    let from = hour - chrono::Duration::hours(2);
+
+    let response = get("metrics", from, hour, &[]).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let metrics: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(metrics["partial"], true);
+    assert!(!metrics["errors"].as_array().unwrap().is_empty());
+
+    // Points that couldn't be read are kept, flagged with their errors
+    let response = get("metrics/graph", from, hour, &[("granularity", "Hourly")]).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let graph: serde_json::Value = response.json().await.unwrap();
+    let points = graph["dates"].as_array().unwrap();
+    assert_eq!(points.len(), 2);
+    for point in points {
+        assert_eq!(point["partial"], true);
+        assert!(!point["errors"].as_array().unwrap().is_empty());
+    }
+
+    let response = client
+        .get(format!("{base_url}/checks/{check_id}/metrics/burn-rate"))
+        .query(&[("slo_percent", "99.9")])
+        .header("Cookie", &session_cookie)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let rates: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(rates["partial"], true);
+    assert!(!rates["errors"].as_array().unwrap().is_empty());
+    assert!(rates["long_burn_rate"].is_null());
+}
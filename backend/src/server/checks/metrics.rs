@@ -1,22 +1,26 @@
 use crate::{
+    eager_env,
     queries::{
+        annotations::{CheckAnnotation, list_annotations},
         authorization::get_user_access_to_check,
         check_results::{
-            GraphGranularity, MetricsResponse, MetricsResponseDate, get_check_metrics,
-            get_check_metrics_graph, is_rounded_to_granularity,
+            BurnRates, GraphGranularity, MetricsResponse, MetricsResponseDate, MetricsSummary,
+            OverallUptime, ResponseTimeUnit, UptimeWeighting, get_check_burn_rates,
+            get_check_metrics, get_check_metrics_graph, is_rounded_to_granularity,
         },
+        checks::get_check_by_id,
     },
     regions::Region,
     server::{AppState, auth::AuthenticatedUser},
 };
 use actix_web::{
     Error,
-    error::{ErrorBadRequest, ErrorForbidden, ErrorInternalServerError},
+    error::{ErrorBadRequest, ErrorForbidden, ErrorInternalServerError, ErrorNotFound},
     get,
     web::{Data, Json, Path, Query},
 };
-use chrono::{DateTime, Utc};
-use serde::Deserialize;
+use chrono::{DateTime, Duration, DurationRound, Utc};
+use serde::{Deserialize, Serialize};
 use strum::IntoEnumIterator;
 use utoipa::ToSchema;
 use uuid::Uuid;
@@ -29,6 +33,29 @@ pub struct MetricsQuery {
     pub to: DateTime<Utc>,
     /// Comma-separated list of regions (optional, defaults to all)
     pub regions: Option<String>,
+    /// Weighting of `uptime_percent`, both are always returned separately too
+    #[serde(default)]
+    pub uptime_weighting: UptimeWeighting,
+    /// Unit of the response times, echoed as `response_time_unit`
+    #[serde(default)]
+    pub unit: ResponseTimeUnit,
+}
+
+impl MetricsQuery {
+    /// Applies the presentation options of the query to a computed summary
+    fn present(&self, summary: &mut MetricsSummary) {
+        summary.select_uptime_weighting(self.uptime_weighting);
+        summary.convert_response_times(self.unit);
+    }
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct MetricsSummaryQuery {
+    #[serde(flatten)]
+    pub query: MetricsQuery,
+    /// How the overall uptimes are derived from the regional ones, pooled by default
+    #[serde(default)]
+    pub overall_uptime: OverallUptime,
 }
 
 const CHECK_RESULTS_MAX_DAYS: u32 = 90;
@@ -39,8 +66,11 @@ const CHECK_RESULTS_MAX_DAYS: u32 = 90;
     params(
         ("check_id" = Uuid, Path, description = "Check ID"),
         ("from" = DateTime<Utc>, Query, description = "Start timestamp (ISO 8601)"),
-        ("to" = DateTime<Utc>, Query, description = "End timestamp (ISO 8601, exclusive)"),
+        ("to" = DateTime<Utc>, Query, description = "End timestamp (ISO 8601, exclusive). Equal to `from` for an empty window, whose metrics have `has_data` unset"),
         ("regions" = Option<String>, Query, description = "Comma-separated list of regions to filter by"),
+        ("uptime_weighting" = Option<UptimeWeighting>, Query, description = "Weighting of `uptime_percent`, defaults to `Time`"),
+        ("unit" = Option<ResponseTimeUnit>, Query, description = "Unit of the response times, defaults to `micros`"),
+        ("overall_uptime" = Option<OverallUptime>, Query, description = "How the overall uptimes are derived: from all results (`Pooled`, the default), or as the mean (`RegionAverage`) or the lowest (`WorstRegion`) of the regional ones"),
     ),
     responses(
         (status = 200, description = "Metrics retrieved successfully", body = MetricsResponse),
@@ -59,7 +89,7 @@ const CHECK_RESULTS_MAX_DAYS: u32 = 90;
 #[get("/{check_id}/metrics")]
 pub async fn get_check_metrics_endpoint(
     check_id: Path<Uuid>,
-    query: Query<MetricsQuery>,
+    query: Query<MetricsSummaryQuery>,
     app_state: Data<AppState>,
     auth: AuthenticatedUser,
 ) -> Result<Json<MetricsResponse>, Error> {
@@ -72,13 +102,13 @@ pub async fn get_check_metrics_endpoint(
         }
     };
 
-    // Validate from < to
-    if query.from >= query.to {
-        return Err(ErrorBadRequest("'from' must be before 'to'"));
+    // An empty window (from == to) is valid and has no results
+    if query.query.from > query.query.to {
+        return Err(ErrorBadRequest("'from' must not be after 'to'"));
     }
 
     // Validate time range doesn't exceed max days
-    let duration = query.to - query.from;
+    let duration = query.query.to - query.query.from;
     if duration.num_days() > CHECK_RESULTS_MAX_DAYS.into() {
         return Err(ErrorBadRequest(format!(
             "Time range cannot exceed {} days",
@@ -86,7 +116,7 @@ pub async fn get_check_metrics_endpoint(
         )));
     }
 
-    let regions = parse_regions(query.regions.as_ref()).map_err(ErrorBadRequest)?;
+    let regions = parse_regions(query.query.regions.as_ref()).map_err(ErrorBadRequest)?;
 
     // Check user access
     let access = get_user_access_to_check(&app_state.database, user_id, check_id)
@@ -98,17 +128,34 @@ pub async fn get_check_metrics_endpoint(
         return Err(ErrorForbidden("No permission to view this check"));
     }
 
+    let check = get_check_by_id(&app_state.database, check_id)
+        .await
+        .map_err(ErrorInternalServerError)?
+        .ok_or_else(|| ErrorNotFound("Check not found"))?;
+
     // Get metrics
-    let metrics = get_check_metrics(
-        &app_state.database,
+    let mut metrics = get_check_metrics(
+        app_state.metrics_database(),
         check_id,
         &regions,
-        query.from,
-        query.to,
+        query.query.from,
+        query.query.to,
+        check
+            .data
+            .degraded_response_time_millis
+            .map(|millis| Duration::milliseconds(millis.into())),
+        check.data.require_agreeing_regions.unwrap_or(1) as u32,
     )
     .await
     .map_err(ErrorInternalServerError)?;
 
+    metrics.select_overall_uptime(query.overall_uptime);
+    query.query.present(&mut metrics.overall);
+    for region_metrics in &mut metrics.by_region {
+        query.query.present(&mut region_metrics.metrics);
+    }
+    metrics.metadata = check.data.metadata;
+
     Ok(Json(metrics))
 }
 
@@ -117,20 +164,35 @@ pub struct MetricsGraphQuery {
     #[serde(flatten)]
     pub query: MetricsQuery,
     pub granularity: GraphGranularity,
+    /// Switch to daily points, rather than failing, when the window is too long for hourly ones
+    #[serde(default)]
+    pub coarsen: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct MetricsGraph {
+    /// Points of the graph, sorted by date. Dates without results have no point
+    pub dates: Vec<MetricsResponseDate>,
+    /// Annotations of the check overlapping the window, ordered by start, to be placed by their
+    /// own `starts_at` and `ends_at` rather than by point
+    pub annotations: Vec<CheckAnnotation>,
 }
 
 #[utoipa::path(
     summary = "Get check metrics graph",
-    description = "Get time-series metrics data for a check with specified granularity",
+    description = "Get time-series metrics data for a check with specified granularity, along with the annotations of the check overlapping the window",
     params(
         ("check_id" = Uuid, Path, description = "Check ID"),
         ("from" = DateTime<Utc>, Query, description = "Start timestamp, included (ISO 8601, must be rounded to granularity)"),
-        ("to" = DateTime<Utc>, Query, description = "End timestamp, excluded (ISO 8601, exclusive, must be rounded to granularity)"),
+        ("to" = DateTime<Utc>, Query, description = "End timestamp, excluded (ISO 8601, exclusive, must be rounded to granularity). Future values are clamped to the end of the current period. Equal to `from` for an empty window, without points"),
         ("regions" = Option<String>, Query, description = "Comma-separated list of regions to filter by"),
         ("granularity" = GraphGranularity, Query, description = "Time granularity for data points"),
+        ("coarsen" = Option<bool>, Query, description = "Return daily points, over the window extended to whole days, when it has too many hourly ones instead of failing, defaults to false"),
+        ("uptime_weighting" = Option<UptimeWeighting>, Query, description = "Weighting of `uptime_percent`, defaults to `Time`"),
+        ("unit" = Option<ResponseTimeUnit>, Query, description = "Unit of the response times, defaults to `micros`"),
     ),
     responses(
-        (status = 200, description = "Metrics graph data retrieved successfully", body = Vec<MetricsResponseDate>),
+        (status = 200, description = "Metrics graph data retrieved successfully", body = MetricsGraph),
         (status = 400, description = "Invalid query parameters"),
         (status = 403, description = "Forbidden - no access to check"),
         (status = 404, description = "Check not found"),
@@ -149,9 +211,10 @@ pub async fn get_check_metrics_graph_endpoint(
     query: Query<MetricsGraphQuery>,
     app_state: Data<AppState>,
     auth: AuthenticatedUser,
-) -> Result<Json<Vec<MetricsResponseDate>>, Error> {
-    if query.query.from >= query.query.to {
-        return Err(ErrorBadRequest("'from' must be before 'to'"));
+) -> Result<Json<MetricsGraph>, Error> {
+    // An empty window (from == to) is valid and has no points
+    if query.query.from > query.query.to {
+        return Err(ErrorBadRequest("'from' must not be after 'to'"));
     }
 
     if !is_rounded_to_granularity(query.query.from, query.granularity) {
@@ -174,16 +237,193 @@ pub async fn get_check_metrics_graph_endpoint(
         }
     };
 
-    // Validate time range doesn't exceed max days
-    let duration = query.query.to - query.query.from;
-    if duration.num_days() > CHECK_RESULTS_MAX_DAYS.into() {
+    let (from, to, granularity) = resolve_graph_window(
+        query.query.from,
+        query.query.to,
+        query.granularity,
+        query.coarsen,
+        *eager_env::METRICS_MAX_GRAPH_POINTS,
+    )
+    .map_err(ErrorBadRequest)?;
+
+    let regions = parse_regions(query.query.regions.as_ref()).map_err(ErrorBadRequest)?;
+
+    // Check user access
+    let access = get_user_access_to_check(&app_state.database, user_id, check_id)
+        .await
+        .map_err(ErrorInternalServerError)?
+        .ok_or_else(|| ErrorForbidden("No access to this check"))?;
+
+    if !access.can_see {
+        return Err(ErrorForbidden("No permission to view this check"));
+    }
+
+    // Get metrics
+    let mut dates = get_check_metrics_graph(
+        &app_state.database,
+        app_state.metrics_database(),
+        check_id,
+        &regions,
+        from,
+        to,
+        granularity,
+    )
+    .await
+    .map_err(ErrorInternalServerError)?;
+
+    for region_metrics in dates
+        .iter_mut()
+        .flat_map(|date| date.by_region.values_mut())
+    {
+        query.query.present(region_metrics);
+    }
+
+    let annotations = list_annotations(&app_state.database, check_id, from, to)
+        .await
+        .map_err(ErrorInternalServerError)?;
+
+    Ok(Json(MetricsGraph { dates, annotations }))
+}
+
+/// Checks the graph window doesn't exceed the limit of its granularity, nor `max_points` points
+fn validate_graph_window(
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+    granularity: GraphGranularity,
+    max_points: i64,
+) -> Result<(), String> {
+    let max_days = granularity.max_window_days();
+
+    if to - from > Duration::days(max_days) {
+        return Err(format!(
+            "Time range cannot exceed {max_days} days for {granularity:?} granularity"
+        ));
+    }
+
+    let points = (to - from).num_seconds() / granularity.step().num_seconds();
+    if points > max_points {
+        return Err(format!(
+            "Time range has {points} points, more than the {max_points} allowed"
+        ));
+    }
+
+    Ok(())
+}
+
+/// Validates the graph window, falling back to daily points when it's too long for hourly ones
+/// and `coarsen` is set. Returns the window to compute, extended to whole days when coarsened,
+/// and its granularity.
+fn resolve_graph_window(
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+    granularity: GraphGranularity,
+    coarsen: bool,
+    max_points: i64,
+) -> Result<(DateTime<Utc>, DateTime<Utc>, GraphGranularity), String> {
+    let error = match validate_graph_window(from, to, granularity, max_points) {
+        Ok(()) => return Ok((from, to, granularity)),
+        Err(error) => error,
+    };
+    if granularity != GraphGranularity::Hourly {
+        return Err(error);
+    }
+    if !coarsen {
+        return Err(format!(
+            "{error}, use Daily granularity or set 'coarsen' to get daily points"
+        ));
+    }
+
+    let day = GraphGranularity::Daily.step();
+    let from = from.duration_trunc(day).expect("representable date");
+    let to = to.duration_round_up(day).expect("representable date");
+    validate_graph_window(from, to, GraphGranularity::Daily, max_points)?;
+    Ok((from, to, GraphGranularity::Daily))
+}
+
+fn default_short_window_minutes() -> u32 {
+    60
+}
+
+fn default_long_window_minutes() -> u32 {
+    6 * 60
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct BurnRateQuery {
+    /// SLO target in percent, e.g. `99.9`
+    pub slo_percent: f64,
+    #[serde(default = "default_short_window_minutes")]
+    pub short_window_minutes: u32,
+    #[serde(default = "default_long_window_minutes")]
+    pub long_window_minutes: u32,
+    /// Comma-separated list of regions (optional, defaults to all)
+    pub regions: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct BurnRateResponse {
+    #[serde(flatten)]
+    pub rates: BurnRates,
+    pub slo_percent: f64,
+    pub short_window_minutes: u32,
+    pub long_window_minutes: u32,
+}
+
+#[utoipa::path(
+    summary = "Get check SLO burn rates",
+    description = "Get the error-budget burn rates for an SLO target over a short and a long window ending now, for multi-window burn-rate alerting",
+    params(
+        ("check_id" = Uuid, Path, description = "Check ID"),
+        ("slo_percent" = f64, Query, description = "SLO target in percent, e.g. 99.9"),
+        ("short_window_minutes" = Option<u32>, Query, description = "Short window, defaults to 60 minutes"),
+        ("long_window_minutes" = Option<u32>, Query, description = "Long window, defaults to 360 minutes"),
+        ("regions" = Option<String>, Query, description = "Comma-separated list of regions to filter by"),
+    ),
+    responses(
+        (status = 200, description = "Burn rates retrieved successfully", body = BurnRateResponse),
+        (status = 400, description = "Invalid query parameters"),
+        (status = 403, description = "Forbidden - no access to check"),
+        (status = 500, description = "Internal server error")
+    ),
+    security(
+        ("cookie_auth" = []),
+        ("bearer_auth" = [])
+    ),
+    tags = ["checks"],
+    operation_id = "getCheckBurnRates"
+)]
+#[get("/{check_id}/metrics/burn-rate")]
+pub async fn get_check_burn_rates_endpoint(
+    check_id: Path<Uuid>,
+    query: Query<BurnRateQuery>,
+    app_state: Data<AppState>,
+    auth: AuthenticatedUser,
+) -> Result<Json<BurnRateResponse>, Error> {
+    let check_id = check_id.into_inner();
+    let user_id = match auth {
+        AuthenticatedUser::User(session) => session.user_id,
+        AuthenticatedUser::Api(_) => {
+            // TODO: Check API key permissions
+            todo!("API key check metrics not yet implemented")
+        }
+    };
+
+    if !(query.slo_percent > 0.0 && query.slo_percent < 100.0) {
+        return Err(ErrorBadRequest("'slo_percent' must be between 0 and 100"));
+    }
+    if query.short_window_minutes == 0 || query.short_window_minutes >= query.long_window_minutes {
+        return Err(ErrorBadRequest(
+            "'short_window_minutes' must be positive and shorter than the long window",
+        ));
+    }
+    if query.long_window_minutes > CHECK_RESULTS_MAX_DAYS * 24 * 60 {
         return Err(ErrorBadRequest(format!(
-            "Time range cannot exceed {} days",
+            "Windows cannot exceed {} days",
             CHECK_RESULTS_MAX_DAYS
         )));
     }
 
-    let regions = parse_regions(query.query.regions.as_ref()).map_err(ErrorBadRequest)?;
+    let regions = parse_regions(query.regions.as_ref()).map_err(ErrorBadRequest)?;
 
     // Check user access
     let access = get_user_access_to_check(&app_state.database, user_id, check_id)
@@ -195,19 +435,24 @@ pub async fn get_check_metrics_graph_endpoint(
         return Err(ErrorForbidden("No permission to view this check"));
     }
 
-    // Get metrics
-    let metrics = get_check_metrics_graph(
-        &app_state.database,
+    let rates = get_check_burn_rates(
+        app_state.metrics_database(),
         check_id,
         &regions,
-        query.query.from,
-        query.query.to,
-        query.granularity,
+        Utc::now(),
+        Duration::minutes(query.short_window_minutes.into()),
+        Duration::minutes(query.long_window_minutes.into()),
+        query.slo_percent,
     )
     .await
     .map_err(ErrorInternalServerError)?;
 
-    Ok(Json(metrics))
+    Ok(Json(BurnRateResponse {
+        rates,
+        slo_percent: query.slo_percent,
+        short_window_minutes: query.short_window_minutes,
+        long_window_minutes: query.long_window_minutes,
+    }))
 }
 
 fn parse_regions(regions_str: Option<&String>) -> Result<Vec<Region>, &'static str> {
@@ -226,3 +471,123 @@ fn parse_regions(regions_str: Option<&String>) -> Result<Vec<Region>, &'static s
         None => Ok(Region::iter().collect()),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_millis_unit() {
+        let query = Query::<MetricsQuery>::from_query(
+            "from=2025-01-01T00:00:00Z&to=2025-01-02T00:00:00Z&unit=millis",
+        )
+        .unwrap();
+        assert_eq!(query.unit, ResponseTimeUnit::Millis);
+
+        let mut summary = MetricsSummary::example();
+        query.present(&mut summary);
+
+        assert_eq!(summary.response_time_unit, ResponseTimeUnit::Millis);
+        assert_eq!(summary.avg_response_time_micros, 105);
+        assert_eq!(summary.min_response_time_micros, 70);
+        assert_eq!(summary.max_response_time_micros, 250);
+        // Rounded to the nearest millisecond
+        assert_eq!(summary.p50_response_time_micros, Some(100));
+        assert_eq!(summary.p95_response_time_micros, Some(201));
+        assert_eq!(summary.p99_response_time_micros, Some(240));
+
+        let json = serde_json::to_value(&summary).unwrap();
+        assert_eq!(json["response_time_unit"], "millis");
+        assert_eq!(json["avg_response_time_micros"], 105);
+
+        // Micros by default, left untouched
+        let query =
+            Query::<MetricsQuery>::from_query("from=2025-01-01T00:00:00Z&to=2025-01-02T00:00:00Z")
+                .unwrap();
+        let mut summary = MetricsSummary::example();
+        query.present(&mut summary);
+        assert_eq!(summary.response_time_unit, ResponseTimeUnit::Micros);
+        assert_eq!(summary.p95_response_time_micros, Some(200_500));
+    }
+
+    #[test]
+    fn test_validate_graph_window() {
+        let from = "2025-01-01T00:00:00Z".parse::<DateTime<Utc>>().unwrap();
+
+        for granularity in [GraphGranularity::Hourly, GraphGranularity::Daily] {
+            let max = Duration::days(granularity.max_window_days());
+
+            assert!(validate_graph_window(from, from + max, granularity, i64::MAX).is_ok());
+
+            let error =
+                validate_graph_window(from, from + max + Duration::hours(1), granularity, i64::MAX)
+                    .unwrap_err();
+            assert!(error.contains(&granularity.max_window_days().to_string()));
+        }
+
+        // Too many points, within the limit in days
+        assert!(
+            validate_graph_window(
+                from,
+                from + Duration::hours(48),
+                GraphGranularity::Hourly,
+                48
+            )
+            .is_ok()
+        );
+        let error = validate_graph_window(
+            from,
+            from + Duration::hours(49),
+            GraphGranularity::Hourly,
+            48,
+        )
+        .unwrap_err();
+        assert!(error.contains("49 points"));
+    }
+
+    #[test]
+    fn test_resolve_graph_window() {
+        let from = "2025-01-01T06:00:00Z".parse::<DateTime<Utc>>().unwrap();
+        let to = from + Duration::days(3);
+
+        // Fits, left untouched
+        assert_eq!(
+            resolve_graph_window(from, to, GraphGranularity::Hourly, true, 100),
+            Ok((from, to, GraphGranularity::Hourly))
+        );
+
+        // Too many hourly points
+        let error =
+            resolve_graph_window(from, to, GraphGranularity::Hourly, false, 48).unwrap_err();
+        assert!(error.contains("72 points"));
+        assert!(error.contains("coarsen"));
+
+        // Daily points over whole days instead
+        let day = |date: &str| {
+            format!("{date}T00:00:00Z")
+                .parse::<DateTime<Utc>>()
+                .unwrap()
+        };
+        assert_eq!(
+            resolve_graph_window(from, to, GraphGranularity::Hourly, true, 48),
+            Ok((
+                day("2025-01-01"),
+                day("2025-01-05"),
+                GraphGranularity::Daily
+            ))
+        );
+
+        // Still too many once daily
+        assert!(resolve_graph_window(from, to, GraphGranularity::Hourly, true, 3).is_err());
+        assert!(
+            resolve_graph_window(
+                day("2025-01-01"),
+                day("2025-01-05"),
+                GraphGranularity::Daily,
+                true,
+                3
+            )
+            .is_err()
+        );
+    }
+}
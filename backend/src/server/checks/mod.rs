@@ -1,30 +1,44 @@
+pub mod alert_destinations;
+pub mod annotations;
+pub mod badge;
 pub mod metrics;
+pub mod ping;
+pub mod transfer;
 
-use std::sync::Arc;
+use std::{collections::BTreeSet, net::IpAddr};
 
 use crate::{
     collab::{
         get_bucket_for_check,
-        heartbeat::HeartbeatManager,
         internode::{MessageWithFilters, messages::InterNodeMessage, standard_broadcast},
     },
+    eager_env,
     queries::{
         authorization::{
             CheckAccess, get_user_access_to_check, get_user_checks, grant_check_access,
         },
-        checks::{Check, create_check, delete_check, get_check_by_id, update_check},
+        check_results::{CheckResultRow, get_recent_check_results},
+        checks::{
+            Check, CheckData, create_check, delete_check, get_check_by_id, get_checks_by_ids,
+            update_check,
+        },
         users::get_user_by_id,
     },
     server::{AppState, auth::AuthenticatedUser},
+    worker::{BodyRegex, CheckKind, IpVersionPreference, Method, is_safe_ip},
 };
 use actix_web::{
     Error, HttpResponse, delete,
-    error::{ErrorForbidden, ErrorInternalServerError, ErrorNotFound},
+    error::{
+        ErrorBadRequest, ErrorConflict, ErrorForbidden, ErrorInternalServerError, ErrorNotFound,
+    },
     get, patch, post,
-    web::{Data, Json, Path},
+    web::{Data, Json, Path, Query},
 };
 use log::error;
 use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use url::{Host, Url};
 use utoipa::ToSchema;
 use utoipa_actix_web::{scope, service_config::ServiceConfig};
 use uuid::Uuid;
@@ -38,15 +52,31 @@ pub fn configure_routes(config: &mut ServiceConfig) {
             .service(update_check_endpoint)
             .service(delete_check_endpoint)
             .service(metrics::get_check_metrics_endpoint)
-            .service(metrics::get_check_metrics_graph_endpoint),
+            .service(metrics::get_check_metrics_graph_endpoint)
+            .service(metrics::get_check_burn_rates_endpoint)
+            .service(annotations::create_annotation_endpoint)
+            .service(annotations::list_annotations_endpoint)
+            .service(alert_destinations::get_alert_destinations_endpoint)
+            .service(badge::rotate_read_token_endpoint)
+            .service(badge::get_uptime_badge_endpoint)
+            .service(ping::rotate_ping_token_endpoint)
+            .service(ping::ping_endpoint)
+            .service(transfer::transfer_check_endpoint),
     );
 }
 
-fn broadcast_check_mutation(heartbeat_manager: Arc<HeartbeatManager>, check_id: Uuid) {
+pub(crate) fn broadcast_check_mutation(app_state: &AppState, check_id: Uuid) {
+    app_state
+        .recent_mutations
+        .record(check_id, app_state.clock.now());
+
+    let heartbeat_manager = app_state.heartbeat_manager.clone();
+    let clock = app_state.clock.clone();
     tokio::spawn(async move {
         let bucket = get_bucket_for_check(check_id).1 as u32;
         let result = standard_broadcast(
-            &heartbeat_manager,
+            &*heartbeat_manager,
+            &*clock,
             vec![MessageWithFilters {
                 message: InterNodeMessage::ServiceCheckMutation { check_id },
                 filter_bucket: Some(bucket),
@@ -60,21 +90,426 @@ fn broadcast_check_mutation(heartbeat_manager: Arc<HeartbeatManager>, check_id:
     });
 }
 
+/// Applies a JSON merge patch (RFC 7396) to `target`: objects are merged recursively,
+/// `null` removes a field and any other value replaces it.
+fn merge_patch(target: &mut Value, patch: Value) {
+    let Value::Object(patch) = patch else {
+        *target = patch;
+        return;
+    };
+
+    if !target.is_object() {
+        *target = Value::Object(Map::new());
+    }
+    let Value::Object(target) = target else {
+        unreachable!("target was just made an object");
+    };
+
+    for (key, value) in patch {
+        if value.is_null() {
+            target.remove(&key);
+        } else {
+            merge_patch(target.entry(key).or_insert(Value::Null), value);
+        }
+    }
+}
+
+/// Most entries in the `metadata` of a check
+const MAX_METADATA_ENTRIES: usize = 32;
+/// Largest total size of the keys and values in the `metadata` of a check
+const MAX_METADATA_BYTES: usize = 4096;
+/// Longest pause before a retry, also bounding the stored value when retries are off
+const MAX_RETRY_DELAY_MS: u32 = 60_000;
+/// Longest a resolved address can be reused
+const MAX_DNS_CACHE_TTL_SECONDS: i32 = 3600;
+
+/// Rejects configurations the worker could not run
+fn validate_check_data(data: &CheckData) -> Result<(), Error> {
+    let max_timeout = *eager_env::MAX_PROBE_TIMEOUT_SECONDS;
+    if !(1..=max_timeout as i64).contains(&(data.timeout_seconds as i64)) {
+        return Err(ErrorBadRequest(format!(
+            "timeout_seconds must be between 1 and {max_timeout}"
+        )));
+    }
+
+    if let Some(degraded) = data.degraded_response_time_millis
+        && !(1..data.timeout_seconds.saturating_mul(1000)).contains(&degraded)
+    {
+        return Err(ErrorBadRequest(
+            "degraded_response_time_millis must be positive and below the timeout",
+        ));
+    }
+
+    if let Some(connect_timeout) = data.connect_timeout_millis
+        && !(1..data.timeout_seconds.saturating_mul(1000)).contains(&connect_timeout)
+    {
+        return Err(ErrorBadRequest(
+            "connect_timeout_millis must be positive and below the timeout",
+        ));
+    }
+
+    if let Some(ttl) = data.dns_cache_ttl_seconds
+        && !(1..=MAX_DNS_CACHE_TTL_SECONDS).contains(&ttl)
+    {
+        return Err(ErrorBadRequest(format!(
+            "dns_cache_ttl_seconds must be between 1 and {MAX_DNS_CACHE_TTL_SECONDS}"
+        )));
+    }
+
+    if data.retry_delay_ms > MAX_RETRY_DELAY_MS {
+        return Err(ErrorBadRequest(format!(
+            "retry_delay_ms must be at most {MAX_RETRY_DELAY_MS}"
+        )));
+    }
+
+    // Every attempt must fit before the next run of the check
+    let retries = i64::from(data.retries);
+    let worst_case_millis = (retries + 1) * i64::from(data.timeout_seconds) * 1000
+        + retries * i64::from(data.retry_delay_ms);
+    if retries > 0 && worst_case_millis > i64::from(data.check_frequency_seconds) * 1000 {
+        return Err(ErrorBadRequest(
+            "retries with their timeouts and retry_delay_ms must fit within the check frequency",
+        ));
+    }
+
+    if data.tags.keys().any(|tag| tag.trim().is_empty()) {
+        return Err(ErrorBadRequest("Tag names cannot be empty"));
+    }
+
+    let metadata_bytes: usize = data.metadata.iter().map(|(k, v)| k.len() + v.len()).sum();
+    if data.metadata.len() > MAX_METADATA_ENTRIES || metadata_bytes > MAX_METADATA_BYTES {
+        return Err(ErrorBadRequest(format!(
+            "metadata is limited to {MAX_METADATA_ENTRIES} entries and {MAX_METADATA_BYTES} bytes"
+        )));
+    }
+
+    data.expected_status_codes
+        .validate()
+        .map_err(ErrorBadRequest)?;
+
+    // An empty `any` would never pass
+    if data
+        .assertions
+        .as_ref()
+        .is_some_and(|assertions| assertions.assertions.is_empty())
+    {
+        return Err(ErrorBadRequest("assertions must not be empty"));
+    }
+
+    let has_body = |body: &Option<String>| body.as_ref().is_some_and(|body| !body.is_empty());
+    if has_body(&data.request_body) && !data.http_method.allows_body() {
+        return Err(ErrorBadRequest(format!(
+            "request_body is not allowed for {:?} checks",
+            data.http_method
+        )));
+    }
+    if let Some(index) = data
+        .steps
+        .iter()
+        .position(|step| has_body(&step.request_body) && !step.http_method.allows_body())
+    {
+        return Err(ErrorBadRequest(format!(
+            "request_body is not allowed for the {:?} method of step {index}",
+            data.steps[index].http_method
+        )));
+    }
+
+    if data.kind == CheckKind::Steps {
+        if data.steps.is_empty() {
+            return Err(ErrorBadRequest("Steps checks need at least one step"));
+        }
+        // URLs referencing extracted values are validated by the probe once filled in
+        for (index, step) in data.steps.iter().enumerate() {
+            if !step.url.contains("{{") {
+                validate_target_url(
+                    &step.url,
+                    &format!("URL of step {index}"),
+                    data.allow_private_targets,
+                )?;
+            }
+        }
+    }
+
+    if let Some(pattern) = &data.body_regex {
+        pattern
+            .parse::<BodyRegex>()
+            .map_err(|e| ErrorBadRequest(format!("Invalid body_regex: {e}")))?;
+    }
+
+    for fallback_url in &data.fallback_urls {
+        validate_target_url(fallback_url, "fallback URL", data.allow_private_targets)?;
+    }
+
+    Ok(())
+}
+
+/// Rejects URLs the probes couldn't parse, and those pointing at private addresses unless
+/// `allow_private_targets` is set. Host names are resolved and checked on every probe.
+fn validate_target_url(
+    url: &str,
+    description: &str,
+    allow_private_targets: bool,
+) -> Result<(), Error> {
+    let parsed = Url::parse(url)
+        .ok()
+        .filter(|parsed| ["http", "https"].contains(&parsed.scheme()))
+        .ok_or_else(|| {
+            ErrorBadRequest(format!(
+                "Invalid {description} '{url}', expected http or https"
+            ))
+        })?;
+
+    let ip = match parsed.host() {
+        Some(Host::Ipv4(ip)) => Some(IpAddr::V4(ip)),
+        Some(Host::Ipv6(ip)) => Some(IpAddr::V6(ip)),
+        Some(Host::Domain(domain)) => domain
+            .eq_ignore_ascii_case("localhost")
+            .then_some(IpAddr::from([127, 0, 0, 1])),
+        None => {
+            return Err(ErrorBadRequest(format!(
+                "The {description} '{url}' has no host"
+            )));
+        }
+    };
+    if let Some(ip) = ip
+        && !is_safe_ip(&ip, allow_private_targets)
+    {
+        return Err(ErrorBadRequest(format!(
+            "The {description} '{url}' points to a private address, set allow_private_targets to probe it"
+        )));
+    }
+
+    Ok(())
+}
+
+/// Methods of the requests a check sends, none for passive checks
+fn probe_methods(check: &Check) -> Vec<Method> {
+    if check.data.kind == CheckKind::Passive {
+        return Vec::new();
+    }
+
+    std::iter::once(check.data.http_method)
+        .chain(check.data.steps.iter().map(|step| step.http_method))
+        .collect()
+}
+
+/// Rejects checks whose requests may change the state of the target, e.g. a `DELETE` repeated on
+/// every probe, unless the side effects were acknowledged. Methods the `previous` version of the
+/// check already used were acknowledged then.
+fn ensure_side_effects_acknowledged(
+    check: &Check,
+    previous: Option<&Check>,
+    acknowledged: bool,
+) -> Result<(), Error> {
+    if acknowledged {
+        return Ok(());
+    }
+
+    let previous_methods = previous.map(probe_methods).unwrap_or_default();
+    if let Some(method) = probe_methods(check)
+        .into_iter()
+        .find(|method| method.has_side_effects() && !previous_methods.contains(method))
+    {
+        return Err(ErrorBadRequest(format!(
+            "{method:?} requests are sent on every probe and may change the monitored endpoint, set acknowledge_side_effects to probe with it anyway"
+        )));
+    }
+
+    Ok(())
+}
+
+/// Rejects checks probing private addresses, unless the server allows them. Checks already
+/// allowed to keep the setting, so that they can still be edited.
+fn ensure_private_targets_allowed(
+    app_state: &AppState,
+    check: &Check,
+    previously_allowed: bool,
+) -> Result<(), Error> {
+    if check.data.allow_private_targets && !previously_allowed && !app_state.private_targets_allowed
+    {
+        return Err(ErrorForbidden(
+            "This server doesn't allow checks probing private addresses",
+        ));
+    }
+
+    Ok(())
+}
+
+/// Rejects checks running in more than `max_regions_per_check` distinct regions, or requiring
+/// more agreeing regions than they run in.
+fn validate_regions(app_state: &AppState, check: &Check) -> Result<(), Error> {
+    let max_regions = app_state.max_regions_per_check;
+    let distinct_regions = check.regions.iter().collect::<BTreeSet<_>>().len();
+    if distinct_regions > max_regions {
+        return Err(ErrorBadRequest(format!(
+            "A check can run in at most {max_regions} regions"
+        )));
+    }
+
+    if let Some(required) = check.data.require_agreeing_regions
+        && !(1..=distinct_regions as i64).contains(&required.into())
+    {
+        return Err(ErrorBadRequest(format!(
+            "require_agreeing_regions must be between 1 and the {distinct_regions} regions of the check"
+        )));
+    }
+
+    Ok(())
+}
+
+/// Rejects `check_name` if another check the user has access to is named the same, ignoring case.
+///
+/// `check_id` is the check being renamed, if any, which may keep its own name.
+async fn ensure_unique_check_name(
+    app_state: &AppState,
+    user_id: Uuid,
+    check_name: &str,
+    check_id: Option<Uuid>,
+) -> Result<(), Error> {
+    let check_accesses = get_user_checks(&app_state.database, user_id)
+        .await
+        .map_err(ErrorInternalServerError)?;
+
+    let checks = get_checks_by_ids(
+        &app_state.database,
+        check_accesses
+            .into_iter()
+            .map(|(id, _)| id)
+            .filter(|&id| Some(id) != check_id),
+    )
+    .await
+    .map_err(ErrorInternalServerError)?;
+
+    let check_name = check_name.trim();
+    if checks.values().any(|check| {
+        check
+            .data
+            .check_name
+            .trim()
+            .eq_ignore_ascii_case(check_name)
+    }) {
+        return Err(ErrorConflict(format!(
+            "A check named '{check_name}' already exists"
+        )));
+    }
+
+    Ok(())
+}
+
+/// Warns about the chosen regions of `check` that have no alive worker, so the check won't run
+/// there until one starts. Never fails: the workers are only looked up on a best-effort basis.
+async fn unmonitored_region_warnings(app_state: &AppState, check: &Check) -> Vec<String> {
+    let alive_nodes = match app_state
+        .heartbeat_manager
+        .get_alive_workers_all_regions()
+        .await
+    {
+        Ok(alive_nodes) => alive_nodes,
+        Err(e) => {
+            error!("Failed to fetch alive nodes: {e:?}");
+            return Vec::new();
+        }
+    };
+
+    check
+        .regions
+        .iter()
+        .filter(|region| !alive_nodes.iter().any(|node| node.region == **region))
+        .map(|region| {
+            format!(
+                "Region {} has no alive workers, the check won't run there until one starts",
+                region.to_identifier()
+            )
+        })
+        .collect()
+}
+
+/// Warns about the URLs of `check` that only resolve to address families the nodes can't probe,
+/// per `ip_version_preference`. Never fails: URLs that don't resolve are left to the probes.
+async fn ip_version_warnings(app_state: &AppState, check: &Check) -> Vec<String> {
+    let preference = app_state.ip_version_preference;
+    if preference == IpVersionPreference::Any || check.data.kind != CheckKind::Http {
+        return Vec::new();
+    }
+
+    let mut warnings = Vec::new();
+    for url in std::iter::once(&check.data.url).chain(&check.data.fallback_urls) {
+        let Some((host, port)) = Url::parse(url)
+            .ok()
+            .and_then(|url| Some((url.host_str()?.to_string(), url.port_or_known_default()?)))
+        else {
+            continue;
+        };
+        // Bracketed IPv6 literals resolve without them
+        let host = host.trim_start_matches('[').trim_end_matches(']');
+
+        let Ok(addrs) = tokio::net::lookup_host((host, port)).await else {
+            continue;
+        };
+        let addrs: Vec<_> = addrs.map(|addr| addr.ip()).collect();
+
+        if let Some(ip) = addrs.first()
+            && !addrs.iter().any(|ip| preference.allows(ip))
+        {
+            let family = if ip.is_ipv4() { "IPv4" } else { "IPv6" };
+            warnings.push(format!(
+                "{host} only resolves to {family} addresses, but nodes only probe over {preference}: the check will fail"
+            ));
+        }
+    }
+
+    warnings
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct CreateCheckRequest {
+    #[serde(flatten)]
+    pub check: Check,
+    /// Required to create checks probing with `POST`, `PUT`, `PATCH` or `DELETE`, which may have
+    /// side effects on every probe
+    #[serde(default)]
+    pub acknowledge_side_effects: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct CreatedCheck {
+    #[serde(flatten)]
+    pub check: Check,
+    /// Issues that don't prevent creating the check, e.g. a region with no alive workers or an
+    /// IPv6-only host on IPv4-only nodes
+    pub warnings: Vec<String>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct CheckWithAccess {
     #[serde(flatten)]
     pub check: Check,
     #[serde(flatten)]
     pub access: CheckAccess,
+    /// Most recent results across regions, newest first. Only set when requested with `recent`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub recent_results: Option<Vec<CheckResultRow>>,
+}
+
+/// Most results `GET /checks/{check_id}` returns inline
+const MAX_RECENT_RESULTS: usize = 100;
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct GetCheckQuery {
+    /// Number of recent results to include, at most 100
+    pub recent: Option<usize>,
 }
 
 #[utoipa::path(
     summary = "Create a new check",
-    description = "Creates a new check across multiple regions. The creator automatically gets full access (can_edit and can_see).",
-    request_body = Check,
+    description = "Creates a new check across multiple regions, at most `MAX_REGIONS_PER_CHECK` of them. The creator is recorded as its owner and automatically gets full access (can_edit and can_see). Regions with no alive workers, and hosts only resolving to address families the nodes can't probe, are accepted but reported in `warnings`. Checks probing with `POST`, `PUT`, `PATCH` or `DELETE` are only created with `acknowledge_side_effects`. Checks with `allow_private_targets` are only accepted by servers started with `ALLOW_PRIVATE_TARGETS`.",
+    request_body = CreateCheckRequest,
     responses(
-        (status = 200, description = "Check created successfully", body = Check),
+        (status = 200, description = "Check created successfully", body = CreatedCheck),
+        (status = 400, description = "Invalid check configuration, too many regions, or unacknowledged side effects"),
         (status = 401, description = "Unauthorized - authentication required"),
+        (status = 403, description = "allow_private_targets is set but the server doesn't allow it"),
+        (status = 409, description = "Another check of the user has the same name, when check names must be unique"),
         (status = 500, description = "Internal server error")
     ),
     security(
@@ -86,10 +521,10 @@ pub struct CheckWithAccess {
 )]
 #[post("/")]
 async fn create_check_endpoint(
-    body: Json<Check>,
+    body: Json<CreateCheckRequest>,
     app_state: Data<AppState>,
     auth: AuthenticatedUser,
-) -> Result<Json<Check>, Error> {
+) -> Result<Json<CreatedCheck>, Error> {
     let user_id = match auth {
         AuthenticatedUser::User(session) => session.user_id,
         AuthenticatedUser::Api(_) => {
@@ -98,13 +533,33 @@ async fn create_check_endpoint(
         }
     };
 
+    let CreateCheckRequest {
+        check: body,
+        acknowledge_side_effects,
+    } = body.into_inner();
+
+    validate_check_data(&body.data)?;
+    validate_regions(&app_state, &body)?;
+    ensure_side_effects_acknowledged(&body, None, acknowledge_side_effects)?;
+    ensure_private_targets_allowed(&app_state, &body, false)?;
+
+    if app_state.unique_check_names {
+        ensure_unique_check_name(&app_state, user_id, &body.data.check_name, None).await?;
+    }
+
     // Get user info for username
     let user = get_user_by_id(&app_state.database, user_id)
         .await
         .map_err(ErrorInternalServerError)?
         .ok_or_else(|| ErrorNotFound("User not found"))?;
 
-    let check = create_check(&app_state.database, body.regions.clone(), body.data.clone())
+    let mut data = body.data.clone();
+    data.created_by = Some(user_id);
+    data.created_by_username = Some(user.username.clone());
+    // Only nodes pin their self-checks
+    data.pinned_node = None;
+
+    let check = create_check(&app_state.database, body.regions.clone(), data)
         .await
         .map_err(ErrorInternalServerError)?;
 
@@ -122,14 +577,21 @@ async fn create_check_endpoint(
     .await
     .map_err(ErrorInternalServerError)?;
 
-    broadcast_check_mutation(app_state.heartbeat_manager.clone(), check.check_id);
+    broadcast_check_mutation(&app_state, check.check_id);
 
-    Ok(Json(check))
+    let mut warnings = unmonitored_region_warnings(&app_state, &check).await;
+    warnings.extend(ip_version_warnings(&app_state, &check).await);
+
+    Ok(Json(CreatedCheck { check, warnings }))
 }
 
 #[utoipa::path(
     summary = "Get check by ID",
-    description = "Retrieves a check by its ID. User must have access to view the check.",
+    description = "Retrieves a check by its ID. User must have access to view the check. With `recent`, the most recent raw results of the last day are included, e.g. for a sparkline.",
+    params(
+        ("check_id" = Uuid, Path, description = "Check ID"),
+        ("recent" = Option<usize>, Query, description = "Number of recent results to include, capped at 100"),
+    ),
     responses(
         (status = 200, description = "Check found", body = CheckWithAccess),
         (status = 401, description = "Unauthorized - authentication required"),
@@ -147,6 +609,7 @@ async fn create_check_endpoint(
 #[get("/{check_id}")]
 async fn get_check_endpoint(
     check_id: Path<Uuid>,
+    query: Query<GetCheckQuery>,
     app_state: Data<AppState>,
     auth: AuthenticatedUser,
 ) -> Result<Json<CheckWithAccess>, Error> {
@@ -175,7 +638,26 @@ async fn get_check_endpoint(
         .map_err(ErrorInternalServerError)?
         .ok_or_else(|| ErrorNotFound("Check not found"))?;
 
-    Ok(Json(CheckWithAccess { check, access }))
+    let recent_results = match query.recent {
+        Some(recent) => Some(
+            get_recent_check_results(
+                app_state.metrics_database(),
+                check_id,
+                &check.regions,
+                app_state.clock.now(),
+                recent.min(MAX_RECENT_RESULTS),
+            )
+            .await
+            .map_err(ErrorInternalServerError)?,
+        ),
+        None => None,
+    };
+
+    Ok(Json(CheckWithAccess {
+        check,
+        access,
+        recent_results,
+    }))
 }
 
 #[utoipa::path(
@@ -210,29 +692,40 @@ async fn list_my_checks(
         .await
         .map_err(ErrorInternalServerError)?;
 
-    let mut checks_with_access = Vec::new();
+    let mut checks = get_checks_by_ids(
+        &app_state.database,
+        check_accesses.iter().map(|(check_id, _)| *check_id),
+    )
+    .await
+    .map_err(ErrorInternalServerError)?;
 
-    for (check_id, access) in check_accesses {
-        if let Some(check) = get_check_by_id(&app_state.database, check_id)
-            .await
-            .map_err(ErrorInternalServerError)?
-        {
-            checks_with_access.push(CheckWithAccess { check, access });
-        }
-    }
+    // Access rows of deleted checks are skipped
+    let checks_with_access = check_accesses
+        .into_iter()
+        .filter_map(|(check_id, access)| {
+            let check = checks.remove(&check_id)?;
+            Some(CheckWithAccess {
+                check,
+                access,
+                recent_results: None,
+            })
+        })
+        .collect();
 
     Ok(Json(checks_with_access))
 }
 
 #[utoipa::path(
     summary = "Update check",
-    description = "Updates a check. User must have edit access to the check.",
-    request_body = Check,
+    description = "Updates the given fields of a check, as a JSON merge patch (RFC 7396): omitted fields are kept, `null` clears optional ones and nested objects are merged. User must have edit access to the check. Switching to a method with side effects also requires `acknowledge_side_effects: true` in the body.",
+    request_body(content = Object, description = "Fields of `Check` to change"),
     responses(
         (status = 200, description = "Check updated successfully", body = Check),
+        (status = 400, description = "Invalid check configuration, too many regions, or unacknowledged side effects"),
         (status = 401, description = "Unauthorized - authentication required"),
-        (status = 403, description = "Forbidden - no edit access to check"),
+        (status = 403, description = "Forbidden - no edit access to check, or enabling allow_private_targets on a server not allowing it"),
         (status = 404, description = "Check not found"),
+        (status = 409, description = "Another check of the user has the same name, when check names must be unique"),
         (status = 500, description = "Internal server error")
     ),
     security(
@@ -245,7 +738,7 @@ async fn list_my_checks(
 #[patch("/{check_id}")]
 async fn update_check_endpoint(
     check_id: Path<Uuid>,
-    body: Json<Check>,
+    body: Json<Value>,
     app_state: Data<AppState>,
     auth: AuthenticatedUser,
 ) -> Result<Json<Check>, Error> {
@@ -270,27 +763,73 @@ async fn update_check_endpoint(
     }
 
     // Verify check exists
-    let _existing_check = get_check_by_id(&app_state.database, check_id)
+    let existing_check = get_check_by_id(&app_state.database, check_id)
         .await
         .map_err(ErrorInternalServerError)?
         .ok_or_else(|| ErrorNotFound("Check not found"))?;
 
-    // Use the check from the request but ensure check_id matches
-    let mut check = body.into_inner();
+    let mut patch = body.into_inner();
+    let acknowledge_side_effects = patch
+        .as_object_mut()
+        .and_then(|patch| patch.remove("acknowledge_side_effects"))
+        .is_some_and(|acknowledged| acknowledged == Value::Bool(true));
+    // The former name replaces the stored codes, rather than being merged next to them
+    if let Some(patch) = patch.as_object_mut()
+        && let Some(code) = patch.remove("expected_status_code")
+    {
+        patch.entry("expected_status_codes").or_insert(code);
+    }
+    // Merging drops `null` values, so an explicit one is the only way to tell a cleared password apart
+    let clears_proxy_password = patch.pointer("/proxy/password").is_some_and(Value::is_null);
+
+    let mut merged = serde_json::to_value(&existing_check).map_err(ErrorInternalServerError)?;
+    merge_patch(&mut merged, patch);
+    let mut check: Check = serde_json::from_value(merged)
+        .map_err(|e| ErrorBadRequest(format!("Invalid check: {e}")))?;
     check.check_id = check_id;
 
+    validate_check_data(&check.data)?;
+    validate_regions(&app_state, &check)?;
+    ensure_side_effects_acknowledged(&check, Some(&existing_check), acknowledge_side_effects)?;
+    ensure_private_targets_allowed(
+        &app_state,
+        &check,
+        existing_check.data.allow_private_targets,
+    )?;
+
+    if app_state.unique_check_names {
+        ensure_unique_check_name(&app_state, user_id, &check.data.check_name, Some(check_id))
+            .await?;
+    }
+
+    // Ownership is immutable, and so is the node probing a self-check
+    check.data.created_by = existing_check.data.created_by;
+    check.data.created_by_username = existing_check.data.created_by_username;
+    check.data.pinned_node = existing_check.data.pinned_node.clone();
+
+    // The proxy password is never returned, so keep the stored one unless a new one is supplied
+    // or it is cleared with `null`
+    if let (Some(proxy), Some(existing_proxy)) = (&mut check.data.proxy, existing_check.data.proxy)
+        && proxy.password.is_none()
+        && !clears_proxy_password
+        && proxy.url == existing_proxy.url
+        && proxy.username == existing_proxy.username
+    {
+        proxy.password = existing_proxy.password;
+    }
+
     update_check(&app_state.database, check.clone())
         .await
         .map_err(ErrorInternalServerError)?;
 
-    broadcast_check_mutation(app_state.heartbeat_manager.clone(), check_id);
+    broadcast_check_mutation(&app_state, check_id);
 
     Ok(Json(check))
 }
 
 #[utoipa::path(
     summary = "Delete check",
-    description = "Deletes a check from all regions. User must have edit access to the check.",
+    description = "Deletes a check from all regions, along with its annotations. User must have edit access to the check.",
     responses(
         (status = 200, description = "Check deleted successfully"),
         (status = 401, description = "Unauthorized - authentication required"),
@@ -341,7 +880,7 @@ async fn delete_check_endpoint(
         .await
         .map_err(ErrorInternalServerError)?;
 
-    broadcast_check_mutation(app_state.heartbeat_manager.clone(), check_id);
+    broadcast_check_mutation(&app_state, check_id);
 
     Ok(HttpResponse::Ok().json(serde_json::json!({ "message": "Check deleted successfully" })))
 }
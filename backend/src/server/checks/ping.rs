@@ -0,0 +1,130 @@
+use crate::{
+    queries::{
+        authorization::get_user_access_to_check,
+        checks::get_check_by_id,
+        pings::{check_ping_token, rotate_check_ping_token, set_last_check_ping},
+    },
+    server::{AppState, auth::AuthenticatedUser},
+    worker::CheckKind,
+};
+use actix_web::{
+    Error, HttpResponse,
+    error::{ErrorBadRequest, ErrorForbidden, ErrorInternalServerError, ErrorNotFound},
+    post,
+    web::{Data, Json, Path, Query},
+};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct PingTokenResponse {
+    pub ping_token: Uuid,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct PingQuery {
+    /// Ping token of the check
+    pub token: Uuid,
+}
+
+#[utoipa::path(
+    summary = "Create a check ping token",
+    description = "Creates the token authorizing pings of a passive check. Any previous token is revoked.",
+    params(
+        ("check_id" = Uuid, Path, description = "Check ID"),
+    ),
+    responses(
+        (status = 200, description = "Ping token created", body = PingTokenResponse),
+        (status = 400, description = "Check is not passive"),
+        (status = 403, description = "Forbidden - no permission to edit check"),
+        (status = 404, description = "Check not found"),
+        (status = 500, description = "Internal server error")
+    ),
+    security(
+        ("cookie_auth" = []),
+        ("bearer_auth" = [])
+    ),
+    tags = ["checks"],
+    operation_id = "rotateCheckPingToken"
+)]
+#[post("/{check_id}/ping-token")]
+pub async fn rotate_ping_token_endpoint(
+    check_id: Path<Uuid>,
+    app_state: Data<AppState>,
+    auth: AuthenticatedUser,
+) -> Result<Json<PingTokenResponse>, Error> {
+    let check_id = check_id.into_inner();
+    let user_id = match auth {
+        AuthenticatedUser::User(session) => session.user_id,
+        AuthenticatedUser::Api(_) => {
+            // TODO: Check API key permissions
+            todo!("API key access not yet implemented")
+        }
+    };
+
+    let access = get_user_access_to_check(&app_state.database, user_id, check_id)
+        .await
+        .map_err(ErrorInternalServerError)?
+        .ok_or_else(|| ErrorForbidden("No access to this check"))?;
+
+    if !access.can_edit {
+        return Err(ErrorForbidden("No permission to edit this check"));
+    }
+
+    let check = get_check_by_id(&app_state.database, check_id)
+        .await
+        .map_err(ErrorInternalServerError)?
+        .ok_or_else(|| ErrorNotFound("Check not found"))?;
+
+    if check.data.kind != CheckKind::Passive {
+        return Err(ErrorBadRequest("Only passive checks can be pinged"));
+    }
+
+    let ping_token = rotate_check_ping_token(&app_state.database, check_id)
+        .await
+        .map_err(ErrorInternalServerError)?;
+
+    Ok(Json(PingTokenResponse { ping_token }))
+}
+
+#[utoipa::path(
+    summary = "Ping a passive check",
+    description = "Records that the monitored job is alive, authorized by the check's ping token. The check goes down when no ping arrives within its frequency plus a grace period.",
+    params(
+        ("check_id" = Uuid, Path, description = "Check ID"),
+        ("token" = Uuid, Query, description = "Ping token of the check"),
+    ),
+    responses(
+        (status = 204, description = "Ping recorded"),
+        (status = 403, description = "Forbidden - invalid ping token"),
+        (status = 500, description = "Internal server error")
+    ),
+    tags = ["checks"],
+    operation_id = "pingCheck"
+)]
+#[post("/{check_id}/ping")]
+pub async fn ping_endpoint(
+    check_id: Path<Uuid>,
+    query: Query<PingQuery>,
+    app_state: Data<AppState>,
+) -> Result<HttpResponse, Error> {
+    let check_id = check_id.into_inner();
+    let valid = check_ping_token(&app_state.database, check_id, query.token)
+        .await
+        .map_err(ErrorInternalServerError)?;
+
+    if !valid {
+        return Err(ErrorForbidden("Invalid ping token"));
+    }
+
+    match &app_state.ping_batcher {
+        Some(batcher) => batcher.record(check_id, Utc::now()),
+        None => set_last_check_ping(&app_state.database, check_id, Utc::now())
+            .await
+            .map_err(ErrorInternalServerError)?,
+    }
+
+    Ok(HttpResponse::NoContent().finish())
+}
@@ -0,0 +1,116 @@
+use crate::{
+    queries::{
+        authorization::{
+            CheckAccess, get_user_access_to_check, grant_check_access, revoke_check_access,
+        },
+        checks::{Check, get_check_by_id, update_check},
+        users::get_user_by_username,
+    },
+    server::{AppState, auth::AuthenticatedUser},
+};
+use actix_web::{
+    Error,
+    error::{ErrorForbidden, ErrorInternalServerError, ErrorNotFound},
+    post,
+    web::{Data, Json, Path},
+};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct TransferCheckRequest {
+    /// Username of the new owner
+    pub username: String,
+    /// Also removes all access of the previous owner
+    #[serde(default)]
+    pub revoke_previous_owner: bool,
+}
+
+#[utoipa::path(
+    summary = "Transfer check ownership",
+    description = "Makes another user the owner of a check, granting them full access (can_edit and can_see). The previous owner keeps their access unless `revoke_previous_owner` is set. User must have edit access to the check.",
+    params(
+        ("check_id" = Uuid, Path, description = "Check ID"),
+    ),
+    request_body = TransferCheckRequest,
+    responses(
+        (status = 200, description = "Check transferred", body = Check),
+        (status = 401, description = "Unauthorized - authentication required"),
+        (status = 403, description = "Forbidden - no edit access to check"),
+        (status = 404, description = "Check or user not found"),
+        (status = 500, description = "Internal server error")
+    ),
+    security(
+        ("cookie_auth" = []),
+        ("bearer_auth" = [])
+    ),
+    tags = ["checks"],
+    operation_id = "transferCheck"
+)]
+#[post("/{check_id}/transfer")]
+pub async fn transfer_check_endpoint(
+    check_id: Path<Uuid>,
+    body: Json<TransferCheckRequest>,
+    app_state: Data<AppState>,
+    auth: AuthenticatedUser,
+) -> Result<Json<Check>, Error> {
+    let check_id = check_id.into_inner();
+    let user_id = match auth {
+        AuthenticatedUser::User(session) => session.user_id,
+        AuthenticatedUser::Api(_) => {
+            // TODO: Check API key permissions
+            todo!("API key check transfer not yet implemented")
+        }
+    };
+
+    let access = get_user_access_to_check(&app_state.database, user_id, check_id)
+        .await
+        .map_err(ErrorInternalServerError)?
+        .ok_or_else(|| ErrorForbidden("No access to this check"))?;
+
+    if !access.can_edit {
+        return Err(ErrorForbidden("No edit access to this check"));
+    }
+
+    let mut check = get_check_by_id(&app_state.database, check_id)
+        .await
+        .map_err(ErrorInternalServerError)?
+        .ok_or_else(|| ErrorNotFound("Check not found"))?;
+
+    let target = get_user_by_username(&app_state.database, body.username.trim())
+        .await
+        .map_err(ErrorInternalServerError)?
+        .ok_or_else(|| ErrorNotFound("User not found"))?;
+
+    grant_check_access(
+        &app_state.database,
+        check_id,
+        target.user_id,
+        &target.username,
+        CheckAccess {
+            can_edit: true,
+            can_see: true,
+        },
+    )
+    .await
+    .map_err(ErrorInternalServerError)?;
+
+    let previous_owner = check.data.created_by.replace(target.user_id);
+    check.data.created_by_username = Some(target.username);
+
+    update_check(&app_state.database, check.clone())
+        .await
+        .map_err(ErrorInternalServerError)?;
+
+    if body.revoke_previous_owner
+        && let Some(previous_owner) = previous_owner
+        && previous_owner != target.user_id
+    {
+        revoke_check_access(&app_state.database, check_id, previous_owner)
+            .await
+            .map_err(ErrorInternalServerError)?;
+    }
+
+    Ok(Json(check))
+}
@@ -1,16 +1,117 @@
-use actix_web::{HttpResponse, get};
+use actix_web::{HttpResponse, get, web::Data};
+use log::warn;
+use serde::{Deserialize, Serialize};
 use serde_json::json;
+use utoipa::ToSchema;
 
+use crate::{database, eager_env, server::AppState};
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct DatabaseHealth {
+    /// Whether a query round trip succeeded
+    pub healthy: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct HeartbeatHealth {
+    /// Whether the last heartbeat is recent enough for other nodes to consider this one alive
+    pub healthy: bool,
+    /// Seconds since the last successful heartbeat write, `None` before the first one
+    pub last_write_age_seconds: Option<f64>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct WorkerHealth {
+    /// Whether checks are dispatched on time, see `SCHEDULING_LAG_THRESHOLD_MILLIS`
+    pub healthy: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct HealthComponents {
+    pub database: DatabaseHealth,
+    pub heartbeat: HeartbeatHealth,
+    pub worker: WorkerHealth,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct HealthStatus {
+    /// `ok`, or `scheduling_starved` when that fails the request
+    pub status: String,
+    /// Whether every component is healthy
+    pub healthy: bool,
+    pub components: HealthComponents,
+}
+
+/// Reports the status of each component of the node.
+///
+/// Only fails while check scheduling is starved, when `SCHEDULING_STARVATION_FAILS_HEALTH` is set:
+/// other unhealthy components are reported without failing the request.
 #[utoipa::path(
     responses(
-        (status = 200, description = "Health check")
+        (status = 200, description = "Health check", body = HealthStatus),
+        (status = 503, description = "Checks have been dispatched late for a sustained period", body = HealthStatus)
     ),
     tags = ["health"]
 )]
 #[get("/health")]
-pub async fn health() -> HttpResponse {
+pub async fn health(app_state: Data<AppState>) -> HttpResponse {
+    let database_healthy = match database::ping(&app_state.database).await {
+        Ok(()) => true,
+        Err(e) => {
+            warn!("Health check database ping failed: {e}");
+            false
+        }
+    };
+
+    let heartbeat = &app_state.heartbeat_manager;
+    let components = HealthComponents {
+        database: DatabaseHealth {
+            healthy: database_healthy,
+        },
+        heartbeat: HeartbeatHealth {
+            healthy: heartbeat.is_heartbeat_fresh(),
+            last_write_age_seconds: heartbeat.last_heartbeat_age().map(|age| age.as_secs_f64()),
+        },
+        worker: WorkerHealth {
+            healthy: !app_state.worker_status.is_scheduling_starved(),
+        },
+    };
+    let healthy =
+        components.database.healthy && components.heartbeat.healthy && components.worker.healthy;
+
+    if *eager_env::SCHEDULING_STARVATION_FAILS_HEALTH && !components.worker.healthy {
+        return HttpResponse::ServiceUnavailable().json(HealthStatus {
+            status: "scheduling_starved".to_string(),
+            healthy,
+            components,
+        });
+    }
+
+    HttpResponse::Ok().json(HealthStatus {
+        status: "ok".to_string(),
+        healthy,
+        components,
+    })
+}
+
+/// Fails until a required startup self-test succeeds, e.g. while outbound requests are blocked
+#[utoipa::path(
+    responses(
+        (status = 200, description = "Node is ready"),
+        (status = 503, description = "Node is not ready")
+    ),
+    tags = ["health"]
+)]
+#[get("/ready")]
+pub async fn ready(app_state: Data<AppState>) -> HttpResponse {
+    if !*app_state.ready.borrow() {
+        return HttpResponse::ServiceUnavailable().json(json!({
+            "status": "not_ready"
+        }));
+    }
+
     HttpResponse::Ok().json(json!({
-        "status": "ok"
+        "status": "ready"
     }))
 }
 
@@ -1,24 +1,65 @@
 use actix_web::{
-    HttpRequest, HttpResponse, post,
-    web::{Data, Json},
+    HttpRequest, HttpResponse, get, post,
+    web::{Data, Json, Path, Query},
 };
+use chrono::{DateTime, Utc};
+use futures::{StreamExt, stream};
 use log::error;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use strum::IntoEnumIterator;
+use utoipa::ToSchema;
 use utoipa_actix_web::service_config::ServiceConfig;
+use uuid::Uuid;
 
 use crate::{
-    collab::internode::{BroadcastBody, messages::InterNodeMessage},
+    collab::{
+        RingCoverage, calculate_ring_coverage,
+        heartbeat::{HeartbeatRecord, get_heartbeat_history},
+        internode::{
+            BroadcastBody, MessageWithFilters, messages::InterNodeMessage, standard_broadcast,
+        },
+    },
     eager_env,
+    queries::{
+        check_results::{
+            CheckResultRow, GraphGranularity, MetricsResponseDate, RawResultsNotStored,
+            get_latest_check_result, is_rounded_to_granularity, recompute_cached_check_results,
+        },
+        checks::{Check, list_all_checks, listed_buckets_count},
+        cluster::set_probing_enabled,
+    },
+    regions::Region,
     server::AppState,
 };
 
 pub fn configure_routes(config: &mut ServiceConfig) {
     config.service(internal);
+    config.service(checks_owned);
+    config.service(cluster_status);
+    config.service(set_probing);
+    config.service(list_checks);
+    config.service(recompute_check_aggregates);
+    config.service(check_gauges);
+    config.service(heartbeat_history);
+}
+
+fn is_authorized(req: &HttpRequest) -> bool {
+    let token = req
+        .headers()
+        .get("Authorization")
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "));
+
+    token == Some(&*eager_env::BACKEND_INTERNAL_PASSWORD)
 }
 
 #[utoipa::path(
     responses(
         (status = 200, description = "Internal endpoint success"),
         (status = 401, description = "Unauthorized - invalid or missing password"),
+        (status = 409, description = "Stale or replayed message"),
     ),
     tags = ["internal"],
     security(
@@ -31,23 +72,24 @@ pub async fn internal(
     app_state: Data<AppState>,
     body: Json<BroadcastBody>,
 ) -> HttpResponse {
-    let token = req
-        .headers()
-        .get("Authorization")
-        .and_then(|v| v.to_str().ok())
-        .and_then(|v| v.strip_prefix("Bearer "));
-
-    if token != Some(&*eager_env::BACKEND_INTERNAL_PASSWORD) {
+    if !is_authorized(&req) {
         log::warn!("unauthorized call to internal endpoint");
         return HttpResponse::Unauthorized().body("Invalid or missing internal password");
     }
 
-    let messages = body.into_inner();
+    let body = body.into_inner();
+    if !app_state
+        .replay_guard
+        .accept(body.sent_at, body.nonce, app_state.clock.now())
+    {
+        log::warn!("rejected stale or replayed internal message {}", body.nonce);
+        return HttpResponse::Conflict().body("Stale or replayed message");
+    }
 
     let mut check_ids = Vec::new();
     let mut shutting_process_ids = Vec::new();
 
-    for msg in messages {
+    for msg in body.messages {
         log::info!("Received message: {msg:?}");
 
         match msg {
@@ -57,6 +99,9 @@ pub async fn internal(
             InterNodeMessage::ShuttingDown { process_id } => {
                 shutting_process_ids.push(process_id);
             }
+            InterNodeMessage::SetProbingEnabled { enabled } => {
+                app_state.probing_enabled.send_replace(enabled);
+            }
         }
     }
 
@@ -68,22 +113,543 @@ pub async fn internal(
     HttpResponse::Ok().finish()
 }
 
+/// Lists the check ids this node is currently responsible for executing.
+#[utoipa::path(
+    responses(
+        (status = 200, description = "Check ids owned by this node", body = Vec<uuid::Uuid>),
+        (status = 401, description = "Unauthorized - invalid or missing password"),
+    ),
+    tags = ["internal"],
+    security(
+        ("internal_bearer" = [])
+    )
+)]
+#[get("/debug/checks-owned")]
+pub async fn checks_owned(req: HttpRequest, app_state: Data<AppState>) -> HttpResponse {
+    if !is_authorized(&req) {
+        log::warn!("unauthorized call to debug endpoint");
+        return HttpResponse::Unauthorized().body("Invalid or missing internal password");
+    }
+
+    HttpResponse::Ok().json(app_state.worker_status.owned_check_ids().await)
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ClusterStatus {
+    pub replication_factor: u32,
+    pub bucket_count: u32,
+    /// Checks of this node's region, extrapolated from those it owns. `None` before the node
+    /// gets a range
+    pub estimated_checks: Option<u64>,
+    /// Coverage of the ring of each region, including regions without alive nodes
+    pub regions: BTreeMap<Region, RingCoverage>,
+}
+
+/// Reports, per region, whether the alive nodes hold every check `REPLICATION_FACTOR` times,
+/// how many nodes that takes and the share of buckets held, with roughly how many checks there
+/// are.
+#[utoipa::path(
+    responses(
+        (status = 200, description = "Ring coverage per region", body = ClusterStatus),
+        (status = 401, description = "Unauthorized - invalid or missing password"),
+        (status = 500, description = "Failed to fetch the alive nodes"),
+    ),
+    tags = ["internal"],
+    security(
+        ("internal_bearer" = [])
+    )
+)]
+#[get("/debug/cluster")]
+pub async fn cluster_status(req: HttpRequest, app_state: Data<AppState>) -> HttpResponse {
+    if !is_authorized(&req) {
+        log::warn!("unauthorized call to cluster status endpoint");
+        return HttpResponse::Unauthorized().body("Invalid or missing internal password");
+    }
+
+    let alive_nodes = match app_state
+        .heartbeat_manager
+        .get_alive_workers_all_regions()
+        .await
+    {
+        Ok(alive_nodes) => alive_nodes,
+        Err(e) => {
+            error!("Failed to fetch alive nodes: {e:?}");
+            return HttpResponse::InternalServerError().finish();
+        }
+    };
+
+    let replication_factor = *eager_env::REPLICATION_FACTOR;
+    let bucket_count = *eager_env::CURRENT_BUCKETS_COUNT;
+    let regions = Region::iter()
+        .map(|region| {
+            let coverage =
+                calculate_ring_coverage(replication_factor, &alive_nodes, region, bucket_count);
+            (region, coverage)
+        })
+        .collect();
+
+    HttpResponse::Ok().json(ClusterStatus {
+        replication_factor,
+        bucket_count,
+        estimated_checks: app_state.worker_status.estimated_region_checks().await,
+        regions,
+    })
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct ProbingState {
+    pub enabled: bool,
+}
+
+/// Pauses or resumes probing on every node. The flag is persisted so restarted nodes respect it.
+#[utoipa::path(
+    request_body = ProbingState,
+    responses(
+        (status = 200, description = "Probing flag updated", body = ProbingState),
+        (status = 401, description = "Unauthorized - invalid or missing password"),
+        (status = 500, description = "Failed to persist the flag"),
+    ),
+    tags = ["internal"],
+    security(
+        ("internal_bearer" = [])
+    )
+)]
+#[post("/internal/probing")]
+pub async fn set_probing(
+    req: HttpRequest,
+    app_state: Data<AppState>,
+    body: Json<ProbingState>,
+) -> HttpResponse {
+    if !is_authorized(&req) {
+        log::warn!("unauthorized call to probing endpoint");
+        return HttpResponse::Unauthorized().body("Invalid or missing internal password");
+    }
+
+    let enabled = body.enabled;
+
+    if let Err(e) = set_probing_enabled(&app_state.database, enabled).await {
+        error!("Failed to persist probing flag: {e:?}");
+        return HttpResponse::InternalServerError().finish();
+    }
+
+    log::warn!(
+        "probing {} cluster-wide",
+        if enabled { "resumed" } else { "paused" }
+    );
+    app_state.probing_enabled.send_replace(enabled);
+
+    let result = standard_broadcast(
+        &*app_state.heartbeat_manager,
+        &*app_state.clock,
+        vec![MessageWithFilters {
+            message: InterNodeMessage::SetProbingEnabled { enabled },
+            filter_bucket: None,
+        }],
+    )
+    .await;
+
+    if let Err(e) = result {
+        error!("Failed to broadcast probing flag: {e}");
+    }
+
+    HttpResponse::Ok().json(ProbingState { enabled })
+}
+
+const DEFAULT_CHECKS_PAGE_SIZE: usize = 100;
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct ListChecksQuery {
+    /// `next_cursor` of the previous page, start from the beginning when unset
+    pub cursor: Option<i32>,
+    /// Minimum number of checks per page. Buckets are never split, so pages may be larger
+    pub page_size: Option<usize>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct ChecksPageResponse {
+    pub checks: Vec<Check>,
+    /// `None` on the last page
+    pub next_cursor: Option<i32>,
+}
+
+/// Lists the checks of every user, for admin and ops tooling.
+///
+/// Each page reads whole buckets across all regions, so listing everything scans the entire
+/// checks table. Avoid calling it in loops.
+#[utoipa::path(
+    params(
+        ("cursor" = Option<i32>, Query, description = "`next_cursor` of the previous page"),
+        ("page_size" = Option<usize>, Query, description = "Minimum number of checks per page, pages may be larger"),
+    ),
+    responses(
+        (status = 200, description = "Page of checks", body = ChecksPageResponse),
+        (status = 400, description = "Invalid cursor"),
+        (status = 401, description = "Unauthorized - invalid or missing password"),
+        (status = 500, description = "Internal server error"),
+    ),
+    tags = ["internal"],
+    security(
+        ("internal_bearer" = [])
+    )
+)]
+#[get("/internal/checks")]
+pub async fn list_checks(
+    req: HttpRequest,
+    app_state: Data<AppState>,
+    query: Query<ListChecksQuery>,
+) -> HttpResponse {
+    if !is_authorized(&req) {
+        log::warn!("unauthorized call to checks listing endpoint");
+        return HttpResponse::Unauthorized().body("Invalid or missing internal password");
+    }
+
+    let cursor = query.cursor.unwrap_or(0);
+    if !(0..listed_buckets_count(app_state.previous_buckets)).contains(&cursor) {
+        return HttpResponse::BadRequest().body("Invalid cursor");
+    }
+
+    let page_size = query.page_size.unwrap_or(DEFAULT_CHECKS_PAGE_SIZE);
+
+    match list_all_checks(
+        &app_state.database,
+        cursor,
+        page_size,
+        app_state.previous_buckets,
+    )
+    .await
+    {
+        Ok(page) => HttpResponse::Ok().json(ChecksPageResponse {
+            checks: page.checks,
+            next_cursor: page.next_bucket,
+        }),
+        Err(e) => {
+            error!("Failed to list checks: {e:?}");
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct RecomputeQuery {
+    /// Start timestamp, included (ISO 8601, must be rounded to granularity)
+    pub from: DateTime<Utc>,
+    /// End timestamp, excluded (ISO 8601, must be rounded to granularity)
+    pub to: DateTime<Utc>,
+    pub granularity: GraphGranularity,
+}
+
+/// Drops the cached aggregates of a check in the window and recomputes them from raw results,
+/// in every region. Meant to repair aggregates cached by a bug.
+#[utoipa::path(
+    params(
+        ("check_id" = Uuid, Path, description = "Check ID"),
+        ("from" = DateTime<Utc>, Query, description = "Start timestamp, included (ISO 8601, must be rounded to granularity)"),
+        ("to" = DateTime<Utc>, Query, description = "End timestamp, excluded (ISO 8601, must be rounded to granularity). Future values are clamped to the end of the current period"),
+        ("granularity" = GraphGranularity, Query, description = "Aggregates to recompute"),
+    ),
+    responses(
+        (status = 200, description = "Recomputed aggregates", body = Vec<MetricsResponseDate>),
+        (status = 400, description = "Invalid query parameters"),
+        (status = 401, description = "Unauthorized - invalid or missing password"),
+        (status = 409, description = "The check doesn't store raw results, its aggregates can't be recomputed"),
+        (status = 500, description = "Internal server error"),
+    ),
+    tags = ["internal"],
+    security(
+        ("internal_bearer" = [])
+    )
+)]
+#[post("/admin/checks/{check_id}/recompute")]
+pub async fn recompute_check_aggregates(
+    req: HttpRequest,
+    app_state: Data<AppState>,
+    check_id: Path<Uuid>,
+    query: Query<RecomputeQuery>,
+) -> HttpResponse {
+    if !is_authorized(&req) {
+        log::warn!("unauthorized call to aggregates recompute endpoint");
+        return HttpResponse::Unauthorized().body("Invalid or missing internal password");
+    }
+
+    if query.from >= query.to {
+        return HttpResponse::BadRequest().body("'from' must be before 'to'");
+    }
+    if !is_rounded_to_granularity(query.from, query.granularity)
+        || !is_rounded_to_granularity(query.to, query.granularity)
+    {
+        return HttpResponse::BadRequest()
+            .body("'from' and 'to' must be rounded to the specified granularity");
+    }
+    let max_days = query.granularity.max_window_days();
+    if query.to - query.from > chrono::Duration::days(max_days) {
+        return HttpResponse::BadRequest().body(format!(
+            "Time range cannot exceed {max_days} days for {:?} granularity",
+            query.granularity
+        ));
+    }
+
+    let check_id = check_id.into_inner();
+    log::warn!(
+        "recomputing {:?} aggregates of check {check_id} in [{}, {})",
+        query.granularity,
+        query.from,
+        query.to
+    );
+
+    match recompute_cached_check_results(
+        &app_state.database,
+        check_id,
+        query.from,
+        query.to,
+        query.granularity,
+    )
+    .await
+    {
+        Ok(points) => HttpResponse::Ok().json(points),
+        Err(e) if e.downcast_ref::<RawResultsNotStored>().is_some() => {
+            HttpResponse::Conflict().body(e.to_string())
+        }
+        Err(e) => {
+            error!("Failed to recompute aggregates of check {check_id}: {e:?}");
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+/// Reads the value of a gauge from the latest result of a check
+type GaugeValue = fn(&CheckResultRow) -> i64;
+
+/// Per-check gauges, as `(name, help, value)`
+const CHECK_GAUGES: [(&str, &str, GaugeValue); 2] = [
+    (
+        "uptime_check_up",
+        "Whether the latest probe of the check matched its expectations",
+        |result| result.matches_expected as i64,
+    ),
+    (
+        "uptime_check_response_micros",
+        "Response time of the latest probe of the check, in microseconds",
+        |result| result.response_time_micros,
+    ),
+];
+
+/// Renders the Prometheus gauges of the latest result of each check, in the text format
+fn render_check_gauges(results: &[(Uuid, CheckResultRow)]) -> String {
+    let mut output = String::new();
+
+    for (name, help, value) in CHECK_GAUGES {
+        writeln!(output, "# HELP {name} {help}").expect("writing to a String");
+        writeln!(output, "# TYPE {name} gauge").expect("writing to a String");
+        for (check_id, result) in results {
+            writeln!(
+                output,
+                "{name}{{check_id=\"{check_id}\",region=\"{}\"}} {}",
+                result.region.to_identifier(),
+                value(result)
+            )
+            .expect("writing to a String");
+        }
+    }
+
+    output
+}
+
+/// Exports the latest result of every check this node owns as Prometheus gauges.
+///
+/// Limited to owned checks so that the cardinality is bounded by the node's share of the ring.
+#[utoipa::path(
+    responses(
+        (status = 200, description = "Gauges in the Prometheus text format", content_type = "text/plain", body = String),
+        (status = 401, description = "Unauthorized - invalid or missing password"),
+    ),
+    tags = ["internal"],
+    security(
+        ("internal_bearer" = [])
+    )
+)]
+#[get("/internal/metrics")]
+pub async fn check_gauges(req: HttpRequest, app_state: Data<AppState>) -> HttpResponse {
+    if !is_authorized(&req) {
+        log::warn!("unauthorized call to metrics export endpoint");
+        return HttpResponse::Unauthorized().body("Invalid or missing internal password");
+    }
+
+    let region = app_state.worker_status.region();
+    let now = app_state.clock.now();
+    let database = &app_state.database;
+
+    let mut results: Vec<_> = stream::iter(app_state.worker_status.owned_check_ids().await)
+        .map(|check_id| async move {
+            match get_latest_check_result(database, check_id, region, now).await {
+                Ok(result) => result.map(|result| (check_id, result)),
+                Err(e) => {
+                    error!("Failed to fetch the latest result of check {check_id}: {e:?}");
+                    None
+                }
+            }
+        })
+        .buffer_unordered(eager_env::DATABASE_CONCURRENT_READS.get())
+        .filter_map(|result| async move { result })
+        .collect()
+        .await;
+    results.sort_by_key(|(check_id, _)| *check_id);
+
+    HttpResponse::Ok()
+        .content_type("text/plain; version=0.0.4")
+        .body(render_check_gauges(&results))
+}
+
+/// Longest window of heartbeats readable at once, each minute being a query
+const HEARTBEAT_HISTORY_MAX_HOURS: i64 = 24;
+
+const DEFAULT_HEARTBEATS_PAGE_SIZE: usize = 500;
+const MAX_HEARTBEATS_PAGE_SIZE: usize = 5000;
+
+/// Heartbeats per page for the requested `page_size`, kept within `1..=MAX_HEARTBEATS_PAGE_SIZE`
+fn heartbeats_page_size(requested: Option<usize>) -> usize {
+    requested
+        .unwrap_or(DEFAULT_HEARTBEATS_PAGE_SIZE)
+        .clamp(1, MAX_HEARTBEATS_PAGE_SIZE)
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct HeartbeatHistoryQuery {
+    /// Start timestamp, included (ISO 8601)
+    pub from: DateTime<Utc>,
+    /// End timestamp, excluded (ISO 8601)
+    pub to: DateTime<Utc>,
+    /// `next_cursor` of the previous page, start from `from` when unset
+    pub cursor: Option<DateTime<Utc>>,
+    pub page_size: Option<usize>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct HeartbeatHistoryResponse {
+    /// Oldest first
+    pub heartbeats: Vec<HeartbeatRecord>,
+    /// `None` on the last page
+    pub next_cursor: Option<DateTime<Utc>>,
+}
+
+/// Lists the heartbeats a node wrote in a window, to find when it started missing beats.
+#[utoipa::path(
+    params(
+        ("process_id" = Uuid, Path, description = "Process ID of the node"),
+        ("from" = DateTime<Utc>, Query, description = "Start timestamp, included (ISO 8601)"),
+        ("to" = DateTime<Utc>, Query, description = "End timestamp, excluded (ISO 8601), at most 24 hours after `from`"),
+        ("cursor" = Option<DateTime<Utc>>, Query, description = "`next_cursor` of the previous page"),
+        ("page_size" = Option<usize>, Query, description = "Maximum number of heartbeats per page, defaults to 500 and capped at 5000"),
+    ),
+    responses(
+        (status = 200, description = "Page of heartbeats", body = HeartbeatHistoryResponse),
+        (status = 400, description = "Invalid query parameters"),
+        (status = 401, description = "Unauthorized - invalid or missing password"),
+        (status = 500, description = "Internal server error"),
+    ),
+    tags = ["internal"],
+    security(
+        ("internal_bearer" = [])
+    )
+)]
+#[get("/internal/nodes/{process_id}/heartbeats")]
+pub async fn heartbeat_history(
+    req: HttpRequest,
+    app_state: Data<AppState>,
+    process_id: Path<Uuid>,
+    query: Query<HeartbeatHistoryQuery>,
+) -> HttpResponse {
+    if !is_authorized(&req) {
+        log::warn!("unauthorized call to heartbeat history endpoint");
+        return HttpResponse::Unauthorized().body("Invalid or missing internal password");
+    }
+
+    if query.from >= query.to {
+        return HttpResponse::BadRequest().body("'from' must be before 'to'");
+    }
+    if query.to - query.from > chrono::Duration::hours(HEARTBEAT_HISTORY_MAX_HOURS) {
+        return HttpResponse::BadRequest().body(format!(
+            "Time range cannot exceed {HEARTBEAT_HISTORY_MAX_HOURS} hours"
+        ));
+    }
+    let from = match query.cursor {
+        Some(cursor) if !(query.from..query.to).contains(&cursor) => {
+            return HttpResponse::BadRequest().body("Invalid cursor");
+        }
+        Some(cursor) => cursor,
+        None => query.from,
+    };
+
+    let process_id = process_id.into_inner();
+    let page_size = heartbeats_page_size(query.page_size);
+
+    // Only alive nodes have a known region, the heartbeats of others are searched in every region
+    let region = match app_state
+        .heartbeat_manager
+        .get_alive_workers_all_regions()
+        .await
+    {
+        Ok(nodes) => nodes
+            .into_iter()
+            .find(|node| node.node_id == process_id)
+            .map(|node| node.region),
+        Err(e) => {
+            log::warn!(
+                "Failed to get the alive nodes, searching heartbeats in every region: {e:?}"
+            );
+            None
+        }
+    };
+
+    match get_heartbeat_history(
+        &app_state.database,
+        process_id,
+        region,
+        from,
+        query.to,
+        page_size,
+    )
+    .await
+    {
+        Ok((heartbeats, has_more)) => {
+            // Timestamps have millisecond precision, so the next page starts right after
+            let next_cursor = heartbeats
+                .last()
+                .filter(|_| has_more)
+                .map(|last| last.timestamp + chrono::Duration::milliseconds(1));
+
+            HttpResponse::Ok().json(HeartbeatHistoryResponse {
+                heartbeats,
+                next_cursor,
+            })
+        }
+        Err(e) => {
+            error!("Failed to read the heartbeats of node {process_id}: {e:?}");
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::server::start_server_test;
-    use uuid::Uuid;
+    use crate::clock::{Clock, MockClock};
+    use crate::collab::PreviousBuckets;
+    use crate::collab::heartbeat::{Heartbeat, HeartbeatStore, ScyllaHeartbeatStore};
+    use crate::queries::checks::{CheckData, create_check};
+    use crate::server::{start_server_test, start_server_test_with};
+    use chrono::DurationRound;
+    use std::time::Duration;
 
     #[tokio::test]
     async fn test_internal_endpoint() {
-        let (port, _) = start_server_test(None).await;
+        let (port, state) = start_server_test(None).await;
         let client = reqwest::Client::new();
         let url = format!("http://localhost:{}/internal", port);
 
         // No token - should be unauthorized
         let response = client
             .post(&url)
-            .json(&Vec::<InterNodeMessage>::new())
+            .json(&BroadcastBody::new(vec![], state.clock.now()))
             .send()
             .await
             .unwrap();
@@ -93,26 +659,302 @@ mod tests {
         let response = client
             .post(&url)
             .header("Authorization", "Bearer wrong_password")
-            .json(&Vec::<InterNodeMessage>::new())
+            .json(&BroadcastBody::new(vec![], state.clock.now()))
             .send()
             .await
             .unwrap();
         assert_eq!(response.status(), 401);
 
         // Correct token - should succeed
+        let body = BroadcastBody::new(
+            vec![InterNodeMessage::ServiceCheckMutation {
+                check_id: Uuid::new_v4(),
+            }],
+            state.clock.now(),
+        );
+        let send = |body: &BroadcastBody| {
+            client
+                .post(&url)
+                .header(
+                    "Authorization",
+                    format!("Bearer {}", *eager_env::BACKEND_INTERNAL_PASSWORD),
+                )
+                .json(body)
+                .send()
+        };
+        assert_eq!(send(&body).await.unwrap().status(), 200);
+
+        // Replaying the same request is rejected
+        assert_eq!(send(&body).await.unwrap().status(), 409);
+    }
+
+    #[tokio::test]
+    async fn test_internal_endpoint_rejects_stale_messages() {
+        let clock = MockClock::new(Utc::now());
+        let (port, _) = start_server_test_with(None, |state| state.clock = clock.clone()).await;
+        let client = reqwest::Client::new();
+        let url = format!("http://localhost:{}/internal", port);
+        let window = Duration::from_secs(*eager_env::INTERNODE_REPLAY_WINDOW_SECONDS);
+
         let messages = vec![InterNodeMessage::ServiceCheckMutation {
             check_id: Uuid::new_v4(),
         }];
+        let send = |body: BroadcastBody| {
+            client
+                .post(&url)
+                .header(
+                    "Authorization",
+                    format!("Bearer {}", *eager_env::BACKEND_INTERNAL_PASSWORD),
+                )
+                .json(&body)
+                .send()
+        };
+
+        let stale = BroadcastBody::new(
+            messages.clone(),
+            clock.now() - (window + Duration::from_secs(1)),
+        );
+        assert_eq!(send(stale).await.unwrap().status(), 409);
+
+        let sent_at = clock.now();
+        let fresh = BroadcastBody::new(messages.clone(), sent_at);
+        assert_eq!(send(fresh).await.unwrap().status(), 200);
+
+        // Received after the window by the server's clock
+        clock.advance(window + Duration::from_secs(1));
+        let delayed = BroadcastBody::new(messages, sent_at);
+        assert_eq!(send(delayed).await.unwrap().status(), 409);
+    }
+
+    #[tokio::test]
+    async fn test_list_checks_endpoint_reads_previous_buckets() {
+        let previous = PreviousBuckets {
+            bucket_version: *eager_env::CURRENT_BUCKET_VERSION as i16 + 1,
+            bucket_count: 7,
+        };
+        let (port, state) = start_server_test_with(None, |state| {
+            state.previous_buckets = Some(previous);
+        })
+        .await;
+        let client = reqwest::Client::new();
+        let url = format!("http://localhost:{port}/internal/checks");
+
+        // Only stored under the previous version, not rewritten since the migration started
+        let migrating = Uuid::new_v4();
+        let (bucket_version, bucket) = previous.bucket_for_check(migrating);
+        state
+            .database
+            .query_unpaged(
+                "INSERT INTO checks (check_id, region, bucket_version, bucket, check_name, url, http_method, check_frequency_seconds, timeout_seconds, expected_status_code, request_headers, is_enabled, created_at) VALUES (?, 'hel1', ?, ?, 'previous', 'https://example.com', 'GET', 60, 10, 200, {}, true, toTimestamp(now()))",
+                (migrating, bucket_version, bucket),
+            )
+            .await
+            .unwrap();
+        let current = create_check(&state.database, vec![Region::Fsn1], CheckData::example())
+            .await
+            .unwrap();
+
+        let get_page = |cursor: i32| {
+            client
+                .get(&url)
+                .header(
+                    "Authorization",
+                    format!("Bearer {}", *eager_env::BACKEND_INTERNAL_PASSWORD),
+                )
+                .query(&[("cursor", cursor), ("page_size", 1)])
+                .send()
+        };
+
+        let mut listed = Vec::new();
+        let mut cursor = Some(0);
+        while let Some(bucket) = cursor {
+            let response = get_page(bucket).await.unwrap();
+            assert_eq!(response.status(), 200);
+            let page: ChecksPageResponse = response.json().await.unwrap();
+            listed.extend(page.checks.into_iter().map(|check| check.check_id));
+            cursor = page.next_cursor;
+        }
+        listed.sort();
+        let mut expected = vec![migrating, current.check_id];
+        expected.sort();
+        assert_eq!(listed, expected);
+
+        // Cursors cover the buckets of both versions only
+        let response = get_page(listed_buckets_count(Some(previous)))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 400);
+    }
+
+    #[test]
+    fn test_render_check_gauges() {
+        let check_id = Uuid::new_v4();
+        let result = CheckResultRow {
+            check_started_at: Utc::now(),
+            response_time_micros: 1500,
+            ttfb_micros: Some(900),
+            status_code: Some(200),
+            matches_expected: true,
+            response_size_bytes: None,
+            region: Region::Hel1,
+            sample_weight: 1,
+        };
+
+        let output = render_check_gauges(&[(check_id, result)]);
+
+        assert!(output.contains("# TYPE uptime_check_up gauge\n"));
+        assert!(output.contains(&format!(
+            "uptime_check_up{{check_id=\"{check_id}\",region=\"hel1\"}} 1\n"
+        )));
+        assert!(output.contains("# TYPE uptime_check_response_micros gauge\n"));
+        assert!(output.contains(&format!(
+            "uptime_check_response_micros{{check_id=\"{check_id}\",region=\"hel1\"}} 1500\n"
+        )));
+    }
+
+    #[tokio::test]
+    async fn test_check_gauges_endpoint() {
+        let (port, _) = start_server_test(None).await;
+        let client = reqwest::Client::new();
+        let url = format!("http://localhost:{}/internal/metrics", port);
+
+        let response = client.get(&url).send().await.unwrap();
+        assert_eq!(response.status(), 401);
+
         let response = client
-            .post(&url)
+            .get(&url)
             .header(
                 "Authorization",
                 format!("Bearer {}", *eager_env::BACKEND_INTERNAL_PASSWORD),
             )
-            .json(&messages)
             .send()
             .await
             .unwrap();
         assert_eq!(response.status(), 200);
+        assert!(
+            response
+                .text()
+                .await
+                .unwrap()
+                .contains("# TYPE uptime_check_up gauge")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_heartbeat_history_endpoint() {
+        let (port, state) = start_server_test(None).await;
+        let client = reqwest::Client::new();
+        let process_id = Uuid::new_v4();
+        let url = format!("http://localhost:{port}/internal/nodes/{process_id}/heartbeats");
+
+        let store = ScyllaHeartbeatStore::new(state.database.clone())
+            .await
+            .unwrap();
+        let start = Utc::now()
+            .duration_trunc(chrono::Duration::minutes(1))
+            .unwrap()
+            - chrono::Duration::minutes(10);
+        // One missed beat at minute 2
+        for minutes in [0, 1, 3, 4] {
+            store
+                .insert_heartbeat(
+                    Region::Fsn1,
+                    process_id,
+                    7,
+                    start + chrono::Duration::minutes(minutes),
+                )
+                .await
+                .unwrap();
+        }
+        // Another node
+        store
+            .insert_heartbeat(
+                Region::Fsn1,
+                Uuid::new_v4(),
+                8,
+                start + chrono::Duration::seconds(30),
+            )
+            .await
+            .unwrap();
+
+        let response = client.get(&url).send().await.unwrap();
+        assert_eq!(response.status(), 401);
+
+        let get_page = |cursor: Option<DateTime<Utc>>| {
+            let mut query = vec![
+                ("from", start.to_rfc3339()),
+                ("to", (start + chrono::Duration::minutes(10)).to_rfc3339()),
+                ("page_size", "3".to_string()),
+            ];
+            if let Some(cursor) = cursor {
+                query.push(("cursor", cursor.to_rfc3339()));
+            }
+            client
+                .get(&url)
+                .header(
+                    "Authorization",
+                    format!("Bearer {}", *eager_env::BACKEND_INTERNAL_PASSWORD),
+                )
+                .query(&query)
+                .send()
+        };
+
+        let response = get_page(None).await.unwrap();
+        assert_eq!(response.status(), 200);
+        let first: HeartbeatHistoryResponse = response.json().await.unwrap();
+        assert_eq!(
+            first
+                .heartbeats
+                .iter()
+                .map(|h| h.timestamp)
+                .collect::<Vec<_>>(),
+            [0, 1, 3].map(|minutes| start + chrono::Duration::minutes(minutes))
+        );
+        assert!(first.heartbeats.iter().all(|h| h.position == 7));
+
+        let response = get_page(first.next_cursor).await.unwrap();
+        assert_eq!(response.status(), 200);
+        let second: HeartbeatHistoryResponse = response.json().await.unwrap();
+        assert_eq!(second.heartbeats.len(), 1);
+        assert_eq!(
+            second.heartbeats[0].timestamp,
+            start + chrono::Duration::minutes(4)
+        );
+        assert_eq!(second.next_cursor, None);
+
+        // Once the node is known to be alive, only its region is read
+        state
+            .heartbeat_manager
+            .register_nodes(&[Heartbeat {
+                node_id: process_id,
+                position: 7,
+                socket_address: None,
+                region: Region::Fsn1,
+            }])
+            .await;
+        store
+            .insert_heartbeat(
+                Region::Hel1,
+                process_id,
+                7,
+                start + chrono::Duration::minutes(5),
+            )
+            .await
+            .unwrap();
+        let response = get_page(first.next_cursor).await.unwrap();
+        assert_eq!(response.status(), 200);
+        let third: HeartbeatHistoryResponse = response.json().await.unwrap();
+        assert_eq!(third.heartbeats, second.heartbeats);
+    }
+
+    #[test]
+    fn test_heartbeats_page_size() {
+        assert_eq!(heartbeats_page_size(None), DEFAULT_HEARTBEATS_PAGE_SIZE);
+        assert_eq!(heartbeats_page_size(Some(3)), 3);
+        assert_eq!(heartbeats_page_size(Some(0)), 1);
+        assert_eq!(
+            heartbeats_page_size(Some(usize::MAX)),
+            MAX_HEARTBEATS_PAGE_SIZE
+        );
     }
 }
@@ -3,15 +3,26 @@ pub mod checks;
 mod health;
 mod internal;
 mod openapi;
+mod regions;
 mod users;
 
 use crate::{
-    collab::heartbeat::HeartbeatManager, database::Database, eager_env, server::health::*,
+    clock::SharedClock,
+    collab::{
+        PreviousBuckets,
+        heartbeat::HeartbeatManager,
+        internode::{recent::RecentMutations, replay::ReplayGuard},
+    },
+    database::Database,
+    eager_env,
+    queries::pings::PingBatcher,
+    server::health::*,
+    worker::{IpVersionPreference, WorkerStatus},
 };
 use actix_cors::Cors;
 use actix_web::{App, HttpServer, http::Method, web::Data};
 use std::{collections::BTreeSet, net::TcpListener, sync::Arc};
-use tokio::sync::mpsc::UnboundedSender;
+use tokio::sync::{mpsc::UnboundedSender, watch};
 use utoipa::OpenApi;
 use utoipa_actix_web::AppExt;
 use utoipa_swagger_ui::SwaggerUi;
@@ -21,10 +32,42 @@ pub type AppState = Arc<AppStateInner>;
 pub type TaskUpdateType = BTreeSet<Uuid>;
 
 pub struct AppStateInner {
+    #[allow(dead_code)]
     pub process_id: Uuid,
     pub database: Arc<Database>,
+    /// Session of a read-only replica serving the metrics scans, see [`Self::metrics_database`]
+    pub read_replica: Option<Arc<Database>>,
     pub task_updates: UnboundedSender<TaskUpdateType>,
     pub heartbeat_manager: Arc<HeartbeatManager>,
+    pub worker_status: WorkerStatus,
+    pub probing_enabled: watch::Sender<bool>,
+    pub clock: SharedClock,
+    /// Cleared while a required startup self-test keeps failing
+    pub ready: watch::Receiver<bool>,
+    /// Accepts checks probing private addresses, rejected otherwise
+    pub private_targets_allowed: bool,
+    /// Rejects check names already used by another check of the same user
+    pub unique_check_names: bool,
+    /// Rejects checks running in more regions
+    pub max_regions_per_check: usize,
+    pub replay_guard: ReplayGuard,
+    /// Mutations broadcast by this node, sent again to nodes joining the cluster
+    pub recent_mutations: Arc<RecentMutations>,
+    /// Unless `Any`, hosts of new checks are resolved to warn when none of their addresses
+    /// can be probed
+    pub ip_version_preference: IpVersionPreference,
+    /// Coalesces the writes of passive check pings, written one by one when unset
+    pub ping_batcher: Option<Arc<PingBatcher>>,
+    /// Layout checks are migrating from, also listed by the internal checks listing
+    pub previous_buckets: Option<PreviousBuckets>,
+}
+
+impl AppStateInner {
+    /// Session reading check results for metrics: the read replica when configured, so that
+    /// heavy scans don't compete with writes, the primary otherwise
+    pub fn metrics_database(&self) -> &Database {
+        self.read_replica.as_deref().unwrap_or(&self.database)
+    }
 }
 
 pub async fn start_server(state: AppState, listener: TcpListener) -> std::io::Result<()> {
@@ -59,6 +102,8 @@ pub async fn start_server(state: AppState, listener: TcpListener) -> std::io::Re
             .openapi(openapi::ApiDoc::openapi())
             .service(home)
             .service(health)
+            .service(ready)
+            .service(regions::list_regions)
             .configure(users::configure_routes)
             .configure(checks::configure_routes)
             .configure(internal::configure_routes)
@@ -76,9 +121,18 @@ pub async fn start_server(state: AppState, listener: TcpListener) -> std::io::Re
 
 #[cfg(test)]
 pub async fn start_server_test(fixtures: Option<&str>) -> (u16, AppState) {
+    start_server_test_with(fixtures, |_| {}).await
+}
+
+/// Like [`start_server_test`], with `configure` adjusting the state before the server starts.
+#[cfg(test)]
+pub async fn start_server_test_with(
+    fixtures: Option<&str>,
+    configure: impl FnOnce(&mut AppStateInner),
+) -> (u16, AppState) {
     use std::time::Duration;
 
-    use crate::{database::testing::create_test_database, regions::Region};
+    use crate::{clock::SystemClock, database::testing::create_test_database, regions::Region};
     use tokio::sync::mpsc;
 
     let (task_updates, _rx) = mpsc::unbounded_channel();
@@ -89,7 +143,7 @@ pub async fn start_server_test(fixtures: Option<&str>) -> (u16, AppState) {
     let database = Arc::new(database);
 
     let process_id = Uuid::new_v4();
-    let state = AppStateInner {
+    let mut state = AppStateInner {
         process_id,
         task_updates,
         heartbeat_manager: Arc::new(
@@ -98,12 +152,31 @@ pub async fn start_server_test(fixtures: Option<&str>) -> (u16, AppState) {
                 Region::Fsn1,
                 Duration::from_secs(99999),
                 database.clone(),
+                None,
             )
             .await
             .unwrap(),
         ),
         database,
+        read_replica: None,
+        worker_status: WorkerStatus::detached(),
+        probing_enabled: watch::Sender::new(true),
+        clock: SystemClock::shared(),
+        ready: watch::channel(true).1,
+        private_targets_allowed: false,
+        unique_check_names: false,
+        max_regions_per_check: usize::MAX,
+        replay_guard: ReplayGuard::new(Duration::from_secs(
+            *eager_env::INTERNODE_REPLAY_WINDOW_SECONDS,
+        )),
+        recent_mutations: Arc::new(RecentMutations::new(Duration::from_secs(
+            *eager_env::RECENT_MUTATIONS_RETENTION_SECONDS,
+        ))),
+        ip_version_preference: IpVersionPreference::Any,
+        ping_batcher: None,
+        previous_buckets: None,
     };
+    configure(&mut state);
     let app_state: AppState = Arc::new(state);
 
     let listener = TcpListener::bind("0.0.0.0:0").expect("failed to bind to random port");
@@ -137,5 +210,41 @@ mod tests {
 
         let status = response.status();
         assert_eq!(status, 200);
+
+        let body: serde_json::Value = response.json().await.unwrap();
+        assert_eq!(body["status"], "ok");
+        assert!(body["healthy"].is_boolean());
+        assert_eq!(body["components"]["database"]["healthy"], true);
+        assert_eq!(body["components"]["worker"]["healthy"], true);
+        // The test server never starts its heartbeat
+        assert_eq!(body["components"]["heartbeat"]["healthy"], false);
+        assert!(body["components"]["heartbeat"]["last_write_age_seconds"].is_null());
+    }
+
+    #[tokio::test]
+    async fn test_regions_endpoint() {
+        use crate::regions::Region;
+        use strum::IntoEnumIterator;
+
+        let (port, _) = start_server_test(None).await;
+
+        let regions: Vec<serde_json::Value> =
+            reqwest::get(format!("http://localhost:{port}/regions"))
+                .await
+                .unwrap()
+                .json()
+                .await
+                .unwrap();
+
+        assert_eq!(regions.len(), Region::iter().count());
+        for region in Region::iter() {
+            let info = regions
+                .iter()
+                .find(|info| info["identifier"] == region.to_identifier())
+                .unwrap_or_else(|| panic!("missing region {region:?}"));
+            assert!(!info["display_name"].as_str().unwrap().is_empty());
+            assert!(info["latitude"].is_f64());
+            assert!(info["longitude"].is_f64());
+        }
     }
 }
@@ -1,4 +1,6 @@
-use crate::queries::check_results::GraphGranularity;
+use crate::queries::check_results::{
+    GraphGranularity, OverallUptime, ResponseTimeUnit, UptimeWeighting,
+};
 use crate::server::auth::SESSION_COOKIE_NAME;
 use utoipa::OpenApi;
 use utoipa::openapi::{
@@ -12,10 +14,11 @@ use utoipa::openapi::{
         (name = "health", description = "Health-related endpoints."),
         (name = "users", description = "User-related endpoints."),
         (name = "checks", description = "Health check management endpoints."),
+        (name = "regions", description = "Monitoring regions."),
         (name = "internal", description = "Internal endpoints for backend-to-backend communication."),
     ),
     modifiers(&SecurityAddon),
-    components(schemas(GraphGranularity)), // Auto registering fails
+    components(schemas(GraphGranularity, OverallUptime, ResponseTimeUnit, UptimeWeighting)), // Auto registering fails
 )]
 pub struct ApiDoc;
 
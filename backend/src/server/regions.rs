@@ -0,0 +1,44 @@
+use actix_web::{get, web::Json};
+use serde::Serialize;
+use strum::IntoEnumIterator;
+use utoipa::ToSchema;
+
+use crate::regions::Region;
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct RegionInfo {
+    pub region: Region,
+    /// Identifier used in storage and internal APIs, e.g. `fsn1`
+    pub identifier: &'static str,
+    pub display_name: &'static str,
+    pub latitude: f64,
+    pub longitude: f64,
+}
+
+impl From<Region> for RegionInfo {
+    fn from(region: Region) -> Self {
+        let (latitude, longitude) = region.coordinates();
+
+        Self {
+            region,
+            identifier: region.to_identifier(),
+            display_name: region.display_name(),
+            latitude,
+            longitude,
+        }
+    }
+}
+
+#[utoipa::path(
+    summary = "List regions",
+    description = "Every region checks can run in, with its location",
+    responses(
+        (status = 200, description = "Monitoring regions", body = Vec<RegionInfo>)
+    ),
+    tags = ["regions"],
+    operation_id = "listRegions"
+)]
+#[get("/regions")]
+pub async fn list_regions() -> Json<Vec<RegionInfo>> {
+    Json(Region::iter().map(RegionInfo::from).collect())
+}
@@ -1,5 +1,6 @@
 use crate::{
     queries::{
+        alert_routing::{AlertRouting, get_alert_routing, set_alert_routing},
         sessions::{create_session, log_out_session},
         users::{LoginResult, PublicUser, create_user, get_user_by_id, login_user},
     },
@@ -11,10 +12,11 @@ use crate::{
 use actix_web::{
     Error, HttpResponse,
     error::{ErrorBadRequest, ErrorInternalServerError, ErrorNotFound, ErrorUnauthorized},
-    get, post,
+    get, post, put,
     web::{Data, Json, Path},
 };
 use serde::{Deserialize, Serialize};
+use url::Url;
 use utoipa::ToSchema;
 use utoipa_actix_web::{scope, service_config::ServiceConfig};
 use uuid::Uuid;
@@ -26,7 +28,9 @@ pub fn configure_routes(config: &mut ServiceConfig) {
             .service(get_current_user)
             .service(create_new_user)
             .service(login)
-            .service(logout),
+            .service(logout)
+            .service(get_alert_routing_endpoint)
+            .service(set_alert_routing_endpoint),
     );
 }
 
@@ -142,7 +146,7 @@ async fn create_new_user(
 
     // Create session
     let session_id = Uuid::new_v4();
-    create_session(&app_state.database, user_id, session_id)
+    create_session(&app_state.database, &*app_state.clock, user_id, session_id)
         .await
         .map_err(|e| {
             // TODO: log error
@@ -182,12 +186,17 @@ async fn login(body: Json<LoginRequest>, app_state: Data<AppState>) -> Result<Ht
         LoginResult::Ok(public_user) => {
             // Create session
             let session_id = Uuid::new_v4();
-            create_session(&app_state.database, public_user.user_id, session_id)
-                .await
-                .map_err(|e| {
-                    // TODO: log error
-                    ErrorInternalServerError(e)
-                })?;
+            create_session(
+                &app_state.database,
+                &*app_state.clock,
+                public_user.user_id,
+                session_id,
+            )
+            .await
+            .map_err(|e| {
+                // TODO: log error
+                ErrorInternalServerError(e)
+            })?;
 
             // Create session cookie
             let cookie = create_session_cookie(session_id);
@@ -236,5 +245,100 @@ async fn logout(app_state: Data<AppState>, auth: AuthenticatedUser) -> Result<Ht
     }
 }
 
+fn session_user_id(auth: AuthenticatedUser) -> Result<Uuid, Error> {
+    match auth {
+        AuthenticatedUser::User(UserSession { user_id, .. }) => Ok(user_id),
+        AuthenticatedUser::Api(_) => Err(ErrorBadRequest("API keys have no alert routing")),
+    }
+}
+
+/// Rejects routes the dispatcher could not follow
+fn validate_alert_routing(routing: &AlertRouting) -> Result<(), Error> {
+    let validate_url = |webhook_url: &str| -> Result<(), Error> {
+        match Url::parse(webhook_url) {
+            Ok(url) if ["http", "https"].contains(&url.scheme()) => Ok(()),
+            _ => Err(ErrorBadRequest(format!(
+                "Invalid webhook URL '{webhook_url}', expected http or https"
+            ))),
+        }
+    };
+
+    for route in &routing.routes {
+        if route.tag.trim().is_empty() {
+            return Err(ErrorBadRequest("Route tags cannot be empty"));
+        }
+        validate_url(&route.webhook_url)?;
+    }
+    if let Some(default_webhook_url) = &routing.default_webhook_url {
+        validate_url(default_webhook_url)?;
+    }
+
+    Ok(())
+}
+
+#[utoipa::path(
+    summary = "Get alert routing",
+    description = "Retrieves where the alerts of the current user's checks are sent, depending on their tags",
+    responses(
+        (status = 200, description = "Alert routing, empty if never set", body = AlertRouting),
+        (status = 400, description = "API keys have no alert routing"),
+        (status = 401, description = "Unauthorized - authentication required"),
+        (status = 500, description = "Internal server error")
+    ),
+    security(
+        ("cookie_auth" = []),
+        ("bearer_auth" = [])
+    ),
+    tags = ["users"],
+    operation_id = "getAlertRouting"
+)]
+#[get("/me/alert-routing")]
+async fn get_alert_routing_endpoint(
+    app_state: Data<AppState>,
+    auth: AuthenticatedUser,
+) -> Result<Json<AlertRouting>, Error> {
+    let user_id = session_user_id(auth)?;
+
+    let routing = get_alert_routing(&app_state.database, user_id)
+        .await
+        .map_err(ErrorInternalServerError)?;
+
+    Ok(Json(routing))
+}
+
+#[utoipa::path(
+    summary = "Set alert routing",
+    description = "Replaces the alert routing of the current user. The alerts of a check go to every route matching one of its tags, or to the default webhook if none does",
+    request_body = AlertRouting,
+    responses(
+        (status = 200, description = "Alert routing updated successfully", body = AlertRouting),
+        (status = 400, description = "Invalid routes"),
+        (status = 401, description = "Unauthorized - authentication required"),
+        (status = 500, description = "Internal server error")
+    ),
+    security(
+        ("cookie_auth" = []),
+        ("bearer_auth" = [])
+    ),
+    tags = ["users"],
+    operation_id = "setAlertRouting"
+)]
+#[put("/me/alert-routing")]
+async fn set_alert_routing_endpoint(
+    body: Json<AlertRouting>,
+    app_state: Data<AppState>,
+    auth: AuthenticatedUser,
+) -> Result<Json<AlertRouting>, Error> {
+    let user_id = session_user_id(auth)?;
+    let routing = body.into_inner();
+    validate_alert_routing(&routing)?;
+
+    set_alert_routing(&app_state.database, user_id, &routing)
+        .await
+        .map_err(ErrorInternalServerError)?;
+
+    Ok(Json(routing))
+}
+
 #[cfg(test)]
 mod user_endpoints_tests;
@@ -0,0 +1,111 @@
+use std::{
+    collections::HashMap,
+    hash::Hash,
+    sync::{Arc, Mutex},
+};
+use tokio::sync::OnceCell;
+
+/// Coalesces concurrent computations of the same key: callers arriving while a computation
+/// is in flight wait for it and share its result.
+///
+/// Errors are not shared: if the computation fails, the next waiter runs its own.
+/// Nothing is kept once a computation completes, so it is not a cache.
+pub struct SingleFlight<K, V> {
+    in_flight: Mutex<HashMap<K, Arc<OnceCell<V>>>>,
+}
+
+impl<K, V> Default for SingleFlight<K, V> {
+    fn default() -> Self {
+        Self {
+            in_flight: Default::default(),
+        }
+    }
+}
+
+impl<K: Hash + Eq + Clone, V: Clone> SingleFlight<K, V> {
+    pub async fn run<F, Fut, E>(&self, key: K, compute: F) -> Result<V, E>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<V, E>>,
+    {
+        let cell = self
+            .in_flight
+            .lock()
+            .expect("single flight lock poisoned")
+            .entry(key.clone())
+            .or_default()
+            .clone();
+
+        let result = cell.get_or_try_init(compute).await.cloned();
+
+        // Whoever finishes first clears the entry, unless it was already replaced
+        let mut in_flight = self.in_flight.lock().expect("single flight lock poisoned");
+        if in_flight
+            .get(&key)
+            .is_some_and(|current| Arc::ptr_eq(current, &cell))
+        {
+            in_flight.remove(&key);
+        }
+
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{
+        sync::atomic::{AtomicUsize, Ordering},
+        time::Duration,
+    };
+
+    #[tokio::test]
+    async fn test_concurrent_calls_share_computation() {
+        let single_flight = SingleFlight::<u32, u32>::default();
+        let runs = AtomicUsize::new(0);
+
+        let compute = || async {
+            runs.fetch_add(1, Ordering::SeqCst);
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            Ok::<_, ()>(42)
+        };
+
+        let results =
+            futures::future::join_all((0..8).map(|_| single_flight.run(1, compute))).await;
+
+        assert!(results.iter().all(|r| *r == Ok(42)));
+        assert_eq!(runs.load(Ordering::SeqCst), 1);
+        assert!(single_flight.in_flight.lock().unwrap().is_empty());
+
+        // Completed computations are not reused
+        single_flight.run(1, compute).await.unwrap();
+        assert_eq!(runs.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_distinct_keys_and_errors_are_not_shared() {
+        let single_flight = SingleFlight::<u32, u32>::default();
+        let runs = AtomicUsize::new(0);
+
+        let failing = || async {
+            runs.fetch_add(1, Ordering::SeqCst);
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            Err::<u32, _>("failed")
+        };
+        let other_key = || async {
+            runs.fetch_add(1, Ordering::SeqCst);
+            Ok::<_, &str>(7)
+        };
+
+        let (first, second, other) = tokio::join!(
+            single_flight.run(1, failing),
+            single_flight.run(1, failing),
+            single_flight.run(2, other_key),
+        );
+
+        assert_eq!(first, Err("failed"));
+        assert_eq!(second, Err("failed"));
+        assert_eq!(other, Ok(7));
+        assert_eq!(runs.load(Ordering::SeqCst), 3);
+    }
+}
@@ -0,0 +1,204 @@
+use crate::eager_env;
+use log::info;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use uuid::Uuid;
+
+/// Probes of a check backed off this many times at most, i.e. up to `2^MAX_DOUBLINGS` times its
+/// frequency, before `max_interval` applies
+const MAX_DOUBLINGS: u32 = 16;
+
+/// Failure streaks not extended for this many times `max_interval` are forgotten.
+///
+/// Backed-off checks are probed at least every `max_interval`, so these belong to checks no longer
+/// probed by this node, e.g. deleted or moved to another node. Checks slower than `max_interval`
+/// are never backed off, forgetting their streak changes nothing.
+const IDLE_INTERVALS: u32 = 2;
+
+struct FailureStreak {
+    failures: u32,
+    last_failure_at: Instant,
+}
+
+#[derive(Default)]
+struct Streaks {
+    /// Checks that failed their last probe, removed on success
+    by_check: HashMap<Uuid, FailureStreak>,
+    /// When idle streaks were last evicted
+    swept_at: Option<Instant>,
+}
+
+/// Backs off checks whose target is persistently failing.
+///
+/// After `threshold` consecutive failures, the interval between probes doubles with each further
+/// failure, up to `max_interval`. A single success restores the check's frequency.
+/// A `threshold` of `0` disables backing off.
+///
+/// Failure streaks are dropped on success, and when idle for long, see [`IDLE_INTERVALS`].
+pub struct CircuitBreaker {
+    threshold: u32,
+    max_interval: Duration,
+    streaks: Mutex<Streaks>,
+}
+
+impl CircuitBreaker {
+    pub fn new(threshold: u32, max_interval: Duration) -> Self {
+        Self {
+            threshold,
+            max_interval,
+            streaks: Default::default(),
+        }
+    }
+
+    pub fn from_env() -> Self {
+        Self::new(
+            *eager_env::CIRCUIT_BREAKER_FAILURE_THRESHOLD,
+            Duration::from_secs(*eager_env::CIRCUIT_BREAKER_MAX_INTERVAL_SECONDS),
+        )
+    }
+
+    #[cfg(test)]
+    pub fn disabled() -> Self {
+        Self::new(0, Duration::ZERO)
+    }
+
+    /// Records the outcome of a probe of `check_id` at `now`.
+    ///
+    /// Returns whether the check recovered, i.e. it was backed off and its backoff must be reset.
+    pub fn record(&self, check_id: Uuid, success: bool, now: Instant) -> bool {
+        if self.threshold == 0 {
+            return false;
+        }
+
+        let mut streaks = self.streaks.lock().expect("circuit breaker lock poisoned");
+
+        if success {
+            let recovered = streaks
+                .by_check
+                .remove(&check_id)
+                .is_some_and(|streak| streak.failures >= self.threshold);
+            if recovered {
+                info!("Check {check_id} recovered, restoring its frequency");
+            }
+            return recovered;
+        }
+
+        let idle_after = self.max_interval.saturating_mul(IDLE_INTERVALS);
+        if streaks
+            .swept_at
+            .is_none_or(|swept_at| now.duration_since(swept_at) >= idle_after)
+        {
+            streaks
+                .by_check
+                .retain(|_, streak| now.duration_since(streak.last_failure_at) < idle_after);
+            streaks.swept_at = Some(now);
+        }
+
+        let streak = streaks.by_check.entry(check_id).or_insert(FailureStreak {
+            failures: 0,
+            last_failure_at: now,
+        });
+        streak.failures = streak.failures.saturating_add(1);
+        streak.last_failure_at = now;
+        if streak.failures == self.threshold {
+            info!(
+                "Check {check_id} failed {} times in a row, backing off",
+                streak.failures
+            );
+        }
+
+        false
+    }
+
+    /// Delay added to the `frequency` of `check_id`, zero unless its breaker is open.
+    pub fn backoff(&self, check_id: Uuid, frequency: Duration) -> Duration {
+        let failures = self
+            .streaks
+            .lock()
+            .expect("circuit breaker lock poisoned")
+            .by_check
+            .get(&check_id)
+            .map_or(0, |streak| streak.failures);
+
+        if self.threshold == 0 || failures < self.threshold {
+            return Duration::ZERO;
+        }
+
+        let doublings = (failures - self.threshold + 1).min(MAX_DOUBLINGS);
+        let interval = frequency
+            .saturating_mul(1 << doublings)
+            .min(self.max_interval)
+            .max(frequency);
+
+        interval - frequency
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backoff_doubles_up_to_max() {
+        let breaker = CircuitBreaker::new(3, Duration::from_secs(300));
+        let check_id = Uuid::new_v4();
+        let frequency = Duration::from_secs(30);
+        let now = Instant::now();
+
+        let backoffs: Vec<_> = (0..7)
+            .map(|_| {
+                breaker.record(check_id, false, now);
+                breaker.backoff(check_id, frequency).as_secs()
+            })
+            .collect();
+        assert_eq!(backoffs, [0, 0, 30, 90, 210, 270, 270]);
+
+        // Other checks are unaffected
+        assert_eq!(breaker.backoff(Uuid::new_v4(), frequency), Duration::ZERO);
+
+        assert!(breaker.record(check_id, true, now));
+        assert_eq!(breaker.backoff(check_id, frequency), Duration::ZERO);
+        assert!(!breaker.record(check_id, true, now));
+
+        // Never shortens checks slower than the max interval
+        for _ in 0..5 {
+            breaker.record(check_id, false, now);
+        }
+        assert_eq!(
+            breaker.backoff(check_id, Duration::from_secs(600)),
+            Duration::ZERO
+        );
+
+        let disabled = CircuitBreaker::disabled();
+        for _ in 0..10 {
+            disabled.record(check_id, false, now);
+        }
+        assert_eq!(disabled.backoff(check_id, frequency), Duration::ZERO);
+    }
+
+    #[test]
+    fn test_idle_streaks_are_evicted() {
+        let max_interval = Duration::from_secs(300);
+        let breaker = CircuitBreaker::new(3, max_interval);
+        let start = Instant::now();
+        let abandoned = Uuid::new_v4();
+        let failing = Uuid::new_v4();
+
+        for _ in 0..5 {
+            breaker.record(abandoned, false, start);
+        }
+        let mut now = start;
+        while now < start + max_interval * IDLE_INTERVALS {
+            breaker.record(failing, false, now);
+            now += max_interval;
+        }
+        assert_eq!(breaker.streaks.lock().unwrap().by_check.len(), 2);
+
+        // No longer probed for twice the max interval
+        breaker.record(failing, false, now);
+        let streaks = breaker.streaks.lock().unwrap();
+        assert!(!streaks.by_check.contains_key(&abandoned));
+        assert_eq!(streaks.by_check[&failing].failures, 3);
+    }
+}
@@ -0,0 +1,239 @@
+use crate::{
+    database::Database,
+    queries::check_results::{
+        CheckResultRow, GraphGranularity, PeriodAggregate, cache_aggregated_check_results,
+    },
+    regions::Region,
+    worker::check::execute::CheckResult,
+};
+use anyhow::{Result, anyhow};
+use chrono::{DateTime, DurationRound, Utc};
+use std::{collections::HashMap, sync::Mutex};
+use uuid::Uuid;
+
+/// A check and the start of one of its hourly or daily points
+type PeriodKey = (Uuid, GraphGranularity, DateTime<Utc>);
+
+/// Running aggregates of the checks not storing raw results, see `store_raw_results`.
+///
+/// Results are summarized in memory until their hour, and their day, is over, then merged into
+/// the hourly and daily caches along with what other nodes aggregated for the same points, e.g.
+/// when ranges move.
+pub struct ResultAggregator {
+    region: Region,
+    periods: Mutex<HashMap<PeriodKey, PeriodAggregate>>,
+}
+
+impl ResultAggregator {
+    pub fn new(region: Region) -> Self {
+        Self {
+            region,
+            periods: Default::default(),
+        }
+    }
+
+    pub fn record(&self, result: &CheckResult) {
+        let row = CheckResultRow {
+            check_started_at: result.check_started_at,
+            response_time_micros: result.response_time_micros,
+            status_code: result.status_code,
+            matches_expected: result.matches_expected,
+            response_size_bytes: result.response_size_bytes,
+            ttfb_micros: result.ttfb_micros,
+            region: self.region,
+            sample_weight: 1,
+        };
+
+        let mut periods = self.periods.lock().unwrap();
+        for granularity in [GraphGranularity::Hourly, GraphGranularity::Daily] {
+            let start = row
+                .check_started_at
+                .duration_trunc(granularity.step())
+                .expect("representable date");
+            periods
+                .entry((result.service_check_id, granularity, start))
+                .or_default()
+                .record(&row);
+        }
+    }
+
+    /// Removes the periods over at `now`, or all of them when `in_progress` too. Each comes with
+    /// whether it is still in progress.
+    fn take(
+        &self,
+        now: DateTime<Utc>,
+        in_progress: bool,
+    ) -> Vec<(PeriodKey, PeriodAggregate, bool)> {
+        let mut periods = self.periods.lock().unwrap();
+        let taken: Vec<_> = periods
+            .keys()
+            .filter(|(_, granularity, start)| in_progress || *start + granularity.step() <= now)
+            .copied()
+            .collect();
+
+        taken
+            .into_iter()
+            .filter_map(|key| periods.remove_entry(&key))
+            .map(|((check_id, granularity, start), aggregate)| {
+                let partial = start + granularity.step() > now;
+                ((check_id, granularity, start), aggregate, partial)
+            })
+            .collect()
+    }
+
+    /// Caches the aggregates of the periods over at `now`. With `in_progress`, e.g. on shutdown,
+    /// those of the periods in progress are cached too rather than lost, flagged as partial until
+    /// the node taking over merges the rest.
+    ///
+    /// Periods failing to be written are kept for the next flush. Returns the number of written
+    /// aggregates.
+    pub async fn flush(
+        &self,
+        db: &Database,
+        now: DateTime<Utc>,
+        in_progress: bool,
+    ) -> Result<usize> {
+        let mut written = 0;
+        let mut failed = Vec::new();
+        for ((check_id, granularity, start), aggregate, partial) in self.take(now, in_progress) {
+            match cache_aggregated_check_results(
+                db,
+                check_id,
+                self.region,
+                start,
+                granularity,
+                aggregate.clone(),
+                partial,
+            )
+            .await
+            {
+                Ok(()) => written += 1,
+                Err(e) => failed.push(((check_id, granularity, start), aggregate, e)),
+            }
+        }
+
+        let Some((_, _, first_error)) = failed.first() else {
+            return Ok(written);
+        };
+        let error = anyhow!("failed to cache {} aggregates: {first_error}", failed.len());
+        let mut periods = self.periods.lock().unwrap();
+        for (key, aggregate, _) in failed {
+            periods.entry(key).or_default().merge(aggregate);
+        }
+        Err(error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        database::testing::create_test_database, queries::check_results::get_check_metrics_graph,
+    };
+    use chrono::Duration;
+
+    fn result(check_id: Uuid, started_at: DateTime<Utc>) -> CheckResult {
+        CheckResult {
+            result_id: Uuid::new_v4(),
+            service_check_id: check_id,
+            check_started_at: started_at,
+            response_time_micros: 1500,
+            ttfb_micros: None,
+            status_code: Some(200),
+            matches_expected: true,
+            response_body_fetched: false,
+            response_body: None,
+            response_size_bytes: None,
+            decompressed_size_bytes: None,
+            failed_step: None,
+            fallback_index: None,
+            failure_reason: None,
+            failure_detail: None,
+            assertion_results: None,
+            tls_version: None,
+        }
+    }
+
+    #[test]
+    fn test_periods_complete_in_order() {
+        let aggregator = ResultAggregator::new(Region::Fsn1);
+        let check_id = Uuid::new_v4();
+        let midnight = "2025-11-29T00:00:00Z".parse::<DateTime<Utc>>().unwrap();
+
+        // Every 10 minutes during the first two hours
+        for i in 0..12 {
+            aggregator.record(&result(check_id, midnight + Duration::minutes(10 * i)));
+        }
+
+        assert!(aggregator.take(midnight, false).is_empty());
+
+        let mut completed = aggregator.take(midnight + Duration::minutes(90), false);
+        assert_eq!(completed.len(), 1);
+        let ((_, granularity, start), aggregate, partial) = completed.remove(0);
+        assert_eq!((granularity, start), (GraphGranularity::Hourly, midnight));
+        assert_eq!(aggregate.metrics().total_checks, 6);
+        assert!(!partial);
+
+        // The day keeps all of them
+        let mut completed = aggregator.take(midnight + Duration::days(1), false);
+        completed
+            .sort_by_key(|((_, granularity, _), _, _)| *granularity == GraphGranularity::Daily);
+        let totals: Vec<_> = completed
+            .iter()
+            .map(|((_, granularity, _), aggregate, partial)| {
+                (*granularity, aggregate.metrics().total_checks, *partial)
+            })
+            .collect();
+        assert_eq!(
+            totals,
+            [
+                (GraphGranularity::Hourly, 6, false),
+                (GraphGranularity::Daily, 12, false)
+            ]
+        );
+
+        // Periods in progress are only taken on shutdown, and flagged as partial
+        let now = midnight + Duration::days(1);
+        aggregator.record(&result(check_id, now));
+        assert!(aggregator.take(now, false).is_empty());
+        let taken = aggregator.take(now, true);
+        assert_eq!(taken.len(), 2);
+        assert!(taken.iter().all(|(_, _, partial)| *partial));
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_flushes_into_the_same_point() -> Result<()> {
+        let (db, _keyspace) = create_test_database(None).await?;
+        let check_id = Uuid::new_v4();
+        let hour = "2025-11-29T10:00:00Z".parse::<DateTime<Utc>>().unwrap();
+
+        // Two nodes of the same region, e.g. while a range moves between them
+        let first = ResultAggregator::new(Region::Fsn1);
+        let second = ResultAggregator::new(Region::Fsn1);
+        for i in 0..6 {
+            first.record(&result(check_id, hour + Duration::minutes(i)));
+            second.record(&result(check_id, hour + Duration::minutes(30 + i)));
+        }
+
+        let now = hour + Duration::days(1);
+        let (first_written, second_written) =
+            tokio::join!(first.flush(&db, now, false), second.flush(&db, now, false));
+        assert_eq!((first_written?, second_written?), (2, 2));
+
+        // Neither merge overwrote the other
+        let graph = get_check_metrics_graph(
+            &db,
+            &db,
+            check_id,
+            &[Region::Fsn1],
+            hour,
+            hour + Duration::hours(1),
+            GraphGranularity::Hourly,
+        )
+        .await?;
+        assert_eq!(graph.len(), 1);
+        assert_eq!(graph[0].by_region[&Region::Fsn1].total_checks, 12);
+
+        Ok(())
+    }
+}
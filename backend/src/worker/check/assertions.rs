@@ -0,0 +1,174 @@
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use utoipa::ToSchema;
+
+/// A single condition on the response of a probe.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Assertion {
+    StatusCode {
+        equals: i32,
+    },
+    /// Only the first [`MAX_BODY_ASSERTION_BYTES`](super::body::MAX_BODY_ASSERTION_BYTES) of the
+    /// body are searched
+    BodyContains {
+        text: String,
+    },
+    /// Time until the response headers, as recorded in `ttfb_micros`
+    ResponseTimeBelow {
+        millis: i64,
+    },
+}
+
+/// How the results of the assertions combine into the result of the probe.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Combinator {
+    /// Every assertion must pass
+    #[default]
+    All,
+    /// At least one assertion must pass
+    Any,
+}
+
+/// Assertions of a check, on top of its `expected_status_code`, `body_regex` and `geo_assertion`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct Assertions {
+    #[serde(default)]
+    pub combinator: Combinator,
+    pub assertions: Vec<Assertion>,
+}
+
+/// Outcome of one assertion, recorded with the check result.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AssertionResult {
+    pub assertion: Assertion,
+    pub passed: bool,
+}
+
+/// What assertions are evaluated against.
+pub struct AssertionInput<'a> {
+    pub status_code: i32,
+    /// `None` when the body wasn't read, e.g. for `HEAD` requests
+    pub body: Option<&'a [u8]>,
+    pub response_time: Duration,
+}
+
+impl Assertion {
+    fn evaluate(&self, input: &AssertionInput) -> bool {
+        match self {
+            Assertion::StatusCode { equals } => input.status_code == *equals,
+            Assertion::BodyContains { text } => input
+                .body
+                .is_some_and(|body| String::from_utf8_lossy(body).contains(text.as_str())),
+            Assertion::ResponseTimeBelow { millis } => {
+                input.response_time.as_millis() < (*millis).max(0) as u128
+            }
+        }
+    }
+}
+
+impl Assertions {
+    /// Whether the body must be read to evaluate the assertions
+    pub fn needs_body(&self) -> bool {
+        self.assertions
+            .iter()
+            .any(|assertion| matches!(assertion, Assertion::BodyContains { .. }))
+    }
+
+    /// Evaluates every assertion, returning their combined result along with each one's.
+    pub fn evaluate(&self, input: &AssertionInput) -> (bool, Vec<AssertionResult>) {
+        let results: Vec<_> = self
+            .assertions
+            .iter()
+            .map(|assertion| AssertionResult {
+                assertion: assertion.clone(),
+                passed: assertion.evaluate(input),
+            })
+            .collect();
+
+        let passed = match self.combinator {
+            Combinator::All => results.iter().all(|result| result.passed),
+            Combinator::Any => results.iter().any(|result| result.passed),
+        };
+
+        (passed, results)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assertions(combinator: Combinator) -> Assertions {
+        Assertions {
+            combinator,
+            assertions: vec![
+                Assertion::StatusCode { equals: 200 },
+                Assertion::BodyContains {
+                    text: "ok".to_string(),
+                },
+                Assertion::ResponseTimeBelow { millis: 300 },
+            ],
+        }
+    }
+
+    #[test]
+    fn test_all_combinator() {
+        let input = AssertionInput {
+            status_code: 200,
+            body: Some(b"status: ok"),
+            response_time: Duration::from_millis(120),
+        };
+        let (passed, results) = assertions(Combinator::All).evaluate(&input);
+        assert!(passed);
+        assert!(results.iter().all(|r| r.passed));
+
+        // Too slow
+        let input = AssertionInput {
+            response_time: Duration::from_millis(300),
+            ..input
+        };
+        let (passed, results) = assertions(Combinator::All).evaluate(&input);
+        assert!(!passed);
+        assert_eq!(
+            results.iter().map(|r| r.passed).collect::<Vec<_>>(),
+            [true, true, false]
+        );
+    }
+
+    #[test]
+    fn test_any_combinator() {
+        // Only the response time passes
+        let input = AssertionInput {
+            status_code: 503,
+            body: Some(b"unavailable"),
+            response_time: Duration::from_millis(50),
+        };
+        let (passed, results) = assertions(Combinator::Any).evaluate(&input);
+        assert!(passed);
+        assert_eq!(
+            results.iter().map(|r| r.passed).collect::<Vec<_>>(),
+            [false, false, true]
+        );
+
+        // None passes, and an unread body never contains the text
+        let input = AssertionInput {
+            body: None,
+            response_time: Duration::from_secs(1),
+            ..input
+        };
+        let (passed, _) = assertions(Combinator::Any).evaluate(&input);
+        assert!(!passed);
+    }
+
+    #[test]
+    fn test_deserialize() {
+        let assertions: Assertions = serde_json::from_str(
+            r#"{"assertions": [{"type": "status_code", "equals": 200}, {"type": "response_time_below", "millis": 300}]}"#,
+        )
+        .unwrap();
+        assert_eq!(assertions.combinator, Combinator::All);
+        assert!(!assertions.needs_body());
+    }
+}
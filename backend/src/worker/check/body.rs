@@ -0,0 +1,243 @@
+use flate2::read::GzDecoder;
+use regex::bytes::{Regex, RegexBuilder};
+use reqwest::{Response, header};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::io::{self, Read};
+use std::str::FromStr;
+
+/// Only this many bytes of the response body are read and matched against `body_regex`
+pub const MAX_BODY_ASSERTION_BYTES: usize = 64 * 1024;
+
+/// Bounds the compiled size of user supplied patterns
+const REGEX_SIZE_LIMIT: usize = 1024 * 1024;
+
+/// Encodings advertised by checks with `decompress_response`
+pub const ACCEPT_ENCODING: &str = "gzip, br";
+
+/// Bodies are only downloaded up to this size on the wire, bounding the transfer of a probe
+pub const MAX_RESPONSE_BYTES: u64 = 16 * 1024 * 1024;
+
+/// Decompressed bodies are only measured up to this size, bounding decompression bombs
+pub const MAX_DECOMPRESSED_BYTES: u64 = 16 * 1024 * 1024;
+
+/// Pattern the response body must match, compiled once when the check is loaded.
+#[derive(Debug, Clone)]
+pub struct BodyRegex(Regex);
+
+impl FromStr for BodyRegex {
+    type Err = regex::Error;
+
+    fn from_str(pattern: &str) -> Result<Self, Self::Err> {
+        RegexBuilder::new(pattern)
+            .size_limit(REGEX_SIZE_LIMIT)
+            .build()
+            .map(Self)
+    }
+}
+
+impl BodyRegex {
+    pub fn is_match(&self, body: &[u8]) -> bool {
+        self.0.is_match(body)
+    }
+}
+
+impl Serialize for BodyRegex {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.0.as_str())
+    }
+}
+
+impl<'de> Deserialize<'de> for BodyRegex {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+/// Reads at most `limit` bytes of the body.
+///
+/// Also returns the size from `Content-Length`, or from the read body if it was read whole.
+pub async fn read_body_prefix(
+    mut response: Response,
+    limit: usize,
+) -> reqwest::Result<(Vec<u8>, Option<i64>)> {
+    let declared = response
+        .headers()
+        .get(header::CONTENT_LENGTH)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse::<i64>().ok());
+
+    let mut body = Vec::new();
+    let mut truncated = false;
+
+    while let Some(chunk) = response.chunk().await? {
+        let remaining = limit - body.len();
+        if chunk.len() >= remaining {
+            // The rest of the body is never read, so its size is unknown
+            body.extend_from_slice(&chunk[..remaining]);
+            truncated = true;
+            break;
+        }
+        body.extend_from_slice(&chunk);
+    }
+
+    let size = declared.or((!truncated).then_some(body.len() as i64));
+
+    Ok((body, size))
+}
+
+/// Response body read whole, then decompressed according to its `Content-Encoding`.
+pub struct DecompressedBody {
+    /// Start of the decompressed body, or of the raw one if its encoding is unsupported
+    pub prefix: Vec<u8>,
+    /// Size of the body as transferred
+    pub compressed_size: i64,
+    /// `None` if the encoding is unsupported or the body exceeds [`MAX_DECOMPRESSED_BYTES`]
+    pub decompressed_size: Option<i64>,
+}
+
+fn decoder<'a>(encoding: Option<&str>, body: &'a [u8]) -> Option<Box<dyn Read + 'a>> {
+    match encoding.map(str::trim) {
+        None => Some(Box::new(body)),
+        Some(encoding) if encoding.eq_ignore_ascii_case("identity") => Some(Box::new(body)),
+        Some(encoding)
+            if encoding.eq_ignore_ascii_case("gzip") || encoding.eq_ignore_ascii_case("x-gzip") =>
+        {
+            Some(Box::new(GzDecoder::new(body)))
+        }
+        Some(encoding) if encoding.eq_ignore_ascii_case("br") => {
+            Some(Box::new(brotli::Decompressor::new(body, 4096)))
+        }
+        Some(_) => None,
+    }
+}
+
+/// Reads the whole body, up to `max_compressed_bytes` as transferred, and decompresses it,
+/// keeping at most `limit` decompressed bytes. Decompression runs on the blocking pool.
+///
+/// Fails if the body can't be read, exceeds `max_compressed_bytes` or is corrupt.
+pub async fn read_decompressed(
+    mut response: Response,
+    limit: usize,
+    max_compressed_bytes: u64,
+) -> io::Result<DecompressedBody> {
+    let encoding = response
+        .headers()
+        .get(header::CONTENT_ENCODING)
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_string);
+
+    let mut body = Vec::new();
+    while let Some(chunk) = response.chunk().await.map_err(io::Error::other)? {
+        if (body.len() + chunk.len()) as u64 > max_compressed_bytes {
+            return Err(io::Error::other(format!(
+                "body exceeds {max_compressed_bytes} bytes"
+            )));
+        }
+        body.extend_from_slice(&chunk);
+    }
+
+    tokio::task::spawn_blocking(move || decompress(encoding.as_deref(), &body, limit))
+        .await
+        .map_err(io::Error::other)?
+}
+
+fn decompress(encoding: Option<&str>, body: &[u8], limit: usize) -> io::Result<DecompressedBody> {
+    let compressed_size = body.len() as i64;
+
+    let Some(mut decoder) = decoder(encoding, body) else {
+        return Ok(DecompressedBody {
+            prefix: body[..body.len().min(limit)].to_vec(),
+            compressed_size,
+            decompressed_size: None,
+        });
+    };
+
+    let mut prefix = Vec::new();
+    decoder
+        .by_ref()
+        .take(limit as u64)
+        .read_to_end(&mut prefix)?;
+
+    // Only counted, reading one byte past the cap to detect oversized bodies
+    let remaining = MAX_DECOMPRESSED_BYTES.saturating_sub(prefix.len() as u64) + 1;
+    let size = prefix.len() as u64 + io::copy(&mut decoder.take(remaining), &mut io::sink())?;
+
+    Ok(DecompressedBody {
+        prefix,
+        compressed_size,
+        decompressed_size: (size <= MAX_DECOMPRESSED_BYTES).then_some(size as i64),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_body_regex_parsing() {
+        assert!("(unclosed".parse::<BodyRegex>().is_err());
+        // Compiles to more than the size limit
+        assert!(r"\w{1000}{1000}".parse::<BodyRegex>().is_err());
+
+        let regex: BodyRegex = r"v\d+\.\d+".parse().unwrap();
+        assert!(regex.is_match(b"running v2.14"));
+        assert!(!regex.is_match(b"running v2"));
+        assert_eq!(serde_json::to_string(&regex).unwrap(), r#""v\\d+\\.\\d+""#);
+    }
+
+    #[test]
+    fn test_decoder() {
+        use flate2::{Compression, write::GzEncoder};
+        use std::io::Write;
+
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(b"hello").unwrap();
+        let compressed = encoder.finish().unwrap();
+
+        let mut decoded = Vec::new();
+        decoder(Some("GZIP"), &compressed)
+            .unwrap()
+            .read_to_end(&mut decoded)
+            .unwrap();
+        assert_eq!(decoded, b"hello");
+
+        assert!(decoder(None, b"hello").is_some());
+        assert!(decoder(Some("identity"), b"hello").is_some());
+        assert!(decoder(Some("zstd"), b"hello").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_read_decompressed_bounds_the_transfer() {
+        use flate2::{Compression, write::GzEncoder};
+        use httpmock::prelude::*;
+        use std::io::Write;
+
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&b"hello ".repeat(1000)).unwrap();
+        let compressed = encoder.finish().unwrap();
+
+        let server = MockServer::start();
+        server.mock(|when, then| {
+            when.method(GET).path("/");
+            then.status(200)
+                .header("content-encoding", "gzip")
+                .body(&compressed);
+        });
+        let get = async || reqwest::get(server.url("/")).await.unwrap();
+
+        let body = read_decompressed(get().await, 5, compressed.len() as u64)
+            .await
+            .unwrap();
+        assert_eq!(body.prefix, b"hello");
+        assert_eq!(body.compressed_size, compressed.len() as i64);
+        assert_eq!(body.decompressed_size, Some(6000));
+
+        assert!(
+            read_decompressed(get().await, 5, compressed.len() as u64 - 1)
+                .await
+                .is_err()
+        );
+    }
+}
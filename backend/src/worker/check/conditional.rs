@@ -0,0 +1,90 @@
+use chrono::{DateTime, Utc};
+use reqwest::{RequestBuilder, StatusCode, header};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::worker::check::status::ExpectedStatusCodes;
+
+/// Validators sent with every probe, making it a conditional request.
+///
+/// When configured, a `304 Not Modified` response is healthy too.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct ConditionalRequest {
+    /// Sent as `If-None-Match`
+    #[serde(default)]
+    pub etag: Option<String>,
+    /// Sent as `If-Modified-Since`
+    #[serde(default)]
+    pub modified_since: Option<DateTime<Utc>>,
+}
+
+impl ConditionalRequest {
+    /// Builds the config from its stored columns, `None` when no validator is set.
+    pub fn from_columns(
+        etag: Option<String>,
+        modified_since: Option<DateTime<Utc>>,
+    ) -> Option<Self> {
+        (etag.is_some() || modified_since.is_some()).then_some(Self {
+            etag,
+            modified_since,
+        })
+    }
+
+    pub fn apply(&self, mut request: RequestBuilder) -> RequestBuilder {
+        if let Some(etag) = &self.etag {
+            request = request.header(header::IF_NONE_MATCH, etag);
+        }
+
+        if let Some(modified_since) = self.modified_since {
+            request = request.header(header::IF_MODIFIED_SINCE, to_http_date(modified_since));
+        }
+
+        request
+    }
+}
+
+/// `expected_status_code` accepting any received response: only connection errors and
+/// timeouts fail
+pub const ANY_STATUS_CODE: i32 = 0;
+
+/// Whether `status_code` is healthy, accepting `304` for conditional requests.
+pub fn is_acceptable_status(
+    status_code: i32,
+    expected: &ExpectedStatusCodes,
+    conditional: Option<&ConditionalRequest>,
+) -> bool {
+    expected.contains(status_code)
+        || (conditional.is_some() && status_code == StatusCode::NOT_MODIFIED.as_u16() as i32)
+}
+
+/// Formats as an IMF-fixdate, e.g. `Sun, 06 Nov 1994 08:49:37 GMT`
+fn to_http_date(time: DateTime<Utc>) -> String {
+    time.format("%a, %d %b %Y %H:%M:%S GMT").to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_to_http_date() {
+        let time = DateTime::from_timestamp(784111777, 0).unwrap();
+        assert_eq!(to_http_date(time), "Sun, 06 Nov 1994 08:49:37 GMT");
+    }
+
+    #[test]
+    fn test_is_acceptable_status() {
+        let conditional = ConditionalRequest {
+            etag: Some("\"v1\"".to_string()),
+            modified_since: None,
+        };
+
+        assert!(is_acceptable_status(200, &200.into(), None));
+        assert!(!is_acceptable_status(304, &200.into(), None));
+        assert!(is_acceptable_status(304, &200.into(), Some(&conditional)));
+        assert!(!is_acceptable_status(500, &200.into(), Some(&conditional)));
+
+        assert!(is_acceptable_status(500, &ANY_STATUS_CODE.into(), None));
+        assert!(is_acceptable_status(404, &ANY_STATUS_CODE.into(), None));
+    }
+}
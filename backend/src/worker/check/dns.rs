@@ -0,0 +1,248 @@
+use anyhow::{Context, Result, bail};
+use std::collections::HashMap;
+use std::fmt;
+use std::net::{IpAddr, SocketAddr};
+use std::str::FromStr;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Most hosts kept by a [`DnsCache`], the oldest resolutions are evicted beyond it
+const MAX_CACHED_HOSTS: usize = 10_000;
+
+struct ResolvedAddrs {
+    addrs: Vec<SocketAddr>,
+    resolved_at: Instant,
+    /// TTL of the check that resolved the host, after which the entry is evicted
+    ttl: Duration,
+}
+
+impl ResolvedAddrs {
+    fn is_expired(&self) -> bool {
+        self.resolved_at.elapsed() >= self.ttl
+    }
+}
+
+/// Context of the errors caused by a host not resolving, as opposed to resolving to addresses
+/// that are not allowed. Find it with [`is_resolution_failure`].
+#[derive(Debug)]
+pub struct ResolutionFailed;
+
+impl fmt::Display for ResolutionFailed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("DNS resolution failed")
+    }
+}
+
+/// Whether `error` was caused by a host not resolving, through any added context.
+pub fn is_resolution_failure(error: &anyhow::Error) -> bool {
+    error.downcast_ref::<ResolutionFailed>().is_some()
+}
+
+/// Address families the node can probe, for nodes without IPv4 or IPv6 egress.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum IpVersionPreference {
+    #[default]
+    Any,
+    V4,
+    V6,
+}
+
+impl IpVersionPreference {
+    pub fn allows(self, ip: &IpAddr) -> bool {
+        match self {
+            IpVersionPreference::Any => true,
+            IpVersionPreference::V4 => ip.is_ipv4(),
+            IpVersionPreference::V6 => ip.is_ipv6(),
+        }
+    }
+}
+
+impl fmt::Display for IpVersionPreference {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            IpVersionPreference::Any => "any",
+            IpVersionPreference::V4 => "IPv4",
+            IpVersionPreference::V6 => "IPv6",
+        })
+    }
+}
+
+impl FromStr for IpVersionPreference {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "any" => Ok(IpVersionPreference::Any),
+            "v4" => Ok(IpVersionPreference::V4),
+            "v6" => Ok(IpVersionPreference::V6),
+            _ => bail!("Unsupported IP version preference: '{s}'"),
+        }
+    }
+}
+
+/// Resolved addresses shared across probes, so that frequent checks don't hit the resolver
+/// on every execution.
+///
+/// Entries are stored unfiltered: callers must still validate the returned addresses.
+/// Expired entries are evicted whenever a host is added, and at most [`MAX_CACHED_HOSTS`] are
+/// kept.
+#[derive(Default)]
+pub struct DnsCache {
+    entries: Mutex<HashMap<(String, u16), ResolvedAddrs>>,
+}
+
+impl DnsCache {
+    /// Resolves `host`, reusing a previous resolution younger than `ttl`.
+    ///
+    /// With no `ttl` the cache is bypassed entirely.
+    pub async fn lookup(
+        &self,
+        host: &str,
+        port: u16,
+        ttl: Option<Duration>,
+    ) -> Result<Vec<SocketAddr>> {
+        let key = (host.to_string(), port);
+
+        if let Some(ttl) = ttl
+            && let Some(entry) = self.entries.lock().expect("not poisoned").get(&key)
+            && entry.resolved_at.elapsed() < ttl
+        {
+            return Ok(entry.addrs.clone());
+        }
+
+        let addrs: Vec<SocketAddr> = tokio::net::lookup_host((host, port))
+            .await
+            .context(ResolutionFailed)?
+            .collect();
+
+        if let Some(ttl) = ttl
+            && !addrs.is_empty()
+        {
+            let mut entries = self.entries.lock().expect("not poisoned");
+            entries.retain(|_, entry| !entry.is_expired());
+            if entries.len() >= MAX_CACHED_HOSTS
+                && !entries.contains_key(&key)
+                && let Some(oldest) = entries
+                    .iter()
+                    .min_by_key(|(_, entry)| entry.resolved_at)
+                    .map(|(key, _)| key.clone())
+            {
+                entries.remove(&oldest);
+            }
+            entries.insert(
+                key,
+                ResolvedAddrs {
+                    addrs: addrs.clone(),
+                    resolved_at: Instant::now(),
+                    ttl,
+                },
+            );
+        }
+
+        Ok(addrs)
+    }
+
+    #[cfg(test)]
+    fn resolved_at(&self, host: &str, port: u16) -> Option<Instant> {
+        self.entries
+            .lock()
+            .unwrap()
+            .get(&(host.to_string(), port))
+            .map(|e| e.resolved_at)
+    }
+
+    #[cfg(test)]
+    fn len(&self) -> usize {
+        self.entries.lock().unwrap().len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ip_version_preference() {
+        let v4: IpAddr = "93.184.216.34".parse().unwrap();
+        let v6: IpAddr = "2606:2800:220:1::".parse().unwrap();
+
+        assert_eq!(
+            "V6".parse::<IpVersionPreference>().unwrap(),
+            IpVersionPreference::V6
+        );
+        assert!("v5".parse::<IpVersionPreference>().is_err());
+
+        assert!(IpVersionPreference::Any.allows(&v4) && IpVersionPreference::Any.allows(&v6));
+        assert!(IpVersionPreference::V4.allows(&v4) && !IpVersionPreference::V4.allows(&v6));
+        assert!(!IpVersionPreference::V6.allows(&v4) && IpVersionPreference::V6.allows(&v6));
+    }
+
+    #[tokio::test]
+    async fn test_lookup_within_ttl_resolves_once() {
+        let cache = DnsCache::default();
+        let ttl = Some(Duration::from_secs(60));
+
+        let first = cache.lookup("localhost", 80, ttl).await.unwrap();
+        let resolved_at = cache.resolved_at("localhost", 80).unwrap();
+
+        let second = cache.lookup("localhost", 80, ttl).await.unwrap();
+
+        assert_eq!(first, second);
+        assert_eq!(cache.resolved_at("localhost", 80), Some(resolved_at));
+    }
+
+    #[tokio::test]
+    async fn test_lookup_expired_or_disabled() {
+        let cache = DnsCache::default();
+
+        // No TTL: nothing is cached
+        cache.lookup("localhost", 80, None).await.unwrap();
+        assert_eq!(cache.resolved_at("localhost", 80), None);
+
+        // Zero TTL: every lookup resolves again
+        cache
+            .lookup("localhost", 80, Some(Duration::ZERO))
+            .await
+            .unwrap();
+        let resolved_at = cache.resolved_at("localhost", 80).unwrap();
+        cache
+            .lookup("localhost", 80, Some(Duration::ZERO))
+            .await
+            .unwrap();
+        assert!(cache.resolved_at("localhost", 80).unwrap() > resolved_at);
+    }
+
+    #[tokio::test]
+    async fn test_expired_entries_are_evicted() {
+        let cache = DnsCache::default();
+
+        cache
+            .lookup("localhost", 80, Some(Duration::ZERO))
+            .await
+            .unwrap();
+        cache
+            .lookup("localhost", 81, Some(Duration::from_secs(60)))
+            .await
+            .unwrap();
+
+        // The first entry expired immediately
+        assert_eq!(cache.len(), 1);
+        assert!(cache.resolved_at("localhost", 81).is_some());
+    }
+
+    #[tokio::test]
+    async fn test_oldest_entry_evicted_when_full() {
+        let cache = DnsCache::default();
+        let ttl = Some(Duration::from_secs(60));
+
+        for port in 0..MAX_CACHED_HOSTS as u16 {
+            cache.lookup("127.0.0.1", port, ttl).await.unwrap();
+        }
+        assert_eq!(cache.len(), MAX_CACHED_HOSTS);
+
+        cache.lookup("localhost", 80, ttl).await.unwrap();
+        assert_eq!(cache.len(), MAX_CACHED_HOSTS);
+        assert_eq!(cache.resolved_at("127.0.0.1", 0), None);
+        assert!(cache.resolved_at("localhost", 80).is_some());
+    }
+}
@@ -1,8 +1,21 @@
+use crate::eager_env;
+use crate::worker::check::assertions::{AssertionInput, AssertionResult, Assertions};
+use crate::worker::check::body::{
+    ACCEPT_ENCODING, MAX_BODY_ASSERTION_BYTES, MAX_RESPONSE_BYTES, read_body_prefix,
+    read_decompressed,
+};
+use crate::worker::check::conditional::is_acceptable_status;
+use crate::worker::check::dns::{DnsCache, ResolutionFailed, is_resolution_failure};
+use crate::worker::check::proxy::HostAllowlist;
+use crate::worker::check::steps::{self, CheckKind};
+use crate::worker::check::tls;
 use crate::worker::fetch::{self, ServiceCheck};
-use anyhow::{Context, Result, bail};
+use anyhow::{Context, Result, anyhow, bail};
 use chrono::{DateTime, Utc};
-use log::trace;
-use reqwest::{Client, Method, header};
+use log::{trace, warn};
+use reqwest::{Client, ClientBuilder, Method, Response, header};
+use serde::{Deserialize, Serialize};
+use std::error::Error;
 use std::net::{IpAddr, SocketAddr};
 use std::time::{Duration, Instant};
 use url::Url;
@@ -12,14 +25,141 @@ pub struct CheckResult {
     pub result_id: Uuid,
     pub service_check_id: Uuid,
     pub check_started_at: DateTime<Utc>,
+    /// Time until the body was read, see [`get_response_size`]
     pub response_time_micros: i64,
+    /// Time until the response headers, `None` when no response was received
+    pub ttfb_micros: Option<i64>,
     pub status_code: Option<i32>,
     pub matches_expected: bool,
     pub response_body_fetched: bool,
     pub response_body: Option<String>,
+    /// Size of the response body, from `Content-Length` or the read body.
+    /// Always `None` for `HEAD` requests.
+    ///
+    /// Bodies are measured as transferred, i.e. compressed if the server compressed them.
+    pub response_size_bytes: Option<i64>,
+    /// Size of the body once decompressed, only set for checks with `decompress_response`
+    pub decompressed_size_bytes: Option<i64>,
+    /// Index of the first failing step, only set for `Steps` checks
+    pub failed_step: Option<i32>,
+    /// Index in `fallback_urls` of the URL that succeeded after the primary failed
+    pub fallback_index: Option<i32>,
+    /// Set whenever `matches_expected` is `false`
+    pub failure_reason: Option<FailureReason>,
+    /// The underlying error, when the request itself failed
+    pub failure_detail: Option<String>,
+    /// Outcome of each of the check's `assertions`, `None` when they weren't evaluated
+    pub assertion_results: Option<Vec<AssertionResult>>,
+    /// TLS version negotiated with the target, e.g. `TLSv1.3`. Only recorded for HTTPS checks
+    /// with `min_tls_version` that got a response, see [`tls::negotiated_version`]
+    pub tls_version: Option<String>,
 }
 
-fn is_safe_ip(ip: &IpAddr, accept_local: bool) -> bool {
+/// Why a probe did not match the expectations.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum FailureReason {
+    /// The host did not resolve to any address
+    Dns,
+    /// The response was not received within the check's timeout
+    Timeout,
+    /// No connection was established within the check's `connect_timeout_millis`
+    ConnectTimeout,
+    Connect,
+    /// Handshake or certificate verification failed, e.g. untrusted chain or hostname mismatch.
+    /// Often caused by the monitor's trust store rather than by the service being down.
+    Tls,
+    /// The handshake failed to agree on a TLS version, e.g. the service only supports versions
+    /// older than the check's `min_tls_version`
+    TlsVersion,
+    Request,
+    Body,
+    UnexpectedStatus,
+    /// A `Steps` check could not extract a value from a response
+    Extraction,
+    /// The response was not served from the location expected for the monitoring region
+    RegionMismatch,
+    /// The response body did not match the check's `body_regex`
+    BodyMismatch,
+    /// The check's `assertions` did not pass, see `assertion_results`
+    AssertionFailed,
+    /// A `Passive` check received no ping in time
+    MissedPing,
+}
+
+impl FailureReason {
+    /// Failures that may not happen again right away, worth retrying, see the check's `retries`.
+    /// TLS failures are not: they are either a setup issue or persistent, like an expired certificate
+    pub fn is_transient(self) -> bool {
+        matches!(
+            self,
+            FailureReason::Timeout | FailureReason::ConnectTimeout | FailureReason::Connect
+        )
+    }
+}
+
+/// Durations of a single HTTP request, in microseconds.
+#[derive(Copy, Clone)]
+struct ProbeTimings {
+    /// Until the body was read, or the request failed
+    total_micros: i64,
+    /// Until the response headers, `None` without a response
+    ttfb_micros: Option<i64>,
+}
+
+impl ProbeTimings {
+    fn failed(start: Instant) -> Self {
+        Self {
+            total_micros: start.elapsed().as_micros() as i64,
+            ttfb_micros: None,
+        }
+    }
+}
+
+/// Result of a single HTTP request, before it becomes a [`CheckResult`].
+pub struct ProbeOutcome {
+    pub status_code: Option<i32>,
+    pub matches_expected: bool,
+    pub response_size_bytes: Option<i64>,
+    pub decompressed_size_bytes: Option<i64>,
+    pub failure_reason: Option<FailureReason>,
+    pub failure_detail: Option<String>,
+    pub assertion_results: Option<Vec<AssertionResult>>,
+    pub tls_version: Option<String>,
+}
+
+impl ProbeOutcome {
+    /// Outcome of a request that failed with a genuine error.
+    pub fn from_error(error: &reqwest::Error) -> Self {
+        Self {
+            status_code: None,
+            matches_expected: false,
+            response_size_bytes: None,
+            decompressed_size_bytes: None,
+            failure_reason: Some(classify_error(error)),
+            failure_detail: Some(error_chain(error)),
+            assertion_results: None,
+            tls_version: None,
+        }
+    }
+
+    /// Outcome of a request never sent because its host did not resolve,
+    /// see [`is_resolution_failure`].
+    pub fn from_resolution_error(error: &anyhow::Error) -> Self {
+        Self {
+            status_code: None,
+            matches_expected: false,
+            response_size_bytes: None,
+            decompressed_size_bytes: None,
+            failure_reason: Some(FailureReason::Dns),
+            failure_detail: Some(format!("{error:#}")),
+            assertion_results: None,
+            tls_version: None,
+        }
+    }
+}
+
+pub fn is_safe_ip(ip: &IpAddr, accept_local: bool) -> bool {
     if accept_local {
         return true;
     }
@@ -44,7 +184,14 @@ fn is_safe_ip(ip: &IpAddr, accept_local: bool) -> bool {
 
 /// Validates the URL's resolved IP addresses and transforms the URL to use the IP directly.
 /// Returns the transformed URL and the original host for the Host header.
-pub async fn validate_and_transform_url(url: &Url, accept_local: bool) -> Result<(Url, String)> {
+///
+/// Resolutions younger than `dns_ttl` are reused from `dns_cache`, but always re-validated.
+pub async fn validate_and_transform_url(
+    url: &Url,
+    accept_local: bool,
+    dns_cache: &DnsCache,
+    dns_ttl: Option<Duration>,
+) -> Result<(Url, String)> {
     let original_host = url.host_str().context("URL missing host")?.to_string();
 
     // Should always work for http(s)
@@ -53,13 +200,28 @@ pub async fn validate_and_transform_url(url: &Url, accept_local: bool) -> Result
         .context("Unable to determine port")?;
 
     // Resolve DNS
-    let addrs: Vec<SocketAddr> = tokio::net::lookup_host((original_host.as_str(), port))
-        .await
-        .context("DNS resolution failed")?
-        .collect();
+    let addrs: Vec<SocketAddr> = dns_cache
+        .lookup(original_host.as_str(), port, dns_ttl)
+        .await?;
 
     if addrs.is_empty() {
-        bail!("No IP addresses resolved for host: {}", original_host);
+        return Err(
+            anyhow!("No IP addresses resolved for host: {}", original_host)
+                .context(ResolutionFailed),
+        );
+    }
+
+    // Addresses the node can't reach are as good as unresolved
+    let preference = *eager_env::IP_VERSION_PREFERENCE;
+    let addrs: Vec<SocketAddr> = addrs
+        .into_iter()
+        .filter(|addr| preference.allows(&addr.ip()))
+        .collect();
+    if addrs.is_empty() {
+        return Err(
+            anyhow!("No {preference} addresses resolved for host: {original_host}")
+                .context(ResolutionFailed),
+        );
     }
 
     // Find first safe IP
@@ -88,88 +250,469 @@ pub async fn validate_and_transform_url(url: &Url, accept_local: bool) -> Result
     Ok((ip_url, original_host))
 }
 
-pub async fn execute_check(
-    client: &Client,
-    check: &ServiceCheck,
+/// Decides whether the probes of a check may be sent to a URL.
+#[derive(Clone, Copy)]
+pub struct TargetValidator<'a> {
+    dns_cache: &'a DnsCache,
+    dns_ttl: Option<Duration>,
     accept_local: bool,
-) -> Result<CheckResult> {
-    trace!(
-        "Executing health check task: {} {} {}",
-        check.check_name, check.check_frequency_seconds, check.check_id
-    );
+    /// Set when the check's proxy resolves DNS itself
+    remote_dns_hosts: Option<&'a HostAllowlist>,
+}
+
+impl<'a> TargetValidator<'a> {
+    pub fn new(
+        check: &ServiceCheck,
+        dns_cache: &'a DnsCache,
+        remote_dns_hosts: &'a HostAllowlist,
+    ) -> Self {
+        Self {
+            dns_cache,
+            dns_ttl: check.dns_cache_ttl(),
+            accept_local: check.allow_private_targets,
+            remote_dns_hosts: check
+                .proxy
+                .as_ref()
+                .is_some_and(|proxy| proxy.remote_dns)
+                .then_some(remote_dns_hosts),
+        }
+    }
+
+    /// Resolving a remote DNS target locally would leak the lookup and may not match what the
+    /// proxy resolves, so those are checked against the allowlist by name instead.
+    pub async fn validate(&self, url: &Url) -> Result<()> {
+        let Some(allowlist) = self.remote_dns_hosts else {
+            validate_and_transform_url(url, self.accept_local, self.dns_cache, self.dns_ttl)
+                .await?;
+            return Ok(());
+        };
+
+        let host = url.host_str().context("URL missing host")?;
+        if !allowlist.allows(host) {
+            bail!("Host {host} is not allowed for proxies resolving DNS remotely");
+        }
 
-    let method = match check.http_method {
+        Ok(())
+    }
+}
+
+pub fn to_reqwest_method(method: fetch::Method) -> Method {
+    match method {
         fetch::Method::Get => Method::GET,
         fetch::Method::Post => Method::POST,
         fetch::Method::Put => Method::PUT,
         fetch::Method::Delete => Method::DELETE,
         fetch::Method::Head => Method::HEAD,
-    };
+        fetch::Method::Patch => Method::PATCH,
+        fetch::Method::Options => Method::OPTIONS,
+    }
+}
 
-    // Validate URL and transform to use IP address
-    let (_ip_url, _original_host) = validate_and_transform_url(&check.url, accept_local)
-        .await
-        .context("URL validation failed")?;
+/// Only errors that indicate the service is down/unhealthy are genuine failures.
+/// Other errors indicate problems with our check implementation itself.
+pub fn is_genuine_fail(error: &reqwest::Error) -> bool {
+    error.is_timeout() || error.is_connect() || error.is_request() || error.is_body()
+}
+
+fn tls_error(error: &reqwest::Error) -> Option<&native_tls::Error> {
+    let mut source = error.source();
+    while let Some(err) = source {
+        if let Some(tls_error) = err.downcast_ref::<native_tls::Error>() {
+            return Some(tls_error);
+        }
+        source = err.source();
+    }
+    None
+}
+
+/// Expects a genuine fail, see [`is_genuine_fail`].
+pub fn classify_error(error: &reqwest::Error) -> FailureReason {
+    if error.is_timeout() && error.is_connect() {
+        FailureReason::ConnectTimeout
+    } else if error.is_timeout() {
+        FailureReason::Timeout
+    } else if let Some(tls_error) = tls_error(error) {
+        if tls::is_version_error(&error_chain(tls_error)) {
+            FailureReason::TlsVersion
+        } else {
+            FailureReason::Tls
+        }
+    } else if error.is_connect() {
+        FailureReason::Connect
+    } else if error.is_body() {
+        FailureReason::Body
+    } else {
+        FailureReason::Request
+    }
+}
+
+/// Formats the error with all its sources, e.g. `error sending request: ...: certificate verify failed`
+fn error_chain(error: &dyn Error) -> String {
+    let mut chain = error.to_string();
+    let mut source = error.source();
+    while let Some(err) = source {
+        chain.push_str(": ");
+        chain.push_str(&err.to_string());
+        source = err.source();
+    }
+    chain
+}
+
+/// Reads the body without keeping it, so that the response time covers its download. At most
+/// `limit` bytes are read.
+///
+/// The size comes from the `Content-Length` header, falling back to the bytes read if the body
+/// was read whole. Fails if the body is cut short.
+pub async fn get_response_size(mut response: Response, limit: u64) -> reqwest::Result<Option<i64>> {
+    let declared = response
+        .headers()
+        .get(header::CONTENT_LENGTH)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse::<i64>().ok());
+
+    let mut read = 0;
+    while let Some(chunk) = response.chunk().await? {
+        read += chunk.len() as u64;
+        if read > limit {
+            // The rest of the body is never read, so its size is unknown
+            return Ok(declared);
+        }
+    }
+
+    Ok(Some(declared.unwrap_or(read as i64)))
+}
+
+/// Sends the check's request to `url` and evaluates the response.
+/// Returns the outcome and how long the request took.
+///
+/// The total time stops once the body is read, before it is decompressed or matched. Bodies read
+/// for `body_regex` or `assertions` are only read up to [`MAX_BODY_ASSERTION_BYTES`].
+///
+/// `url` must already be validated, see [`validate_and_transform_url`].
+async fn probe_url(
+    client: &Client,
+    check: &ServiceCheck,
+    url: &Url,
+) -> Result<(ProbeOutcome, ProbeTimings)> {
+    let is_head = check.http_method == fetch::Method::Head;
+    let method = to_reqwest_method(check.http_method);
 
     let start = Instant::now();
-    let check_started_at = Utc::now();
 
     // TODO: use `ip_url` or fix
     // code: -67843, message: "The certificate was not trusted."
-    let mut request = client
-        .request(method, check.url.clone())
-        .timeout(Duration::from_secs(check.timeout_seconds as u64));
+    let mut request = client.request(method, url.clone()).timeout(check.timeout());
 
     for (key, value) in &check.request_headers {
         request = request.header(key, value);
     }
 
+    // Headers set on the check take precedence
+    if check.decompress_response
+        && !check
+            .request_headers
+            .keys()
+            .any(|key| key.eq_ignore_ascii_case(header::ACCEPT_ENCODING.as_str()))
+    {
+        request = request.header(header::ACCEPT_ENCODING, ACCEPT_ENCODING);
+    }
+
     if let Some(body) = &check.request_body
         && !body.is_empty()
+        && check.http_method.allows_body()
     {
         request = request.body(body.clone());
     }
 
+    if let Some(conditional) = &check.conditional {
+        request = conditional.apply(request);
+    }
+
     let result = request.send().await;
-    let response_time_micros = start.elapsed().as_micros() as i64;
+    let ttfb_micros = start.elapsed().as_micros() as i64;
 
-    let (status_code, matches_expected) = match result {
+    let (outcome, timings) = match result {
         Ok(response) => {
             let status_code = response.status().as_u16() as i32;
-            let matches_expected = status_code == check.expected_status_code;
-            (Some(status_code), matches_expected)
+            let status_matches = is_acceptable_status(
+                status_code,
+                &check.expected_status_codes,
+                check.conditional.as_ref(),
+            );
+            let region_matches = check
+                .geo_assertion
+                .as_ref()
+                .is_none_or(|assertion| assertion.matches(check.region, response.headers()));
+            let needs_body = check.body_regex.is_some()
+                || check
+                    .assertions
+                    .as_ref()
+                    .is_some_and(Assertions::needs_body);
+            let (response_size_bytes, decompressed_size_bytes, body) = if is_head {
+                (None, None, Ok(None))
+            } else if check.decompress_response {
+                match read_decompressed(response, MAX_BODY_ASSERTION_BYTES, MAX_RESPONSE_BYTES)
+                    .await
+                {
+                    Ok(body) => (
+                        Some(body.compressed_size),
+                        body.decompressed_size,
+                        Ok(Some(body.prefix)),
+                    ),
+                    Err(error) => (None, None, Err(error_chain(&error))),
+                }
+            } else if needs_body {
+                match read_body_prefix(response, MAX_BODY_ASSERTION_BYTES).await {
+                    Ok((body, size)) => (size, None, Ok(Some(body))),
+                    Err(error) => (None, None, Err(error_chain(&error))),
+                }
+            } else {
+                match get_response_size(response, MAX_RESPONSE_BYTES).await {
+                    Ok(size) => (size, None, Ok(None)),
+                    Err(error) => (None, None, Err(error_chain(&error))),
+                }
+            };
+            let timings = ProbeTimings {
+                total_micros: start.elapsed().as_micros() as i64,
+                ttfb_micros: Some(ttfb_micros),
+            };
+            let body_matches = body.as_ref().map(|body| match (&check.body_regex, body) {
+                (Some(body_regex), Some(body)) => body_regex.is_match(body),
+                _ => true,
+            });
+            let assertions = match (&check.assertions, &body) {
+                (Some(assertions), Ok(body)) => Some(assertions.evaluate(&AssertionInput {
+                    status_code,
+                    body: body.as_deref(),
+                    response_time: Duration::from_micros(ttfb_micros as u64),
+                })),
+                _ => None,
+            };
+            let (failure_reason, failure_detail) = if !status_matches {
+                (Some(FailureReason::UnexpectedStatus), None)
+            } else if !region_matches {
+                (Some(FailureReason::RegionMismatch), None)
+            } else {
+                match body_matches {
+                    Ok(true) if assertions.as_ref().is_some_and(|(passed, _)| !passed) => {
+                        (Some(FailureReason::AssertionFailed), None)
+                    }
+                    Ok(true) => (None, None),
+                    Ok(false) => (Some(FailureReason::BodyMismatch), None),
+                    Err(detail) => (Some(FailureReason::Body), Some(detail.clone())),
+                }
+            };
+            // After the timings, the extra handshake isn't part of the response time
+            let tls_version = match check.min_tls_version {
+                Some(min_version) if url.scheme() == "https" && check.proxy.is_none() => {
+                    tls::negotiated_version(url, min_version, check.timeout())
+                        .await
+                        .inspect_err(|error| trace!("No TLS version for {url}: {error:#}"))
+                        .ok()
+                }
+                _ => None,
+            };
+            let outcome = ProbeOutcome {
+                status_code: Some(status_code),
+                matches_expected: failure_reason.is_none(),
+                response_size_bytes,
+                decompressed_size_bytes,
+                failure_reason,
+                failure_detail,
+                assertion_results: assertions.map(|(_, results)| results),
+                tls_version,
+            };
+            (outcome, timings)
         }
         Err(error) => {
-            // Only mark as genuine failure for errors that indicate the service is down/unhealthy
-            // Exclude errors that indicate problems with our check implementation itself
-            let genuine_fail =
-                error.is_timeout() || error.is_connect() || error.is_request() || error.is_body();
-
-            if !genuine_fail {
+            if !is_genuine_fail(&error) {
                 return Err(error).context("not a genuine fail");
             } else {
                 trace!("Service check encountered error: {:?}", error);
             }
 
             // This never matches the expected code
-            (None, false)
+            (
+                ProbeOutcome::from_error(&error),
+                ProbeTimings::failed(start),
+            )
+        }
+    };
+
+    Ok((outcome, timings))
+}
+
+/// Probes `url` like [`probe_url`], then up to `retries` more times while the failure is
+/// transient, see [`FailureReason::is_transient`]. Returns the last attempt only.
+///
+/// No retry is started if it could end after `deadline`.
+async fn probe_url_with_retries(
+    client: &Client,
+    check: &ServiceCheck,
+    url: &Url,
+    deadline: Instant,
+) -> Result<(ProbeOutcome, ProbeTimings)> {
+    let mut attempt = probe_url(client, check, url).await?;
+
+    for retry in 1..=check.retries {
+        let (outcome, _) = &attempt;
+        if !outcome
+            .failure_reason
+            .is_some_and(FailureReason::is_transient)
+        {
+            break;
+        }
+        if Instant::now() + check.retry_delay() + check.timeout() > deadline {
+            trace!("No time left to retry {url} of {}", check.check_id);
+            break;
+        }
+
+        trace!(
+            "Retrying {url} of {} ({retry}/{}) after {:?}",
+            check.check_id, check.retries, outcome.failure_reason
+        );
+        tokio::time::sleep(check.retry_delay()).await;
+        attempt = probe_url(client, check, url).await?;
+    }
+
+    Ok(attempt)
+}
+
+/// Builder of the clients sending probes, egressing from `bind_address` when set
+pub fn probe_client_builder(bind_address: Option<IpAddr>) -> ClientBuilder {
+    Client::builder().local_address(bind_address)
+}
+
+pub async fn execute_check(
+    client: &Client,
+    dns_cache: &DnsCache,
+    check: &ServiceCheck,
+    remote_dns_hosts: &HostAllowlist,
+) -> Result<CheckResult> {
+    trace!(
+        "Executing health check task: {} {} {}",
+        check.check_name, check.check_frequency_seconds, check.check_id
+    );
+
+    if check.kind == CheckKind::Passive {
+        bail!("Passive checks are evaluated from their pings, not probed");
+    }
+
+    // reqwest configures proxies, TLS versions and connect timeouts per client, so such checks
+    // get their own
+    let dedicated_client;
+    let client = if check.proxy.is_some()
+        || check.min_tls_version.is_some()
+        || check.connect_timeout().is_some()
+    {
+        let mut builder = probe_client_builder(eager_env::probe_bind_address());
+
+        if let Some(proxy) = &check.proxy {
+            let proxy_url: Url = proxy.url.parse().context("Invalid proxy URL")?;
+            validate_and_transform_url(
+                &proxy_url,
+                check.allow_private_targets,
+                dns_cache,
+                check.dns_cache_ttl(),
+            )
+            .await
+            .context("Proxy URL validation failed")?;
+
+            builder = builder.proxy(proxy.build_proxy()?);
+        }
+
+        if let Some(min_tls_version) = check.min_tls_version {
+            builder = builder.min_tls_version(min_tls_version.into());
+        }
+
+        if let Some(connect_timeout) = check.connect_timeout() {
+            builder = builder.connect_timeout(connect_timeout);
         }
+
+        dedicated_client = builder.build().context("Failed to build check client")?;
+        &dedicated_client
+    } else {
+        client
     };
 
+    let validator = TargetValidator::new(check, dns_cache, remote_dns_hosts);
+
+    if check.kind == CheckKind::Steps {
+        return steps::execute_steps(client, check, &validator).await;
+    }
+
+    let check_started_at = Utc::now();
+    // Retries must not overlap with the next run of the check
+    let deadline =
+        Instant::now() + Duration::from_secs(check.check_frequency_seconds.max(0) as u64);
+
+    // Validate every URL upfront, so that no request reaches an internal address.
+    // Hosts that don't resolve are not probed, but still fail the check
+    let mut unresolved = Vec::new();
+    for url in std::iter::once(&check.url).chain(&check.fallback_urls) {
+        let start = Instant::now();
+        match validator.validate(url).await {
+            Ok(()) => unresolved.push(None),
+            Err(error) if is_resolution_failure(&error) => {
+                trace!("Host of {url} did not resolve: {error:#}");
+                let outcome = ProbeOutcome::from_resolution_error(&error);
+                unresolved.push(Some((outcome, ProbeTimings::failed(start))));
+            }
+            Err(error) => return Err(error).context("URL validation failed"),
+        }
+    }
+    let mut unresolved = unresolved.into_iter();
+
+    let (mut outcome, mut timings) = match unresolved.next().flatten() {
+        Some(failed) => failed,
+        None => probe_url_with_retries(client, check, &check.url, deadline).await?,
+    };
+    let mut fallback_index = None;
+
+    // Fallbacks only matter when the primary fails. If they all fail too, the primary's
+    // outcome is recorded
+    if !outcome.matches_expected {
+        for ((index, url), unresolved) in check.fallback_urls.iter().enumerate().zip(unresolved) {
+            if unresolved.is_some() {
+                continue;
+            }
+
+            match probe_url_with_retries(client, check, url, deadline).await {
+                Ok((fallback_outcome, fallback_timings)) if fallback_outcome.matches_expected => {
+                    outcome = fallback_outcome;
+                    timings = fallback_timings;
+                    fallback_index = Some(index as i32);
+                    break;
+                }
+                Ok(_) => {}
+                Err(error) => warn!("Skipping fallback {url} of {}: {error:?}", check.check_id),
+            }
+        }
+    }
+
     let result = CheckResult {
         result_id: Uuid::new_v4(),
         service_check_id: check.check_id,
         check_started_at,
-        response_time_micros,
-        status_code,
-        matches_expected,
+        response_time_micros: timings.total_micros,
+        ttfb_micros: timings.ttfb_micros,
+        status_code: outcome.status_code,
+        matches_expected: outcome.matches_expected,
         response_body_fetched: false,
         response_body: None,
+        response_size_bytes: outcome.response_size_bytes,
+        decompressed_size_bytes: outcome.decompressed_size_bytes,
+        failed_step: None,
+        fallback_index,
+        failure_reason: outcome.failure_reason,
+        failure_detail: outcome.failure_detail,
+        assertion_results: outcome.assertion_results,
+        tls_version: outcome.tls_version,
     };
 
     trace!(
         "Health check completed: {} - status: {:?}, matches: {}, time: {}μs",
-        check.check_name, status_code, matches_expected, response_time_micros
+        check.check_name, result.status_code, result.matches_expected, result.response_time_micros
     );
 
     Ok(result)
@@ -177,15 +720,30 @@ pub async fn execute_check(
 
 #[cfg(test)]
 mod tests {
-    use std::collections::HashMap;
+    use std::collections::{BTreeMap, HashMap};
+    use std::sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+    };
 
     use super::*;
     use crate::{
         regions::Region,
         utils::init_logging,
-        worker::fetch::{Method, ServiceCheck},
+        worker::{
+            check::{
+                assertions::{Assertion, Combinator},
+                conditional::{ANY_STATUS_CODE, ConditionalRequest},
+                geo::GeoAssertion,
+                proxy::ProxyConfig,
+                status::ExpectedStatusCodes,
+                tls::MinTlsVersion,
+            },
+            fetch::{Method, ServiceCheck},
+        },
     };
     use httpmock::prelude::*;
+    use openssl::ssl::SslVersion;
     use uuid::Uuid;
 
     #[tokio::test]
@@ -205,14 +763,37 @@ mod tests {
             http_method: Method::Get,
             check_frequency_seconds: 60,
             timeout_seconds: 30,
-            expected_status_code: 200,
+            expected_status_codes: 200.into(),
             request_headers: HashMap::new(),
             request_body: None,
             is_enabled: true,
             created_at: Utc::now(),
+            kind: CheckKind::Http,
+            steps: vec![],
+            dns_cache_ttl_seconds: None,
+            proxy: None,
+            conditional: None,
+            geo_assertion: None,
+            fallback_urls: vec![],
+            body_regex: None,
+            min_tls_version: None,
+            decompress_response: false,
+            allow_private_targets: true,
+            connect_timeout_millis: None,
+            assertions: None,
+            store_raw_results: true,
+            retries: 0,
+            retry_delay_ms: 0,
+            pinned_node: None,
         };
 
-        let result = execute_check(&client, &check, true).await;
+        let result = execute_check(
+            &client,
+            &DnsCache::default(),
+            &check,
+            &HostAllowlist::default(),
+        )
+        .await;
         assert!(result.is_ok());
 
         let check_result = result.unwrap();
@@ -224,6 +805,984 @@ mod tests {
         mock.assert();
     }
 
+    #[tokio::test]
+    async fn test_execute_check_response_size() {
+        let server = MockServer::start();
+        let body = "a".repeat(1234);
+        let get_mock = server.mock(|when, then| {
+            when.method(GET).path("/sized");
+            then.status(200).body(&body);
+        });
+        let head_mock = server.mock(|when, then| {
+            when.method(httpmock::Method::HEAD).path("/sized");
+            then.status(200).header("content-length", "1234");
+        });
+
+        let client = Client::new();
+        let mut check = ServiceCheck {
+            url: server.url("/sized").parse().unwrap(),
+            allow_private_targets: true,
+            ..ServiceCheck::example()
+        };
+
+        let result = execute_check(
+            &client,
+            &DnsCache::default(),
+            &check,
+            &HostAllowlist::default(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(result.response_size_bytes, Some(1234));
+
+        // HEAD responses carry no body, so no size is recorded
+        check.http_method = Method::Head;
+        let result = execute_check(
+            &client,
+            &DnsCache::default(),
+            &check,
+            &HostAllowlist::default(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(result.status_code, Some(200));
+        assert_eq!(result.response_size_bytes, None);
+
+        get_mock.assert();
+        head_mock.assert();
+    }
+
+    #[tokio::test]
+    async fn test_execute_check_conditional_not_modified() {
+        let server = MockServer::start();
+        let conditional_mock = server.mock(|when, then| {
+            when.method(GET)
+                .path("/cached")
+                .header("if-none-match", "\"v1\"")
+                .header("if-modified-since", "Sun, 06 Nov 1994 08:49:37 GMT");
+            then.status(304);
+        });
+        let unconditional_mock = server.mock(|when, then| {
+            when.method(GET)
+                .path("/cached")
+                .header_missing("if-none-match");
+            then.status(304);
+        });
+
+        let client = Client::new();
+        let mut check = ServiceCheck {
+            url: server.url("/cached").parse().unwrap(),
+            conditional: Some(ConditionalRequest {
+                etag: Some("\"v1\"".to_string()),
+                modified_since: DateTime::from_timestamp(784111777, 0),
+            }),
+            allow_private_targets: true,
+            ..ServiceCheck::example()
+        };
+
+        let result = execute_check(
+            &client,
+            &DnsCache::default(),
+            &check,
+            &HostAllowlist::default(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(result.status_code, Some(304));
+        assert!(result.matches_expected);
+        assert_eq!(result.failure_reason, None);
+
+        // Without the conditional config, a 304 is unexpected
+        check.conditional = None;
+        let result = execute_check(
+            &client,
+            &DnsCache::default(),
+            &check,
+            &HostAllowlist::default(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(result.status_code, Some(304));
+        assert!(!result.matches_expected);
+
+        conditional_mock.assert_calls(1);
+        unconditional_mock.assert_calls(1);
+    }
+
+    #[tokio::test]
+    async fn test_execute_check_any_status() {
+        let server = MockServer::start();
+        let mock = server.mock(|when, then| {
+            when.method(GET).path("/broken");
+            then.status(500);
+        });
+
+        let client = Client::new();
+        let mut check = ServiceCheck {
+            url: server.url("/broken").parse().unwrap(),
+            expected_status_codes: ANY_STATUS_CODE.into(),
+            allow_private_targets: true,
+            ..ServiceCheck::example()
+        };
+
+        let result = execute_check(
+            &client,
+            &DnsCache::default(),
+            &check,
+            &HostAllowlist::default(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(result.status_code, Some(500));
+        assert!(result.matches_expected);
+        assert_eq!(result.failure_reason, None);
+
+        // Not responding at all still fails
+        let port = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        check.url = format!("http://127.0.0.1:{port}/").parse().unwrap();
+        let result = execute_check(
+            &client,
+            &DnsCache::default(),
+            &check,
+            &HostAllowlist::default(),
+        )
+        .await
+        .unwrap();
+        assert!(!result.matches_expected);
+        assert_eq!(result.failure_reason, Some(FailureReason::Connect));
+
+        mock.assert_calls(1);
+    }
+
+    #[tokio::test]
+    async fn test_execute_check_expected_status_codes() {
+        let server = MockServer::start();
+        server.mock(|when, then| {
+            when.method(GET).path("/no-content");
+            then.status(204);
+        });
+        server.mock(|when, then| {
+            when.method(GET).path("/created");
+            then.status(201);
+        });
+        server.mock(|when, then| {
+            when.method(GET).path("/missing");
+            then.status(404);
+        });
+
+        let client = Client::new();
+        let execute = async |path: &str, expected_status_codes: ExpectedStatusCodes| {
+            let check = ServiceCheck {
+                url: server.url(path).parse().unwrap(),
+                expected_status_codes,
+                allow_private_targets: true,
+                ..ServiceCheck::example()
+            };
+            execute_check(
+                &client,
+                &DnsCache::default(),
+                &check,
+                &HostAllowlist::default(),
+            )
+            .await
+            .unwrap()
+        };
+
+        // Exact match in a list
+        let result = execute("/no-content", ExpectedStatusCodes::Codes(vec![200, 204])).await;
+        assert!(result.matches_expected);
+        assert_eq!(result.status_code, Some(204));
+
+        // Range match
+        let success = ExpectedStatusCodes::Range { min: 200, max: 299 };
+        let result = execute("/created", success.clone()).await;
+        assert!(result.matches_expected);
+
+        // Miss
+        let result = execute("/missing", success).await;
+        assert!(!result.matches_expected);
+        assert_eq!(result.failure_reason, Some(FailureReason::UnexpectedStatus));
+        let result = execute("/created", ExpectedStatusCodes::Codes(vec![200, 204])).await;
+        assert!(!result.matches_expected);
+    }
+
+    #[tokio::test]
+    async fn test_execute_check_geo_assertion() {
+        let server = MockServer::start();
+        let mock = server.mock(|when, then| {
+            when.method(GET).path("/anycast");
+            then.status(200).header("CF-Ray", "8a1b2c3d4e5f6789-FRA");
+        });
+
+        let client = Client::new();
+        let mut check = ServiceCheck {
+            url: server.url("/anycast").parse().unwrap(),
+            region: Region::Fsn1,
+            geo_assertion: Some(GeoAssertion {
+                header: "CF-Ray".to_string(),
+                expected: BTreeMap::from([
+                    (Region::Fsn1, "-FRA".to_string()),
+                    (Region::Hel1, "-HEL".to_string()),
+                ]),
+            }),
+            allow_private_targets: true,
+            ..ServiceCheck::example()
+        };
+
+        let result = execute_check(
+            &client,
+            &DnsCache::default(),
+            &check,
+            &HostAllowlist::default(),
+        )
+        .await
+        .unwrap();
+        assert!(result.matches_expected);
+        assert_eq!(result.failure_reason, None);
+
+        // Helsinki expects its own PoP, but the mock is served from Frankfurt
+        check.region = Region::Hel1;
+        let result = execute_check(
+            &client,
+            &DnsCache::default(),
+            &check,
+            &HostAllowlist::default(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(result.status_code, Some(200));
+        assert!(!result.matches_expected);
+        assert_eq!(result.failure_reason, Some(FailureReason::RegionMismatch));
+
+        mock.assert_calls(2);
+    }
+
+    #[tokio::test]
+    async fn test_execute_check_assertions() {
+        let server = MockServer::start();
+        server.mock(|when, then| {
+            when.method(GET).path("/status");
+            then.status(200).body("status: degraded");
+        });
+
+        let assertions = |combinator| Assertions {
+            combinator,
+            assertions: vec![
+                Assertion::StatusCode { equals: 200 },
+                Assertion::BodyContains {
+                    text: "ok".to_string(),
+                },
+                Assertion::ResponseTimeBelow { millis: 10_000 },
+            ],
+        };
+        let client = Client::new();
+        let mut check = ServiceCheck {
+            url: server.url("/status").parse().unwrap(),
+            assertions: Some(assertions(Combinator::All)),
+            allow_private_targets: true,
+            ..ServiceCheck::example()
+        };
+
+        let result = execute_check(
+            &client,
+            &DnsCache::default(),
+            &check,
+            &HostAllowlist::default(),
+        )
+        .await
+        .unwrap();
+        assert!(!result.matches_expected);
+        assert_eq!(result.failure_reason, Some(FailureReason::AssertionFailed));
+        assert_eq!(
+            result
+                .assertion_results
+                .unwrap()
+                .iter()
+                .map(|r| r.passed)
+                .collect::<Vec<_>>(),
+            [true, false, true]
+        );
+
+        check.assertions = Some(assertions(Combinator::Any));
+        let result = execute_check(
+            &client,
+            &DnsCache::default(),
+            &check,
+            &HostAllowlist::default(),
+        )
+        .await
+        .unwrap();
+        assert!(result.matches_expected);
+        assert_eq!(result.failure_reason, None);
+        assert_eq!(result.assertion_results.unwrap().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_execute_check_body_regex() {
+        let server = MockServer::start();
+        let version_mock = server.mock(|when, then| {
+            when.method(GET).path("/version");
+            then.status(200).body(r#"{"version":"2.14.3"}"#);
+        });
+        let large_mock = server.mock(|when, then| {
+            when.method(GET).path("/large");
+            then.status(200)
+                .body(format!("{}ready", " ".repeat(MAX_BODY_ASSERTION_BYTES)));
+        });
+
+        let client = Client::new();
+        let mut check = ServiceCheck {
+            url: server.url("/version").parse().unwrap(),
+            body_regex: Some(r#""version":"2\.\d+\.\d+""#.parse().unwrap()),
+            allow_private_targets: true,
+            ..ServiceCheck::example()
+        };
+
+        let result = execute_check(
+            &client,
+            &DnsCache::default(),
+            &check,
+            &HostAllowlist::default(),
+        )
+        .await
+        .unwrap();
+        assert!(result.matches_expected);
+        assert_eq!(result.failure_reason, None);
+        assert_eq!(result.response_size_bytes, Some(20));
+
+        check.body_regex = Some(r#""version":"3\."#.parse().unwrap());
+        let result = execute_check(
+            &client,
+            &DnsCache::default(),
+            &check,
+            &HostAllowlist::default(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(result.status_code, Some(200));
+        assert!(!result.matches_expected);
+        assert_eq!(result.failure_reason, Some(FailureReason::BodyMismatch));
+
+        // Past the read limit, so never matched
+        check.url = server.url("/large").parse().unwrap();
+        check.body_regex = Some("ready".parse().unwrap());
+        let result = execute_check(
+            &client,
+            &DnsCache::default(),
+            &check,
+            &HostAllowlist::default(),
+        )
+        .await
+        .unwrap();
+        assert!(!result.matches_expected);
+        assert_eq!(result.failure_reason, Some(FailureReason::BodyMismatch));
+
+        version_mock.assert_calls(2);
+        large_mock.assert();
+    }
+
+    #[tokio::test]
+    async fn test_probe_client_bind_address() {
+        let server = MockServer::start();
+        let mock = server.mock(|when, then| {
+            when.method(GET).path("/");
+            then.status(200);
+        });
+        let check = ServiceCheck {
+            url: server.url("/").parse().unwrap(),
+            allow_private_targets: true,
+            ..ServiceCheck::example()
+        };
+
+        let client = probe_client_builder(Some("127.0.0.1".parse().unwrap()))
+            .build()
+            .unwrap();
+        let result = execute_check(
+            &client,
+            &DnsCache::default(),
+            &check,
+            &HostAllowlist::default(),
+        )
+        .await
+        .unwrap();
+        assert!(result.matches_expected);
+        mock.assert();
+
+        // Addresses the host doesn't have can't be bound
+        let client = probe_client_builder(Some("192.0.2.1".parse().unwrap()))
+            .build()
+            .unwrap();
+        let result = execute_check(
+            &client,
+            &DnsCache::default(),
+            &check,
+            &HostAllowlist::default(),
+        )
+        .await
+        .unwrap();
+        assert!(!result.matches_expected);
+        assert_eq!(result.status_code, None);
+        mock.assert_calls(1);
+    }
+
+    #[tokio::test]
+    async fn test_execute_check_decompress_response() {
+        use flate2::{Compression, write::GzEncoder};
+        use std::io::Write;
+
+        let body = "all systems operational\n".repeat(1000);
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(body.as_bytes()).unwrap();
+        let compressed = encoder.finish().unwrap();
+
+        let server = MockServer::start();
+        let advertised_mock = server.mock(|when, then| {
+            when.method(GET)
+                .path("/status")
+                .header("accept-encoding", ACCEPT_ENCODING);
+            then.status(200)
+                .header("content-encoding", "gzip")
+                .body(&compressed);
+        });
+        let plain_mock = server.mock(|when, then| {
+            when.method(GET)
+                .path("/status")
+                .header_missing("accept-encoding");
+            then.status(200).body(&body);
+        });
+
+        let client = Client::new();
+        let mut check = ServiceCheck {
+            url: server.url("/status").parse().unwrap(),
+            decompress_response: true,
+            body_regex: Some("operational".parse().unwrap()),
+            allow_private_targets: true,
+            ..ServiceCheck::example()
+        };
+
+        let result = execute_check(
+            &client,
+            &DnsCache::default(),
+            &check,
+            &HostAllowlist::default(),
+        )
+        .await
+        .unwrap();
+        assert!(result.matches_expected);
+        assert_eq!(result.response_size_bytes, Some(compressed.len() as i64));
+        assert_eq!(result.decompressed_size_bytes, Some(body.len() as i64));
+        advertised_mock.assert();
+
+        // Without decompression nothing is advertised and the body is measured as sent
+        check.decompress_response = false;
+        let result = execute_check(
+            &client,
+            &DnsCache::default(),
+            &check,
+            &HostAllowlist::default(),
+        )
+        .await
+        .unwrap();
+        assert!(result.matches_expected);
+        assert_eq!(result.response_size_bytes, Some(body.len() as i64));
+        assert_eq!(result.decompressed_size_bytes, None);
+        plain_mock.assert();
+    }
+
+    #[tokio::test]
+    async fn test_execute_check_fallback_urls() {
+        let server = MockServer::start();
+        let primary_mock = server.mock(|when, then| {
+            when.method(GET).path("/primary");
+            then.status(503);
+        });
+        let broken_fallback_mock = server.mock(|when, then| {
+            when.method(GET).path("/broken");
+            then.status(500);
+        });
+        let healthy_fallback_mock = server.mock(|when, then| {
+            when.method(GET).path("/healthy");
+            then.status(200);
+        });
+        let unused_fallback_mock = server.mock(|when, then| {
+            when.method(GET).path("/unused");
+            then.status(200);
+        });
+
+        let client = Client::new();
+        let mut check = ServiceCheck {
+            url: server.url("/primary").parse().unwrap(),
+            fallback_urls: ["/broken", "/healthy", "/unused"]
+                .map(|path| server.url(path).parse().unwrap())
+                .to_vec(),
+            allow_private_targets: true,
+            ..ServiceCheck::example()
+        };
+
+        let result = execute_check(
+            &client,
+            &DnsCache::default(),
+            &check,
+            &HostAllowlist::default(),
+        )
+        .await
+        .unwrap();
+        assert!(result.matches_expected);
+        assert_eq!(result.status_code, Some(200));
+        assert_eq!(result.fallback_index, Some(1));
+        assert_eq!(result.failure_reason, None);
+
+        // When every URL fails, the primary's failure is recorded
+        check.fallback_urls.truncate(1);
+        let result = execute_check(
+            &client,
+            &DnsCache::default(),
+            &check,
+            &HostAllowlist::default(),
+        )
+        .await
+        .unwrap();
+        assert!(!result.matches_expected);
+        assert_eq!(result.status_code, Some(503));
+        assert_eq!(result.fallback_index, None);
+
+        primary_mock.assert_calls(2);
+        broken_fallback_mock.assert_calls(2);
+        healthy_fallback_mock.assert_calls(1);
+        unused_fallback_mock.assert_calls(0);
+    }
+
+    #[tokio::test]
+    async fn test_execute_check_retries() {
+        let server = MockServer::start();
+        // Times out twice, then responds right away
+        let attempts = Arc::new(AtomicUsize::new(0));
+        let counted_attempts = attempts.clone();
+        server.mock(|when, then| {
+            when.method(GET)
+                .path("/flaky")
+                .is_true(move |_| counted_attempts.fetch_add(1, Ordering::SeqCst) < 2);
+            then.status(200).delay(Duration::from_secs(2));
+        });
+        let flaky_healthy_mock = server.mock(|when, then| {
+            when.method(GET).path("/flaky");
+            then.status(200);
+        });
+        let slow_mock = server.mock(|when, then| {
+            when.method(GET).path("/slow");
+            then.status(200).delay(Duration::from_secs(2));
+        });
+        let error_mock = server.mock(|when, then| {
+            when.method(GET).path("/error");
+            then.status(500);
+        });
+
+        let client = Client::new();
+        let execute = async |check: &ServiceCheck| {
+            execute_check(
+                &client,
+                &DnsCache::default(),
+                check,
+                &HostAllowlist::default(),
+            )
+            .await
+            .unwrap()
+        };
+        let mut check = ServiceCheck {
+            url: server.url("/flaky").parse().unwrap(),
+            timeout_seconds: 1,
+            allow_private_targets: true,
+            retries: 3,
+            retry_delay_ms: 50,
+            ..ServiceCheck::example()
+        };
+
+        let result = execute(&check).await;
+        assert!(result.matches_expected);
+        assert_eq!(result.failure_reason, None);
+        // Only the final attempt is timed
+        assert!(result.response_time_micros < 1_000_000);
+        // Asserting the calls of the timing out mock would run its matcher again
+        assert_eq!(attempts.load(Ordering::SeqCst), 3);
+        flaky_healthy_mock.assert_calls(1);
+
+        // The last failure is recorded once the retries are exhausted
+        check.url = server.url("/slow").parse().unwrap();
+        check.retries = 1;
+        let result = execute(&check).await;
+        assert!(!result.matches_expected);
+        assert_eq!(result.failure_reason, Some(FailureReason::Timeout));
+        slow_mock.assert_calls(2);
+
+        // No retry could end before the next run
+        check.check_frequency_seconds = 1;
+        let result = execute(&check).await;
+        assert_eq!(result.failure_reason, Some(FailureReason::Timeout));
+        slow_mock.assert_calls(3);
+
+        // Responses are never retried, even unexpected ones
+        check.url = server.url("/error").parse().unwrap();
+        check.check_frequency_seconds = 60;
+        let result = execute(&check).await;
+        assert_eq!(result.failure_reason, Some(FailureReason::UnexpectedStatus));
+        error_mock.assert_calls(1);
+
+        // Refused connections are retried, each after the delay
+        let closed_port = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        check.url = format!("http://127.0.0.1:{closed_port}/").parse().unwrap();
+        check.retries = 2;
+        check.retry_delay_ms = 200;
+        let start = Instant::now();
+        let result = execute(&check).await;
+        assert_eq!(result.failure_reason, Some(FailureReason::Connect));
+        assert!(start.elapsed() >= Duration::from_millis(400));
+    }
+
+    #[tokio::test]
+    async fn test_execute_check_through_authenticated_proxy() {
+        // The mock acts as the proxy: plain HTTP requests are forwarded to it in absolute form
+        let proxy = MockServer::start();
+        let authorized_mock = proxy.mock(|when, then| {
+            when.method(GET)
+                .path("/health")
+                .header("proxy-authorization", "Basic dXNlcjpodW50ZXIy");
+            then.status(200).body("OK");
+        });
+        let rejected_mock = proxy.mock(|when, then| {
+            when.method(GET)
+                .path("/health")
+                .header_missing("proxy-authorization");
+            then.status(407);
+        });
+
+        let client = Client::new();
+        let mut check = ServiceCheck {
+            // Nothing listens here, the request must go through the proxy
+            url: "http://localhost:9/health".parse().unwrap(),
+            proxy: Some(ProxyConfig {
+                url: proxy.base_url(),
+                username: None,
+                password: None,
+                remote_dns: false,
+            }),
+            allow_private_targets: true,
+            ..ServiceCheck::example()
+        };
+
+        let result = execute_check(
+            &client,
+            &DnsCache::default(),
+            &check,
+            &HostAllowlist::default(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(result.status_code, Some(407));
+        assert!(!result.matches_expected);
+
+        check.proxy = Some(ProxyConfig {
+            url: proxy.base_url(),
+            username: Some("user".to_string()),
+            password: Some("hunter2".to_string()),
+            remote_dns: false,
+        });
+
+        let result = execute_check(
+            &client,
+            &DnsCache::default(),
+            &check,
+            &HostAllowlist::default(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(result.status_code, Some(200));
+        assert!(result.matches_expected);
+
+        rejected_mock.assert();
+        authorized_mock.assert();
+    }
+
+    #[tokio::test]
+    async fn test_execute_check_remote_dns_allowlist() {
+        let proxy = MockServer::start();
+        let proxy_mock = proxy.mock(|when, then| {
+            when.method(GET)
+                .path("/health")
+                .header("host", "status.monitored.invalid");
+            then.status(200).body("OK");
+        });
+
+        let client = Client::new();
+        let mut check = ServiceCheck {
+            // `.invalid` never resolves, so this only succeeds if the proxy does the lookup
+            url: "http://status.monitored.invalid/health".parse().unwrap(),
+            proxy: Some(ProxyConfig {
+                url: proxy.base_url(),
+                username: None,
+                password: None,
+                remote_dns: true,
+            }),
+            allow_private_targets: true,
+            ..ServiceCheck::example()
+        };
+        let allowlist: HostAllowlist = "*.monitored.invalid".parse().unwrap();
+
+        let result = execute_check(&client, &DnsCache::default(), &check, &allowlist)
+            .await
+            .unwrap();
+        assert!(result.matches_expected);
+        assert_eq!(result.status_code, Some(200));
+
+        // Not allowlisted, and never resolved locally as a fallback
+        check.url = "http://other.invalid/health".parse().unwrap();
+        let result = execute_check(&client, &DnsCache::default(), &check, &allowlist).await;
+        assert!(result.is_err());
+
+        proxy_mock.assert_calls(1);
+    }
+
+    #[tokio::test]
+    async fn test_execute_check_unresolvable_host() {
+        let server = MockServer::start();
+        let mock = server.mock(|when, then| {
+            when.method(GET).path("/health");
+            then.status(200).body("OK");
+        });
+
+        let client = Client::new();
+        let mut check = ServiceCheck {
+            url: "http://unresolvable.invalid/health".parse().unwrap(),
+            allow_private_targets: true,
+            ..ServiceCheck::example()
+        };
+
+        let result = execute_check(
+            &client,
+            &DnsCache::default(),
+            &check,
+            &HostAllowlist::default(),
+        )
+        .await
+        .unwrap();
+        assert!(!result.matches_expected);
+        assert_eq!(result.status_code, None);
+        assert_eq!(result.failure_reason, Some(FailureReason::Dns));
+        assert!(
+            result
+                .failure_detail
+                .unwrap()
+                .contains("DNS resolution failed")
+        );
+
+        // A resolvable fallback is still probed
+        check.fallback_urls = vec![server.url("/health").parse().unwrap()];
+        let result = execute_check(
+            &client,
+            &DnsCache::default(),
+            &check,
+            &HostAllowlist::default(),
+        )
+        .await
+        .unwrap();
+        assert!(result.matches_expected);
+        assert_eq!(result.fallback_index, Some(0));
+        mock.assert();
+    }
+
+    #[tokio::test]
+    async fn test_execute_check_ttfb() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        // Sends the headers right away, then the body after a delay
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let server = tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut request = [0; 1024];
+            let _ = stream.read(&mut request).await.unwrap();
+            stream
+                .write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\n")
+                .await
+                .unwrap();
+            stream.flush().await.unwrap();
+            tokio::time::sleep(Duration::from_millis(300)).await;
+            stream.write_all(b"hello").await.unwrap();
+        });
+
+        let check = ServiceCheck {
+            url: format!("http://{address}/").parse().unwrap(),
+            allow_private_targets: true,
+            ..ServiceCheck::example()
+        };
+        let result = execute_check(
+            &Client::new(),
+            &DnsCache::default(),
+            &check,
+            &HostAllowlist::default(),
+        )
+        .await
+        .unwrap();
+        server.await.unwrap();
+
+        assert!(result.matches_expected);
+        assert_eq!(result.response_size_bytes, Some(5));
+        let ttfb_micros = result.ttfb_micros.unwrap();
+        assert!(ttfb_micros < 200_000, "TTFB {ttfb_micros}μs");
+        // The total includes the delayed body
+        assert!(
+            result.response_time_micros >= 300_000,
+            "total {}μs",
+            result.response_time_micros
+        );
+    }
+
+    #[tokio::test]
+    async fn test_get_response_size() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        // Serves `response` then closes the connection
+        let serve = async |response: &'static [u8]| {
+            let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let address = listener.local_addr().unwrap();
+            tokio::spawn(async move {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                let mut request = [0; 1024];
+                let _ = stream.read(&mut request).await.unwrap();
+                stream.write_all(response).await.unwrap();
+            });
+            Client::new()
+                .get(format!("http://{address}/"))
+                .send()
+                .await
+                .unwrap()
+        };
+
+        let response = serve(b"HTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\nhello").await;
+        assert_eq!(get_response_size(response, 5).await.unwrap(), Some(5));
+
+        // Reading stops past the limit, only the declared size is known
+        let response = serve(b"HTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\nhello").await;
+        assert_eq!(get_response_size(response, 4).await.unwrap(), Some(5));
+        let response = serve(b"HTTP/1.1 200 OK\r\nConnection: close\r\n\r\nhello").await;
+        assert_eq!(get_response_size(response, 4).await.unwrap(), None);
+
+        // Cut short, the declared size is not trusted
+        let response = serve(b"HTTP/1.1 200 OK\r\nContent-Length: 100\r\n\r\nhello").await;
+        assert!(get_response_size(response, 1000).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_execute_check_truncated_body() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        // Declares more than it sends, then closes the connection
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let server = tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut request = [0; 1024];
+            let _ = stream.read(&mut request).await.unwrap();
+            stream
+                .write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 100\r\n\r\nhello")
+                .await
+                .unwrap();
+        });
+
+        let check = ServiceCheck {
+            url: format!("http://{address}/").parse().unwrap(),
+            allow_private_targets: true,
+            ..ServiceCheck::example()
+        };
+        let result = execute_check(
+            &Client::new(),
+            &DnsCache::default(),
+            &check,
+            &HostAllowlist::default(),
+        )
+        .await
+        .unwrap();
+        server.await.unwrap();
+
+        assert!(!result.matches_expected);
+        assert_eq!(result.status_code, Some(200));
+        assert_eq!(result.failure_reason, Some(FailureReason::Body));
+        assert_eq!(result.response_size_bytes, None);
+    }
+
+    /// Listener whose accept queue is full, so that further connection attempts hang
+    async fn unresponsive_listener() -> (tokio::net::TcpListener, Vec<tokio::net::TcpStream>) {
+        let socket = tokio::net::TcpSocket::new_v4().unwrap();
+        socket.bind("127.0.0.1:0".parse().unwrap()).unwrap();
+        let listener = socket.listen(0).unwrap();
+        let address = listener.local_addr().unwrap();
+
+        let mut backlog = Vec::new();
+        while let Ok(Ok(stream)) = tokio::time::timeout(
+            Duration::from_millis(100),
+            tokio::net::TcpStream::connect(address),
+        )
+        .await
+        {
+            backlog.push(stream);
+        }
+        (listener, backlog)
+    }
+
+    #[tokio::test]
+    async fn test_execute_check_connect_timeout() {
+        let server = MockServer::start();
+        let mock = server.mock(|when, then| {
+            when.method(GET).path("/slow");
+            then.status(200).delay(Duration::from_secs(5));
+        });
+        let (listener, _backlog) = unresponsive_listener().await;
+
+        let client = Client::new();
+        let mut check = ServiceCheck {
+            url: server.url("/slow").parse().unwrap(),
+            timeout_seconds: 2,
+            connect_timeout_millis: Some(300),
+            allow_private_targets: true,
+            ..ServiceCheck::example()
+        };
+
+        // Connected in time, but the response is too slow
+        let result = execute_check(
+            &client,
+            &DnsCache::default(),
+            &check,
+            &HostAllowlist::default(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(result.failure_reason, Some(FailureReason::Timeout));
+        mock.assert();
+
+        // Never connected
+        check.url = format!("http://{}/", listener.local_addr().unwrap())
+            .parse()
+            .unwrap();
+        let start = Instant::now();
+        let result = execute_check(
+            &client,
+            &DnsCache::default(),
+            &check,
+            &HostAllowlist::default(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(result.failure_reason, Some(FailureReason::ConnectTimeout));
+        assert!(start.elapsed() < Duration::from_secs(1));
+    }
+
     #[tokio::test]
     async fn test_execute_check_timeout() {
         let server = MockServer::start();
@@ -241,15 +1800,39 @@ mod tests {
             http_method: Method::Get,
             check_frequency_seconds: 60,
             timeout_seconds: 1,
-            expected_status_code: 200,
+            expected_status_codes: 200.into(),
             request_headers: HashMap::new(),
             request_body: None,
             is_enabled: true,
             created_at: Utc::now(),
+            kind: CheckKind::Http,
+            steps: vec![],
+            dns_cache_ttl_seconds: None,
+            proxy: None,
+            conditional: None,
+            geo_assertion: None,
+            fallback_urls: vec![],
+            body_regex: None,
+            min_tls_version: None,
+            decompress_response: false,
+            allow_private_targets: true,
+            connect_timeout_millis: None,
+            assertions: None,
+            store_raw_results: true,
+            retries: 0,
+            retry_delay_ms: 0,
+            pinned_node: None,
         };
 
         let start = Instant::now();
-        let result = execute_check(&client, &check, true).await.unwrap();
+        let result = execute_check(
+            &client,
+            &DnsCache::default(),
+            &check,
+            &HostAllowlist::default(),
+        )
+        .await
+        .unwrap();
         let duration = start.elapsed();
 
         // Should timeout early
@@ -258,10 +1841,176 @@ mod tests {
         assert_eq!(result.service_check_id, check.check_id);
         assert_eq!(result.status_code, None);
         assert!(!result.matches_expected);
+        assert_eq!(result.failure_reason, Some(FailureReason::Timeout));
 
         mock.assert();
     }
 
+    /// Serves TLS on a random port with a self-signed certificate valid only for `dns_name`,
+    /// up to `max_protocol`, answering `200 OK` to each request. Returns the port and the
+    /// certificate in PEM format.
+    fn start_tls_server(dns_name: &str, max_protocol: Option<SslVersion>) -> (u16, Vec<u8>) {
+        use openssl::{
+            asn1::Asn1Time,
+            ec::{EcGroup, EcKey},
+            hash::MessageDigest,
+            nid::Nid,
+            pkey::PKey,
+            ssl::{SslAcceptor, SslMethod},
+            x509::{X509, X509NameBuilder, extension::SubjectAlternativeName},
+        };
+
+        let group = EcGroup::from_curve_name(Nid::X9_62_PRIME256V1).unwrap();
+        let key = PKey::from_ec_key(EcKey::generate(&group).unwrap()).unwrap();
+
+        let mut name = X509NameBuilder::new().unwrap();
+        name.append_entry_by_nid(Nid::COMMONNAME, dns_name).unwrap();
+        let name = name.build();
+
+        let mut builder = X509::builder().unwrap();
+        builder.set_version(2).unwrap();
+        builder.set_subject_name(&name).unwrap();
+        builder.set_issuer_name(&name).unwrap();
+        builder.set_pubkey(&key).unwrap();
+        builder
+            .set_not_before(&Asn1Time::days_from_now(0).unwrap())
+            .unwrap();
+        builder
+            .set_not_after(&Asn1Time::days_from_now(1).unwrap())
+            .unwrap();
+        let san = SubjectAlternativeName::new()
+            .dns(dns_name)
+            .build(&builder.x509v3_context(None, None))
+            .unwrap();
+        builder.append_extension(san).unwrap();
+        builder.sign(&key, MessageDigest::sha256()).unwrap();
+        let certificate = builder.build();
+        let cert_pem = certificate.to_pem().unwrap();
+
+        // The native TLS acceptor disables TLS 1.3, OpenSSL's own one is needed to offer it
+        let mut acceptor = SslAcceptor::mozilla_intermediate_v5(SslMethod::tls()).unwrap();
+        acceptor.set_private_key(&key).unwrap();
+        acceptor.set_certificate(&certificate).unwrap();
+        acceptor.set_max_proto_version(max_protocol).unwrap();
+        let acceptor = Arc::new(acceptor.build());
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+
+        std::thread::spawn(move || {
+            use std::io::{Read, Write};
+
+            for stream in listener.incoming().flatten() {
+                let acceptor = acceptor.clone();
+                std::thread::spawn(move || {
+                    // Clients rejecting the certificate or version abort the handshake
+                    let Ok(mut stream) = acceptor.accept(stream) else {
+                        return;
+                    };
+                    let mut request = [0; 1024];
+                    if stream.read(&mut request).unwrap_or(0) > 0 {
+                        let _ = stream.write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nOK");
+                    }
+                });
+            }
+        });
+
+        (port, cert_pem)
+    }
+
+    #[tokio::test]
+    async fn test_execute_check_tls_hostname_mismatch() {
+        let (port, cert_pem) = start_tls_server("example.invalid", None);
+
+        // Trust the certificate, so that only the hostname check fails
+        let client = Client::builder()
+            .add_root_certificate(reqwest::Certificate::from_pem(&cert_pem).unwrap())
+            .build()
+            .unwrap();
+        let check = ServiceCheck {
+            url: format!("https://localhost:{port}/").parse().unwrap(),
+            timeout_seconds: 5,
+            allow_private_targets: true,
+            ..ServiceCheck::example()
+        };
+
+        let result = execute_check(
+            &client,
+            &DnsCache::default(),
+            &check,
+            &HostAllowlist::default(),
+        )
+        .await
+        .unwrap();
+
+        assert!(!result.matches_expected);
+        assert_eq!(result.status_code, None);
+        assert_eq!(result.failure_reason, Some(FailureReason::Tls));
+        let detail = result.failure_detail.unwrap().to_lowercase();
+        assert!(detail.contains("hostname mismatch"), "{detail}");
+    }
+
+    #[tokio::test]
+    async fn test_probe_records_negotiated_tls_version() {
+        let (port, cert_pem) = start_tls_server("localhost", None);
+
+        // The client of checks with `min_tls_version`, trusting the certificate
+        let client = probe_client_builder(None)
+            .add_root_certificate(reqwest::Certificate::from_pem(&cert_pem).unwrap())
+            .min_tls_version(MinTlsVersion::Tls1_2.into())
+            .build()
+            .unwrap();
+        let mut check = ServiceCheck {
+            url: format!("https://localhost:{port}/").parse().unwrap(),
+            timeout_seconds: 5,
+            min_tls_version: Some(MinTlsVersion::Tls1_2),
+            allow_private_targets: true,
+            ..ServiceCheck::example()
+        };
+
+        let (outcome, _) = probe_url(&client, &check, &check.url).await.unwrap();
+        assert!(outcome.matches_expected, "{:?}", outcome.failure_detail);
+        assert_eq!(outcome.status_code, Some(200));
+        assert_eq!(outcome.tls_version.as_deref(), Some("TLSv1.3"));
+
+        // Only recorded when a minimum is set
+        check.min_tls_version = None;
+        let (outcome, _) = probe_url(&client, &check, &check.url).await.unwrap();
+        assert!(outcome.matches_expected);
+        assert_eq!(outcome.tls_version, None);
+    }
+
+    #[tokio::test]
+    async fn test_execute_check_min_tls_version_above_server() {
+        // Offers TLS 1.1 at most, below the minimum of the check
+        let (port, _) = start_tls_server("localhost", Some(SslVersion::TLS1_1));
+        let check = ServiceCheck {
+            url: format!("https://localhost:{port}/").parse().unwrap(),
+            timeout_seconds: 5,
+            min_tls_version: Some(MinTlsVersion::Tls1_2),
+            allow_private_targets: true,
+            ..ServiceCheck::example()
+        };
+
+        let result = execute_check(
+            &Client::new(),
+            &DnsCache::default(),
+            &check,
+            &HostAllowlist::default(),
+        )
+        .await
+        .unwrap();
+        assert!(!result.matches_expected);
+        assert_eq!(result.status_code, None);
+        assert_eq!(
+            result.failure_reason,
+            Some(FailureReason::TlsVersion),
+            "{:?}",
+            result.failure_detail
+        );
+        assert_eq!(result.tls_version, None);
+    }
+
     #[tokio::test]
     async fn test_execute_check_example_com() {
         let client = Client::new();
@@ -273,14 +2022,38 @@ mod tests {
             http_method: Method::Get,
             check_frequency_seconds: 60,
             timeout_seconds: 10,
-            expected_status_code: 200,
+            expected_status_codes: 200.into(),
             request_headers: HashMap::new(),
             request_body: None,
             is_enabled: true,
             created_at: Utc::now(),
+            kind: CheckKind::Http,
+            steps: vec![],
+            dns_cache_ttl_seconds: None,
+            proxy: None,
+            conditional: None,
+            geo_assertion: None,
+            fallback_urls: vec![],
+            body_regex: None,
+            min_tls_version: None,
+            decompress_response: false,
+            allow_private_targets: false,
+            connect_timeout_millis: None,
+            assertions: None,
+            store_raw_results: true,
+            retries: 0,
+            retry_delay_ms: 0,
+            pinned_node: None,
         };
 
-        execute_check(&client, &check, false).await.unwrap();
+        execute_check(
+            &client,
+            &DnsCache::default(),
+            &check,
+            &HostAllowlist::default(),
+        )
+        .await
+        .unwrap();
     }
 
     #[tokio::test]
@@ -300,19 +2073,81 @@ mod tests {
             http_method: Method::Get,
             check_frequency_seconds: 60,
             timeout_seconds: 30,
-            expected_status_code: 200,
+            expected_status_codes: 200.into(),
             request_headers: HashMap::new(),
             request_body: None,
             is_enabled: true,
             created_at: Utc::now(),
+            kind: CheckKind::Http,
+            steps: vec![],
+            dns_cache_ttl_seconds: None,
+            proxy: None,
+            conditional: None,
+            geo_assertion: None,
+            fallback_urls: vec![],
+            body_regex: None,
+            min_tls_version: None,
+            decompress_response: false,
+            allow_private_targets: false,
+            connect_timeout_millis: None,
+            assertions: None,
+            store_raw_results: true,
+            retries: 0,
+            retry_delay_ms: 0,
+            pinned_node: None,
         };
 
-        let result = execute_check(&client, &check, false).await;
+        let result = execute_check(
+            &client,
+            &DnsCache::default(),
+            &check,
+            &HostAllowlist::default(),
+        )
+        .await;
         assert!(result.is_err());
 
         mock.assert_calls(0);
     }
 
+    #[tokio::test]
+    async fn test_execute_check_private_targets_per_check() {
+        let server = MockServer::start();
+        let mock = server.mock(|when, then| {
+            when.method(GET).path("/");
+            then.status(200);
+        });
+
+        let client = Client::new();
+        let mut check = ServiceCheck {
+            url: server.url("/").parse().unwrap(),
+            allow_private_targets: false,
+            ..ServiceCheck::example()
+        };
+
+        // Blocked without the flag, whatever DEV_MODE is
+        let result = execute_check(
+            &client,
+            &DnsCache::default(),
+            &check,
+            &HostAllowlist::default(),
+        )
+        .await;
+        assert!(result.is_err(), "dev mode: {}", *eager_env::DEV_MODE);
+        mock.assert_calls(0);
+
+        check.allow_private_targets = true;
+        let result = execute_check(
+            &client,
+            &DnsCache::default(),
+            &check,
+            &HostAllowlist::default(),
+        )
+        .await
+        .unwrap();
+        assert!(result.matches_expected);
+        mock.assert_calls(1);
+    }
+
     #[tokio::test]
     async fn test_execute_check_google() {
         init_logging(log::LevelFilter::Trace);
@@ -326,20 +2161,44 @@ mod tests {
             http_method: Method::Get,
             check_frequency_seconds: 60,
             timeout_seconds: 30,
-            expected_status_code: 200,
+            expected_status_codes: 200.into(),
             request_headers: HashMap::new(),
             request_body: None,
             is_enabled: true,
             created_at: Utc::now(),
+            kind: CheckKind::Http,
+            steps: vec![],
+            dns_cache_ttl_seconds: None,
+            proxy: None,
+            conditional: None,
+            geo_assertion: None,
+            fallback_urls: vec![],
+            body_regex: None,
+            min_tls_version: None,
+            decompress_response: false,
+            allow_private_targets: false,
+            connect_timeout_millis: None,
+            assertions: None,
+            store_raw_results: true,
+            retries: 0,
+            retry_delay_ms: 0,
+            pinned_node: None,
         };
 
-        execute_check(&client, &check, false).await.unwrap();
+        execute_check(
+            &client,
+            &DnsCache::default(),
+            &check,
+            &HostAllowlist::default(),
+        )
+        .await
+        .unwrap();
     }
 
     #[tokio::test]
     async fn test_validate_and_transform_url_success() {
         let url: Url = "https://example.com/path".parse().unwrap();
-        let result = validate_and_transform_url(&url, false).await;
+        let result = validate_and_transform_url(&url, false, &DnsCache::default(), None).await;
 
         assert!(result.is_ok());
         let (ip_url, original_host) = result.unwrap();
@@ -357,7 +2216,7 @@ mod tests {
     #[tokio::test]
     async fn test_validate_and_transform_url_blocks_private_ip() {
         let url: Url = "http://localhost/admin".parse().unwrap();
-        let result = validate_and_transform_url(&url, false).await;
+        let result = validate_and_transform_url(&url, false, &DnsCache::default(), None).await;
 
         // Should fail because localhost resolves to 127.0.0.1 (private)
         assert!(result.is_err());
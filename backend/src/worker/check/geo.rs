@@ -0,0 +1,56 @@
+use crate::regions::Region;
+use reqwest::header::HeaderMap;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use utoipa::ToSchema;
+
+/// Asserts which edge location served the probe, e.g. the PoP code at the end of a `CF-Ray` header.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct GeoAssertion {
+    /// Response header carrying the serving location, e.g. `CF-Ray` or `X-Served-By`
+    pub header: String,
+    /// Substring the header must contain, per monitoring region.
+    /// Regions without an entry are not checked.
+    pub expected: BTreeMap<Region, String>,
+}
+
+impl GeoAssertion {
+    /// Whether the response was served from the location expected for `region`.
+    ///
+    /// Fails when the header is missing or not valid UTF-8.
+    pub fn matches(&self, region: Region, headers: &HeaderMap) -> bool {
+        let Some(expected) = self.expected.get(&region) else {
+            return true;
+        };
+
+        headers
+            .get(&self.header)
+            .and_then(|value| value.to_str().ok())
+            .is_some_and(|value| value.contains(expected.as_str()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::header::HeaderValue;
+
+    #[test]
+    fn test_matches() {
+        let assertion = GeoAssertion {
+            header: "CF-Ray".to_string(),
+            expected: BTreeMap::from([(Region::Fsn1, "FRA".to_string())]),
+        };
+
+        let mut headers = HeaderMap::new();
+        assert!(!assertion.matches(Region::Fsn1, &headers));
+        // Not asserted for this region
+        assert!(assertion.matches(Region::Hel1, &headers));
+
+        headers.insert("cf-ray", HeaderValue::from_static("8a1b2c3d4e5f6789-FRA"));
+        assert!(assertion.matches(Region::Fsn1, &headers));
+
+        headers.insert("cf-ray", HeaderValue::from_static("8a1b2c3d4e5f6789-AMS"));
+        assert!(!assertion.matches(Region::Fsn1, &headers));
+    }
+}
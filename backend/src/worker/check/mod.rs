@@ -1,2 +1,13 @@
+pub mod aggregate;
+pub mod assertions;
+pub mod body;
+pub mod conditional;
+pub mod dns;
 pub mod execute;
+pub mod geo;
+pub mod passive;
+pub mod proxy;
 pub mod save;
+pub mod status;
+pub mod steps;
+pub mod tls;
@@ -0,0 +1,152 @@
+use crate::database::Database;
+use crate::queries::pings::get_last_check_ping;
+use crate::worker::check::execute::{CheckResult, FailureReason};
+use crate::worker::fetch::ServiceCheck;
+use anyhow::Result;
+use chrono::{DateTime, Duration, Utc};
+use log::trace;
+use uuid::Uuid;
+
+/// Whether a passive check is up at `now`, i.e. its last ping is at most `frequency` plus
+/// `grace` old. A check never pinged counts from its creation.
+fn is_ping_fresh(
+    last_ping: Option<DateTime<Utc>>,
+    created_at: DateTime<Utc>,
+    frequency: Duration,
+    grace: Duration,
+    now: DateTime<Utc>,
+) -> bool {
+    now - last_ping.unwrap_or(created_at) <= frequency + grace
+}
+
+/// Evaluates a `Passive` check from its last ping instead of probing it.
+pub async fn evaluate_passive_check(
+    db: &Database,
+    check: &ServiceCheck,
+    grace: std::time::Duration,
+    now: DateTime<Utc>,
+) -> Result<CheckResult> {
+    let last_ping = get_last_check_ping(db, check.check_id).await?;
+    let fresh = is_ping_fresh(
+        last_ping,
+        check.created_at,
+        Duration::seconds(check.check_frequency_seconds as i64),
+        Duration::from_std(grace)?,
+        now,
+    );
+
+    trace!(
+        "Passive check evaluated: {} - last ping: {:?}, up: {}",
+        check.check_name, last_ping, fresh
+    );
+
+    Ok(CheckResult {
+        result_id: Uuid::new_v4(),
+        service_check_id: check.check_id,
+        check_started_at: now,
+        response_time_micros: 0,
+        ttfb_micros: None,
+        status_code: None,
+        matches_expected: fresh,
+        response_body_fetched: false,
+        response_body: None,
+        response_size_bytes: None,
+        decompressed_size_bytes: None,
+        failed_step: None,
+        fallback_index: None,
+        failure_reason: (!fresh).then_some(FailureReason::MissedPing),
+        failure_detail: match last_ping {
+            _ if fresh => None,
+            Some(last_ping) => Some(format!("Last ping at {}", last_ping.to_rfc3339())),
+            None => Some("Never pinged".to_string()),
+        },
+        assertion_results: None,
+        tls_version: None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::testing::create_test_database;
+    use crate::queries::pings::{rotate_check_ping_token, set_last_check_ping};
+    use crate::worker::CheckKind;
+
+    const GRACE: std::time::Duration = std::time::Duration::from_secs(30);
+
+    #[test]
+    fn test_is_ping_fresh() {
+        let now = Utc::now();
+        let frequency = Duration::seconds(60);
+        let grace = Duration::seconds(30);
+        let created_at = now - Duration::days(1);
+
+        assert!(is_ping_fresh(
+            Some(now - Duration::seconds(10)),
+            created_at,
+            frequency,
+            grace,
+            now
+        ));
+        // Late pings are tolerated within the grace period
+        assert!(is_ping_fresh(
+            Some(now - Duration::seconds(90)),
+            created_at,
+            frequency,
+            grace,
+            now
+        ));
+        assert!(!is_ping_fresh(
+            Some(now - Duration::seconds(91)),
+            created_at,
+            frequency,
+            grace,
+            now
+        ));
+
+        // Never pinged, only up right after creation
+        assert!(is_ping_fresh(
+            None,
+            now - Duration::seconds(10),
+            frequency,
+            grace,
+            now
+        ));
+        assert!(!is_ping_fresh(None, created_at, frequency, grace, now));
+    }
+
+    #[tokio::test]
+    async fn test_evaluate_passive_check() -> Result<()> {
+        let (session, _keyspace) = create_test_database(None).await?;
+        let check = ServiceCheck {
+            kind: CheckKind::Passive,
+            check_frequency_seconds: 60,
+            created_at: Utc::now() - Duration::days(1),
+            ..ServiceCheck::example()
+        };
+
+        // Missed ping, never received any
+        let result = evaluate_passive_check(&session, &check, GRACE, Utc::now()).await?;
+        assert!(!result.matches_expected);
+        assert_eq!(result.failure_reason, Some(FailureReason::MissedPing));
+        assert_eq!(result.failure_detail.as_deref(), Some("Never pinged"));
+
+        // Ping received
+        rotate_check_ping_token(&session, check.check_id).await?;
+        let pinged_at = Utc::now();
+        set_last_check_ping(&session, check.check_id, pinged_at).await?;
+
+        let result = evaluate_passive_check(&session, &check, GRACE, pinged_at).await?;
+        assert!(result.matches_expected);
+        assert_eq!(result.failure_reason, None);
+        assert_eq!(result.service_check_id, check.check_id);
+
+        // Missed ping, the last one is too old
+        let later = pinged_at + Duration::seconds(91);
+        let result = evaluate_passive_check(&session, &check, GRACE, later).await?;
+        assert!(!result.matches_expected);
+        assert_eq!(result.failure_reason, Some(FailureReason::MissedPing));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,211 @@
+use crate::eager_env;
+use anyhow::{Context, Result, anyhow};
+use openssl::{
+    base64,
+    rand::rand_bytes,
+    symm::{Cipher, decrypt_aead, encrypt_aead},
+};
+use reqwest::Proxy;
+use serde::{Deserialize, Serialize};
+use std::{convert::Infallible, fmt, str::FromStr};
+use utoipa::ToSchema;
+
+/// HTTP(S) or SOCKS5 proxy the probes of a check are routed through.
+#[derive(Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct ProxyConfig {
+    pub url: String,
+    #[serde(default)]
+    pub username: Option<String>,
+    /// Write-only: never returned by the API
+    #[serde(default, skip_serializing)]
+    pub password: Option<String>,
+    /// Let the proxy resolve target hosts instead of resolving them locally.
+    ///
+    /// Targets can then only be validated by name, so they must be in `PROXY_REMOTE_DNS_ALLOWED_HOSTS`.
+    /// HTTP proxies always resolve remotely, this switches SOCKS5 proxies to `socks5h`.
+    #[serde(default)]
+    pub remote_dns: bool,
+}
+
+impl fmt::Debug for ProxyConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ProxyConfig")
+            .field("url", &self.url)
+            .field("username", &self.username)
+            .field("password", &self.password.as_ref().map(|_| "<redacted>"))
+            .field("remote_dns", &self.remote_dns)
+            .finish()
+    }
+}
+
+impl ProxyConfig {
+    /// Builds the proxy every request of the check is routed through.
+    ///
+    /// Credentials are sent with basic auth when a username is set.
+    pub fn build_proxy(&self) -> Result<Proxy> {
+        let url = match self.url.strip_prefix("socks5://") {
+            Some(rest) if self.remote_dns => format!("socks5h://{rest}"),
+            _ => self.url.clone(),
+        };
+        let mut proxy = Proxy::all(url).context("Invalid proxy URL")?;
+
+        if let Some(username) = &self.username {
+            proxy = proxy.basic_auth(username, self.password.as_deref().unwrap_or_default());
+        }
+
+        Ok(proxy)
+    }
+}
+
+/// Marks stored proxy passwords encrypted with `PROXY_PASSWORD_KEY`, the others are plaintext
+const ENCRYPTED_PASSWORD_PREFIX: &str = "aes256gcm:";
+const NONCE_LEN: usize = 12;
+const TAG_LEN: usize = 16;
+
+/// Proxy password as written to the database: encrypted when `PROXY_PASSWORD_KEY` is set.
+pub fn encrypt_password(password: &str) -> Result<String> {
+    match eager_env::proxy_password_key() {
+        Some(key) => encrypt_password_with(&key, password),
+        None => Ok(password.to_string()),
+    }
+}
+
+/// Proxy password read from the database, decrypting it if it was stored encrypted.
+pub fn decrypt_password(stored: String) -> Result<String> {
+    if !stored.starts_with(ENCRYPTED_PASSWORD_PREFIX) {
+        return Ok(stored);
+    }
+    let key = eager_env::proxy_password_key()
+        .context("PROXY_PASSWORD_KEY is required to read encrypted proxy passwords")?;
+    decrypt_password_with(&key, &stored)
+}
+
+/// Stored as the prefix followed by the base64 of the nonce, ciphertext and tag
+fn encrypt_password_with(key: &[u8; 32], password: &str) -> Result<String> {
+    let mut nonce = [0; NONCE_LEN];
+    rand_bytes(&mut nonce)?;
+    let mut tag = [0; TAG_LEN];
+    let ciphertext = encrypt_aead(
+        Cipher::aes_256_gcm(),
+        key,
+        Some(&nonce),
+        &[],
+        password.as_bytes(),
+        &mut tag,
+    )?;
+
+    Ok(format!(
+        "{ENCRYPTED_PASSWORD_PREFIX}{}",
+        base64::encode_block(&[&nonce[..], &ciphertext, &tag].concat())
+    ))
+}
+
+fn decrypt_password_with(key: &[u8; 32], stored: &str) -> Result<String> {
+    let sealed = stored
+        .strip_prefix(ENCRYPTED_PASSWORD_PREFIX)
+        .map(base64::decode_block)
+        .transpose()?
+        .filter(|sealed| sealed.len() >= NONCE_LEN + TAG_LEN)
+        .ok_or_else(|| anyhow!("Malformed encrypted proxy password"))?;
+    let (nonce, rest) = sealed.split_at(NONCE_LEN);
+    let (ciphertext, tag) = rest.split_at(rest.len() - TAG_LEN);
+    let password = decrypt_aead(
+        Cipher::aes_256_gcm(),
+        key,
+        Some(nonce),
+        &[],
+        ciphertext,
+        tag,
+    )
+    .context("Failed to decrypt proxy password, was PROXY_PASSWORD_KEY changed?")?;
+
+    Ok(String::from_utf8(password)?)
+}
+
+/// Target hosts that proxies resolving DNS remotely may reach.
+///
+/// Parsed from a comma-separated list, where `*.example.com` matches any subdomain of `example.com`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct HostAllowlist(Vec<String>);
+
+impl FromStr for HostAllowlist {
+    type Err = Infallible;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(Self(
+            s.split(',')
+                .map(|host| host.trim().trim_end_matches('.').to_ascii_lowercase())
+                .filter(|host| !host.is_empty())
+                .collect(),
+        ))
+    }
+}
+
+impl HostAllowlist {
+    pub fn allows(&self, host: &str) -> bool {
+        let host = host.trim_end_matches('.').to_ascii_lowercase();
+
+        self.0
+            .iter()
+            .any(|allowed| match allowed.strip_prefix("*.") {
+                Some(domain) => host
+                    .strip_suffix(domain)
+                    .is_some_and(|subdomain| subdomain.ends_with('.') && subdomain.len() > 1),
+                None => host == *allowed,
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_password_is_hidden() {
+        let config = ProxyConfig {
+            url: "http://proxy.example.com:3128".to_string(),
+            username: Some("user".to_string()),
+            password: Some("hunter2".to_string()),
+            remote_dns: false,
+        };
+
+        let json = serde_json::to_string(&config).unwrap();
+        assert!(!json.contains("hunter2"));
+        assert!(!format!("{config:?}").contains("hunter2"));
+    }
+
+    #[test]
+    fn test_password_encryption() {
+        let key = [7; 32];
+
+        let stored = encrypt_password_with(&key, "hunter2").unwrap();
+        assert!(stored.starts_with(ENCRYPTED_PASSWORD_PREFIX));
+        assert!(!stored.contains("hunter2"));
+        assert_ne!(stored, encrypt_password_with(&key, "hunter2").unwrap());
+        assert_eq!(decrypt_password_with(&key, &stored).unwrap(), "hunter2");
+
+        assert!(decrypt_password_with(&[8; 32], &stored).is_err());
+        assert!(decrypt_password_with(&key, "aes256gcm:c2hvcnQ=").is_err());
+
+        // Passwords stored before encryption are read as is
+        assert_eq!(decrypt_password("hunter2".to_string()).unwrap(), "hunter2");
+    }
+
+    #[test]
+    fn test_host_allowlist() {
+        let allowlist: HostAllowlist = " api.example.com, *.internal.example.org ,"
+            .parse()
+            .unwrap();
+
+        assert!(allowlist.allows("api.example.com"));
+        assert!(allowlist.allows("API.Example.com."));
+        assert!(allowlist.allows("status.internal.example.org"));
+        assert!(allowlist.allows("a.b.internal.example.org"));
+
+        assert!(!allowlist.allows("example.com"));
+        assert!(!allowlist.allows("www.api.example.com"));
+        assert!(!allowlist.allows("internal.example.org"));
+        assert!(!allowlist.allows("evilinternal.example.org"));
+        assert!(!HostAllowlist::default().allows("api.example.com"));
+    }
+}
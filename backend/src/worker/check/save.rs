@@ -1,11 +1,20 @@
 use crate::database::preparer::CachedPreparedStatement;
-use crate::{database::Database, eager_env, regions::Region, worker::check::execute::CheckResult};
+use crate::{
+    database::Database,
+    eager_env,
+    regions::Region,
+    worker::check::{aggregate::ResultAggregator, execute::CheckResult},
+};
 use anyhow::Result;
+use chrono::{DateTime, NaiveDate, Utc};
 use futures::StreamExt;
+use scylla::SerializeRow;
 use std::sync::Arc;
-use tokio::sync::mpsc;
+use tokio::sync::{mpsc, oneshot};
 use tokio::task::JoinHandle;
+use tokio::time::MissedTickBehavior;
 use tokio_stream::wrappers::UnboundedReceiverStream;
+use uuid::Uuid;
 
 static SAVE_CHECK_RESULT_QUERY: CachedPreparedStatement = CachedPreparedStatement::new(
     "
@@ -18,14 +27,54 @@ static SAVE_CHECK_RESULT_QUERY: CachedPreparedStatement = CachedPreparedStatemen
                                status_code,
                                matches_expected,
                                response_body_fetched,
-                               response_body)
-    VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+                               response_body,
+                               response_size_bytes,
+                               decompressed_size_bytes,
+                               failed_step,
+                               fallback_index,
+                               failure_reason,
+                               failure_detail,
+                               assertion_results,
+                               ttfb_micros,
+                               tls_version)
+    VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
     ",
 );
 
+/// Values bound to [`SAVE_CHECK_RESULT_QUERY`]
+#[derive(SerializeRow)]
+struct CheckResultInsertRow<'a> {
+    result_id: Uuid,
+    service_check_id: Uuid,
+    region: &'static str,
+    day: NaiveDate,
+    check_started_at: DateTime<Utc>,
+    response_time_micros: i64,
+    status_code: Option<i32>,
+    matches_expected: bool,
+    response_body_fetched: bool,
+    response_body: Option<&'a str>,
+    response_size_bytes: Option<i64>,
+    decompressed_size_bytes: Option<i64>,
+    failed_step: Option<i32>,
+    fallback_index: Option<i32>,
+    failure_reason: Option<String>,
+    failure_detail: Option<&'a str>,
+    assertion_results: Option<String>,
+    ttfb_micros: Option<i64>,
+    tls_version: Option<&'a str>,
+}
+
+/// Time between two writes of the completed aggregates, see [`ResultSaveManager::aggregate`]
+const AGGREGATE_FLUSH_INTERVAL: std::time::Duration = std::time::Duration::from_secs(60);
+
 pub struct ResultSaveManager {
     sender: mpsc::UnboundedSender<CheckResult>,
     worker_handle: JoinHandle<()>,
+    db: Arc<Database>,
+    aggregator: Arc<ResultAggregator>,
+    aggregate_flush_shutdown: oneshot::Sender<()>,
+    aggregate_flush_handle: JoinHandle<()>,
 }
 
 impl ResultSaveManager {
@@ -34,24 +83,65 @@ impl ResultSaveManager {
 
         let (sender, receiver) = mpsc::unbounded_channel();
 
-        let worker_handle = tokio::spawn(Self::worker(db, receiver, region));
+        let db_clone = db.clone();
+        let worker_handle = tokio::spawn(Self::worker(receiver, move |result| {
+            let db = db_clone.clone();
+            async move { Self::save_single(&db, result, region).await }
+        }));
+
+        let aggregator = Arc::new(ResultAggregator::new(region));
+        let (aggregate_flush_shutdown, shutdown) = oneshot::channel();
+        let aggregate_flush_handle = tokio::spawn(Self::flush_aggregates_task_body(
+            db.clone(),
+            aggregator.clone(),
+            shutdown,
+        ));
 
         Ok(Self {
             sender,
             worker_handle,
+            db,
+            aggregator,
+            aggregate_flush_shutdown,
+            aggregate_flush_handle,
         })
     }
 
-    async fn worker(
+    /// Caches the aggregates of the completed periods every [`AGGREGATE_FLUSH_INTERVAL`], until
+    /// `shutdown` fires or its sender is dropped.
+    ///
+    /// Only the wait between flushes is interrupted: a flush has taken its periods out of the
+    /// aggregator, cancelling it would lose them.
+    async fn flush_aggregates_task_body(
         db: Arc<Database>,
-        receiver: mpsc::UnboundedReceiver<CheckResult>,
-        region: Region,
+        aggregator: Arc<ResultAggregator>,
+        mut shutdown: oneshot::Receiver<()>,
     ) {
+        let mut interval = tokio::time::interval(AGGREGATE_FLUSH_INTERVAL);
+        interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        loop {
+            tokio::select! {
+                _ = interval.tick() => {}
+                _ = &mut shutdown => return,
+            }
+            if let Err(e) = aggregator.flush(&db, Utc::now(), false).await {
+                log::error!("Failed to cache aggregated results: {:?}", e);
+            }
+        }
+    }
+
+    /// Saves received results until the channel closes, with at most
+    /// `DATABASE_CONCURRENT_WRITES` saves in flight.
+    async fn worker<F, Fut>(receiver: mpsc::UnboundedReceiver<CheckResult>, save: F)
+    where
+        F: Fn(CheckResult) -> Fut,
+        Fut: Future<Output = Result<()>>,
+    {
         UnboundedReceiverStream::new(receiver)
-            .for_each_concurrent(*eager_env::DATABASE_CONCURRENT_REQUESTS, |result| {
-                let db = db.clone();
+            .for_each_concurrent(eager_env::DATABASE_CONCURRENT_WRITES.get(), |result| {
+                let saved = save(result);
                 async move {
-                    if let Err(e) = Self::save_single(&db, result, region).await {
+                    if let Err(e) = saved.await {
                         log::error!("Failed to save check result: {:?}", e);
                     }
                 }
@@ -60,26 +150,36 @@ impl ResultSaveManager {
     }
 
     async fn save_single(db: &Database, result: CheckResult, region: Region) -> Result<()> {
-        let region_str = region.to_identifier();
-        let day = result.check_started_at.date_naive();
-
-        SAVE_CHECK_RESULT_QUERY
-            .execute_unpaged(
-                db,
-                (
-                    result.result_id,
-                    result.service_check_id,
-                    region_str,
-                    day,
-                    result.check_started_at,
-                    result.response_time_micros,
-                    result.status_code,
-                    result.matches_expected,
-                    result.response_body_fetched,
-                    result.response_body.as_ref(),
-                ),
-            )
-            .await?;
+        let row = CheckResultInsertRow {
+            result_id: result.result_id,
+            service_check_id: result.service_check_id,
+            region: region.to_identifier(),
+            day: result.check_started_at.date_naive(),
+            check_started_at: result.check_started_at,
+            response_time_micros: result.response_time_micros,
+            status_code: result.status_code,
+            matches_expected: result.matches_expected,
+            response_body_fetched: result.response_body_fetched,
+            response_body: result.response_body.as_deref(),
+            response_size_bytes: result.response_size_bytes,
+            decompressed_size_bytes: result.decompressed_size_bytes,
+            failed_step: result.failed_step,
+            fallback_index: result.fallback_index,
+            failure_reason: result
+                .failure_reason
+                .map(|r| serde_plain::to_string(&r))
+                .transpose()?,
+            failure_detail: result.failure_detail.as_deref(),
+            assertion_results: result
+                .assertion_results
+                .as_ref()
+                .map(serde_json::to_string)
+                .transpose()?,
+            ttfb_micros: result.ttfb_micros,
+            tls_version: result.tls_version.as_deref(),
+        };
+
+        SAVE_CHECK_RESULT_QUERY.execute_unpaged(db, row).await?;
 
         Ok(())
     }
@@ -90,6 +190,12 @@ impl ResultSaveManager {
         Ok(())
     }
 
+    /// Only counts the result in the hourly and daily aggregates of its check, for checks not
+    /// storing raw results
+    pub fn aggregate(&self, result: &CheckResult) {
+        self.aggregator.record(result);
+    }
+
     pub async fn close(self) {
         // Drop the sender to signal the worker to stop
         drop(self.sender);
@@ -97,6 +203,15 @@ impl ResultSaveManager {
         if let Err(e) = self.worker_handle.await {
             log::error!("Worker handle join error: {:?}", e);
         }
+
+        // Let a flush in progress complete, then flush what it left, in progress or failed
+        let _ = self.aggregate_flush_shutdown.send(());
+        if let Err(e) = self.aggregate_flush_handle.await {
+            log::error!("Aggregate flush handle join error: {:?}", e);
+        }
+        if let Err(e) = self.aggregator.flush(&self.db, Utc::now(), true).await {
+            log::error!("Failed to cache aggregated results: {:?}", e);
+        }
     }
 }
 
@@ -104,29 +219,73 @@ impl ResultSaveManager {
 mod tests {
     use super::*;
     use crate::database::testing::create_test_database;
-    use crate::worker::check::execute::CheckResult;
-    use chrono::Utc;
+    use crate::worker::check::dns::DnsCache;
+    use crate::worker::check::execute::{CheckResult, execute_check};
+    use crate::worker::check::proxy::HostAllowlist;
+    use crate::worker::fetch::ServiceCheck;
+    use chrono::{DurationRound, Utc};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
     use uuid::Uuid;
 
-    #[tokio::test]
-    async fn test_save_result() -> Result<()> {
-        let (session, _keyspace) = create_test_database(None).await?;
-        let session = Arc::new(session);
-
-        let manager = ResultSaveManager::new(session.clone(), Region::Hel1).await?;
-
-        let result = CheckResult {
+    fn example_result() -> CheckResult {
+        CheckResult {
             result_id: Uuid::new_v4(),
             service_check_id: Uuid::new_v4(),
             check_started_at: Utc::now(),
             response_time_micros: 1500,
+            ttfb_micros: Some(900),
             status_code: Some(200),
             matches_expected: true,
             response_body_fetched: false,
             response_body: None,
-        };
+            response_size_bytes: Some(512),
+            decompressed_size_bytes: None,
+            failed_step: None,
+            fallback_index: None,
+            failure_reason: None,
+            failure_detail: None,
+            assertion_results: None,
+            tls_version: None,
+        }
+    }
 
-        manager.save(result)?;
+    #[tokio::test]
+    async fn test_worker_uses_write_concurrency_limit() {
+        let limit = eager_env::DATABASE_CONCURRENT_WRITES.get();
+        let in_flight = Arc::new(AtomicUsize::new(0));
+        let max_in_flight = Arc::new(AtomicUsize::new(0));
+
+        let (sender, receiver) = mpsc::unbounded_channel();
+        for _ in 0..limit * 3 {
+            sender.send(example_result()).unwrap();
+        }
+        drop(sender);
+
+        ResultSaveManager::worker(receiver, |_| {
+            let in_flight = in_flight.clone();
+            let max_in_flight = max_in_flight.clone();
+            async move {
+                let current = in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+                max_in_flight.fetch_max(current, Ordering::SeqCst);
+                tokio::time::sleep(Duration::from_millis(10)).await;
+                in_flight.fetch_sub(1, Ordering::SeqCst);
+                Ok(())
+            }
+        })
+        .await;
+
+        assert_eq!(max_in_flight.load(Ordering::SeqCst), limit);
+    }
+
+    #[tokio::test]
+    async fn test_save_result() -> Result<()> {
+        let (session, _keyspace) = create_test_database(None).await?;
+        let session = Arc::new(session);
+
+        let manager = ResultSaveManager::new(session.clone(), Region::Hel1).await?;
+
+        manager.save(example_result())?;
 
         // Close manager to flush and stop worker
         manager.close().await;
@@ -141,4 +300,126 @@ mod tests {
         assert_eq!(count, 1);
         Ok(())
     }
+
+    #[tokio::test]
+    async fn test_aggregate_results_without_raw_rows() -> Result<()> {
+        let (session, _keyspace) = create_test_database(None).await?;
+        let session = Arc::new(session);
+
+        let manager = ResultSaveManager::new(session.clone(), Region::Hel1).await?;
+
+        let check_id = Uuid::new_v4();
+        // Within the previous hour, completed
+        let hour_ago =
+            Utc::now().duration_trunc(chrono::Duration::hours(1))? - chrono::Duration::hours(1);
+        for i in 0..5 {
+            manager.aggregate(&CheckResult {
+                service_check_id: check_id,
+                check_started_at: hour_ago + chrono::Duration::seconds(i),
+                matches_expected: i != 0,
+                ..example_result()
+            });
+        }
+        manager.close().await;
+
+        let count = |table: &'static str| {
+            let session = session.clone();
+            async move {
+                session
+                    .query_unpaged(
+                        format!("SELECT COUNT(*) FROM {table} WHERE service_check_id = ? ALLOW FILTERING"),
+                        (check_id,),
+                    )
+                    .await?
+                    .into_rows_result()?
+                    .single_row::<(i64,)>()
+                    .map(|(count,)| count)
+                    .map_err(anyhow::Error::from)
+            }
+        };
+        assert_eq!(count("check_results").await?, 0);
+        assert_eq!(count("check_results_hourly").await?, 1);
+        assert_eq!(count("check_results_daily").await?, 1);
+
+        let (successful_checks, failed_checks) = session
+            .query_unpaged(
+                "SELECT successful_checks, failed_checks FROM check_results_hourly WHERE service_check_id = ? ALLOW FILTERING",
+                (check_id,),
+            )
+            .await?
+            .into_rows_result()?
+            .single_row::<(i32, i32)>()?;
+        assert_eq!((successful_checks, failed_checks), (4, 1));
+
+        // Another node probing the same hour later on, and stopping before the current one ends
+        let manager = ResultSaveManager::new(session.clone(), Region::Hel1).await?;
+        for i in 5..8 {
+            manager.aggregate(&CheckResult {
+                service_check_id: check_id,
+                check_started_at: hour_ago + chrono::Duration::seconds(i),
+                ..example_result()
+            });
+        }
+        manager.aggregate(&CheckResult {
+            service_check_id: check_id,
+            check_started_at: Utc::now(),
+            ..example_result()
+        });
+        manager.close().await;
+
+        let rows = session
+            .query_unpaged(
+                "SELECT hour, successful_checks, failed_checks, partial FROM check_results_hourly WHERE service_check_id = ? ALLOW FILTERING",
+                (check_id,),
+            )
+            .await?
+            .into_rows_result()?
+            .rows::<(DateTime<Utc>, i32, i32, Option<bool>)>()?
+            .collect::<Result<Vec<_>, _>>()?;
+        let completed = rows.iter().find(|(hour, ..)| *hour == hour_ago).unwrap();
+        assert_eq!((completed.1, completed.2, completed.3), (7, 1, Some(false)));
+        let in_progress = rows.iter().find(|(hour, ..)| *hour > hour_ago).unwrap();
+        assert_eq!(
+            (in_progress.1, in_progress.2, in_progress.3),
+            (1, 0, Some(true))
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_save_unresolvable_host_result() -> Result<()> {
+        let (session, _keyspace) = create_test_database(None).await?;
+        let session = Arc::new(session);
+
+        let check = ServiceCheck {
+            url: "http://unresolvable.invalid/health".parse()?,
+            allow_private_targets: true,
+            ..ServiceCheck::example()
+        };
+        let result = execute_check(
+            &reqwest::Client::new(),
+            &DnsCache::default(),
+            &check,
+            &HostAllowlist::default(),
+        )
+        .await?;
+
+        let manager = ResultSaveManager::new(session.clone(), Region::Hel1).await?;
+        manager.save(result)?;
+        manager.close().await;
+
+        let (matches_expected, failure_reason) = session
+            .query_unpaged(
+                "SELECT matches_expected, failure_reason FROM check_results",
+                &[],
+            )
+            .await?
+            .into_rows_result()?
+            .single_row::<(bool, Option<String>)>()?;
+
+        assert!(!matches_expected);
+        assert_eq!(failure_reason.as_deref(), Some("DNS"));
+        Ok(())
+    }
 }
@@ -0,0 +1,168 @@
+use serde::{Deserialize, Serialize};
+use std::ops::RangeInclusive;
+use utoipa::ToSchema;
+
+use crate::worker::check::conditional::ANY_STATUS_CODE;
+
+/// Status codes a check accepts, a list or an inclusive range.
+///
+/// A single code is accepted too, as a one-element list, so that the former
+/// `expected_status_code` still deserializes. `0` in a list accepts any response.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(untagged, from = "ExpectedStatusCodesRepr")]
+pub enum ExpectedStatusCodes {
+    /// Any of these codes
+    Codes(Vec<i32>),
+    /// Any code from `min` to `max`, both included, e.g. `200` to `299` for any success
+    Range { min: i32, max: i32 },
+}
+
+/// Status codes a server may send
+const VALID_STATUS_CODES: RangeInclusive<i32> = 100..=599;
+
+/// Accepted forms of [`ExpectedStatusCodes`]
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ExpectedStatusCodesRepr {
+    Code(i32),
+    Codes(Vec<i32>),
+    Range { min: i32, max: i32 },
+}
+
+impl From<ExpectedStatusCodesRepr> for ExpectedStatusCodes {
+    fn from(repr: ExpectedStatusCodesRepr) -> Self {
+        match repr {
+            ExpectedStatusCodesRepr::Code(code) => code.into(),
+            ExpectedStatusCodesRepr::Codes(codes) => ExpectedStatusCodes::Codes(codes),
+            ExpectedStatusCodesRepr::Range { min, max } => ExpectedStatusCodes::Range { min, max },
+        }
+    }
+}
+
+impl From<i32> for ExpectedStatusCodes {
+    fn from(code: i32) -> Self {
+        ExpectedStatusCodes::Codes(vec![code])
+    }
+}
+
+impl ExpectedStatusCodes {
+    pub fn contains(&self, status_code: i32) -> bool {
+        match self {
+            ExpectedStatusCodes::Codes(codes) => codes
+                .iter()
+                .any(|&code| code == ANY_STATUS_CODE || code == status_code),
+            ExpectedStatusCodes::Range { min, max } => (*min..=*max).contains(&status_code),
+        }
+    }
+
+    /// Single code kept in the former `expected_status_code` column: the first code of a list,
+    /// the start of a range
+    pub fn legacy_code(&self) -> i32 {
+        match self {
+            ExpectedStatusCodes::Codes(codes) => codes.first().copied().unwrap_or(ANY_STATUS_CODE),
+            ExpectedStatusCodes::Range { min, .. } => *min,
+        }
+    }
+
+    /// Why the codes are invalid or no code could ever be accepted, if so
+    pub fn validate(&self) -> Result<(), String> {
+        let invalid = |code: &i32| *code != ANY_STATUS_CODE && !VALID_STATUS_CODES.contains(code);
+
+        match self {
+            ExpectedStatusCodes::Codes(codes) if codes.is_empty() => {
+                Err("expected_status_codes must not be empty".to_string())
+            }
+            ExpectedStatusCodes::Codes(codes) => match codes.iter().find(|code| invalid(code)) {
+                Some(code) => Err(format!(
+                    "expected_status_codes has {code}, status codes are between 100 and 599"
+                )),
+                None => Ok(()),
+            },
+            ExpectedStatusCodes::Range { min, max } if min > max => Err(format!(
+                "expected_status_codes range is empty, {min} is above {max}"
+            )),
+            ExpectedStatusCodes::Range { min, max }
+                if !VALID_STATUS_CODES.contains(min) || !VALID_STATUS_CODES.contains(max) =>
+            {
+                Err(format!(
+                    "expected_status_codes range from {min} to {max} is not between 100 and 599"
+                ))
+            }
+            ExpectedStatusCodes::Range { .. } => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_expected_status_codes_serialization() {
+        let parse = |json: &str| serde_json::from_str::<ExpectedStatusCodes>(json).unwrap();
+
+        // The former single value
+        assert_eq!(parse("200"), ExpectedStatusCodes::Codes(vec![200]));
+        assert_eq!(
+            parse("[200, 204]"),
+            ExpectedStatusCodes::Codes(vec![200, 204])
+        );
+        assert_eq!(
+            parse(r#"{"min": 200, "max": 299}"#),
+            ExpectedStatusCodes::Range { min: 200, max: 299 }
+        );
+        assert!(serde_json::from_str::<ExpectedStatusCodes>(r#""2xx""#).is_err());
+
+        for codes in [
+            ExpectedStatusCodes::Codes(vec![200, 204]),
+            ExpectedStatusCodes::Range { min: 200, max: 299 },
+        ] {
+            let json = serde_json::to_string(&codes).unwrap();
+            assert_eq!(parse(&json), codes);
+        }
+    }
+
+    #[test]
+    fn test_expected_status_codes_legacy_code() {
+        assert_eq!(
+            ExpectedStatusCodes::Codes(vec![204, 200]).legacy_code(),
+            204
+        );
+        assert_eq!(
+            ExpectedStatusCodes::Range { min: 200, max: 299 }.legacy_code(),
+            200
+        );
+    }
+
+    #[test]
+    fn test_expected_status_codes_validation() {
+        assert!(ExpectedStatusCodes::from(200).validate().is_ok());
+        assert!(
+            ExpectedStatusCodes::from(ANY_STATUS_CODE)
+                .validate()
+                .is_ok()
+        );
+        assert!(ExpectedStatusCodes::Codes(vec![]).validate().is_err());
+        assert!(
+            ExpectedStatusCodes::Codes(vec![200, 999])
+                .validate()
+                .is_err()
+        );
+        assert!(ExpectedStatusCodes::from(-1).validate().is_err());
+        assert!(
+            ExpectedStatusCodes::Range { min: 299, max: 200 }
+                .validate()
+                .is_err()
+        );
+        assert!(
+            ExpectedStatusCodes::Range { min: 0, max: 299 }
+                .validate()
+                .is_err()
+        );
+        assert!(
+            ExpectedStatusCodes::Range { min: 200, max: 600 }
+                .validate()
+                .is_err()
+        );
+    }
+}
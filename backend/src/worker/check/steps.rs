@@ -0,0 +1,390 @@
+use crate::worker::check::body::MAX_RESPONSE_BYTES;
+use crate::worker::check::conditional::is_acceptable_status;
+use crate::worker::check::dns::is_resolution_failure;
+use crate::worker::check::execute::{
+    CheckResult, FailureReason, ProbeOutcome, TargetValidator, get_response_size, is_genuine_fail,
+    to_reqwest_method,
+};
+use crate::worker::fetch::{Method, ServiceCheck};
+use anyhow::{Context, Result, bail};
+use chrono::Utc;
+use log::trace;
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+use url::Url;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum CheckKind {
+    /// A single request to the check's `url`
+    #[default]
+    Http,
+    /// An ordered list of requests, see [`CheckStep`]
+    Steps,
+    /// Never probed: up while pings arrive on `POST /checks/{check_id}/ping` at least every
+    /// `check_frequency_seconds`, plus a grace period
+    Passive,
+}
+
+/// Where to read an extracted value from.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ExtractionSource {
+    /// Response header name
+    Header(String),
+    /// RFC 6901 pointer into a JSON response body, e.g. `/data/token`
+    JsonPointer(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct StepExtraction {
+    /// Later steps reference the value as `{{variable}}`
+    pub variable: String,
+    pub from: ExtractionSource,
+}
+
+/// A single request of a `Steps` check.
+///
+/// `url`, header values and `request_body` may reference values extracted by previous steps.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct CheckStep {
+    pub url: String,
+    pub http_method: Method,
+    #[serde(default)]
+    pub request_headers: HashMap<String, String>,
+    #[serde(default)]
+    pub request_body: Option<String>,
+    /// `0` accepts any response
+    pub expected_status_code: i32,
+    #[serde(default)]
+    pub extract: Vec<StepExtraction>,
+}
+
+/// Replaces every `{{variable}}` with its value. Unknown variables are left untouched.
+fn inject_variables(template: &str, variables: &HashMap<String, String>) -> String {
+    variables
+        .iter()
+        .fold(template.to_string(), |acc, (name, value)| {
+            acc.replace(&format!("{{{{{name}}}}}"), value)
+        })
+}
+
+/// Runs a single step, storing its extracted values into `variables`.
+async fn execute_step(
+    client: &Client,
+    validator: &TargetValidator<'_>,
+    step: &CheckStep,
+    timeout: Duration,
+    variables: &mut HashMap<String, String>,
+) -> Result<ProbeOutcome> {
+    let url: Url = inject_variables(&step.url, variables)
+        .parse()
+        .context("Invalid step URL")?;
+
+    match validator.validate(&url).await {
+        Ok(()) => {}
+        Err(error) if is_resolution_failure(&error) => {
+            return Ok(ProbeOutcome::from_resolution_error(&error));
+        }
+        Err(error) => return Err(error).context("URL validation failed"),
+    }
+
+    let mut request = client
+        .request(to_reqwest_method(step.http_method), url)
+        .timeout(timeout);
+
+    for (key, value) in &step.request_headers {
+        request = request.header(key, inject_variables(value, variables));
+    }
+
+    if let Some(body) = &step.request_body
+        && !body.is_empty()
+        && step.http_method.allows_body()
+    {
+        request = request.body(inject_variables(body, variables));
+    }
+
+    let response = match request.send().await {
+        Ok(response) => response,
+        Err(error) => {
+            if !is_genuine_fail(&error) {
+                return Err(error).context("not a genuine fail");
+            }
+            trace!("Step encountered error: {:?}", error);
+
+            return Ok(ProbeOutcome::from_error(&error));
+        }
+    };
+
+    let status_code = response.status().as_u16() as i32;
+    let status_matches = is_acceptable_status(status_code, &step.expected_status_code.into(), None);
+    let mut extracted_all = true;
+
+    for extraction in &step.extract {
+        if let ExtractionSource::Header(name) = &extraction.from {
+            match response.headers().get(name).map(|v| v.to_str()) {
+                Some(Ok(value)) => {
+                    variables.insert(extraction.variable.clone(), value.to_string());
+                }
+                _ => extracted_all = false,
+            }
+        }
+    }
+
+    let needs_body = step
+        .extract
+        .iter()
+        .any(|e| matches!(e.from, ExtractionSource::JsonPointer(_)));
+
+    let response_size_bytes = if step.http_method == Method::Head {
+        None
+    } else if needs_body {
+        let body = response.bytes().await.ok();
+        let json = body
+            .as_ref()
+            .and_then(|b| serde_json::from_slice::<serde_json::Value>(b).ok());
+
+        for extraction in &step.extract {
+            if let ExtractionSource::JsonPointer(pointer) = &extraction.from {
+                let value = json.as_ref().and_then(|j| j.pointer(pointer));
+                match value {
+                    Some(serde_json::Value::String(s)) => {
+                        variables.insert(extraction.variable.clone(), s.clone());
+                    }
+                    Some(serde_json::Value::Null) | None => extracted_all = false,
+                    Some(other) => {
+                        variables.insert(extraction.variable.clone(), other.to_string());
+                    }
+                }
+            }
+        }
+
+        body.map(|b| b.len() as i64)
+    } else {
+        match get_response_size(response, MAX_RESPONSE_BYTES).await {
+            Ok(size) => size,
+            Err(error) => return Ok(ProbeOutcome::from_error(&error)),
+        }
+    };
+
+    let failure_reason = if !status_matches {
+        Some(FailureReason::UnexpectedStatus)
+    } else if !extracted_all {
+        Some(FailureReason::Extraction)
+    } else {
+        None
+    };
+
+    Ok(ProbeOutcome {
+        status_code: Some(status_code),
+        matches_expected: failure_reason.is_none(),
+        response_size_bytes,
+        decompressed_size_bytes: None,
+        failure_reason,
+        failure_detail: None,
+        assertion_results: None,
+        tls_version: None,
+    })
+}
+
+/// Runs the steps of a `Steps` check sequentially, stopping at the first failing one.
+///
+/// The check's timeout, see [`ServiceCheck::timeout`], bounds the whole flow.
+/// The reported status code and size are the ones of the last step executed.
+pub async fn execute_steps(
+    client: &Client,
+    check: &ServiceCheck,
+    validator: &TargetValidator<'_>,
+) -> Result<CheckResult> {
+    if check.steps.is_empty() {
+        bail!("Steps check has no steps");
+    }
+
+    let timeout = check.timeout();
+    let start = Instant::now();
+    let check_started_at = Utc::now();
+
+    let mut variables = HashMap::new();
+    let mut last_outcome = None;
+    let mut failed_step = None;
+
+    for (index, step) in check.steps.iter().enumerate() {
+        let remaining = timeout.saturating_sub(start.elapsed());
+        let outcome = execute_step(client, validator, step, remaining, &mut variables)
+            .await
+            .with_context(|| format!("Step {index} failed to execute"))?;
+
+        let passed = outcome.matches_expected;
+        last_outcome = Some(outcome);
+
+        if !passed {
+            failed_step = Some(index as i32);
+            break;
+        }
+    }
+
+    let response_time_micros = start.elapsed().as_micros() as i64;
+    let last_outcome = last_outcome.expect("at least one step");
+
+    trace!(
+        "Steps check completed: {} - failed step: {:?}, time: {}μs",
+        check.check_name, failed_step, response_time_micros
+    );
+
+    Ok(CheckResult {
+        result_id: Uuid::new_v4(),
+        service_check_id: check.check_id,
+        check_started_at,
+        response_time_micros,
+        // Spans several requests
+        ttfb_micros: None,
+        status_code: last_outcome.status_code,
+        matches_expected: failed_step.is_none(),
+        response_body_fetched: false,
+        response_body: None,
+        response_size_bytes: last_outcome.response_size_bytes,
+        decompressed_size_bytes: last_outcome.decompressed_size_bytes,
+        failed_step,
+        fallback_index: None,
+        failure_reason: last_outcome.failure_reason,
+        failure_detail: last_outcome.failure_detail,
+        assertion_results: None,
+        tls_version: None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::worker::check::{dns::DnsCache, proxy::HostAllowlist};
+    use crate::worker::fetch::Method;
+    use httpmock::prelude::*;
+
+    fn step(url: String, http_method: Method, expected_status_code: i32) -> CheckStep {
+        CheckStep {
+            url,
+            http_method,
+            request_headers: HashMap::new(),
+            request_body: None,
+            expected_status_code,
+            extract: vec![],
+        }
+    }
+
+    #[test]
+    fn test_inject_variables() {
+        let variables = HashMap::from([("token".to_string(), "abc".to_string())]);
+
+        assert_eq!(
+            inject_variables("Bearer {{token}}", &variables),
+            "Bearer abc"
+        );
+        assert_eq!(inject_variables("{{missing}}", &variables), "{{missing}}");
+    }
+
+    #[tokio::test]
+    async fn test_two_step_flow_with_token() {
+        let server = MockServer::start();
+        let login_mock = server.mock(|when, then| {
+            when.method(POST).path("/login");
+            then.status(200)
+                .header("x-session", "session-1")
+                .json_body(serde_json::json!({ "data": { "token": "secret-token" } }));
+        });
+        let profile_mock = server.mock(|when, then| {
+            when.method(GET)
+                .path("/profile")
+                .header("authorization", "Bearer secret-token")
+                .header("x-session", "session-1");
+            then.status(200).body("profile");
+        });
+
+        let mut login = step(server.url("/login"), Method::Post, 200);
+        login.extract = vec![
+            StepExtraction {
+                variable: "token".to_string(),
+                from: ExtractionSource::JsonPointer("/data/token".to_string()),
+            },
+            StepExtraction {
+                variable: "session".to_string(),
+                from: ExtractionSource::Header("x-session".to_string()),
+            },
+        ];
+        let mut profile = step(server.url("/profile"), Method::Get, 200);
+        profile.request_headers = HashMap::from([
+            ("Authorization".to_string(), "Bearer {{token}}".to_string()),
+            ("X-Session".to_string(), "{{session}}".to_string()),
+        ]);
+
+        let check = ServiceCheck {
+            kind: CheckKind::Steps,
+            steps: vec![login, profile],
+            allow_private_targets: true,
+            ..ServiceCheck::example()
+        };
+
+        let dns_cache = DnsCache::default();
+        let allowlist = HostAllowlist::default();
+        let validator = TargetValidator::new(&check, &dns_cache, &allowlist);
+        let result = execute_steps(&Client::new(), &check, &validator)
+            .await
+            .unwrap();
+
+        assert!(result.matches_expected);
+        assert_eq!(result.failed_step, None);
+        assert_eq!(result.failure_reason, None);
+        assert_eq!(result.status_code, Some(200));
+        assert_eq!(result.response_size_bytes, Some(7));
+
+        login_mock.assert();
+        profile_mock.assert();
+    }
+
+    #[tokio::test]
+    async fn test_steps_stop_at_first_failure() {
+        let server = MockServer::start();
+        let login_mock = server.mock(|when, then| {
+            when.method(POST).path("/login");
+            then.status(200)
+                .json_body(serde_json::json!({ "other": 1 }));
+        });
+        let profile_mock = server.mock(|when, then| {
+            when.method(GET).path("/profile");
+            then.status(200);
+        });
+
+        let mut login = step(server.url("/login"), Method::Post, 200);
+        login.extract = vec![StepExtraction {
+            variable: "token".to_string(),
+            from: ExtractionSource::JsonPointer("/token".to_string()),
+        }];
+        let profile = step(server.url("/profile"), Method::Get, 200);
+
+        let check = ServiceCheck {
+            kind: CheckKind::Steps,
+            steps: vec![login, profile],
+            allow_private_targets: true,
+            ..ServiceCheck::example()
+        };
+
+        let dns_cache = DnsCache::default();
+        let allowlist = HostAllowlist::default();
+        let validator = TargetValidator::new(&check, &dns_cache, &allowlist);
+        let result = execute_steps(&Client::new(), &check, &validator)
+            .await
+            .unwrap();
+
+        // Extraction failed, so the second step never runs
+        assert!(!result.matches_expected);
+        assert_eq!(result.failed_step, Some(0));
+        assert_eq!(result.status_code, Some(200));
+        assert_eq!(result.failure_reason, Some(FailureReason::Extraction));
+
+        login_mock.assert();
+        profile_mock.assert_calls(0);
+    }
+}
@@ -0,0 +1,142 @@
+use anyhow::{Context, Result, anyhow, bail};
+use openssl::ssl::{SslConnector, SslMethod, SslVerifyMode, SslVersion};
+use reqwest::tls::Version;
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+use std::time::Duration;
+use url::Url;
+use utoipa::ToSchema;
+
+/// Oldest TLS version a check accepts: handshakes negotiating an older one fail.
+///
+/// TLS 1.3 can't be required, the native TLS backend doesn't support it as a minimum.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub enum MinTlsVersion {
+    #[serde(rename = "1.0")]
+    Tls1_0,
+    #[serde(rename = "1.1")]
+    Tls1_1,
+    #[serde(rename = "1.2")]
+    Tls1_2,
+}
+
+impl MinTlsVersion {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            MinTlsVersion::Tls1_0 => "1.0",
+            MinTlsVersion::Tls1_1 => "1.1",
+            MinTlsVersion::Tls1_2 => "1.2",
+        }
+    }
+}
+
+impl FromStr for MinTlsVersion {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "1.0" => Ok(MinTlsVersion::Tls1_0),
+            "1.1" => Ok(MinTlsVersion::Tls1_1),
+            "1.2" => Ok(MinTlsVersion::Tls1_2),
+            _ => bail!("Unsupported minimum TLS version: '{s}'"),
+        }
+    }
+}
+
+impl From<MinTlsVersion> for Version {
+    fn from(version: MinTlsVersion) -> Self {
+        match version {
+            MinTlsVersion::Tls1_0 => Version::TLS_1_0,
+            MinTlsVersion::Tls1_1 => Version::TLS_1_1,
+            MinTlsVersion::Tls1_2 => Version::TLS_1_2,
+        }
+    }
+}
+
+impl From<MinTlsVersion> for SslVersion {
+    fn from(version: MinTlsVersion) -> Self {
+        match version {
+            MinTlsVersion::Tls1_0 => SslVersion::TLS1,
+            MinTlsVersion::Tls1_1 => SslVersion::TLS1_1,
+            MinTlsVersion::Tls1_2 => SslVersion::TLS1_2,
+        }
+    }
+}
+
+/// Protocol version a handshake with the host of `url` negotiates, e.g. `TLSv1.3`, refusing
+/// versions older than `min_version`.
+///
+/// reqwest doesn't expose the version of its connections, so this takes a handshake of its own.
+/// The certificate is not verified, the probe's request does.
+pub async fn negotiated_version(
+    url: &Url,
+    min_version: MinTlsVersion,
+    timeout: Duration,
+) -> Result<String> {
+    let host = url.host_str().context("URL missing host")?.to_string();
+    let port = url
+        .port_or_known_default()
+        .context("Unable to determine port")?;
+
+    // Bracketed IPv6 literals resolve without them
+    let address = (host.trim_start_matches('[').trim_end_matches(']'), port);
+    let stream = tokio::time::timeout(timeout, tokio::net::TcpStream::connect(address))
+        .await
+        .context("Timed out connecting")??
+        .into_std()?;
+    stream.set_nonblocking(false)?;
+    stream.set_read_timeout(Some(timeout))?;
+    stream.set_write_timeout(Some(timeout))?;
+
+    tokio::task::spawn_blocking(move || {
+        let mut connector = SslConnector::builder(SslMethod::tls_client())?;
+        connector.set_verify(SslVerifyMode::NONE);
+        connector.set_min_proto_version(Some(min_version.into()))?;
+        let stream = connector
+            .build()
+            .configure()?
+            .verify_hostname(false)
+            .connect(&host, stream)
+            .map_err(|error| anyhow!("{error}"))?;
+
+        Ok(stream.ssl().version_str().to_string())
+    })
+    .await?
+}
+
+/// OpenSSL errors of handshakes failing to agree on a protocol version
+const VERSION_ERRORS: [&str; 3] = [
+    "unsupported protocol",
+    "alert protocol version",
+    "no protocols available",
+];
+
+/// Whether a TLS error message is caused by the versions supported by either side.
+pub fn is_version_error(message: &str) -> bool {
+    VERSION_ERRORS
+        .iter()
+        .any(|pattern| message.contains(pattern))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_min_tls_version_round_trip() {
+        for version in [
+            MinTlsVersion::Tls1_0,
+            MinTlsVersion::Tls1_1,
+            MinTlsVersion::Tls1_2,
+        ] {
+            assert_eq!(version.as_str().parse::<MinTlsVersion>().unwrap(), version);
+            assert_eq!(
+                serde_json::to_string(&version).unwrap(),
+                format!("\"{}\"", version.as_str())
+            );
+        }
+
+        assert!("1.3".parse::<MinTlsVersion>().is_err());
+        assert!(serde_json::from_str::<MinTlsVersion>("\"1.3\"").is_err());
+    }
+}
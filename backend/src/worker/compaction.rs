@@ -0,0 +1,406 @@
+use crate::{
+    clock::SharedClock,
+    database::{Database, preparer::CachedPreparedStatement},
+    eager_env,
+    regions::Region,
+    worker::WorkerStatus,
+};
+use anyhow::Result;
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use log::{error, info};
+use scylla::statement::batch::{Batch, BatchType};
+use std::{
+    collections::{BTreeMap, btree_map::Entry},
+    sync::Arc,
+};
+use uuid::Uuid;
+
+/// Time between two compactions of the checks owned by a worker
+const COMPACTION_INTERVAL: std::time::Duration = std::time::Duration::from_secs(60 * 60);
+
+/// Raw results older than the compaction cutoff by more than this are left alone, they were
+/// compacted by earlier runs. Covers nodes being down or ranges changing owner for a while.
+const COMPACTION_LOOKBACK: Duration = Duration::days(2);
+
+/// Thins the raw results of high-frequency checks once they are old enough: failures are all
+/// kept, successes only one per `interval`.
+///
+/// The metrics stay the same: the kept success carries the weight of the ones it replaces, see
+/// [`CheckResultRow::sample_weight`](crate::queries::check_results::CheckResultRow), and
+/// successes following a failure are kept too, so that the time each state lasted is unchanged.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct SamplingPolicy {
+    /// Age after which results are sampled
+    pub after: Duration,
+    /// Successes are kept at most once per interval, aligned to the Unix epoch
+    pub interval: Duration,
+}
+
+impl SamplingPolicy {
+    /// `None` when sampling is disabled
+    pub fn from_env() -> Option<Self> {
+        let after_hours = *eager_env::RESULT_SAMPLING_AFTER_HOURS;
+        let interval_seconds = *eager_env::RESULT_SAMPLING_INTERVAL_SECONDS;
+        if after_hours == 0 || interval_seconds == 0 {
+            return None;
+        }
+
+        Some(Self {
+            after: Duration::hours(after_hours as i64),
+            interval: Duration::seconds(interval_seconds as i64),
+        })
+    }
+
+    /// Samples `results`, given as `(check_started_at, matches_expected, sample_weight)`.
+    ///
+    /// Among the successes following another success, the earliest of each interval is kept
+    /// and gets their total weight, the others are deleted. The first and last results and those
+    /// following a failure are always kept as they are.
+    ///
+    /// Applying it again to the kept results changes nothing more.
+    fn sample(&self, results: &[(DateTime<Utc>, bool, i32)]) -> Vec<SampledInterval> {
+        let interval_millis = self.interval.num_milliseconds().max(1);
+
+        let mut results = results.to_vec();
+        results.sort_by_key(|(started_at, _, _)| *started_at);
+
+        let mut sampled = BTreeMap::<i64, SampledInterval>::new();
+        // The last result bounds the time of the others
+        let last = results.len().saturating_sub(1);
+        for window in results[..last].windows(2) {
+            let [(_, previous_success, _), (started_at, true, weight)] = window else {
+                continue;
+            };
+            if !previous_success {
+                continue;
+            }
+
+            let interval = started_at.timestamp_millis().div_euclid(interval_millis);
+            match sampled.entry(interval) {
+                Entry::Vacant(entry) => {
+                    entry.insert(SampledInterval {
+                        kept: *started_at,
+                        weight: *weight,
+                        deleted: Vec::new(),
+                    });
+                }
+                Entry::Occupied(mut entry) => {
+                    let interval = entry.get_mut();
+                    interval.weight += weight;
+                    interval.deleted.push(*started_at);
+                }
+            }
+        }
+
+        sampled
+            .into_values()
+            .filter(|interval| !interval.deleted.is_empty())
+            .collect()
+    }
+}
+
+/// Successes of an interval replaced by the earliest one, see [`SamplingPolicy::sample`]
+#[derive(Debug, PartialEq)]
+struct SampledInterval {
+    kept: DateTime<Utc>,
+    /// Weight of `kept` once the `deleted` results are gone
+    weight: i32,
+    deleted: Vec<DateTime<Utc>>,
+}
+
+static GET_RESULT_OUTCOMES_QUERY: CachedPreparedStatement = CachedPreparedStatement::new(
+    "
+    SELECT check_started_at,
+           matches_expected,
+           sample_weight
+    FROM check_results
+    WHERE service_check_id = ?
+      AND region = ?
+      AND day = ?
+      AND check_started_at >= ?
+      AND check_started_at < ?
+    ",
+);
+
+static DELETE_RESULT_QUERY: CachedPreparedStatement = CachedPreparedStatement::new(
+    "
+    DELETE FROM check_results
+    WHERE service_check_id = ?
+      AND region = ?
+      AND day = ?
+      AND check_started_at IN ?
+    ",
+);
+
+static UPDATE_SAMPLE_WEIGHT_QUERY: CachedPreparedStatement = CachedPreparedStatement::new(
+    "
+    UPDATE check_results
+    SET sample_weight = ?
+    WHERE service_check_id = ?
+      AND region = ?
+      AND day = ?
+      AND check_started_at = ?
+    ",
+);
+
+/// Applies `policy` to the raw results of a check in a region that turned old enough since the
+/// last runs, i.e. those started in `[cutoff - COMPACTION_LOOKBACK, cutoff)`.
+///
+/// Returns the number of deleted results.
+pub async fn compact_check_results(
+    db: &Database,
+    check_id: Uuid,
+    region: Region,
+    policy: &SamplingPolicy,
+    now: DateTime<Utc>,
+) -> Result<usize> {
+    let to = now - policy.after;
+    let from = to - COMPACTION_LOOKBACK;
+    let region_id = region.to_identifier();
+
+    let mut deleted = 0;
+    for day in from.date_naive().iter_days() {
+        if day > to.date_naive() {
+            break;
+        }
+
+        let results: Vec<_> = GET_RESULT_OUTCOMES_QUERY
+            .execute_unpaged(db, (check_id, region_id, day, from, to))
+            .await?
+            .into_rows_result()?
+            .rows::<(DateTime<Utc>, bool, Option<i32>)>()?
+            .map(|row| {
+                row.map(|(started_at, matches_expected, weight)| {
+                    (started_at, matches_expected, weight.unwrap_or(1))
+                })
+            })
+            .collect::<Result<_, _>>()?;
+
+        for interval in policy.sample(&results) {
+            deleted += interval.deleted.len();
+            apply_sampled_interval(db, check_id, region_id, day, interval).await?;
+        }
+    }
+
+    Ok(deleted)
+}
+
+/// Compacts the results of the checks the worker owns in its region every
+/// [`COMPACTION_INTERVAL`], forever.
+pub async fn compaction_task_body(
+    db: Arc<Database>,
+    status: WorkerStatus,
+    clock: SharedClock,
+    policy: SamplingPolicy,
+) {
+    loop {
+        tokio::time::sleep(COMPACTION_INTERVAL).await;
+
+        let mut deleted = 0;
+        for check_id in status.owned_check_ids().await {
+            match compact_check_results(&db, check_id, status.region(), &policy, clock.now()).await
+            {
+                Ok(count) => deleted += count,
+                Err(e) => error!("failed to compact the results of check {check_id}: {e:?}"),
+            }
+        }
+        info!("Compaction deleted {deleted} sampled out results");
+    }
+}
+
+/// Moves the weight of the deleted results to the kept one. A single partition is written, so
+/// the batch applies entirely or not at all and the weights always add up.
+async fn apply_sampled_interval(
+    db: &Database,
+    check_id: Uuid,
+    region_id: &str,
+    day: NaiveDate,
+    interval: SampledInterval,
+) -> Result<()> {
+    let update = UPDATE_SAMPLE_WEIGHT_QUERY
+        .get_prepared_statement(db)
+        .await?;
+    let delete = DELETE_RESULT_QUERY.get_prepared_statement(db).await?;
+
+    let mut batch = Batch::new(BatchType::Unlogged);
+    batch.append_statement(update);
+    batch.append_statement(delete);
+    let values = (
+        (interval.weight, check_id, region_id, day, interval.kept),
+        (check_id, region_id, day, interval.deleted),
+    );
+
+    db.batch(&batch, values).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        database::testing::create_test_database,
+        queries::check_results::{CheckResultRow, MetricsSummary, calculate_overall_metrics},
+    };
+
+    fn policy() -> SamplingPolicy {
+        SamplingPolicy {
+            after: Duration::days(1),
+            interval: Duration::minutes(1),
+        }
+    }
+
+    /// Applies the sampled intervals to `results` like [`apply_sampled_interval`]
+    fn apply(
+        results: &[(DateTime<Utc>, bool, i32)],
+        sampled: &[SampledInterval],
+    ) -> Vec<(DateTime<Utc>, bool, i32)> {
+        results
+            .iter()
+            .filter(|(started_at, _, _)| !sampled.iter().any(|s| s.deleted.contains(started_at)))
+            .map(
+                |&(started_at, ok, weight)| match sampled.iter().find(|s| s.kept == started_at) {
+                    Some(interval) => (started_at, ok, interval.weight),
+                    None => (started_at, ok, weight),
+                },
+            )
+            .collect()
+    }
+
+    fn metrics(results: &[(DateTime<Utc>, bool, i32)]) -> MetricsSummary {
+        let rows: Vec<_> = results
+            .iter()
+            .enumerate()
+            .map(
+                |(i, &(check_started_at, matches_expected, sample_weight))| CheckResultRow {
+                    check_started_at,
+                    response_time_micros: 1000 + 100 * (i as i64 % 3),
+                    status_code: Some(if matches_expected { 200 } else { 500 }),
+                    matches_expected,
+                    response_size_bytes: None,
+                    ttfb_micros: None,
+                    region: Region::Fsn1,
+                    sample_weight,
+                },
+            )
+            .collect();
+        calculate_overall_metrics(&rows)
+    }
+
+    #[test]
+    fn test_sample() {
+        let start = "2025-11-29T10:00:00Z".parse::<DateTime<Utc>>().unwrap();
+        // Every 5 seconds for 3 minutes, failing during the second one
+        let results: Vec<_> = (0..36)
+            .map(|i| (start + Duration::seconds(5 * i), !(12..24).contains(&i), 1))
+            .collect();
+
+        let sampled = policy().sample(&results);
+        // The first and last successes and the recovery are kept on their own, the earliest of
+        // the following ones stands for the others of its minute
+        assert_eq!(
+            sampled
+                .iter()
+                .map(|s| (s.kept, s.weight, s.deleted.len()))
+                .collect::<Vec<_>>(),
+            [
+                (start + Duration::seconds(5), 11, 10),
+                (start + Duration::seconds(125), 10, 9),
+            ]
+        );
+
+        let kept = apply(&results, &sampled);
+        assert_eq!(kept.len(), 17);
+        // Failures are kept
+        assert_eq!(kept.iter().filter(|(_, ok, _)| !ok).count(), 12);
+        assert!(kept.contains(&(start + Duration::minutes(2), true, 1)));
+
+        // The metrics are unchanged, apart from the response times of the deleted results
+        let (before, after) = (metrics(&results), metrics(&kept));
+        assert_eq!(after.total_checks, before.total_checks);
+        assert_eq!(after.successful_checks, before.successful_checks);
+        assert_eq!(after.status_code_counts, before.status_code_counts);
+        assert!(
+            (after.time_weighted_uptime_percent - before.time_weighted_uptime_percent).abs() < 0.01
+        );
+        assert!(
+            (after.count_weighted_uptime_percent - before.count_weighted_uptime_percent).abs()
+                < 0.01
+        );
+
+        // Idempotent
+        assert!(policy().sample(&kept).is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_compact_check_results() -> Result<()> {
+        let (db, _keyspace) = create_test_database(None).await?;
+        let check_id = Uuid::new_v4();
+        let now = Utc::now();
+
+        // Every 5 seconds during 10 minutes, old enough to be compacted, with a failure
+        // every 2 minutes. Then the same during the last 10 minutes.
+        let old_start = now - Duration::hours(30);
+        let recent_start = now - Duration::minutes(10);
+        for start in [old_start, recent_start] {
+            for i in 0..120 {
+                let started_at = start + Duration::seconds(5 * i);
+                db.query_unpaged(
+                    "INSERT INTO check_results (result_id, service_check_id, region, day, check_started_at, response_time_micros, status_code, matches_expected, response_body_fetched) VALUES (?, ?, 'fsn1', ?, ?, 1000, 200, ?, false)",
+                    (Uuid::new_v4(), check_id, started_at.date_naive(), started_at, i % 24 != 0),
+                )
+                .await?;
+            }
+        }
+
+        let deleted = compact_check_results(&db, check_id, Region::Fsn1, &policy(), now).await?;
+        assert!(deleted > 0);
+
+        let old = get_outcomes(&db, check_id, old_start, old_start + Duration::minutes(10)).await?;
+        // All 5 failures are retained
+        assert_eq!(old.iter().filter(|(_, ok, _)| !ok).count(), 5);
+        // Successes are thinned, but still stand for all of them
+        let successes = old.iter().filter(|(_, ok, _)| *ok).count();
+        assert!(successes < 30, "{successes} successes");
+        let total_weight: i32 = old.iter().map(|(_, _, weight)| weight).sum();
+        assert_eq!(total_weight, 120);
+        assert_eq!(deleted, 120 - old.len());
+
+        // Recent results are untouched
+        let recent = get_outcomes(&db, check_id, recent_start, now).await?;
+        assert_eq!(recent.len(), 120);
+
+        // Nothing more to compact
+        assert_eq!(
+            compact_check_results(&db, check_id, Region::Fsn1, &policy(), now).await?,
+            0
+        );
+
+        Ok(())
+    }
+
+    async fn get_outcomes(
+        db: &Database,
+        check_id: Uuid,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> Result<Vec<(DateTime<Utc>, bool, i32)>> {
+        let mut outcomes = Vec::new();
+        for day in from.date_naive().iter_days() {
+            if day > to.date_naive() {
+                break;
+            }
+            outcomes.extend(
+                GET_RESULT_OUTCOMES_QUERY
+                    .execute_unpaged(db, (check_id, "fsn1", day, from, to))
+                    .await?
+                    .into_rows_result()?
+                    .rows::<(DateTime<Utc>, bool, Option<i32>)>()?
+                    .map(|row| {
+                        row.map(|(started_at, ok, weight)| (started_at, ok, weight.unwrap_or(1)))
+                    })
+                    .collect::<Result<Vec<_>, _>>()?,
+            );
+        }
+        Ok(outcomes)
+    }
+}
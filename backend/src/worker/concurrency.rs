@@ -0,0 +1,258 @@
+use crate::eager_env;
+use log::info;
+use std::{
+    cmp::Ordering,
+    collections::BinaryHeap,
+    sync::{
+        Arc, Mutex,
+        atomic::{self, AtomicU64},
+    },
+};
+use tokio::{
+    sync::{Notify, OwnedSemaphorePermit, Semaphore, oneshot},
+    task::JoinHandle,
+};
+
+/// Bounds concurrent check executions, sized after the number of owned checks.
+///
+/// A node owning a large part of the ring gets more permits than one owning a sliver,
+/// within `[min, max]`.
+pub struct ConcurrencyLimit {
+    semaphore: Arc<Semaphore>,
+    min: usize,
+    max: usize,
+    /// Permits the semaphore holds once pending shrinks complete
+    size: Mutex<usize>,
+}
+
+impl ConcurrencyLimit {
+    pub fn new(min: usize, max: usize) -> Self {
+        let min = min.min(max);
+
+        Self {
+            semaphore: Arc::new(Semaphore::new(min)),
+            min,
+            max,
+            size: Mutex::new(min),
+        }
+    }
+
+    pub fn from_env() -> Self {
+        Self::new(
+            *eager_env::MIN_CONCURRENT_HEALTH_CHECKS,
+            *eager_env::MAX_CONCURRENT_HEALTH_CHECKS,
+        )
+    }
+
+    pub fn semaphore(&self) -> Arc<Semaphore> {
+        self.semaphore.clone()
+    }
+
+    #[cfg(test)]
+    pub fn size(&self) -> usize {
+        *self.size.lock().expect("concurrency limit lock poisoned")
+    }
+
+    /// Resizes to one permit per owned check, within bounds.
+    ///
+    /// Shrinking never interrupts running checks: permits in use are retired once released.
+    pub fn resize_for(&self, checks: usize) {
+        let target = checks.clamp(self.min, self.max);
+        let mut size = self.size.lock().expect("concurrency limit lock poisoned");
+
+        if target > *size {
+            self.semaphore.add_permits(target - *size);
+        } else if target < *size {
+            let excess = *size - target;
+            let retired = self.semaphore.forget_permits(excess);
+
+            // Take the rest one by one, so that checks already waiting still run first
+            let semaphore = self.semaphore.clone();
+            tokio::spawn(async move {
+                for _ in retired..excess {
+                    match semaphore.acquire().await {
+                        Ok(permit) => permit.forget(),
+                        Err(_) => return,
+                    }
+                }
+            });
+        } else {
+            return;
+        }
+
+        info!(
+            "Check concurrency resized from {} to {target} for {checks} checks",
+            *size
+        );
+        *size = target;
+    }
+}
+
+/// Hands the permits of a semaphore to the waiting probes by priority rather than in arrival
+/// order, so that a burst of frequent checks can't starve rarer ones under saturation.
+/// Probes of equal priority are served first come, first served.
+pub struct ProbeQueue {
+    waiters: Mutex<BinaryHeap<Waiter>>,
+    waiter_added: Notify,
+    next_seq: AtomicU64,
+}
+
+struct Waiter {
+    priority: u64,
+    /// Arrival order, to break ties
+    seq: u64,
+    permit_tx: oneshot::Sender<OwnedSemaphorePermit>,
+}
+
+impl PartialEq for Waiter {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Waiter {}
+
+impl PartialOrd for Waiter {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Waiter {
+    /// Max-heap: highest priority first, then earliest arrival
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.priority
+            .cmp(&other.priority)
+            .then_with(|| other.seq.cmp(&self.seq))
+    }
+}
+
+impl ProbeQueue {
+    /// Starts handing out the permits of `semaphore`, until the returned task is aborted
+    pub fn start(semaphore: Arc<Semaphore>) -> (Arc<Self>, JoinHandle<()>) {
+        let queue = Arc::new(Self {
+            waiters: Default::default(),
+            waiter_added: Notify::new(),
+            next_seq: AtomicU64::new(0),
+        });
+
+        let dispatcher = queue.clone();
+        let task = tokio::spawn(async move {
+            loop {
+                loop {
+                    let waiter_added = dispatcher.waiter_added.notified();
+                    if !dispatcher.waiters.lock().expect("not poisoned").is_empty() {
+                        break;
+                    }
+                    waiter_added.await;
+                }
+
+                let Ok(permit) = semaphore.clone().acquire_owned().await else {
+                    return;
+                };
+                // The waiter is only chosen once a permit is free, so that it's the most urgent
+                let waiter = dispatcher
+                    .waiters
+                    .lock()
+                    .expect("not poisoned")
+                    .pop()
+                    .expect("only the dispatcher removes waiters");
+                // A waiter that gave up drops the permit, releasing it
+                let _ = waiter.permit_tx.send(permit);
+            }
+        });
+
+        (queue, task)
+    }
+
+    /// Waits for a permit, served before the waiters of lower `priority`
+    pub async fn acquire(&self, priority: u64) -> OwnedSemaphorePermit {
+        let (permit_tx, permit_rx) = oneshot::channel();
+        let seq = self.next_seq.fetch_add(1, atomic::Ordering::Relaxed);
+        self.waiters.lock().expect("not poisoned").push(Waiter {
+            priority,
+            seq,
+            permit_tx,
+        });
+        self.waiter_added.notify_one();
+
+        permit_rx.await.expect("probe queue stopped")
+    }
+}
+
+/// Priority of the probes of a check: the longer its interval, the more coverage a delayed
+/// probe costs, so rarer checks go first. All equal without `PROBE_PRIORITY_BY_FREQUENCY`.
+pub fn probe_priority(check_frequency_seconds: i32) -> u64 {
+    if *eager_env::PROBE_PRIORITY_BY_FREQUENCY {
+        check_frequency_seconds.max(0) as u64
+    } else {
+        0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use tokio::sync::mpsc;
+
+    #[tokio::test]
+    async fn test_resize_within_bounds() {
+        let limit = ConcurrencyLimit::new(4, 32);
+        let semaphore = limit.semaphore();
+        assert_eq!(semaphore.available_permits(), 4);
+
+        // Adopting many checks raises the permits up to the cap
+        limit.resize_for(10);
+        assert_eq!(limit.size(), 10);
+        assert_eq!(semaphore.available_permits(), 10);
+
+        limit.resize_for(1_000);
+        assert_eq!(limit.size(), 32);
+        assert_eq!(semaphore.available_permits(), 32);
+
+        // Permits in use are only retired once released
+        let running = semaphore.clone().acquire_many_owned(30).await.unwrap();
+        limit.resize_for(2);
+        assert_eq!(limit.size(), 4);
+        assert_eq!(semaphore.available_permits(), 0);
+
+        drop(running);
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        assert_eq!(semaphore.available_permits(), 4);
+    }
+
+    #[tokio::test]
+    async fn test_probe_queue_serves_rare_checks_first() {
+        let semaphore = Arc::new(Semaphore::new(1));
+        let (queue, dispatcher) = ProbeQueue::start(semaphore.clone());
+        let held = semaphore.clone().acquire_owned().await.unwrap();
+
+        // A burst of checks running every 10 seconds, then one running every 10 minutes
+        let (order_tx, mut order_rx) = mpsc::unbounded_channel();
+        for (id, priority) in (0..20).map(|id| (id, 10)).chain([(20, 600)]) {
+            let queue = queue.clone();
+            let order_tx = order_tx.clone();
+            tokio::spawn(async move {
+                let permit = queue.acquire(priority).await;
+                order_tx.send(id).unwrap();
+                tokio::time::sleep(Duration::from_millis(1)).await;
+                drop(permit);
+            });
+            tokio::task::yield_now().await;
+        }
+        drop(order_tx);
+
+        drop(held);
+        let mut order = Vec::new();
+        while let Some(id) = order_rx.recv().await {
+            order.push(id);
+        }
+
+        // The rare check isn't starved, and the burst keeps its arrival order
+        assert_eq!(order[0], 20);
+        assert_eq!(order[1..], (0..20).collect::<Vec<_>>());
+
+        dispatcher.abort();
+    }
+}
@@ -1,17 +1,29 @@
 use crate::{
-    collab::{NodePosition, RingRange, get_bucket_for_check},
+    collab::{NodePosition, PreviousBuckets, RingRange, bucket_for_check, get_bucket_for_check},
     database::preparer::CachedPreparedStatement,
     eager_env,
+    queries::checks::default_store_raw_results,
     regions::Region,
+    worker::check::{
+        assertions::Assertions,
+        body::BodyRegex,
+        conditional::ConditionalRequest,
+        geo::GeoAssertion,
+        proxy::{ProxyConfig, decrypt_password},
+        status::ExpectedStatusCodes,
+        steps::{CheckKind, CheckStep},
+        tls::MinTlsVersion,
+    },
 };
 use anyhow::Result;
 use chrono::{DateTime, Utc};
 use futures::{StreamExt, stream};
 use itertools::Itertools;
-use log::{error, warn};
-use scylla::{client::session::Session, response::query_result::QueryRowsResult};
+use log::{error, info, warn};
+use scylla::{DeserializeRow, client::session::Session, response::query_result::QueryRowsResult};
 use serde::{Deserialize, Serialize};
-use std::collections::{BTreeSet, HashMap};
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::time::Duration;
 use url::Url;
 use utoipa::ToSchema;
 use uuid::Uuid;
@@ -24,6 +36,23 @@ pub enum Method {
     Put,
     Delete,
     Head,
+    Patch,
+    Options,
+}
+
+impl Method {
+    /// Whether a request body is conventionally allowed, some servers reject bodies on other methods
+    pub fn allows_body(self) -> bool {
+        matches!(self, Method::Post | Method::Put | Method::Patch)
+    }
+
+    /// Whether probing with this method may change the state of the target
+    pub fn has_side_effects(self) -> bool {
+        matches!(
+            self,
+            Method::Post | Method::Put | Method::Delete | Method::Patch
+        )
+    }
 }
 
 #[derive(Clone, Serialize, Deserialize)]
@@ -36,64 +65,157 @@ pub struct ServiceCheck {
     pub http_method: Method,
     pub check_frequency_seconds: i32,
     pub timeout_seconds: i32,
-    pub expected_status_code: i32,
+    /// `expected_status_codes` of the check, or its `expected_status_code`
+    #[serde(alias = "expected_status_code")]
+    pub expected_status_codes: ExpectedStatusCodes,
     pub request_headers: std::collections::HashMap<String, String>,
     pub request_body: Option<String>,
     pub is_enabled: bool,
     pub created_at: DateTime<Utc>,
+    pub kind: CheckKind,
+    pub steps: Vec<CheckStep>,
+    pub dns_cache_ttl_seconds: Option<i32>,
+    pub proxy: Option<ProxyConfig>,
+    pub conditional: Option<ConditionalRequest>,
+    pub geo_assertion: Option<GeoAssertion>,
+    #[serde(default)]
+    pub fallback_urls: Vec<Url>,
+    #[serde(default)]
+    pub body_regex: Option<BodyRegex>,
+    #[serde(default)]
+    pub min_tls_version: Option<MinTlsVersion>,
+    #[serde(default)]
+    pub decompress_response: bool,
+    #[serde(default)]
+    pub allow_private_targets: bool,
+    #[serde(default)]
+    pub connect_timeout_millis: Option<i32>,
+    #[serde(default)]
+    pub assertions: Option<Assertions>,
+    #[serde(default = "default_store_raw_results")]
+    pub store_raw_results: bool,
+    #[serde(default)]
+    pub retries: u8,
+    #[serde(default)]
+    pub retry_delay_ms: u32,
+    /// Probed by this node only, see `CheckData::pinned_node`
+    #[serde(default)]
+    pub pinned_node: Option<String>,
+}
+
+#[derive(DeserializeRow)]
+struct ServiceCheckRow {
+    check_id: Uuid,
+    check_name: String,
+    url: String,
+    http_method: String,
+    check_frequency_seconds: i32,
+    timeout_seconds: i32,
+    expected_status_code: i32,
+    expected_status_codes: Option<String>,
+    request_headers: HashMap<String, String>,
+    request_body: Option<String>,
+    is_enabled: bool,
+    created_at: DateTime<Utc>,
+    region: String,
+    kind: Option<String>,
+    steps: Option<String>,
+    dns_cache_ttl_seconds: Option<i32>,
+    proxy_url: Option<String>,
+    proxy_username: Option<String>,
+    proxy_password: Option<String>,
+    proxy_remote_dns: Option<bool>,
+    conditional_etag: Option<String>,
+    conditional_modified_since: Option<DateTime<Utc>>,
+    geo_assertion: Option<String>,
+    fallback_urls: Option<Vec<String>>,
+    body_regex: Option<String>,
+    min_tls_version: Option<String>,
+    decompress_response: Option<bool>,
+    allow_private_targets: Option<bool>,
+    connect_timeout_millis: Option<i32>,
+    assertions: Option<String>,
+    store_raw_results: Option<bool>,
+    retries: Option<i32>,
+    retry_delay_ms: Option<i32>,
+    pinned_node: Option<String>,
+}
+
+impl ServiceCheckRow {
+    fn into_service_check(self) -> Result<ServiceCheck> {
+        let proxy_password = self.proxy_password.map(decrypt_password).transpose()?;
+
+        Ok(ServiceCheck {
+            check_id: self.check_id,
+            region: self.region.parse()?,
+            check_name: self.check_name,
+            url: self.url.parse()?,
+            http_method: serde_plain::from_str(&self.http_method)?,
+            check_frequency_seconds: self.check_frequency_seconds,
+            timeout_seconds: self.timeout_seconds,
+            expected_status_codes: self
+                .expected_status_codes
+                .map(|c| serde_json::from_str(&c))
+                .transpose()?
+                .unwrap_or_else(|| self.expected_status_code.into()),
+            request_headers: self.request_headers,
+            request_body: self.request_body,
+            is_enabled: self.is_enabled,
+            created_at: self.created_at,
+            kind: self
+                .kind
+                .map(|k| serde_plain::from_str(&k))
+                .transpose()?
+                .unwrap_or_default(),
+            steps: self
+                .steps
+                .map(|s| serde_json::from_str(&s))
+                .transpose()?
+                .unwrap_or_default(),
+            dns_cache_ttl_seconds: self.dns_cache_ttl_seconds,
+            proxy: self.proxy_url.map(|url| ProxyConfig {
+                url,
+                username: self.proxy_username,
+                password: proxy_password,
+                remote_dns: self.proxy_remote_dns.unwrap_or_default(),
+            }),
+            conditional: ConditionalRequest::from_columns(
+                self.conditional_etag,
+                self.conditional_modified_since,
+            ),
+            geo_assertion: self
+                .geo_assertion
+                .map(|g| serde_json::from_str(&g))
+                .transpose()?,
+            fallback_urls: self
+                .fallback_urls
+                .unwrap_or_default()
+                .iter()
+                .map(|url| url.parse())
+                .collect::<Result<_, _>>()?,
+            body_regex: self.body_regex.map(|r| r.parse()).transpose()?,
+            min_tls_version: self.min_tls_version.map(|v| v.parse()).transpose()?,
+            decompress_response: self.decompress_response.unwrap_or_default(),
+            allow_private_targets: self.allow_private_targets.unwrap_or_default(),
+            connect_timeout_millis: self.connect_timeout_millis,
+            assertions: self
+                .assertions
+                .map(|a| serde_json::from_str(&a))
+                .transpose()?,
+            store_raw_results: self.store_raw_results.unwrap_or(true),
+            retries: self.retries.unwrap_or_default().try_into()?,
+            retry_delay_ms: self.retry_delay_ms.unwrap_or_default().try_into()?,
+            pinned_node: self.pinned_node,
+        })
+    }
 }
 
 fn parse_service_check_rows(result: QueryRowsResult) -> Result<Vec<ServiceCheck>> {
-    let rows = result.rows::<(
-        Uuid,
-        String,
-        String,
-        String,
-        i32,
-        i32,
-        i32,
-        HashMap<String, String>,
-        Option<String>,
-        bool,
-        DateTime<Utc>,
-        String,
-    )>()?;
+    let rows = result.rows::<ServiceCheckRow>()?;
 
     let maybe_checks: Vec<Result<_>> = rows
         .into_iter()
-        .map(|row| {
-            let (
-                check_id,
-                check_name,
-                url,
-                http_method,
-                check_frequency_seconds,
-                timeout_seconds,
-                expected_status_code,
-                request_headers,
-                request_body,
-                is_enabled,
-                created_at,
-                region_str,
-            ) = row?;
-
-            let check = ServiceCheck {
-                check_id,
-                region: region_str.parse()?,
-                check_name,
-                url: url.parse()?,
-                http_method: serde_plain::from_str(&http_method)?,
-                check_frequency_seconds,
-                timeout_seconds,
-                expected_status_code,
-                request_headers,
-                request_body,
-                is_enabled,
-                created_at,
-            };
-
-            Ok(check)
-        })
+        .map(|row| row?.into_service_check())
         .collect();
 
     let (checks, errors): (Vec<_>, Vec<_>) = maybe_checks.into_iter().partition_result();
@@ -127,7 +249,29 @@ static HEALTH_CHECKS_QUERY: CachedPreparedStatement = CachedPreparedStatement::n
            request_body,
            is_enabled,
            created_at,
-           region
+           region,
+           kind,
+           steps,
+           dns_cache_ttl_seconds,
+           proxy_url,
+           proxy_username,
+           proxy_password,
+           proxy_remote_dns,
+           conditional_etag,
+           conditional_modified_since,
+           geo_assertion,
+           fallback_urls,
+           body_regex,
+           min_tls_version,
+           decompress_response,
+           allow_private_targets,
+           connect_timeout_millis,
+           assertions,
+           store_raw_results,
+           expected_status_codes,
+           retries,
+           retry_delay_ms,
+           pinned_node
     FROM checks
     WHERE region = ?
       AND bucket_version = ?
@@ -148,7 +292,29 @@ static SPECIFIC_HEALTH_CHECKS_QUERY: CachedPreparedStatement = CachedPreparedSta
            request_body,
            is_enabled,
            created_at,
-           region
+           region,
+           kind,
+           steps,
+           dns_cache_ttl_seconds,
+           proxy_url,
+           proxy_username,
+           proxy_password,
+           proxy_remote_dns,
+           conditional_etag,
+           conditional_modified_since,
+           geo_assertion,
+           fallback_urls,
+           body_regex,
+           min_tls_version,
+           decompress_response,
+           allow_private_targets,
+           connect_timeout_millis,
+           assertions,
+           store_raw_results,
+           expected_status_codes,
+           retries,
+           retry_delay_ms,
+           pinned_node
     FROM checks
     WHERE region = ?
       AND bucket_version = ?
@@ -157,17 +323,66 @@ static SPECIFIC_HEALTH_CHECKS_QUERY: CachedPreparedStatement = CachedPreparedSta
     ",
 );
 
+/// Fetches the checks of `region` in the buckets of `ring_range`.
+///
+/// With `previous_buckets`, checks still stored under the previous bucket version are fetched
+/// too, unless they also exist under the current one.
 pub async fn fetch_health_checks(
     session: &Session,
     region: Region,
     bucket_version: i16,
     ring_range: RingRange,
     ring_size: NodePosition,
+    previous_buckets: Option<PreviousBuckets>,
 ) -> Result<Vec<ServiceCheck>> {
     let region_str = region.to_identifier();
 
-    let buckets = ring_range.iter(ring_size);
+    let mut checks = fetch_buckets(
+        session,
+        region_str,
+        bucket_version,
+        ring_range.iter(ring_size),
+    )
+    .await?;
+
+    if let Some(previous) = previous_buckets {
+        // The range maps to the same buckets unless their count changed, then any may hold
+        // checks of the range
+        let previous_range: Vec<_> = if previous.bucket_count == ring_size {
+            ring_range.iter(ring_size).collect()
+        } else {
+            (0..previous.bucket_count).collect()
+        };
+        let current: HashSet<_> = checks.iter().map(|check| check.check_id).collect();
+
+        let migrating: Vec<_> =
+            fetch_buckets(session, region_str, previous.bucket_version, previous_range)
+                .await?
+                .into_iter()
+                .filter(|check| {
+                    !current.contains(&check.check_id)
+                        && ring_range.contains(bucket_for_check(check.check_id, ring_size))
+                })
+                .collect();
+        if !migrating.is_empty() {
+            info!(
+                "Fetched {} checks from bucket version {}",
+                migrating.len(),
+                previous.bucket_version
+            );
+        }
+        checks.extend(migrating);
+    }
 
+    Ok(checks)
+}
+
+async fn fetch_buckets(
+    session: &Session,
+    region_str: &str,
+    bucket_version: i16,
+    buckets: impl IntoIterator<Item = NodePosition>,
+) -> Result<Vec<ServiceCheck>> {
     let all_checks = stream::iter(buckets)
         .map(|bucket| async move {
             let result = HEALTH_CHECKS_QUERY
@@ -178,7 +393,7 @@ pub async fn fetch_health_checks(
             warn!("Fetching bucket {bucket}");
             parse_service_check_rows(result)
         })
-        .buffer_unordered(*eager_env::DATABASE_CONCURRENT_REQUESTS)
+        .buffer_unordered(eager_env::DATABASE_CONCURRENT_READS.get())
         .collect::<Vec<_>>()
         .await
         .into_iter()
@@ -220,7 +435,7 @@ pub async fn fetch_specific_health_checks(
                 parse_service_check_rows(result)
             },
         )
-        .buffer_unordered(*eager_env::DATABASE_CONCURRENT_REQUESTS)
+        .buffer_unordered(eager_env::DATABASE_CONCURRENT_READS.get())
         .collect::<Vec<_>>()
         .await
         .into_iter()
@@ -245,11 +460,28 @@ impl ServiceCheck {
             http_method: Method::Get,
             check_frequency_seconds: 60,
             timeout_seconds: 30,
-            expected_status_code: 200,
+            expected_status_codes: 200.into(),
             request_headers: HashMap::new(),
             request_body: None,
             is_enabled: true,
             created_at: Utc::now(),
+            kind: CheckKind::Http,
+            steps: vec![],
+            dns_cache_ttl_seconds: None,
+            proxy: None,
+            conditional: None,
+            geo_assertion: None,
+            fallback_urls: vec![],
+            body_regex: None,
+            min_tls_version: None,
+            decompress_response: false,
+            allow_private_targets: false,
+            connect_timeout_millis: None,
+            assertions: None,
+            store_raw_results: true,
+            retries: 0,
+            retry_delay_ms: 0,
+            pinned_node: None,
         }
     }
 }
@@ -263,6 +495,32 @@ where
 }
 
 impl ServiceCheck {
+    /// How long a resolved address can be reused, `None` when caching is disabled.
+    pub fn dns_cache_ttl(&self) -> Option<Duration> {
+        self.dns_cache_ttl_seconds
+            .filter(|&ttl| ttl > 0)
+            .map(|ttl| Duration::from_secs(ttl as u64))
+    }
+
+    /// Timeout of the check's probes, clamped to `MAX_PROBE_TIMEOUT_SECONDS` so that a single
+    /// check can't hold a probe permit for long, nor outlive the shutdown wait.
+    pub fn timeout(&self) -> Duration {
+        Duration::from_secs(self.timeout_seconds.max(0) as u64)
+            .min(Duration::from_secs(*eager_env::MAX_PROBE_TIMEOUT_SECONDS))
+    }
+
+    /// `None` when only the total [`timeout`](Self::timeout) applies
+    pub fn connect_timeout(&self) -> Option<Duration> {
+        self.connect_timeout_millis
+            .filter(|&millis| millis > 0)
+            .map(|millis| Duration::from_millis(millis as u64).min(self.timeout()))
+    }
+
+    /// Pause before each retry of a failed probe
+    pub fn retry_delay(&self) -> Duration {
+        Duration::from_millis(self.retry_delay_ms.into())
+    }
+
     fn parse_url(url_str: &str) -> Result<Url, anyhow::Error> {
         let url: Url = url_str.parse()?;
 
@@ -279,7 +537,11 @@ impl ServiceCheck {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::database::testing::create_test_database;
+    use crate::{
+        collab::{assignment::calculate_node_range, heartbeat::Heartbeat},
+        database::testing::create_test_database,
+        queries::checks::{Check, CheckData, update_check},
+    };
     use uuid::uuid;
 
     const FIXTURES: &str = include_str!("fixtures.cql");
@@ -295,6 +557,7 @@ mod tests {
             1,
             RingRange { start: 0, end: 1 },
             10,
+            None,
         )
         .await?;
         assert_eq!(checks.len(), 1);
@@ -313,6 +576,7 @@ mod tests {
             1,
             RingRange { start: 0, end: 3 },
             10,
+            None,
         )
         .await?;
         assert_eq!(checks.len(), 3);
@@ -324,6 +588,7 @@ mod tests {
             1,
             RingRange { start: 0, end: 1 },
             10,
+            None,
         )
         .await?;
         assert_eq!(checks.len(), 1);
@@ -335,6 +600,120 @@ mod tests {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn test_fetch_health_checks_single_node_full_ring() -> Result<()> {
+        let (db, _keyspace) = create_test_database(None).await?;
+        let bucket_count = *eager_env::CURRENT_BUCKETS_COUNT;
+
+        // One check in each bucket
+        let check_ids: BTreeSet<_> = (0..bucket_count as u128).map(Uuid::from_u128).collect();
+        for &check_id in &check_ids {
+            update_check(
+                &db,
+                Check {
+                    check_id,
+                    regions: vec![Region::Hel1],
+                    data: CheckData::example(),
+                },
+            )
+            .await?;
+        }
+
+        // The only node owns the whole ring, wherever it sits
+        for position in [0, bucket_count / 2, bucket_count - 1] {
+            let node = Heartbeat {
+                position,
+                region: Region::Hel1,
+                ..Heartbeat::example()
+            };
+            let range = calculate_node_range(
+                node.node_id,
+                *eager_env::REPLICATION_FACTOR,
+                &BTreeSet::from([node.clone()]),
+                Region::Hel1,
+            )
+            .expect("node in the state");
+            assert_eq!(range.start, range.end);
+
+            let checks = fetch_health_checks(
+                &db,
+                Region::Hel1,
+                *eager_env::CURRENT_BUCKET_VERSION as i16,
+                range,
+                bucket_count,
+                None,
+            )
+            .await?;
+
+            let fetched: Vec<_> = checks.iter().map(|check| check.check_id).collect();
+            assert_eq!(fetched.len(), check_ids.len(), "position {position}");
+            assert_eq!(fetched.into_iter().collect::<BTreeSet<_>>(), check_ids);
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_fetch_health_checks_reads_previous_bucket_version() -> Result<()> {
+        let (db, _keyspace) = create_test_database(None).await?;
+        let insert = async |check_id: Uuid, bucket_version: i16, bucket: i32, name: &str| {
+            db.query_unpaged(
+                "INSERT INTO checks (check_id, region, bucket_version, bucket, check_name, url, http_method, check_frequency_seconds, timeout_seconds, expected_status_code, request_headers, is_enabled, created_at) VALUES (?, 'hel1', ?, ?, ?, 'https://example.com', 'GET', 60, 10, 200, {}, true, toTimestamp(now()))",
+                (check_id, bucket_version, bucket, name),
+            )
+            .await
+        };
+
+        // Rehashed from 5 buckets under version 1 to 10 under version 2, all in bucket 3 of 5
+        let migrating = Uuid::from_u128(13);
+        let moved_away = Uuid::from_u128(18);
+        let rewritten = Uuid::from_u128(23);
+        insert(migrating, 1, 3, "previous").await?;
+        insert(moved_away, 1, 3, "previous").await?;
+        insert(rewritten, 1, 3, "previous").await?;
+        insert(rewritten, 2, 3, "current").await?;
+
+        let fetch = async |previous_buckets| -> Result<Vec<(Uuid, String)>> {
+            let checks = fetch_health_checks(
+                &db,
+                Region::Hel1,
+                2,
+                RingRange { start: 2, end: 5 },
+                10,
+                previous_buckets,
+            )
+            .await?;
+            Ok(checks
+                .into_iter()
+                .map(|check| (check.check_id, check.check_name))
+                .sorted()
+                .collect())
+        };
+
+        // Outside of a migration, only the current version is read
+        assert_eq!(fetch(None).await?, [(rewritten, "current".to_string())]);
+
+        // Checks now hashing outside of the range are left to their new owner, and the current
+        // version wins over the previous one
+        let expected = [
+            (migrating, "previous".to_string()),
+            (rewritten, "current".to_string()),
+        ];
+        for bucket_count in [5, 10] {
+            let previous = PreviousBuckets {
+                bucket_version: 1,
+                bucket_count,
+            };
+            assert_eq!(
+                fetch(Some(previous)).await?,
+                expected,
+                "{bucket_count} buckets"
+            );
+        }
+
+        Ok(())
+    }
+
     #[tokio::test]
     async fn test_fetch_health_checks_with_malformed() -> Result<()> {
         let (session, _keyspace) = create_test_database(Some(FIXTURES)).await?;
@@ -347,6 +726,7 @@ mod tests {
             1,
             RingRange { start: 0, end: 4 },
             10,
+            None,
         )
         .await?;
 
@@ -363,6 +743,14 @@ mod tests {
     fn test_method_serialization() -> Result<()> {
         // Test serialization
         assert_eq!(serde_plain::to_string(&Method::Get)?, "GET");
+        assert_eq!(serde_plain::to_string(&Method::Patch)?, "PATCH");
+        assert_eq!(serde_plain::to_string(&Method::Options)?, "OPTIONS");
+
+        // Round-trips through the stored `http_method` column
+        for method in [Method::Patch, Method::Options] {
+            let stored = serde_plain::to_string(&method)?;
+            assert_eq!(serde_plain::from_str::<Method>(&stored)?, method);
+        }
 
         Ok(())
     }
@@ -385,4 +773,29 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_timeout_clamped_to_ceiling() {
+        let max = Duration::from_secs(*eager_env::MAX_PROBE_TIMEOUT_SECONDS);
+        let check = |timeout_seconds| ServiceCheck {
+            timeout_seconds,
+            ..ServiceCheck::example()
+        };
+
+        assert_eq!(check(1).timeout(), Duration::from_secs(1));
+        assert_eq!(check(max.as_secs() as i32).timeout(), max);
+        assert_eq!(check(i32::MAX).timeout(), max);
+        assert_eq!(check(-1).timeout(), Duration::ZERO);
+    }
+
+    #[test]
+    fn test_method_allows_body() {
+        assert!(Method::Post.allows_body());
+        assert!(Method::Put.allows_body());
+        assert!(!Method::Get.allows_body());
+        assert!(!Method::Head.allows_body());
+        assert!(!Method::Delete.allows_body());
+        assert!(Method::Patch.allows_body());
+        assert!(!Method::Options.allows_body());
+    }
 }
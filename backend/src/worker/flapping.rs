@@ -0,0 +1,199 @@
+use crate::eager_env;
+use log::{info, warn};
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use uuid::Uuid;
+
+/// Alert raised by a probe result, see [`FlapDetector::record`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum AlertEvent {
+    Down,
+    Up,
+    /// The check changed state too often, its transitions are no longer alerted
+    FlappingStarted,
+    /// The check stayed up, or down, for a whole window since it started flapping
+    FlappingStopped {
+        up: bool,
+    },
+}
+
+#[derive(Default)]
+struct FlapState {
+    /// `None` until the first result
+    up: Option<bool>,
+    /// Times of the state changes within the window, oldest first
+    changes: VecDeque<Instant>,
+    flapping: bool,
+}
+
+/// Suppresses the transition alerts of checks oscillating between up and down.
+///
+/// A check changing state more than `max_changes` times within `window` starts flapping: a single
+/// [`AlertEvent::FlappingStarted`] replaces its transition alerts until it keeps the same state
+/// for a whole window. A `max_changes` of `0` disables the detection.
+pub struct FlapDetector {
+    window: Duration,
+    max_changes: u32,
+    states: Mutex<HashMap<Uuid, FlapState>>,
+}
+
+impl FlapDetector {
+    pub fn new(window: Duration, max_changes: u32) -> Self {
+        Self {
+            window,
+            max_changes,
+            states: Default::default(),
+        }
+    }
+
+    pub fn from_env() -> Self {
+        Self::new(
+            Duration::from_secs(*eager_env::FLAP_WINDOW_SECONDS),
+            *eager_env::FLAP_MAX_STATE_CHANGES,
+        )
+    }
+
+    /// Records whether a probe of `check_id` found it up at `now`.
+    ///
+    /// Returns the alert to send, if any. The first result of a check never alerts, its previous
+    /// state being unknown.
+    pub fn record(&self, check_id: Uuid, up: bool, now: Instant) -> Option<AlertEvent> {
+        let mut states = self.states.lock().expect("flap detector lock poisoned");
+        let state = states.entry(check_id).or_default();
+
+        let changed = state.up.is_some_and(|previous| previous != up);
+        state.up = Some(up);
+        let transition = match (changed, up) {
+            (false, _) => None,
+            (true, true) => Some(AlertEvent::Up),
+            (true, false) => Some(AlertEvent::Down),
+        };
+        if self.max_changes == 0 {
+            return transition;
+        }
+
+        while state
+            .changes
+            .front()
+            .is_some_and(|changed_at| now.saturating_duration_since(*changed_at) >= self.window)
+        {
+            state.changes.pop_front();
+        }
+        if changed {
+            state.changes.push_back(now);
+        }
+
+        if state.flapping {
+            if state.changes.is_empty() {
+                state.flapping = false;
+                return Some(AlertEvent::FlappingStopped { up });
+            }
+            return None;
+        }
+
+        if state.changes.len() > self.max_changes as usize {
+            state.flapping = true;
+            return Some(AlertEvent::FlappingStarted);
+        }
+
+        transition
+    }
+}
+
+/// Reports `event` for `check_id`, no notification channel being available yet
+pub fn log_alert_event(check_id: Uuid, event: AlertEvent) {
+    match event {
+        AlertEvent::Down => warn!("Check {check_id} is down"),
+        AlertEvent::Up => info!("Check {check_id} is up again"),
+        AlertEvent::FlappingStarted => {
+            warn!("Check {check_id} is flapping, suppressing its transition alerts")
+        }
+        AlertEvent::FlappingStopped { up } => {
+            let state = if up { "up" } else { "down" };
+            info!("Check {check_id} stopped flapping, it is {state}")
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_oscillating_check_is_suppressed() {
+        let detector = FlapDetector::new(Duration::from_secs(600), 3);
+        let check_id = Uuid::new_v4();
+        let start = Instant::now();
+        let at = |minutes: u64| start + Duration::from_secs(60 * minutes);
+
+        // Up and down every minute for 10 minutes
+        let events: Vec<_> = (0..10)
+            .map(|minute| detector.record(check_id, minute % 2 == 0, at(minute)))
+            .collect();
+        assert_eq!(
+            events,
+            [
+                None,
+                Some(AlertEvent::Down),
+                Some(AlertEvent::Up),
+                Some(AlertEvent::Down),
+                // The fourth change within the window
+                Some(AlertEvent::FlappingStarted),
+                None,
+                None,
+                None,
+                None,
+                None,
+            ]
+        );
+
+        // Stable, but the last changes are still within the window
+        assert_eq!(detector.record(check_id, false, at(10)), None);
+        assert_eq!(detector.record(check_id, false, at(18)), None);
+        // The last change was 10 minutes ago
+        assert_eq!(
+            detector.record(check_id, false, at(19)),
+            Some(AlertEvent::FlappingStopped { up: false })
+        );
+        // Transitions alert again
+        assert_eq!(
+            detector.record(check_id, true, at(20)),
+            Some(AlertEvent::Up)
+        );
+
+        // Other checks are unaffected
+        let other = Uuid::new_v4();
+        assert_eq!(detector.record(other, true, at(20)), None);
+        assert_eq!(
+            detector.record(other, false, at(21)),
+            Some(AlertEvent::Down)
+        );
+    }
+
+    #[test]
+    fn test_slow_changes_never_flap() {
+        let detector = FlapDetector::new(Duration::from_secs(600), 3);
+        let check_id = Uuid::new_v4();
+        let start = Instant::now();
+
+        // A change every 5 minutes is at most 2 within the window
+        for i in 1..20 {
+            let up = i % 2 == 0;
+            let event = detector.record(check_id, up, start + Duration::from_secs(300 * i));
+            if i > 1 {
+                let expected = if up { AlertEvent::Up } else { AlertEvent::Down };
+                assert_eq!(event, Some(expected));
+            }
+        }
+
+        let disabled = FlapDetector::new(Duration::from_secs(600), 0);
+        for i in 1..20 {
+            let event = disabled.record(check_id, i % 2 == 0, start + Duration::from_secs(i));
+            assert!(!matches!(
+                event,
+                Some(AlertEvent::FlappingStarted | AlertEvent::FlappingStopped { .. })
+            ));
+        }
+    }
+}
@@ -1,18 +1,37 @@
+mod breaker;
 mod check;
+mod compaction;
+mod concurrency;
 mod fetch;
+mod flapping;
+mod self_check;
+mod self_test;
+mod watchdog;
 
 use crate::{
-    collab::{NodePosition, RingRange, get_bucket_for_check},
+    clock::SharedClock,
+    collab::{NodePosition, PreviousBuckets, RingRange, bucket_for_check},
     database::Database,
     eager_env,
     regions::Region,
     server::TaskUpdateType,
     worker::{
-        check::{execute::execute_check, save::ResultSaveManager},
+        breaker::CircuitBreaker,
+        check::{
+            dns::DnsCache,
+            execute::{execute_check, probe_client_builder},
+            passive::evaluate_passive_check,
+            save::ResultSaveManager,
+        },
+        compaction::{SamplingPolicy, compaction_task_body},
+        concurrency::{ConcurrencyLimit, ProbeQueue, probe_priority},
         fetch::{ServiceCheck, fetch_health_checks, fetch_specific_health_checks},
+        flapping::{FlapDetector, log_alert_event},
+        watchdog::StarvationWatchdog,
     },
 };
-use anyhow::Result;
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
 use log::{error, info, trace, warn};
 use std::{
     cmp::Ordering,
@@ -20,9 +39,10 @@ use std::{
     sync::Arc,
     time::{Duration, Instant},
 };
+use strum::IntoEnumIterator;
 use tokio::{
     sync::{
-        Mutex, Semaphore,
+        Mutex, OwnedSemaphorePermit,
         mpsc::{self, UnboundedReceiver, UnboundedSender},
         watch::{self, Receiver},
     },
@@ -30,28 +50,138 @@ use tokio::{
 };
 use uuid::Uuid;
 
+pub use check::assertions::Assertions;
+pub use check::body::BodyRegex;
+pub use check::conditional::ConditionalRequest;
+pub use check::dns::IpVersionPreference;
+pub use check::execute::is_safe_ip;
+pub use check::geo::GeoAssertion;
+pub use check::proxy::{HostAllowlist, ProxyConfig, decrypt_password, encrypt_password};
+pub use check::status::ExpectedStatusCodes;
+pub use check::steps::{CheckKind, CheckStep};
+pub use check::tls::MinTlsVersion;
 pub use fetch::Method;
+use self_check::self_check_task_body;
+pub use self_check::{SelfCheckConfig, register_self_check};
+pub use self_test::{EGRESS_SELF_TEST_RETRY_INTERVAL, egress_readiness};
 
 const SCHEDULING_TOLERANCE_MILLIS: u64 = 100;
 
+/// First executions of newly acquired checks are spread over this window,
+/// so that taking over a range doesn't probe all of its checks at once.
+const TAKEOVER_STAGGER_WINDOW_MILLIS: u64 = 10_000;
+
 pub struct Task {
     last_execution_start: Option<Instant>,
     details: ServiceCheck,
+    /// Added to the check frequency while its target keeps failing, see [`CircuitBreaker`]
+    backoff: Duration,
 }
 
 impl Task {
+    /// Creates a task whose first execution is its next slot, see [`Task::slot_delay`].
+    ///
+    /// `now` and `wall_now` are the same instant, on the monotonic and on the wall clock.
+    fn new_staggered(details: ServiceCheck, now: Instant, wall_now: DateTime<Utc>) -> Self {
+        let frequency = Duration::from_secs(details.check_frequency_seconds as u64);
+        let delay = Self::slot_delay(&details, wall_now);
+
+        // Pretend the check last ran so that the next execution lands at `now + delay`
+        let last_execution_start = (now + delay).checked_sub(frequency);
+
+        Self {
+            last_execution_start,
+            details,
+            backoff: Duration::ZERO,
+        }
+    }
+
+    /// Time from `wall_now` until the next slot of the check: the multiples of its frequency since
+    /// the epoch, shifted by its [`Task::phase_offset`] within [`TAKEOVER_STAGGER_WINDOW_MILLIS`]
+    /// but never more than the frequency.
+    ///
+    /// Slots only depend on the wall clock, so that a check keeps its phase whichever node probes
+    /// it and whenever it was acquired.
+    fn slot_delay(details: &ServiceCheck, wall_now: DateTime<Utc>) -> Duration {
+        let frequency = Duration::from_secs(details.check_frequency_seconds as u64);
+        let window = Duration::from_millis(TAKEOVER_STAGGER_WINDOW_MILLIS).min(frequency);
+        let offset_millis = Self::phase_offset(details, window).as_millis() as i64;
+        let frequency_millis = (frequency.as_millis() as i64).max(1);
+
+        let since_slot = (wall_now.timestamp_millis() - offset_millis).rem_euclid(frequency_millis);
+        Duration::from_millis(((frequency_millis - since_slot) % frequency_millis) as u64)
+    }
+
+    /// Deterministic offset within `window`, derived from the `check_id` and the region.
+    ///
+    /// The regions of a check are spread evenly over the window, so that they don't all probe at
+    /// the same instant and a transient outage is more likely caught by one of them.
+    fn phase_offset(details: &ServiceCheck, window: Duration) -> Duration {
+        let window_millis = window.as_millis().max(1);
+        let region_count = Region::iter().count() as u128;
+        let region_index = Region::iter()
+            .position(|region| region == details.region)
+            .unwrap_or_default() as u128;
+
+        let check_offset = details.check_id.as_u128() % window_millis;
+        let region_offset = window_millis * region_index / region_count;
+        Duration::from_millis(((check_offset + region_offset) % window_millis) as u64)
+    }
+
+    /// Creates the task of an updated check, rescheduled for its new frequency.
+    ///
+    /// It runs at its previously scheduled time or one new period after its last run,
+    /// whichever comes first: a longer frequency doesn't delay the pending execution,
+    /// and a shorter one takes effect right away. If that time has already passed,
+    /// the execution waits for its next slot like for newly acquired checks, so that shortening
+    /// many frequencies at once doesn't cause a burst.
+    fn rescheduled(
+        previous: Option<&Task>,
+        details: ServiceCheck,
+        now: Instant,
+        wall_now: DateTime<Utc>,
+    ) -> Self {
+        let Some(last_start) = previous.and_then(|task| task.last_execution_start) else {
+            return Self {
+                last_execution_start: None,
+                details,
+                backoff: Duration::ZERO,
+            };
+        };
+
+        let frequency = Duration::from_secs(details.check_frequency_seconds as u64);
+        let previous_next = previous
+            .and_then(Task::get_theoretical_time)
+            .unwrap_or(last_start + frequency);
+        let next = previous_next.min(last_start + frequency);
+
+        if next <= now {
+            return Self::new_staggered(details, now, wall_now);
+        }
+
+        Self {
+            last_execution_start: next.checked_sub(frequency).or(Some(last_start)),
+            details,
+            backoff: Duration::ZERO,
+        }
+    }
+
+    /// Time between two executions: the check frequency, plus the backoff.
+    fn interval(&self) -> Duration {
+        Duration::from_secs(self.details.check_frequency_seconds as u64) + self.backoff
+    }
+
     /// Returns the next scheduled execution time for this task.
     ///
     /// If the task has never been executed (`last_execution_start` is `None`),
     /// returns `now` for immediate execution. Otherwise, calculates the next
-    /// execution as `last_execution_start + check_frequency_seconds`, but never
+    /// execution as `last_execution_start + interval`, but never
     /// schedules in the past (returns at least `now`).
     fn get_next_execution(&self, now: Instant) -> Instant {
         match self.last_execution_start {
             None => now,
             Some(last_start) => {
-                let scheduled =
-                    last_start + Duration::from_secs(self.details.check_frequency_seconds as u64);
+                let scheduled = last_start + self.interval();
 
                 if scheduled < now - Duration::from_millis(SCHEDULING_TOLERANCE_MILLIS) {
                     now
@@ -64,11 +194,10 @@ impl Task {
 
     /// Returns the theoretical next execution time for this task.
     ///
-    /// This is calculated as `last_execution_start + check_frequency_seconds`,
+    /// This is calculated as `last_execution_start + interval`,
     /// or `None` if the task has never been executed.
     fn get_theoretical_time(&self) -> Option<Instant> {
-        self.last_execution_start
-            .map(|t| t + Duration::from_secs(self.details.check_frequency_seconds as u64))
+        self.last_execution_start.map(|t| t + self.interval())
     }
 }
 
@@ -77,6 +206,71 @@ pub struct WorkerMetadata {
     region: Region,
     bucket_version: i16,
     bucket_count: NodePosition,
+    /// Also read while checks migrate to `bucket_version`
+    previous_buckets: Option<PreviousBuckets>,
+}
+
+/// Read-only view of what a worker is currently responsible for, shared with the server.
+#[derive(Clone)]
+pub struct WorkerStatus {
+    region: Region,
+    bucket_count: NodePosition,
+    range_updates: Receiver<Option<RingRange>>,
+    next_executions: Arc<Mutex<BinaryHeap<Task>>>,
+    scheduling_starved: Receiver<bool>,
+}
+
+impl WorkerStatus {
+    /// Returns the scheduled check ids that belong to the current range.
+    pub async fn owned_check_ids(&self) -> BTreeSet<Uuid> {
+        let scheduled = self
+            .next_executions
+            .lock()
+            .await
+            .iter()
+            .map(|task| task.details.check_id)
+            .collect();
+
+        Worker::filter_check_ids_by_range(
+            scheduled,
+            *self.range_updates.borrow(),
+            self.bucket_count,
+        )
+    }
+
+    /// Number of checks of the region, extrapolated from those owned in the current range as if
+    /// checks were spread evenly over the buckets. `None` without a range.
+    pub async fn estimated_region_checks(&self) -> Option<u64> {
+        let range = (*self.range_updates.borrow())?;
+        let owned_buckets = range.iter(self.bucket_count).count() as u64;
+        if owned_buckets == 0 {
+            return None;
+        }
+
+        let owned_checks = self.owned_check_ids().await.len() as u64;
+        Some(owned_checks * self.bucket_count as u64 / owned_buckets)
+    }
+
+    /// Region whose checks the worker executes
+    pub fn region(&self) -> Region {
+        self.region
+    }
+
+    /// Whether checks have been dispatched late for a sustained period, see [`StarvationWatchdog`].
+    pub fn is_scheduling_starved(&self) -> bool {
+        *self.scheduling_starved.borrow()
+    }
+
+    #[cfg(test)]
+    pub fn detached() -> Self {
+        Self {
+            region: Region::Fsn1,
+            bucket_count: 1,
+            range_updates: watch::channel(None).1,
+            next_executions: Default::default(),
+            scheduling_starved: watch::channel(false).1,
+        }
+    }
 }
 
 pub struct Worker {
@@ -84,20 +278,33 @@ pub struct Worker {
     metadata: WorkerMetadata,
     range_updates: Receiver<Option<RingRange>>,
     next_executions: Arc<Mutex<BinaryHeap<Task>>>,
-    semaphore: Arc<Semaphore>,
+    concurrency: Arc<ConcurrencyLimit>,
+    breaker: Arc<CircuitBreaker>,
+    flap_detector: Arc<FlapDetector>,
     http_client: reqwest::Client,
+    dns_cache: Arc<DnsCache>,
     save_manager: ResultSaveManager,
     task_updates: UnboundedReceiver<TaskUpdateType>,
+    probing_enabled: Receiver<bool>,
+    clock: SharedClock,
+    watchdog: Arc<StarvationWatchdog>,
+    /// How long after starting probes are held, see [`Worker::work_task_body`]
+    startup_grace: Duration,
+    /// Check pinned to this node, probed outside of the ring, see [`self_check_task_body`]
+    self_check: Option<Uuid>,
 }
 
 impl Worker {
+    #[allow(clippy::too_many_arguments)]
     pub async fn new(
         database: Arc<Database>,
+        clock: SharedClock,
         region: Region,
         bucket_version: i16,
         bucket_count: NodePosition,
         range_updates: Receiver<Option<RingRange>>,
         task_updates: UnboundedReceiver<TaskUpdateType>,
+        probing_enabled: Receiver<bool>,
     ) -> Result<Self> {
         let instance = Self {
             range_updates,
@@ -105,33 +312,67 @@ impl Worker {
                 region,
                 bucket_version,
                 bucket_count,
+                previous_buckets: PreviousBuckets::from_env(),
             },
             next_executions: Default::default(),
-            semaphore: Arc::new(Semaphore::new(*eager_env::MAX_CONCURRENT_HEALTH_CHECKS)),
-            http_client: reqwest::Client::new(),
+            concurrency: Arc::new(ConcurrencyLimit::from_env()),
+            breaker: Arc::new(CircuitBreaker::from_env()),
+            flap_detector: Arc::new(FlapDetector::from_env()),
+            http_client: probe_client_builder(eager_env::probe_bind_address())
+                .build()
+                .context("Failed to build probe client")?,
+            dns_cache: Default::default(),
             save_manager: ResultSaveManager::new(database.clone(), region).await?,
             database,
             task_updates,
+            probing_enabled,
+            clock,
+            watchdog: Arc::new(StarvationWatchdog::from_env()),
+            startup_grace: Duration::from_secs(*eager_env::STARTUP_GRACE_SECONDS),
+            self_check: None,
         };
 
         Ok(instance)
     }
 
+    /// Probes the self-check of the node, see [`register_self_check`]
+    pub fn with_self_check(mut self, check_id: Uuid) -> Self {
+        self.self_check = Some(check_id);
+        self
+    }
+
+    pub fn status(&self) -> WorkerStatus {
+        WorkerStatus {
+            region: self.metadata.region,
+            bucket_count: self.metadata.bucket_count,
+            range_updates: self.range_updates.clone(),
+            next_executions: self.next_executions.clone(),
+            scheduling_starved: self.watchdog.subscribe(),
+        }
+    }
+
     pub fn start(self) -> impl Future<Output = ()> {
         // Clone before moving `self`
+        let status = self.status();
         let sync_task_next_executions = self.next_executions.clone();
         let work_task_next_executions = self.next_executions.clone();
-        let semaphore = self.semaphore.clone();
+        let (probe_queue, probe_queue_task) = ProbeQueue::start(self.concurrency.semaphore());
+        let breaker = self.breaker.clone();
+        let flap_detector = self.flap_detector.clone();
         let http_client = self.http_client.clone();
+        let dns_cache = self.dns_cache.clone();
         let save_manager = Arc::new(self.save_manager);
         let mut task_updates = self.task_updates;
 
         let (queue_update_tx, queue_update_rx) = watch::channel(());
+        let queue_update_tx_lt = queue_update_tx.clone();
 
         // Thread that listens to changes
         let metadata_ru = self.metadata.clone();
         let queue_update_tx_ru = queue_update_tx.clone();
         let database_ru = self.database.clone();
+        let clock_ru = self.clock.clone();
+        let concurrency_ru = self.concurrency.clone();
         let mut range_updates_ru = self.range_updates.clone();
         let sync_task = tokio::spawn(async move {
             while range_updates_ru.changed().await.is_ok() {
@@ -142,7 +383,10 @@ impl Worker {
                 let result = Self::handle_new_range(
                     &metadata_ru,
                     &sync_task_next_executions,
+                    &concurrency_ru,
                     &database_ru,
+                    clock_ru.instant(),
+                    clock_ru.now(),
                     range,
                 )
                 .await;
@@ -159,10 +403,15 @@ impl Worker {
         let metadata_tu = self.metadata.clone();
         let database_tu = self.database.clone();
         let next_executions_tu = self.next_executions.clone();
+        let clock_tu = self.clock.clone();
         let range_updates_tu = self.range_updates.clone();
         let update_task = tokio::spawn(async move {
             while let Some(mut check_ids) = task_updates.recv().await {
-                check_ids = Self::filter_check_ids_by_range(check_ids, *range_updates_tu.borrow());
+                check_ids = Self::filter_check_ids_by_range(
+                    check_ids,
+                    *range_updates_tu.borrow(),
+                    metadata_tu.bucket_count,
+                );
 
                 if check_ids.is_empty() {
                     continue;
@@ -181,7 +430,7 @@ impl Worker {
                 )
                 .await
                 {
-                    Ok(n) => n,
+                    Ok(checks) => Self::ring_checks(checks),
                     Err(e) => {
                         error!("Failed to fetch updated health checks: {}", e);
                         continue;
@@ -189,7 +438,13 @@ impl Worker {
                 };
 
                 let mut executions = next_executions_tu.lock().await;
-                Worker::update_tasks(&mut executions, &check_ids, updated_checks);
+                Worker::update_tasks(
+                    &mut executions,
+                    &check_ids,
+                    updated_checks,
+                    clock_tu.instant(),
+                    clock_tu.now(),
+                );
                 drop(executions);
 
                 let _ = queue_update_tx.send(());
@@ -198,25 +453,118 @@ impl Worker {
 
         let (task_tx, mut task_rx) = mpsc::unbounded_channel();
 
+        let compaction_task = SamplingPolicy::from_env().map(|policy| {
+            tokio::spawn(compaction_task_body(
+                self.database.clone(),
+                status,
+                self.clock.clone(),
+                policy,
+            ))
+        });
+
+        let self_check_task = self.self_check.map(|check_id| {
+            tokio::spawn(self_check_task_body(
+                self.database.clone(),
+                check_id,
+                self.metadata.region,
+                self.http_client.clone(),
+                self.dns_cache.clone(),
+                save_manager.clone(),
+                self.probing_enabled.clone(),
+                self.clock.clone(),
+            ))
+        });
+
         let work_task = tokio::spawn(Self::work_task_body(
             work_task_next_executions,
             queue_update_rx,
+            self.probing_enabled,
+            self.clock.instant() + self.startup_grace,
+            self.clock.clone(),
+            self.breaker,
             task_tx,
         ));
 
         let save_manager_clone = save_manager.clone();
+        let next_executions_lt = self.next_executions.clone();
+        let database_lt = self.database.clone();
+        let clock_lt = self.clock.clone();
+        let watchdog = self.watchdog;
         let listen_task = tokio::spawn(async move {
-            while let Some(task) = task_rx.recv().await {
-                let semaphore_clone = semaphore.clone();
+            while let Some((task, scheduled)) = task_rx.recv().await {
+                let probe_queue_clone = probe_queue.clone();
                 let client_clone = http_client.clone();
+                let dns_cache_clone = dns_cache.clone();
                 let save_manager_clone = save_manager_clone.clone();
+                let breaker_clone = breaker.clone();
+                let flap_detector_clone = flap_detector.clone();
+                let next_executions_clone = next_executions_lt.clone();
+                let queue_update_tx_clone = queue_update_tx_lt.clone();
+                let database_clone = database_lt.clone();
+                let clock_clone = clock_lt.clone();
+                let watchdog_clone = watchdog.clone();
 
                 tokio::spawn(async move {
-                    let guard = semaphore_clone.acquire().await.expect("semaphore closed");
-                    let result = execute_check(&client_clone, &task, *eager_env::DEV_MODE).await;
-                    drop(guard);
-
-                    let result = result.and_then(|r| save_manager_clone.save(r));
+                    let passive = task.kind == CheckKind::Passive;
+                    let result = if passive {
+                        evaluate_passive_check(
+                            &database_clone,
+                            &task,
+                            Duration::from_secs(*eager_env::PASSIVE_CHECK_GRACE_SECONDS),
+                            clock_clone.now(),
+                        )
+                        .await
+                    } else {
+                        let guard = Self::acquire_probe_permit(
+                            &probe_queue_clone,
+                            &watchdog_clone,
+                            &clock_clone,
+                            &task,
+                            scheduled,
+                        )
+                        .await;
+                        let result = execute_check(
+                            &client_clone,
+                            &dns_cache_clone,
+                            &task,
+                            &eager_env::PROXY_REMOTE_DNS_ALLOWED_HOSTS,
+                        )
+                        .await;
+                        drop(guard);
+                        result
+                    };
+
+                    let result = match result {
+                        Ok(r) => {
+                            if let Some(event) = flap_detector_clone.record(
+                                r.service_check_id,
+                                r.matches_expected,
+                                clock_clone.instant(),
+                            ) {
+                                log_alert_event(r.service_check_id, event);
+                            }
+                            // Missed pings are never backed off, they cost no request
+                            if !passive
+                                && breaker_clone.record(
+                                    r.service_check_id,
+                                    r.matches_expected,
+                                    clock_clone.instant(),
+                                )
+                            {
+                                let mut executions = next_executions_clone.lock().await;
+                                Self::reset_backoff(&mut executions, r.service_check_id);
+                                drop(executions);
+                                queue_update_tx_clone.send_replace(());
+                            }
+                            if task.store_raw_results {
+                                save_manager_clone.save(r)
+                            } else {
+                                save_manager_clone.aggregate(&r);
+                                Ok(())
+                            }
+                        }
+                        Err(e) => Err(e),
+                    };
 
                     if let Err(e) = result {
                         error!("error executing check: {e}");
@@ -232,10 +580,22 @@ impl Worker {
             work_task.abort();
             sync_task.abort();
             listen_task.abort();
+            probe_queue_task.abort();
             update_task.abort();
+            if let Some(compaction_task) = compaction_task {
+                compaction_task.abort();
+            }
+            if let Some(self_check_task) = self_check_task {
+                self_check_task.abort();
+            }
 
-            // TODO fix to wait at least the MAXIMUM_TIMEOUT
-            tokio::time::sleep(Duration::from_secs(5)).await;
+            // Probes in flight hold the save manager until their result is sent. Their timeout
+            // is clamped to `MAX_PROBE_TIMEOUT_SECONDS`, so waiting that long lets them all finish
+            let deadline =
+                Instant::now() + Duration::from_secs(*eager_env::MAX_PROBE_TIMEOUT_SECONDS);
+            while Arc::strong_count(&save_manager) > 1 && Instant::now() < deadline {
+                tokio::time::sleep(Duration::from_millis(100)).await;
+            }
 
             // This should succeed as other instances are dropped after the abortion
             match Arc::into_inner(save_manager) {
@@ -255,34 +615,60 @@ impl Worker {
     /// Waits for tasks to become ready based on their scheduled time, executes them,
     /// and reschedules them for their next execution. Responds to queue updates by
     /// re-evaluating the schedule immediately.
+    /// While probing is disabled, due tasks are still rescheduled but not dispatched.
+    /// Before `dispatch_after`, tasks are held in the queue: those falling due meanwhile run once
+    /// it passes, so the node warms up (DNS cache, connection pool) before probing.
     ///
     /// # Parameters
     /// * `next_executions` - Shared priority queue of scheduled tasks
     /// * `queue_update_rx` - Receiver that signals when the task queue has been updated
-    /// * `task_tx` - Channel sender for dispatching tasks ready for execution
+    /// * `probing_enabled` - Receiver of the cluster-wide probing switch
+    /// * `dispatch_after` - End of the startup grace, per `clock`
+    /// * `clock` - Time source used to decide which tasks are due
+    /// * `breaker` - Backs off the tasks of failing checks
+    /// * `task_tx` - Channel sender for dispatching tasks ready for execution, with their
+    ///   scheduled time
     async fn work_task_body(
         next_executions: Arc<Mutex<BinaryHeap<Task>>>,
         mut queue_update_rx: Receiver<()>,
-        task_tx: UnboundedSender<ServiceCheck>,
+        mut probing_enabled: Receiver<bool>,
+        dispatch_after: Instant,
+        clock: SharedClock,
+        breaker: Arc<CircuitBreaker>,
+        task_tx: UnboundedSender<(ServiceCheck, Instant)>,
     ) {
         loop {
+            let now = clock.instant();
+
+            if now < dispatch_after {
+                tokio::select! {
+                    _ = time::sleep(dispatch_after - now) => {}
+                    // The clock may have been advanced
+                    _ = queue_update_rx.changed() => {}
+                }
+                continue;
+            }
             let (tasks, next_task_time) =
-                Self::get_tasks_to_execute_and_reschedule(next_executions.clone(), Instant::now())
+                Self::get_tasks_to_execute_and_reschedule(next_executions.clone(), &breaker, now)
                     .await;
 
-            for task in tasks {
-                trace!(
-                    "Sent health check task for execution: {:?} {}",
-                    task.check_name, task.check_frequency_seconds
-                );
-                let res = task_tx.send(task);
-                if let Err(e) = res {
-                    error!("error sending task to execution: {e}");
+            if *probing_enabled.borrow_and_update() {
+                for (task, scheduled) in tasks {
+                    trace!(
+                        "Sent health check task for execution: {:?} {}",
+                        task.check_name, task.check_frequency_seconds
+                    );
+                    let res = task_tx.send((task, scheduled));
+                    if let Err(e) = res {
+                        error!("error sending task to execution: {e}");
+                    }
                 }
+            } else if !tasks.is_empty() {
+                trace!("Probing disabled, skipping {} due tasks", tasks.len());
             }
 
             let wait_duration = match next_task_time {
-                Some(next_execution) => next_execution.saturating_duration_since(Instant::now()),
+                Some(next_execution) => next_execution.saturating_duration_since(clock.instant()),
                 None => {
                     // In dev mode, "disable" checking by default to spot bugs
                     let default_duration = if *eager_env::DEV_MODE { 100000 } else { 1 };
@@ -297,6 +683,9 @@ impl Worker {
                 _ = queue_update_rx.changed() => {
                     // Queue was updated, re-evaluate
                 }
+                Ok(()) = probing_enabled.changed() => {
+                    // Probing was toggled, re-evaluate
+                }
             }
         }
     }
@@ -304,27 +693,49 @@ impl Worker {
     /// Retrieves all tasks that are due for execution (scheduled at or before `now`),
     /// executes them, and reschedules them for their next run based on their frequency.
     ///
-    /// Returns a tuple of (tasks to execute, next scheduled execution time).
+    /// Returns a tuple of (tasks to execute with their scheduled time, next scheduled execution
+    /// time).
+    ///
+    /// Due tasks are first backed off according to `breaker`: those no longer due are put back
+    /// without executing.
     ///
     /// `now` is used for consistency in tests,
     async fn get_tasks_to_execute_and_reschedule(
         next_executions: Arc<Mutex<BinaryHeap<Task>>>,
+        breaker: &CircuitBreaker,
         now: Instant,
-    ) -> (Vec<ServiceCheck>, Option<Instant>) {
+    ) -> (Vec<(ServiceCheck, Instant)>, Option<Instant>) {
         let mut executions = next_executions.lock().await;
 
-        let mut tasks_to_execute = Vec::new();
+        let mut due_tasks = Vec::new();
         while let Some(task) = executions.peek() {
             if task.get_next_execution(now) <= now {
-                tasks_to_execute.push(executions.pop().expect("peeked"));
+                due_tasks.push(executions.pop().expect("peeked"));
             } else {
                 break;
             }
         }
 
-        let tasks: Vec<ServiceCheck> = tasks_to_execute
+        // The backoff only changes while the task is out of the heap, keeping its order valid
+        let mut tasks_to_execute = Vec::new();
+        for mut task in due_tasks {
+            task.backoff = breaker.backoff(
+                task.details.check_id,
+                Duration::from_secs(task.details.check_frequency_seconds as u64),
+            );
+
+            if task.get_next_execution(now) <= now {
+                tasks_to_execute.push(task);
+            } else {
+                executions.push(task);
+            }
+        }
+
+        let tasks: Vec<(ServiceCheck, Instant)> = tasks_to_execute
             .into_iter()
             .map(|mut task| {
+                // Overdue tasks are rescheduled from `now`, but their lag counts from the slot missed
+                let scheduled = task.get_theoretical_time().unwrap_or(now);
                 // Prevent drift by using the "next execution" (that's in the past since it is not yet updated).
                 // By definition it's not more than SCHEDULING_TOLERANCE_MILLIS in the past
                 task.last_execution_start = Some(task.get_next_execution(now));
@@ -332,7 +743,7 @@ impl Worker {
                 let details = task.details.clone();
                 executions.push(task);
 
-                details
+                (details, scheduled)
             })
             .collect();
 
@@ -341,10 +752,32 @@ impl Worker {
         (tasks, next_execution_time)
     }
 
+    /// Waits for a probe permit, recording in `watchdog` how late past `scheduled` the probe
+    /// can start.
+    async fn acquire_probe_permit(
+        probe_queue: &ProbeQueue,
+        watchdog: &StarvationWatchdog,
+        clock: &SharedClock,
+        task: &ServiceCheck,
+        scheduled: Instant,
+    ) -> OwnedSemaphorePermit {
+        let permit = probe_queue
+            .acquire(probe_priority(task.check_frequency_seconds))
+            .await;
+        let now = clock.instant();
+        watchdog.observe(now.saturating_duration_since(scheduled), now);
+        permit
+    }
+
+    /// Replaces the scheduled checks with those of `range`, and resizes the concurrency limit
+    /// to their number.
     async fn handle_new_range(
         metadata: &WorkerMetadata,
         next_executions: &Arc<Mutex<BinaryHeap<Task>>>,
+        concurrency: &ConcurrencyLimit,
         session: &Database,
+        now: Instant,
+        wall_now: DateTime<Utc>,
         range: Option<RingRange>,
     ) -> Result<()> {
         match range {
@@ -355,22 +788,37 @@ impl Worker {
                     metadata.bucket_version,
                     range,
                     metadata.bucket_count,
+                    metadata.previous_buckets,
                 )
                 .await?;
+                let new_items = Self::ring_checks(new_items);
 
                 let mut executions = next_executions.lock().await;
-                Self::merge_new_checks(new_items, &mut executions);
+                Self::merge_new_checks(new_items, &mut executions, now, wall_now);
+                concurrency.resize_for(executions.len());
             }
             None => {
                 let mut executions = next_executions.lock().await;
-                executions.clear()
+                executions.clear();
+                concurrency.resize_for(0);
             }
         }
 
         Ok(())
     }
 
-    fn merge_new_checks(new_items: Vec<ServiceCheck>, heap: &mut BinaryHeap<Task>) {
+    /// Leaves out checks pinned to a node, which probes them itself whoever owns their bucket
+    fn ring_checks(mut checks: Vec<ServiceCheck>) -> Vec<ServiceCheck> {
+        checks.retain(|check| check.pinned_node.is_none());
+        checks
+    }
+
+    fn merge_new_checks(
+        new_items: Vec<ServiceCheck>,
+        heap: &mut BinaryHeap<Task>,
+        now: Instant,
+        wall_now: DateTime<Utc>,
+    ) {
         let new_item_set: HashSet<_> = new_items.iter().map(|item| item.check_id).collect();
 
         // Remove tasks that are not present in new_items
@@ -385,53 +833,60 @@ impl Worker {
         // TODO: update other fields
         let scheduled_items: HashSet<_> = heap.iter().map(|task| task.details.check_id).collect();
 
-        // Schedule immediate executions for new items
+        // Schedule staggered executions for new items
         for item in new_items {
             if !scheduled_items.contains(&item.check_id) {
-                heap.push(Task {
-                    last_execution_start: None,
-                    details: item,
-                });
+                heap.push(Task::new_staggered(item, now, wall_now));
             }
         }
     }
 
     /// Updates the task heap by removing deleted tasks and updating/inserting modified tasks.
+    /// Updated tasks are rescheduled for their new frequency, see [`Task::rescheduled`].
     ///
     /// # Parameters
     /// * `heap` - The binary heap of tasks to update
     /// * `update_list` - Set of task IDs that were fetched/updated
     /// * `fetched_tasks` - Vector of updated ServiceCheck objects to insert/update
+    /// * `now` - Current time, used to reschedule updated tasks
+    /// * `wall_now` - Same as `now` on the wall clock, see [`Task::slot_delay`]
     fn update_tasks(
         heap: &mut BinaryHeap<Task>,
         update_list: &BTreeSet<Uuid>,
         fetched_tasks: Vec<ServiceCheck>,
+        now: Instant,
+        wall_now: DateTime<Utc>,
     ) {
-        // Preserve execution times for tasks that are being updated
-        let mut preserved_execution_times = std::collections::HashMap::new();
+        // Keep the previous tasks of updated checks to reschedule them
+        let mut previous_tasks = std::collections::HashMap::new();
 
         let existing_tasks: Vec<Task> = heap.drain().collect();
         for task in existing_tasks {
             if update_list.contains(&task.details.check_id) {
-                // Task is being updated, preserve its execution time
-                preserved_execution_times.insert(task.details.check_id, task.last_execution_start);
+                previous_tasks.insert(task.details.check_id, task);
             } else {
                 // Task is not in update list, keep it as-is
                 heap.push(task);
             }
         }
 
-        // Insert/update tasks with preserved execution times where available
+        // Insert/update tasks, rescheduled from their previous execution where available
         for check in fetched_tasks {
-            let last_execution_start = preserved_execution_times
-                .get(&check.check_id)
-                .copied()
-                .flatten();
-            heap.push(Task {
-                last_execution_start,
-                details: check,
-            });
+            let previous = previous_tasks.get(&check.check_id);
+            heap.push(Task::rescheduled(previous, check, now, wall_now));
+        }
+    }
+
+    /// Restores the frequency of a recovered check right away, rather than after its backed off
+    /// execution.
+    fn reset_backoff(heap: &mut BinaryHeap<Task>, check_id: Uuid) {
+        let mut tasks = std::mem::take(heap).into_vec();
+        for task in &mut tasks {
+            if task.details.check_id == check_id {
+                task.backoff = Duration::ZERO;
+            }
         }
+        *heap = tasks.into();
     }
 
     /// Filters check IDs based on the current range assignment.
@@ -440,11 +895,12 @@ impl Worker {
     fn filter_check_ids_by_range(
         check_ids: BTreeSet<Uuid>,
         range: Option<RingRange>,
+        bucket_count: NodePosition,
     ) -> BTreeSet<Uuid> {
         match range {
             Some(range) => check_ids
                 .into_iter()
-                .filter(|id| range.contains(get_bucket_for_check(*id).1 as u32))
+                .filter(|id| range.contains(bucket_for_check(*id, bucket_count)))
                 .collect(),
             None => Default::default(),
         }
@@ -480,7 +936,13 @@ impl Ord for Task {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::database::testing::create_test_database;
+    use crate::{
+        clock::{Clock, MockClock, SystemClock},
+        database::testing::create_test_database,
+    };
+
+    use proptest::prelude::*;
+    use tokio::sync::Semaphore;
     use uuid::uuid;
 
     const FIXTURES: &str = include_str!("fixtures.cql");
@@ -505,22 +967,33 @@ mod tests {
             h.push(Task {
                 last_execution_start: Some(last_execution_check_1),
                 details: check1,
+                backoff: Duration::ZERO,
             });
             h.push(Task {
                 last_execution_start: Some(last_execution_check_2),
                 details: check2,
+                backoff: Duration::ZERO,
             });
         }
 
         let heap_clone = heap.clone();
-        let work_handle = tokio::spawn(Worker::work_task_body(heap_clone, queue_rx, task_tx));
+        let (_probing_tx, probing_rx) = watch::channel(true);
+        let work_handle = tokio::spawn(Worker::work_task_body(
+            heap_clone,
+            queue_rx,
+            probing_rx,
+            Instant::now(),
+            SystemClock::shared(),
+            Arc::new(CircuitBreaker::disabled()),
+            task_tx,
+        ));
 
         // Give work_task_body time to execute
         time::sleep(Duration::from_millis(50)).await;
 
         // Check that the past task was sent
         let received = task_rx.try_recv();
-        assert_eq!(received.unwrap().check_id, check1_id);
+        assert_eq!(received.unwrap().0.check_id, check1_id);
 
         // Verify the task was rescheduled
         {
@@ -537,6 +1010,7 @@ mod tests {
             h.push(Task {
                 last_execution_start: None,
                 details: check_immediate,
+                backoff: Duration::ZERO,
             });
         }
 
@@ -548,11 +1022,190 @@ mod tests {
 
         // Verify that the immediate task was executed
         let received_immediate = task_rx.try_recv();
-        assert_eq!(received_immediate.unwrap().check_id, check_immediate_id);
+        assert_eq!(received_immediate.unwrap().0.check_id, check_immediate_id);
+
+        work_handle.abort();
+    }
+
+    #[tokio::test]
+    async fn test_work_task_body_probing_toggle() {
+        let heap = Arc::new(Mutex::new(BinaryHeap::new()));
+        let (_queue_tx, queue_rx) = watch::channel(());
+        let (probing_tx, probing_rx) = watch::channel(false);
+        let (task_tx, mut task_rx) = mpsc::unbounded_channel();
+
+        let check = ServiceCheck {
+            check_frequency_seconds: 1,
+            ..ServiceCheck::example()
+        };
+        let check_id = check.check_id;
+        heap.lock().await.push(Task {
+            last_execution_start: None,
+            details: check,
+            backoff: Duration::ZERO,
+        });
+
+        let work_handle = tokio::spawn(Worker::work_task_body(
+            heap.clone(),
+            queue_rx,
+            probing_rx,
+            Instant::now(),
+            SystemClock::shared(),
+            Arc::new(CircuitBreaker::disabled()),
+            task_tx,
+        ));
+
+        // Paused: the due task is rescheduled but never dispatched
+        time::sleep(Duration::from_millis(1500)).await;
+        assert!(task_rx.try_recv().is_err());
+        assert_eq!(heap.lock().await.len(), 1);
+
+        // Resumed: dispatching restarts from the next scheduled execution
+        probing_tx.send_replace(true);
+        time::sleep(Duration::from_millis(1200)).await;
+        assert_eq!(task_rx.try_recv().unwrap().0.check_id, check_id);
+
+        // Paused again
+        probing_tx.send_replace(false);
+        while task_rx.try_recv().is_ok() {}
+        time::sleep(Duration::from_millis(1500)).await;
+        assert!(task_rx.try_recv().is_err());
+
+        work_handle.abort();
+    }
+
+    #[tokio::test]
+    async fn test_work_task_body_mock_clock() {
+        let heap = Arc::new(Mutex::new(BinaryHeap::new()));
+        let (queue_tx, queue_rx) = watch::channel(());
+        let (_probing_tx, probing_rx) = watch::channel(true);
+        let (task_tx, mut task_rx) = mpsc::unbounded_channel();
+        let clock = MockClock::new(Utc::now());
+
+        // Last ran at the mock "now", so it is due again in exactly one period
+        let check = ServiceCheck::example();
+        let check_id = check.check_id;
+        let frequency = Duration::from_secs(check.check_frequency_seconds as u64);
+        heap.lock().await.push(Task {
+            last_execution_start: Some(clock.instant()),
+            details: check,
+            backoff: Duration::ZERO,
+        });
+
+        let work_handle = tokio::spawn(Worker::work_task_body(
+            heap.clone(),
+            queue_rx,
+            probing_rx,
+            clock.instant(),
+            clock.clone(),
+            Arc::new(CircuitBreaker::disabled()),
+            task_tx,
+        ));
+
+        time::sleep(Duration::from_millis(50)).await;
+        assert!(task_rx.try_recv().is_err());
+
+        // Not yet due
+        clock.advance(frequency - Duration::from_secs(1));
+        queue_tx.send_replace(());
+        time::sleep(Duration::from_millis(50)).await;
+        assert!(task_rx.try_recv().is_err());
+
+        // Due, without waiting for the wall clock
+        clock.advance(Duration::from_secs(1));
+        queue_tx.send_replace(());
+        time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(task_rx.try_recv().unwrap().0.check_id, check_id);
+        assert!(task_rx.try_recv().is_err());
+
+        work_handle.abort();
+    }
+
+    #[tokio::test]
+    async fn test_work_task_body_startup_grace() {
+        let heap = Arc::new(Mutex::new(BinaryHeap::new()));
+        let (queue_tx, queue_rx) = watch::channel(());
+        let (_probing_tx, probing_rx) = watch::channel(true);
+        let (task_tx, mut task_rx) = mpsc::unbounded_channel();
+        let clock = MockClock::new(Utc::now());
+
+        // Due right away
+        let check = ServiceCheck::example();
+        let check_id = check.check_id;
+        heap.lock().await.push(Task {
+            last_execution_start: None,
+            details: check,
+            backoff: Duration::ZERO,
+        });
+
+        let grace = Duration::from_secs(30);
+        let work_handle = tokio::spawn(Worker::work_task_body(
+            heap.clone(),
+            queue_rx,
+            probing_rx,
+            clock.instant() + grace,
+            clock.clone(),
+            Arc::new(CircuitBreaker::disabled()),
+            task_tx,
+        ));
+
+        // Held during the grace, and kept scheduled
+        time::sleep(Duration::from_millis(50)).await;
+        assert!(task_rx.try_recv().is_err());
+        clock.advance(grace - Duration::from_secs(1));
+        queue_tx.send_replace(());
+        time::sleep(Duration::from_millis(50)).await;
+        assert!(task_rx.try_recv().is_err());
+        assert_eq!(heap.lock().await.len(), 1);
+
+        // Dispatched once it ends
+        clock.advance(Duration::from_secs(1));
+        queue_tx.send_replace(());
+        time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(task_rx.try_recv().unwrap().0.check_id, check_id);
+        assert!(task_rx.try_recv().is_err());
 
         work_handle.abort();
     }
 
+    #[tokio::test]
+    async fn test_probe_lag_is_measured_at_permit_acquisition() {
+        let semaphore = Arc::new(Semaphore::new(1));
+        let (probe_queue, probe_queue_task) = ProbeQueue::start(semaphore.clone());
+        let mock_clock = MockClock::new(Utc::now());
+        let clock: SharedClock = mock_clock.clone();
+        let watchdog = StarvationWatchdog::new(Duration::from_secs(5), Duration::ZERO);
+        let starved = watchdog.subscribe();
+        let task = ServiceCheck::example();
+
+        // Dispatched on time, but all permits are taken by other probes
+        let held = semaphore.clone().acquire_owned().await.unwrap();
+        let scheduled = clock.instant();
+        let acquire =
+            Worker::acquire_probe_permit(&probe_queue, &watchdog, &clock, &task, scheduled);
+        tokio::pin!(acquire);
+        assert!(
+            time::timeout(Duration::from_millis(50), &mut acquire)
+                .await
+                .is_err()
+        );
+
+        // The permit is freed 10s later
+        mock_clock.advance(Duration::from_secs(10));
+        drop(held);
+        let permit = acquire.await;
+        assert!(*starved.borrow());
+        drop(permit);
+
+        // A probe getting its permit right away is on time again
+        let scheduled = clock.instant();
+        let _permit =
+            Worker::acquire_probe_permit(&probe_queue, &watchdog, &clock, &task, scheduled).await;
+        assert!(!*starved.borrow());
+
+        probe_queue_task.abort();
+    }
+
     #[tokio::test]
     async fn check_new_range() -> Result<()> {
         let (session, _keyspace) = create_test_database(Some(FIXTURES)).await?;
@@ -560,7 +1213,18 @@ mod tests {
 
         let (_tx, rx) = watch::channel(None);
         let (_tx, task_update_rx) = mpsc::unbounded_channel();
-        let worker = Worker::new(session.clone(), Region::Hel1, 1, 10, rx, task_update_rx).await?;
+        let (_tx, probing_rx) = watch::channel(true);
+        let worker = Worker::new(
+            session.clone(),
+            SystemClock::shared(),
+            Region::Hel1,
+            1,
+            10,
+            rx,
+            task_update_rx,
+            probing_rx,
+        )
+        .await?;
 
         let check1_id = uuid!("00000000-0000-0000-0000-000000000001");
         let check2_id = uuid!("00000000-0000-0000-0000-000000000002");
@@ -579,6 +1243,7 @@ mod tests {
             heap.push(Task {
                 last_execution_start: Some(scheduled_time_1),
                 details: check1,
+                backoff: Duration::ZERO,
             });
 
             let mut check2 = ServiceCheck::example();
@@ -586,6 +1251,7 @@ mod tests {
             heap.push(Task {
                 last_execution_start: Some(scheduled_time_2),
                 details: check2,
+                backoff: Duration::ZERO,
             });
 
             let mut check3 = ServiceCheck::example();
@@ -593,6 +1259,7 @@ mod tests {
             heap.push(Task {
                 last_execution_start: Some(scheduled_time_3),
                 details: check3,
+                backoff: Duration::ZERO,
             });
         }
 
@@ -601,7 +1268,10 @@ mod tests {
         Worker::handle_new_range(
             &worker.metadata,
             &worker.next_executions,
+            &worker.concurrency,
             &session,
+            Instant::now(),
+            Utc::now(),
             Some(range),
         )
         .await?;
@@ -629,7 +1299,16 @@ mod tests {
         }
 
         // Test with None range (should clear)
-        Worker::handle_new_range(&worker.metadata, &worker.next_executions, &session, None).await?;
+        Worker::handle_new_range(
+            &worker.metadata,
+            &worker.next_executions,
+            &worker.concurrency,
+            &session,
+            Instant::now(),
+            Utc::now(),
+            None,
+        )
+        .await?;
 
         {
             let heap = worker.next_executions.lock().await;
@@ -639,6 +1318,134 @@ mod tests {
         Ok(())
     }
 
+    /// A wall time on a slot boundary of every frequency dividing a day
+    fn midnight() -> DateTime<Utc> {
+        "2025-11-29T00:00:00Z".parse().unwrap()
+    }
+
+    #[test]
+    fn test_merge_new_checks_staggers_first_executions() {
+        let now = Instant::now();
+        let mut heap = BinaryHeap::new();
+
+        let new_items: Vec<_> = (0..100)
+            .map(|_| ServiceCheck {
+                check_id: Uuid::new_v4(),
+                check_frequency_seconds: 60,
+                ..ServiceCheck::example()
+            })
+            .collect();
+
+        Worker::merge_new_checks(new_items, &mut heap, now, midnight());
+        assert_eq!(heap.len(), 100);
+
+        let executions: Vec<_> = heap.iter().map(|t| t.get_next_execution(now)).collect();
+        let window_end = now + Duration::from_millis(TAKEOVER_STAGGER_WINDOW_MILLIS);
+        assert!(executions.iter().all(|&e| e >= now && e < window_end));
+
+        // Spread over the window rather than all at once
+        let distinct: HashSet<_> = executions.iter().collect();
+        assert!(distinct.len() > 90);
+        let immediate = executions.iter().filter(|&&e| e == now).count();
+        assert!(immediate < 5);
+        let first = executions.iter().min().unwrap();
+        let last = executions.iter().max().unwrap();
+        assert!(*last - *first > Duration::from_millis(TAKEOVER_STAGGER_WINDOW_MILLIS / 2));
+
+        // Checks more frequent than the window are staggered within their own frequency
+        let mut heap = BinaryHeap::new();
+        let fast = ServiceCheck {
+            check_frequency_seconds: 2,
+            ..ServiceCheck::example()
+        };
+        Worker::merge_new_checks(vec![fast], &mut heap, now, midnight());
+        let execution = heap.peek().unwrap().get_next_execution(now);
+        assert!(execution < now + Duration::from_secs(2));
+    }
+
+    #[test]
+    fn test_ring_checks_skip_pinned_checks() {
+        let pinned = ServiceCheck {
+            check_id: Uuid::new_v4(),
+            pinned_node: Some("node-a".to_string()),
+            ..ServiceCheck::example()
+        };
+        let regular = ServiceCheck {
+            check_id: Uuid::new_v4(),
+            ..ServiceCheck::example()
+        };
+
+        let checks = Worker::ring_checks(vec![pinned, regular.clone()]);
+        assert_eq!(checks.len(), 1);
+        assert_eq!(checks[0].check_id, regular.check_id);
+    }
+
+    #[test]
+    fn test_slots_anchor_to_wall_clock() {
+        let now = Instant::now();
+        let check = ServiceCheck {
+            check_id: Uuid::new_v4(),
+            check_frequency_seconds: 60,
+            ..ServiceCheck::example()
+        };
+        let offset = Task::phase_offset(
+            &check,
+            Duration::from_millis(TAKEOVER_STAGGER_WINDOW_MILLIS),
+        );
+
+        // Whenever the check is acquired, it runs on the same slots of the wall clock
+        for elapsed_millis in [0, 1, 4_999, 30_000, 59_999, 86_400_000 + 12_345] {
+            let wall_now = midnight() + chrono::Duration::milliseconds(elapsed_millis);
+            let delay = Task::new_staggered(check.clone(), now, wall_now)
+                .get_next_execution(now)
+                .duration_since(now);
+            assert!(delay < Duration::from_secs(60));
+
+            let execution = wall_now + chrono::Duration::from_std(delay).unwrap();
+            let since_slot = (execution - midnight()).num_milliseconds() % 60_000;
+            assert_eq!(
+                since_slot,
+                offset.as_millis() as i64,
+                "at +{elapsed_millis}ms"
+            );
+        }
+
+        // Right on the slot, it runs at once
+        let wall_now = midnight() + chrono::Duration::from_std(offset).unwrap();
+        assert_eq!(Task::slot_delay(&check, wall_now), Duration::ZERO);
+    }
+
+    #[test]
+    fn test_regions_probe_with_different_phases() {
+        let now = Instant::now();
+        let window = Duration::from_millis(TAKEOVER_STAGGER_WINDOW_MILLIS);
+        let check_id = Uuid::new_v4();
+        let next_execution = |region| {
+            let details = ServiceCheck {
+                check_id,
+                region,
+                check_frequency_seconds: 60,
+                ..ServiceCheck::example()
+            };
+            Task::new_staggered(details, now, midnight()).get_next_execution(now)
+        };
+
+        let fsn1 = next_execution(Region::Fsn1);
+        let hel1 = next_execution(Region::Hel1);
+        assert_ne!(fsn1, hel1);
+
+        // A third of the window apart, modulo the window
+        let gap = fsn1.max(hel1) - fsn1.min(hel1);
+        let third = window / Region::iter().count() as u32;
+        assert!(
+            gap.abs_diff(third) <= Duration::from_millis(1)
+                || gap.abs_diff(window - third) <= Duration::from_millis(1)
+        );
+
+        // Deterministic
+        assert_eq!(next_execution(Region::Fsn1), fsn1);
+    }
+
     #[tokio::test]
     async fn test_get_tasks_to_execute_and_reschedule_simple() {
         let heap = Arc::new(Mutex::new(BinaryHeap::new()));
@@ -668,30 +1475,41 @@ mod tests {
             h.push(Task {
                 last_execution_start: Some(last_exec_check_1),
                 details: check1,
+                backoff: Duration::ZERO,
             });
             h.push(Task {
                 last_execution_start: Some(last_exec_check_2),
                 details: check2,
+                backoff: Duration::ZERO,
             });
             h.push(Task {
                 last_execution_start: Some(last_exec_check_3),
                 details: check3,
+                backoff: Duration::ZERO,
             });
             h.push(Task {
                 last_execution_start: Some(last_exec_check_4),
                 details: check4,
+                backoff: Duration::ZERO,
             });
         }
 
-        let (mut tasks, next_time) =
-            Worker::get_tasks_to_execute_and_reschedule(heap.clone(), now).await;
+        let (mut tasks, next_time) = Worker::get_tasks_to_execute_and_reschedule(
+            heap.clone(),
+            &CircuitBreaker::disabled(),
+            now,
+        )
+        .await;
 
-        tasks.sort_by_key(|t| t.check_id);
+        tasks.sort_by_key(|(t, _)| t.check_id);
 
         assert_eq!(tasks.len(), 2);
-        assert_eq!(tasks[0].check_id, check1_id);
-        assert_eq!(tasks[1].check_id, check2_id);
+        assert_eq!(tasks[0].0.check_id, check1_id);
+        assert_eq!(tasks[1].0.check_id, check2_id);
         assert_eq!(next_time, Some(now + Duration::from_secs(40)));
+        // Dispatched with their scheduled time, check2 was due 5s ago
+        assert_eq!(tasks[0].1, now);
+        assert_eq!(tasks[1].1, now - Duration::from_secs(5));
 
         // Verify tasks were rescheduled back into the heap
         let h = heap.lock().await;
@@ -716,25 +1534,85 @@ mod tests {
             h.push(Task {
                 last_execution_start: Some(now - Duration::from_secs(101)),
                 details: check1,
+                backoff: Duration::ZERO,
             });
             h.push(Task {
                 last_execution_start: Some(now - Duration::from_secs(200)),
                 details: check2,
+                backoff: Duration::ZERO,
             });
             h.push(Task {
                 last_execution_start: Some(now - Duration::from_secs(100)),
                 details: check3,
+                backoff: Duration::ZERO,
             });
         }
 
-        let (tasks, next_time) =
-            Worker::get_tasks_to_execute_and_reschedule(heap.clone(), now).await;
+        let (tasks, next_time) = Worker::get_tasks_to_execute_and_reschedule(
+            heap.clone(),
+            &CircuitBreaker::disabled(),
+            now,
+        )
+        .await;
 
         assert_eq!(tasks.len(), 2);
         // The next execution is of one of the tasks just executed given its frequency
         assert_eq!(next_time, Some(now + Duration::from_secs(100)));
     }
 
+    #[tokio::test]
+    async fn test_circuit_breaker_backs_off_failing_check() {
+        let heap = Arc::new(Mutex::new(BinaryHeap::new()));
+        let breaker = CircuitBreaker::new(3, Duration::from_secs(240));
+        let check = ServiceCheck {
+            check_frequency_seconds: 30,
+            ..ServiceCheck::example()
+        };
+        let check_id = check.check_id;
+
+        let start = Instant::now();
+        heap.lock().await.push(Task {
+            last_execution_start: None,
+            details: check,
+            backoff: Duration::ZERO,
+        });
+
+        // Runs the schedule until `executions` probes happened, each with the given outcome
+        let run = async |now: &mut Instant, executions: usize, success: bool| {
+            let mut times = Vec::new();
+            while times.len() < executions {
+                let (tasks, _) =
+                    Worker::get_tasks_to_execute_and_reschedule(heap.clone(), &breaker, *now).await;
+                for (task, _) in tasks {
+                    if breaker.record(task.check_id, success, *now) {
+                        Worker::reset_backoff(&mut *heap.lock().await, task.check_id);
+                    }
+                    times.push(now.duration_since(start).as_secs());
+                }
+                // Re-read like the work loop does on queue updates, after a possible reset
+                let heap = heap.lock().await;
+                *now = heap
+                    .peek()
+                    .expect("task scheduled")
+                    .get_next_execution(*now);
+            }
+            times
+        };
+
+        // A long outage: back off after the third failure, up to the max interval
+        let mut now = start;
+        let outage = run(&mut now, 8, false).await;
+        assert_eq!(outage, [0, 30, 60, 120, 240, 480, 720, 960]);
+        assert_eq!(
+            breaker.backoff(check_id, Duration::from_secs(30)).as_secs(),
+            210
+        );
+
+        // The first success restores the frequency
+        let recovery = run(&mut now, 3, true).await;
+        assert_eq!(recovery, [1200, 1230, 1260]);
+    }
+
     #[tokio::test]
     async fn test_task_ordering() {
         let now = Instant::now();
@@ -746,6 +1624,7 @@ mod tests {
                     check_id: uuid!("00000000-0000-0000-0000-000000000001"),
                     ..ServiceCheck::example()
                 },
+                backoff: Duration::ZERO,
             },
             Task {
                 last_execution_start: Some(now - Duration::from_secs(59)),
@@ -754,6 +1633,7 @@ mod tests {
                     check_frequency_seconds: 60,
                     ..ServiceCheck::example()
                 },
+                backoff: Duration::ZERO,
             },
             Task {
                 last_execution_start: Some(now - Duration::from_secs(28)),
@@ -762,6 +1642,7 @@ mod tests {
                     check_frequency_seconds: 30,
                     ..ServiceCheck::example()
                 },
+                backoff: Duration::ZERO,
             },
         ];
 
@@ -806,6 +1687,7 @@ mod tests {
         heap.push(Task {
             last_execution_start: check_last_execution,
             details: check,
+            backoff: Duration::ZERO,
         });
         (check_id, check_last_execution)
     }
@@ -827,7 +1709,13 @@ mod tests {
         updated_check1.check_id = check1_id;
         updated_check1.check_frequency_seconds = 999;
 
-        Worker::update_tasks(&mut heap, &update_list, vec![updated_check1]);
+        Worker::update_tasks(
+            &mut heap,
+            &update_list,
+            vec![updated_check1],
+            Instant::now(),
+            Utc::now(),
+        );
 
         assert_eq!(heap.len(), 2);
 
@@ -842,14 +1730,97 @@ mod tests {
             .find(|t| t.details.check_id == check3_id)
             .unwrap();
 
-        assert_eq!(task1.last_execution_start, check1_last_execution);
+        // The longer frequency doesn't postpone the pending execution
+        assert_eq!(
+            task1.get_theoretical_time(),
+            check1_last_execution.map(|t| t + Duration::from_secs(60))
+        );
         assert_eq!(task1.details.check_frequency_seconds, 999);
         assert_eq!(task3.last_execution_start, check3_last_execution);
     }
 
-    /// Attention: this uses CURRENT_BUCKETS_COUNT env; it supposes it's greater than 3
-    #[tokio::test]
-    async fn test_filter_check_ids_by_range() {
+    /// Updates a single check that last ran `since_last` ago from `old` to `new` frequency,
+    /// returning the delay until its next execution
+    fn delay_after_frequency_change(since_last: u64, old: i32, new: i32) -> Duration {
+        let now = Instant::now();
+        let check = ServiceCheck {
+            check_frequency_seconds: old,
+            ..ServiceCheck::example()
+        };
+        let mut heap = BinaryHeap::from([Task {
+            last_execution_start: Some(now - Duration::from_secs(since_last)),
+            details: check.clone(),
+            backoff: Duration::ZERO,
+        }]);
+
+        let updated = ServiceCheck {
+            check_frequency_seconds: new,
+            ..check.clone()
+        };
+        Worker::update_tasks(
+            &mut heap,
+            &BTreeSet::from([check.check_id]),
+            vec![updated],
+            now,
+            Utc::now(),
+        );
+
+        let task = heap.pop().unwrap();
+        assert_eq!(task.details.check_frequency_seconds, new);
+        task.get_next_execution(now).duration_since(now)
+    }
+
+    #[test]
+    fn test_update_tasks_frequency_increase() {
+        // Ran 20s ago every 30s: still runs in 10s, not 1h after the last run
+        let delay = delay_after_frequency_change(20, 30, 3600);
+        assert_eq!(delay, Duration::from_secs(10));
+    }
+
+    #[test]
+    fn test_update_tasks_frequency_decrease() {
+        // Ran 100s ago every hour, now every 5 minutes: runs 5 minutes after the last run
+        let delay = delay_after_frequency_change(100, 3600, 300);
+        assert_eq!(delay, Duration::from_secs(200));
+
+        // The new period already elapsed: runs within the stagger window instead of at once
+        let window = Duration::from_millis(TAKEOVER_STAGGER_WINDOW_MILLIS);
+        let now = Instant::now();
+        let mut heap = BinaryHeap::new();
+        let checks: Vec<ServiceCheck> = (0..50)
+            .map(|_| ServiceCheck {
+                check_frequency_seconds: 3600,
+                ..ServiceCheck::example()
+            })
+            .collect();
+        for check in &checks {
+            heap.push(Task {
+                last_execution_start: Some(now - Duration::from_secs(1800)),
+                details: check.clone(),
+                backoff: Duration::ZERO,
+            });
+        }
+
+        let update_list = checks.iter().map(|c| c.check_id).collect();
+        let updated = checks
+            .into_iter()
+            .map(|check| ServiceCheck {
+                check_frequency_seconds: 60,
+                ..check
+            })
+            .collect();
+        Worker::update_tasks(&mut heap, &update_list, updated, now, midnight());
+
+        let delays: Vec<Duration> = heap
+            .iter()
+            .map(|task| task.get_next_execution(now).duration_since(now))
+            .collect();
+        assert!(delays.iter().all(|delay| *delay < window));
+        assert!(delays.iter().filter(|delay| delay.is_zero()).count() <= 1);
+    }
+
+    #[test]
+    fn test_filter_check_ids_by_range() {
         let check1_id = uuid!("00000000-0000-0000-0000-000000000001");
         let check2_id = uuid!("00000000-0000-0000-0000-000000000002");
         let check3_id = uuid!("00000000-0000-0000-0000-000000000003");
@@ -860,19 +1831,82 @@ mod tests {
         check_ids.insert(check3_id);
 
         let range1 = RingRange { start: 0, end: 2 };
-        let filtered = Worker::filter_check_ids_by_range(check_ids.clone(), Some(range1));
+        let filtered = Worker::filter_check_ids_by_range(check_ids.clone(), Some(range1), 10);
         assert_eq!(filtered, BTreeSet::from([check1_id]));
 
         let range2 = RingRange { start: 0, end: 5 };
-        let filtered = Worker::filter_check_ids_by_range(check_ids.clone(), Some(range2));
+        let filtered = Worker::filter_check_ids_by_range(check_ids.clone(), Some(range2), 10);
         assert_eq!(filtered, BTreeSet::from([check1_id, check2_id, check3_id]));
 
         let range3 = RingRange { start: 2, end: 4 };
-        let filtered = Worker::filter_check_ids_by_range(check_ids.clone(), Some(range3));
+        let filtered = Worker::filter_check_ids_by_range(check_ids.clone(), Some(range3), 10);
         assert_eq!(filtered, BTreeSet::from([check2_id, check3_id]));
 
         // Test with None range
-        let filtered = Worker::filter_check_ids_by_range(check_ids.clone(), None);
+        let filtered = Worker::filter_check_ids_by_range(check_ids.clone(), None, 10);
         assert!(filtered.is_empty());
     }
+
+    proptest! {
+        #[test]
+        fn prop_filter_partitions_check_ids(
+            ids in proptest::collection::btree_set(any::<u128>(), 0..50),
+            bucket_count in 2..100 as NodePosition,
+            start in 0..100 as NodePosition,
+            end in 0..100 as NodePosition,
+        ) {
+            let (start, end) = (start % bucket_count, end % bucket_count);
+            prop_assume!(start != end);
+
+            let check_ids: BTreeSet<_> = ids.into_iter().map(Uuid::from_u128).collect();
+            let range = RingRange { start, end };
+            let complement = RingRange { start: end, end: start };
+
+            let filter = |range| {
+                Worker::filter_check_ids_by_range(check_ids.clone(), Some(range), bucket_count)
+            };
+            let inside = filter(range);
+            let outside = filter(complement);
+
+            prop_assert!(inside.is_disjoint(&outside));
+            prop_assert_eq!(&inside | &outside, check_ids);
+            for id in &inside {
+                prop_assert!(range.contains(bucket_for_check(*id, bucket_count)));
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_owned_check_ids() {
+        let (range_tx, range_rx) = watch::channel(Some(RingRange { start: 0, end: 2 }));
+        let status = WorkerStatus {
+            region: Region::Fsn1,
+            bucket_count: 10,
+            range_updates: range_rx,
+            next_executions: Default::default(),
+            scheduling_starved: watch::channel(false).1,
+        };
+
+        let check1_id = uuid!("00000000-0000-0000-0000-000000000001");
+        let check2_id = uuid!("00000000-0000-0000-0000-000000000002");
+        {
+            let mut heap = status.next_executions.lock().await;
+            for check_id in [check1_id, check2_id] {
+                heap.push(Task {
+                    last_execution_start: None,
+                    details: ServiceCheck {
+                        check_id,
+                        ..ServiceCheck::example()
+                    },
+                    backoff: Duration::ZERO,
+                });
+            }
+        }
+
+        // Check 2 is scheduled but falls outside of the range
+        assert_eq!(status.owned_check_ids().await, BTreeSet::from([check1_id]));
+
+        range_tx.send_replace(None);
+        assert!(status.owned_check_ids().await.is_empty());
+    }
 }
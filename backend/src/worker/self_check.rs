@@ -0,0 +1,398 @@
+use crate::{
+    clock::SharedClock,
+    database::{Database, preparer::CachedPreparedStatement},
+    eager_env,
+    queries::{
+        authorization::{CheckAccess, grant_check_access},
+        checks::{Check, CheckData, get_check_by_id, update_check},
+        users::get_user_by_id,
+    },
+    regions::Region,
+    worker::{
+        check::{dns::DnsCache, execute::execute_check, save::ResultSaveManager, steps::CheckKind},
+        fetch::{Method, fetch_specific_health_checks},
+    },
+};
+use anyhow::{Result, bail};
+use chrono::{DateTime, Duration, Utc};
+use log::{error, info};
+use std::{
+    collections::{BTreeSet, HashMap},
+    sync::Arc,
+};
+use tokio::sync::watch::Receiver;
+use url::Url;
+use uuid::Uuid;
+
+const SELF_CHECK_TIMEOUT_SECONDS: i32 = 10;
+
+/// Self-checks of nodes not seen for this long are disabled, e.g. after the node was replaced
+/// or its address changed
+const STALE_SELF_CHECK_AFTER: Duration = Duration::hours(1);
+
+static UPSERT_SELF_CHECK_QUERY: CachedPreparedStatement = CachedPreparedStatement::new(
+    "
+    INSERT INTO self_checks (region, node, check_id, last_seen)
+    VALUES (?, ?, ?, ?)
+    ",
+);
+
+static GET_SELF_CHECKS_QUERY: CachedPreparedStatement = CachedPreparedStatement::new(
+    "
+    SELECT node, check_id, last_seen
+    FROM self_checks
+    WHERE region = ?
+    ",
+);
+
+static DELETE_SELF_CHECK_QUERY: CachedPreparedStatement = CachedPreparedStatement::new(
+    "
+    DELETE FROM self_checks
+    WHERE region = ?
+      AND node = ?
+    ",
+);
+
+/// The check a node registers on startup to monitor itself, see `SELF_CHECK_URL`
+#[derive(Debug, Clone)]
+pub struct SelfCheckConfig {
+    pub url: Url,
+    pub frequency_seconds: i32,
+    /// User who sees the check next to their own ones
+    pub viewer: Option<Uuid>,
+}
+
+impl SelfCheckConfig {
+    /// `None` when `SELF_CHECK_URL` is unset
+    pub fn from_env() -> Option<Self> {
+        Some(Self {
+            url: eager_env::self_check_url()?,
+            frequency_seconds: *eager_env::SELF_CHECK_FREQUENCY_SECONDS,
+            viewer: eager_env::self_check_viewer(),
+        })
+    }
+}
+
+/// Id of the self-check of `node` in `region`, the same across restarts
+fn self_check_id(region: Region, node: &str) -> Uuid {
+    let name = format!("self-check/{}/{node}", region.to_identifier());
+    Uuid::new_v5(&Uuid::NAMESPACE_OID, name.as_bytes())
+}
+
+/// Creates the self-check of `node`, or updates it to the current configuration after a restart.
+///
+/// It is a check of `region` pinned to `node`: only that node probes it, see
+/// [`self_check_task_body`], and its results are stored like any other ones, so its results and
+/// graphs show the health of the node. No user creates it: it only appears to the `viewer`,
+/// who can't edit it, and never counts as one of their checks.
+///
+/// Self-checks of the other nodes of the region that went stale are disabled meanwhile, see
+/// [`STALE_SELF_CHECK_AFTER`].
+pub async fn register_self_check(
+    db: &Database,
+    region: Region,
+    node: &str,
+    config: &SelfCheckConfig,
+) -> Result<Check> {
+    let check_id = self_check_id(region, node);
+    let created_at = get_check_by_id(db, check_id)
+        .await?
+        .map_or_else(Utc::now, |check| check.data.created_at);
+
+    let check = Check {
+        check_id,
+        regions: vec![region],
+        data: CheckData {
+            check_name: format!("Self-check {node} ({})", region.to_identifier()),
+            url: config.url.to_string(),
+            http_method: Method::Get,
+            check_frequency_seconds: config.frequency_seconds,
+            timeout_seconds: SELF_CHECK_TIMEOUT_SECONDS,
+            expected_status_codes: 200.into(),
+            request_headers: HashMap::new(),
+            request_body: None,
+            is_enabled: true,
+            created_at,
+            kind: CheckKind::Http,
+            steps: vec![],
+            dns_cache_ttl_seconds: None,
+            proxy: None,
+            conditional: None,
+            geo_assertion: None,
+            fallback_urls: vec![],
+            body_regex: None,
+            min_tls_version: None,
+            decompress_response: false,
+            // Usually the node itself, configured by the operator
+            allow_private_targets: true,
+            connect_timeout_millis: None,
+            degraded_response_time_millis: None,
+            require_agreeing_regions: None,
+            assertions: None,
+            tags: HashMap::new(),
+            store_raw_results: true,
+            metadata: HashMap::from([("node".to_string(), node.to_string())]),
+            retries: 0,
+            retry_delay_ms: 0,
+            created_by: None,
+            created_by_username: None,
+            pinned_node: Some(node.to_string()),
+        },
+    };
+    update_check(db, check.clone()).await?;
+    mark_self_check_seen(db, region, node, check_id, Utc::now()).await?;
+
+    if let Some(viewer) = config.viewer {
+        let Some(user) = get_user_by_id(db, viewer).await? else {
+            bail!("self-check viewer {viewer} not found");
+        };
+        grant_check_access(
+            db,
+            check_id,
+            viewer,
+            &user.username,
+            CheckAccess {
+                can_edit: false,
+                can_see: true,
+            },
+        )
+        .await?;
+    }
+
+    let disabled = disable_stale_self_checks(db, region, Utc::now()).await?;
+    if disabled > 0 {
+        info!("Disabled {disabled} stale self-checks");
+    }
+
+    Ok(check)
+}
+
+async fn mark_self_check_seen(
+    db: &Database,
+    region: Region,
+    node: &str,
+    check_id: Uuid,
+    now: DateTime<Utc>,
+) -> Result<()> {
+    UPSERT_SELF_CHECK_QUERY
+        .execute_unpaged(db, (region.to_identifier(), node, check_id, now))
+        .await?;
+    Ok(())
+}
+
+/// Disables the self-checks of `region` whose node hasn't probed them for
+/// [`STALE_SELF_CHECK_AFTER`], so that they no longer show as monitored. Registering again
+/// enables them back. Returns the number of disabled checks.
+async fn disable_stale_self_checks(
+    db: &Database,
+    region: Region,
+    now: DateTime<Utc>,
+) -> Result<usize> {
+    let self_checks = GET_SELF_CHECKS_QUERY
+        .execute_unpaged(db, (region.to_identifier(),))
+        .await?
+        .into_rows_result()?
+        .rows::<(String, Uuid, DateTime<Utc>)>()?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut disabled = 0;
+    for (node, check_id, last_seen) in self_checks {
+        if now - last_seen < STALE_SELF_CHECK_AFTER {
+            continue;
+        }
+
+        if let Some(mut check) = get_check_by_id(db, check_id).await?
+            && check.data.is_enabled
+        {
+            check.data.is_enabled = false;
+            update_check(db, check).await?;
+            disabled += 1;
+        }
+        DELETE_SELF_CHECK_QUERY
+            .execute_unpaged(db, (region.to_identifier(), &node))
+            .await?;
+    }
+
+    Ok(disabled)
+}
+
+/// Probes the self-check `check_id` of the node at its frequency, forever, whichever node owns
+/// its bucket. Results are saved like those of the ring's checks.
+#[allow(clippy::too_many_arguments)]
+pub async fn self_check_task_body(
+    db: Arc<Database>,
+    check_id: Uuid,
+    region: Region,
+    client: reqwest::Client,
+    dns_cache: Arc<DnsCache>,
+    save_manager: Arc<ResultSaveManager>,
+    probing_enabled: Receiver<bool>,
+    clock: SharedClock,
+) {
+    let check_ids = BTreeSet::from([check_id]);
+    let mut frequency = std::time::Duration::from_secs(60);
+
+    loop {
+        // Fetched on every run, so that changes apply without a broadcast
+        let check = match fetch_specific_health_checks(&db, region, &check_ids).await {
+            Ok(checks) => checks.into_iter().next(),
+            Err(e) => {
+                error!("failed to fetch the self-check {check_id}: {e:?}");
+                None
+            }
+        };
+
+        if let Some(check) = check.filter(|check| check.is_enabled) {
+            frequency = std::time::Duration::from_secs(check.check_frequency_seconds.max(1) as u64);
+
+            if *probing_enabled.borrow() {
+                let saved = match execute_check(
+                    &client,
+                    &dns_cache,
+                    &check,
+                    &eager_env::PROXY_REMOTE_DNS_ALLOWED_HOSTS,
+                )
+                .await
+                {
+                    Ok(result) if check.store_raw_results => save_manager.save(result),
+                    Ok(result) => {
+                        save_manager.aggregate(&result);
+                        Ok(())
+                    }
+                    Err(e) => Err(e),
+                };
+                if let Err(e) = saved {
+                    error!("error executing the self-check {check_id}: {e}");
+                }
+            }
+
+            if let Some(node) = &check.pinned_node
+                && let Err(e) = mark_self_check_seen(&db, region, node, check_id, clock.now()).await
+            {
+                error!("failed to record the self-check {check_id} as seen: {e:?}");
+            }
+        }
+
+        tokio::time::sleep(frequency).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        clock::SystemClock, database::testing::create_test_database,
+        queries::check_results::get_recent_check_results, worker::Worker,
+    };
+    use httpmock::prelude::*;
+    use std::sync::Arc;
+    use tokio::sync::{mpsc, watch};
+
+    #[test]
+    fn test_self_check_id_is_stable() {
+        assert_eq!(
+            self_check_id(Region::Fsn1, "node-a"),
+            self_check_id(Region::Fsn1, "node-a")
+        );
+        assert_ne!(
+            self_check_id(Region::Fsn1, "node-a"),
+            self_check_id(Region::Fsn1, "node-b")
+        );
+        assert_ne!(
+            self_check_id(Region::Fsn1, "node-a"),
+            self_check_id(Region::Hel1, "node-a")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_self_check_produces_results() -> Result<()> {
+        let (session, _keyspace) = create_test_database(None).await?;
+        let session = Arc::new(session);
+
+        let server = MockServer::start();
+        server.mock(|when, then| {
+            when.method(GET).path("/health");
+            then.status(200);
+        });
+        let config = SelfCheckConfig {
+            url: server.url("/health").parse()?,
+            frequency_seconds: 1,
+            viewer: None,
+        };
+
+        let check = register_self_check(&session, Region::Fsn1, "node-a", &config).await?;
+
+        // Registering again after a restart keeps the same check
+        let again = register_self_check(&session, Region::Fsn1, "node-a", &config).await?;
+        assert_eq!(again.check_id, check.check_id);
+        assert_eq!(again.data.created_at, check.data.created_at);
+
+        // No ring range is ever assigned, the node probes its self-check on its own
+        let (_range_tx, range_rx) = watch::channel(None);
+        let (_task_tx, task_rx) = mpsc::unbounded_channel();
+        let (_probing_tx, probing_rx) = watch::channel(true);
+        let worker = Worker::new(
+            session.clone(),
+            SystemClock::shared(),
+            Region::Fsn1,
+            *eager_env::CURRENT_BUCKET_VERSION as i16,
+            *eager_env::CURRENT_BUCKETS_COUNT,
+            range_rx,
+            task_rx,
+            probing_rx,
+        )
+        .await?
+        .with_self_check(check.check_id);
+        let stop_worker = worker.start();
+
+        let mut results = Vec::new();
+        for _ in 0..50 {
+            tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+            results =
+                get_recent_check_results(&session, check.check_id, &[Region::Fsn1], Utc::now(), 10)
+                    .await?;
+            if !results.is_empty() {
+                break;
+            }
+        }
+        stop_worker.await;
+
+        assert!(!results.is_empty(), "the self-check produced no results");
+        assert!(results.iter().all(|result| result.matches_expected));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_stale_self_checks_are_disabled() -> Result<()> {
+        let (session, _keyspace) = create_test_database(None).await?;
+
+        let server = MockServer::start();
+        let config = SelfCheckConfig {
+            url: server.url("/health").parse()?,
+            frequency_seconds: 60,
+            viewer: None,
+        };
+
+        let old = register_self_check(&session, Region::Fsn1, "node-a", &config).await?;
+        let new = register_self_check(&session, Region::Fsn1, "node-b", &config).await?;
+
+        // node-a was replaced and stopped reporting
+        disable_stale_self_checks(
+            &session,
+            Region::Fsn1,
+            Utc::now() + STALE_SELF_CHECK_AFTER + Duration::seconds(1),
+        )
+        .await?;
+
+        let checks = fetch_specific_health_checks(
+            &session,
+            Region::Fsn1,
+            &BTreeSet::from([old.check_id, new.check_id]),
+        )
+        .await?;
+        assert!(checks.iter().all(|check| !check.is_enabled));
+
+        Ok(())
+    }
+}
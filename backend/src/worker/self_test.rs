@@ -0,0 +1,174 @@
+use crate::{
+    eager_env,
+    regions::Region,
+    worker::{
+        check::{
+            dns::DnsCache,
+            execute::{execute_check, probe_client_builder},
+            proxy::HostAllowlist,
+            status::ExpectedStatusCodes,
+            steps::CheckKind,
+        },
+        fetch::{Method, ServiceCheck},
+    },
+};
+use anyhow::{Result, bail};
+use chrono::Utc;
+use log::{error, info, warn};
+use std::{collections::HashMap, time::Duration};
+use tokio::sync::watch;
+use url::Url;
+use uuid::Uuid;
+
+const EGRESS_SELF_TEST_TIMEOUT_SECONDS: i32 = 10;
+/// Delay between attempts of a required egress self-test, until one succeeds
+pub const EGRESS_SELF_TEST_RETRY_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Probes `url` exactly like a check would, from `PROBE_BIND_ADDRESS` too, expecting any `2xx`.
+pub async fn check_egress(url: &Url, region: Region, accept_local: bool) -> Result<()> {
+    let check = ServiceCheck {
+        check_id: Uuid::nil(),
+        region,
+        check_name: "Egress self-test".to_string(),
+        url: url.clone(),
+        http_method: Method::Get,
+        check_frequency_seconds: 0,
+        timeout_seconds: EGRESS_SELF_TEST_TIMEOUT_SECONDS,
+        expected_status_codes: ExpectedStatusCodes::Range { min: 200, max: 299 },
+        request_headers: HashMap::new(),
+        request_body: None,
+        is_enabled: true,
+        created_at: Utc::now(),
+        kind: CheckKind::Http,
+        steps: vec![],
+        dns_cache_ttl_seconds: None,
+        proxy: None,
+        conditional: None,
+        geo_assertion: None,
+        fallback_urls: vec![],
+        body_regex: None,
+        min_tls_version: None,
+        decompress_response: false,
+        allow_private_targets: accept_local,
+        connect_timeout_millis: None,
+        assertions: None,
+        // Never saved
+        store_raw_results: false,
+        retries: 0,
+        retry_delay_ms: 0,
+        pinned_node: None,
+    };
+
+    let client = probe_client_builder(eager_env::probe_bind_address()).build()?;
+    let result = execute_check(
+        &client,
+        &DnsCache::default(),
+        &check,
+        &HostAllowlist::default(),
+    )
+    .await?;
+
+    if !result.matches_expected {
+        bail!(
+            "got {:?} (status {:?}): {}",
+            result.failure_reason,
+            result.status_code,
+            result.failure_detail.unwrap_or_default()
+        );
+    }
+
+    Ok(())
+}
+
+/// Runs the egress self-test, publishing whether the node is ready.
+///
+/// A failure only makes the node not ready when `required`: the test is then retried every
+/// `retry_interval` until it succeeds, with the returned future. Otherwise it is just logged.
+pub fn egress_readiness(
+    url: Url,
+    region: Region,
+    accept_local: bool,
+    required: bool,
+    retry_interval: Duration,
+) -> (watch::Receiver<bool>, impl Future<Output = ()>) {
+    let (sender, receiver) = watch::channel(!required);
+
+    let task = async move {
+        loop {
+            match check_egress(&url, region, accept_local).await {
+                Ok(()) => {
+                    info!("Egress self-test to {url} succeeded");
+                    sender.send_replace(true);
+                    return;
+                }
+                Err(e) if required => {
+                    error!(
+                        "Egress self-test to {url} failed, node is not ready, retrying in {retry_interval:?}: {e:?}"
+                    );
+                }
+                Err(e) => {
+                    warn!("Egress self-test to {url} failed: {e:?}");
+                    return;
+                }
+            }
+            tokio::time::sleep(retry_interval).await;
+        }
+    };
+
+    (receiver, task)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use httpmock::prelude::*;
+    use std::net::TcpListener;
+
+    const RETRY_INTERVAL: Duration = Duration::from_millis(10);
+
+    #[tokio::test]
+    async fn test_egress_readiness() {
+        let server = MockServer::start();
+        let mut failing = server.mock(|when, then| {
+            when.method(GET).path("/generate_204");
+            then.status(503);
+        });
+        let url: Url = server.url("/generate_204").parse().unwrap();
+        let (mut ready, task) = egress_readiness(url, Region::Fsn1, true, true, RETRY_INTERVAL);
+        let task = tokio::spawn(task);
+
+        // Not ready while it keeps failing
+        while failing.calls_async().await < 2 {
+            tokio::time::sleep(RETRY_INTERVAL).await;
+        }
+        assert!(!*ready.borrow());
+
+        // Ready once it succeeds, which ends the retries
+        failing.delete();
+        let mock = server.mock(|when, then| {
+            when.method(GET).path("/generate_204");
+            then.status(204);
+        });
+        tokio::time::timeout(Duration::from_secs(5), ready.wait_for(|ready| *ready))
+            .await
+            .unwrap()
+            .unwrap();
+        task.await.unwrap();
+        mock.assert();
+
+        // Nothing listens on a port that was just released
+        let port = TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let blocked: Url = format!("http://127.0.0.1:{port}/").parse().unwrap();
+        assert!(check_egress(&blocked, Region::Fsn1, true).await.is_err());
+
+        // Only logged when not required
+        let (ready, task) = egress_readiness(blocked, Region::Fsn1, true, false, RETRY_INTERVAL);
+        assert!(*ready.borrow());
+        task.await;
+        assert!(*ready.borrow());
+    }
+}
@@ -0,0 +1,92 @@
+use crate::eager_env;
+use log::{error, info};
+use std::{
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+use tokio::sync::watch::{self, Receiver, Sender};
+
+/// Detects sustained scheduling lag, i.e. probes starting later than scheduled because the node
+/// can't keep up. The lag of a probe is measured once it gets its permit, so time spent waiting
+/// for the concurrency limit counts.
+///
+/// Once the lag has stayed above `threshold` for `window`, an error is logged and the starved
+/// flag is raised until a probe starts on time again.
+pub struct StarvationWatchdog {
+    threshold: Duration,
+    window: Duration,
+    lagging_since: Mutex<Option<Instant>>,
+    starved: Sender<bool>,
+}
+
+impl StarvationWatchdog {
+    pub fn new(threshold: Duration, window: Duration) -> Self {
+        Self {
+            threshold,
+            window,
+            lagging_since: Default::default(),
+            starved: watch::channel(false).0,
+        }
+    }
+
+    pub fn from_env() -> Self {
+        Self::new(
+            Duration::from_millis(*eager_env::SCHEDULING_LAG_THRESHOLD_MILLIS),
+            Duration::from_secs(*eager_env::SCHEDULING_LAG_WINDOW_SECONDS),
+        )
+    }
+
+    pub fn subscribe(&self) -> Receiver<bool> {
+        self.starved.subscribe()
+    }
+
+    /// Records the lag of a probe that just got its permit.
+    pub fn observe(&self, lag: Duration, now: Instant) {
+        let mut lagging_since = self.lagging_since.lock().expect("not poisoned");
+        if lag <= self.threshold {
+            *lagging_since = None;
+            if self.starved.send_replace(false) {
+                info!("Check scheduling caught up, lag {lag:?}");
+            }
+            return;
+        }
+
+        let lagging_since = *lagging_since.get_or_insert(now);
+
+        if now.duration_since(lagging_since) >= self.window && !*self.starved.borrow() {
+            error!(
+                "Check scheduling starved: probes started more than {:?} late for over {:?}, latest lag {lag:?}",
+                self.threshold, self.window
+            );
+            self.starved.send_replace(true);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fires_after_window_and_recovers() {
+        let watchdog = StarvationWatchdog::new(Duration::from_secs(5), Duration::from_secs(30));
+        let starved = watchdog.subscribe();
+        let start = Instant::now();
+
+        watchdog.observe(Duration::from_secs(10), start);
+        watchdog.observe(Duration::from_secs(10), start + Duration::from_secs(29));
+        assert!(!*starved.borrow());
+
+        // A single probe on time restarts the window
+        watchdog.observe(Duration::from_secs(1), start + Duration::from_secs(30));
+        watchdog.observe(Duration::from_secs(10), start + Duration::from_secs(31));
+        watchdog.observe(Duration::from_secs(10), start + Duration::from_secs(60));
+        assert!(!*starved.borrow());
+
+        watchdog.observe(Duration::from_secs(10), start + Duration::from_secs(61));
+        assert!(*starved.borrow());
+
+        watchdog.observe(Duration::ZERO, start + Duration::from_secs(62));
+        assert!(!*starved.borrow());
+    }
+}
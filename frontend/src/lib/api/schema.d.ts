@@ -60,7 +60,7 @@ export interface paths {
         post?: never;
         /**
          * Delete check
-         * @description Deletes a check from all regions. User must have edit access to the check.
+         * @description Deletes a check from all regions, along with its annotations. User must have edit access to the check.
          */
         delete: operations["deleteCheck"];
         options?: never;
@@ -257,14 +257,41 @@ export interface components {
             can_edit: boolean;
             can_see: boolean;
         };
+        /** Note left on a time range of a check, e.g. a deploy or a maintenance window */
+        CheckAnnotation: {
+            /** Format: uuid */
+            annotation_id: string;
+            /** Format: uuid */
+            author_id: string;
+            author_username: string;
+            /** Format: uuid */
+            check_id: string;
+            /** Format: date-time */
+            created_at: string;
+            /**
+             * End of the annotated range, included. Equal to `starts_at` for a single instant
+             * Format: date-time
+             */
+            ends_at: string;
+            /**
+             * Start of the annotated range, included
+             * Format: date-time
+             */
+            starts_at: string;
+            text: string;
+        };
         CheckData: {
             /** Format: int32 */
             check_frequency_seconds: number;
             check_name: string;
             /** Format: date-time */
             created_at: string;
-            /** Format: int32 */
-            expected_status_code: number;
+            /**
+             * Accepted status codes, a list or an inclusive range. `0` in a list accepts any response,
+             * only connection errors and timeouts fail. The former `expected_status_code` is still
+             * accepted, as a one-element list
+             */
+            expected_status_codes: components["schemas"]["ExpectedStatusCodes"];
             http_method: components["schemas"]["Method"];
             is_enabled: boolean;
             request_body?: string | null;
@@ -280,6 +307,18 @@ export interface components {
             password: string;
             username: string;
         };
+        /**
+         * Status codes a check accepts, a list or an inclusive range.
+         *
+         * A single code is accepted too, as a one-element list, so that the former
+         * `expected_status_code` still deserializes. `0` in a list accepts any response.
+         */
+        ExpectedStatusCodes: number[] | {
+            /** Format: int32 */
+            max: number;
+            /** Format: int32 */
+            min: number;
+        };
         /** @enum {string} */
         GraphGranularity: "Hourly" | "Daily";
         LoginRequest: {
@@ -288,10 +327,29 @@ export interface components {
         };
         /** @enum {string} */
         Method: "GET" | "POST" | "PUT" | "DELETE" | "HEAD";
+        MetricsGraph: {
+            /**
+             * Annotations of the check overlapping the window, ordered by start, to be placed by their
+             * own `starts_at` and `ends_at` rather than by point
+             */
+            annotations: components["schemas"]["CheckAnnotation"][];
+            /** Points of the graph, sorted by date. Dates without results have no point */
+            dates: components["schemas"]["MetricsResponseDate"][];
+        };
         MetricsResponse: components["schemas"]["MetricsSummary"] & {
-            by_region: {
-                [key: string]: components["schemas"]["MetricsSummary"];
+            /**
+             * One entry per requested region, in the requested order. Regions without results in the
+             * window are included, with `has_data` unset
+             */
+            by_region: components["schemas"]["RegionMetricsSummary"][];
+            errors: string[];
+            /** `metadata` of the check, for clients to correlate the metrics with their systems */
+            metadata?: {
+                [key: string]: string;
             };
+            /** Set when some results couldn't be read: metrics only cover the remaining ones */
+            partial: boolean;
+            quorum: components["schemas"]["QuorumStatus"];
         };
         MetricsResponseDate: {
             by_region: {
@@ -299,27 +357,77 @@ export interface components {
             };
             /** Format: date-time */
             date: string;
+            /** Why results of this date couldn't be read, when computed from raw results */
+            errors?: string[];
+            /** Set when some results of this date couldn't be read */
+            partial: boolean;
         };
         MetricsSummary: {
+            /**
+             * `None` when no result in the window recorded a response size
+             * Format: int64
+             */
+            avg_response_size_bytes?: number | null;
             /** Format: int64 */
             avg_response_time_micros: number;
+            /**
+             * Average time to the response headers, in `response_time_unit`. The `*_response_time_micros`
+             * fields also include the download of the body. `None` when no result in the window
+             * recorded it
+             * Format: int64
+             */
+            avg_ttfb_micros?: number | null;
+            /**
+             * Share of successful results, regardless of how they are spaced
+             * Format: float
+             */
+            count_weighted_uptime_percent: number;
             /** Format: int32 */
             failed_checks: number;
+            /**
+             * `false` when the check has no results in the window, e.g. it never ran yet.
+             * Uptimes and response times are then `0` rather than measured
+             */
+            has_data: boolean;
+            /** Format: int64 */
+            max_response_size_bytes?: number | null;
             /** Format: int64 */
             max_response_time_micros: number;
             /** Format: int64 */
             min_response_time_micros: number;
+            /**
+             * `None` when the window has fewer than `PERCENTILE_MIN_SAMPLES` results, too few for
+             * percentiles to mean anything
+             * Format: int64
+             */
+            p50_response_time_micros?: number | null;
             /** Format: int64 */
-            p50_response_time_micros: number;
-            /** Format: int64 */
-            p95_response_time_micros: number;
+            p95_response_time_micros?: number | null;
             /** Format: int64 */
-            p99_response_time_micros: number;
+            p99_response_time_micros?: number | null;
+            /** Unit of the `*_response_time_micros` fields, despite their name */
+            response_time_unit?: components["schemas"]["ResponseTimeUnit"];
+            /**
+             * Number of results per HTTP status code. Results without a response, e.g. timeouts,
+             * are not counted
+             */
+            status_code_counts?: {
+                [key: string]: number;
+            };
             /** Format: int32 */
             successful_checks: number;
+            time_in_state?: null | components["schemas"]["TimeInState"];
+            /**
+             * Each result counts for the time until the next one
+             * Format: float
+             */
+            time_weighted_uptime_percent: number;
             /** Format: int32 */
             total_checks: number;
-            /** Format: float */
+            /**
+             * Uptime with the requested weighting, time-weighted by default
+             * Format: float
+             */
             uptime_percent: number;
         };
         PublicUser: {
@@ -327,8 +435,45 @@ export interface components {
             user_id: string;
             username: string;
         };
+        /**
+         * State of a check combined across regions
+         * @enum {string}
+         */
+        QuorumState: "up" | "down" | "unknown";
+        /**
+         * Status of a check at the end of the window, from the latest result of each region.
+         *
+         * The check is down only when at least `required_agreeing_regions` regions report it down.
+         */
+        QuorumStatus: {
+            /** Format: int32 */
+            down_regions: number;
+            /** Format: int32 */
+            reporting_regions: number;
+            /** Format: int32 */
+            required_agreeing_regions: number;
+            state: components["schemas"]["QuorumState"];
+        };
         /** @enum {string} */
         Region: "Fsn1" | "Hel1" | "Nbg1";
+        /** Metrics of a single region */
+        RegionMetricsSummary: components["schemas"]["MetricsSummary"] & {
+            region: components["schemas"]["Region"];
+        };
+        /** @enum {string} */
+        ResponseTimeUnit: "micros" | "millis";
+        /** Time spent in each state, each result's state lasting until the next result */
+        TimeInState: {
+            /**
+             * Up, but slower than the check's `degraded_response_time_millis`
+             * Format: int64
+             */
+            degraded_seconds: number;
+            /** Format: int64 */
+            down_seconds: number;
+            /** Format: int64 */
+            up_seconds: number;
+        };
         Vec: ({
             ServiceCheckMutation: {
                 /** Format: uuid */
@@ -676,7 +821,7 @@ export interface operations {
                     [name: string]: unknown;
                 };
                 content: {
-                    "application/json": components["schemas"]["MetricsResponseDate"][];
+                    "application/json": components["schemas"]["MetricsGraph"];
                 };
             };
             /** @description Invalid query parameters */
@@ -3,7 +3,7 @@ import type { components, operations } from '$lib/api/schema';
 export type MetricsResponse =
     operations['getCheckMetrics']['responses']['200']['content']['application/json'];
 
-export type SingleMetrics = MetricsResponse['by_region'][string];
+export type SingleMetrics = components['schemas']['MetricsSummary'];
 
 export type CheckWithMetrics = components['schemas']['CheckWithAccess'] & {
     metrics?: MetricsResponse;
@@ -14,4 +14,4 @@ export type Region = components['schemas']['Region'];
 export type GraphGranularity = components['schemas']['GraphGranularity'];
 
 export type GraphData =
-    operations['getCheckMetricsGraph']['responses']['200']['content']['application/json'];
+    operations['getCheckMetricsGraph']['responses']['200']['content']['application/json']['dates'];
@@ -1,3 +1,4 @@
+import type { components } from './api/schema';
 import type { GraphGranularity } from './types';
 import { clsx, type ClassValue } from 'clsx';
 import { twMerge } from 'tailwind-merge';
@@ -26,6 +27,25 @@ export function getUserColor(userId: string): string {
     return `hsl(${hue}, 70%, 60%)`;
 }
 
+type ExpectedStatusCodes = components['schemas']['ExpectedStatusCodes'];
+
+/** Formats expected status codes as `200, 204` or `200-299`, as read by `parseStatusCodes` */
+export function formatStatusCodes(codes: ExpectedStatusCodes): string {
+    return Array.isArray(codes) ? codes.join(', ') : `${codes.min}-${codes.max}`;
+}
+
+export function parseStatusCodes(text: string): ExpectedStatusCodes {
+    const [min, max] = text.split('-').map((code) => parseInt(code.trim()));
+    if (max !== undefined) {
+        return { min, max };
+    }
+    return text
+        .split(',')
+        .map((code) => code.trim())
+        .filter((code) => code !== '')
+        .map((code) => parseInt(code));
+}
+
 export function formatMicrosToMs(micros: number): number {
     return Math.floor(micros / 1000);
 }
@@ -44,5 +44,5 @@ export const load: PageLoad = async ({ params, fetch }) => {
 
     const [defaultGraph, metricsResult] = await Promise.all([defaultGraphPromise, metricsPromise]);
 
-    return { check, metrics: metricsResult.data, defaultGraph: defaultGraph.data };
+    return { check, metrics: metricsResult.data, defaultGraph: defaultGraph.data?.dates };
 };